@@ -20,6 +20,7 @@
 use deps;
 use deps::bitcoin::util::hash::Sha256dHash as BitcoinSha256dHash;
 
+use std::cmp;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::mpsc::sync_channel;
@@ -65,6 +66,7 @@ use chainstate::burn::operations::{
 use chainstate::burn::{BlockSnapshot, Opcodes};
 
 use chainstate::coordinator::comm::CoordinatorChannels;
+use chainstate::coordinator::BurnchainBlockFetcher;
 
 use chainstate::stacks::index::TrieHash;
 use chainstate::stacks::StacksAddress;
@@ -354,7 +356,7 @@ impl Burnchain {
             }
         };
 
-        Ok(Burnchain {
+        let burnchain = Burnchain {
             peer_version: PEER_VERSION,
             network_id: params.network_id,
             chain_name: params.chain_name.clone(),
@@ -365,7 +367,64 @@ impl Burnchain {
             first_block_height: params.first_block_height,
             first_block_hash: params.first_block_hash.clone(),
             pox_constants,
-        })
+        };
+        burnchain.validate().map_err(|e| {
+            error!(
+                "Invalid burnchain parameters for {}/{}: {:?}",
+                chain_name, network_name, &e
+            );
+            e
+        })?;
+        Ok(burnchain)
+    }
+
+    /// Check that the PoX constants are consistent with each other and with the first block
+    /// height.  `Burnchain::new()` does this; code that changes the parameters afterwards should
+    /// call it again.
+    pub fn validate(&self) -> Result<(), burnchain_error> {
+        let reward_cycle_length = self.pox_constants.reward_cycle_length;
+        let prepare_length = self.pox_constants.prepare_length;
+        let anchor_threshold = self.pox_constants.anchor_threshold;
+
+        if prepare_length == 0 {
+            return Err(burnchain_error::InvalidConfiguration(
+                "prepare_length must be positive".to_string(),
+            ));
+        }
+        if anchor_threshold <= prepare_length / 2 || anchor_threshold > prepare_length {
+            return Err(burnchain_error::InvalidConfiguration(format!(
+                "anchor_threshold ({}) must be more than half of and at most prepare_length ({})",
+                anchor_threshold, prepare_length
+            )));
+        }
+
+        if self.pox_constants.pox_rejection_fraction == 0 {
+            return Err(burnchain_error::InvalidConfiguration(
+                "pox_rejection_fraction must be positive".to_string(),
+            ));
+        }
+
+        // the first reward cycle's prepare phase must begin after the block that follows the
+        // first block (which is never a reward cycle start), or there is nothing to choose its
+        // anchor block from
+        let first_cycle_start = self
+            .first_block_height
+            .checked_add(reward_cycle_length as u64 + 1)
+            .ok_or_else(|| {
+                burnchain_error::InvalidConfiguration(format!(
+                    "first_block_height ({}) is too large",
+                    self.first_block_height
+                ))
+            })?;
+        let first_prepare_start = first_cycle_start.saturating_sub(prepare_length as u64);
+        if first_prepare_start <= self.first_block_height + 1 {
+            return Err(burnchain_error::InvalidConfiguration(format!(
+                "the first prepare phase would begin at {}, which is not after first_block_height ({}) + 1: prepare_length ({}) must be less than reward_cycle_length ({})",
+                first_prepare_start, self.first_block_height, prepare_length, reward_cycle_length
+            )));
+        }
+
+        Ok(())
     }
 
     pub fn is_reward_cycle_start(&self, block_height: u64) -> bool {
@@ -395,6 +454,27 @@ impl Burnchain {
         )
     }
 
+    /// Determine which reward cycle this burn block height lives in.
+    /// `burn_block_height` must not be below the first block height.
+    pub fn reward_cycle_of_height(&self, burn_block_height: u64) -> u64 {
+        self.block_height_to_reward_cycle(burn_block_height)
+            .expect("FATAL: burn block height is below the first block height")
+    }
+
+    /// Determine the lowest burn block height that lives in the given reward cycle.
+    /// Note that this is one block before `reward_cycle_to_block_height()`, which is where the
+    /// reward cycle's PoX processing begins.
+    pub fn reward_cycle_start_height(&self, reward_cycle: u64) -> u64 {
+        self.first_block_height + reward_cycle * (self.pox_constants.reward_cycle_length as u64)
+    }
+
+    /// Determine the minimum amount of uSTX that can be stacked if the liquid supply were
+    /// `liquid_ustx`.  This is the same value the PoX contract's `(get-stacking-minimum)` gives,
+    /// but does not need any chain state.
+    pub fn stacking_minimum_for_supply(&self, liquid_ustx: u128) -> u128 {
+        liquid_ustx / self.pox_constants.stacking_threshold()
+    }
+
     #[cfg(test)]
     pub fn default_unittest(
         first_block_height: u64,
@@ -1254,6 +1334,66 @@ impl Burnchain {
     }
 }
 
+/// How many headers `IndexerBlockFetcher` reads at a time while looking for a block's header
+const BLOCK_FETCHER_HEADERS_BATCH: u64 = 2016;
+
+/// Fetches individual burnchain blocks through a burnchain indexer, so the chains coordinator can
+///  fill in gaps in the burnchain DB.  The block's header must already be in the indexer's
+///  headers DB.
+pub struct IndexerBlockFetcher<I: BurnchainIndexer> {
+    indexer: I,
+    first_block_height: u64,
+    burnchain_db_path: String,
+}
+
+impl<I: BurnchainIndexer> IndexerBlockFetcher<I> {
+    pub fn new(burnchain: &Burnchain, indexer: I) -> IndexerBlockFetcher<I> {
+        IndexerBlockFetcher {
+            indexer,
+            first_block_height: burnchain.first_block_height,
+            burnchain_db_path: burnchain.get_burnchaindb_path(),
+        }
+    }
+}
+
+impl<I: BurnchainIndexer> BurnchainBlockFetcher for IndexerBlockFetcher<I> {
+    fn fetch_burnchain_block(&self, hash: &BurnchainHeaderHash) -> Result<(), burnchain_error> {
+        // missing blocks are usually recent, so search the headers from the highest one down
+        let mut end_block = self.indexer.get_headers_height()?;
+        let header = loop {
+            if end_block <= self.first_block_height {
+                warn!("No header for burnchain block {}", hash);
+                return Err(burnchain_error::MissingHeaders);
+            }
+            let start_block = cmp::max(
+                self.first_block_height,
+                end_block.saturating_sub(BLOCK_FETCHER_HEADERS_BATCH),
+            );
+            let headers = self.indexer.read_headers(start_block, end_block)?;
+            let header_opt = headers.into_iter().find(|hdr| {
+                BurnchainHeaderHash::from_bitcoin_hash(&BitcoinSha256dHash(hdr.header_hash()))
+                    == *hash
+            });
+            if let Some(header) = header_opt {
+                break header;
+            }
+            end_block = start_block;
+        };
+
+        debug!(
+            "Fetch burnchain block {} at height {}",
+            hash,
+            header.height()
+        );
+        let ipc_block = self.indexer.downloader().download(&header)?;
+        let burnchain_block = self.indexer.parser().parse(&ipc_block)?;
+
+        let mut burnchain_db = BurnchainDB::open(&self.burnchain_db_path, true)?;
+        Burnchain::process_block(&mut burnchain_db, &burnchain_block)?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -2531,6 +2671,94 @@ pub mod tests {
     // TODO; test that all but the first of the block commits committing to the same key are
     // dropped
     // TODO: test that we can get the histories of all Stacks block headers from different fork segments
+    #[test]
+    fn test_reward_cycle_of_height() {
+        let first_block_height = 120;
+        let mut burnchain =
+            Burnchain::default_unittest(first_block_height, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 10;
+
+        // first block
+        assert_eq!(burnchain.reward_cycle_of_height(first_block_height), 0);
+        assert_eq!(burnchain.reward_cycle_start_height(0), first_block_height);
+
+        // mid-cycle
+        assert_eq!(burnchain.reward_cycle_of_height(first_block_height + 15), 1);
+        assert_eq!(burnchain.reward_cycle_of_height(first_block_height + 25), 2);
+
+        // cycle boundary
+        assert_eq!(burnchain.reward_cycle_of_height(first_block_height + 19), 1);
+        assert_eq!(burnchain.reward_cycle_of_height(first_block_height + 20), 2);
+        assert_eq!(
+            burnchain.reward_cycle_start_height(2),
+            first_block_height + 20
+        );
+        assert_eq!(
+            burnchain.reward_cycle_start_height(2) + 1,
+            burnchain.reward_cycle_to_block_height(2)
+        );
+
+        for height in first_block_height..(first_block_height + 50) {
+            let reward_cycle = burnchain.reward_cycle_of_height(height);
+            assert!(burnchain.reward_cycle_start_height(reward_cycle) <= height);
+            assert!(burnchain.reward_cycle_start_height(reward_cycle + 1) > height);
+        }
+    }
+
+    #[test]
+    fn test_validate_burnchain() {
+        let mut burnchain = Burnchain::default_unittest(120, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.validate().unwrap();
+
+        burnchain.pox_constants = PoxConstants::new(5, 3, 3, 25);
+        burnchain.validate().unwrap();
+
+        // prepare phase as long as the reward cycle
+        burnchain.pox_constants = PoxConstants::new(5, 5, 3, 25);
+        match burnchain.validate() {
+            Err(burnchains::Error::InvalidConfiguration(_)) => {}
+            res => panic!("Expected InvalidConfiguration, got {:?}", res),
+        }
+
+        // prepare phase longer than the reward cycle
+        burnchain.pox_constants = PoxConstants::new(5, 6, 4, 25);
+        assert!(burnchain.validate().is_err());
+
+        // empty prepare phase
+        burnchain.pox_constants = PoxConstants::new(5, 0, 1, 25);
+        assert!(burnchain.validate().is_err());
+
+        // unreachable anchor threshold
+        burnchain.pox_constants = PoxConstants::new(5, 3, 4, 25);
+        assert!(burnchain.validate().is_err());
+
+        // no rejection fraction
+        burnchain.pox_constants = PoxConstants::new(5, 3, 3, 0);
+        assert!(burnchain.validate().is_err());
+
+        // reward cycle boundaries would overflow
+        burnchain.pox_constants = PoxConstants::new(5, 3, 3, 25);
+        burnchain.first_block_height = u64::max_value() - 2;
+        assert!(burnchain.validate().is_err());
+    }
+
+    #[test]
+    fn test_stacking_minimum_for_supply() {
+        let burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+
+        assert_eq!(burnchain.stacking_minimum_for_supply(0), 0);
+        assert_eq!(burnchain.stacking_minimum_for_supply(19999), 0);
+        assert_eq!(burnchain.stacking_minimum_for_supply(20000), 1);
+
+        // floor(total-liquid-ustx / 20000), as in the boot tests
+        for total_liquid_ustx in [1_000_000_000u128, 40_000_000_000_000_000, 123_456_789].iter() {
+            assert_eq!(
+                burnchain.stacking_minimum_for_supply(*total_liquid_ustx),
+                total_liquid_ustx / 20000
+            );
+        }
+    }
+
     // TODO: test top-level sync with a burn chain reorg
     // -- make sure the chain can switch from fork A to fork B back to fork A safely.
     // TODO: test that only relevant user burns get stored in a burn distribution, and that they're
@@ -271,6 +271,10 @@ pub struct Burnchain {
     pub pox_constants: PoxConstants,
 }
 
+/// The PoX contract's stacking minimum is the liquid supply divided by this, as in its
+/// `(get-stacking-minimum)` function.
+pub const POX_STACKING_THRESHOLD: u128 = 20000;
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct PoxConstants {
     /// the length (in burn blocks) of the reward cycle
@@ -315,6 +319,19 @@ impl PoxConstants {
     pub fn testnet_default() -> PoxConstants {
         PoxConstants::new(120, 30, 20, 3333333333333333) // total liquid supply is 40000000000000000 µSTX
     }
+
+    /// The most entries a reward cycle's reward set can have before we consider the PoX
+    /// contract state to be bogus and refuse to decode it.  Every entry stacks at least the
+    /// stacking minimum (the liquid supply divided by the stacking threshold), so there can be
+    /// no more entries than the stacking threshold.
+    pub fn max_reward_set_size(&self) -> u128 {
+        self.stacking_threshold()
+    }
+
+    /// The divisor of the liquid supply that gives the minimum amount of uSTX that can be stacked
+    pub fn stacking_threshold(&self) -> u128 {
+        POX_STACKING_THRESHOLD
+    }
 }
 
 /// Structure for encoding our view of the network
@@ -377,6 +394,8 @@ pub enum Error {
     UnknownBlock(BurnchainHeaderHash),
     NonCanonicalPoxId(PoxId, PoxId),
     CoordinatorClosed,
+    /// Burnchain parameters are inconsistent
+    InvalidConfiguration(String),
 }
 
 impl fmt::Display for Error {
@@ -401,6 +420,9 @@ impl fmt::Display for Error {
                 parent, child
             ),
             Error::CoordinatorClosed => write!(f, "ChainsCoordinator channel hung up"),
+            Error::InvalidConfiguration(ref msg) => {
+                write!(f, "Invalid burnchain configuration: {}", msg)
+            }
         }
     }
 }
@@ -423,6 +445,7 @@ impl error::Error for Error {
             Error::UnknownBlock(_) => None,
             Error::NonCanonicalPoxId(_, _) => None,
             Error::CoordinatorClosed => None,
+            Error::InvalidConfiguration(_) => None,
         }
     }
 }
@@ -793,6 +816,15 @@ pub mod test {
             }
         }
 
+        pub fn from_u64(seed: u64) -> TestMinerFactory {
+            let mut bytes = [0u8; 32];
+            (&mut bytes[0..8]).copy_from_slice(&seed.to_be_bytes());
+            TestMinerFactory {
+                key_seed: bytes,
+                next_miner_id: 1,
+            }
+        }
+
         pub fn next_private_key(&mut self) -> StacksPrivateKey {
             let h = Sha256Sum::from_data(&self.key_seed);
             self.key_seed.copy_from_slice(h.as_bytes());
@@ -1192,6 +1224,37 @@ pub mod test {
         }
     }
 
+    /// Evaluate a sortition over `ops` on top of `parent_snapshot`, without going through a
+    /// burnchain controller or coordinator.  The burnchain block header is derived from the
+    /// parent snapshot and `fork_id` the same way `TestBurnchainBlock` derives it, so ops built
+    /// with a `TestBurnchainBlock` in the same fork will line up with it.
+    /// Returns the new snapshot and the ops that were accepted into it; any candidate op not in
+    /// `accepted_ops` was rejected.
+    pub fn simulate_sortition(
+        db: &mut SortitionDB,
+        burnchain: &Burnchain,
+        parent_snapshot: &BlockSnapshot,
+        fork_id: u64,
+        ops: Vec<BlockstackOperationType>,
+    ) -> (BlockSnapshot, BurnchainStateTransitionOps) {
+        let block_hash = BurnchainHeaderHash::from_test_data(
+            parent_snapshot.block_height + 1,
+            &parent_snapshot.index_root,
+            fork_id,
+        );
+        let header = BurnchainBlockHeader::from_parent_snapshot(
+            parent_snapshot,
+            block_hash,
+            ops.len() as u64,
+        );
+
+        let (snapshot, transition) = db
+            .evaluate_sortition(&header, ops, burnchain, &parent_snapshot.sortition_id, None)
+            .unwrap();
+
+        (snapshot, BurnchainStateTransitionOps::from(transition))
+    }
+
     fn process_next_sortition(
         node: &mut TestBurnchainNode,
         fork: &mut TestBurnchainFork,
@@ -1295,6 +1358,85 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn simulate_sortition_accepts_valid_commit_only() {
+        let mut node = TestBurnchainNode::new();
+        let mut miner_factory = TestMinerFactory::new();
+
+        let mut miners = vec![];
+        for _i in 0..2 {
+            miners.push(miner_factory.next_miner(
+                &node.burnchain,
+                1,
+                1,
+                AddressHashMode::SerializeP2PKH,
+            ));
+        }
+
+        let first_snapshot = SortitionDB::get_first_block_snapshot(node.sortdb.conn()).unwrap();
+        let mut fork = TestBurnchainFork::new(
+            first_snapshot.block_height,
+            &first_snapshot.burn_header_hash,
+            &first_snapshot.index_root,
+            0,
+        );
+
+        // register a key for each miner
+        let block_hashes = vec![BlockHeaderHash([0x01; 32]), BlockHeaderHash([0x02; 32])];
+        let (_, prev_keys, _, _) =
+            process_next_sortition(&mut node, &mut fork, &mut miners, &vec![], &block_hashes);
+
+        let mut block = {
+            let ic = node.sortdb.index_conn();
+            fork.next_block(&ic)
+        };
+
+        let (valid_commit, invalid_commit) = {
+            let ic = node.sortdb.index_conn();
+            let valid_commit = block.add_leader_block_commit(
+                &ic,
+                &mut miners[0],
+                &BlockHeaderHash([0x11; 32]),
+                1000,
+                &prev_keys[0],
+                None,
+                None,
+            );
+
+            // no burn, so this commit must be rejected
+            let invalid_commit = block.add_leader_block_commit(
+                &ic,
+                &mut miners[1],
+                &BlockHeaderHash([0x22; 32]),
+                0,
+                &prev_keys[1],
+                None,
+                None,
+            );
+            (valid_commit, invalid_commit)
+        };
+
+        let (snapshot, transition_ops) = simulate_sortition(
+            &mut node.sortdb,
+            &node.burnchain,
+            &block.parent_snapshot,
+            block.fork_id,
+            block.txs.clone(),
+        );
+
+        assert_eq!(snapshot.block_height, block.block_height);
+        assert!(snapshot.sortition);
+        assert_eq!(snapshot.winning_block_txid, valid_commit.txid);
+
+        let accepted_txids: Vec<Txid> = transition_ops
+            .accepted_ops
+            .iter()
+            .map(|op| op.txid())
+            .collect();
+        assert!(accepted_txids.contains(&valid_commit.txid));
+        assert!(!accepted_txids.contains(&invalid_commit.txid));
+    }
+
     #[test]
     fn mine_10_stacks_blocks_1_fork() {
         let mut node = TestBurnchainNode::new();
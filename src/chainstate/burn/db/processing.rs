@@ -110,20 +110,16 @@ impl<'a> SortitionHandleTx<'a> {
         Ok(ret_filtered)
     }
 
-    /// Process all block's checked transactions
-    /// * make the burn distribution
-    /// * insert the ones that went into the burn distribution
-    /// * snapshot the block and run the sortition
-    /// * return the snapshot (and sortition results)
-    fn process_checked_block_ops(
+    /// Make the burn distribution from a block's checked transactions, and use it to snapshot the
+    /// block and run the sortition.  Nothing is stored.
+    fn snapshot_checked_block_ops(
         &mut self,
         burnchain: &Burnchain,
         parent_snapshot: &BlockSnapshot,
         block_header: &BurnchainBlockHeader,
         this_block_ops: &Vec<BlockstackOperationType>,
-        next_pox_info: Option<RewardCycleInfo>,
+        next_pox_info: Option<&RewardCycleInfo>,
         parent_pox: PoxId,
-        reward_info: Option<&RewardSetInfo>,
     ) -> Result<(BlockSnapshot, BurnchainStateTransition), BurnchainError> {
         let this_block_height = block_header.block_height;
         let this_block_hash = block_header.block_hash.clone();
@@ -142,7 +138,7 @@ impl<'a> SortitionHandleTx<'a> {
             .collect();
 
         let mut next_pox = parent_pox;
-        if let Some(ref next_pox_info) = next_pox_info {
+        if let Some(next_pox_info) = next_pox_info {
             if next_pox_info.is_reward_info_known() {
                 info!(
                     "Begin reward-cycle sortition with present anchor block={:?}",
@@ -161,7 +157,7 @@ impl<'a> SortitionHandleTx<'a> {
         let next_sortition_id = SortitionId::new(&this_block_hash, &next_pox);
 
         // do the cryptographic sortition and pick the next winning block.
-        let mut snapshot = BlockSnapshot::make_snapshot(
+        let snapshot = BlockSnapshot::make_snapshot(
             self,
             burnchain,
             &next_sortition_id,
@@ -179,6 +175,34 @@ impl<'a> SortitionHandleTx<'a> {
             BurnchainError::DBError(e)
         })?;
 
+        Ok((snapshot, state_transition))
+    }
+
+    /// Process all block's checked transactions
+    /// * make the burn distribution
+    /// * insert the ones that went into the burn distribution
+    /// * snapshot the block and run the sortition
+    /// * return the snapshot (and sortition results)
+    fn process_checked_block_ops(
+        &mut self,
+        burnchain: &Burnchain,
+        parent_snapshot: &BlockSnapshot,
+        block_header: &BurnchainBlockHeader,
+        this_block_ops: &Vec<BlockstackOperationType>,
+        next_pox_info: Option<RewardCycleInfo>,
+        parent_pox: PoxId,
+        reward_info: Option<&RewardSetInfo>,
+    ) -> Result<(BlockSnapshot, BurnchainStateTransition), BurnchainError> {
+        let this_block_height = block_header.block_height;
+        let (mut snapshot, state_transition) = self.snapshot_checked_block_ops(
+            burnchain,
+            parent_snapshot,
+            block_header,
+            this_block_ops,
+            next_pox_info.as_ref(),
+            parent_pox,
+        )?;
+
         // store the snapshot
         let index_root = self.append_chain_tip_snapshot(
             parent_snapshot,
@@ -272,6 +296,33 @@ impl<'a> SortitionHandleTx<'a> {
         Ok(res)
     }
 
+    /// Check a block's blockstack operations and run its sortition, exactly as
+    /// `process_block_ops` would, but without storing anything.  Used to re-evaluate a block
+    /// whose sortition has already been processed.
+    pub fn reevaluate_block_ops(
+        &mut self,
+        burnchain: &Burnchain,
+        parent_snapshot: &BlockSnapshot,
+        block_header: &BurnchainBlockHeader,
+        mut blockstack_txs: Vec<BlockstackOperationType>,
+        next_pox_info: Option<&RewardCycleInfo>,
+        parent_pox: PoxId,
+        reward_set_info: Option<&RewardSetInfo>,
+    ) -> Result<BlockSnapshot, BurnchainError> {
+        blockstack_txs.sort_by(|ref a, ref b| a.vtxindex().partial_cmp(&b.vtxindex()).unwrap());
+
+        let block_ops = self.check_block_ops(burnchain, blockstack_txs, reward_set_info)?;
+        let (snapshot, _) = self.snapshot_checked_block_ops(
+            burnchain,
+            parent_snapshot,
+            block_header,
+            &block_ops,
+            next_pox_info,
+            parent_pox,
+        )?;
+        Ok(snapshot)
+    }
+
     /// Given the extracted txs, and a block header, go process them into the next
     /// snapshot.  Unlike process_block_ops, this method applies safety checks against the given
     /// list of blockstack transactions.
@@ -47,7 +47,7 @@ use chainstate::burn::{
     BlockHeaderHash, BlockSnapshot, ConsensusHash, OpsHash, SortitionHash, VRFSeed,
 };
 
-use chainstate::coordinator::{Error as CoordinatorError, PoxAnchorBlockStatus, RewardCycleInfo};
+use chainstate::coordinator::{Error as CoordinatorError, RewardCycleInfo};
 use core::CHAINSTATE_VERSION;
 
 use chainstate::burn::operations::{
@@ -982,9 +982,7 @@ impl<'a> SortitionHandleTx<'a> {
         next_pox_info: Option<&RewardCycleInfo>,
     ) -> Result<Option<RewardSetInfo>, BurnchainError> {
         if let Some(next_pox_info) = next_pox_info {
-            if let PoxAnchorBlockStatus::SelectedAndKnown(ref anchor_block, ref reward_set) =
-                next_pox_info.anchor_status
-            {
+            if let Some((anchor_block, reward_set)) = next_pox_info.known_anchor_and_reward_set() {
                 if reward_set.len() == 0 {
                     return Ok(None);
                 }
@@ -997,7 +995,7 @@ impl<'a> SortitionHandleTx<'a> {
                 );
 
                 let recipient = (
-                    reward_set[chosen_recipient as usize],
+                    reward_set[chosen_recipient as usize].0,
                     u16::try_from(chosen_recipient).unwrap(),
                 );
                 Ok(Some(RewardSetInfo {
@@ -1563,7 +1561,45 @@ impl<'a> SortitionHandleConn<'a> {
         }
 
         let prepare_begin = prepare_end - pox_consts.prepare_length;
+        self.get_confirmed_pox_anchor(prepare_begin, prepare_end, pox_consts)
+    }
+
+    /// Return identifying information for the PoX anchor block that the prepare phase in progress
+    ///   at this handle's chain tip has chosen so far -- i.e. the candidate that has already
+    ///   received `anchor_threshold` confirmations.
+    /// Returns `None` if the chain tip is not in a prepare phase, or if no candidate has enough
+    ///   confirmations yet.
+    pub fn get_prepare_phase_pox_anchor(
+        &self,
+        pox_consts: &PoxConstants,
+    ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, CoordinatorError> {
+        let my_height = SortitionDB::get_block_height(self.deref(), &self.context.chain_tip)?
+            .expect("CORRUPTION: SortitionID known, but no block height in SQL store");
+
+        // the end of the prepare phase that this block would be in
+        let prepare_end = match my_height % pox_consts.reward_cycle_length {
+            0 => my_height,
+            offset => my_height - offset + pox_consts.reward_cycle_length,
+        };
+        if prepare_end < pox_consts.prepare_length {
+            return Ok(None);
+        }
+
+        let prepare_begin = prepare_end - pox_consts.prepare_length;
+        if my_height <= prepare_begin {
+            return Ok(None);
+        }
+        self.get_confirmed_pox_anchor(prepare_begin, my_height, pox_consts)
+    }
 
+    /// Find the PoX anchor block candidate that received at least `anchor_threshold`
+    ///   confirmations from the sortition winners in (prepare_begin, prepare_end].
+    fn get_confirmed_pox_anchor(
+        &self,
+        prepare_begin: u32,
+        prepare_end: u32,
+        pox_consts: &PoxConstants,
+    ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, CoordinatorError> {
         let mut candidate_anchors = HashMap::new();
         let mut memoized_candidates: HashMap<_, (Txid, u64)> = HashMap::new();
 
@@ -2201,7 +2237,7 @@ impl SortitionDB {
     /// Is the given block an expected PoX anchor in this sortition history?
     ///  if so, return the Stacks block hash
     pub fn is_stacks_block_pox_anchor(
-        &mut self,
+        &self,
         block: &BlockHeaderHash,
         sortition_tip: &SortitionId,
     ) -> Result<Option<BlockHeaderHash>, BurnchainError> {
@@ -2358,6 +2394,49 @@ impl SortitionDB {
         Ok(new_snapshot)
     }
 
+    /// Re-run the sortition for a burnchain block that has already been processed, without
+    /// storing anything.  The caller can compare the returned snapshot to the stored one.
+    pub fn reevaluate_sortition(
+        &mut self,
+        burn_header: &BurnchainBlockHeader,
+        ops: Vec<BlockstackOperationType>,
+        burnchain: &Burnchain,
+        from_tip: &SortitionId,
+        next_pox_info: Option<RewardCycleInfo>,
+    ) -> Result<BlockSnapshot, BurnchainError> {
+        let parent_sort_id = self
+            .get_sortition_id(&burn_header.parent_block_hash, from_tip)?
+            .ok_or_else(|| BurnchainError::MissingParentBlock)?;
+
+        // this transaction is never committed
+        let mut sortition_db_handle = SortitionHandleTx::begin(self, &parent_sort_id)?;
+        let parent_snapshot = sortition_db_handle
+            .get_block_snapshot(&burn_header.parent_block_hash, &parent_sort_id)?
+            .ok_or_else(|| {
+                warn!("Unknown block {:?}", burn_header.parent_block_hash);
+                BurnchainError::MissingParentBlock
+            })?;
+
+        let parent_pox = sortition_db_handle.get_pox_id()?;
+
+        let reward_set_vrf_hash = parent_snapshot
+            .sortition_hash
+            .mix_burn_header(&parent_snapshot.burn_header_hash);
+
+        let reward_set_info =
+            sortition_db_handle.pick_recipient(&reward_set_vrf_hash, next_pox_info.as_ref())?;
+
+        sortition_db_handle.reevaluate_block_ops(
+            burnchain,
+            &parent_snapshot,
+            burn_header,
+            ops,
+            next_pox_info.as_ref(),
+            parent_pox,
+            reward_set_info.as_ref(),
+        )
+    }
+
     #[cfg(test)]
     pub fn test_get_next_block_recipients(
         &mut self,
@@ -3234,13 +3313,13 @@ impl<'a> SortitionHandleTx<'a> {
                         // if we have a reward set, then we must also have produced a recipient
                         //   info for this block
                         let (addr, ix) = recipient_info.unwrap().recipient.clone();
-                        assert_eq!(&reward_set.remove(ix as usize), &addr,
+                        assert_eq!(&reward_set.remove(ix as usize).0, &addr,
                             "BUG: Attempted to remove used address from reward set, but failed to do so safely");
                     }
 
                     keys.push(db_keys::pox_reward_set_size().to_string());
                     values.push(db_keys::reward_set_size_to_string(reward_set.len()));
-                    for (ix, address) in reward_set.iter().enumerate() {
+                    for (ix, (address, _)) in reward_set.iter().enumerate() {
                         keys.push(db_keys::pox_reward_set_entry(ix as u16));
                         values.push(address.to_string());
                     }
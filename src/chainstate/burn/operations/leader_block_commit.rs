@@ -363,6 +363,7 @@ impl BlockstackOperation for LeaderBlockCommitOp {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct RewardSetInfo {
     pub anchor_block: BlockHeaderHash,
     pub recipient: (StacksAddress, u16),
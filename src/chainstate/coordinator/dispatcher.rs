@@ -0,0 +1,242 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use chainstate::coordinator::BlockEventDispatcher;
+use chainstate::stacks::db::{StacksChainState, StacksHeaderInfo};
+use chainstate::stacks::events::StacksTransactionReceipt;
+use chainstate::stacks::{Error as ChainstateError, StacksBlock, StacksBlockId};
+use net::StacksMessageCodec;
+use util::db::Error as db_error;
+use vm::types::Value;
+
+const STATUS_SUCCESS: &str = "success";
+const STATUS_ABORT_BY_RESPONSE: &str = "abort_by_response";
+const STATUS_ABORT_BY_POST_CONDITION: &str = "abort_by_post_condition";
+
+/// Announce blocks to two dispatchers, e.g. to log receipts to a file
+///  while also posting them to event observers.
+impl<A: BlockEventDispatcher, B: BlockEventDispatcher> BlockEventDispatcher for (A, B) {
+    fn announce_block(
+        &self,
+        block: StacksBlock,
+        metadata: StacksHeaderInfo,
+        receipts: Vec<StacksTransactionReceipt>,
+        parent: &StacksBlockId,
+    ) {
+        self.0
+            .announce_block(block.clone(), metadata.clone(), receipts.clone(), parent);
+        self.1.announce_block(block, metadata, receipts, parent);
+    }
+
+    fn dispatch_boot_receipts(&mut self, receipts: Vec<StacksTransactionReceipt>) {
+        self.0.dispatch_boot_receipts(receipts.clone());
+        self.1.dispatch_boot_receipts(receipts);
+    }
+}
+
+/// An optional dispatcher does nothing if it's not set.
+impl<T: BlockEventDispatcher> BlockEventDispatcher for Option<T> {
+    fn announce_block(
+        &self,
+        block: StacksBlock,
+        metadata: StacksHeaderInfo,
+        receipts: Vec<StacksTransactionReceipt>,
+        parent: &StacksBlockId,
+    ) {
+        if let Some(dispatcher) = self {
+            dispatcher.announce_block(block, metadata, receipts, parent);
+        }
+    }
+
+    fn dispatch_boot_receipts(&mut self, receipts: Vec<StacksTransactionReceipt>) {
+        if let Some(dispatcher) = self {
+            dispatcher.dispatch_boot_receipts(receipts);
+        }
+    }
+}
+
+/// Dispatcher that appends one JSON line per transaction receipt to a file.
+/// Each line has the transaction's txid, the index block hash of the block that
+///  contained it (absent for boot receipts), its status, and its execution cost.
+pub struct LogFileDispatcher {
+    path: String,
+}
+
+impl LogFileDispatcher {
+    pub fn new(path: &str) -> LogFileDispatcher {
+        LogFileDispatcher {
+            path: path.to_string(),
+        }
+    }
+
+    fn receipt_status(receipt: &StacksTransactionReceipt) -> &'static str {
+        match (receipt.post_condition_aborted, &receipt.result) {
+            (true, _) => STATUS_ABORT_BY_POST_CONDITION,
+            (false, Value::Response(response_data)) if !response_data.committed => {
+                STATUS_ABORT_BY_RESPONSE
+            }
+            (false, _) => STATUS_SUCCESS,
+        }
+    }
+
+    fn make_receipt_json(
+        receipt: &StacksTransactionReceipt,
+        index_block_hash: Option<&StacksBlockId>,
+    ) -> serde_json::Value {
+        json!({
+            "txid": format!("0x{}", receipt.transaction.txid()),
+            "index_block_hash": index_block_hash.map(|h| format!("0x{}", h)),
+            "status": LogFileDispatcher::receipt_status(receipt),
+            "execution_cost": receipt.execution_cost,
+        })
+    }
+
+    fn append_receipts(
+        &self,
+        receipts: &[StacksTransactionReceipt],
+        index_block_hash: Option<&StacksBlockId>,
+    ) {
+        let mut lines = vec![];
+        for receipt in receipts.iter() {
+            let line = LogFileDispatcher::make_receipt_json(receipt, index_block_hash);
+            lines.extend_from_slice(line.to_string().as_bytes());
+            lines.push(b'\n');
+        }
+
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut fd| fd.write_all(&lines));
+
+        if let Err(e) = result {
+            warn!(
+                "Failed to log {} transaction receipts to {}: {:?}",
+                receipts.len(),
+                &self.path,
+                &e
+            );
+        }
+    }
+}
+
+impl BlockEventDispatcher for LogFileDispatcher {
+    fn announce_block(
+        &self,
+        _block: StacksBlock,
+        metadata: StacksHeaderInfo,
+        receipts: Vec<StacksTransactionReceipt>,
+        _parent: &StacksBlockId,
+    ) {
+        self.append_receipts(&receipts, Some(&metadata.index_block_hash()));
+    }
+
+    fn dispatch_boot_receipts(&mut self, receipts: Vec<StacksTransactionReceipt>) {
+        self.append_receipts(&receipts, None);
+    }
+}
+
+/// Dispatcher that writes each announced block to `{archive_dir}/{height}-{block_hash}.blk`,
+///  in the same consensus serialization the chunk store uses, for cold storage.
+/// Blocks that are already archived are not written again.
+pub struct ArchiveDispatcher {
+    archive_dir: String,
+}
+
+impl ArchiveDispatcher {
+    pub fn new(archive_dir: &str) -> ArchiveDispatcher {
+        ArchiveDispatcher {
+            archive_dir: archive_dir.to_string(),
+        }
+    }
+
+    /// Path to which a block at the given height is archived
+    pub fn get_block_path(&self, block: &StacksBlock, height: u64) -> String {
+        let mut path = PathBuf::from(&self.archive_dir);
+        path.push(format!("{}-{}.blk", height, block.block_hash()));
+        path.to_string_lossy().to_string()
+    }
+
+    fn archive_block(&self, block: &StacksBlock, height: u64) -> Result<(), ChainstateError> {
+        let block_path = self.get_block_path(block, height);
+        if fs::metadata(&block_path).is_ok() {
+            test_debug!("Block already archived to {}", &block_path);
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.archive_dir)
+            .map_err(|e| ChainstateError::DBError(db_error::IOError(e)))?;
+        StacksChainState::atomic_file_store(&block_path, true, |ref mut fd| {
+            block.consensus_serialize(fd).map_err(ChainstateError::NetError)
+        })
+    }
+}
+
+impl BlockEventDispatcher for ArchiveDispatcher {
+    fn announce_block(
+        &self,
+        block: StacksBlock,
+        metadata: StacksHeaderInfo,
+        _receipts: Vec<StacksTransactionReceipt>,
+        _parent: &StacksBlockId,
+    ) {
+        if let Err(e) = self.archive_block(&block, metadata.block_height) {
+            warn!(
+                "Failed to archive block {} to {}: {:?}",
+                &block.block_hash(),
+                &self.archive_dir,
+                &e
+            );
+        }
+    }
+
+    fn dispatch_boot_receipts(&mut self, _receipts: Vec<StacksTransactionReceipt>) {}
+}
+
+/// Callback invoked with each Stacks block that the chains coordinator processes
+pub type OnNewBlockCallback = Box<dyn Fn(&StacksBlock, &StacksHeaderInfo) + Send>;
+
+/// Dispatcher that invokes a callback with each announced block.  The callback runs on the
+///  coordinator thread, which cannot process any other block until it returns.
+pub struct OnNewBlockDispatcher(OnNewBlockCallback);
+
+impl OnNewBlockDispatcher {
+    pub fn new(callback: OnNewBlockCallback) -> OnNewBlockDispatcher {
+        OnNewBlockDispatcher(callback)
+    }
+}
+
+impl BlockEventDispatcher for OnNewBlockDispatcher {
+    fn announce_block(
+        &self,
+        block: StacksBlock,
+        metadata: StacksHeaderInfo,
+        _receipts: Vec<StacksTransactionReceipt>,
+        _parent: &StacksBlockId,
+    ) {
+        (self.0)(&block, &metadata);
+    }
+
+    fn dispatch_boot_receipts(&mut self, _receipts: Vec<StacksTransactionReceipt>) {}
+}
@@ -1,5 +1,9 @@
-use std::collections::VecDeque;
+use std::cmp;
+use std::collections::{HashSet, VecDeque};
 use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::time::Duration;
 
 use burnchains::{
@@ -21,12 +25,14 @@ use util::db::Error as DBError;
 use vm::{costs::ExecutionCost, types::PrincipalData};
 
 pub mod comm;
+pub mod dispatcher;
 use chainstate::stacks::index::MarfTrieId;
 
 #[cfg(test)]
 pub mod tests;
 
 pub use self::comm::CoordinatorCommunication;
+pub use self::dispatcher::LogFileDispatcher;
 
 use chainstate::coordinator::comm::{
     ArcCounterCoordinatorNotices, CoordinatorEvents, CoordinatorNotices, CoordinatorReceivers,
@@ -36,11 +42,24 @@ use chainstate::coordinator::comm::{
 ///  reward cycle's relationship to its PoX anchor
 #[derive(Debug, PartialEq)]
 pub enum PoxAnchorBlockStatus {
-    SelectedAndKnown(BlockHeaderHash, Vec<StacksAddress>),
+    SelectedAndKnown(BlockHeaderHash, Vec<(StacksAddress, u128)>),
     SelectedAndUnknown(BlockHeaderHash),
     NotSelected,
 }
 
+/// Where a Stacks block is in the chainstate's processing pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockStatus {
+    /// Processed and accepted
+    Processed,
+    /// Stored, but not processed yet
+    Staging,
+    /// Rejected, or can never be attached to the chain
+    Orphaned,
+    /// Never stored
+    Unknown,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct RewardCycleInfo {
     pub anchor_status: PoxAnchorBlockStatus,
@@ -61,7 +80,7 @@ impl RewardCycleInfo {
             SelectedAndKnown(_, _) | NotSelected => true,
         }
     }
-    pub fn known_selected_anchor_block(&self) -> Option<&Vec<StacksAddress>> {
+    pub fn known_selected_anchor_block(&self) -> Option<&Vec<(StacksAddress, u128)>> {
         use self::PoxAnchorBlockStatus::*;
         match self.anchor_status {
             SelectedAndUnknown(_) => None,
@@ -69,7 +88,16 @@ impl RewardCycleInfo {
             NotSelected => None,
         }
     }
-    pub fn known_selected_anchor_block_owned(self) -> Option<Vec<StacksAddress>> {
+    pub fn known_anchor_and_reward_set(
+        &self,
+    ) -> Option<(&BlockHeaderHash, &Vec<(StacksAddress, u128)>)> {
+        use self::PoxAnchorBlockStatus::*;
+        match self.anchor_status {
+            SelectedAndKnown(ref block, ref reward_set) => Some((block, reward_set)),
+            SelectedAndUnknown(_) | NotSelected => None,
+        }
+    }
+    pub fn known_selected_anchor_block_owned(self) -> Option<Vec<(StacksAddress, u128)>> {
         use self::PoxAnchorBlockStatus::*;
         match self.anchor_status {
             SelectedAndUnknown(_) => None,
@@ -107,8 +135,25 @@ pub struct ChainsCoordinator<
     dispatcher: Option<&'a T>,
     reward_set_provider: R,
     notifier: N,
+    burnchain_block_fetcher: Option<Box<dyn BurnchainBlockFetcher>>,
+    /// the deepest rollback of the sortition history that a newly-processed PoX anchor block
+    ///  may trigger.  A deeper one is refused, and halts the coordinator.
+    max_reorg_depth: u64,
+    /// number of threads used to validate staging blocks before they are processed.
+    ///  1 means blocks are only validated as they are processed.
+    block_validation_threads: usize,
+    /// staging blocks that the validation threads already checked
+    prevalidated_blocks: HashSet<StacksBlockId>,
 }
 
+/// How many times `handle_new_burnchain_block()` will try to fill a gap in the burnchain DB
+///  before giving up on the new burnchain block.
+pub const MAX_BURNCHAIN_GAP_FILL_ATTEMPTS: usize = 3;
+/// How many missing burnchain blocks a single gap fill will fetch.
+pub const MAX_BURNCHAIN_GAP_FILL_BLOCKS: usize = 144;
+/// By default, a PoX anchor block may roll back the sortition history by any depth.
+pub const DEFAULT_MAX_REORG_DEPTH: u64 = u64::max_value();
+
 #[derive(Debug)]
 pub enum Error {
     BurnchainBlockAlreadyProcessed,
@@ -119,6 +164,14 @@ pub enum Error {
     FailedToProcessSortition(BurnchainError),
     DBError(DBError),
     NotPrepareEndBlock,
+    SnapshotIOError(io::Error),
+    SnapshotTipMismatch(StacksBlockId, StacksBlockId),
+    InvalidReplayRange(u64, u64),
+    SortitionTipAhead(u64, u64),
+    /// Re-evaluating the sortition at this height did not reproduce the stored sortition
+    ReplayMismatch(u64),
+    /// A PoX anchor block would roll back this many sortitions, which is more than the maximum
+    ReorgTooDeep(u64, u64),
 }
 
 impl From<BurnchainError> for Error {
@@ -140,6 +193,8 @@ impl From<DBError> for Error {
 }
 
 pub trait RewardSetProvider {
+    /// Get the reward set for the reward cycle that begins after `current_burn_height`, as a list
+    /// of each PoX address and the amount of uSTX stacked for it.
     fn get_reward_set(
         &self,
         current_burn_height: u64,
@@ -147,7 +202,14 @@ pub trait RewardSetProvider {
         burnchain: &Burnchain,
         sortdb: &SortitionDB,
         block_id: &StacksBlockId,
-    ) -> Result<Vec<StacksAddress>, Error>;
+    ) -> Result<Vec<(StacksAddress, u128)>, Error>;
+}
+
+/// Fetches missing burnchain blocks on the coordinator's behalf.  The coordinator only reads the
+///  burnchain DB, so the fetcher is responsible for storing the blocks it fetches there.
+pub trait BurnchainBlockFetcher {
+    /// Download the burnchain block with the given header hash, and store it in the burnchain DB.
+    fn fetch_burnchain_block(&self, hash: &BurnchainHeaderHash) -> Result<(), BurnchainError>;
 }
 
 pub struct OnChainRewardSetProvider();
@@ -160,17 +222,21 @@ impl RewardSetProvider for OnChainRewardSetProvider {
         burnchain: &Burnchain,
         sortdb: &SortitionDB,
         block_id: &StacksBlockId,
-    ) -> Result<Vec<StacksAddress>, Error> {
+    ) -> Result<Vec<(StacksAddress, u128)>, Error> {
         let res =
             chainstate.get_reward_addresses(burnchain, sortdb, current_burn_height, block_id)?;
-        let addresses = res.iter().map(|a| a.0).collect::<Vec<StacksAddress>>();
-        Ok(addresses)
+        Ok(res)
     }
 }
 
 impl<'a, T: BlockEventDispatcher>
     ChainsCoordinator<'a, T, ArcCounterCoordinatorNotices, OnChainRewardSetProvider>
 {
+    /// Run the chains coordinator until it is told to stop.
+    /// If given, `burnchain_block_fetcher` is used to fill in gaps in the burnchain DB (see
+    ///  `fill_burnchain_gap()`).
+    /// Returns Err(ReorgTooDeep) if it halted instead, because it refused a reorg deeper than
+    ///  `max_reorg_depth` -- the caller should then stop the node.
     pub fn run<F>(
         chain_state_path: &str,
         burnchain: Burnchain,
@@ -178,10 +244,14 @@ impl<'a, T: BlockEventDispatcher>
         stacks_chain_id: u32,
         initial_balances: Option<Vec<(PrincipalData, u64)>>,
         block_limit: ExecutionCost,
+        max_reorg_depth: u64,
+        block_validation_threads: usize,
+        burnchain_block_fetcher: Option<Box<dyn BurnchainBlockFetcher>>,
         dispatcher: &mut T,
         comms: CoordinatorReceivers,
         boot_block_exec: F,
-    ) where
+    ) -> Result<(), Error>
+    where
         F: FnOnce(&mut ClarityTx),
         T: BlockEventDispatcher,
     {
@@ -221,6 +291,10 @@ impl<'a, T: BlockEventDispatcher>
             dispatcher: Some(dispatcher),
             notifier: arc_notices,
             reward_set_provider: OnChainRewardSetProvider(),
+            burnchain_block_fetcher,
+            max_reorg_depth,
+            block_validation_threads: cmp::max(1, block_validation_threads),
+            prevalidated_blocks: HashSet::new(),
         };
 
         loop {
@@ -228,24 +302,193 @@ impl<'a, T: BlockEventDispatcher>
             match comms.wait_on() {
                 CoordinatorEvents::NEW_STACKS_BLOCK => {
                     debug!("Received new stacks block notice");
-                    if let Err(e) = inst.handle_new_stacks_block() {
-                        warn!("Error processing new stacks block: {:?}", e);
+                    match inst.handle_new_stacks_block() {
+                        Err(Error::ReorgTooDeep(depth, max_depth)) => {
+                            error!(
+                                "Halting the chains coordinator: refusing a {}-block reorg (max is {})",
+                                depth, max_depth
+                            );
+                            return Err(Error::ReorgTooDeep(depth, max_depth));
+                        }
+                        Err(e) => warn!("Error processing new stacks block: {:?}", e),
+                        Ok(_) => {}
                     }
                 }
                 CoordinatorEvents::NEW_BURN_BLOCK => {
                     debug!("Received new burn block notice");
-                    if let Err(e) = inst.handle_new_burnchain_block() {
-                        warn!("Error processing new burn block: {:?}", e);
+                    match inst.handle_new_burnchain_block() {
+                        Err(Error::ReorgTooDeep(depth, max_depth)) => {
+                            error!(
+                                "Halting the chains coordinator: refusing a {}-block reorg (max is {})",
+                                depth, max_depth
+                            );
+                            return Err(Error::ReorgTooDeep(depth, max_depth));
+                        }
+                        Err(e) => warn!("Error processing new burn block: {:?}", e),
+                        Ok(_) => {}
                     }
                 }
                 CoordinatorEvents::STOP => {
                     debug!("Received stop notice");
-                    return;
+                    return Ok(());
                 }
                 CoordinatorEvents::TIMEOUT => {}
             }
         }
     }
+
+    /// Bootstrap a fresh node from a trusted chainstate snapshot instead of replaying the
+    ///  chain from genesis, and then run the coordinator as `run()` does.
+    /// The snapshot is first imported with `import_chainstate_snapshot()`, which rejects it
+    ///  unless its canonical Stacks tip is `expected_tip`.
+    pub fn run_from_snapshot<F>(
+        snapshot_path: &str,
+        expected_tip: &StacksBlockId,
+        chain_state_path: &str,
+        burnchain: Burnchain,
+        stacks_mainnet: bool,
+        stacks_chain_id: u32,
+        initial_balances: Option<Vec<(PrincipalData, u64)>>,
+        block_limit: ExecutionCost,
+        max_reorg_depth: u64,
+        block_validation_threads: usize,
+        burnchain_block_fetcher: Option<Box<dyn BurnchainBlockFetcher>>,
+        dispatcher: &mut T,
+        comms: CoordinatorReceivers,
+        boot_block_exec: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ClarityTx),
+        T: BlockEventDispatcher,
+    {
+        import_chainstate_snapshot(
+            snapshot_path,
+            &burnchain,
+            chain_state_path,
+            stacks_mainnet,
+            stacks_chain_id,
+            expected_tip,
+        )?;
+
+        ChainsCoordinator::run(
+            chain_state_path,
+            burnchain,
+            stacks_mainnet,
+            stacks_chain_id,
+            initial_balances,
+            block_limit,
+            max_reorg_depth,
+            block_validation_threads,
+            burnchain_block_fetcher,
+            dispatcher,
+            comms,
+            boot_block_exec,
+        )
+    }
+}
+
+fn copy_dir_all(src_dir: &Path, dest_dir: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(dest_dir)?;
+    for dirent_res in fs::read_dir(src_dir)? {
+        let dirent = dirent_res?;
+        let dest_path = dest_dir.join(dirent.file_name());
+        if dirent.file_type()?.is_dir() {
+            copy_dir_all(&dirent.path(), &dest_path)?;
+        } else {
+            fs::copy(dirent.path(), dest_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Import a chainstate snapshot into the (not yet existing) burnchain and chainstate
+///  directories of a fresh node.
+/// The snapshot directory holds the node's burnchain directory (with the sortition DB and
+///  burnchain DB) in `burnchain/`, and its chainstate directory in `chainstate/`.
+/// Once copied, the snapshot's canonical Stacks tip must be `expected_tip`, and must have been
+///  processed.  If it isn't, the copied directories are removed again and the snapshot is rejected.
+pub fn import_chainstate_snapshot(
+    snapshot_path: &str,
+    burnchain: &Burnchain,
+    chain_state_path: &str,
+    stacks_mainnet: bool,
+    stacks_chain_id: u32,
+    expected_tip: &StacksBlockId,
+) -> Result<(), Error> {
+    let burnchain_dir = Burnchain::get_chainstate_path(
+        &burnchain.working_dir,
+        &burnchain.chain_name,
+        &burnchain.network_name,
+    );
+
+    for dest_dir in [burnchain_dir.as_str(), chain_state_path].iter() {
+        if Path::new(dest_dir).exists() {
+            return Err(Error::SnapshotIOError(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Cannot import snapshot over existing directory {}", dest_dir),
+            )));
+        }
+    }
+
+    info!(
+        "Import chainstate snapshot {} into {} and {}",
+        snapshot_path, &burnchain_dir, chain_state_path
+    );
+
+    let snapshot_dir = Path::new(snapshot_path);
+    let result = copy_dir_all(&snapshot_dir.join("burnchain"), Path::new(&burnchain_dir))
+        .and_then(|_| {
+            copy_dir_all(
+                &snapshot_dir.join("chainstate"),
+                Path::new(chain_state_path),
+            )
+        })
+        .map_err(Error::SnapshotIOError)
+        .and_then(|_| {
+            check_snapshot_tip(
+                burnchain,
+                chain_state_path,
+                stacks_mainnet,
+                stacks_chain_id,
+                expected_tip,
+            )
+        });
+
+    if let Err(ref e) = result {
+        warn!("Rejecting chainstate snapshot {}: {:?}", snapshot_path, e);
+        let _ = fs::remove_dir_all(&burnchain_dir);
+        let _ = fs::remove_dir_all(chain_state_path);
+    }
+    result
+}
+
+fn check_snapshot_tip(
+    burnchain: &Burnchain,
+    chain_state_path: &str,
+    stacks_mainnet: bool,
+    stacks_chain_id: u32,
+    expected_tip: &StacksBlockId,
+) -> Result<(), Error> {
+    let tip = {
+        let sortition_db = SortitionDB::open(&burnchain.get_db_path(), false)?;
+        let (consensus_hash, block_hash) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(sortition_db.conn())?;
+        StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash)
+    };
+
+    if &tip != expected_tip {
+        return Err(Error::SnapshotTipMismatch(expected_tip.clone(), tip));
+    }
+
+    let (chain_state_db, _) =
+        StacksChainState::open(stacks_mainnet, stacks_chain_id, chain_state_path)?;
+    match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+        chain_state_db.headers_db(),
+        &tip,
+    )? {
+        Some(_) => Ok(()),
+        None => Err(Error::ChainstateError(ChainstateError::NoSuchBlockError)),
+    }
 }
 
 impl<'a, T: BlockEventDispatcher, U: RewardSetProvider> ChainsCoordinator<'a, T, (), U> {
@@ -277,7 +520,85 @@ impl<'a, T: BlockEventDispatcher, U: RewardSetProvider> ChainsCoordinator<'a, T,
             dispatcher: None,
             reward_set_provider,
             notifier: (),
+            burnchain_block_fetcher: None,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            block_validation_threads: 1,
+            prevalidated_blocks: HashSet::new(),
+        }
+    }
+
+    /// Limit how deep a rollback a PoX anchor block may trigger
+    #[cfg(test)]
+    pub fn set_max_reorg_depth(&mut self, max_reorg_depth: u64) {
+        self.max_reorg_depth = max_reorg_depth;
+    }
+
+    /// Announce processed blocks to `dispatcher`
+    #[cfg(test)]
+    pub fn set_dispatcher(&mut self, dispatcher: &'a T) {
+        self.dispatcher = Some(dispatcher);
+    }
+
+    /// Check staging blocks' signatures on `num_threads` threads before processing them.
+    ///   Processing itself (and every database write) stays serialized in the coordinator thread.
+    #[cfg(test)]
+    pub fn set_block_validation_threads(&mut self, num_threads: usize) {
+        self.block_validation_threads = cmp::max(1, num_threads);
+    }
+}
+
+/// Memoizes the output of `get_reward_cycle_info()` for the most recent
+///  (reward cycle, sortition tip) pair it was asked about, so that a miner
+///  planning several block-commits within a reward cycle doesn't re-derive
+///  the PoX anchor block and reward set each time.
+/// The cached entry is replaced as soon as it is queried with a different sortition
+///  tip, which happens on every new burn block.
+#[derive(Debug, Default)]
+pub struct RewardCycleInfoCache {
+    key: Option<(u64, SortitionId)>,
+    info: Option<RewardCycleInfo>,
+}
+
+impl RewardCycleInfoCache {
+    pub fn new() -> RewardCycleInfoCache {
+        RewardCycleInfoCache::default()
+    }
+
+    /// Forget the cached reward cycle info, e.g. on the arrival of a new burn block.
+    pub fn invalidate(&mut self) {
+        self.key = None;
+        self.info = None;
+    }
+
+    fn get_or_compute<U: RewardSetProvider>(
+        &mut self,
+        burn_height: u64,
+        parent_bhh: &BurnchainHeaderHash,
+        sortition_tip: &SortitionId,
+        burnchain: &Burnchain,
+        chain_state: &mut StacksChainState,
+        sort_db: &SortitionDB,
+        provider: &U,
+    ) -> Result<Option<&RewardCycleInfo>, Error> {
+        let key = (
+            burn_height / (burnchain.pox_constants.reward_cycle_length as u64),
+            sortition_tip.clone(),
+        );
+        if self.key.as_ref() != Some(&key) {
+            // clear first, so that an error leaves the cache empty
+            self.invalidate();
+            self.info = get_reward_cycle_info(
+                burn_height,
+                parent_bhh,
+                sortition_tip,
+                burnchain,
+                chain_state,
+                sort_db,
+                provider,
+            )?;
+            self.key = Some(key);
         }
+        Ok(self.info.as_ref())
     }
 }
 
@@ -302,6 +623,30 @@ pub fn get_next_recipients<U: RewardSetProvider>(
         .map_err(|e| Error::from(e))
 }
 
+/// Same as `get_next_recipients()`, but re-uses the reward cycle info held in `cache`
+///  if it was computed for this reward cycle and sortition tip.
+pub fn get_next_recipients_cached<U: RewardSetProvider>(
+    sortition_tip: &BlockSnapshot,
+    chain_state: &mut StacksChainState,
+    sort_db: &mut SortitionDB,
+    burnchain: &Burnchain,
+    provider: &U,
+    cache: &mut RewardCycleInfoCache,
+) -> Result<Option<RewardSetInfo>, Error> {
+    let reward_cycle_info = cache.get_or_compute(
+        sortition_tip.block_height + 1,
+        &sortition_tip.burn_header_hash,
+        &sortition_tip.sortition_id,
+        burnchain,
+        chain_state,
+        sort_db,
+        provider,
+    )?;
+    sort_db
+        .get_next_block_recipients(sortition_tip, reward_cycle_info)
+        .map_err(|e| Error::from(e))
+}
+
 /// returns None if this burnchain block is _not_ the start of a reward cycle
 ///         otherwise, returns the required reward cycle info for this burnchain block
 ///                     in our current sortition view:
@@ -365,88 +710,441 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
         }
     }
 
+    /// Process up to `max` ready staging blocks, and return each processed block along with its
+    ///   header info and transaction receipts, in the order they were processed.  Blocks are
+    ///   still announced to the dispatcher, if there is one.
+    /// If a processed block turns out to be a PoX anchor block that invalidates the current PoX
+    ///   fork, the batch stops at that block: the PoX fork is unwound before returning, but no
+    ///   Stacks blocks past the anchor block are processed while the sortitions are reprocessed.
+    ///   They are left for the next call.
+    pub fn drain_processed_blocks(
+        &mut self,
+        max: usize,
+    ) -> Result<Vec<(StacksBlock, StacksHeaderInfo, Vec<StacksTransactionReceipt>)>, Error> {
+        let mut drained = vec![];
+        let mut budget = max;
+        if let Some(pox_anchor) =
+            self.process_ready_blocks_up_to(Some(&mut budget), Some(&mut drained))?
+        {
+            self.process_new_pox_anchor_up_to(pox_anchor, Some(&mut 0), None)?;
+        }
+        Ok(drained)
+    }
+
+    /// The canonical Stacks chain tip, as of the last Stacks block this coordinator processed
+    pub fn canonical_stacks_tip(&self) -> Option<StacksBlockId> {
+        self.canonical_chain_tip.clone()
+    }
+
+    /// The canonical sortition tip, as of the last burnchain block this coordinator processed
+    pub fn canonical_sortition_tip(&self) -> Option<SortitionId> {
+        self.canonical_sortition_tip.clone()
+    }
+
+    /// The header of the canonical burnchain block at `height`, as of the last burnchain block
+    ///   this coordinator processed.  Returns None if `height` is past the sortition tip.
+    pub fn get_burnchain_header_by_height(
+        &self,
+        height: u64,
+    ) -> Result<Option<BurnchainBlockHeader>, Error> {
+        let canonical_sortition_tip = self
+            .canonical_sortition_tip
+            .as_ref()
+            .ok_or(Error::NoSortitions)?;
+        let tip_height =
+            SortitionDB::get_block_snapshot(self.sortition_db.conn(), canonical_sortition_tip)?
+                .expect("BUG: no data for sortition")
+                .block_height;
+        if height > tip_height {
+            return Ok(None);
+        }
+
+        let ic = self.sortition_db.index_conn();
+        let snapshot =
+            match SortitionDB::get_ancestor_snapshot(&ic, height, canonical_sortition_tip)? {
+                Some(snapshot) => snapshot,
+                None => return Ok(None),
+            };
+        let block = self
+            .burnchain_blocks_db
+            .get_burnchain_block(&snapshot.burn_header_hash)?;
+        Ok(Some(block.header))
+    }
+
+    /// Is the given Stacks block an expected PoX anchor block in the canonical sortition history?
+    ///   If so, return its block hash.  Nothing gets processed.
+    pub fn is_pox_anchor(
+        &self,
+        block_hash: &BlockHeaderHash,
+    ) -> Result<Option<BlockHeaderHash>, Error> {
+        let canonical_sortition_tip = self
+            .canonical_sortition_tip
+            .as_ref()
+            .ok_or(Error::NoSortitions)?;
+        Ok(self
+            .sortition_db
+            .is_stacks_block_pox_anchor(block_hash, canonical_sortition_tip)?)
+    }
+
+    /// The PoX anchor block chosen so far by the prepare phase that the canonical sortition tip
+    ///   is in.  Returns None if the tip is not in a prepare phase, or if no candidate has been
+    ///   confirmed enough times yet.  Nothing gets processed.
+    pub fn current_prepare_phase_anchor(
+        &self,
+    ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, Error> {
+        let canonical_sortition_tip = self
+            .canonical_sortition_tip
+            .as_ref()
+            .ok_or(Error::NoSortitions)?;
+        self.sortition_db
+            .index_handle(canonical_sortition_tip)
+            .get_prepare_phase_pox_anchor(&self.burnchain.pox_constants)
+    }
+
+    /// Whether the given Stacks block has been processed, is still staging, or was orphaned.
+    ///   Nothing gets processed.
+    pub fn block_status(&self, block_id: &StacksBlockId) -> Result<BlockStatus, Error> {
+        let staging_block = match self
+            .chain_state_db
+            .get_staging_block_by_index_hash(block_id)?
+        {
+            Some(staging_block) => staging_block,
+            None => return Ok(BlockStatus::Unknown),
+        };
+
+        let status = if staging_block.orphaned {
+            BlockStatus::Orphaned
+        } else if staging_block.processed {
+            BlockStatus::Processed
+        } else {
+            BlockStatus::Staging
+        };
+        Ok(status)
+    }
+
     pub fn handle_new_burnchain_block(&mut self) -> Result<(), Error> {
+        self.handle_new_burnchain_block_up_to(None, None)
+    }
+
+    /// Process new sortitions like `handle_new_burnchain_block()`, but process at most `budget`
+    ///   ready Stacks blocks along the way (if given), decrementing it for each one.
+    /// Each such block is appended to `processed` (if given).
+    fn handle_new_burnchain_block_up_to(
+        &mut self,
+        mut budget: Option<&mut usize>,
+        mut processed: Option<
+            &mut Vec<(StacksBlock, StacksHeaderInfo, Vec<StacksTransactionReceipt>)>,
+        >,
+    ) -> Result<(), Error> {
         // Retrieve canonical burnchain chain tip from the BurnchainBlocksDB
         let canonical_burnchain_tip = self.burnchain_blocks_db.get_canonical_chain_tip()?;
 
         // Retrieve canonical pox id (<=> reward cycle id)
-        let mut canonical_sortition_tip = self
+        let canonical_sortition_tip = self
             .canonical_sortition_tip
             .clone()
             .expect("FAIL: no canonical sortition tip");
 
-        // Retrieve all the direct ancestors of this block with an unprocessed sortition
-        let mut cursor = canonical_burnchain_tip.block_hash.clone();
-        let mut sortitions_to_process = VecDeque::new();
+        // Retrieve all the direct ancestors of this block with an unprocessed sortition,
+        //  filling in any missing ancestors along the way
+        let mut gap_fill_attempts = 0;
+        let mut sortitions_to_process = loop {
+            match self.get_unprocessed_burnchain_ancestors(
+                &canonical_burnchain_tip.block_hash,
+                &canonical_sortition_tip,
+            )? {
+                Ok(blocks) => break blocks,
+                Err((missing, e)) => {
+                    warn!(
+                        "ChainsCoordinator: could not retrieve  block burnhash={}",
+                        &missing
+                    );
+                    if gap_fill_attempts >= MAX_BURNCHAIN_GAP_FILL_ATTEMPTS
+                        || self.fill_burnchain_gap(missing)? == 0
+                    {
+                        return Err(Error::NonContiguousBurnchainBlock(e));
+                    }
+                    gap_fill_attempts += 1;
+                }
+            }
+        };
+
+        for unprocessed_block in sortitions_to_process.drain(..) {
+            self.process_burnchain_block(unprocessed_block)?;
+
+            if let Some(pox_anchor) = self.process_ready_blocks_up_to(
+                budget.as_mut().map(|b| &mut **b),
+                processed.as_mut().map(|p| &mut **p),
+            )? {
+                return self.process_new_pox_anchor_up_to(pox_anchor, budget, processed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Walk back from `tip` to its first ancestor with a processed sortition, and return the
+    ///  blocks walked over in ascending order.  If an ancestor is missing from the burnchain DB,
+    ///  the inner error carries its header hash.
+    fn get_unprocessed_burnchain_ancestors(
+        &self,
+        tip: &BurnchainHeaderHash,
+        canonical_sortition_tip: &SortitionId,
+    ) -> Result<Result<VecDeque<BurnchainBlockData>, (BurnchainHeaderHash, BurnchainError)>, Error>
+    {
+        let mut cursor = tip.clone();
+        let mut ancestors = VecDeque::new();
 
         // We halt the ancestry research as soon as we find a processed parent
         while !(self
             .sortition_db
-            .is_sortition_processed(&cursor, &canonical_sortition_tip)?)
+            .is_sortition_processed(&cursor, canonical_sortition_tip)?)
         {
-            let current_block = self
-                .burnchain_blocks_db
-                .get_burnchain_block(&cursor)
-                .map_err(|e| {
-                    warn!(
-                        "ChainsCoordinator: could not retrieve  block burnhash={}",
-                        &cursor
-                    );
-                    Error::NonContiguousBurnchainBlock(e)
-                })?;
+            let current_block = match self.burnchain_blocks_db.get_burnchain_block(&cursor) {
+                Ok(block) => block,
+                Err(e) => return Ok(Err((cursor, e))),
+            };
 
             let parent = current_block.header.parent_block_hash.clone();
-            sortitions_to_process.push_front(current_block);
+            ancestors.push_front(current_block);
             cursor = parent;
         }
 
-        for unprocessed_block in sortitions_to_process.drain(..) {
-            let BurnchainBlockData { header, ops } = unprocessed_block;
+        Ok(Ok(ancestors))
+    }
 
-            let sortition_tip_snapshot = SortitionDB::get_block_snapshot(
-                self.sortition_db.conn(),
-                &canonical_sortition_tip,
-            )?
-            .expect("BUG: no data for sortition");
+    /// Fetch the missing burnchain block `from`, and then its missing ancestors, until reaching an
+    ///  ancestor that is already in the burnchain DB (or `MAX_BURNCHAIN_GAP_FILL_BLOCKS` blocks
+    ///  have been fetched).  Returns the number of blocks fetched, which is 0 if there is no
+    ///  burnchain block fetcher or it fails to fetch `from`.
+    pub fn fill_burnchain_gap(&mut self, from: BurnchainHeaderHash) -> Result<usize, Error> {
+        let fetcher = match self.burnchain_block_fetcher {
+            Some(ref fetcher) => fetcher,
+            None => return Ok(0),
+        };
 
-            // at this point, we need to figure out if the sortition we are
-            //  about to process is the first block in reward cycle.
-            let reward_cycle_info = self.get_reward_cycle_info(&header)?;
-            let next_snapshot = self
-                .sortition_db
-                .evaluate_sortition(
-                    &header,
-                    ops,
-                    &self.burnchain,
-                    &canonical_sortition_tip,
-                    reward_cycle_info,
-                )
-                .map_err(|e| {
-                    error!("ChainsCoordinator: unable to evaluate sortition {:?}", e);
-                    Error::FailedToProcessSortition(e)
-                })?
-                .0;
-
-            let sortition_id = next_snapshot.sortition_id;
-
-            self.notifier.notify_sortition_processed();
+        let mut cursor = from;
+        let mut num_fetched = 0;
+        while num_fetched < MAX_BURNCHAIN_GAP_FILL_BLOCKS {
+            if self
+                .burnchain_blocks_db
+                .get_burnchain_block(&cursor)
+                .is_ok()
+            {
+                break;
+            }
+
+            if let Err(e) = fetcher.fetch_burnchain_block(&cursor) {
+                warn!(
+                    "ChainsCoordinator: failed to fetch missing burnchain block {}: {:?}",
+                    &cursor, &e
+                );
+                break;
+            }
+            num_fetched += 1;
 
+            // the fetcher stored it, so it can be read back now
+            let fetched_block = self.burnchain_blocks_db.get_burnchain_block(&cursor)?;
             debug!(
-                "Sortition processed: {} (tip {} height {})",
-                &sortition_id, &next_snapshot.burn_header_hash, next_snapshot.block_height
+                "ChainsCoordinator: fetched missing burnchain block {} at height {}",
+                &cursor, fetched_block.header.block_height
             );
+            cursor = fetched_block.header.parent_block_hash;
+        }
+
+        Ok(num_fetched)
+    }
+
+    /// Use `fetcher` to fill in gaps in the burnchain DB when a new burnchain block's ancestors
+    ///  are missing.
+    pub fn set_burnchain_block_fetcher(&mut self, fetcher: Box<dyn BurnchainBlockFetcher>) {
+        self.burnchain_block_fetcher = Some(fetcher);
+    }
+
+    /// Evaluate the sortition for a burnchain block whose parent's sortition has been processed,
+    /// and advance the canonical sortition tip if this block is higher.
+    fn process_burnchain_block(&mut self, block: BurnchainBlockData) -> Result<(), Error> {
+        let BurnchainBlockData { header, ops } = block;
+        let canonical_sortition_tip = self
+            .canonical_sortition_tip
+            .clone()
+            .expect("FAIL: no canonical sortition tip");
+
+        let sortition_tip_snapshot =
+            SortitionDB::get_block_snapshot(self.sortition_db.conn(), &canonical_sortition_tip)?
+                .expect("BUG: no data for sortition");
+
+        // at this point, we need to figure out if the sortition we are
+        //  about to process is the first block in reward cycle.
+        let reward_cycle_info = self.get_reward_cycle_info(&header)?;
+        let next_snapshot = self
+            .sortition_db
+            .evaluate_sortition(
+                &header,
+                ops,
+                &self.burnchain,
+                &canonical_sortition_tip,
+                reward_cycle_info,
+            )
+            .map_err(|e| {
+                error!("ChainsCoordinator: unable to evaluate sortition {:?}", e);
+                Error::FailedToProcessSortition(e)
+            })?
+            .0;
+
+        let sortition_id = next_snapshot.sortition_id;
+
+        self.notifier.notify_sortition_processed();
+
+        debug!(
+            "Sortition processed: {} (tip {} height {})",
+            &sortition_id, &next_snapshot.burn_header_hash, next_snapshot.block_height
+        );
 
-            if sortition_tip_snapshot.block_height < header.block_height {
-                // bump canonical sortition...
-                self.canonical_sortition_tip = Some(sortition_id.clone());
-                canonical_sortition_tip = sortition_id;
+        if sortition_tip_snapshot.block_height < header.block_height {
+            // bump canonical sortition...
+            self.canonical_sortition_tip = Some(sortition_id);
+        }
+        Ok(())
+    }
+
+    /// Re-run sortition and Stacks block processing for the canonical burnchain blocks in
+    /// [start_height, end_height], using the burnchain blocks we already have on disk.
+    /// Missing sortitions are evaluated and stored.  Sortitions that are already in the sortition
+    /// DB are re-evaluated from their burnchain block, and must match the stored snapshot (the
+    /// sortition DB is append-only, so they can't be replaced); a mismatch is a
+    /// `ReplayMismatch` error.  After each block, any newly-processable Stacks blocks are
+    /// processed.
+    ///
+    /// Replaying heights at or below the canonical sortition tip is refused unless `force` is set.
+    /// Returns the number of sortitions that were re-evaluated.
+    pub fn replay_burnchain_range(
+        &mut self,
+        start_height: u64,
+        end_height: u64,
+        force: bool,
+    ) -> Result<u64, Error> {
+        if start_height > end_height {
+            return Err(Error::InvalidReplayRange(start_height, end_height));
+        }
+
+        let canonical_sortition_tip = self
+            .canonical_sortition_tip
+            .clone()
+            .expect("FAIL: no canonical sortition tip");
+        let sortition_tip_height =
+            SortitionDB::get_block_snapshot(self.sortition_db.conn(), &canonical_sortition_tip)?
+                .expect("BUG: no data for sortition")
+                .block_height;
+
+        if sortition_tip_height >= start_height && !force {
+            warn!(
+                "ChainsCoordinator: will not replay from height {}, since the sortition tip is at height {}",
+                start_height, sortition_tip_height
+            );
+            return Err(Error::SortitionTipAhead(sortition_tip_height, start_height));
+        }
+
+        // find the canonical burnchain blocks in this range
+        let mut blocks_to_replay = VecDeque::new();
+        let mut cursor = self.burnchain_blocks_db.get_canonical_chain_tip()?;
+        while cursor.block_height >= start_height {
+            let parent = cursor.parent_block_hash.clone();
+            if cursor.block_height <= end_height {
+                let block = self
+                    .burnchain_blocks_db
+                    .get_burnchain_block(&cursor.block_hash)
+                    .map_err(Error::NonContiguousBurnchainBlock)?;
+                blocks_to_replay.push_front(block);
+            }
+            if cursor.block_height <= self.burnchain.first_block_height {
+                break;
+            }
+            cursor = self
+                .burnchain_blocks_db
+                .get_burnchain_block(&parent)
+                .map_err(Error::NonContiguousBurnchainBlock)?
+                .header;
+        }
+
+        let mut num_reevaluated = 0;
+        for block in blocks_to_replay.drain(..) {
+            let canonical_sortition_tip = self
+                .canonical_sortition_tip
+                .clone()
+                .expect("FAIL: no canonical sortition tip");
+
+            if let Some(sortition_id) = self
+                .sortition_db
+                .get_sortition_id(&block.header.block_hash, &canonical_sortition_tip)?
+            {
+                self.reevaluate_burnchain_block(block, &sortition_id)?;
+                num_reevaluated += 1;
+            } else {
+                self.process_burnchain_block(block)?;
             }
 
             if let Some(pox_anchor) = self.process_ready_blocks()? {
-                return self.process_new_pox_anchor(pox_anchor);
+                self.process_new_pox_anchor(pox_anchor)?;
+                return Ok(num_reevaluated);
             }
         }
 
+        Ok(num_reevaluated)
+    }
+
+    /// Re-evaluate the sortition for a burnchain block that was already processed into
+    /// `sortition_id`, and check that it matches what is stored.
+    fn reevaluate_burnchain_block(
+        &mut self,
+        block: BurnchainBlockData,
+        sortition_id: &SortitionId,
+    ) -> Result<(), Error> {
+        let BurnchainBlockData { header, ops } = block;
+        let canonical_sortition_tip = self
+            .canonical_sortition_tip
+            .clone()
+            .expect("FAIL: no canonical sortition tip");
+
+        let stored_snapshot =
+            SortitionDB::get_block_snapshot(self.sortition_db.conn(), sortition_id)?
+                .expect("BUG: no data for sortition");
+
+        let reward_cycle_info = self.get_reward_cycle_info(&header)?;
+        let snapshot = self
+            .sortition_db
+            .reevaluate_sortition(
+                &header,
+                ops,
+                &self.burnchain,
+                &canonical_sortition_tip,
+                reward_cycle_info,
+            )
+            .map_err(|e| {
+                error!("ChainsCoordinator: unable to re-evaluate sortition {:?}", e);
+                Error::FailedToProcessSortition(e)
+            })?;
+
+        if snapshot.sortition_id != stored_snapshot.sortition_id
+            || snapshot.consensus_hash != stored_snapshot.consensus_hash
+            || snapshot.winning_stacks_block_hash != stored_snapshot.winning_stacks_block_hash
+        {
+            error!(
+                "Replay: sortition for {} (height {}) does not match the stored sortition: got {} ({}), expected {} ({})",
+                &header.block_hash,
+                header.block_height,
+                &snapshot.sortition_id,
+                &snapshot.consensus_hash,
+                &stored_snapshot.sortition_id,
+                &stored_snapshot.consensus_hash
+            );
+            return Err(Error::ReplayMismatch(header.block_height));
+        }
+
+        debug!(
+            "Replay: sortition for {} (height {}) matches consensus hash {}",
+            &header.block_hash, header.block_height, &snapshot.consensus_hash
+        );
         Ok(())
     }
 
@@ -483,10 +1181,43 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
     ///   otherwise returns None
     ///
     fn process_ready_blocks(&mut self) -> Result<Option<BlockHeaderHash>, Error> {
+        self.process_ready_blocks_up_to(None, None)
+    }
+
+    ///
+    /// Process ready staging blocks, like `process_ready_blocks()`, but stop once `budget`
+    ///   blocks in the canonical sortition fork have been processed (if given).  The budget is
+    ///   decremented for each such block, and each such block is appended to `processed` (if given).
+    ///
+    fn process_ready_blocks_up_to(
+        &mut self,
+        mut budget: Option<&mut usize>,
+        mut processed: Option<
+            &mut Vec<(StacksBlock, StacksHeaderInfo, Vec<StacksTransactionReceipt>)>,
+        >,
+    ) -> Result<Option<BlockHeaderHash>, Error> {
         let canonical_sortition_tip = self.canonical_sortition_tip.as_ref().expect(
             "FAIL: processing a new Stacks block, but don't have a canonical sortition tip",
         );
 
+        if budget.as_ref().map(|b| **b == 0).unwrap_or(false) {
+            return Ok(None);
+        }
+
+        if self.block_validation_threads > 1 {
+            let invalid = self.chain_state_db.prevalidate_staging_blocks(
+                self.block_validation_threads,
+                &mut self.prevalidated_blocks,
+            )?;
+            if invalid.len() > 0 {
+                debug!(
+                    "Found {} invalid staging blocks ahead of processing",
+                    invalid.len()
+                );
+            }
+        }
+
+        self.check_staged_anchor_blocks()?;
         let sortdb_handle = self.sortition_db.tx_handle_begin(canonical_sortition_tip)?;
         let mut processed_blocks = self.chain_state_db.process_blocks(sortdb_handle, 1)?;
 
@@ -514,10 +1245,13 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
                     debug!("Bump blocks processed");
                     self.notifier.notify_stacks_block_processed();
                     increment_stx_blocks_processed_counter();
+                    if let Some(ref mut budget) = budget {
+                        **budget -= 1;
+                    }
                     let block_hash = block_receipt.header.anchored_header.block_hash();
 
-                    if let Some(dispatcher) = self.dispatcher {
-                        let metadata = &block_receipt.header;
+                    if self.dispatcher.is_some() || processed.is_some() {
+                        let metadata = block_receipt.header;
                         let block: StacksBlock = {
                             let block_path = StacksChainState::get_block_path(
                                 &self.chain_state_db.blocks_path,
@@ -527,18 +1261,36 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
                             .unwrap();
                             StacksChainState::consensus_load(&block_path).unwrap()
                         };
-                        let stacks_block =
-                            StacksBlockId::new(&metadata.consensus_hash, &block_hash);
-                        let parent = self
-                            .chain_state_db
-                            .get_parent(&stacks_block)
-                            .expect("BUG: failed to get parent for processed block");
-                        dispatcher.announce_block(
-                            block,
-                            block_receipt.header,
-                            block_receipt.tx_receipts,
-                            &parent,
-                        );
+
+                        if let Some(dispatcher) = self.dispatcher {
+                            let stacks_block =
+                                StacksBlockId::new(&metadata.consensus_hash, &block_hash);
+                            let parent = self
+                                .chain_state_db
+                                .get_parent(&stacks_block)
+                                .expect("BUG: failed to get parent for processed block");
+                            match processed {
+                                Some(ref mut processed) => {
+                                    dispatcher.announce_block(
+                                        block.clone(),
+                                        metadata.clone(),
+                                        block_receipt.tx_receipts.clone(),
+                                        &parent,
+                                    );
+                                    processed.push((block, metadata, block_receipt.tx_receipts));
+                                }
+                                None => {
+                                    dispatcher.announce_block(
+                                        block,
+                                        metadata,
+                                        block_receipt.tx_receipts,
+                                        &parent,
+                                    );
+                                }
+                            }
+                        } else if let Some(ref mut processed) = processed {
+                            processed.push((block, metadata, block_receipt.tx_receipts));
+                        }
                     }
 
                     // if, just after processing the block, we _know_ that this block is a pox anchor, that means
@@ -555,6 +1307,11 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
             }
             // TODO: do something with a poison result
 
+            if budget.as_ref().map(|b| **b == 0).unwrap_or(false) {
+                break;
+            }
+
+            self.check_staged_anchor_blocks()?;
             let sortdb_handle = self.sortition_db.tx_handle_begin(canonical_sortition_tip)?;
             processed_blocks = self.chain_state_db.process_blocks(sortdb_handle, 1)?;
         }
@@ -562,31 +1319,89 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
         Ok(None)
     }
 
-    fn process_new_pox_anchor(&mut self, block_id: BlockHeaderHash) -> Result<(), Error> {
-        // get the last sortition in the prepare phase that chose this anchor block
-        //   that sortition is now the current canonical sortition,
-        //   and now that we have process the anchor block for the corresponding reward phase,
-        //   update the canonical pox bitvector.
+    /// Before committing any more staging blocks, make sure that none of the candidates is an old
+    ///  anchor block whose reorg would be too deep.  Such a block is refused while it is still
+    ///  staged, so that it is discovered again once the limit is raised (e.g. on restart).
+    fn check_staged_anchor_blocks(&self) -> Result<(), Error> {
+        if self.max_reorg_depth == DEFAULT_MAX_REORG_DEPTH {
+            return Ok(());
+        }
+
+        let canonical_sortition_tip = self.canonical_sortition_tip.as_ref().expect(
+            "FAIL: processing a new Stacks block, but don't have a canonical sortition tip",
+        );
+        for staging_block in self.chain_state_db.get_attachable_staging_blocks()? {
+            if let Some(pox_anchor) = self.sortition_db.is_stacks_block_pox_anchor(
+                &staging_block.anchored_block_hash,
+                canonical_sortition_tip,
+            )? {
+                self.get_anchor_prepare_end(&pox_anchor)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the sortition that ends the earliest prepare phase which chose `block_id` as its
+    ///  anchor block -- i.e. the sortition to roll back to once that anchor block is known.
+    /// Returns Err(ReorgTooDeep) if this would roll back more than `max_reorg_depth` sortitions.
+    fn get_anchor_prepare_end(&self, block_id: &BlockHeaderHash) -> Result<BlockSnapshot, Error> {
         let sortition_id = self.canonical_sortition_tip.as_ref().expect(
             "FAIL: processing a new anchor block, but don't have a canonical sortition tip",
         );
 
         let mut prep_end = self
             .sortition_db
-            .get_prepare_end_for(sortition_id, &block_id)?
+            .get_prepare_end_for(sortition_id, block_id)?
             .expect(&format!(
                 "FAIL: expected to get a sortition for a chosen anchor block {}, but not found.",
-                &block_id
+                block_id
             ));
 
         // was this block a pox anchor for an even earlier reward cycle?
         while let Some(older_prep_end) = self
             .sortition_db
-            .get_prepare_end_for(&prep_end.sortition_id, &block_id)?
+            .get_prepare_end_for(&prep_end.sortition_id, block_id)?
         {
             prep_end = older_prep_end;
         }
 
+        let tip_height = SortitionDB::get_block_snapshot(self.sortition_db.conn(), sortition_id)?
+            .expect("BUG: no data for the canonical sortition")
+            .block_height;
+        let reorg_depth = tip_height.saturating_sub(prep_end.block_height);
+        if reorg_depth > self.max_reorg_depth {
+            error!(
+                "CRITICAL: anchor block {} would roll back {} sortitions, from height {} to {}, but at most {} are allowed. Refusing to reorg; this needs to be investigated.",
+                block_id, reorg_depth, tip_height, prep_end.block_height, self.max_reorg_depth
+            );
+            return Err(Error::ReorgTooDeep(reorg_depth, self.max_reorg_depth));
+        }
+
+        Ok(prep_end)
+    }
+
+    fn process_new_pox_anchor(&mut self, block_id: BlockHeaderHash) -> Result<(), Error> {
+        self.process_new_pox_anchor_up_to(block_id, None, None)
+    }
+
+    /// Unwind to a newly-discovered PoX anchor block like `process_new_pox_anchor()`, but process
+    ///   at most `budget` ready Stacks blocks while reprocessing the sortitions (if given).
+    /// Each such block is appended to `processed` (if given).
+    fn process_new_pox_anchor_up_to(
+        &mut self,
+        block_id: BlockHeaderHash,
+        budget: Option<&mut usize>,
+        processed: Option<&mut Vec<(StacksBlock, StacksHeaderInfo, Vec<StacksTransactionReceipt>)>>,
+    ) -> Result<(), Error> {
+        // get the last sortition in the prepare phase that chose this anchor block
+        //   that sortition is now the current canonical sortition,
+        //   and now that we have process the anchor block for the corresponding reward phase,
+        //   update the canonical pox bitvector.
+        let prep_end = self.get_anchor_prepare_end(&block_id)?;
+        let sortition_id = self.canonical_sortition_tip.as_ref().expect(
+            "FAIL: processing a new anchor block, but don't have a canonical sortition tip",
+        );
+
         info!(
             "Reprocessing with anchor block information, starting at block height: {}",
             prep_end.block_height
@@ -607,6 +1422,6 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
         self.canonical_pox_id = Some(pox_id);
 
         // Start processing from the beginning of the new PoX reward set
-        self.handle_new_burnchain_block()
+        self.handle_new_burnchain_block_up_to(budget, processed)
     }
 }
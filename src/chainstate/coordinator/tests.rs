@@ -1,8 +1,11 @@
 use chainstate::burn::operations::leader_block_commit::*;
 use chainstate::burn::operations::*;
+use chainstate::coordinator::dispatcher::{ArchiveDispatcher, OnNewBlockDispatcher};
 use chainstate::coordinator::{Error as CoordError, *};
 use chainstate::stacks::*;
+use std::cell::Cell;
 use std::collections::VecDeque;
+use std::path::Path;
 use util::hash::Hash160;
 
 use burnchains::{db::*, *};
@@ -15,7 +18,7 @@ use monitoring::increment_stx_blocks_processed_counter;
 use std::collections::HashSet;
 use std::sync::{
     atomic::{AtomicBool, AtomicU64, Ordering},
-    Arc, RwLock,
+    Arc, Mutex, RwLock,
 };
 use util::vrf::*;
 use vm::{
@@ -238,8 +241,25 @@ impl RewardSetProvider for StubbedRewardSetProvider {
         burnchain: &Burnchain,
         sortdb: &SortitionDB,
         block_id: &StacksBlockId,
-    ) -> Result<Vec<StacksAddress>, chainstate::coordinator::Error> {
-        Ok(self.0.clone())
+    ) -> Result<Vec<(StacksAddress, u128)>, chainstate::coordinator::Error> {
+        Ok(self.0.iter().map(|addr| (addr.clone(), 1)).collect())
+    }
+}
+
+/// Reward set provider that counts how many times the reward set was computed
+struct CountingRewardSetProvider(Vec<StacksAddress>, Cell<u64>);
+
+impl RewardSetProvider for CountingRewardSetProvider {
+    fn get_reward_set(
+        &self,
+        _current_burn_height: u64,
+        _chainstate: &mut StacksChainState,
+        _burnchain: &Burnchain,
+        _sortdb: &SortitionDB,
+        _block_id: &StacksBlockId,
+    ) -> Result<Vec<(StacksAddress, u128)>, chainstate::coordinator::Error> {
+        self.1.set(self.1.get() + 1);
+        Ok(self.0.iter().map(|addr| (addr.clone(), 1)).collect())
     }
 }
 
@@ -299,10 +319,11 @@ fn make_genesis_block(
         vrf_key,
         key_index,
         None,
+        &[],
     )
 }
 
-/// build a stacks block with just the coinbase off of
+/// build a stacks block with the coinbase and then `txs` off of
 ///  parent_block, in the canonical sortition fork.
 fn make_genesis_block_with_recipients(
     sort_db: &SortitionDB,
@@ -313,6 +334,7 @@ fn make_genesis_block_with_recipients(
     vrf_key: &VRFPrivateKey,
     key_index: u32,
     recipients: Option<&RewardSetInfo>,
+    txs: &[StacksTransaction],
 ) -> (BlockstackOperationType, StacksBlock) {
     let tx_auth = TransactionAuth::from_p2pkh(miner).unwrap();
 
@@ -345,6 +367,9 @@ fn make_genesis_block_with_recipients(
     let iconn = sort_db.index_conn();
     let mut epoch_tx = builder.epoch_begin(state, &iconn).unwrap();
     builder.try_mine_tx(&mut epoch_tx, &coinbase_op).unwrap();
+    for tx in txs.iter() {
+        builder.try_mine_tx(&mut epoch_tx, tx).unwrap();
+    }
 
     let block = builder.mine_anchored_block(&mut epoch_tx);
     builder.epoch_finish(epoch_tx);
@@ -506,6 +531,33 @@ fn make_stacks_block_with_recipients(
     (BlockstackOperationType::LeaderBlockCommit(commit_op), block)
 }
 
+#[test]
+fn test_known_anchor_and_reward_set() {
+    let anchor_block = BlockHeaderHash([1; 32]);
+    let reward_set = vec![(p2pkh_from(&StacksPrivateKey::new()), 1000)];
+
+    let known = RewardCycleInfo {
+        anchor_status: PoxAnchorBlockStatus::SelectedAndKnown(
+            anchor_block.clone(),
+            reward_set.clone(),
+        ),
+    };
+    assert_eq!(
+        known.known_anchor_and_reward_set(),
+        Some((&anchor_block, &reward_set))
+    );
+
+    let unknown = RewardCycleInfo {
+        anchor_status: PoxAnchorBlockStatus::SelectedAndUnknown(anchor_block.clone()),
+    };
+    assert_eq!(unknown.known_anchor_and_reward_set(), None);
+
+    let not_selected = RewardCycleInfo {
+        anchor_status: PoxAnchorBlockStatus::NotSelected,
+    };
+    assert_eq!(not_selected.known_anchor_and_reward_set(), None);
+}
+
 #[test]
 fn test_simple_setup() {
     let path = "/tmp/stacks-blockchain-simple-setup";
@@ -701,114 +753,29 @@ fn test_simple_setup() {
 }
 
 #[test]
-fn test_sortition_with_reward_set() {
-    let path = "/tmp/stacks-blockchain-simple-reward-set";
+fn test_is_pox_anchor() {
+    let path = "/tmp/stacks-blockchain.test.is_pox_anchor";
     let _r = std::fs::remove_dir_all(path);
 
-    let mut vrf_keys: Vec<_> = (0..150).map(|_| VRFPrivateKey::new()).collect();
-    let mut committers: Vec<_> = (0..150).map(|_| StacksPrivateKey::new()).collect();
-
-    let reward_set_size = 5;
-    let reward_set: Vec<_> = (0..reward_set_size)
-        .map(|_| p2pkh_from(&StacksPrivateKey::new()))
-        .collect();
+    let vrf_keys: Vec<_> = (0..12).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..12).map(|_| StacksPrivateKey::new()).collect();
 
     setup_states(&[path], &vrf_keys, &committers);
 
-    let mut coord = make_reward_set_coordinator(path, reward_set);
+    let mut coord = make_coordinator(path);
 
     coord.handle_new_burnchain_block().unwrap();
 
     let sort_db = get_sortition_db(path);
 
-    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
-    assert_eq!(tip.block_height, 1);
-    assert_eq!(tip.sortition, false);
-    let (_, ops) = sort_db
-        .get_sortition_result(&tip.sortition_id)
-        .unwrap()
-        .unwrap();
-
-    // we should have all the VRF registrations accepted
-    assert_eq!(ops.accepted_ops.len(), vrf_keys.len());
-    assert_eq!(ops.consumed_leader_keys.len(), 0);
-
-    let mut started_first_reward_cycle = false;
-    // process sequential blocks, and their sortitions...
-    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
+    let mut parent = BlockHeaderHash([0; 32]);
+    let mut stacks_blocks = vec![];
     let mut anchor_blocks = vec![];
-
-    // split up the vrf keys and committers so that we have some that will be mining "correctly"
-    //   and some that will be producing bad outputs
-
-    let BURNER_OFFSET = 50;
-    let mut vrf_key_burners = vrf_keys.split_off(50);
-    let mut miner_burners = committers.split_off(50);
-
-    let WRONG_OUTS_OFFSET = 100;
-    let vrf_key_wrong_outs = vrf_key_burners.split_off(50);
-    let miner_wrong_outs = miner_burners.split_off(50);
-
-    // track the reward set consumption
-    let mut reward_recipients = HashSet::new();
-    for ix in 0..vrf_keys.len() {
-        let vrf_key = &vrf_keys[ix];
-        let miner = &committers[ix];
-
-        let vrf_burner = &vrf_key_burners[ix];
-        let miner_burner = &miner_burners[ix];
-
-        let vrf_wrong_out = &vrf_key_wrong_outs[ix];
-        let miner_wrong_out = &miner_wrong_outs[ix];
-
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
         let mut burnchain = get_burnchain_db(path);
         let mut chainstate = get_chainstate(path);
-
-        let parent = if ix == 0 {
-            BlockHeaderHash([0; 32])
-        } else if ix == 49 {
-            // lets mine off a block that _isn't_ a descendant of our PoX anchor
-            stacks_blocks[1].1.header.block_hash()
-        } else {
-            stacks_blocks[ix - 1].1.header.block_hash()
-        };
-
-        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
-        let next_mock_header = BurnchainBlockHeader {
-            block_height: burnchain_tip.block_height + 1,
-            block_hash: BurnchainHeaderHash([0; 32]),
-            parent_block_hash: burnchain_tip.block_hash,
-            num_txs: 0,
-            timestamp: 1,
-        };
-
-        let reward_cycle_info = coord.get_reward_cycle_info(&next_mock_header).unwrap();
-        if reward_cycle_info.is_some() {
-            // did we process a reward set last cycle? check if the
-            //  recipient set size matches our expectation
-            if started_first_reward_cycle {
-                assert_eq!(reward_recipients.len(), reward_set_size);
-            }
-            // clear the reward recipients tracker, since those
-            //  recipients are now eligible again in the new reward cycle
-            reward_recipients.clear();
-        }
-        let next_block_recipients = get_rw_sortdb(path)
-            .test_get_next_block_recipients(reward_cycle_info.as_ref())
-            .unwrap();
-        if let Some(ref next_block_recipients) = next_block_recipients {
-            let (addr, _) = next_block_recipients.recipient;
-            assert!(
-                !reward_recipients.contains(&addr),
-                "Reward set should not already contain address {}",
-                addr
-            );
-            eprintln!("At iteration: {}, inserting address ... {}", ix, addr);
-            reward_recipients.insert(addr.clone());
-        }
-
-        let (good_op, mut block) = if ix == 0 {
-            make_genesis_block_with_recipients(
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
                 &sort_db,
                 &mut chainstate,
                 &parent,
@@ -816,10 +783,9 @@ fn test_sortition_with_reward_set() {
                 10000,
                 vrf_key,
                 ix as u32,
-                next_block_recipients.as_ref(),
             )
         } else {
-            make_stacks_block_with_recipients(
+            make_stacks_block(
                 &sort_db,
                 &mut chainstate,
                 &parent,
@@ -827,207 +793,88 @@ fn test_sortition_with_reward_set() {
                 10000,
                 vrf_key,
                 ix as u32,
-                next_block_recipients.as_ref(),
             )
         };
-
-        let mut expected_winner = good_op.txid();
-        let mut ops = vec![good_op];
-
-        if started_first_reward_cycle {
-            // make the bad commitments
-            // only create bad "burn" commitments if we know they'll be rejected:
-            //   (a) we're in a reward cycle _and_ (b) there are expected recipients
-            if next_block_recipients.is_some() {
-                let (all_burn_op, all_burn_block) = make_stacks_block_with_recipients(
-                    &sort_db,
-                    &mut chainstate,
-                    &parent,
-                    miner_burner,
-                    10000,
-                    vrf_burner,
-                    (ix + BURNER_OFFSET) as u32,
-                    None,
-                );
-                if ix == 49 {
-                    // at this ix, _all_burn_block_ should be the winner
-                    //   because "parent" isn't a descendant of the PoX anchor
-                    expected_winner = all_burn_op.txid();
-                    block = all_burn_block;
-                }
-                ops.push(all_burn_op);
-            }
-
-            // sometime have the wrong _number_ of recipients,
-            //   other times just have the wrong set of recipients
-            let recipient = if ix % 2 == 0 {
-                (p2pkh_from(miner_wrong_out), 0)
-            } else {
-                (p2pkh_from(&StacksPrivateKey::new()), 0)
-            };
-            let bad_block_recipipients = Some(RewardSetInfo {
-                anchor_block: BlockHeaderHash([0; 32]),
-                recipient,
-            });
-            let (bad_outs_op, _) = make_stacks_block_with_recipients(
-                &sort_db,
-                &mut chainstate,
-                &parent,
-                miner_wrong_out,
-                10000,
-                vrf_burner,
-                (ix + WRONG_OUTS_OFFSET) as u32,
-                bad_block_recipipients.as_ref(),
-            );
-            ops.push(bad_outs_op);
-        }
-
         let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
         produce_burn_block(
             &mut burnchain,
             &burnchain_tip.block_hash,
-            ops,
+            vec![op],
             vec![].iter_mut(),
         );
-        // handle the sortition
         coord.handle_new_burnchain_block().unwrap();
 
         let b = get_burnchain(path);
         let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
         if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
-            started_first_reward_cycle = true;
-            // store the anchor block for this sortition for later checking
             let ic = sort_db.index_handle_at_tip();
             let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
             anchor_blocks.push(bhh);
         }
 
         let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
-        assert_eq!(&tip.winning_block_txid, &expected_winner);
-
-        // load the block into staging
         let block_hash = block.header.block_hash();
-
-        assert_eq!(&tip.winning_stacks_block_hash, &block_hash);
-        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
+        stacks_blocks.push(block_hash.clone());
 
         preprocess_block(&mut chainstate, &sort_db, &tip, block);
-
-        // handle the stacks block
         coord.handle_new_stacks_block().unwrap();
+
+        parent = block_hash;
     }
 
-    let stacks_tip = SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
-    let mut chainstate = get_chainstate(path);
-    assert_eq!(
-        chainstate.with_read_only_clarity_tx(
-            &sort_db.index_conn(),
-            &StacksBlockId::new(&stacks_tip.0, &stacks_tip.1),
-            |conn| conn
-                .with_readonly_clarity_env(
-                    PrincipalData::parse("SP3Q4A5WWZ80REGBN0ZXNE540ECJ9JZ4A765Q5K2Q").unwrap(),
-                    LimitedCostTracker::new_max_limit(),
-                    |env| env.eval_raw("block-height")
-                )
-                .unwrap()
-        ),
-        // we only got to block height 49, because of the little fork at the end.
-        Value::UInt(49)
-    );
+    assert!(anchor_blocks.len() > 0);
 
-    {
-        let ic = sort_db.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(),
-                   "11111111111",
-                   "PoX ID should reflect the 10 reward cycles _with_ a known anchor block, plus the 'initial' known reward cycle at genesis");
+    // exactly the selected anchor blocks are reported
+    for block_hash in stacks_blocks.iter() {
+        let expected = if anchor_blocks.contains(block_hash) {
+            Some(block_hash.clone())
+        } else {
+            None
+        };
+        assert_eq!(coord.is_pox_anchor(block_hash).unwrap(), expected);
     }
+
+    // an unknown block is not an anchor
+    assert_eq!(
+        coord.is_pox_anchor(&BlockHeaderHash([0xff; 32])).unwrap(),
+        None
+    );
 }
 
 #[test]
-// This test should panic until the MARF stability issue
-// https://github.com/blockstack/stacks-blockchain/issues/1805
-// is resolved:
-#[should_panic]
-/// Test a block that is processable in 2 PoX forks:
-///   block "11" should be processable in both `111` and `110`
-///   (because its parent is block `0`, and nobody stacks in
-///    this test, all block commits must burn)
-fn test_pox_processable_block_in_different_pox_forks() {
-    let path = "/tmp/stacks-blockchain.test.pox_processable_block_in_different_pox_forks";
-    // setup a second set of states that won't see the broadcasted blocks
-    let path_blinded =
-        "/tmp/stacks-blockchain.test.pox_processable_block_in_different_pox_forks.blinded";
+fn test_current_prepare_phase_anchor() {
+    let path = "/tmp/stacks-blockchain.test.current_prepare_phase_anchor";
     let _r = std::fs::remove_dir_all(path);
-    let _r = std::fs::remove_dir_all(path_blinded);
 
     let vrf_keys: Vec<_> = (0..12).map(|_| VRFPrivateKey::new()).collect();
     let committers: Vec<_> = (0..12).map(|_| StacksPrivateKey::new()).collect();
 
-    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+    setup_states(&[path], &vrf_keys, &committers);
 
     let mut coord = make_coordinator(path);
-    let mut coord_blind = make_coordinator(path_blinded);
 
     coord.handle_new_burnchain_block().unwrap();
-    coord_blind.handle_new_burnchain_block().unwrap();
 
     let sort_db = get_sortition_db(path);
+    let b = get_burnchain(path);
 
-    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
-    assert_eq!(tip.block_height, 1);
-    assert_eq!(tip.sortition, false);
-    let (_, ops) = sort_db
-        .get_sortition_result(&tip.sortition_id)
-        .unwrap()
-        .unwrap();
-
-    let sort_db_blind = get_sortition_db(path_blinded);
-
-    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
-    assert_eq!(tip.block_height, 1);
-    assert_eq!(tip.sortition, false);
-    let (_, ops) = sort_db_blind
-        .get_sortition_result(&tip.sortition_id)
-        .unwrap()
-        .unwrap();
-
-    // we should have all the VRF registrations accepted
-    assert_eq!(ops.accepted_ops.len(), vrf_keys.len());
-    assert_eq!(ops.consumed_leader_keys.len(), 0);
-
-    // at first, sortition_ids shouldn't have diverged
-    //  but once the first reward cycle begins, they should diverge.
-    let mut sortition_ids_diverged = false;
-    // process sequential blocks, and their sortitions...
-    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
-    let mut anchor_blocks = vec![];
-
-    // setup:
-    //   0 - 1 - 2 - 3 - 4 - 5 - 6 - 7 - 8 - 9
-    //    \_ 10 _ 11
-    //  blocks `10` and `11` can be processed either
-    //    in PoX fork 111 or in 110
+    let mut parent = BlockHeaderHash([0; 32]);
+    let mut reported_anchor = None;
+    let mut confirmed_anchors = 0;
     for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
         let mut burnchain = get_burnchain_db(path);
         let mut chainstate = get_chainstate(path);
-        eprintln!("Making block {}", ix);
         let (op, block) = if ix == 0 {
             make_genesis_block(
                 &sort_db,
                 &mut chainstate,
-                &BlockHeaderHash([0; 32]),
+                &parent,
                 miner,
                 10000,
                 vrf_key,
                 ix as u32,
             )
         } else {
-            let parent = if ix == 10 {
-                stacks_blocks[0].1.header.block_hash()
-            } else {
-                stacks_blocks[ix - 1].1.header.block_hash()
-            };
             make_stacks_block(
                 &sort_db,
                 &mut chainstate,
@@ -1039,236 +886,185 @@ fn test_pox_processable_block_in_different_pox_forks() {
             )
         };
         let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
-        let burnchain_blinded = get_burnchain_db(path_blinded);
         produce_burn_block(
             &mut burnchain,
             &burnchain_tip.block_hash,
             vec![op],
-            [burnchain_blinded].iter_mut(),
+            vec![].iter_mut(),
         );
-        // handle the sortition
         coord.handle_new_burnchain_block().unwrap();
-        coord_blind.handle_new_burnchain_block().unwrap();
 
-        let b = get_burnchain(path);
         let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
-        if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
-            eprintln!(
-                "Reward cycle start at height={}",
-                new_burnchain_tip.block_height
-            );
-            // the "blinded" sortition db and the one that's processed all the blocks
-            //   should have diverged in sortition_ids now...
-            sortition_ids_diverged = true;
-            // store the anchor block for this sortition for later checking
+        let height = new_burnchain_tip.block_height;
+        if b.is_reward_cycle_start(height) {
+            // the anchor confirmed by this reward cycle is the one the prepare phase reported
             let ic = sort_db.index_handle_at_tip();
             let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
-            eprintln!(
-                "Anchor block={}, selected at height={}",
-                &bhh,
-                SortitionDB::get_block_snapshot_for_winning_stacks_block(
-                    &sort_db.index_conn(),
-                    &ic.context.chain_tip,
-                    &bhh
-                )
-                .unwrap()
-                .unwrap()
-                .block_height
-            );
-            anchor_blocks.push(bhh);
+            assert_eq!(reported_anchor.take().map(|(_, bhh)| bhh), Some(bhh));
+            confirmed_anchors += 1;
         }
 
-        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
-        let blinded_tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
-        if sortition_ids_diverged {
-            assert_ne!(
-                tip.sortition_id, blinded_tip.sortition_id,
-                "Sortitions should have diverged by block height = {}",
-                blinded_tip.block_height
-            );
-        } else {
-            assert_eq!(
-                tip.sortition_id, blinded_tip.sortition_id,
-                "Sortitions should not have diverged at block height = {}",
-                blinded_tip.block_height
-            );
+        let current = coord.current_prepare_phase_anchor().unwrap();
+        let cycle_offset = height % (b.pox_constants.reward_cycle_length as u64);
+        let prepare_offset =
+            (b.pox_constants.reward_cycle_length - b.pox_constants.prepare_length) as u64;
+        if cycle_offset != 0 && cycle_offset <= prepare_offset {
+            // not in a prepare phase
+            assert_eq!(current, None);
+        }
+        if current.is_some() {
+            reported_anchor = current;
         }
 
-        // load the block into staging
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
         let block_hash = block.header.block_hash();
-        eprintln!("Block hash={}, ix={}", &block_hash, ix);
-
-        assert_eq!(&tip.winning_stacks_block_hash, &block_hash);
-        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
 
         preprocess_block(&mut chainstate, &sort_db, &tip, block);
-
-        // handle the stacks block
         coord.handle_new_stacks_block().unwrap();
-    }
-
-    let block_height = eval_at_chain_tip(path, &sort_db, "block-height");
-    assert_eq!(block_height, Value::UInt(10));
-
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(0));
-
-    {
-        let ic = sort_db.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "111");
-    }
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "100");
+        parent = block_hash;
     }
 
-    // now, we reveal `0` to the blinded coordinator
+    assert!(confirmed_anchors > 0);
+}
 
-    reveal_block(
-        path_blinded,
-        &sort_db_blind,
-        &mut coord_blind,
-        &stacks_blocks[0].0,
-        &stacks_blocks[0].1,
-    );
+#[test]
+fn test_canonical_tip_accessors() {
+    let path = "/tmp/stacks-blockchain.test.canonical_tip_accessors";
+    let _r = std::fs::remove_dir_all(path);
 
-    // after revealing ``0``, we should now have the anchor block for
-    //   the first reward cycle after the initial one
+    let vrf_keys: Vec<_> = (0..1).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..1).map(|_| StacksPrivateKey::new()).collect();
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "110");
-    }
+    setup_states(&[path], &vrf_keys, &committers);
 
-    // now, the blinded node should be able to process blocks 10 and 11
-    //   10 will process fine, because its parent has consensus hash =
-    //       INITIAL_CONSENSUS_HASH
-    //   11 will NOT process fine, even though it _should_, because its parents
-    //     consensus hash is different than the consensus hash of the parent when it was mined
+    let mut coord = make_coordinator(path);
+    coord.handle_new_burnchain_block().unwrap();
 
-    let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-        &sort_db_blind.index_conn(),
-        &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
-        &stacks_blocks[10].1.block_hash(),
-    )
-    .unwrap()
-    .unwrap()
-    .sortition_id;
+    let sort_db = get_sortition_db(path);
 
-    reveal_block(
-        path_blinded,
-        &sort_db_blind,
-        &mut coord_blind,
-        &sort_id,
-        &stacks_blocks[10].1,
+    // no Stacks block processed yet
+    assert_eq!(coord.canonical_stacks_tip(), None);
+    assert_eq!(
+        coord.canonical_sortition_tip(),
+        Some(SortitionDB::get_canonical_sortition_tip(sort_db.conn()).unwrap())
     );
 
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(2));
-    eprintln!("Processed block 10 okay!");
+    let mut burnchain = get_burnchain_db(path);
+    let mut chainstate = get_chainstate(path);
+    let (op, block) = make_genesis_block(
+        &sort_db,
+        &mut chainstate,
+        &BlockHeaderHash([0; 32]),
+        &committers[0],
+        10000,
+        &vrf_keys[0],
+        0,
+    );
+    let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+    produce_burn_block(
+        &mut burnchain,
+        &burnchain_tip.block_hash,
+        vec![op],
+        vec![].iter_mut(),
+    );
+    coord.handle_new_burnchain_block().unwrap();
 
-    // won't successfully process the block
-    let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-        &sort_db_blind.index_conn(),
-        &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
-        &stacks_blocks[11].1.block_hash(),
-    )
-    .unwrap()
-    .unwrap()
-    .sortition_id;
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    preprocess_block(&mut chainstate, &sort_db, &tip, block);
+    coord.handle_new_stacks_block().unwrap();
 
-    reveal_block(
-        path_blinded,
-        &sort_db_blind,
-        &mut coord_blind,
-        &sort_id,
-        &stacks_blocks[11].1,
+    let stacks_tip = SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
+    assert_eq!(
+        coord.canonical_stacks_tip(),
+        Some(StacksBlockId::new(&stacks_tip.0, &stacks_tip.1))
     );
-
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(3));
-    eprintln!("Processed block 11 okay!");
+    assert_eq!(
+        coord.canonical_sortition_tip(),
+        Some(SortitionDB::get_canonical_sortition_tip(sort_db.conn()).unwrap())
+    );
+    assert_eq!(coord.canonical_sortition_tip(), Some(tip.sortition_id));
 }
 
 #[test]
-fn test_pox_no_anchor_selected() {
-    let path = "/tmp/stacks-blockchain.test.pox_fork_no_anchor_selected";
-    // setup a second set of states that won't see the broadcasted blocks
-    let path_blinded = "/tmp/stacks-blockchain.test.pox_fork_no_anchor_selected.blinded";
+fn test_block_status() {
+    let path = "/tmp/stacks-blockchain.test.block_status";
     let _r = std::fs::remove_dir_all(path);
-    let _r = std::fs::remove_dir_all(path_blinded);
 
-    let vrf_keys: Vec<_> = (0..10).map(|_| VRFPrivateKey::new()).collect();
-    let committers: Vec<_> = (0..10).map(|_| StacksPrivateKey::new()).collect();
+    let vrf_keys: Vec<_> = (0..1).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..1).map(|_| StacksPrivateKey::new()).collect();
 
-    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+    setup_states(&[path], &vrf_keys, &committers);
 
     let mut coord = make_coordinator(path);
-    let mut coord_blind = make_coordinator(path_blinded);
-
     coord.handle_new_burnchain_block().unwrap();
-    coord_blind.handle_new_burnchain_block().unwrap();
 
     let sort_db = get_sortition_db(path);
+    let mut burnchain = get_burnchain_db(path);
+    let mut chainstate = get_chainstate(path);
+    let (op, block) = make_genesis_block(
+        &sort_db,
+        &mut chainstate,
+        &BlockHeaderHash([0; 32]),
+        &committers[0],
+        10000,
+        &vrf_keys[0],
+        0,
+    );
+    let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+    produce_burn_block(
+        &mut burnchain,
+        &burnchain_tip.block_hash,
+        vec![op],
+        vec![].iter_mut(),
+    );
+    coord.handle_new_burnchain_block().unwrap();
 
     let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
-    assert_eq!(tip.block_height, 1);
-    assert_eq!(tip.sortition, false);
-    let (_, ops) = sort_db
-        .get_sortition_result(&tip.sortition_id)
-        .unwrap()
-        .unwrap();
+    let block_id = StacksBlockId::new(&tip.consensus_hash, &block.block_hash());
+    assert_eq!(coord.block_status(&block_id).unwrap(), BlockStatus::Unknown);
 
-    let sort_db_blind = get_sortition_db(path_blinded);
+    preprocess_block(&mut chainstate, &sort_db, &tip, block);
+    assert_eq!(coord.block_status(&block_id).unwrap(), BlockStatus::Staging);
 
-    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
-    assert_eq!(tip.block_height, 1);
-    assert_eq!(tip.sortition, false);
-    let (_, ops) = sort_db_blind
-        .get_sortition_result(&tip.sortition_id)
-        .unwrap()
-        .unwrap();
+    coord.handle_new_stacks_block().unwrap();
+    assert_eq!(
+        coord.block_status(&block_id).unwrap(),
+        BlockStatus::Processed
+    );
+    assert_eq!(coord.canonical_stacks_tip(), Some(block_id));
+}
 
-    // we should have all the VRF registrations accepted
-    assert_eq!(ops.accepted_ops.len(), vrf_keys.len());
-    assert_eq!(ops.consumed_leader_keys.len(), 0);
+#[test]
+fn test_replay_burnchain_range() {
+    let path = "/tmp/stacks-blockchain-replay-burnchain-range";
+    let _r = std::fs::remove_dir_all(path);
 
-    // at first, sortition_ids shouldn't have diverged
-    //  but once the first reward cycle begins, they should diverge.
-    let mut sortition_ids_diverged = false;
-    // process sequential blocks, and their sortitions...
-    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
-    let mut anchor_blocks = vec![];
+    let vrf_keys: Vec<_> = (0..10).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..10).map(|_| StacksPrivateKey::new()).collect();
 
-    // setup:
-    //   0 - 1 - 2 - 3 - 4 - 5 - 6
-    //    \_ 7   \_ 8 _ 9
+    setup_states(&[path], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    coord.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    let mut parent = BlockHeaderHash([0; 32]);
     for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
         let mut burnchain = get_burnchain_db(path);
         let mut chainstate = get_chainstate(path);
-        eprintln!("Making block {}", ix);
         let (op, block) = if ix == 0 {
             make_genesis_block(
                 &sort_db,
                 &mut chainstate,
-                &BlockHeaderHash([0; 32]),
+                &parent,
                 miner,
                 10000,
                 vrf_key,
                 ix as u32,
             )
         } else {
-            let parent = if ix == 7 {
-                stacks_blocks[0].1.header.block_hash()
-            } else if ix == 8 {
-                stacks_blocks[2].1.header.block_hash()
-            } else {
-                stacks_blocks[ix - 1].1.header.block_hash()
-            };
             make_stacks_block(
                 &sort_db,
                 &mut chainstate,
@@ -1280,139 +1076,69 @@ fn test_pox_no_anchor_selected() {
             )
         };
         let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
-        let burnchain_blinded = get_burnchain_db(path_blinded);
         produce_burn_block(
             &mut burnchain,
             &burnchain_tip.block_hash,
             vec![op],
-            [burnchain_blinded].iter_mut(),
+            vec![].iter_mut(),
         );
-        // handle the sortition
         coord.handle_new_burnchain_block().unwrap();
-        coord_blind.handle_new_burnchain_block().unwrap();
-
-        let b = get_burnchain(path);
-        let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
-        if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
-            eprintln!(
-                "Reward cycle start at height={}",
-                new_burnchain_tip.block_height
-            );
-            // processed first anchor block, not expecting a second one!
-            if anchor_blocks.len() == 1 {
-                let ic = sort_db.index_handle_at_tip();
-                assert!(
-                    ic.get_last_anchor_block_hash().unwrap().is_none(),
-                    "No anchor block should have been chosen!"
-                );
-            } else {
-                // the "blinded" sortition db and the one that's processed all the blocks
-                //   should have diverged in sortition_ids now...
-                sortition_ids_diverged = true;
-                // store the anchor block for this sortition for later checking
-                let ic = sort_db.index_handle_at_tip();
-                let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
-                eprintln!(
-                    "Anchor block={}, selected at height={}",
-                    &bhh,
-                    SortitionDB::get_block_snapshot_for_winning_stacks_block(
-                        &sort_db.index_conn(),
-                        &ic.context.chain_tip,
-                        &bhh
-                    )
-                    .unwrap()
-                    .unwrap()
-                    .block_height
-                );
-                anchor_blocks.push(bhh);
-            }
-        }
 
         let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
-        let blinded_tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
-        if sortition_ids_diverged {
-            assert_ne!(
-                tip.sortition_id, blinded_tip.sortition_id,
-                "Sortitions should have diverged by block height = {}",
-                blinded_tip.block_height
-            );
-        } else {
-            assert_eq!(
-                tip.sortition_id, blinded_tip.sortition_id,
-                "Sortitions should not have diverged at block height = {}",
-                blinded_tip.block_height
-            );
-        }
-
-        // load the block into staging
         let block_hash = block.header.block_hash();
-        eprintln!("Block hash={}, ix={}", &block_hash, ix);
-
-        assert_eq!(&tip.winning_stacks_block_hash, &block_hash);
-        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
-
         preprocess_block(&mut chainstate, &sort_db, &tip, block);
-
-        // handle the stacks block
         coord.handle_new_stacks_block().unwrap();
-    }
-
-    let block_height = eval_at_chain_tip(path, &sort_db, "block-height");
-    assert_eq!(block_height, Value::UInt(7));
-
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(0));
 
-    {
-        let ic = sort_db.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "111");
+        parent = block_hash;
     }
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "101");
-    }
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    let stacks_tip = SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
+    assert_eq!(stacks_tip.1, parent);
 
-    for (sort_id, block) in stacks_blocks.iter() {
-        reveal_block(
-            path_blinded,
-            &sort_db_blind,
-            &mut coord_blind,
-            &sort_id,
-            block,
-        );
+    // the sortition tip is ahead of the replay range
+    match coord.replay_burnchain_range(3, 7, false) {
+        Err(CoordError::SortitionTipAhead(tip_height, start_height)) => {
+            assert_eq!(tip_height, tip.block_height);
+            assert_eq!(start_height, 3);
+        }
+        x => panic!("Unexpected replay result: {:?}", x),
     }
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "111");
+    match coord.replay_burnchain_range(7, 3, true) {
+        Err(CoordError::InvalidReplayRange(7, 3)) => {}
+        x => panic!("Unexpected replay result: {:?}", x),
     }
 
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(7));
+    // forced replay re-evaluates every sortition in the range, and leaves the chain tips where
+    // they were
+    assert_eq!(coord.replay_burnchain_range(3, 7, true).unwrap(), 5);
+
+    let replayed_tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    let replayed_stacks_tip =
+        SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
+    assert_eq!(replayed_tip, tip);
+    assert_eq!(replayed_stacks_tip, stacks_tip);
 }
 
 #[test]
-fn test_pox_fork_out_of_order() {
-    let path = "/tmp/stacks-blockchain.test.pox_fork_out_of_order";
-    // setup a second set of states that won't see the broadcasted blocks
-    let path_blinded = "/tmp/stacks-blockchain.test.pox_fork_out_of_order.blinded";
+fn test_sortition_with_reward_set() {
+    let path = "/tmp/stacks-blockchain-simple-reward-set";
     let _r = std::fs::remove_dir_all(path);
-    let _r = std::fs::remove_dir_all(path_blinded);
 
-    let vrf_keys: Vec<_> = (0..15).map(|_| VRFPrivateKey::new()).collect();
-    let committers: Vec<_> = (0..15).map(|_| StacksPrivateKey::new()).collect();
+    let mut vrf_keys: Vec<_> = (0..150).map(|_| VRFPrivateKey::new()).collect();
+    let mut committers: Vec<_> = (0..150).map(|_| StacksPrivateKey::new()).collect();
 
-    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+    let reward_set_size = 5;
+    let reward_set: Vec<_> = (0..reward_set_size)
+        .map(|_| p2pkh_from(&StacksPrivateKey::new()))
+        .collect();
 
-    let mut coord = make_coordinator(path);
-    let mut coord_blind = make_coordinator(path_blinded);
+    setup_states(&[path], &vrf_keys, &committers);
+
+    let mut coord = make_reward_set_coordinator(path, reward_set);
 
     coord.handle_new_burnchain_block().unwrap();
-    coord_blind.handle_new_burnchain_block().unwrap();
 
     let sort_db = get_sortition_db(path);
 
@@ -1424,55 +1150,98 @@ fn test_pox_fork_out_of_order() {
         .unwrap()
         .unwrap();
 
-    let sort_db_blind = get_sortition_db(path_blinded);
-
-    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
-    assert_eq!(tip.block_height, 1);
-    assert_eq!(tip.sortition, false);
-    let (_, ops) = sort_db_blind
-        .get_sortition_result(&tip.sortition_id)
-        .unwrap()
-        .unwrap();
-
     // we should have all the VRF registrations accepted
     assert_eq!(ops.accepted_ops.len(), vrf_keys.len());
     assert_eq!(ops.consumed_leader_keys.len(), 0);
 
-    // at first, sortition_ids shouldn't have diverged
-    //  but once the first reward cycle begins, they should diverge.
-    let mut sortition_ids_diverged = false;
+    let mut started_first_reward_cycle = false;
     // process sequential blocks, and their sortitions...
     let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
     let mut anchor_blocks = vec![];
 
-    // setup:
-    //  2 forks: 0 - 1 - 2 - 3 - 4 - 5 - 11 - 12 - 13 - 14 - 15
-    //            \_ 6 _ 7 _ 8 _ 9 _ 10
-    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+    // split up the vrf keys and committers so that we have some that will be mining "correctly"
+    //   and some that will be producing bad outputs
+
+    let BURNER_OFFSET = 50;
+    let mut vrf_key_burners = vrf_keys.split_off(50);
+    let mut miner_burners = committers.split_off(50);
+
+    let WRONG_OUTS_OFFSET = 100;
+    let vrf_key_wrong_outs = vrf_key_burners.split_off(50);
+    let miner_wrong_outs = miner_burners.split_off(50);
+
+    // track the reward set consumption
+    let mut reward_recipients = HashSet::new();
+    for ix in 0..vrf_keys.len() {
+        let vrf_key = &vrf_keys[ix];
+        let miner = &committers[ix];
+
+        let vrf_burner = &vrf_key_burners[ix];
+        let miner_burner = &miner_burners[ix];
+
+        let vrf_wrong_out = &vrf_key_wrong_outs[ix];
+        let miner_wrong_out = &miner_wrong_outs[ix];
+
         let mut burnchain = get_burnchain_db(path);
         let mut chainstate = get_chainstate(path);
-        eprintln!("Making block {}", ix);
-        let (op, block) = if ix == 0 {
-            make_genesis_block(
+
+        let parent = if ix == 0 {
+            BlockHeaderHash([0; 32])
+        } else if ix == 49 {
+            // lets mine off a block that _isn't_ a descendant of our PoX anchor
+            stacks_blocks[1].1.header.block_hash()
+        } else {
+            stacks_blocks[ix - 1].1.header.block_hash()
+        };
+
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let next_mock_header = BurnchainBlockHeader {
+            block_height: burnchain_tip.block_height + 1,
+            block_hash: BurnchainHeaderHash([0; 32]),
+            parent_block_hash: burnchain_tip.block_hash,
+            num_txs: 0,
+            timestamp: 1,
+        };
+
+        let reward_cycle_info = coord.get_reward_cycle_info(&next_mock_header).unwrap();
+        if reward_cycle_info.is_some() {
+            // did we process a reward set last cycle? check if the
+            //  recipient set size matches our expectation
+            if started_first_reward_cycle {
+                assert_eq!(reward_recipients.len(), reward_set_size);
+            }
+            // clear the reward recipients tracker, since those
+            //  recipients are now eligible again in the new reward cycle
+            reward_recipients.clear();
+        }
+        let next_block_recipients = get_rw_sortdb(path)
+            .test_get_next_block_recipients(reward_cycle_info.as_ref())
+            .unwrap();
+        if let Some(ref next_block_recipients) = next_block_recipients {
+            let (addr, _) = next_block_recipients.recipient;
+            assert!(
+                !reward_recipients.contains(&addr),
+                "Reward set should not already contain address {}",
+                addr
+            );
+            eprintln!("At iteration: {}, inserting address ... {}", ix, addr);
+            reward_recipients.insert(addr.clone());
+        }
+
+        let (good_op, mut block) = if ix == 0 {
+            make_genesis_block_with_recipients(
                 &sort_db,
                 &mut chainstate,
-                &BlockHeaderHash([0; 32]),
+                &parent,
                 miner,
                 10000,
                 vrf_key,
                 ix as u32,
+                next_block_recipients.as_ref(),
+                &[],
             )
         } else {
-            let parent = if ix == 1 {
-                stacks_blocks[0].1.header.block_hash()
-            } else if ix == 6 {
-                stacks_blocks[0].1.header.block_hash()
-            } else if ix == 11 {
-                stacks_blocks[5].1.header.block_hash()
-            } else {
-                stacks_blocks[ix - 1].1.header.block_hash()
-            };
-            make_stacks_block(
+            make_stacks_block_with_recipients(
                 &sort_db,
                 &mut chainstate,
                 &parent,
@@ -1480,68 +1249,86 @@ fn test_pox_fork_out_of_order() {
                 10000,
                 vrf_key,
                 ix as u32,
+                next_block_recipients.as_ref(),
             )
         };
-        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
-        let burnchain_blinded = get_burnchain_db(path_blinded);
-        produce_burn_block(
-            &mut burnchain,
-            &burnchain_tip.block_hash,
-            vec![op],
-            [burnchain_blinded].iter_mut(),
-        );
-        // handle the sortition
-        coord.handle_new_burnchain_block().unwrap();
-        coord_blind.handle_new_burnchain_block().unwrap();
 
-        let b = get_burnchain(path);
-        let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
-        if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
-            eprintln!(
-                "Reward cycle start at height={}",
-                new_burnchain_tip.block_height
-            );
-            // the "blinded" sortition db and the one that's processed all the blocks
-            //   should have diverged in sortition_ids now...
-            sortition_ids_diverged = true;
-            // store the anchor block for this sortition for later checking
-            let ic = sort_db.index_handle_at_tip();
-            let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
-            eprintln!(
-                "Anchor block={}, selected at height={}",
-                &bhh,
-                SortitionDB::get_block_snapshot_for_winning_stacks_block(
-                    &sort_db.index_conn(),
-                    &ic.context.chain_tip,
-                    &bhh
-                )
-                .unwrap()
-                .unwrap()
-                .block_height
-            );
-
-            anchor_blocks.push(bhh);
-        }
+        let mut expected_winner = good_op.txid();
+        let mut ops = vec![good_op];
 
-        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
-        let blinded_tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
-        if sortition_ids_diverged {
-            assert_ne!(
-                tip.sortition_id, blinded_tip.sortition_id,
-                "Sortitions should have diverged by block height = {}",
-                blinded_tip.block_height
-            );
-        } else {
-            assert_eq!(
-                tip.sortition_id, blinded_tip.sortition_id,
-                "Sortitions should not have diverged at block height = {}",
-                blinded_tip.block_height
+        if started_first_reward_cycle {
+            // make the bad commitments
+            // only create bad "burn" commitments if we know they'll be rejected:
+            //   (a) we're in a reward cycle _and_ (b) there are expected recipients
+            if next_block_recipients.is_some() {
+                let (all_burn_op, all_burn_block) = make_stacks_block_with_recipients(
+                    &sort_db,
+                    &mut chainstate,
+                    &parent,
+                    miner_burner,
+                    10000,
+                    vrf_burner,
+                    (ix + BURNER_OFFSET) as u32,
+                    None,
+                );
+                if ix == 49 {
+                    // at this ix, _all_burn_block_ should be the winner
+                    //   because "parent" isn't a descendant of the PoX anchor
+                    expected_winner = all_burn_op.txid();
+                    block = all_burn_block;
+                }
+                ops.push(all_burn_op);
+            }
+
+            // sometime have the wrong _number_ of recipients,
+            //   other times just have the wrong set of recipients
+            let recipient = if ix % 2 == 0 {
+                (p2pkh_from(miner_wrong_out), 0)
+            } else {
+                (p2pkh_from(&StacksPrivateKey::new()), 0)
+            };
+            let bad_block_recipipients = Some(RewardSetInfo {
+                anchor_block: BlockHeaderHash([0; 32]),
+                recipient,
+            });
+            let (bad_outs_op, _) = make_stacks_block_with_recipients(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner_wrong_out,
+                10000,
+                vrf_burner,
+                (ix + WRONG_OUTS_OFFSET) as u32,
+                bad_block_recipipients.as_ref(),
             );
+            ops.push(bad_outs_op);
+        }
+
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            ops,
+            vec![].iter_mut(),
+        );
+        // handle the sortition
+        coord.handle_new_burnchain_block().unwrap();
+
+        let b = get_burnchain(path);
+        let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
+            started_first_reward_cycle = true;
+            // store the anchor block for this sortition for later checking
+            let ic = sort_db.index_handle_at_tip();
+            let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
+            anchor_blocks.push(bhh);
         }
 
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        assert_eq!(&tip.winning_block_txid, &expected_winner);
+
         // load the block into staging
         let block_hash = block.header.block_hash();
-        eprintln!("Block hash={}, ix={}", &block_hash, ix);
 
         assert_eq!(&tip.winning_stacks_block_hash, &block_hash);
         stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
@@ -1552,237 +1339,2102 @@ fn test_pox_fork_out_of_order() {
         coord.handle_new_stacks_block().unwrap();
     }
 
-    let block_height = eval_at_chain_tip(path, &sort_db, "block-height");
-    assert_eq!(block_height, Value::UInt(10));
-
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(0));
+    let stacks_tip = SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
+    let mut chainstate = get_chainstate(path);
+    assert_eq!(
+        chainstate.with_read_only_clarity_tx(
+            &sort_db.index_conn(),
+            &StacksBlockId::new(&stacks_tip.0, &stacks_tip.1),
+            |conn| conn
+                .with_readonly_clarity_env(
+                    PrincipalData::parse("SP3Q4A5WWZ80REGBN0ZXNE540ECJ9JZ4A765Q5K2Q").unwrap(),
+                    LimitedCostTracker::new_max_limit(),
+                    |env| env.eval_raw("block-height")
+                )
+                .unwrap()
+        ),
+        // we only got to block height 49, because of the little fork at the end.
+        Value::UInt(49)
+    );
 
     {
         let ic = sort_db.index_handle_at_tip();
         let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "1111");
+        assert_eq!(&pox_id.to_string(),
+                   "11111111111",
+                   "PoX ID should reflect the 10 reward cycles _with_ a known anchor block, plus the 'initial' known reward cycle at genesis");
     }
+}
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "1000");
-    }
+#[test]
+fn test_get_next_recipients_cached() {
+    let path = "/tmp/stacks-blockchain-next-recipients-cached";
+    let _r = std::fs::remove_dir_all(path);
 
-    // now, we reveal to the blinded coordinator, but out of order.
-    //  reveal block 0 first,
-    //   then the 6-7-8-9-10 fork.
-    //   then reveal 1-2-3-4-5
-    //   then reveal 11-12-13-14
+    let vrf_keys: Vec<_> = (0..50).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..50).map(|_| StacksPrivateKey::new()).collect();
 
-    reveal_block(
-        path_blinded,
-        &sort_db_blind,
-        &mut coord_blind,
-        &stacks_blocks[0].0,
-        &stacks_blocks[0].1,
-    );
+    let reward_set: Vec<_> = (0..5)
+        .map(|_| p2pkh_from(&StacksPrivateKey::new()))
+        .collect();
 
-    // after revealing ``0``, we should now have the anchor block for
-    //   the 6-7-8-9-10 fork
+    setup_states(&[path], &vrf_keys, &committers);
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "1110");
-    }
+    let mut coord = make_reward_set_coordinator(path, reward_set.clone());
 
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(1));
+    coord.handle_new_burnchain_block().unwrap();
 
-    // reveal [6-10]
-    for (_sort_id, block) in stacks_blocks[6..=10].iter() {
-        // cannot use sort_id from stacks_blocks, because the blinded coordinator
-        //   has different sortition_id's for blocks 6-10 (because it's missing
-        //   the 2nd anchor block).
-        let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-            &sort_db_blind.index_conn(),
-            &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
-            &block.header.block_hash(),
-        )
-        .unwrap()
-        .unwrap()
-        .sortition_id;
-        reveal_block(
-            path_blinded,
-            &sort_db_blind,
-            &mut coord_blind,
-            &sort_id,
-            block,
+    let sort_db = get_sortition_db(path);
+    let b = get_burnchain(path);
+
+    // mine sequential blocks until the _next_ burn block starts a reward cycle
+    //  whose anchor block we've processed
+    let mut parent = BlockHeaderHash([0; 32]);
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            vec![].iter_mut(),
         );
+        coord.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        let block_hash = block.header.block_hash();
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
+
+        parent = block_hash;
+
+        if ix > (b.pox_constants.reward_cycle_length as usize)
+            && b.is_reward_cycle_start(tip.block_height + 1)
+        {
+            break;
+        }
     }
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "1110");
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    assert!(b.is_reward_cycle_start(tip.block_height + 1));
+
+    let mut sort_db = get_rw_sortdb(path);
+    let mut chainstate = get_chainstate(path);
+    let provider = CountingRewardSetProvider(reward_set, Cell::new(0));
+    let mut cache = RewardCycleInfoCache::new();
+
+    let first = get_next_recipients_cached(
+        &tip,
+        &mut chainstate,
+        &mut sort_db,
+        &b,
+        &provider,
+        &mut cache,
+    )
+    .unwrap();
+    assert!(first.is_some());
+    assert_eq!(provider.1.get(), 1);
+
+    let second = get_next_recipients_cached(
+        &tip,
+        &mut chainstate,
+        &mut sort_db,
+        &b,
+        &provider,
+        &mut cache,
+    )
+    .unwrap();
+    assert_eq!(first, second);
+    // served from the cache
+    assert_eq!(provider.1.get(), 1);
+
+    // the uncached variant always recomputes
+    let uncached =
+        get_next_recipients(&tip, &mut chainstate, &mut sort_db, &b, &provider).unwrap();
+    assert_eq!(first, uncached);
+    assert_eq!(provider.1.get(), 2);
+
+    // invalidating the cache forces a recomputation
+    cache.invalidate();
+    let third = get_next_recipients_cached(
+        &tip,
+        &mut chainstate,
+        &mut sort_db,
+        &b,
+        &provider,
+        &mut cache,
+    )
+    .unwrap();
+    assert_eq!(first, third);
+    assert_eq!(provider.1.get(), 3);
+}
+
+#[test]
+fn test_import_chainstate_snapshot() {
+    let path = "/tmp/stacks-blockchain-import-snapshot";
+    let snapshot_path = "/tmp/stacks-blockchain-import-snapshot.snapshot";
+    let import_path = "/tmp/stacks-blockchain-import-snapshot.import";
+    let bad_import_path = "/tmp/stacks-blockchain-import-snapshot.bad-import";
+    for p in [path, snapshot_path, import_path, bad_import_path].iter() {
+        let _r = std::fs::remove_dir_all(p);
     }
 
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(6));
+    let vrf_keys: Vec<_> = (0..5).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..5).map(|_| StacksPrivateKey::new()).collect();
 
-    let block_hash = eval_at_chain_tip(
-        path_blinded,
-        &sort_db_blind,
-        "(get-block-info? header-hash u5)",
-    );
-    assert_eq!(
-        block_hash,
-        Value::some(
-            Value::buff_from(stacks_blocks[9].1.header.block_hash().as_bytes().to_vec()).unwrap()
-        )
-        .unwrap()
-    );
+    setup_states(&[path], &vrf_keys, &committers);
 
-    // reveal [1-5]
-    for (_sort_id, block) in stacks_blocks[1..=5].iter() {
-        // cannot use sort_id from stacks_blocks, because the blinded coordinator
-        //   has different sortition_id's for blocks 6-10 (because it's missing
-        //   the 2nd anchor block).
-        let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-            &sort_db_blind.index_conn(),
-            &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
-            &block.header.block_hash(),
-        )
-        .unwrap()
-        .unwrap()
-        .sortition_id;
+    let mut coord = make_coordinator(path);
+    coord.handle_new_burnchain_block().unwrap();
 
-        // before processing the last of these blocks, the stacks_block[9] should still
-        //   be the canonical tip
-        let block_hash = eval_at_chain_tip(
-            path_blinded,
-            &sort_db_blind,
-            "(get-block-info? header-hash u5)",
-        );
+    let sort_db = get_sortition_db(path);
+
+    // build a small chain
+    let mut parent = BlockHeaderHash([0; 32]);
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            vec![].iter_mut(),
+        );
+        coord.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        let block_hash = block.header.block_hash();
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
+
+        parent = block_hash;
+    }
+
+    let (consensus_hash, block_hash) =
+        SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
+    let expected_tip = StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash);
+    let expected_height = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+        get_chainstate(path).headers_db(),
+        &expected_tip,
+    )
+    .unwrap()
+    .unwrap()
+    .block_height;
+    assert_eq!(expected_height, vrf_keys.len() as u64);
+
+    // export the DB directories
+    let b = get_burnchain(path);
+    let burnchain_dir =
+        Burnchain::get_chainstate_path(&b.working_dir, &b.chain_name, &b.network_name);
+    copy_dir_all(
+        Path::new(&burnchain_dir),
+        &Path::new(snapshot_path).join("burnchain"),
+    )
+    .unwrap();
+    copy_dir_all(
+        Path::new(&get_chainstate_path(path)),
+        &Path::new(snapshot_path).join("chainstate"),
+    )
+    .unwrap();
+
+    // a snapshot whose tip isn't the expected checkpoint is rejected, and leaves nothing behind
+    let bad_tip = StacksBlockId([0x11; 32]);
+    match import_chainstate_snapshot(
+        snapshot_path,
+        &get_burnchain(bad_import_path),
+        &get_chainstate_path(bad_import_path),
+        false,
+        0x80000000,
+        &bad_tip,
+    ) {
+        Err(CoordError::SnapshotTipMismatch(expected, found)) => {
+            assert_eq!(expected, bad_tip);
+            assert_eq!(found, expected_tip);
+        }
+        x => panic!("Expected a snapshot tip mismatch, got {:?}", x),
+    }
+    assert!(!Path::new(&get_chainstate_path(bad_import_path)).exists());
+    assert!(!Path::new(&get_burnchain(bad_import_path).get_db_path()).exists());
+
+    // the snapshot is accepted at its actual tip
+    import_chainstate_snapshot(
+        snapshot_path,
+        &get_burnchain(import_path),
+        &get_chainstate_path(import_path),
+        false,
+        0x80000000,
+        &expected_tip,
+    )
+    .unwrap();
+
+    // ...and can't be imported over an existing chainstate
+    match import_chainstate_snapshot(
+        snapshot_path,
+        &get_burnchain(import_path),
+        &get_chainstate_path(import_path),
+        false,
+        0x80000000,
+        &expected_tip,
+    ) {
+        Err(CoordError::SnapshotIOError(_)) => {}
+        x => panic!("Expected a snapshot I/O error, got {:?}", x),
+    }
+
+    // a coordinator on the imported chainstate resumes at the snapshot's tip,
+    //  without reprocessing any blocks
+    let mut imported_coord = make_coordinator(import_path);
+    imported_coord.handle_new_burnchain_block().unwrap();
+    imported_coord.handle_new_stacks_block().unwrap();
+
+    let imported_sort_db = get_sortition_db(import_path);
+    let (consensus_hash, block_hash) =
+        SortitionDB::get_canonical_stacks_chain_tip_hash(imported_sort_db.conn()).unwrap();
+    assert_eq!(
+        StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash),
+        expected_tip
+    );
+
+    let imported_chainstate = get_chainstate(import_path);
+    let header = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+        imported_chainstate.headers_db(),
+        &expected_tip,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(header.block_height, expected_height);
+    assert!(
+        imported_chainstate
+            .get_stacks_chain_tip(&imported_sort_db)
+            .unwrap()
+            .unwrap()
+            .processed
+    );
+}
+
+#[test]
+// This test should panic until the MARF stability issue
+// https://github.com/blockstack/stacks-blockchain/issues/1805
+// is resolved:
+#[should_panic]
+/// Test a block that is processable in 2 PoX forks:
+///   block "11" should be processable in both `111` and `110`
+///   (because its parent is block `0`, and nobody stacks in
+///    this test, all block commits must burn)
+fn test_pox_processable_block_in_different_pox_forks() {
+    let path = "/tmp/stacks-blockchain.test.pox_processable_block_in_different_pox_forks";
+    // setup a second set of states that won't see the broadcasted blocks
+    let path_blinded =
+        "/tmp/stacks-blockchain.test.pox_processable_block_in_different_pox_forks.blinded";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_blinded);
+
+    let vrf_keys: Vec<_> = (0..12).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..12).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    let mut coord_blind = make_coordinator(path_blinded);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_blind.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+    assert_eq!(tip.sortition, false);
+    let (_, ops) = sort_db
+        .get_sortition_result(&tip.sortition_id)
+        .unwrap()
+        .unwrap();
+
+    let sort_db_blind = get_sortition_db(path_blinded);
+
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+    assert_eq!(tip.sortition, false);
+    let (_, ops) = sort_db_blind
+        .get_sortition_result(&tip.sortition_id)
+        .unwrap()
+        .unwrap();
+
+    // we should have all the VRF registrations accepted
+    assert_eq!(ops.accepted_ops.len(), vrf_keys.len());
+    assert_eq!(ops.consumed_leader_keys.len(), 0);
+
+    // at first, sortition_ids shouldn't have diverged
+    //  but once the first reward cycle begins, they should diverge.
+    let mut sortition_ids_diverged = false;
+    // process sequential blocks, and their sortitions...
+    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
+    let mut anchor_blocks = vec![];
+
+    // setup:
+    //   0 - 1 - 2 - 3 - 4 - 5 - 6 - 7 - 8 - 9
+    //    \_ 10 _ 11
+    //  blocks `10` and `11` can be processed either
+    //    in PoX fork 111 or in 110
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        eprintln!("Making block {}", ix);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &BlockHeaderHash([0; 32]),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            let parent = if ix == 10 {
+                stacks_blocks[0].1.header.block_hash()
+            } else {
+                stacks_blocks[ix - 1].1.header.block_hash()
+            };
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let burnchain_blinded = get_burnchain_db(path_blinded);
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [burnchain_blinded].iter_mut(),
+        );
+        // handle the sortition
+        coord.handle_new_burnchain_block().unwrap();
+        coord_blind.handle_new_burnchain_block().unwrap();
+
+        let b = get_burnchain(path);
+        let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
+            eprintln!(
+                "Reward cycle start at height={}",
+                new_burnchain_tip.block_height
+            );
+            // the "blinded" sortition db and the one that's processed all the blocks
+            //   should have diverged in sortition_ids now...
+            sortition_ids_diverged = true;
+            // store the anchor block for this sortition for later checking
+            let ic = sort_db.index_handle_at_tip();
+            let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
+            eprintln!(
+                "Anchor block={}, selected at height={}",
+                &bhh,
+                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                    &sort_db.index_conn(),
+                    &ic.context.chain_tip,
+                    &bhh
+                )
+                .unwrap()
+                .unwrap()
+                .block_height
+            );
+            anchor_blocks.push(bhh);
+        }
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        let blinded_tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+        if sortition_ids_diverged {
+            assert_ne!(
+                tip.sortition_id, blinded_tip.sortition_id,
+                "Sortitions should have diverged by block height = {}",
+                blinded_tip.block_height
+            );
+        } else {
+            assert_eq!(
+                tip.sortition_id, blinded_tip.sortition_id,
+                "Sortitions should not have diverged at block height = {}",
+                blinded_tip.block_height
+            );
+        }
+
+        // load the block into staging
+        let block_hash = block.header.block_hash();
+        eprintln!("Block hash={}, ix={}", &block_hash, ix);
+
+        assert_eq!(&tip.winning_stacks_block_hash, &block_hash);
+        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+
+        // handle the stacks block
+        coord.handle_new_stacks_block().unwrap();
+    }
+
+    let block_height = eval_at_chain_tip(path, &sort_db, "block-height");
+    assert_eq!(block_height, Value::UInt(10));
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(0));
+
+    {
+        let ic = sort_db.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "111");
+    }
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "100");
+    }
+
+    // now, we reveal `0` to the blinded coordinator
+
+    reveal_block(
+        path_blinded,
+        &sort_db_blind,
+        &mut coord_blind,
+        &stacks_blocks[0].0,
+        &stacks_blocks[0].1,
+    );
+
+    // after revealing ``0``, we should now have the anchor block for
+    //   the first reward cycle after the initial one
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "110");
+    }
+
+    // now, the blinded node should be able to process blocks 10 and 11
+    //   10 will process fine, because its parent has consensus hash =
+    //       INITIAL_CONSENSUS_HASH
+    //   11 will NOT process fine, even though it _should_, because its parents
+    //     consensus hash is different than the consensus hash of the parent when it was mined
+
+    let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+        &sort_db_blind.index_conn(),
+        &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
+        &stacks_blocks[10].1.block_hash(),
+    )
+    .unwrap()
+    .unwrap()
+    .sortition_id;
+
+    reveal_block(
+        path_blinded,
+        &sort_db_blind,
+        &mut coord_blind,
+        &sort_id,
+        &stacks_blocks[10].1,
+    );
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(2));
+    eprintln!("Processed block 10 okay!");
+
+    // won't successfully process the block
+    let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+        &sort_db_blind.index_conn(),
+        &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
+        &stacks_blocks[11].1.block_hash(),
+    )
+    .unwrap()
+    .unwrap()
+    .sortition_id;
+
+    reveal_block(
+        path_blinded,
+        &sort_db_blind,
+        &mut coord_blind,
+        &sort_id,
+        &stacks_blocks[11].1,
+    );
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(3));
+    eprintln!("Processed block 11 okay!");
+}
+
+#[test]
+fn test_pox_no_anchor_selected() {
+    let path = "/tmp/stacks-blockchain.test.pox_fork_no_anchor_selected";
+    // setup a second set of states that won't see the broadcasted blocks
+    let path_blinded = "/tmp/stacks-blockchain.test.pox_fork_no_anchor_selected.blinded";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_blinded);
+
+    let vrf_keys: Vec<_> = (0..10).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..10).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    let mut coord_blind = make_coordinator(path_blinded);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_blind.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+    assert_eq!(tip.sortition, false);
+    let (_, ops) = sort_db
+        .get_sortition_result(&tip.sortition_id)
+        .unwrap()
+        .unwrap();
+
+    let sort_db_blind = get_sortition_db(path_blinded);
+
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+    assert_eq!(tip.sortition, false);
+    let (_, ops) = sort_db_blind
+        .get_sortition_result(&tip.sortition_id)
+        .unwrap()
+        .unwrap();
+
+    // we should have all the VRF registrations accepted
+    assert_eq!(ops.accepted_ops.len(), vrf_keys.len());
+    assert_eq!(ops.consumed_leader_keys.len(), 0);
+
+    // at first, sortition_ids shouldn't have diverged
+    //  but once the first reward cycle begins, they should diverge.
+    let mut sortition_ids_diverged = false;
+    // process sequential blocks, and their sortitions...
+    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
+    let mut anchor_blocks = vec![];
+
+    // setup:
+    //   0 - 1 - 2 - 3 - 4 - 5 - 6
+    //    \_ 7   \_ 8 _ 9
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        eprintln!("Making block {}", ix);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &BlockHeaderHash([0; 32]),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            let parent = if ix == 7 {
+                stacks_blocks[0].1.header.block_hash()
+            } else if ix == 8 {
+                stacks_blocks[2].1.header.block_hash()
+            } else {
+                stacks_blocks[ix - 1].1.header.block_hash()
+            };
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let burnchain_blinded = get_burnchain_db(path_blinded);
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [burnchain_blinded].iter_mut(),
+        );
+        // handle the sortition
+        coord.handle_new_burnchain_block().unwrap();
+        coord_blind.handle_new_burnchain_block().unwrap();
+
+        let b = get_burnchain(path);
+        let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
+            eprintln!(
+                "Reward cycle start at height={}",
+                new_burnchain_tip.block_height
+            );
+            // processed first anchor block, not expecting a second one!
+            if anchor_blocks.len() == 1 {
+                let ic = sort_db.index_handle_at_tip();
+                assert!(
+                    ic.get_last_anchor_block_hash().unwrap().is_none(),
+                    "No anchor block should have been chosen!"
+                );
+            } else {
+                // the "blinded" sortition db and the one that's processed all the blocks
+                //   should have diverged in sortition_ids now...
+                sortition_ids_diverged = true;
+                // store the anchor block for this sortition for later checking
+                let ic = sort_db.index_handle_at_tip();
+                let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
+                eprintln!(
+                    "Anchor block={}, selected at height={}",
+                    &bhh,
+                    SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                        &sort_db.index_conn(),
+                        &ic.context.chain_tip,
+                        &bhh
+                    )
+                    .unwrap()
+                    .unwrap()
+                    .block_height
+                );
+                anchor_blocks.push(bhh);
+            }
+        }
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        let blinded_tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+        if sortition_ids_diverged {
+            assert_ne!(
+                tip.sortition_id, blinded_tip.sortition_id,
+                "Sortitions should have diverged by block height = {}",
+                blinded_tip.block_height
+            );
+        } else {
+            assert_eq!(
+                tip.sortition_id, blinded_tip.sortition_id,
+                "Sortitions should not have diverged at block height = {}",
+                blinded_tip.block_height
+            );
+        }
+
+        // load the block into staging
+        let block_hash = block.header.block_hash();
+        eprintln!("Block hash={}, ix={}", &block_hash, ix);
+
+        assert_eq!(&tip.winning_stacks_block_hash, &block_hash);
+        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+
+        // handle the stacks block
+        coord.handle_new_stacks_block().unwrap();
+    }
+
+    let block_height = eval_at_chain_tip(path, &sort_db, "block-height");
+    assert_eq!(block_height, Value::UInt(7));
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(0));
+
+    {
+        let ic = sort_db.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "111");
+    }
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "101");
+    }
+
+    for (sort_id, block) in stacks_blocks.iter() {
+        reveal_block(
+            path_blinded,
+            &sort_db_blind,
+            &mut coord_blind,
+            &sort_id,
+            block,
+        );
+    }
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "111");
+    }
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(7));
+}
+
+#[test]
+fn test_pox_fork_out_of_order() {
+    let path = "/tmp/stacks-blockchain.test.pox_fork_out_of_order";
+    // setup a second set of states that won't see the broadcasted blocks
+    let path_blinded = "/tmp/stacks-blockchain.test.pox_fork_out_of_order.blinded";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_blinded);
+
+    let vrf_keys: Vec<_> = (0..15).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..15).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    let mut coord_blind = make_coordinator(path_blinded);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_blind.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+    assert_eq!(tip.sortition, false);
+    let (_, ops) = sort_db
+        .get_sortition_result(&tip.sortition_id)
+        .unwrap()
+        .unwrap();
+
+    let sort_db_blind = get_sortition_db(path_blinded);
+
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+    assert_eq!(tip.sortition, false);
+    let (_, ops) = sort_db_blind
+        .get_sortition_result(&tip.sortition_id)
+        .unwrap()
+        .unwrap();
+
+    // we should have all the VRF registrations accepted
+    assert_eq!(ops.accepted_ops.len(), vrf_keys.len());
+    assert_eq!(ops.consumed_leader_keys.len(), 0);
+
+    // at first, sortition_ids shouldn't have diverged
+    //  but once the first reward cycle begins, they should diverge.
+    let mut sortition_ids_diverged = false;
+    // process sequential blocks, and their sortitions...
+    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
+    let mut anchor_blocks = vec![];
+
+    // setup:
+    //  2 forks: 0 - 1 - 2 - 3 - 4 - 5 - 11 - 12 - 13 - 14 - 15
+    //            \_ 6 _ 7 _ 8 _ 9 _ 10
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        eprintln!("Making block {}", ix);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &BlockHeaderHash([0; 32]),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            let parent = if ix == 1 {
+                stacks_blocks[0].1.header.block_hash()
+            } else if ix == 6 {
+                stacks_blocks[0].1.header.block_hash()
+            } else if ix == 11 {
+                stacks_blocks[5].1.header.block_hash()
+            } else {
+                stacks_blocks[ix - 1].1.header.block_hash()
+            };
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let burnchain_blinded = get_burnchain_db(path_blinded);
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [burnchain_blinded].iter_mut(),
+        );
+        // handle the sortition
+        coord.handle_new_burnchain_block().unwrap();
+        coord_blind.handle_new_burnchain_block().unwrap();
+
+        let b = get_burnchain(path);
+        let new_burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        if b.is_reward_cycle_start(new_burnchain_tip.block_height) {
+            eprintln!(
+                "Reward cycle start at height={}",
+                new_burnchain_tip.block_height
+            );
+            // the "blinded" sortition db and the one that's processed all the blocks
+            //   should have diverged in sortition_ids now...
+            sortition_ids_diverged = true;
+            // store the anchor block for this sortition for later checking
+            let ic = sort_db.index_handle_at_tip();
+            let bhh = ic.get_last_anchor_block_hash().unwrap().unwrap();
+            eprintln!(
+                "Anchor block={}, selected at height={}",
+                &bhh,
+                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                    &sort_db.index_conn(),
+                    &ic.context.chain_tip,
+                    &bhh
+                )
+                .unwrap()
+                .unwrap()
+                .block_height
+            );
+
+            anchor_blocks.push(bhh);
+        }
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        let blinded_tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+        if sortition_ids_diverged {
+            assert_ne!(
+                tip.sortition_id, blinded_tip.sortition_id,
+                "Sortitions should have diverged by block height = {}",
+                blinded_tip.block_height
+            );
+        } else {
+            assert_eq!(
+                tip.sortition_id, blinded_tip.sortition_id,
+                "Sortitions should not have diverged at block height = {}",
+                blinded_tip.block_height
+            );
+        }
+
+        // load the block into staging
+        let block_hash = block.header.block_hash();
+        eprintln!("Block hash={}, ix={}", &block_hash, ix);
+
+        assert_eq!(&tip.winning_stacks_block_hash, &block_hash);
+        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+
+        // handle the stacks block
+        coord.handle_new_stacks_block().unwrap();
+    }
+
+    let block_height = eval_at_chain_tip(path, &sort_db, "block-height");
+    assert_eq!(block_height, Value::UInt(10));
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(0));
+
+    {
+        let ic = sort_db.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "1111");
+    }
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "1000");
+    }
+
+    // now, we reveal to the blinded coordinator, but out of order.
+    //  reveal block 0 first,
+    //   then the 6-7-8-9-10 fork.
+    //   then reveal 1-2-3-4-5
+    //   then reveal 11-12-13-14
+
+    reveal_block(
+        path_blinded,
+        &sort_db_blind,
+        &mut coord_blind,
+        &stacks_blocks[0].0,
+        &stacks_blocks[0].1,
+    );
+
+    // after revealing ``0``, we should now have the anchor block for
+    //   the 6-7-8-9-10 fork
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "1110");
+    }
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(1));
+
+    // reveal [6-10]
+    for (_sort_id, block) in stacks_blocks[6..=10].iter() {
+        // cannot use sort_id from stacks_blocks, because the blinded coordinator
+        //   has different sortition_id's for blocks 6-10 (because it's missing
+        //   the 2nd anchor block).
+        let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+            &sort_db_blind.index_conn(),
+            &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
+            &block.header.block_hash(),
+        )
+        .unwrap()
+        .unwrap()
+        .sortition_id;
+        reveal_block(
+            path_blinded,
+            &sort_db_blind,
+            &mut coord_blind,
+            &sort_id,
+            block,
+        );
+    }
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "1110");
+    }
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(6));
+
+    let block_hash = eval_at_chain_tip(
+        path_blinded,
+        &sort_db_blind,
+        "(get-block-info? header-hash u5)",
+    );
+    assert_eq!(
+        block_hash,
+        Value::some(
+            Value::buff_from(stacks_blocks[9].1.header.block_hash().as_bytes().to_vec()).unwrap()
+        )
+        .unwrap()
+    );
+
+    // reveal [1-5]
+    for (_sort_id, block) in stacks_blocks[1..=5].iter() {
+        // cannot use sort_id from stacks_blocks, because the blinded coordinator
+        //   has different sortition_id's for blocks 6-10 (because it's missing
+        //   the 2nd anchor block).
+        let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+            &sort_db_blind.index_conn(),
+            &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
+            &block.header.block_hash(),
+        )
+        .unwrap()
+        .unwrap()
+        .sortition_id;
+
+        // before processing the last of these blocks, the stacks_block[9] should still
+        //   be the canonical tip
+        let block_hash = eval_at_chain_tip(
+            path_blinded,
+            &sort_db_blind,
+            "(get-block-info? header-hash u5)",
+        );
+        assert_eq!(
+            block_hash,
+            Value::some(
+                Value::buff_from(stacks_blocks[9].1.header.block_hash().as_bytes().to_vec())
+                    .unwrap()
+            )
+            .unwrap()
+        );
+
+        reveal_block(
+            path_blinded,
+            &sort_db_blind,
+            &mut coord_blind,
+            &sort_id,
+            block,
+        );
+    }
+
+    {
+        let ic = sort_db_blind.index_handle_at_tip();
+        let pox_id = ic.get_pox_id().unwrap();
+        assert_eq!(&pox_id.to_string(), "1111");
+    }
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(6));
+
+    // reveal [11-14]
+    for (_sort_id, block) in stacks_blocks[11..].iter() {
+        // cannot use sort_id from stacks_blocks, because the blinded coordinator
+        //   has different sortition_id's for blocks 6-10 (because it's missing
+        //   the 2nd anchor block).
+        let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+            &sort_db_blind.index_conn(),
+            &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
+            &block.header.block_hash(),
+        )
+        .unwrap()
+        .unwrap()
+        .sortition_id;
+
+        reveal_block(
+            path_blinded,
+            &sort_db_blind,
+            &mut coord_blind,
+            &sort_id,
+            block,
+        );
+    }
+
+    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
+    assert_eq!(block_height, Value::UInt(10));
+
+    let block_hash = eval_at_chain_tip(
+        path_blinded,
+        &sort_db_blind,
+        "(get-block-info? header-hash u9)",
+    );
+    assert_eq!(
+        block_hash,
+        Value::some(
+            Value::buff_from(stacks_blocks[13].1.header.block_hash().as_bytes().to_vec()).unwrap()
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_drain_processed_blocks() {
+    let path = "/tmp/stacks-blockchain.test.drain_processed_blocks";
+    // setup a second set of states that only sees the stacks blocks once they've all been mined
+    let path_drained = "/tmp/stacks-blockchain.test.drain_processed_blocks.drained";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_drained);
+
+    let vrf_keys: Vec<_> = (0..3).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..3).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_drained], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    let mut coord_drained = make_coordinator(path_drained);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_drained.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
+
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &BlockHeaderHash([0; 32]),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &stacks_blocks[ix - 1].1.header.block_hash(),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let burnchain_drained = get_burnchain_db(path_drained);
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [burnchain_drained].iter_mut(),
+        );
+        // handle the sortition
+        coord.handle_new_burnchain_block().unwrap();
+        coord_drained.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
+    }
+
+    // make all the blocks ready at once
+    let sort_db_drained = get_sortition_db(path_drained);
+    let mut chainstate_drained = get_chainstate(path_drained);
+    for (sortition_id, block) in stacks_blocks.iter() {
+        let sortition = SortitionDB::get_block_snapshot(sort_db_drained.conn(), sortition_id)
+            .unwrap()
+            .unwrap();
+        preprocess_block(
+            &mut chainstate_drained,
+            &sort_db_drained,
+            &sortition,
+            block.clone(),
+        );
+    }
+
+    let drained = coord_drained.drain_processed_blocks(3).unwrap();
+    assert_eq!(drained.len(), 3);
+
+    for ((block, header, receipts), (_, expected_block)) in drained.iter().zip(stacks_blocks.iter())
+    {
+        assert_eq!(block.block_hash(), expected_block.block_hash());
+        assert_eq!(header.anchored_header.block_hash(), block.block_hash());
+        // just the coinbase
+        assert_eq!(receipts.len(), 1);
+    }
+
+    // the headers form a contiguous chain
+    assert_eq!(drained[0].1.block_height, 1);
+    for pair in drained.windows(2) {
+        let (_, ref parent, _) = pair[0];
+        let (_, ref child, _) = pair[1];
+        assert_eq!(
+            child.anchored_header.parent_block,
+            parent.anchored_header.block_hash()
+        );
+        assert_eq!(child.block_height, parent.block_height + 1);
+    }
+
+    // nothing is left to drain
+    assert!(coord_drained.drain_processed_blocks(3).unwrap().is_empty());
+
+    let block_height = eval_at_chain_tip(path_drained, &sort_db_drained, "block-height");
+    assert_eq!(block_height, Value::UInt(3));
+}
+
+#[test]
+fn test_parallel_block_validation() {
+    let path = "/tmp/stacks-blockchain.test.parallel_block_validation";
+    // two more sets of states that only see the stacks blocks once they've all been mined
+    let path_serial = "/tmp/stacks-blockchain.test.parallel_block_validation.serial";
+    let path_parallel = "/tmp/stacks-blockchain.test.parallel_block_validation.parallel";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_serial);
+    let _r = std::fs::remove_dir_all(path_parallel);
+
+    let vrf_keys: Vec<_> = (0..5).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..5).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_serial, path_parallel], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    let mut coord_serial = make_coordinator(path_serial);
+    let mut coord_parallel = make_coordinator(path_parallel);
+    coord_parallel.set_block_validation_threads(4);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_serial.handle_new_burnchain_block().unwrap();
+    coord_parallel.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
+
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &BlockHeaderHash([0; 32]),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            // blocks 1, 2 and 3 all build on block 0, so that they are ready to be processed at
+            //  the same time, and block 4 builds on block 3.
+            let parent_ix = if ix == 4 { 3 } else { 0 };
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &stacks_blocks[parent_ix].1.header.block_hash(),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [
+                get_burnchain_db(path_serial),
+                get_burnchain_db(path_parallel),
+            ]
+            .iter_mut(),
+        );
+        // handle the sortition
+        coord.handle_new_burnchain_block().unwrap();
+        coord_serial.handle_new_burnchain_block().unwrap();
+        coord_parallel.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
+    }
+
+    // make all the blocks ready at once, with and without the validation threads
+    for (other_path, other_coord) in [
+        (path_serial, &mut coord_serial),
+        (path_parallel, &mut coord_parallel),
+    ]
+    .iter_mut()
+    {
+        let other_sort_db = get_sortition_db(other_path);
+        let mut other_chainstate = get_chainstate(other_path);
+        for (sortition_id, block) in stacks_blocks.iter() {
+            let sortition = SortitionDB::get_block_snapshot(other_sort_db.conn(), sortition_id)
+                .unwrap()
+                .unwrap();
+            preprocess_block(
+                &mut other_chainstate,
+                &other_sort_db,
+                &sortition,
+                block.clone(),
+            );
+        }
+        other_coord.handle_new_stacks_block().unwrap();
+    }
+
+    let expected_tip = coord.canonical_stacks_tip().unwrap();
+    assert_eq!(
+        coord_serial.canonical_stacks_tip(),
+        Some(expected_tip.clone())
+    );
+    assert_eq!(coord_parallel.canonical_stacks_tip(), Some(expected_tip));
+
+    // nothing valid was rejected
+    let parallel_sort_db = get_sortition_db(path_parallel);
+    let parallel_chainstate = get_chainstate(path_parallel);
+    for (sortition_id, block) in stacks_blocks.iter() {
+        let sortition = SortitionDB::get_block_snapshot(parallel_sort_db.conn(), sortition_id)
+            .unwrap()
+            .unwrap();
+        assert!(StacksChainState::get_anchored_block_header_info(
+            parallel_chainstate.headers_db(),
+            &sortition.consensus_hash,
+            &block.block_hash(),
+        )
+        .unwrap()
+        .is_some());
+    }
+
+    let block_height = eval_at_chain_tip(path_parallel, &parallel_sort_db, "block-height");
+    assert_eq!(block_height, Value::UInt(3));
+    assert_eq!(
+        eval_at_chain_tip(path_serial, &get_sortition_db(path_serial), "block-height"),
+        block_height
+    );
+}
+
+/// Fetches burnchain blocks by copying them from the burnchain DB at the first path into the
+///  burnchain DB at the second path.
+struct CopyingBurnchainBlockFetcher(String, String);
+
+impl BurnchainBlockFetcher for CopyingBurnchainBlockFetcher {
+    fn fetch_burnchain_block(&self, hash: &BurnchainHeaderHash) -> Result<(), burnchains::Error> {
+        let BurnchainBlockData { header, ops } =
+            get_burnchain_db(&self.0).get_burnchain_block(hash)?;
+        get_burnchain_db(&self.1).raw_store_burnchain_block(header, ops)
+    }
+}
+
+#[test]
+fn test_burnchain_gap_fill() {
+    let path = "/tmp/stacks-blockchain.test.burnchain_gap_fill";
+    // setup a second set of states that sees every burnchain block
+    let path_source = "/tmp/stacks-blockchain.test.burnchain_gap_fill.source";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_source);
+
+    let vrf_keys: Vec<_> = (0..1).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..1).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_source], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    coord.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+
+    // the parent block only makes it into the source's burnchain DB...
+    let mut burnchain_source = get_burnchain_db(path_source);
+    let burnchain_tip = burnchain_source.get_canonical_chain_tip().unwrap();
+    let missing_hash = produce_burn_block(
+        &mut burnchain_source,
+        &burnchain_tip.block_hash,
+        vec![],
+        vec![].iter_mut(),
+    );
+
+    // ...but its child makes it into both
+    let burnchain = get_burnchain_db(path);
+    produce_burn_block(
+        &mut burnchain_source,
+        &missing_hash,
+        vec![],
+        [burnchain].iter_mut(),
+    );
+
+    // without a fetcher, the new block can't be processed
+    match coord.handle_new_burnchain_block() {
+        Err(CoordError::NonContiguousBurnchainBlock(_)) => {}
+        x => panic!("Expected NonContiguousBurnchainBlock, got {:?}", x),
+    }
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    assert_eq!(tip.block_height, 1);
+
+    // with a fetcher, the missing block gets fetched and both blocks get processed
+    coord.set_burnchain_block_fetcher(Box::new(CopyingBurnchainBlockFetcher(
+        path_source.to_string(),
+        path.to_string(),
+    )));
+    coord.handle_new_burnchain_block().unwrap();
+
+    assert!(get_burnchain_db(path)
+        .get_burnchain_block(&missing_hash)
+        .is_ok());
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    assert_eq!(tip.block_height, 3);
+}
+
+fn eval_at_chain_tip(chainstate_path: &str, sort_db: &SortitionDB, eval: &str) -> Value {
+    let stacks_tip = SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
+    let mut chainstate = get_chainstate(chainstate_path);
+    chainstate.with_read_only_clarity_tx(
+        &sort_db.index_conn(),
+        &StacksBlockId::new(&stacks_tip.0, &stacks_tip.1),
+        |conn| {
+            conn.with_readonly_clarity_env(
+                PrincipalData::parse("SP3Q4A5WWZ80REGBN0ZXNE540ECJ9JZ4A765Q5K2Q").unwrap(),
+                LimitedCostTracker::new_max_limit(),
+                |env| env.eval_raw(eval),
+            )
+            .unwrap()
+        },
+    )
+}
+
+fn reveal_block<T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>(
+    chainstate_path: &str,
+    sort_db: &SortitionDB,
+    coord: &mut ChainsCoordinator<T, N, U>,
+    my_sortition: &SortitionId,
+    block: &StacksBlock,
+) {
+    let mut chainstate = get_chainstate(chainstate_path);
+    let sortition = SortitionDB::get_block_snapshot(sort_db.conn(), &my_sortition)
+        .unwrap()
+        .unwrap();
+    preprocess_block(&mut chainstate, sort_db, &sortition, block.clone());
+    coord.handle_new_stacks_block().unwrap();
+}
+
+fn preprocess_block(
+    chain_state: &mut StacksChainState,
+    sort_db: &SortitionDB,
+    my_sortition: &BlockSnapshot,
+    block: StacksBlock,
+) {
+    let ic = sort_db.index_conn();
+    let parent_consensus_hash = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+        &ic,
+        &my_sortition.sortition_id,
+        &block.header.parent_block,
+    )
+    .unwrap()
+    .unwrap()
+    .consensus_hash;
+    // Preprocess the anchored block
+    chain_state
+        .preprocess_anchored_block(
+            &ic,
+            &my_sortition.consensus_hash,
+            &block,
+            &parent_consensus_hash,
+            5,
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_get_burnchain_header_by_height() {
+    let path = "/tmp/stacks-blockchain-burnchain-header-by-height";
+    let _r = std::fs::remove_dir_all(path);
+
+    let vrf_keys: Vec<_> = (0..1).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..1).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    coord.handle_new_burnchain_block().unwrap();
+
+    let mut burnchain = get_burnchain_db(path);
+    let mut headers = vec![];
+    for _ in 0..5 {
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let block_hash = produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![],
+            vec![].iter_mut(),
+        );
+        coord.handle_new_burnchain_block().unwrap();
+        headers.push(burnchain.get_burnchain_block(&block_hash).unwrap().header);
+    }
+
+    for header in headers.iter() {
         assert_eq!(
-            block_hash,
-            Value::some(
-                Value::buff_from(stacks_blocks[9].1.header.block_hash().as_bytes().to_vec())
-                    .unwrap()
+            coord
+                .get_burnchain_header_by_height(header.block_height)
+                .unwrap()
+                .as_ref(),
+            Some(header)
+        );
+    }
+
+    // nothing past the tip
+    let tip_height = headers.last().unwrap().block_height;
+    assert_eq!(
+        coord
+            .get_burnchain_header_by_height(tip_height + 1)
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        coord
+            .get_burnchain_header_by_height(u64::max_value())
+            .unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_drain_processed_blocks_across_unwind() {
+    let path = "/tmp/stacks-blockchain.test.drain_processed_blocks_across_unwind";
+    // setup a second set of states that won't see the broadcasted blocks
+    let path_blinded = "/tmp/stacks-blockchain.test.drain_processed_blocks_across_unwind.blinded";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_blinded);
+
+    let vrf_keys: Vec<_> = (0..12).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..12).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    let mut coord_blind = make_coordinator(path_blinded);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_blind.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+    let sort_db_blind = get_sortition_db(path_blinded);
+
+    let mut parent = BlockHeaderHash([0; 32]);
+    let mut stacks_blocks = vec![];
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let burnchain_blinded = get_burnchain_db(path_blinded);
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [burnchain_blinded].iter_mut(),
+        );
+        coord.handle_new_burnchain_block().unwrap();
+        coord_blind.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        let block_hash = block.header.block_hash();
+        stacks_blocks.push(block.clone());
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
+
+        parent = block_hash;
+    }
+
+    // reveal the first anchor block and two of its descendants to the blinded coordinator at once
+    let blinded_tip = SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap();
+    let mut chainstate = get_chainstate(path_blinded);
+    let mut block_ids = vec![];
+    for block in stacks_blocks[0..3].iter() {
+        let sortition = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+            &sort_db_blind.index_conn(),
+            &blinded_tip,
+            &block.header.block_hash(),
+        )
+        .unwrap()
+        .unwrap();
+        block_ids.push(StacksBlockId::new(
+            &sortition.consensus_hash,
+            &block.header.block_hash(),
+        ));
+        preprocess_block(&mut chainstate, &sort_db_blind, &sortition, block.clone());
+    }
+
+    // processing the anchor block unwinds the PoX fork, and the batch stops there, even though
+    //  the batch size would allow its descendants too
+    let drained = coord_blind.drain_processed_blocks(10).unwrap();
+    assert_eq!(drained.len(), 1);
+    assert_eq!(
+        drained[0].0.block_hash(),
+        stacks_blocks[0].header.block_hash()
+    );
+    for block_id in block_ids[1..].iter() {
+        assert!(
+            !chainstate
+                .get_staging_block_by_index_hash(block_id)
+                .unwrap()
+                .unwrap()
+                .processed
+        );
+    }
+
+    // ...and the blocks past the boundary are returned by the next batch instead
+    let drained = coord_blind.drain_processed_blocks(10).unwrap();
+    assert_eq!(drained.len(), 2);
+    assert_eq!(
+        drained[0].0.block_hash(),
+        stacks_blocks[1].header.block_hash()
+    );
+    assert_eq!(
+        drained[1].0.block_hash(),
+        stacks_blocks[2].header.block_hash()
+    );
+    assert_eq!(
+        drained[1].1.anchored_header.parent_block,
+        drained[0].1.anchored_header.block_hash()
+    );
+    assert!(coord_blind.drain_processed_blocks(10).unwrap().is_empty());
+}
+
+#[test]
+fn test_max_reorg_depth() {
+    let path = "/tmp/stacks-blockchain.test.max_reorg_depth";
+    // setup a second set of states that won't see the broadcasted blocks
+    let path_blinded = "/tmp/stacks-blockchain.test.max_reorg_depth.blinded";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_blinded);
+
+    let vrf_keys: Vec<_> = (0..12).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..12).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+
+    let mut coord = make_coordinator(path);
+    let mut coord_blind = make_coordinator(path_blinded);
+    coord_blind.set_max_reorg_depth(2);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_blind.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+    let sort_db_blind = get_sortition_db(path_blinded);
+
+    let mut parent = BlockHeaderHash([0; 32]);
+    let mut stacks_blocks = vec![];
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &parent,
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
             )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        let burnchain_blinded = get_burnchain_db(path_blinded);
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [burnchain_blinded].iter_mut(),
+        );
+        coord.handle_new_burnchain_block().unwrap();
+        coord_blind.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        let block_hash = block.header.block_hash();
+        stacks_blocks.push(block.clone());
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
+
+        parent = block_hash;
+    }
+
+    let blinded_tip = SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap();
+    let blinded_pox_id = sort_db_blind.index_handle_at_tip().get_pox_id().unwrap();
+
+    // revealing the first anchor block to the blinded coordinator would roll back every
+    //  sortition since the first prepare phase -- more than it allows
+    let mut chainstate = get_chainstate(path_blinded);
+    let sortition = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+        &sort_db_blind.index_conn(),
+        &blinded_tip,
+        &stacks_blocks[0].header.block_hash(),
+    )
+    .unwrap()
+    .unwrap();
+    preprocess_block(
+        &mut chainstate,
+        &sort_db_blind,
+        &sortition,
+        stacks_blocks[0].clone(),
+    );
+    match coord_blind.handle_new_stacks_block() {
+        Err(CoordError::ReorgTooDeep(depth, max_depth)) => {
+            assert!(depth > 2);
+            assert_eq!(max_depth, 2);
+        }
+        x => panic!("Expected ReorgTooDeep, got {:?}", &x),
+    }
+
+    // nothing was rolled back
+    assert_eq!(
+        coord_blind.canonical_sortition_tip(),
+        Some(blinded_tip.clone())
+    );
+    assert_eq!(
+        SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
+        blinded_tip
+    );
+    assert_eq!(
+        sort_db_blind.index_handle_at_tip().get_pox_id().unwrap(),
+        blinded_pox_id
+    );
+
+    // ...and the anchor block was refused before it was committed, so it's still staged
+    let anchor_block_id = StacksBlockId::new(
+        &sortition.consensus_hash,
+        &stacks_blocks[0].header.block_hash(),
+    );
+    let staging_block = chainstate
+        .get_staging_block_by_index_hash(&anchor_block_id)
+        .unwrap()
+        .unwrap();
+    assert!(!staging_block.processed);
+    assert!(!staging_block.orphaned);
+
+    // once the limit is lifted (e.g. on restart), the anchor block is found again and the
+    //  reorg goes through
+    coord_blind.set_max_reorg_depth(DEFAULT_MAX_REORG_DEPTH);
+    coord_blind.handle_new_stacks_block().unwrap();
+    assert!(
+        chainstate
+            .get_staging_block_by_index_hash(&anchor_block_id)
             .unwrap()
-        );
+            .unwrap()
+            .processed
+    );
+    assert_ne!(
+        sort_db_blind.index_handle_at_tip().get_pox_id().unwrap(),
+        blinded_pox_id
+    );
+}
 
-        reveal_block(
-            path_blinded,
-            &sort_db_blind,
-            &mut coord_blind,
-            &sort_id,
-            block,
+fn make_signed_tx(
+    sk: &StacksPrivateKey,
+    nonce: u64,
+    payload: TransactionPayload,
+) -> StacksTransaction {
+    let mut tx = StacksTransaction::new(
+        TransactionVersion::Testnet,
+        TransactionAuth::from_p2pkh(sk).unwrap(),
+        payload,
+    );
+    tx.chain_id = 0x80000000;
+    tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
+    tx.set_origin_nonce(nonce);
+    let mut tx_signer = StacksTransactionSigner::new(&tx);
+    tx_signer.sign_origin(sk).unwrap();
+    tx_signer.get_tx().unwrap()
+}
+
+#[test]
+fn test_log_file_dispatcher() {
+    let path = "/tmp/stacks-blockchain.test.log_file_dispatcher";
+    let log_path = "/tmp/stacks-blockchain.test.log_file_dispatcher.jsonl";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_file(log_path);
+
+    let vrf_keys: Vec<_> = (0..1).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..1).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path], &vrf_keys, &committers);
+
+    // log receipts alongside another dispatcher
+    let dispatcher = (None::<ArchiveDispatcher>, LogFileDispatcher::new(log_path));
+    let mut coord: ChainsCoordinator<_, (), _> =
+        ChainsCoordinator::test_new(&get_burnchain(path), path, OnChainRewardSetProvider());
+    coord.set_dispatcher(&dispatcher);
+    coord.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+    let mut chainstate = get_chainstate(path);
+    let miner = &committers[0];
+
+    // the block's coinbase is followed by a contract publish, and a call to it that aborts
+    let tx_publish = make_signed_tx(
+        miner,
+        1,
+        TransactionPayload::new_smart_contract(
+            &"dispatched".to_string(),
+            &"(define-public (fail) (err u1))".to_string(),
+        )
+        .unwrap(),
+    );
+    let tx_call = make_signed_tx(
+        miner,
+        2,
+        TransactionPayload::new_contract_call(p2pkh_from(miner), "dispatched", "fail", vec![])
+            .unwrap(),
+    );
+
+    let (op, block) = make_genesis_block_with_recipients(
+        &sort_db,
+        &mut chainstate,
+        &BlockHeaderHash([0; 32]),
+        miner,
+        10000,
+        &vrf_keys[0],
+        0,
+        None,
+        &[tx_publish.clone(), tx_call.clone()],
+    );
+    let mut burnchain = get_burnchain_db(path);
+    let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+    produce_burn_block(
+        &mut burnchain,
+        &burnchain_tip.block_hash,
+        vec![op],
+        vec![].iter_mut(),
+    );
+    coord.handle_new_burnchain_block().unwrap();
+
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+    preprocess_block(&mut chainstate, &sort_db, &tip, block.clone());
+    coord.handle_new_stacks_block().unwrap();
+
+    let index_block_hash = StacksBlockId::new(&tip.consensus_hash, &block.block_hash());
+    assert_eq!(coord.canonical_stacks_tip(), Some(index_block_hash.clone()));
+
+    // one line per receipt of the processed block.  Boot receipts are only dispatched by
+    //  ChainsCoordinator::run(), so there are none.
+    let contents = std::fs::read_to_string(log_path).unwrap();
+    let lines: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+
+    let expected = [
+        (&block.txs[0], "success"),
+        (&tx_publish, "success"),
+        (&tx_call, "abort_by_response"),
+    ];
+    for (line, (tx, status)) in lines.iter().zip(expected.iter()) {
+        assert_eq!(line["txid"].as_str().unwrap(), format!("0x{}", tx.txid()));
+        assert_eq!(
+            line["index_block_hash"].as_str().unwrap(),
+            format!("0x{}", &index_block_hash)
         );
+        assert_eq!(line["status"].as_str().unwrap(), *status);
+        assert!(serde_json::from_value::<ExecutionCost>(line["execution_cost"].clone()).is_ok());
     }
+}
 
-    {
-        let ic = sort_db_blind.index_handle_at_tip();
-        let pox_id = ic.get_pox_id().unwrap();
-        assert_eq!(&pox_id.to_string(), "1111");
+#[test]
+fn test_archive_dispatcher() {
+    let path = "/tmp/stacks-blockchain.test.archive_dispatcher";
+    // a second set of states, archiving to the same directory, that only sees the blocks later
+    let path_blinded = "/tmp/stacks-blockchain.test.archive_dispatcher.blinded";
+    let archive_dir = "/tmp/stacks-blockchain.test.archive_dispatcher.archive";
+    let _r = std::fs::remove_dir_all(path);
+    let _r = std::fs::remove_dir_all(path_blinded);
+    let _r = std::fs::remove_dir_all(archive_dir);
+
+    let vrf_keys: Vec<_> = (0..3).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..3).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path, path_blinded], &vrf_keys, &committers);
+
+    // archive alongside another dispatcher
+    let archiver = ArchiveDispatcher::new(archive_dir);
+    let dispatcher = (
+        None::<LogFileDispatcher>,
+        ArchiveDispatcher::new(archive_dir),
+    );
+    let dispatcher_blinded = ArchiveDispatcher::new(archive_dir);
+
+    let mut coord: ChainsCoordinator<_, (), _> =
+        ChainsCoordinator::test_new(&get_burnchain(path), path, OnChainRewardSetProvider());
+    coord.set_dispatcher(&dispatcher);
+    let mut coord_blind: ChainsCoordinator<_, (), _> = ChainsCoordinator::test_new(
+        &get_burnchain(path_blinded),
+        path_blinded,
+        OnChainRewardSetProvider(),
+    );
+    coord_blind.set_dispatcher(&dispatcher_blinded);
+
+    coord.handle_new_burnchain_block().unwrap();
+    coord_blind.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    // a chain of three stacks blocks
+    let mut stacks_blocks: Vec<(SortitionId, StacksBlock)> = vec![];
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &BlockHeaderHash([0; 32]),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &stacks_blocks[ix - 1].1.block_hash(),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            [get_burnchain_db(path_blinded)].iter_mut(),
+        );
+        coord.handle_new_burnchain_block().unwrap();
+        coord_blind.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        stacks_blocks.push((tip.sortition_id.clone(), block.clone()));
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
     }
 
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(6));
+    // every processed block got archived at its height
+    for (i, (_, block)) in stacks_blocks.iter().enumerate() {
+        let block_path = archiver.get_block_path(block, i as u64 + 1);
+        assert_eq!(
+            block_path,
+            format!("{}/{}-{}.blk", archive_dir, i + 1, block.block_hash())
+        );
+        let archived: StacksBlock = StacksChainState::consensus_load(&block_path).unwrap();
+        assert_eq!(&archived, block);
+    }
 
-    // reveal [11-14]
-    for (_sort_id, block) in stacks_blocks[11..].iter() {
-        // cannot use sort_id from stacks_blocks, because the blinded coordinator
-        //   has different sortition_id's for blocks 6-10 (because it's missing
-        //   the 2nd anchor block).
-        let sort_id = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-            &sort_db_blind.index_conn(),
-            &SortitionDB::get_canonical_sortition_tip(sort_db_blind.conn()).unwrap(),
-            &block.header.block_hash(),
-        )
-        .unwrap()
-        .unwrap()
-        .sortition_id;
+    // already-archived blocks are left alone when they're processed again
+    let block_path = archiver.get_block_path(&stacks_blocks[0].1, 1);
+    std::fs::write(&block_path, b"already archived").unwrap();
 
+    let sort_db_blind = get_sortition_db(path_blinded);
+    for (sortition_id, block) in stacks_blocks.iter() {
         reveal_block(
             path_blinded,
             &sort_db_blind,
             &mut coord_blind,
-            &sort_id,
+            sortition_id,
             block,
         );
     }
-
-    let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
-    assert_eq!(block_height, Value::UInt(10));
-
-    let block_hash = eval_at_chain_tip(
-        path_blinded,
-        &sort_db_blind,
-        "(get-block-info? header-hash u9)",
+    assert_eq!(
+        coord_blind.canonical_stacks_tip(),
+        coord.canonical_stacks_tip()
     );
+
     assert_eq!(
-        block_hash,
-        Value::some(
-            Value::buff_from(stacks_blocks[13].1.header.block_hash().as_bytes().to_vec()).unwrap()
-        )
-        .unwrap()
+        std::fs::read(&block_path).unwrap(),
+        b"already archived".to_vec()
     );
+    assert_eq!(std::fs::read_dir(archive_dir).unwrap().count(), 3);
 }
 
-fn eval_at_chain_tip(chainstate_path: &str, sort_db: &SortitionDB, eval: &str) -> Value {
-    let stacks_tip = SortitionDB::get_canonical_stacks_chain_tip_hash(sort_db.conn()).unwrap();
-    let mut chainstate = get_chainstate(chainstate_path);
-    chainstate.with_read_only_clarity_tx(
-        &sort_db.index_conn(),
-        &StacksBlockId::new(&stacks_tip.0, &stacks_tip.1),
-        |conn| {
-            conn.with_readonly_clarity_env(
-                PrincipalData::parse("SP3Q4A5WWZ80REGBN0ZXNE540ECJ9JZ4A765Q5K2Q").unwrap(),
-                LimitedCostTracker::new_max_limit(),
-                |env| env.eval_raw(eval),
-            )
+#[test]
+fn test_on_new_block_dispatcher() {
+    let path = "/tmp/stacks-blockchain.test.on_new_block_dispatcher";
+    let _r = std::fs::remove_dir_all(path);
+
+    let vrf_keys: Vec<_> = (0..3).map(|_| VRFPrivateKey::new()).collect();
+    let committers: Vec<_> = (0..3).map(|_| StacksPrivateKey::new()).collect();
+
+    setup_states(&[path], &vrf_keys, &committers);
+
+    // record the hash and height of each block the callback is invoked with
+    let new_blocks = Arc::new(Mutex::new(vec![]));
+    let callback_blocks = new_blocks.clone();
+    let dispatcher = OnNewBlockDispatcher::new(Box::new(move |block, header_info| {
+        callback_blocks
+            .lock()
             .unwrap()
-        },
-    )
-}
+            .push((block.block_hash(), header_info.block_height));
+    }));
 
-fn reveal_block<T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>(
-    chainstate_path: &str,
-    sort_db: &SortitionDB,
-    coord: &mut ChainsCoordinator<T, N, U>,
-    my_sortition: &SortitionId,
-    block: &StacksBlock,
-) {
-    let mut chainstate = get_chainstate(chainstate_path);
-    let sortition = SortitionDB::get_block_snapshot(sort_db.conn(), &my_sortition)
-        .unwrap()
-        .unwrap();
-    preprocess_block(&mut chainstate, sort_db, &sortition, block.clone());
-    coord.handle_new_stacks_block().unwrap();
-}
+    let mut coord: ChainsCoordinator<_, (), _> =
+        ChainsCoordinator::test_new(&get_burnchain(path), path, OnChainRewardSetProvider());
+    coord.set_dispatcher(&dispatcher);
 
-fn preprocess_block(
-    chain_state: &mut StacksChainState,
-    sort_db: &SortitionDB,
-    my_sortition: &BlockSnapshot,
-    block: StacksBlock,
-) {
-    let ic = sort_db.index_conn();
-    let parent_consensus_hash = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-        &ic,
-        &my_sortition.sortition_id,
-        &block.header.parent_block,
-    )
-    .unwrap()
-    .unwrap()
-    .consensus_hash;
-    // Preprocess the anchored block
-    chain_state
-        .preprocess_anchored_block(
-            &ic,
-            &my_sortition.consensus_hash,
-            &block,
-            &parent_consensus_hash,
-            5,
-        )
-        .unwrap();
+    coord.handle_new_burnchain_block().unwrap();
+
+    let sort_db = get_sortition_db(path);
+
+    // a chain of three stacks blocks
+    let mut stacks_blocks: Vec<StacksBlock> = vec![];
+    for (ix, (vrf_key, miner)) in vrf_keys.iter().zip(committers.iter()).enumerate() {
+        let mut burnchain = get_burnchain_db(path);
+        let mut chainstate = get_chainstate(path);
+        let (op, block) = if ix == 0 {
+            make_genesis_block(
+                &sort_db,
+                &mut chainstate,
+                &BlockHeaderHash([0; 32]),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        } else {
+            make_stacks_block(
+                &sort_db,
+                &mut chainstate,
+                &stacks_blocks[ix - 1].block_hash(),
+                miner,
+                10000,
+                vrf_key,
+                ix as u32,
+            )
+        };
+        let burnchain_tip = burnchain.get_canonical_chain_tip().unwrap();
+        produce_burn_block(
+            &mut burnchain,
+            &burnchain_tip.block_hash,
+            vec![op],
+            vec![].iter_mut(),
+        );
+        coord.handle_new_burnchain_block().unwrap();
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn()).unwrap();
+        stacks_blocks.push(block.clone());
+
+        preprocess_block(&mut chainstate, &sort_db, &tip, block);
+        coord.handle_new_stacks_block().unwrap();
+    }
+
+    // the callback fired once per processed block, in order
+    let expected: Vec<_> = stacks_blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| (block.block_hash(), i as u64 + 1))
+        .collect();
+    assert_eq!(*new_blocks.lock().unwrap(), expected);
 }
@@ -163,6 +163,19 @@ impl StacksAddress {
         ))
     }
 
+    /// Convert to the Bitcoin address with the same hash bytes
+    pub fn to_bitcoin_address(&self) -> BitcoinAddress {
+        let btc_version = to_b52_version_byte(self.version)
+            .expect("BUG: failed to decode Stacks version byte to Bitcoin version byte");
+        let (addrtype, network_id) = version_byte_to_address_type(btc_version)
+            .expect("BUG: failed to decode Bitcoin version byte");
+        BitcoinAddress {
+            addrtype,
+            network_id,
+            bytes: self.bytes.clone(),
+        }
+    }
+
     pub fn to_bitcoin_tx_out(&self, value: u64) -> TxOut {
         let btc_version = to_b52_version_byte(self.version)
             .expect("BUG: failed to decode Stacks version byte to Bitcoin version byte");
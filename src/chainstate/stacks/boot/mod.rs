@@ -21,16 +21,20 @@ use chainstate::stacks::db::StacksChainState;
 use chainstate::stacks::Error;
 use chainstate::stacks::StacksAddress;
 use chainstate::stacks::StacksBlockHeader;
+use chainstate::stacks::{
+    StacksPrivateKey, StacksTransaction, StacksTransactionSigner, TransactionAuth,
+    TransactionPayload, TransactionPostConditionMode, TransactionVersion,
+};
 
 use address::AddressHashMode;
-use burnchains::bitcoin::address::BitcoinAddress;
+use burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
+use burnchains::bitcoin::BitcoinNetworkType;
 use burnchains::Address;
 
 use chainstate::burn::db::sortdb::SortitionDB;
 
 use vm::types::{
-    PrincipalData, QualifiedContractIdentifier, SequenceData, StandardPrincipalData, TupleData,
-    Value,
+    PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, TupleData, Value,
 };
 
 use chainstate::stacks::StacksBlockId;
@@ -42,6 +46,7 @@ use vm::representations::ContractName;
 use util::hash::Hash160;
 
 use std::boxed::Box;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 
@@ -85,29 +90,151 @@ pub fn make_contract_id(addr: &StacksAddress, name: &str) -> QualifiedContractId
     )
 }
 
-/// Extract a PoX address from its tuple representation
-fn tuple_to_pox_addr(tuple_data: TupleData) -> (AddressHashMode, Hash160) {
-    let version_value = tuple_data
-        .get("version")
-        .expect("FATAL: no 'version' field in pox-addr")
-        .to_owned();
-    let hashbytes_value = tuple_data
-        .get("hashbytes")
-        .expect("FATAL: no 'hashbytes' field in pox-addr")
-        .to_owned();
-
-    let version_u8 = version_value.expect_buff(1)[0];
-    let version: AddressHashMode = version_u8
-        .try_into()
-        .expect("FATAL: PoX version is not a supported version byte");
-
-    let hashbytes_vec = hashbytes_value.expect_buff(20);
+/// Extract a PoX address from its tuple representation.
+/// Returns None if the tuple is malformed.
+fn try_tuple_to_pox_addr(tuple_data: TupleData) -> Option<(AddressHashMode, Hash160)> {
+    let version_value = tuple_data.get("version").ok()?.to_owned();
+    let hashbytes_value = tuple_data.get("hashbytes").ok()?.to_owned();
+
+    let version_u8 = version_value.try_buff(1)?[0];
+    let version: AddressHashMode = version_u8.try_into().ok()?;
+
+    let hashbytes_vec = hashbytes_value.try_buff(20)?;
 
     let mut hashbytes_20 = [0u8; 20];
     hashbytes_20.copy_from_slice(&hashbytes_vec[0..20]);
     let hashbytes = Hash160(hashbytes_20);
 
-    (version, hashbytes)
+    Some((version, hashbytes))
+}
+
+/// Extract a PoX address from its tuple representation
+fn tuple_to_pox_addr(tuple_data: TupleData) -> (AddressHashMode, Hash160) {
+    try_tuple_to_pox_addr(tuple_data).expect("FATAL: malformed pox-addr tuple")
+}
+
+/// Convert a PoX address into the Bitcoin address that will receive its rewards.
+/// P2PKH hash modes map to Bitcoin P2PKH addresses; all other hash modes are P2SH (or
+/// P2SH-wrapped segwit) addresses.
+pub fn pox_addr_to_bitcoin_address(
+    hash_mode: AddressHashMode,
+    hash: Hash160,
+    mainnet: bool,
+) -> BitcoinAddress {
+    let addrtype = match hash_mode {
+        AddressHashMode::SerializeP2PKH => BitcoinAddressType::PublicKeyHash,
+        _ => BitcoinAddressType::ScriptHash,
+    };
+    let network_id = match mainnet {
+        true => BitcoinNetworkType::Mainnet,
+        false => BitcoinNetworkType::Testnet,
+    };
+    BitcoinAddress {
+        addrtype,
+        network_id,
+        bytes: hash,
+    }
+}
+
+/// An entry of a reward cycle's reward set, as exported for off-chain verification.
+/// The PoX contract does not record which stacker registered a reward set entry, so only the
+/// reward address and the amount stacked for it are available.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardSetEntry {
+    /// The reward address, as a Stacks address
+    pub stacks_address: String,
+    /// The Bitcoin address that receives the rewards
+    pub bitcoin_address: String,
+    /// The number of uSTX stacked for this entry
+    pub total_ustx: u128,
+}
+
+/// How the reward set changed from one reward cycle to another.  Each entry is an
+/// `(address, old_amount, new_amount)` triple, where a missing side is 0, and each list is sorted
+/// by address.  Amounts stacked for the same address in several slots are summed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RewardSetDiff {
+    /// Addresses that only appear in the second reward set
+    pub added: Vec<(StacksAddress, u128, u128)>,
+    /// Addresses that only appear in the first reward set
+    pub removed: Vec<(StacksAddress, u128, u128)>,
+    /// Addresses in both reward sets, with different amounts stacked for them
+    pub changed: Vec<(StacksAddress, u128, u128)>,
+}
+
+impl RewardSetDiff {
+    /// Diff two reward sets, as returned by `get_reward_set_entries()`
+    pub fn from_entries(
+        entries_a: &[(StacksAddress, u128)],
+        entries_b: &[(StacksAddress, u128)],
+    ) -> RewardSetDiff {
+        let mut amounts: HashMap<StacksAddress, (u128, u128)> = HashMap::new();
+        for (addr, total_ustx) in entries_a.iter() {
+            amounts.entry(addr.clone()).or_insert((0, 0)).0 += total_ustx;
+        }
+        for (addr, total_ustx) in entries_b.iter() {
+            amounts.entry(addr.clone()).or_insert((0, 0)).1 += total_ustx;
+        }
+
+        let mut amounts: Vec<_> = amounts.into_iter().collect();
+        amounts.sort_by_key(|(addr, _)| (addr.bytes.0, addr.version));
+
+        let mut diff = RewardSetDiff::default();
+        for (addr, (old_amount, new_amount)) in amounts.into_iter() {
+            let entry = (addr, old_amount, new_amount);
+            if old_amount == 0 {
+                diff.added.push(entry);
+            } else if new_amount == 0 {
+                diff.removed.push(entry);
+            } else if old_amount != new_amount {
+                diff.changed.push(entry);
+            }
+        }
+        diff
+    }
+}
+
+/// Build and sign a transaction that calls `stack-stx` in the PoX contract, locking up `amount`
+/// uSTX for `lock_period` reward cycles and paying rewards to the given PoX address.
+/// The transaction is zero-fee, single-sig, and allows any post-conditions.
+pub fn build_stack_stx_tx(
+    key: &StacksPrivateKey,
+    nonce: u64,
+    amount: u128,
+    addr_version: AddressHashMode,
+    addr_bytes: Hash160,
+    lock_period: u128,
+    chain_id: u32,
+    version: TransactionVersion,
+) -> StacksTransaction {
+    // (define-public (stack-stx (amount-ustx uint)
+    //                           (pox-addr (tuple (version (buff 1)) (hashbytes (buff 20))))
+    //                           (lock-period uint))
+
+    let auth = TransactionAuth::from_p2pkh(key).unwrap();
+    let mut pox_lockup = StacksTransaction::new(
+        version,
+        auth,
+        TransactionPayload::new_contract_call(
+            boot_code_addr(),
+            "pox",
+            "stack-stx",
+            vec![
+                Value::UInt(amount),
+                Value::pox_addr(addr_version, addr_bytes),
+                Value::UInt(lock_period),
+            ],
+        )
+        .unwrap(),
+    );
+    pox_lockup.chain_id = chain_id;
+    pox_lockup.auth.set_origin_nonce(nonce);
+    pox_lockup.set_post_condition_mode(TransactionPostConditionMode::Allow);
+    pox_lockup.set_fee_rate(0);
+
+    let mut tx_signer = StacksTransactionSigner::new(&pox_lockup);
+    tx_signer.sign_origin(key).unwrap();
+    tx_signer.get_tx().unwrap()
 }
 
 impl StacksChainState {
@@ -129,8 +256,33 @@ impl StacksChainState {
 
     /// Determine which reward cycle this particular block lives in.
     pub fn get_reward_cycle(&mut self, burnchain: &Burnchain, burn_block_height: u64) -> u128 {
-        ((burn_block_height - burnchain.first_block_height)
-            / burnchain.pox_constants.reward_cycle_length as u64) as u128
+        burnchain.reward_cycle_of_height(burn_block_height) as u128
+    }
+
+    /// Determine the burnchain block height at which STX that are locked for `lock_period` reward
+    /// cycles, starting in `first_reward_cycle`, become unlocked.
+    /// Returns None if that height doesn't fit in a u64.
+    pub fn compute_unlock_height(
+        burnchain: &Burnchain,
+        first_reward_cycle: u128,
+        lock_period: u128,
+    ) -> Option<u64> {
+        let unlock_height = first_reward_cycle
+            .checked_add(lock_period)?
+            .checked_mul(burnchain.pox_constants.reward_cycle_length as u128)?
+            .checked_add(burnchain.first_block_height as u128)?;
+        u64::try_from(unlock_height).ok()
+    }
+
+    /// Get the liquid uSTX supply as of the given Stacks block -- the value of `stx-liquid-supply`
+    /// in that block -- straight from its header, without evaluating any Clarity.
+    pub fn get_liquid_ustx(&self, block_id: &StacksBlockId) -> Result<u128, Error> {
+        StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.headers_db(),
+            block_id,
+        )?
+        .map(|header_info| header_info.total_liquid_ustx)
+        .ok_or(Error::NoSuchBlockError)
     }
 
     /// Determine the minimum amount of STX per reward address required to stack in the _next_
@@ -179,11 +331,89 @@ impl StacksChainState {
             stacks_block_id,
             "pox",
             &format!("(is-pox-active u{})", reward_cycle),
-        )
-        .map(|value| value.expect_bool())
+        )?
+        .try_bool()
+        .ok_or_else(|| {
+            error!(
+                "(is-pox-active u{}) did not return a bool at {}",
+                reward_cycle, stacks_block_id
+            );
+            Error::InvalidChainstateDB
+        })
+    }
+
+    /// Get the Bitcoin address, as a base58check string, to which the given principal's PoX
+    /// rewards are paid.  Returns None if the principal isn't stacking.
+    pub fn get_stacker_bitcoin_address(
+        &mut self,
+        sortdb: &SortitionDB,
+        stacks_block_id: &StacksBlockId,
+        principal: &PrincipalData,
+    ) -> Result<Option<String>, Error> {
+        let stacker_info = match self
+            .eval_boot_code_read_only(
+                sortdb,
+                stacks_block_id,
+                "pox",
+                &format!("(get-stacker-info '{})", principal),
+            )?
+            .expect_optional()
+        {
+            Some(data) => data.expect_tuple(),
+            None => {
+                return Ok(None);
+            }
+        };
+
+        let pox_addr = stacker_info
+            .get("pox-addr")
+            .expect("FATAL: no pox-addr in stacker info")
+            .to_owned()
+            .expect_tuple();
+        let (hash_mode, hash) = tuple_to_pox_addr(pox_addr);
+        Ok(Some(
+            pox_addr_to_bitcoin_address(hash_mode, hash, self.mainnet).to_b58(),
+        ))
+    }
+
+    /// Determine how many reward addresses are in a given reward cycle's reward set, without
+    /// enumerating them.  This is a single read-only evaluation, so callers can use it to
+    /// budget the work that `get_reward_addresses()` will do.
+    pub fn get_reward_set_size_only(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        reward_cycle: u128,
+    ) -> Result<u128, Error> {
+        self.eval_boot_code_read_only(
+            sortdb,
+            block_id,
+            "pox",
+            &format!("(get-reward-set-size u{})", reward_cycle),
+        )?
+        .try_u128()
+        .ok_or_else(|| {
+            error!(
+                "(get-reward-set-size u{}) did not return a uint at {}",
+                reward_cycle, block_id
+            );
+            Error::InvalidChainstateDB
+        })
+    }
+
+    /// Decode an entry returned by (get-reward-set-pox-address ..) into its PoX address and
+    /// stacked amount.  Returns None if the value is not a (some (tuple ...)) of the right shape.
+    fn parse_reward_set_entry(value: Value) -> Option<(AddressHashMode, Hash160, u128)> {
+        let tuple_data = value.try_optional()??.try_tuple()?;
+        let pox_addr_tuple = tuple_data.get("pox-addr").ok()?.to_owned().try_tuple()?;
+        let (hash_mode, hash) = try_tuple_to_pox_addr(pox_addr_tuple)?;
+        let total_ustx = tuple_data.get("total-ustx").ok()?.to_owned().try_u128()?;
+        Some((hash_mode, hash, total_ustx))
     }
 
     /// Each address will have at least (get-stacking-minimum) tokens.
+    /// The addresses are sorted by address bytes, then by address version.  This order is
+    /// consensus-relevant: reward slots are assigned to the addresses in this order.
     pub fn get_reward_addresses(
         &mut self,
         burnchain: &Burnchain,
@@ -192,6 +422,25 @@ impl StacksChainState {
         block_id: &StacksBlockId,
     ) -> Result<Vec<(StacksAddress, u128)>, Error> {
         let reward_cycle = self.get_reward_cycle(burnchain, current_burn_height);
+        self.get_reward_set_entries(
+            sortdb,
+            block_id,
+            reward_cycle,
+            burnchain.pox_constants.max_reward_set_size(),
+        )
+    }
+
+    /// Get the reward addresses and the amounts stacked for them in the given reward cycle,
+    /// sorted by address bytes, then by address version.  Empty if PoX is disabled in this reward cycle.
+    /// Fails with Error::RewardSetTooLarge, without decoding any entries, if the reward set has
+    /// more than `max_addrs` entries.
+    pub fn get_reward_set_entries(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        reward_cycle: u128,
+        max_addrs: u128,
+    ) -> Result<Vec<(StacksAddress, u128)>, Error> {
         if !self.is_pox_active(sortdb, block_id, reward_cycle)? {
             debug!(
                 "PoX was voted disabled in block {} (reward cycle {})",
@@ -201,53 +450,70 @@ impl StacksChainState {
         }
 
         // how many in this cycle?
-        let num_addrs = self
-            .eval_boot_code_read_only(
-                sortdb,
-                block_id,
-                "pox",
-                &format!("(get-reward-set-size u{})", reward_cycle),
-            )?
-            .expect_u128();
+        let num_addrs = self.get_reward_set_size_only(sortdb, block_id, reward_cycle)?;
 
         debug!(
             "At block {:?} (reward cycle {}): {} PoX reward addresses",
             block_id, reward_cycle, num_addrs
         );
 
-        let mut ret = vec![];
-        for i in 0..num_addrs {
-            // value should be (optional (tuple (pox-addr (tuple (...))) (total-ustx uint))).
-            // Get the tuple.
-            let tuple_data = self
-                .eval_boot_code_read_only(
+        let mainnet = self.mainnet;
+        StacksChainState::collect_reward_set_entries(
+            num_addrs,
+            max_addrs,
+            reward_cycle,
+            mainnet,
+            |i| {
+                self.eval_boot_code_read_only(
                     sortdb,
                     block_id,
                     "pox",
                     &format!("(get-reward-set-pox-address u{} u{})", reward_cycle, i),
-                )?
-                .expect_optional()
-                .expect(&format!(
-                    "FATAL: missing PoX address in slot {} out of {} in reward cycle {}",
-                    i, num_addrs, reward_cycle
-                ))
-                .expect_tuple();
-
-            let pox_addr_tuple = tuple_data
-                .get("pox-addr")
-                .expect(&format!("FATAL: no 'pox-addr' in return value from (get-reward-set-pox-address u{} u{})", reward_cycle, i))
-                .to_owned()
-                .expect_tuple();
-
-            let (hash_mode, hash) = tuple_to_pox_addr(pox_addr_tuple);
-
-            let total_ustx = tuple_data
-                .get("total-ustx")
-                .expect(&format!("FATAL: no 'total-ustx' in return value from (get-reward-set-pox-address u{} u{})", reward_cycle, i))
-                .to_owned()
-                .expect_u128();
-
-            let version = match self.mainnet {
+                )
+            },
+        )
+    }
+
+    /// Decode the `num_addrs` entries of a reward set, using `get_entry` to load each one, and
+    /// sort them by address bytes, then by address version.  Refuses to load any of them if there are more than
+    /// `max_addrs`.
+    fn collect_reward_set_entries<F>(
+        num_addrs: u128,
+        max_addrs: u128,
+        reward_cycle: u128,
+        mainnet: bool,
+        mut get_entry: F,
+    ) -> Result<Vec<(StacksAddress, u128)>, Error>
+    where
+        F: FnMut(u128) -> Result<Value, Error>,
+    {
+        if num_addrs > max_addrs {
+            warn!(
+                "Reward cycle {} has {} PoX reward addresses, but at most {} are allowed",
+                reward_cycle, num_addrs, max_addrs
+            );
+            return Err(Error::RewardSetTooLarge(num_addrs, max_addrs));
+        }
+
+        let mut ret = vec![];
+        for i in 0..num_addrs {
+            // value should be (optional (tuple (pox-addr (tuple (...))) (total-ustx uint))).
+            // Get the tuple.
+            let value = get_entry(i)?;
+
+            let entry_opt = StacksChainState::parse_reward_set_entry(value);
+            let (hash_mode, hash, total_ustx) = match entry_opt {
+                Some(entry) => entry,
+                None => {
+                    error!(
+                        "Malformed or missing PoX address in slot {} out of {} in reward cycle {}",
+                        i, num_addrs, reward_cycle
+                    );
+                    return Err(Error::InvalidChainstateDB);
+                }
+            };
+
+            let version = match mainnet {
                 true => hash_mode.to_version_mainnet(),
                 false => hash_mode.to_version_testnet(),
             };
@@ -255,17 +521,65 @@ impl StacksChainState {
             ret.push((StacksAddress::new(version, hash), total_ustx));
         }
 
-        ret.sort_by_key(|k| k.0.bytes.0);
+        // consensus-critical: the reward set order is (address bytes, address version), so that
+        // addresses that differ only in their hash mode are ordered the same way everywhere
+        ret.sort_by_key(|k| (k.0.bytes.0, k.0.version));
 
         Ok(ret)
     }
+
+    /// Export the reward set of the given reward cycle as a JSON array of `RewardSetEntry`s, in
+    /// the same order as `get_reward_addresses()`.
+    pub fn export_reward_set_json(
+        &mut self,
+        burnchain: &Burnchain,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        reward_cycle: u128,
+    ) -> Result<String, Error> {
+        let entries: Vec<_> = self
+            .get_reward_set_entries(
+                sortdb,
+                block_id,
+                reward_cycle,
+                burnchain.pox_constants.max_reward_set_size(),
+            )?
+            .into_iter()
+            .map(|(addr, total_ustx)| RewardSetEntry {
+                stacks_address: addr.to_string(),
+                bitcoin_address: addr.to_bitcoin_address().to_b58(),
+                total_ustx,
+            })
+            .collect();
+
+        Ok(serde_json::to_string(&entries).expect("FATAL: failed to serialize reward set"))
+    }
+
+    /// Compare the reward sets of `cycle_a` and `cycle_b`, as of the given block, and report
+    /// which reward addresses entered, left, or changed the amount stacked for them.
+    pub fn diff_reward_sets(
+        &mut self,
+        burnchain: &Burnchain,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        cycle_a: u128,
+        cycle_b: u128,
+    ) -> Result<RewardSetDiff, Error> {
+        let max_addrs = burnchain.pox_constants.max_reward_set_size();
+        let entries_a = self.get_reward_set_entries(sortdb, block_id, cycle_a, max_addrs)?;
+        let entries_b = self.get_reward_set_entries(sortdb, block_id, cycle_b, max_addrs)?;
+        Ok(RewardSetDiff::from_entries(&entries_a, &entries_b))
+    }
 }
 
 #[cfg(test)]
 pub mod test {
     use chainstate::burn::db::sortdb::*;
     use chainstate::burn::db::*;
+    use chainstate::burn::operations::leader_block_commit::OUTPUTS_PER_COMMIT;
     use chainstate::burn::*;
+    use chainstate::coordinator::{OnChainRewardSetProvider, RewardSetProvider};
+    use chainstate::stacks::db::blocks::MINIMUM_TX_FEE_RATE_PER_BYTE;
     use chainstate::stacks::db::test::*;
     use chainstate::stacks::db::*;
     use chainstate::stacks::miner::test::*;
@@ -274,15 +588,19 @@ pub mod test {
     use chainstate::stacks::*;
 
     use burnchains::Address;
+    use burnchains::PoxConstants;
     use burnchains::PublicKey;
 
     use super::*;
 
     use net::test::*;
+    use net::StacksMessageCodec;
 
     use util::*;
 
     use vm::contracts::Contract;
+    use vm::costs::ExecutionCost;
+    use vm::representations::ClarityName;
     use vm::types::*;
 
     use std::convert::From;
@@ -304,9 +622,36 @@ pub mod test {
         burnchain: &Burnchain,
         test_name: &str,
         port: u16,
+    ) -> (TestPeer<'a>, Vec<StacksPrivateKey>) {
+        instantiate_pox_peer_with_seed(burnchain, test_name, port, None)
+    }
+
+    fn instantiate_pox_peer_with_seed<'a>(
+        burnchain: &Burnchain,
+        test_name: &str,
+        port: u16,
+        rng_seed: Option<u64>,
+    ) -> (TestPeer<'a>, Vec<StacksPrivateKey>) {
+        instantiate_pox_peer_with_opts(
+            burnchain,
+            test_name,
+            port,
+            rng_seed,
+            MinerRewardSchedule::default(),
+        )
+    }
+
+    fn instantiate_pox_peer_with_opts<'a>(
+        burnchain: &Burnchain,
+        test_name: &str,
+        port: u16,
+        rng_seed: Option<u64>,
+        miner_reward_schedule: MinerRewardSchedule,
     ) -> (TestPeer<'a>, Vec<StacksPrivateKey>) {
         let mut peer_config = TestPeerConfig::new(test_name, port, port + 1);
         peer_config.burnchain = burnchain.clone();
+        peer_config.rng_seed = rng_seed;
+        peer_config.miner_reward_schedule = miner_reward_schedule;
         peer_config.setup_code = format!(
             "(contract-call? .pox set-burnchain-parameters u{} u{} u{} u{})",
             burnchain.first_block_height,
@@ -484,24 +829,6 @@ pub mod test {
         contract_opt
     }
 
-    fn make_pox_addr(addr_version: AddressHashMode, addr_bytes: Hash160) -> Value {
-        Value::Tuple(
-            TupleData::from_data(vec![
-                (
-                    ClarityName::try_from("version".to_owned()).unwrap(),
-                    Value::buff_from_byte(addr_version as u8),
-                ),
-                (
-                    ClarityName::try_from("hashbytes".to_owned()).unwrap(),
-                    Value::Sequence(SequenceData::Buffer(BuffData {
-                        data: addr_bytes.as_bytes().to_vec(),
-                    })),
-                ),
-            ])
-            .unwrap(),
-        )
-    }
-
     fn make_pox_lockup(
         key: &StacksPrivateKey,
         nonce: u64,
@@ -510,35 +837,16 @@ pub mod test {
         addr_bytes: Hash160,
         lock_period: u128,
     ) -> StacksTransaction {
-        // (define-public (stack-stx (amount-ustx uint)
-        //                           (pox-addr (tuple (version (buff 1)) (hashbytes (buff 20))))
-        //                           (lock-period uint))
-
-        let auth = TransactionAuth::from_p2pkh(key).unwrap();
-        let addr = auth.origin().address_testnet();
-        let mut pox_lockup = StacksTransaction::new(
+        build_stack_stx_tx(
+            key,
+            nonce,
+            amount,
+            addr_version,
+            addr_bytes,
+            lock_period,
+            0x80000000,
             TransactionVersion::Testnet,
-            auth,
-            TransactionPayload::new_contract_call(
-                boot_code_addr(),
-                "pox",
-                "stack-stx",
-                vec![
-                    Value::UInt(amount),
-                    make_pox_addr(addr_version, addr_bytes),
-                    Value::UInt(lock_period),
-                ],
-            )
-            .unwrap(),
-        );
-        pox_lockup.chain_id = 0x80000000;
-        pox_lockup.auth.set_origin_nonce(nonce);
-        pox_lockup.set_post_condition_mode(TransactionPostConditionMode::Allow);
-        pox_lockup.set_fee_rate(0);
-
-        let mut tx_signer = StacksTransactionSigner::new(&pox_lockup);
-        tx_signer.sign_origin(key).unwrap();
-        tx_signer.get_tx().unwrap()
+        )
     }
 
     // make a stream of invalid pox-lockup transactions
@@ -595,7 +903,7 @@ pub mod test {
 
         let bad_lock_period_short = generator(
             amount,
-            make_pox_addr(AddressHashMode::SerializeP2PKH, addr_bytes.clone()),
+            Value::pox_addr(AddressHashMode::SerializeP2PKH, addr_bytes.clone()),
             0,
             nonce,
         );
@@ -604,7 +912,7 @@ pub mod test {
 
         let bad_lock_period_long = generator(
             amount,
-            make_pox_addr(AddressHashMode::SerializeP2PKH, addr_bytes.clone()),
+            Value::pox_addr(AddressHashMode::SerializeP2PKH, addr_bytes.clone()),
             13,
             nonce,
         );
@@ -613,7 +921,7 @@ pub mod test {
 
         let bad_amount = generator(
             0,
-            make_pox_addr(AddressHashMode::SerializeP2PKH, addr_bytes.clone()),
+            Value::pox_addr(AddressHashMode::SerializeP2PKH, addr_bytes.clone()),
             1,
             nonce,
         );
@@ -730,7 +1038,7 @@ pub mod test {
                 "do-contract-lockup",
                 vec![
                     Value::UInt(amount),
-                    make_pox_addr(addr_version, addr_bytes),
+                    Value::pox_addr(addr_version, addr_bytes),
                     Value::UInt(lock_period),
                 ],
             )
@@ -839,80 +1147,399 @@ pub mod test {
     }
 
     #[test]
-    fn test_liquid_ustx() {
-        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
-        burnchain.pox_constants.reward_cycle_length = 5;
-        burnchain.pox_constants.prepare_length = 2;
-
-        let (mut peer, keys) = instantiate_pox_peer(&burnchain, "test-liquid-ustx", 6000);
-
-        let num_blocks = 10;
-        let mut expected_liquid_ustx = 1024 * 1000000 * (keys.len() as u128);
-
-        for tenure_id in 0..num_blocks {
-            let microblock_privkey = StacksPrivateKey::new();
-            let microblock_pubkeyhash =
-                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
-            let tip =
-                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
-                    .unwrap();
-
-            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
-                |ref mut miner,
-                 ref mut sortdb,
-                 ref mut chainstate,
-                 vrf_proof,
-                 ref parent_opt,
-                 ref parent_microblock_header_opt| {
-                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
-                    let coinbase_tx = make_coinbase(miner, tenure_id);
-
-                    let block_txs = vec![coinbase_tx];
+    fn boot_code_parses() {
+        use vm::analysis::type_check;
+        use vm::ast::build_ast;
+        use vm::database::MemoryBackingStore;
+
+        for boot_code in [&*STACKS_BOOT_CODE_MAINNET, &*STACKS_BOOT_CODE_TESTNET].iter() {
+            let mut marf = MemoryBackingStore::new();
+            let mut analysis_db = marf.as_analysis_db();
+            for (boot_contract_name, boot_contract_code) in boot_code.iter() {
+                let contract_id = boot_code_id(boot_contract_name);
+                let mut ast = build_ast(&contract_id, boot_contract_code, &mut ())
+                    .expect(&format!("Boot contract {} does not parse", boot_contract_name));
+                type_check(&contract_id, &mut ast.expressions, &mut analysis_db, true).expect(
+                    &format!("Boot contract {} does not type-check", boot_contract_name),
+                );
+            }
+        }
+    }
 
-                    let block_builder = StacksBlockBuilder::make_block_builder(
-                        &parent_tip,
-                        vrf_proof,
-                        tip.total_burn,
-                        microblock_pubkeyhash,
-                    )
-                    .unwrap();
-                    let (anchored_block, _size, _cost) =
-                        StacksBlockBuilder::make_anchored_block_from_txs(
-                            block_builder,
-                            chainstate,
-                            &sortdb.index_conn(),
-                            block_txs,
-                        )
-                        .unwrap();
-                    (anchored_block, vec![])
-                },
+    #[test]
+    fn test_pox_addr_tuple_roundtrip() {
+        let hash = Hash160::from_hex("6ea17fc39169cdd9f2414a893aa5ce0c4b4c8934").unwrap();
+        for hash_mode in [
+            AddressHashMode::SerializeP2PKH,
+            AddressHashMode::SerializeP2SH,
+            AddressHashMode::SerializeP2WPKH,
+            AddressHashMode::SerializeP2WSH,
+        ]
+        .iter()
+        {
+            let pox_addr = Value::pox_addr(*hash_mode, hash.clone());
+            assert_eq!(
+                tuple_to_pox_addr(pox_addr.expect_tuple()),
+                (*hash_mode, hash.clone())
             );
+        }
+    }
 
-            peer.next_burnchain_block(burn_ops.clone());
-            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+    #[test]
+    fn test_pox_addr_to_bitcoin_address() {
+        let p2pkh_bytes = Hash160::from_hex("6ea17fc39169cdd9f2414a893aa5ce0c4b4c8934").unwrap();
+        let p2pkh_tuple =
+            Value::pox_addr(AddressHashMode::SerializeP2PKH, p2pkh_bytes).expect_tuple();
+        let (hash_mode, hash) = tuple_to_pox_addr(p2pkh_tuple);
+        assert_eq!(hash_mode, AddressHashMode::SerializeP2PKH);
+        assert_eq!(
+            pox_addr_to_bitcoin_address(hash_mode, hash.clone(), true).to_b58(),
+            "1B5xoFjSwAB3DUum7dxXgj3brnYsXibLbc"
+        );
 
-            let liquid_ustx = get_liquid_ustx(&mut peer);
-            assert_eq!(liquid_ustx, expected_liquid_ustx);
+        let p2pkh_testnet_bytes =
+            Hash160::from_hex("74178497e927ff3ff1428a241be454d393c3c91c").unwrap();
+        assert_eq!(
+            pox_addr_to_bitcoin_address(hash_mode, p2pkh_testnet_bytes, false).to_b58(),
+            "mr6nrMvvh44sR5MiX929mMXP5hqgaTr6fx"
+        );
 
-            if tenure_id >= (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW) as usize {
-                // add mature coinbases
-                expected_liquid_ustx += 500 * 1000000;
-            }
-        }
+        let p2sh_bytes = Hash160::from_hex("2c2edf39b098e05cf770e6b5a2fcedb54ee4fe05").unwrap();
+        let p2sh_tuple = Value::pox_addr(AddressHashMode::SerializeP2SH, p2sh_bytes).expect_tuple();
+        let (hash_mode, hash) = tuple_to_pox_addr(p2sh_tuple);
+        assert_eq!(hash_mode, AddressHashMode::SerializeP2SH);
+        assert_eq!(
+            pox_addr_to_bitcoin_address(hash_mode, hash, true).to_b58(),
+            "35idohuiQNndP1xR3FhNVHXgKF9YYPhWo4"
+        );
+
+        let p2sh_testnet_bytes =
+            Hash160::from_hex("3bbc6b200412398dc98c6eb49d20c6b01715c2c1").unwrap();
+        assert_eq!(
+            pox_addr_to_bitcoin_address(hash_mode, p2sh_testnet_bytes, false).to_b58(),
+            "2Mxh5a9QxP5jgABfzATLpmFVofbzDeFRJyt"
+        );
     }
 
     #[test]
-    fn test_hook_special_contract_call() {
-        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
-        burnchain.pox_constants.reward_cycle_length = 3;
-        burnchain.pox_constants.prepare_length = 1;
+    fn test_parse_reward_set_entry() {
+        let hash = Hash160([0x11; 20]);
+        let make_entry = |pox_addr: Value, total_ustx: Value| {
+            Value::some(Value::Tuple(
+                TupleData::from_data(vec![
+                    (
+                        ClarityName::try_from("pox-addr".to_string()).unwrap(),
+                        pox_addr,
+                    ),
+                    (
+                        ClarityName::try_from("total-ustx".to_string()).unwrap(),
+                        total_ustx,
+                    ),
+                ])
+                .unwrap(),
+            ))
+            .unwrap()
+        };
 
-        let (mut peer, mut keys) =
-            instantiate_pox_peer(&burnchain, "test-hook-special-contract-call", 6007);
+        let entry = make_entry(
+            Value::pox_addr(AddressHashMode::SerializeP2PKH, hash.clone()),
+            Value::UInt(123),
+        );
+        assert_eq!(
+            StacksChainState::parse_reward_set_entry(entry),
+            Some((AddressHashMode::SerializeP2PKH, hash.clone(), 123))
+        );
 
-        let num_blocks = 15;
+        // missing entry
+        assert_eq!(
+            StacksChainState::parse_reward_set_entry(Value::none()),
+            None
+        );
 
-        let alice = keys.pop().unwrap();
+        // wrong type for total-ustx
+        let entry = make_entry(
+            Value::pox_addr(AddressHashMode::SerializeP2PKH, hash.clone()),
+            Value::Int(123),
+        );
+        assert_eq!(StacksChainState::parse_reward_set_entry(entry), None);
+
+        // pox-addr hashbytes of the wrong length
+        let bad_pox_addr = Value::Tuple(
+            TupleData::from_data(vec![
+                (
+                    ClarityName::try_from("version".to_string()).unwrap(),
+                    Value::buff_from_byte(AddressHashMode::SerializeP2PKH as u8),
+                ),
+                (
+                    ClarityName::try_from("hashbytes".to_string()).unwrap(),
+                    Value::buff_from(vec![0x11; 19]).unwrap(),
+                ),
+            ])
+            .unwrap(),
+        );
+        let entry = make_entry(bad_pox_addr, Value::UInt(123));
+        assert_eq!(StacksChainState::parse_reward_set_entry(entry), None);
+
+        // not an optional at all
+        assert_eq!(
+            StacksChainState::parse_reward_set_entry(Value::UInt(123)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reward_set_size_guard() {
+        let burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        let max_addrs = burnchain.pox_constants.max_reward_set_size();
+
+        // every entry holds at least 1/20000 of the liquid supply, whatever the cycle's shape
+        assert_eq!(max_addrs, 20000);
+        assert_eq!(PoxConstants::new(5, 2, 2, 25).max_reward_set_size(), 20000);
+
+        let hash = Hash160([0x22; 20]);
+        let entry = Value::some(Value::Tuple(
+            TupleData::from_data(vec![
+                (
+                    ClarityName::try_from("pox-addr".to_string()).unwrap(),
+                    Value::pox_addr(AddressHashMode::SerializeP2PKH, hash.clone()),
+                ),
+                (
+                    ClarityName::try_from("total-ustx".to_string()).unwrap(),
+                    Value::UInt(1000),
+                ),
+            ])
+            .unwrap(),
+        ))
+        .unwrap();
+
+        // an absurd (get-reward-set-size) trips the guard before any entry is loaded
+        let mut num_loaded = 0;
+        let res = StacksChainState::collect_reward_set_entries(
+            u128::max_value(),
+            max_addrs,
+            1,
+            false,
+            |_i| {
+                num_loaded += 1;
+                Ok(entry.clone())
+            },
+        );
+        match res {
+            Err(chainstate_error::RewardSetTooLarge(num, max)) => {
+                assert_eq!(num, u128::max_value());
+                assert_eq!(max, max_addrs);
+            }
+            x => panic!("Expected RewardSetTooLarge, got {:?}", &x),
+        }
+        assert_eq!(num_loaded, 0);
+
+        // so does one just over the limit
+        let res = StacksChainState::collect_reward_set_entries(
+            max_addrs + 1,
+            max_addrs,
+            1,
+            false,
+            |_i| Ok(entry.clone()),
+        );
+        match res {
+            Err(chainstate_error::RewardSetTooLarge(num, max)) => {
+                assert_eq!(num, 20001);
+                assert_eq!(max, 20000);
+            }
+            x => panic!("Expected RewardSetTooLarge, got {:?}", &x),
+        }
+
+        // a reward set at the limit is loaded in full
+        let entries =
+            StacksChainState::collect_reward_set_entries(max_addrs, max_addrs, 1, false, |_i| {
+                Ok(entry.clone())
+            })
+            .unwrap();
+        assert_eq!(entries.len(), 20000);
+
+        // a reward set within the limit is loaded in full
+        let mut num_loaded = 0;
+        let entries = StacksChainState::collect_reward_set_entries(3, max_addrs, 1, false, |_i| {
+            num_loaded += 1;
+            Ok(entry.clone())
+        })
+        .unwrap();
+        assert_eq!(num_loaded, 3);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].0.bytes, hash);
+        assert_eq!(entries[0].1, 1000);
+    }
+
+    #[test]
+    fn test_reward_set_order() {
+        let make_entry = |hash_mode: AddressHashMode, hash: Hash160, total_ustx: u128| {
+            Value::some(Value::Tuple(
+                TupleData::from_data(vec![
+                    (
+                        ClarityName::try_from("pox-addr".to_string()).unwrap(),
+                        Value::pox_addr(hash_mode, hash),
+                    ),
+                    (
+                        ClarityName::try_from("total-ustx".to_string()).unwrap(),
+                        Value::UInt(total_ustx),
+                    ),
+                ])
+                .unwrap(),
+            ))
+            .unwrap()
+        };
+
+        // the same hash bytes under both hash modes, plus a smaller hash
+        let shared = Hash160([0x33; 20]);
+        let smaller = Hash160([0x11; 20]);
+        let entries = vec![
+            make_entry(AddressHashMode::SerializeP2PKH, shared.clone(), 1000),
+            make_entry(AddressHashMode::SerializeP2SH, shared.clone(), 2000),
+            make_entry(AddressHashMode::SerializeP2PKH, smaller.clone(), 3000),
+        ];
+
+        // sorted by bytes, then version, regardless of the order the entries were loaded in
+        for mainnet in [true, false].iter() {
+            let (p2pkh, p2sh) = if *mainnet {
+                (
+                    C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+                    C32_ADDRESS_VERSION_MAINNET_MULTISIG,
+                )
+            } else {
+                (
+                    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                    C32_ADDRESS_VERSION_TESTNET_MULTISIG,
+                )
+            };
+            // multisig versions sort before singlesig versions on both networks
+            assert!(p2sh < p2pkh);
+            let expected = vec![
+                (StacksAddress::new(p2pkh, smaller.clone()), 3000),
+                (StacksAddress::new(p2sh, shared.clone()), 2000),
+                (StacksAddress::new(p2pkh, shared.clone()), 1000),
+            ];
+
+            let forwards = StacksChainState::collect_reward_set_entries(3, 10, 1, *mainnet, |i| {
+                Ok(entries[i as usize].clone())
+            })
+            .unwrap();
+            assert_eq!(forwards, expected);
+
+            let backwards = StacksChainState::collect_reward_set_entries(3, 10, 1, *mainnet, |i| {
+                Ok(entries[2 - i as usize].clone())
+            })
+            .unwrap();
+            assert_eq!(backwards, expected);
+        }
+    }
+
+    #[test]
+    fn test_compute_unlock_height() {
+        let mut burnchain = Burnchain::default_unittest(100, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+
+        // single-cycle lock
+        assert_eq!(
+            StacksChainState::compute_unlock_height(&burnchain, 2, 1),
+            Some((2 + 1) * 5 + 100)
+        );
+
+        // multi-cycle lock
+        assert_eq!(
+            StacksChainState::compute_unlock_height(&burnchain, 2, 12),
+            Some((2 + 12) * 5 + 100)
+        );
+
+        // the unlock height falls on the boundary of the reward cycle after the lock period
+        let unlock_height = StacksChainState::compute_unlock_height(&burnchain, 3, 4).unwrap();
+        assert_eq!(
+            (unlock_height - burnchain.first_block_height)
+                % (burnchain.pox_constants.reward_cycle_length as u64),
+            0
+        );
+        assert_eq!(
+            (unlock_height - burnchain.first_block_height)
+                / (burnchain.pox_constants.reward_cycle_length as u64),
+            3 + 4
+        );
+
+        // heights past u64::MAX are not truncated
+        assert_eq!(
+            StacksChainState::compute_unlock_height(&burnchain, (u64::MAX / 5) as u128, 1),
+            None
+        );
+        assert_eq!(
+            StacksChainState::compute_unlock_height(&burnchain, u128::MAX, 1),
+            None
+        );
+    }
+
+    #[test]
+    fn test_build_stack_stx_tx() {
+        let key = StacksPrivateKey::new();
+        let addr_bytes = Hash160([0x11; 20]);
+        let tx = build_stack_stx_tx(
+            &key,
+            7,
+            1_000_000,
+            AddressHashMode::SerializeP2SH,
+            addr_bytes.clone(),
+            12,
+            0x00000001,
+            TransactionVersion::Mainnet,
+        );
+
+        tx.verify().unwrap();
+        assert_eq!(tx.version, TransactionVersion::Mainnet);
+        assert_eq!(tx.chain_id, 0x00000001);
+        assert_eq!(tx.get_origin_nonce(), 7);
+        assert_eq!(tx.get_fee_rate(), 0);
+        assert_eq!(tx.post_condition_mode, TransactionPostConditionMode::Allow);
+        assert_eq!(
+            tx.origin_address(),
+            StacksAddress::from_public_keys(
+                C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+                &AddressHashMode::SerializeP2PKH,
+                1,
+                &vec![StacksPublicKey::from_private(&key)],
+            )
+            .unwrap()
+        );
+
+        // survives a round-trip through the wire format
+        let mut tx_bytes = vec![];
+        tx.consensus_serialize(&mut tx_bytes).unwrap();
+        let decoded_tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).unwrap();
+        assert_eq!(decoded_tx, tx);
+        decoded_tx.verify().unwrap();
+
+        match decoded_tx.payload {
+            TransactionPayload::ContractCall(ref cc) => {
+                assert_eq!(cc.address, boot_code_addr());
+                assert_eq!(cc.contract_name.as_str(), "pox");
+                assert_eq!(cc.function_name.as_str(), "stack-stx");
+                assert_eq!(cc.function_args.len(), 3);
+                assert_eq!(cc.function_args[0], Value::UInt(1_000_000));
+                assert_eq!(
+                    tuple_to_pox_addr(cc.function_args[1].clone().expect_tuple()),
+                    (AddressHashMode::SerializeP2SH, addr_bytes)
+                );
+                assert_eq!(cc.function_args[2], Value::UInt(12));
+            }
+            _ => panic!("Expected a contract-call, got {:?}", &decoded_tx.payload),
+        }
+    }
+
+    #[test]
+    fn test_liquid_ustx() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, keys) = instantiate_pox_peer(&burnchain, "test-liquid-ustx", 6000);
+
+        let num_blocks = 10;
+        let mut expected_liquid_ustx = 1024 * 1000000 * (keys.len() as u128);
 
         for tenure_id in 0..num_blocks {
             let microblock_privkey = StacksPrivateKey::new();
@@ -922,103 +1549,1342 @@ pub mod test {
                 SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
                     .unwrap();
 
-            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(|ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, ref parent_opt, ref parent_microblock_header_opt| {
-                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
-                let coinbase_tx = make_coinbase(miner, tenure_id);
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
 
-                let mut block_txs = vec![
-                    coinbase_tx
-                ];
+                    let block_txs = vec![coinbase_tx];
 
-                if tenure_id == 1 {
-                    let alice_lockup_1 = make_pox_lockup(&alice, 0, 512 * 1000000, AddressHashMode::SerializeP2PKH, key_to_stacks_addr(&alice).bytes, 1);
-                    block_txs.push(alice_lockup_1);
-                }
-                if tenure_id == 2 {
-                    let alice_test_tx = make_bare_contract(&alice, 1, 0, "nested-stacker", &format!(
-                        "(define-public (nested-stack-stx)
-                            (contract-call? '{}.pox stack-stx u512000000 (tuple (version 0x00) (hashbytes 0xffffffffffffffffffffffffffffffffffffffff)) u1))", STACKS_BOOT_CODE_CONTRACT_ADDRESS));
+                    let block_builder = StacksBlockBuilder::make_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
 
-                    block_txs.push(alice_test_tx);
-                }
-                if tenure_id == 8 {
-                    // alice locks 512_000_000 STX through her contract
-                    let auth = TransactionAuth::from_p2pkh(&alice).unwrap();
-                    let addr = auth.origin().address_testnet();
-                    let mut contract_call = StacksTransaction::new(TransactionVersion::Testnet, auth,
-                                                                TransactionPayload::new_contract_call(key_to_stacks_addr(&alice),
-                                                                                                     "nested-stacker",
-                                                                                                     "nested-stack-stx",
-                                                                                                     vec![]).unwrap());
-                    contract_call.chain_id = 0x80000000;
-                    contract_call.auth.set_origin_nonce(2);
-                    contract_call.set_post_condition_mode(TransactionPostConditionMode::Allow);
-                    contract_call.set_fee_rate(0);
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
 
-                    let mut tx_signer = StacksTransactionSigner::new(&contract_call);
-                    tx_signer.sign_origin(&alice).unwrap();
-                    let tx = tx_signer.get_tx().unwrap();
+            let liquid_ustx = get_liquid_ustx(&mut peer);
+            assert_eq!(liquid_ustx, expected_liquid_ustx);
 
-                    block_txs.push(tx);
-                }
+            if tenure_id >= (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW) as usize {
+                // add mature coinbases
+                expected_liquid_ustx += 500 * 1000000;
+            }
+        }
+    }
 
-                let block_builder = StacksBlockBuilder::make_block_builder(&parent_tip, vrf_proof, tip.total_burn, microblock_pubkeyhash).unwrap();
-                let (anchored_block, _size, _cost) = StacksBlockBuilder::make_anchored_block_from_txs(block_builder, chainstate, &sortdb.index_conn(), block_txs).unwrap();
-                (anchored_block, vec![])
-            });
+    #[test]
+    fn test_get_liquid_ustx() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, keys) = instantiate_pox_peer(&burnchain, "test-get-liquid-ustx", 6053);
+
+        let num_blocks = MINER_REWARD_MATURITY + MINER_REWARD_WINDOW + 3;
+        let mut expected_liquid_ustx = 1024 * 1000000 * (keys.len() as u128);
+
+        for tenure_id in 0..num_blocks as usize {
+            let microblock_privkey = StacksPrivateKey::new();
+            let microblock_pubkeyhash =
+                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let block_txs = vec![coinbase_tx];
+
+                    let block_builder = StacksBlockBuilder::make_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
 
             peer.next_burnchain_block(burn_ops.clone());
             peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
 
-            // before/after alice's tokens lock
-            if tenure_id == 0 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 1024 * 1000000);
-            } else if tenure_id == 1 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 512 * 1000000);
-            }
-            // before/after alice's tokens unlock
-            else if tenure_id == 4 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 512 * 1000000);
-            } else if tenure_id == 5 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 1024 * 1000000);
-            }
-            // before/after contract lockup
-            else if tenure_id == 7 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 1024 * 1000000);
-            } else if tenure_id == 8 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 512 * 1000000);
+            let (consensus_hash, block_bhh) = SortitionDB::get_canonical_stacks_chain_tip_hash(
+                peer.sortdb.as_ref().unwrap().conn(),
+            )
+            .unwrap();
+            let tip_id = StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+
+            // same as what the boot contract sees
+            let liquid_ustx = peer.chainstate().get_liquid_ustx(&tip_id).unwrap();
+            assert_eq!(liquid_ustx, expected_liquid_ustx);
+            assert_eq!(liquid_ustx, get_liquid_ustx(&mut peer));
+
+            if tenure_id >= (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW) as usize {
+                // add mature coinbases
+                expected_liquid_ustx += 500 * 1000000;
             }
-            // before/after contract-locked tokens unlock
-            else if tenure_id == 13 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 512 * 1000000);
-            } else if tenure_id == 14 {
-                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
-                assert_eq!(alice_balance, 1024 * 1000000);
+        }
+
+        // some coinbases matured
+        assert!(expected_liquid_ustx > 1024 * 1000000 * (keys.len() as u128));
+
+        // unknown block
+        match peer
+            .chainstate()
+            .get_liquid_ustx(&StacksBlockId([0x11; 32]))
+        {
+            Err(chainstate_error::NoSuchBlockError) => {}
+            x => panic!("Expected NoSuchBlockError, got {:?}", &x),
+        }
+    }
+
+    #[test]
+    fn test_liquid_ustx_short_maturity() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let schedule = MinerRewardSchedule {
+            maturity: 1,
+            window: 1,
+        };
+        assert!(schedule.delay() < MINER_REWARD_MATURITY + MINER_REWARD_WINDOW);
+
+        let (mut peer, keys) = instantiate_pox_peer_with_opts(
+            &burnchain,
+            "test-liquid-ustx-short-maturity",
+            6042,
+            None,
+            schedule,
+        );
+
+        // the schedule is stored with the chainstate, so every other handle to it uses it too
+        let (reopened, _) = peer.chainstate().reopen().unwrap();
+        assert_eq!(reopened.miner_reward_schedule, schedule);
+
+        // not enough blocks for any coinbase to mature on the default schedule
+        let num_blocks = MINER_REWARD_MATURITY + MINER_REWARD_WINDOW;
+        let initial_liquid_ustx = 1024 * 1000000 * (keys.len() as u128);
+        let mut expected_liquid_ustx = initial_liquid_ustx;
+
+        for tenure_id in 0..num_blocks as usize {
+            let microblock_privkey = StacksPrivateKey::new();
+            let microblock_pubkeyhash =
+                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let block_txs = vec![coinbase_tx];
+
+                    let block_builder = StacksBlockBuilder::make_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            let liquid_ustx = get_liquid_ustx(&mut peer);
+            assert_eq!(liquid_ustx, expected_liquid_ustx);
+
+            if tenure_id == (schedule.delay() + 1) as usize {
+                // first coinbase matured on the configured schedule
+                assert!(liquid_ustx > initial_liquid_ustx);
+            }
+
+            if tenure_id >= schedule.delay() as usize {
+                // add mature coinbases
+                expected_liquid_ustx += 500 * 1000000;
+            }
+        }
+    }
+
+    #[test]
+    fn test_advance_tenures() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let schedule = MinerRewardSchedule {
+            maturity: 1,
+            window: 1,
+        };
+        let (mut peer, keys) = instantiate_pox_peer_with_opts(
+            &burnchain,
+            "test-advance-tenures",
+            6050,
+            None,
+            schedule,
+        );
+
+        let initial_liquid_ustx = 1024 * 1000000 * (keys.len() as u128);
+        let start_height =
+            SortitionDB::get_canonical_burn_chain_tip(peer.sortdb.as_ref().unwrap().conn())
+                .unwrap()
+                .block_height;
+
+        let num_tenures = 5;
+        let tip = peer.advance_tenures(num_tenures);
+
+        let end_height =
+            SortitionDB::get_canonical_burn_chain_tip(peer.sortdb.as_ref().unwrap().conn())
+                .unwrap()
+                .block_height;
+        assert_eq!(end_height, start_height + num_tenures as u64);
+
+        let tip_header = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            peer.chainstate().headers_db(),
+            &tip,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(tip_header.block_height, num_tenures as u64);
+
+        // every coinbase at least `schedule.delay()` blocks below the tip has matured
+        let matured = (num_tenures as u64 - 1).saturating_sub(schedule.delay());
+        assert!(matured > 0);
+        assert_eq!(
+            get_liquid_ustx(&mut peer),
+            initial_liquid_ustx + (matured as u128) * 500 * 1000000
+        );
+    }
+
+    #[test]
+    fn test_hook_special_contract_call() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 3;
+        burnchain.pox_constants.prepare_length = 1;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-hook-special-contract-call", 6007);
+
+        let num_blocks = 15;
+
+        let alice = keys.pop().unwrap();
+
+        for tenure_id in 0..num_blocks {
+            let microblock_privkey = StacksPrivateKey::new();
+            let microblock_pubkeyhash =
+                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(|ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, ref parent_opt, ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                let mut block_txs = vec![
+                    coinbase_tx
+                ];
+
+                if tenure_id == 1 {
+                    let alice_lockup_1 = make_pox_lockup(&alice, 0, 512 * 1000000, AddressHashMode::SerializeP2PKH, key_to_stacks_addr(&alice).bytes, 1);
+                    block_txs.push(alice_lockup_1);
+                }
+                if tenure_id == 2 {
+                    let alice_test_tx = make_bare_contract(&alice, 1, 0, "nested-stacker", &format!(
+                        "(define-public (nested-stack-stx)
+                            (contract-call? '{}.pox stack-stx u512000000 (tuple (version 0x00) (hashbytes 0xffffffffffffffffffffffffffffffffffffffff)) u1))", STACKS_BOOT_CODE_CONTRACT_ADDRESS));
+
+                    block_txs.push(alice_test_tx);
+                }
+                if tenure_id == 8 {
+                    // alice locks 512_000_000 STX through her contract
+                    let auth = TransactionAuth::from_p2pkh(&alice).unwrap();
+                    let addr = auth.origin().address_testnet();
+                    let mut contract_call = StacksTransaction::new(TransactionVersion::Testnet, auth,
+                                                                TransactionPayload::new_contract_call(key_to_stacks_addr(&alice),
+                                                                                                     "nested-stacker",
+                                                                                                     "nested-stack-stx",
+                                                                                                     vec![]).unwrap());
+                    contract_call.chain_id = 0x80000000;
+                    contract_call.auth.set_origin_nonce(2);
+                    contract_call.set_post_condition_mode(TransactionPostConditionMode::Allow);
+                    contract_call.set_fee_rate(0);
+
+                    let mut tx_signer = StacksTransactionSigner::new(&contract_call);
+                    tx_signer.sign_origin(&alice).unwrap();
+                    let tx = tx_signer.get_tx().unwrap();
+
+                    block_txs.push(tx);
+                }
+
+                let block_builder = StacksBlockBuilder::make_block_builder(&parent_tip, vrf_proof, tip.total_burn, microblock_pubkeyhash).unwrap();
+                let (anchored_block, _size, _cost) = StacksBlockBuilder::make_anchored_block_from_txs(block_builder, chainstate, &sortdb.index_conn(), block_txs).unwrap();
+                (anchored_block, vec![])
+            });
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            // before/after alice's tokens lock
+            if tenure_id == 0 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 1024 * 1000000);
+            } else if tenure_id == 1 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 512 * 1000000);
+            }
+            // before/after alice's tokens unlock
+            else if tenure_id == 4 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 512 * 1000000);
+            } else if tenure_id == 5 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 1024 * 1000000);
+            }
+            // before/after contract lockup
+            else if tenure_id == 7 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 1024 * 1000000);
+            } else if tenure_id == 8 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 512 * 1000000);
+            }
+            // before/after contract-locked tokens unlock
+            else if tenure_id == 13 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 512 * 1000000);
+            } else if tenure_id == 14 {
+                let alice_balance = get_balance(&mut peer, &key_to_stacks_addr(&alice).into());
+                assert_eq!(alice_balance, 1024 * 1000000);
+            }
+        }
+    }
+
+    #[test]
+    fn test_liquid_ustx_burns() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) = instantiate_pox_peer(&burnchain, "test-liquid-ustx", 6026);
+
+        let num_blocks = 10;
+        let mut expected_liquid_ustx = 1024 * 1000000 * (keys.len() as u128);
+
+        let alice = keys.pop().unwrap();
+
+        for tenure_id in 0..num_blocks {
+            let microblock_privkey = StacksPrivateKey::new();
+            let microblock_pubkeyhash =
+                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let burn_tx = make_bare_contract(
+                        &alice,
+                        tenure_id as u64,
+                        0,
+                        &format!("alice-burns-{}", &tenure_id),
+                        "(stx-burn? u1 tx-sender)",
+                    );
+
+                    let block_txs = vec![coinbase_tx, burn_tx];
+
+                    let block_builder = StacksBlockBuilder::make_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            expected_liquid_ustx -= 1;
+
+            let liquid_ustx = get_liquid_ustx(&mut peer);
+            assert_eq!(liquid_ustx, expected_liquid_ustx);
+
+            if tenure_id >= (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW) as usize {
+                // add mature coinbases
+                expected_liquid_ustx += 500 * 1000000;
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_read_only_batch() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, _) = instantiate_pox_peer(&burnchain, "test-eval-read-only-batch", 6036);
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 0);
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        let stacking_minimum = eval_at_tip(&mut peer, "pox", "(get-stacking-minimum)");
+
+        let (values, failed) = with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            let values = chainstate
+                .eval_read_only_batch(
+                    sortdb,
+                    &stacks_block_id,
+                    &[
+                        (boot_code_id("pox"), "(get-total-ustx-stacked u0)"),
+                        (boot_code_id("pox"), "(get-stacking-minimum)"),
+                    ],
+                )
+                .unwrap();
+            let failed = chainstate.eval_read_only_batch(
+                sortdb,
+                &stacks_block_id,
+                &[
+                    (boot_code_id("pox"), "(get-total-ustx-stacked u0)"),
+                    (boot_code_id("pox"), "(no-such-function)"),
+                ],
+            );
+            (values, failed)
+        });
+
+        assert_eq!(values, vec![Value::UInt(0), stacking_minimum]);
+        match failed {
+            Err(chainstate_error::ReadOnlyQueryError(1, _)) => {}
+            _ => panic!("expected the second query to fail"),
+        }
+    }
+
+    #[test]
+    fn test_clarity_eval_read_only_to_writer() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, _) =
+            instantiate_pox_peer(&burnchain, "test-clarity-eval-read-only-to-writer", 6055);
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 0);
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        let list_expr = "(list (get-stacking-minimum) (get-total-ustx-stacked u0) u1 u2 u3)";
+        let mut expected = vec![];
+        eval_at_tip(&mut peer, "pox", list_expr)
+            .serialize_write(&mut expected)
+            .unwrap();
+
+        let (streamed, failed, failed_bytes) = with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            let iconn = sortdb.index_conn();
+
+            let mut streamed = vec![];
+            chainstate
+                .clarity_eval_read_only_to_writer(
+                    &iconn,
+                    &stacks_block_id,
+                    &boot_code_id("pox"),
+                    list_expr,
+                    &mut streamed,
+                )
+                .unwrap();
+
+            let mut failed_bytes = vec![];
+            let failed = chainstate.clarity_eval_read_only_to_writer(
+                &iconn,
+                &stacks_block_id,
+                &boot_code_id("pox"),
+                "(no-such-function)",
+                &mut failed_bytes,
+            );
+
+            let mut partial_bytes = vec![];
+            let partial = chainstate.clarity_eval_read_only_to_writer(
+                &iconn,
+                &stacks_block_id,
+                &boot_code_id("pox"),
+                "(list (get-stacking-minimum) u1 (unwrap-panic none) u3)",
+                &mut partial_bytes,
+            );
+
+            let mut mismatched_bytes = vec![];
+            let mismatched = chainstate.clarity_eval_read_only_to_writer(
+                &iconn,
+                &stacks_block_id,
+                &boot_code_id("pox"),
+                "(list u1 u2 3)",
+                &mut mismatched_bytes,
+            );
+            (
+                streamed,
+                (failed, failed_bytes),
+                (partial, partial_bytes),
+                (mismatched, mismatched_bytes),
+            )
+        });
+
+        assert_eq!(streamed, expected);
+
+        // nothing gets written if the expression fails before it produces a list
+        assert!(failed.0.is_err());
+        assert!(failed.1.is_empty());
+
+        // list items are written out as they are evaluated, so the ones before a failing item
+        // are already out by the time it fails
+        let mut expected_partial = vec![];
+        Value::serialize_write_list_prefix(&mut expected_partial, 4).unwrap();
+        eval_at_tip(&mut peer, "pox", "(get-stacking-minimum)")
+            .serialize_write(&mut expected_partial)
+            .unwrap();
+        Value::UInt(1)
+            .serialize_write(&mut expected_partial)
+            .unwrap();
+        assert!(partial.0.is_err());
+        assert_eq!(partial.1, expected_partial);
+
+        // items are type-checked as they are written
+        let mut expected_mismatched = vec![];
+        Value::serialize_write_list_prefix(&mut expected_mismatched, 3).unwrap();
+        Value::UInt(1)
+            .serialize_write(&mut expected_mismatched)
+            .unwrap();
+        Value::UInt(2)
+            .serialize_write(&mut expected_mismatched)
+            .unwrap();
+        assert!(mismatched.0.is_err());
+        assert_eq!(mismatched.1, expected_mismatched);
+    }
+
+    #[test]
+    fn test_list_contracts_by_deployer() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-list-contracts-by-deployer", 6038);
+
+        let alice = keys.pop().unwrap();
+        let alice_principal: StandardPrincipalData = key_to_stacks_addr(&alice).into();
+        let bob_principal: StandardPrincipalData = key_to_stacks_addr(&keys[0]).into();
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        // alice deploys two contracts
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 0);
+                let alpha_tx =
+                    make_bare_contract(&alice, 0, 0, "alpha", "(define-data-var x int 1)");
+                let beta_tx = make_bare_contract(&alice, 1, 0, "beta", "(define-data-var y int 2)");
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx, alpha_tx, beta_tx],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        let (alice_contracts, bob_contracts) = with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            let alice_contracts = chainstate
+                .list_contracts_by_deployer(sortdb, &stacks_block_id, &alice_principal)
+                .unwrap();
+            let bob_contracts = chainstate
+                .list_contracts_by_deployer(sortdb, &stacks_block_id, &bob_principal)
+                .unwrap();
+            (alice_contracts, bob_contracts)
+        });
+
+        assert_eq!(
+            alice_contracts,
+            vec![
+                ContractName::try_from("alpha".to_string()).unwrap(),
+                ContractName::try_from("beta".to_string()).unwrap()
+            ]
+        );
+        assert!(bob_contracts.is_empty());
+    }
+
+    #[test]
+    fn test_get_contract_source() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-get-contract-source", 6048);
+
+        let alice = keys.pop().unwrap();
+        let alice_principal: StandardPrincipalData = key_to_stacks_addr(&alice).into();
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        let code = "(define-data-var x int 1)\n  ;; a comment, with \"quotes\" and trailing spaces   \n(define-read-only (get-x) (var-get x))\n";
+
+        // alice deploys a contract
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 0);
+                let alpha_tx = make_bare_contract(&alice, 0, 0, "alpha", code);
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx, alpha_tx],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        let alpha_id = QualifiedContractIdentifier::new(
+            alice_principal.clone(),
+            ContractName::try_from("alpha".to_string()).unwrap(),
+        );
+        let beta_id = QualifiedContractIdentifier::new(
+            alice_principal,
+            ContractName::try_from("beta".to_string()).unwrap(),
+        );
+
+        let (alpha_source, beta_source) = with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            let alpha_source = chainstate
+                .get_contract_source(sortdb, &stacks_block_id, &alpha_id)
+                .unwrap();
+            let beta_source = chainstate
+                .get_contract_source(sortdb, &stacks_block_id, &beta_id)
+                .unwrap();
+            (alpha_source, beta_source)
+        });
+
+        assert_eq!(alpha_source.unwrap().as_bytes(), code.as_bytes());
+        assert!(beta_source.is_none());
+    }
+
+    #[test]
+    fn test_get_stacker_bitcoin_address() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-get-stacker-bitcoin-address", 6052);
+
+        let alice = keys.pop().unwrap();
+        let bob = keys.pop().unwrap();
+
+        peer.advance_tenures(1);
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        // alice stacks to a known testnet P2PKH address
+        let pox_addr_bytes = Hash160::from_hex("74178497e927ff3ff1428a241be454d393c3c91c").unwrap();
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 1);
+                let alice_lockup = make_pox_lockup(
+                    &alice,
+                    0,
+                    1024 * 1000000,
+                    AddressHashMode::SerializeP2PKH,
+                    pox_addr_bytes.clone(),
+                    12,
+                );
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx, alice_lockup],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        let (alice_btc_addr, bob_btc_addr) = with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            let alice_btc_addr = chainstate
+                .get_stacker_bitcoin_address(
+                    sortdb,
+                    &stacks_block_id,
+                    &key_to_stacks_addr(&alice).into(),
+                )
+                .unwrap();
+            let bob_btc_addr = chainstate
+                .get_stacker_bitcoin_address(
+                    sortdb,
+                    &stacks_block_id,
+                    &key_to_stacks_addr(&bob).into(),
+                )
+                .unwrap();
+            (alice_btc_addr, bob_btc_addr)
+        });
+
+        assert_eq!(
+            alice_btc_addr,
+            Some("mr6nrMvvh44sR5MiX929mMXP5hqgaTr6fx".to_string())
+        );
+        assert_eq!(bob_btc_addr, None);
+    }
+
+    #[test]
+    fn test_simulate_contract_call() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-simulate-contract-call", 6040);
+
+        let alice = keys.pop().unwrap();
+        let alice_principal: StandardPrincipalData = key_to_stacks_addr(&alice).into();
+        let bob_principal = PrincipalData::from(key_to_stacks_addr(&keys[0]));
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        // alice deploys a counter contract
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 0);
+                let counter_tx = make_bare_contract(
+                    &alice,
+                    0,
+                    0,
+                    "counter",
+                    "(define-data-var counter int 1)
+                     (define-public (incr)
+                        (begin
+                            (var-set counter (+ (var-get counter) 1))
+                            (ok (var-get counter))))",
+                );
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx, counter_tx],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        let contract_id = QualifiedContractIdentifier::new(
+            alice_principal,
+            ContractName::try_from("counter".to_string()).unwrap(),
+        );
+
+        let (simulation, counter) = with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            let simulation = chainstate
+                .simulate_contract_call(
+                    sortdb,
+                    &stacks_block_id,
+                    &bob_principal,
+                    &contract_id,
+                    "incr",
+                    &[],
+                )
+                .unwrap();
+            let counter = chainstate.with_read_only_clarity_tx(
+                &sortdb.index_conn(),
+                &stacks_block_id,
+                |clarity_tx| {
+                    StacksChainState::get_data_var(clarity_tx, &contract_id, "counter")
+                        .unwrap()
+                        .unwrap()
+                },
+            );
+            (simulation, counter)
+        });
+
+        assert_eq!(simulation.value, Value::okay(Value::Int(2)).unwrap());
+        assert!(simulation.cost.runtime > 0);
+
+        // the simulated write was not persisted
+        assert_eq!(counter, Value::Int(1));
+    }
+
+    #[test]
+    fn test_check_post_conditions() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-check-post-conditions", 6046);
+
+        let alice = keys.pop().unwrap();
+        let alice_addr = key_to_stacks_addr(&alice);
+        let bob_principal = PrincipalData::from(key_to_stacks_addr(&keys[0]));
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        // alice deploys a contract that sends STX on behalf of the caller
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 0);
+                let sender_tx = make_bare_contract(
+                    &alice,
+                    0,
+                    0,
+                    "sender",
+                    "(define-public (send (amount uint) (recipient principal))
+                        (stx-transfer? amount tx-sender recipient))",
+                );
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx, sender_tx],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        // alice sends bob 100 uSTX through the contract
+        let make_send_tx = |mode: TransactionPostConditionMode,
+                            post_condition: Option<TransactionPostCondition>| {
+            let mut tx = StacksTransaction::new(
+                TransactionVersion::Testnet,
+                TransactionAuth::from_p2pkh(&alice).unwrap(),
+                TransactionPayload::new_contract_call(
+                    alice_addr.clone(),
+                    "sender",
+                    "send",
+                    vec![Value::UInt(100), Value::Principal(bob_principal.clone())],
+                )
+                .unwrap(),
+            );
+            tx.chain_id = 0x80000000;
+            tx.auth.set_origin_nonce(1);
+            tx.set_post_condition_mode(mode);
+            if let Some(post_condition) = post_condition {
+                tx.add_post_condition(post_condition);
             }
+            tx
+        };
+
+        let satisfied_tx = make_send_tx(
+            TransactionPostConditionMode::Deny,
+            Some(TransactionPostCondition::STX(
+                PostConditionPrincipal::Origin,
+                FungibleConditionCode::SentEq,
+                100,
+            )),
+        );
+        let violated_tx = make_send_tx(
+            TransactionPostConditionMode::Deny,
+            Some(TransactionPostCondition::STX(
+                PostConditionPrincipal::Origin,
+                FungibleConditionCode::SentLe,
+                50,
+            )),
+        );
+        let unchecked_deny_tx = make_send_tx(TransactionPostConditionMode::Deny, None);
+        let unchecked_allow_tx = make_send_tx(TransactionPostConditionMode::Allow, None);
+
+        let results = with_sortdb(&mut peer, |chainstate, sortdb| {
+            let (consensus_hash, block_bhh) =
+                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+            let stacks_block_id =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+            vec![
+                &satisfied_tx,
+                &violated_tx,
+                &unchecked_deny_tx,
+                &unchecked_allow_tx,
+            ]
+            .into_iter()
+            .map(|tx| {
+                chainstate
+                    .check_post_conditions(sortdb, &stacks_block_id, tx)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>()
+        });
+
+        assert_eq!(results, vec![true, false, false, true]);
+    }
+
+    #[test]
+    fn test_estimate_tx_fee() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) = instantiate_pox_peer(&burnchain, "test-estimate-tx-fee", 6054);
+
+        let alice = keys.pop().unwrap();
+        let alice_addr = key_to_stacks_addr(&alice);
+        let bob_principal = PrincipalData::from(key_to_stacks_addr(&keys[0]));
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        // alice deploys a contract that does some work
+        let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+            |ref mut miner,
+             ref mut sortdb,
+             ref mut chainstate,
+             vrf_proof,
+             ref parent_opt,
+             ref parent_microblock_header_opt| {
+                let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                let coinbase_tx = make_coinbase(miner, 0);
+                let adder_tx = make_bare_contract(
+                    &alice,
+                    0,
+                    0,
+                    "adder",
+                    "(define-data-var total uint u0)
+                    (define-public (add-all (amounts (list 10 uint)))
+                        (ok (var-set total (fold + amounts (var-get total)))))",
+                );
+
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, _size, _cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx, adder_tx],
+                    )
+                    .unwrap();
+                (anchored_block, vec![])
+            },
+        );
+
+        peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+        // execution costs still count towards the fee if the block's budget is unlimited
+        peer.chainstate().block_limit = ExecutionCost::max_value();
+
+        let transfer_tx = make_token_transfer(&alice, 1, 0, bob_principal, 100);
+        let mut call_tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&alice).unwrap(),
+            TransactionPayload::new_contract_call(
+                alice_addr.clone(),
+                "adder",
+                "add-all",
+                vec![Value::list_from((1..11).map(Value::UInt).collect()).unwrap()],
+            )
+            .unwrap(),
+        );
+        call_tx.chain_id = 0x80000000;
+        call_tx.auth.set_origin_nonce(1);
+
+        let (transfer_fee, call_fee, pricier_transfer_fee) =
+            with_sortdb(&mut peer, |chainstate, sortdb| {
+                let (consensus_hash, block_bhh) =
+                    SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+                let stacks_block_id =
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+                let transfer_fee = chainstate
+                    .estimate_tx_fee(sortdb, &stacks_block_id, &transfer_tx, 0)
+                    .unwrap();
+                let call_fee = chainstate
+                    .estimate_tx_fee(sortdb, &stacks_block_id, &call_tx, 0)
+                    .unwrap();
+
+                let pricier_transfer_fee = chainstate
+                    .estimate_tx_fee(sortdb, &stacks_block_id, &transfer_tx, 3)
+                    .unwrap();
+                (transfer_fee, call_fee, pricier_transfer_fee)
+            });
+
+        // a transfer only pays for its length
+        assert_eq!(
+            transfer_fee,
+            transfer_tx.tx_len() * MINIMUM_TX_FEE_RATE_PER_BYTE
+        );
+        assert_eq!(pricier_transfer_fee, 3 * transfer_tx.tx_len());
+
+        // the contract call also pays for its execution
+        assert!(call_fee > call_tx.tx_len());
+        assert!(call_fee > transfer_fee);
+    }
+
+    /// Mine a few tenures of coinbases with a seeded peer, and return the blocks' IDs
+    fn run_seeded_tenures(test_name: &str, port: u16, rng_seed: u64) -> Vec<StacksBlockId> {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, _) =
+            instantiate_pox_peer_with_seed(&burnchain, test_name, port, Some(rng_seed));
+
+        let mut block_ids = vec![];
+        for tenure_id in 0..3 {
+            let microblock_privkey = peer.next_microblock_privkey();
+            let microblock_pubkeyhash =
+                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let block_txs = vec![coinbase_tx];
+
+                    let block_builder = StacksBlockBuilder::make_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+            block_ids.push(StacksBlockHeader::make_index_block_hash(
+                &tip.consensus_hash,
+                &stacks_block.block_hash(),
+            ));
         }
+        block_ids
     }
 
     #[test]
-    fn test_liquid_ustx_burns() {
+    fn test_seeded_tenures_are_reproducible() {
+        let first_run = run_seeded_tenures("test-seeded-tenures-1", 6030, 42);
+        let second_run = run_seeded_tenures("test-seeded-tenures-2", 6032, 42);
+        assert_eq!(first_run.len(), 3);
+        assert_eq!(first_run, second_run);
+
+        let other_seed_run = run_seeded_tenures("test-seeded-tenures-3", 6034, 43);
+        assert!(first_run != other_seed_run);
+    }
+
+    #[test]
+    fn test_get_next_nonce() {
         let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
         burnchain.pox_constants.reward_cycle_length = 5;
         burnchain.pox_constants.prepare_length = 2;
 
-        let (mut peer, mut keys) = instantiate_pox_peer(&burnchain, "test-liquid-ustx", 6026);
-
-        let num_blocks = 10;
-        let mut expected_liquid_ustx = 1024 * 1000000 * (keys.len() as u128);
+        let (mut peer, mut keys) = instantiate_pox_peer(&burnchain, "test-get-next-nonce", 6028);
 
         let alice = keys.pop().unwrap();
+        let alice_principal: PrincipalData = key_to_stacks_addr(&alice).into();
+        let bob_principal: PrincipalData = key_to_stacks_addr(&keys[0]).into();
+        let nobody_principal: PrincipalData = key_to_stacks_addr(&StacksPrivateKey::new()).into();
 
-        for tenure_id in 0..num_blocks {
+        // alice transfers to bob in every tenure after the first
+        for tenure_id in 0..3 {
             let microblock_privkey = StacksPrivateKey::new();
             let microblock_pubkeyhash =
                 Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
@@ -1036,15 +2902,16 @@ pub mod test {
                     let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
                     let coinbase_tx = make_coinbase(miner, tenure_id);
 
-                    let burn_tx = make_bare_contract(
-                        &alice,
-                        tenure_id as u64,
-                        0,
-                        &format!("alice-burns-{}", &tenure_id),
-                        "(stx-burn? u1 tx-sender)",
-                    );
-
-                    let block_txs = vec![coinbase_tx, burn_tx];
+                    let mut block_txs = vec![coinbase_tx];
+                    if tenure_id > 0 {
+                        block_txs.push(make_token_transfer(
+                            &alice,
+                            (tenure_id - 1) as u64,
+                            0,
+                            bob_principal.clone(),
+                            1,
+                        ));
+                    }
 
                     let block_builder = StacksBlockBuilder::make_block_builder(
                         &parent_tip,
@@ -1068,15 +2935,30 @@ pub mod test {
             peer.next_burnchain_block(burn_ops.clone());
             peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
 
-            expected_liquid_ustx -= 1;
-
-            let liquid_ustx = get_liquid_ustx(&mut peer);
-            assert_eq!(liquid_ustx, expected_liquid_ustx);
+            let (alice_nonce, nobody_nonce) = with_sortdb(&mut peer, |chainstate, sortdb| {
+                let (consensus_hash, block_bhh) =
+                    SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+                let stacks_block_id =
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+                (
+                    chainstate
+                        .get_next_nonce(sortdb, &stacks_block_id, &alice_principal)
+                        .unwrap(),
+                    chainstate
+                        .get_next_nonce(sortdb, &stacks_block_id, &nobody_principal)
+                        .unwrap(),
+                )
+            });
+            assert_eq!(alice_nonce, tenure_id as u64);
+            assert_eq!(nobody_nonce, 0);
+        }
 
-            if tenure_id >= (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW) as usize {
-                // add mature coinbases
-                expected_liquid_ustx += 500 * 1000000;
-            }
+        let result = with_sortdb(&mut peer, |chainstate, sortdb| {
+            chainstate.get_next_nonce(sortdb, &StacksBlockId([0xff; 32]), &alice_principal)
+        });
+        match result {
+            Err(chainstate_error::NoSuchBlockError) => {}
+            _ => panic!("expected NoSuchBlockError"),
         }
     }
 
@@ -1714,6 +3596,13 @@ pub mod test {
                     cur_reward_cycle, min_ustx, &reward_addrs, total_liquid_ustx
                 );
 
+                // the reward set size matches the full enumeration
+                let reward_set_size = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                    chainstate.get_reward_set_size_only(sortdb, &tip_index_block, cur_reward_cycle)
+                })
+                .unwrap();
+                assert_eq!(reward_set_size as usize, reward_addrs.len());
+
                 if cur_reward_cycle >= first_reward_cycle {
                     // this will grow as more miner rewards are unlocked, so be wary
                     if tenure_id >= (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW + 1) as usize {
@@ -1744,6 +3633,54 @@ pub mod test {
                     );
                     assert_eq!((reward_addrs[0].0).bytes, key_to_stacks_addr(&bob).bytes);
                     assert_eq!(reward_addrs[0].1, (4 * 1024 * 1000000) / 5);
+
+                    // the coordinator's reward set carries the same per-address amounts
+                    let reward_set = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                        OnChainRewardSetProvider().get_reward_set(
+                            tip_burn_block_height,
+                            chainstate,
+                            &burnchain,
+                            sortdb,
+                            &tip_index_block,
+                        )
+                    })
+                    .unwrap();
+                    assert_eq!(reward_set.len(), 2);
+                    assert_eq!(reward_set[0].0.bytes, key_to_stacks_addr(&bob).bytes);
+                    assert_eq!(reward_set[0].1, (4 * 1024 * 1000000) / 5);
+                    assert_eq!(reward_set[1].0.bytes, key_to_stacks_addr(&alice).bytes);
+                    assert_eq!(reward_set[1].1, 1024 * 1000000);
+
+                    // the exported reward set lists the same entries, in the same order
+                    let reward_set_json =
+                        with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                            chainstate.export_reward_set_json(
+                                &burnchain,
+                                sortdb,
+                                &tip_index_block,
+                                cur_reward_cycle,
+                            )
+                        })
+                        .unwrap();
+                    let exported: Vec<RewardSetEntry> =
+                        serde_json::from_str(&reward_set_json).unwrap();
+                    let expected: Vec<RewardSetEntry> = reward_addrs
+                        .iter()
+                        .map(|(addr, total_ustx)| RewardSetEntry {
+                            stacks_address: addr.to_string(),
+                            bitcoin_address: addr.to_bitcoin_address().to_b58(),
+                            total_ustx: *total_ustx,
+                        })
+                        .collect();
+                    assert_eq!(exported, expected);
+                    assert_eq!(
+                        exported[0].stacks_address,
+                        key_to_stacks_addr(&bob).to_string()
+                    );
+                    assert_eq!(
+                        exported[1].stacks_address,
+                        key_to_stacks_addr(&alice).to_string()
+                    );
                 } else {
                     // no reward addresses
                     assert_eq!(reward_addrs.len(), 0);
@@ -1752,6 +3689,297 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_diff_reward_sets() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-diff-reward-sets", 6044);
+
+        let num_blocks = 12;
+
+        let alice = keys.pop().unwrap();
+        let bob = keys.pop().unwrap();
+
+        let mut alice_reward_cycle = 0;
+        let mut bob_reward_cycle = 0;
+        let mut tip_index_block = StacksBlockId([0u8; 32]);
+
+        for tenure_id in 0..num_blocks {
+            let microblock_privkey = StacksPrivateKey::new();
+            let microblock_pubkeyhash =
+                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let mut block_txs = vec![coinbase_tx];
+
+                    if tenure_id == 1 {
+                        // Alice stacks first...
+                        let alice_lockup = make_pox_lockup(
+                            &alice,
+                            0,
+                            1024 * 1000000,
+                            AddressHashMode::SerializeP2PKH,
+                            key_to_stacks_addr(&alice).bytes,
+                            12,
+                        );
+                        block_txs.push(alice_lockup);
+                    }
+
+                    if tenure_id == 6 {
+                        // ...and Bob joins her a reward cycle later
+                        let bob_lockup = make_pox_lockup(
+                            &bob,
+                            0,
+                            (4 * 1024 * 1000000) / 5,
+                            AddressHashMode::SerializeP2PKH,
+                            key_to_stacks_addr(&bob).bytes,
+                            12,
+                        );
+                        block_txs.push(bob_lockup);
+                    }
+
+                    let block_builder = StacksBlockBuilder::make_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
+
+            let (_, _, consensus_hash) = peer.next_burnchain_block(burn_ops);
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            tip_index_block = StacksBlockHeader::make_index_block_hash(
+                &consensus_hash,
+                &stacks_block.block_hash(),
+            );
+
+            // stacking takes effect in the reward cycle after the lockup
+            let tip_burn_block_height =
+                get_par_burn_block_height(peer.chainstate(), &tip_index_block);
+            let cur_reward_cycle = peer
+                .chainstate()
+                .get_reward_cycle(&burnchain, tip_burn_block_height);
+            if tenure_id == 1 {
+                alice_reward_cycle = cur_reward_cycle + 1;
+            }
+            if tenure_id == 6 {
+                bob_reward_cycle = cur_reward_cycle + 1;
+            }
+        }
+
+        assert!(bob_reward_cycle > alice_reward_cycle);
+
+        let diff = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+            chainstate.diff_reward_sets(
+                &burnchain,
+                sortdb,
+                &tip_index_block,
+                alice_reward_cycle,
+                bob_reward_cycle,
+            )
+        })
+        .unwrap();
+
+        // Bob entered the reward set, and Alice's stake is unchanged
+        assert_eq!(
+            diff.added,
+            vec![(key_to_stacks_addr(&bob), 0, (4 * 1024 * 1000000) / 5)]
+        );
+        assert_eq!(diff.removed.len(), 0);
+        assert_eq!(diff.changed.len(), 0);
+
+        // diffing the other way around reports Bob's exit
+        let diff = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+            chainstate.diff_reward_sets(
+                &burnchain,
+                sortdb,
+                &tip_index_block,
+                bob_reward_cycle,
+                alice_reward_cycle,
+            )
+        })
+        .unwrap();
+        assert_eq!(diff.added.len(), 0);
+        assert_eq!(
+            diff.removed,
+            vec![(key_to_stacks_addr(&bob), (4 * 1024 * 1000000) / 5, 0)]
+        );
+        assert_eq!(diff.changed.len(), 0);
+    }
+
+    #[test]
+    fn test_reward_set_larger_than_reward_slots() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+
+        let (mut peer, keys) =
+            instantiate_pox_peer(&burnchain, "test-reward-set-larger-than-reward-slots", 6056);
+
+        // each reward cycle has fewer reward slots than there will be stackers
+        let reward_slots = (burnchain.pox_constants.reward_cycle_length
+            - burnchain.pox_constants.prepare_length) as usize
+            * OUTPUTS_PER_COMMIT;
+        assert!(reward_slots < keys.len());
+
+        let num_blocks = 10;
+        let mut first_reward_cycle = 0;
+
+        for tenure_id in 0..num_blocks {
+            let microblock_privkey = StacksPrivateKey::new();
+            let microblock_pubkeyhash =
+                Hash160::from_data(&StacksPublicKey::from_private(&microblock_privkey).to_bytes());
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let mut block_txs = vec![coinbase_tx];
+
+                    if tenure_id == 1 {
+                        // everyone stacks half of their STX, each well over the stacking minimum
+                        for key in keys.iter() {
+                            block_txs.push(make_pox_lockup(
+                                key,
+                                0,
+                                512 * 1000000,
+                                AddressHashMode::SerializeP2PKH,
+                                key_to_stacks_addr(key).bytes,
+                                12,
+                            ));
+                        }
+                    }
+
+                    let block_builder = StacksBlockBuilder::make_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
+
+            let (_, _, consensus_hash) = peer.next_burnchain_block(burn_ops);
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            let tip_index_block = StacksBlockHeader::make_index_block_hash(
+                &consensus_hash,
+                &stacks_block.block_hash(),
+            );
+            let tip_burn_block_height =
+                get_par_burn_block_height(peer.chainstate(), &tip_index_block);
+            let cur_reward_cycle = peer
+                .chainstate()
+                .get_reward_cycle(&burnchain, tip_burn_block_height);
+
+            if tenure_id == 1 {
+                first_reward_cycle = cur_reward_cycle + 1;
+            }
+            if tenure_id <= 1 || cur_reward_cycle < first_reward_cycle {
+                continue;
+            }
+
+            // every stacker is in the reward set, even though there are not enough reward
+            // slots to go around
+            let reward_addrs = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                get_reward_addresses_with_par_tip(chainstate, &burnchain, sortdb, &tip_index_block)
+            })
+            .unwrap();
+            assert_eq!(reward_addrs.len(), keys.len());
+            for key in keys.iter() {
+                assert!(reward_addrs.contains(&(key_to_stacks_addr(key), 512 * 1000000)));
+            }
+
+            // so the coordinator can still compute this reward cycle's reward set...
+            let reward_set = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                OnChainRewardSetProvider().get_reward_set(
+                    tip_burn_block_height,
+                    chainstate,
+                    &burnchain,
+                    sortdb,
+                    &tip_index_block,
+                )
+            })
+            .unwrap();
+            assert_eq!(reward_set, reward_addrs);
+
+            // ...and the reward set can still be exported and diffed
+            let reward_set_json = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                chainstate.export_reward_set_json(
+                    &burnchain,
+                    sortdb,
+                    &tip_index_block,
+                    cur_reward_cycle,
+                )
+            })
+            .unwrap();
+            let exported: Vec<RewardSetEntry> = serde_json::from_str(&reward_set_json).unwrap();
+            assert_eq!(exported.len(), keys.len());
+
+            let diff = with_sortdb(&mut peer, |ref mut chainstate, ref sortdb| {
+                chainstate.diff_reward_sets(
+                    &burnchain,
+                    sortdb,
+                    &tip_index_block,
+                    first_reward_cycle,
+                    cur_reward_cycle,
+                )
+            })
+            .unwrap();
+            assert_eq!(diff.added.len(), 0);
+            assert_eq!(diff.removed.len(), 0);
+            assert_eq!(diff.changed.len(), 0);
+        }
+
+        assert!(first_reward_cycle > 0);
+    }
+
     #[test]
     fn test_pox_lockup_no_double_stacking() {
         let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash([0u8; 32]));
@@ -24,6 +24,7 @@ use rusqlite::Row;
 
 use burnchains::Address;
 
+use chainstate::burn::db::sortdb::SortitionDB;
 use chainstate::stacks::db::blocks::*;
 use chainstate::stacks::db::*;
 use chainstate::stacks::Error;
@@ -168,6 +169,30 @@ impl StacksChainState {
         })
     }
 
+    /// Get the nonce that the next transaction from `principal` must have, as of the given Stacks
+    /// block.  An account that has never been seen has a next nonce of 0.
+    pub fn get_next_nonce(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        principal: &PrincipalData,
+    ) -> Result<u64, Error> {
+        if StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.headers_db(),
+            block_id,
+        )?
+        .is_none()
+        {
+            return Err(Error::NoSuchBlockError);
+        }
+
+        let account =
+            self.with_read_only_clarity_tx(&sortdb.index_conn(), block_id, |clarity_tx| {
+                StacksChainState::get_account(clarity_tx, principal)
+            });
+        Ok(account.nonce)
+    }
+
     pub fn get_account_ft<'a>(
         clarity_tx: &mut ClarityTx<'a>,
         contract_id: &QualifiedContractIdentifier,
@@ -516,7 +541,7 @@ impl StacksChainState {
     /// The first tuple item is the miner's reward; the second tuple item is the list of
     /// user-support burns that helped the miner win.
     ///
-    /// There must be MINER_REWARD_WINDOW items in the sample.
+    /// There must be `window` items in the sample, per the chainstate's miner reward schedule.
     ///
     /// TODO: this is incomplete -- it does not calculate transaction fees.  This is just stubbed
     /// out for now -- it only grants miners and user burn supports their coinbases.
@@ -627,12 +652,14 @@ impl StacksChainState {
     /// scheduled miner payments to the cache.  Miner payments are immutable once written, and are
     /// keyed to the index block hash (which is globally unique), so once cached, no invalidation
     /// should be necessary.
+    /// Rewards mature according to `schedule`.
     pub fn find_mature_miner_rewards<'a>(
         tx: &mut StacksDBTx<'a>,
         tip: &StacksHeaderInfo,
         mut cache: Option<&mut MinerPaymentCache>,
+        schedule: &MinerRewardSchedule,
     ) -> Result<Option<Vec<MinerReward>>, Error> {
-        if tip.block_height <= MINER_REWARD_MATURITY + MINER_REWARD_WINDOW {
+        if tip.block_height <= schedule.delay() {
             // no mature rewards exist
             return Ok(None);
         }
@@ -640,7 +667,7 @@ impl StacksChainState {
         let latest_matured_miners = StacksChainState::get_scheduled_block_rewards_in_fork(
             tx,
             tip,
-            tip.block_height - MINER_REWARD_MATURITY,
+            tip.block_height - schedule.maturity,
         )?;
         assert!(latest_matured_miners.len() > 0);
         assert!(latest_matured_miners[0].vtxindex == 0);
@@ -655,9 +682,9 @@ impl StacksChainState {
             latest_matured_miners[1..].to_vec(),
         )];
 
-        // load all miner rewards from tip.block_height - MINER_REWARD_MATURITY - MINER_REWARD_WINDOW
-        // up to tip.block_height - MINER_REWARD_MATURITY, in that order.
-        for _i in 0..MINER_REWARD_WINDOW {
+        // load all miner rewards from tip.block_height - maturity - window
+        // up to tip.block_height - maturity, in that order.
+        for _i in 0..schedule.window {
             let scheduled_rewards = if let Some(ref mut cache) = cache {
                 if let Some(miner_rewards) = cache.get(&index_block_hash) {
                     miner_rewards.clone()
@@ -721,7 +748,7 @@ impl StacksChainState {
             let rs_before = StacksChainState::get_scheduled_block_rewards_in_fork(
                 tx,
                 tip,
-                tip.block_height - MINER_REWARD_MATURITY - MINER_REWARD_WINDOW,
+                tip.block_height - schedule.delay(),
             )?;
             assert!(rs_before.len() > 0);
 
@@ -732,7 +759,7 @@ impl StacksChainState {
             let rs_after = StacksChainState::get_scheduled_block_rewards_in_fork(
                 tx,
                 tip,
-                tip.block_height - MINER_REWARD_MATURITY,
+                tip.block_height - schedule.maturity,
             )?;
             assert!(rs_after.len() > 0);
             let rs_miners_after = (rs_after[0].clone(), rs_after[1..].to_vec());
@@ -1089,6 +1116,7 @@ mod test {
 
         let mut parent_tip = StacksHeaderInfo::genesis_block_header_info(TrieHash([0u8; 32]), 0);
 
+        let schedule = MinerRewardSchedule::default();
         let mut cache = MinerPaymentCache::new();
         let mut matured_miners = vec![];
         let mut expected_scheduled_payments = vec![];
@@ -1130,15 +1158,20 @@ mod test {
 
             if i < (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW) {
                 let mut tx = chainstate.headers_tx_begin().unwrap();
-                let rewards_opt =
-                    StacksChainState::find_mature_miner_rewards(&mut tx, &parent_tip, None)
-                        .unwrap();
+                let rewards_opt = StacksChainState::find_mature_miner_rewards(
+                    &mut tx,
+                    &parent_tip,
+                    None,
+                    &schedule,
+                )
+                .unwrap();
                 assert!(rewards_opt.is_none()); // not mature yet
 
                 let rewards_opt = StacksChainState::find_mature_miner_rewards(
                     &mut tx,
                     &parent_tip,
                     Some(&mut cache),
+                    &schedule,
                 )
                 .unwrap();
                 assert!(rewards_opt.is_none()); // not mature yet
@@ -1168,17 +1201,22 @@ mod test {
         assert_eq!(legacy_rewards, expected_rewards);
 
         let rewards_opt =
-            StacksChainState::find_mature_miner_rewards(&mut tx, &parent_tip, None).unwrap();
+            StacksChainState::find_mature_miner_rewards(&mut tx, &parent_tip, None, &schedule)
+                .unwrap();
         assert!(rewards_opt.is_some());
 
         let rewards = rewards_opt.unwrap();
         assert_eq!(rewards.len(), 2);
         assert_eq!(rewards, expected_rewards);
 
-        let rewards_cached =
-            StacksChainState::find_mature_miner_rewards(&mut tx, &parent_tip, Some(&mut cache))
-                .unwrap()
-                .unwrap();
+        let rewards_cached = StacksChainState::find_mature_miner_rewards(
+            &mut tx,
+            &parent_tip,
+            Some(&mut cache),
+            &schedule,
+        )
+        .unwrap()
+        .unwrap();
         assert_eq!(rewards_cached, rewards);
         assert_eq!(rewards_cached, expected_rewards);
 
@@ -1187,6 +1225,7 @@ mod test {
             &mut tx,
             &parent_tip,
             Some(&mut empty_cache),
+            &schedule,
         )
         .unwrap()
         .unwrap();
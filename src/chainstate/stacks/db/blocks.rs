@@ -44,6 +44,7 @@ use chainstate::stacks::*;
 use chainstate::burn::BlockSnapshot;
 
 use std::path::{Path, PathBuf};
+use std::thread;
 
 use util::db::Error as db_error;
 use util::db::{
@@ -146,76 +147,86 @@ pub enum MemPoolRejection {
     NoSuchChainTip(ConsensusHash, BlockHeaderHash),
     ConflictingNonceInMempool,
     TooMuchChaining,
+    MempoolFull,
     DBError(db_error),
     Other(String),
 }
 
 impl MemPoolRejection {
+    /// Get the machine-readable reason why a transaction was rejected.  This is the `reason` field
+    /// of the JSON error returned to RPC clients.
+    pub fn reason_code(&self) -> &'static str {
+        use self::MemPoolRejection::*;
+        match self {
+            SerializationFailure(_) => "Serialization",
+            DeserializationFailure(_) => "Deserialization",
+            TooMuchChaining => "TooMuchChaining",
+            MempoolFull => "MempoolFull",
+            FailedToValidate(_) => "SignatureValidation",
+            FeeTooLow(..) => "FeeTooLow",
+            BadNonces(_) => "BadNonce",
+            NotEnoughFunds(..) => "NotEnoughFunds",
+            NoSuchContract => "NoSuchContract",
+            NoSuchPublicFunction => "NoSuchPublicFunction",
+            BadFunctionArgument(_) => "BadFunctionArgument",
+            ConflictingNonceInMempool => "ConflictingNonceInMempool",
+            ContractAlreadyExists(_) => "ContractAlreadyExists",
+            PoisonMicroblocksDoNotConflict => "PoisonMicroblocksDoNotConflict",
+            NoAnchorBlockWithPubkeyHash(_) => "PoisonMicroblockHasUnknownPubKeyHash",
+            InvalidMicroblocks => "PoisonMicroblockIsInvalid",
+            BadAddressVersionByte => "BadAddressVersionByte",
+            NoCoinbaseViaMempool => "NoCoinbaseViaMempool",
+            // this should never happen via the RPC interface
+            NoSuchChainTip(..) => "ServerFailureNoSuchChainTip",
+            DBError(_) => "ServerFailureDatabase",
+            Other(_) => "ServerFailureOther",
+        }
+    }
+
     pub fn into_json(self, txid: &Txid) -> serde_json::Value {
         use self::MemPoolRejection::*;
-        let (reason_code, reason_data) = match self {
-            SerializationFailure(e) => ("Serialization", Some(json!({"message": e.to_string()}))),
-            DeserializationFailure(e) => {
-                ("Deserialization", Some(json!({"message": e.to_string()})))
+        let reason_code = self.reason_code();
+        let reason_data = match self {
+            SerializationFailure(e) => Some(json!({"message": e.to_string()})),
+            DeserializationFailure(e) => Some(json!({"message": e.to_string()})),
+            TooMuchChaining => {
+                Some(json!({"message": "Nonce would exceed chaining limit in mempool"}))
             }
-            TooMuchChaining => (
-                "TooMuchChaining",
-                Some(json!({"message": "Nonce would exceed chaining limit in mempool"})),
-            ),
-            FailedToValidate(e) => (
-                "SignatureValidation",
-                Some(json!({"message": e.to_string()})),
-            ),
-            FeeTooLow(actual, expected) => (
-                "FeeTooLow",
-                Some(json!({
-                                                "expected": expected,
-                                                "actual": actual})),
-            ),
+            FailedToValidate(e) => Some(json!({"message": e.to_string()})),
+            FeeTooLow(actual, expected) => Some(json!({
+                "expected": expected,
+                "actual": actual})),
             BadNonces(TransactionNonceMismatch {
                 expected,
                 actual,
                 principal,
                 is_origin,
                 ..
-            }) => (
-                "BadNonce",
-                Some(json!({
-                     "expected": expected,
-                     "actual": actual,
-                     "principal": principal.to_string(),
-                     "is_origin": is_origin})),
-            ),
-            NotEnoughFunds(expected, actual) => (
-                "NotEnoughFunds",
-                Some(json!({
-                    "expected": format!("0x{}", to_hex(&expected.to_be_bytes())),
-                    "actual": format!("0x{}", to_hex(&actual.to_be_bytes()))
-                })),
-            ),
-            NoSuchContract => ("NoSuchContract", None),
-            NoSuchPublicFunction => ("NoSuchPublicFunction", None),
-            BadFunctionArgument(e) => (
-                "BadFunctionArgument",
-                Some(json!({"message": e.to_string()})),
-            ),
-            ConflictingNonceInMempool => ("ConflictingNonceInMempool", None),
-            ContractAlreadyExists(id) => (
-                "ContractAlreadyExists",
-                Some(json!({ "contract_identifier": id.to_string() })),
-            ),
-            PoisonMicroblocksDoNotConflict => ("PoisonMicroblocksDoNotConflict", None),
-            NoAnchorBlockWithPubkeyHash(_h) => ("PoisonMicroblockHasUnknownPubKeyHash", None),
-            InvalidMicroblocks => ("PoisonMicroblockIsInvalid", None),
-            BadAddressVersionByte => ("BadAddressVersionByte", None),
-            NoCoinbaseViaMempool => ("NoCoinbaseViaMempool", None),
-            // this should never happen via the RPC interface
-            NoSuchChainTip(..) => ("ServerFailureNoSuchChainTip", None),
-            DBError(e) => (
-                "ServerFailureDatabase",
-                Some(json!({"message": e.to_string()})),
-            ),
-            Other(s) => ("ServerFailureOther", Some(json!({ "message": s }))),
+            }) => Some(json!({
+                 "expected": expected,
+                 "actual": actual,
+                 "principal": principal.to_string(),
+                 "is_origin": is_origin})),
+            NotEnoughFunds(expected, actual) => Some(json!({
+                "expected": format!("0x{}", to_hex(&expected.to_be_bytes())),
+                "actual": format!("0x{}", to_hex(&actual.to_be_bytes()))
+            })),
+            BadFunctionArgument(e) => Some(json!({"message": e.to_string()})),
+            ContractAlreadyExists(id) => {
+                Some(json!({ "contract_identifier": id.to_string() }))
+            }
+            DBError(e) => Some(json!({"message": e.to_string()})),
+            Other(s) => Some(json!({ "message": s })),
+            NoSuchContract
+            | NoSuchPublicFunction
+            | ConflictingNonceInMempool
+            | MempoolFull
+            | PoisonMicroblocksDoNotConflict
+            | NoAnchorBlockWithPubkeyHash(_)
+            | InvalidMicroblocks
+            | BadAddressVersionByte
+            | NoCoinbaseViaMempool
+            | NoSuchChainTip(..) => None,
         };
         let mut result = json!({
             "txid": format!("{}", txid.to_hex()),
@@ -2378,6 +2389,85 @@ impl StacksChainState {
         }
     }
 
+    /// Given an index block hash, get its staging block record, without the block data.
+    /// The block may be staging, processed, or orphaned.
+    pub fn get_staging_block_by_index_hash(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Option<StagingBlock>, Error> {
+        let sql = "SELECT * FROM staging_blocks WHERE index_block_hash = ?1";
+        let args = [index_block_hash as &dyn ToSql];
+        query_row::<StagingBlock, _>(&self.blocks_db, sql, &args).map_err(Error::DBError)
+    }
+
+    /// Get the staging block records (without the block data) of all blocks that are ready to be
+    /// processed -- i.e. the candidates that the next call to process_blocks() may pick from.
+    pub fn get_attachable_staging_blocks(&self) -> Result<Vec<StagingBlock>, Error> {
+        let sql =
+            "SELECT * FROM staging_blocks WHERE processed = 0 AND attachable = 1 AND orphaned = 0";
+        query_rows::<StagingBlock, _>(&self.blocks_db, sql, NO_PARAMS).map_err(Error::DBError)
+    }
+
+    /// Load up all of the transactions an anchored block commits to: its own transactions, and
+    /// the transactions in the parent microblock stream it confirms (in stream order).
+    /// The block may be staging or processed.
+    /// Returns Ok((anchored_txs, microblock_txs)) on success.
+    /// Returns Err(NoSuchBlockError) if we don't have the block or the confirmed microblocks.
+    pub fn get_block_full_txs(
+        &mut self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<(Vec<StacksTransaction>, Vec<StacksTransaction>), Error> {
+        let sql = "SELECT * FROM staging_blocks WHERE index_block_hash = ?1 AND orphaned = 0";
+        let args = [index_block_hash as &dyn ToSql];
+        let staging_block = query_row::<StagingBlock, _>(&self.blocks_db, sql, &args)
+            .map_err(Error::DBError)?
+            .ok_or(Error::NoSuchBlockError)?;
+
+        let block = StacksChainState::load_block(
+            &self.blocks_path,
+            &staging_block.consensus_hash,
+            &staging_block.anchored_block_hash,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        if block.header.parent_microblock == EMPTY_MICROBLOCK_PARENT_HASH
+            && block.header.parent_microblock_sequence == 0
+        {
+            // confirms no microblocks
+            return Ok((block.txs, vec![]));
+        }
+
+        let microblocks = StacksChainState::load_staging_microblock_stream(
+            &self.blocks_db,
+            &self.blocks_path,
+            &staging_block.parent_consensus_hash,
+            &staging_block.parent_anchored_block_hash,
+            block.header.parent_microblock_sequence,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        match microblocks.last() {
+            Some(last_microblock)
+                if last_microblock.block_hash() == block.header.parent_microblock => {}
+            _ => {
+                debug!(
+                    "Do not have the confirmed microblock stream of {}: expected {},{}",
+                    index_block_hash,
+                    &block.header.parent_microblock,
+                    block.header.parent_microblock_sequence
+                );
+                return Err(Error::NoSuchBlockError);
+            }
+        }
+
+        let microblock_txs = microblocks
+            .into_iter()
+            .flat_map(|microblock| microblock.txs.into_iter())
+            .collect();
+
+        Ok((block.txs, microblock_txs))
+    }
+
     /// Get the sqlite rowid for a staging microblock.
     /// Returns None if no such microblock.
     fn stream_microblock_get_rowid(
@@ -3785,6 +3875,7 @@ impl StacksChainState {
                 &mut chainstate_tx.headers_tx,
                 parent_chain_tip,
                 Some(chainstate_tx.miner_payment_cache),
+                &chainstate_tx.miner_reward_schedule,
             )?
         };
 
@@ -4507,6 +4598,123 @@ impl StacksChainState {
         Ok(ret)
     }
 
+    /// Check the transaction signatures of one staging block.  This only looks at the loaded
+    /// block, so it can run on any thread.
+    /// Returns false if a transaction fails verification, in which case the block can never be
+    /// appended.
+    fn prevalidate_staging_block(index_block_hash: &StacksBlockId, block: &StacksBlock) -> bool {
+        for tx in block.txs.iter() {
+            if let Err(e) = tx.verify() {
+                warn!(
+                    "Invalid stacks block {} -- transaction {} failed verification: {:?}",
+                    index_block_hash,
+                    &tx.txid(),
+                    &e
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Check the unprocessed staging anchored blocks whose parents have already been processed,
+    /// spreading the work across up to `num_threads` threads.  The blocks are loaded on the
+    /// calling thread, and the workers only verify their transactions' signatures (see
+    /// `prevalidate_staging_block()`), so no database is written to, and no block is marked as
+    /// processed here.  Invalid blocks are still rejected by the serialized `process_blocks()`.
+    /// `prevalidated` holds the blocks that an earlier call already checked; they are not
+    /// checked again, and it is trimmed down to the blocks still waiting.
+    /// Returns the index block hashes of the blocks that failed the check.
+    pub fn prevalidate_staging_blocks(
+        &self,
+        num_threads: usize,
+        prevalidated: &mut HashSet<StacksBlockId>,
+    ) -> Result<Vec<StacksBlockId>, Error> {
+        let sql =
+            "SELECT * FROM staging_blocks WHERE processed = 0 AND orphaned = 0 ORDER BY height"
+                .to_string();
+        let mut candidates = vec![];
+        for candidate in query_rows::<StagingBlock, _>(&self.blocks_db, &sql, NO_PARAMS)
+            .map_err(Error::DBError)?
+            .into_iter()
+        {
+            let parent_processed = candidate.is_first_mined()
+                || StacksChainState::get_anchored_block_header_info(
+                    self.headers_db(),
+                    &candidate.parent_consensus_hash,
+                    &candidate.parent_anchored_block_hash,
+                )?
+                .is_some();
+            if parent_processed {
+                candidates.push(candidate);
+            }
+        }
+
+        let waiting: HashSet<_> = candidates
+            .iter()
+            .map(|candidate| {
+                StacksBlockHeader::make_index_block_hash(
+                    &candidate.consensus_hash,
+                    &candidate.anchored_block_hash,
+                )
+            })
+            .collect();
+        prevalidated.retain(|index_block_hash| waiting.contains(index_block_hash));
+
+        let mut blocks = vec![];
+        for candidate in candidates.iter() {
+            let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                &candidate.consensus_hash,
+                &candidate.anchored_block_hash,
+            );
+            if prevalidated.contains(&index_block_hash) {
+                continue;
+            }
+            // blocks that can't be loaded are left for process_blocks() to deal with
+            if let Ok(Some(block)) = StacksChainState::load_block(
+                &self.blocks_path,
+                &candidate.consensus_hash,
+                &candidate.anchored_block_hash,
+            ) {
+                blocks.push((index_block_hash, block));
+            }
+        }
+
+        if blocks.len() == 0 {
+            return Ok(vec![]);
+        }
+
+        let num_threads = cmp::max(1, cmp::min(num_threads, blocks.len()));
+        let chunk_size = (blocks.len() + num_threads - 1) / num_threads;
+
+        let mut workers = vec![];
+        for chunk in blocks.chunks(chunk_size) {
+            let chunk = chunk.to_vec();
+            workers.push(thread::spawn(move || {
+                let mut checked = vec![];
+                let mut invalid = vec![];
+                for (index_block_hash, block) in chunk.into_iter() {
+                    if !StacksChainState::prevalidate_staging_block(&index_block_hash, &block) {
+                        invalid.push(index_block_hash.clone());
+                    }
+                    checked.push(index_block_hash);
+                }
+                (checked, invalid)
+            }));
+        }
+
+        let mut invalid = vec![];
+        for worker in workers.into_iter() {
+            let (worker_checked, mut worker_invalid) = worker
+                .join()
+                .expect("FATAL: block validation thread panicked");
+            prevalidated.extend(worker_checked);
+            invalid.append(&mut worker_invalid);
+        }
+
+        Ok(invalid)
+    }
+
     fn is_valid_address_version(mainnet: bool, version: u8) -> bool {
         if mainnet {
             version == C32_ADDRESS_VERSION_MAINNET_SINGLESIG
@@ -7018,6 +7226,95 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn stacks_db_get_block_full_txs() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "stacks_db_get_block_full_txs");
+        let privk = StacksPrivateKey::from_hex(
+            "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+        )
+        .unwrap();
+
+        let block_1 = make_empty_coinbase_block(&privk);
+        let mut block_2 = make_empty_coinbase_block(&privk);
+
+        let mut mblocks = make_sample_microblock_stream(&privk, &block_1.block_hash());
+        mblocks.truncate(3);
+
+        // block_1 --> mblocks[0] --> mblocks[1] --> block_2
+        block_2.header.parent_block = block_1.block_hash();
+        block_2.header.parent_microblock = mblocks[1].block_hash();
+        block_2.header.parent_microblock_sequence = mblocks[1].header.sequence;
+
+        let consensus_hash_1 = ConsensusHash([2u8; 20]);
+        let consensus_hash_2 = ConsensusHash([3u8; 20]);
+
+        let index_block_hash_1 =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash_1, &block_1.block_hash());
+        let index_block_hash_2 =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash_2, &block_2.block_hash());
+
+        match chainstate.get_block_full_txs(&index_block_hash_2) {
+            Err(chainstate_error::NoSuchBlockError) => {}
+            x => panic!("Expected NoSuchBlockError, got {:?}", x),
+        }
+
+        store_staging_block(
+            &mut chainstate,
+            &consensus_hash_1,
+            &block_1,
+            &ConsensusHash([1u8; 20]),
+            1,
+            2,
+        );
+        for mblock in mblocks.iter() {
+            store_staging_microblock(
+                &mut chainstate,
+                &consensus_hash_1,
+                &block_1.block_hash(),
+                mblock,
+            );
+        }
+        store_staging_block(
+            &mut chainstate,
+            &consensus_hash_2,
+            &block_2,
+            &consensus_hash_1,
+            1,
+            2,
+        );
+
+        // block_1 doesn't confirm any microblocks
+        let (anchored_txs, microblock_txs) =
+            chainstate.get_block_full_txs(&index_block_hash_1).unwrap();
+        assert_eq!(anchored_txs, block_1.txs);
+        assert_eq!(microblock_txs.len(), 0);
+
+        // block_2 confirms the first two microblocks, but not the third
+        let mut expected_microblock_txs = mblocks[0].txs.clone();
+        expected_microblock_txs.append(&mut mblocks[1].txs.clone());
+
+        let (anchored_txs, microblock_txs) =
+            chainstate.get_block_full_txs(&index_block_hash_2).unwrap();
+        assert_eq!(anchored_txs, block_2.txs);
+        assert_eq!(microblock_txs, expected_microblock_txs);
+
+        // same result once the blocks and microblocks are processed
+        set_block_processed(&mut chainstate, &consensus_hash_1, &block_1.block_hash(), true);
+        set_microblocks_confirmed(
+            &mut chainstate,
+            &consensus_hash_1,
+            &block_1.block_hash(),
+            block_2.header.parent_microblock_sequence,
+        );
+        set_block_processed(&mut chainstate, &consensus_hash_2, &block_2.block_hash(), true);
+
+        let (anchored_txs, microblock_txs) =
+            chainstate.get_block_full_txs(&index_block_hash_2).unwrap();
+        assert_eq!(anchored_txs, block_2.txs);
+        assert_eq!(microblock_txs, expected_microblock_txs);
+    }
+
     #[test]
     fn stacks_db_staging_blocks_orphaned() {
         let mut chainstate =
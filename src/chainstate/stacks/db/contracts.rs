@@ -53,9 +53,24 @@ use vm::clarity::ClarityConnection;
 pub use vm::analysis::errors::CheckErrors;
 use vm::errors::Error as clarity_vm_error;
 
-use vm::database::ClarityDatabase;
+use vm::database::{ClarityDatabase, StoreType, STORE_CONTRACT_SRC_INTERFACE};
 
 use vm::contracts::Contract;
+use vm::costs::{ExecutionCost, LimitedCostTracker};
+use vm::representations::{ContractName, SymbolicExpression};
+
+use chainstate::stacks::events::StacksTransactionEvent;
+
+/// What a contract call would do if it were executed at a given chain tip
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationResult {
+    /// The function's return value
+    pub value: Value,
+    /// The events the call would emit
+    pub events: Vec<StacksTransactionEvent>,
+    /// The cost of executing the call
+    pub cost: ExecutionCost,
+}
 
 impl StacksChainState {
     pub fn get_contract<T: ClarityConnection>(
@@ -71,6 +86,124 @@ impl StacksChainState {
             .map_err(Error::ClarityError)
     }
 
+    /// List the names of the contracts that `deployer` has deployed, as of the given chain tip.
+    pub fn list_contracts_by_deployer(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        deployer: &StandardPrincipalData,
+    ) -> Result<Vec<ContractName>, Error> {
+        // every contract gets a contract-size entry when it's deployed, but the entries for all
+        // forks live side-by-side, so only keep the contracts that exist in this one.
+        let contract_prefix = format!("{}.", deployer);
+        let key = ClarityDatabase::make_metadata_key(StoreType::Contract, "contract-size");
+        let candidates = self.clarity_state.with_side_store(|side_store| {
+            side_store.find_contracts_with_metadata(&contract_prefix, &key)
+        });
+        if candidates.len() == 0 {
+            return Ok(vec![]);
+        }
+
+        self.with_read_only_clarity_tx(&sortdb.index_conn(), block_id, |clarity_tx| {
+            let mut contract_names = vec![];
+            for candidate in candidates.iter() {
+                let contract_id = match QualifiedContractIdentifier::parse(candidate) {
+                    Ok(contract_id) => contract_id,
+                    Err(_) => {
+                        warn!("Unparseable contract identifier in metadata: {}", candidate);
+                        continue;
+                    }
+                };
+                if StacksChainState::get_contract(clarity_tx, &contract_id)?.is_some() {
+                    contract_names.push(contract_id.name);
+                }
+            }
+            Ok(contract_names)
+        })
+    }
+
+    /// Get the Clarity source code of a contract exactly as it was deployed, as of the given chain
+    /// tip.  Returns None if the contract does not exist at that tip, or if contract source is not
+    /// being stored.
+    pub fn get_contract_source(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        contract_id: &QualifiedContractIdentifier,
+    ) -> Result<Option<String>, Error> {
+        if !STORE_CONTRACT_SRC_INTERFACE {
+            return Ok(None);
+        }
+
+        self.with_read_only_clarity_tx(&sortdb.index_conn(), block_id, |clarity_tx| {
+            // the source metadata of all forks lives side-by-side, so make sure the contract
+            // exists in this one first.
+            if StacksChainState::get_contract(clarity_tx, contract_id)?.is_none() {
+                return Ok(None);
+            }
+            Ok(clarity_tx.with_clarity_db_readonly(|ref mut db| db.get_contract_src(contract_id)))
+        })
+    }
+
+    /// Run `to_do` in a Clarity environment on top of the given chain tip, limited by the block's
+    /// execution budget, and report what it returned along with the cost it incurred.  Nothing it
+    /// writes is persisted.
+    pub fn simulate_in_environment<F, R>(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        to_do: F,
+    ) -> (R, ExecutionCost)
+    where
+        F: FnOnce(&mut OwnedEnvironment) -> R,
+    {
+        let cost_track = LimitedCostTracker::new(self.block_limit.clone());
+        self.with_read_only_clarity_tx(&sortdb.index_conn(), block_id, |clarity_tx| {
+            // the read-only connection rolls back whatever the simulation writes
+            clarity_tx.with_clarity_db_readonly_owned(|clarity_db| {
+                let mut vm_env = OwnedEnvironment::new_cost_limited(clarity_db, cost_track);
+                let result = to_do(&mut vm_env);
+                let (db, cost_track) = vm_env
+                    .destruct()
+                    .expect("Failed to recover database reference after executing transaction");
+                ((result, cost_track.get_total()), db)
+            })
+        })
+    }
+
+    /// Execute a contract call on top of the given chain tip, and report its return value, events,
+    /// and cost.  Nothing the call writes is persisted.
+    pub fn simulate_contract_call(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        sender: &PrincipalData,
+        contract_id: &QualifiedContractIdentifier,
+        function: &str,
+        args: &[Value],
+    ) -> Result<SimulationResult, Error> {
+        let args: Vec<SymbolicExpression> = args
+            .iter()
+            .map(|arg| SymbolicExpression::atom_value(arg.clone()))
+            .collect();
+
+        let (result, cost) = self.simulate_in_environment(sortdb, block_id, |vm_env| {
+            vm_env.execute_transaction(
+                Value::Principal(sender.clone()),
+                contract_id.clone(),
+                function,
+                &args,
+            )
+        });
+        result
+            .map(|(value, _, events)| SimulationResult {
+                value,
+                events,
+                cost,
+            })
+            .map_err(|e| Error::ClarityError(clarity_error::Interpreter(e)))
+    }
+
     pub fn get_data_var<T: ClarityConnection>(
         clarity_tx: &mut T,
         contract_id: &QualifiedContractIdentifier,
@@ -61,8 +61,8 @@ use std::path::{Path, PathBuf};
 
 use util::db::Error as db_error;
 use util::db::{
-    db_mkdirs, query_count, query_row, tx_begin_immediate, tx_busy_handler, DBConn, DBTx,
-    FromColumn, FromRow, IndexDBTx,
+    db_mkdirs, query_count, query_row, tx_begin_immediate, tx_busy_handler, u64_to_sql, DBConn,
+    DBTx, FromColumn, FromRow, IndexDBTx,
 };
 
 use util::hash::to_hex;
@@ -81,7 +81,7 @@ use vm::clarity::{
     Error as clarity_error,
 };
 use vm::contexts::OwnedEnvironment;
-use vm::costs::ExecutionCost;
+use vm::costs::{ExecutionCost, LimitedCostTracker};
 use vm::database::marf::MarfedKV;
 use vm::database::{
     BurnStateDB, ClarityDatabase, HeadersDB, STXBalance, SqliteConnection, NULL_BURN_STATE_DB,
@@ -106,6 +106,7 @@ pub struct StacksChainState {
     cached_miner_payments: MinerPaymentCache,
     pub block_limit: ExecutionCost,
     pub unconfirmed_state: Option<UnconfirmedState>,
+    pub miner_reward_schedule: MinerRewardSchedule,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -344,6 +345,7 @@ pub struct ChainstateTx<'a> {
     pub headers_tx: StacksDBTx<'a>,
     pub blocks_tx: BlocksDBTx<'a>,
     pub miner_payment_cache: &'a mut MinerPaymentCache,
+    pub miner_reward_schedule: MinerRewardSchedule,
 }
 
 impl<'a> ChainstateTx<'a> {
@@ -494,8 +496,17 @@ const STACKS_CHAIN_STATE_SQL: &'static [&'static str] = &[
         mainnet INTEGER NOT NULL,
         chain_id INTEGER NOT NULL
     )"#,
+    MINER_REWARD_SCHEDULE_SQL,
 ];
 
+/// Test networks may mature miner rewards on their own schedule, which is stored here (at most one
+/// row).  Created on open if missing, since chainstates from before it existed use the default.
+const MINER_REWARD_SCHEDULE_SQL: &'static str = r#"
+    CREATE TABLE IF NOT EXISTS miner_reward_schedule(
+        reward_maturity INTEGER NOT NULL,
+        reward_window INTEGER NOT NULL
+    )"#;
+
 #[cfg(test)]
 pub const MINER_REWARD_MATURITY: u64 = 2; // small for testing purposes
 
@@ -508,6 +519,37 @@ pub const MINER_REWARD_WINDOW: u64 = 5; // small for testing purposes
 #[cfg(not(test))]
 pub const MINER_REWARD_WINDOW: u64 = 16;
 
+/// When a block's coinbase becomes liquid.  A block mined at height h has its reward paid out
+/// when the block at height h + maturity + window is processed, and the payout is calculated
+/// from the miners of the window blocks after h.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinerRewardSchedule {
+    pub maturity: u64,
+    pub window: u64,
+}
+
+impl FromRow<MinerRewardSchedule> for MinerRewardSchedule {
+    fn from_row<'a>(row: &'a Row) -> Result<MinerRewardSchedule, db_error> {
+        let maturity = u64::from_column(row, "reward_maturity")?;
+        let window = u64::from_column(row, "reward_window")?;
+        Ok(MinerRewardSchedule { maturity, window })
+    }
+}
+
+impl MinerRewardSchedule {
+    pub fn default() -> MinerRewardSchedule {
+        MinerRewardSchedule {
+            maturity: MINER_REWARD_MATURITY,
+            window: MINER_REWARD_WINDOW,
+        }
+    }
+
+    /// How many blocks must be built on top of a block before its reward matures
+    pub fn delay(&self) -> u64 {
+        self.maturity + self.window
+    }
+}
+
 pub const MINER_FEE_MINIMUM_BLOCK_USAGE: u64 = 80; // miner must share the first F% of the anchored block tx fees, and gets 100% - F% exclusively
 
 pub const MINER_FEE_WINDOW: u64 = 24; // number of blocks (B) used to smooth over the fraction of tx fees they share from anchored blocks
@@ -555,6 +597,10 @@ impl StacksChainState {
             StacksChainState::instantiate_headers_db(mainnet, chain_id, index_path)
         } else {
             let marf = StacksChainState::open_index(index_path)?;
+            marf.sqlite_conn()
+                .execute(MINER_REWARD_SCHEDULE_SQL, NO_PARAMS)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
             // sanity check
             let db_config = query_row::<DBConfig, _>(
                 marf.sqlite_conn(),
@@ -930,7 +976,13 @@ impl StacksChainState {
             cached_miner_payments: MinerPaymentCache::new(),
             block_limit: block_limit,
             unconfirmed_state: None,
+            miner_reward_schedule: MinerRewardSchedule::default(),
         };
+        if let Some(schedule) =
+            StacksChainState::load_miner_reward_schedule(chainstate.headers_db())?
+        {
+            chainstate.miner_reward_schedule = schedule;
+        }
 
         let mut receipts = vec![];
         if !index_exists {
@@ -946,6 +998,85 @@ impl StacksChainState {
         Ok((chainstate, receipts))
     }
 
+    /// Open a test network chainstate that matures miner rewards on a different schedule than
+    /// the default one.  Mainnet always uses MINER_REWARD_MATURITY and MINER_REWARD_WINDOW.
+    /// The schedule is stored with the chainstate, so every later handle to it (e.g. from `open()`)
+    /// uses it too.  It can only be set before any Stacks block is processed, and never changed.
+    pub fn open_testnet_with_miner_reward_schedule<F>(
+        chain_id: u32,
+        path_str: &str,
+        initial_balances: Option<Vec<(PrincipalData, u64)>>,
+        in_boot_block: F,
+        block_limit: ExecutionCost,
+        schedule: MinerRewardSchedule,
+    ) -> Result<(StacksChainState, Vec<StacksTransactionReceipt>), Error>
+    where
+        F: FnOnce(&mut ClarityTx) -> (),
+    {
+        if schedule.window == 0 {
+            return Err(Error::InvalidMinerRewardSchedule(
+                "Miner reward window must be at least one block".to_string(),
+            ));
+        }
+
+        let (mut chainstate, receipts) = StacksChainState::open_and_exec(
+            false,
+            chain_id,
+            path_str,
+            initial_balances,
+            in_boot_block,
+            block_limit,
+        )?;
+
+        if let Some(stored) = StacksChainState::load_miner_reward_schedule(chainstate.headers_db())?
+        {
+            if stored != schedule {
+                return Err(Error::InvalidMinerRewardSchedule(format!(
+                    "Chainstate already uses miner reward schedule {:?}",
+                    &stored
+                )));
+            }
+            return Ok((chainstate, receipts));
+        }
+
+        let num_blocks = query_count(
+            chainstate.headers_db(),
+            &"SELECT COUNT(*) FROM block_headers WHERE block_height > 0".to_string(),
+            NO_PARAMS,
+        )?;
+        if num_blocks > 0 {
+            return Err(Error::InvalidMinerRewardSchedule(
+                "Cannot change the miner reward schedule once blocks have been processed"
+                    .to_string(),
+            ));
+        }
+
+        {
+            let mut headers_tx = chainstate.headers_tx_begin()?;
+            let args: &[&dyn ToSql] = &[
+                &u64_to_sql(schedule.maturity)?,
+                &u64_to_sql(schedule.window)?,
+            ];
+            headers_tx.tx().execute(
+                "INSERT INTO miner_reward_schedule (reward_maturity,reward_window) VALUES (?1,?2)",
+                args,
+            )?;
+            headers_tx.commit()?;
+        }
+        chainstate.miner_reward_schedule = schedule;
+        Ok((chainstate, receipts))
+    }
+
+    /// Get the miner reward schedule this chainstate was opened with, if it isn't the default one.
+    fn load_miner_reward_schedule(conn: &DBConn) -> Result<Option<MinerRewardSchedule>, Error> {
+        query_row::<MinerRewardSchedule, _>(
+            conn,
+            "SELECT * FROM miner_reward_schedule LIMIT 1",
+            NO_PARAMS,
+        )
+        .map_err(Error::DBError)
+    }
+
     pub fn config(&self) -> DBConfig {
         DBConfig {
             mainnet: self.mainnet,
@@ -983,6 +1114,7 @@ impl StacksChainState {
             headers_tx: headers_tx,
             blocks_tx: blocks_tx,
             miner_payment_cache: &mut self.cached_miner_payments,
+            miner_reward_schedule: self.miner_reward_schedule,
         };
 
         Ok((chainstate_tx, clarity_instance))
@@ -1025,6 +1157,55 @@ impl StacksChainState {
             .map_err(Error::ClarityError)
     }
 
+    /// Evaluate several read-only Clarity expressions, each against its own contract, at the same
+    /// chain tip.  The tip's Clarity state is only opened once.  If a query fails, the error
+    /// carries its index in `queries`.
+    pub fn eval_read_only_batch(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        queries: &[(QualifiedContractIdentifier, &str)],
+    ) -> Result<Vec<Value>, Error> {
+        let iconn = sortdb.index_conn();
+        self.with_read_only_clarity_tx(&iconn, block_id, |clarity_tx| {
+            let mut values = vec![];
+            for (i, (contract, code)) in queries.iter().enumerate() {
+                let value = clarity_tx
+                    .with_readonly_clarity_env(
+                        PrincipalData::from(QualifiedContractIdentifier::transient().issuer),
+                        LimitedCostTracker::new_free(),
+                        |env| env.eval_read_only(contract, code),
+                    )
+                    .map_err(|e| Error::ReadOnlyQueryError(i, clarity_error::Interpreter(e)))?;
+                values.push(value);
+            }
+            Ok(values)
+        })
+    }
+
+    /// Evaluate a read-only Clarity expression and write the consensus serialization of its
+    /// result to `fd`, instead of returning the `Value`.  If the expression is a `(list ...)`,
+    /// each item is written out as soon as it has been evaluated, so the whole list is never held
+    /// in memory.  This means that if an item fails to evaluate, the items before it will already
+    /// have been written.  Any other expression is evaluated in full before it is written.
+    pub fn clarity_eval_read_only_to_writer<W: Write>(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        parent_id_bhh: &StacksBlockId,
+        contract: &QualifiedContractIdentifier,
+        code: &str,
+        fd: &mut W,
+    ) -> Result<(), Error> {
+        self.with_read_only_clarity_tx(burn_dbconn, parent_id_bhh, |clarity_tx| {
+            clarity_tx.with_readonly_clarity_env(
+                PrincipalData::from(QualifiedContractIdentifier::transient().issuer),
+                LimitedCostTracker::new_free(),
+                |env| env.eval_read_only_to_writer(contract, code, fd),
+            )
+        })
+        .map_err(|e| Error::ClarityError(clarity_error::Interpreter(e)))
+    }
+
     pub fn headers_db(&self) -> &DBConn {
         self.headers_state_index.sqlite_conn()
     }
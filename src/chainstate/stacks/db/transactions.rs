@@ -21,10 +21,12 @@ use std::io;
 use std::io::prelude::*;
 use std::io::{Read, Write};
 
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fs;
 
+use chainstate::stacks::db::blocks::{MINIMUM_TX_FEE, MINIMUM_TX_FEE_RATE_PER_BYTE};
 use chainstate::stacks::db::*;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
@@ -49,6 +51,7 @@ use vm::contexts::{AssetMap, AssetMapEntry};
 use vm::analysis::run_analysis;
 use vm::ast::build_ast;
 use vm::costs::ExecutionCost;
+use vm::representations::SymbolicExpression;
 
 use vm::types::{AssetIdentifier, Value};
 
@@ -66,6 +69,16 @@ use vm::database::ClarityDatabase;
 
 use vm::contracts::Contract;
 
+/// Execution budget that `estimate_tx_fee()` prices a transaction's cost against when the
+/// chainstate's own block limit is unlimited.
+pub const FEE_ESTIMATE_BLOCK_LIMIT: ExecutionCost = ExecutionCost {
+    write_length: 15_000_000,
+    write_count: 50_000,
+    read_length: 1_000_000_000,
+    read_count: 50_000,
+    runtime: 100_000_000,
+};
+
 // make it possible to have a set of Values
 impl std::hash::Hash for Value {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
@@ -886,6 +899,169 @@ impl StacksChainState {
 
         Ok((fee, tx_receipt))
     }
+
+    /// Simulate the transaction's asset movements on top of the given chain tip, and report
+    /// whether its post-conditions would hold under its post-condition mode.  Nothing the
+    /// transaction writes is persisted, and its nonces and fee are not checked.
+    pub fn check_post_conditions(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        tx: &StacksTransaction,
+    ) -> Result<bool, Error> {
+        match tx.payload {
+            TransactionPayload::ContractCall(_) | TransactionPayload::SmartContract(_) => {}
+            TransactionPayload::TokenTransfer(..) | TransactionPayload::PoisonMicroblock(..) => {
+                // these variants can't carry post-conditions, and are never checked against them
+                if tx.post_conditions.len() > 0 {
+                    let msg = format!(
+                        "Invalid Stacks transaction: {} transactions do not support post-conditions",
+                        tx.payload.name()
+                    );
+                    return Err(Error::InvalidStacksTransaction(msg, false));
+                }
+                return Ok(true);
+            }
+            TransactionPayload::Coinbase(_) => {
+                // never checked against its post-conditions
+                return Ok(true);
+            }
+        }
+
+        let (origin_account, result, _) = self.simulate_transaction(sortdb, block_id, tx);
+        let asset_map = result.or_else(|e| match e {
+            // runtime errors are okay -- nothing materializes, so no assets move
+            InterpreterError::Runtime(..) => Ok(AssetMap::new()),
+            _ => Err(Error::ClarityError(clarity_error::Interpreter(e))),
+        })?;
+
+        Ok(StacksChainState::check_transaction_postconditions(
+            &tx.post_conditions,
+            &tx.post_condition_mode,
+            &origin_account,
+            &asset_map,
+        ))
+    }
+
+    /// Suggest a fee for the given transaction, were it mined on top of the given chain tip.
+    /// Contract calls and smart contracts are simulated to find their execution cost, and pay for
+    /// it as if it were the same share of the block's bytes as the largest share of the block's
+    /// execution budget that it uses.  Other transactions don't run any Clarity code, so they
+    /// just pay the flat rate for their length.  Every byte is charged at `fee_rate_per_byte` uSTX
+    /// (but never less than the minimum relay fee rate) -- the chainstate can't see the mempool, so
+    /// it's up to the caller to pick this rate (e.g. from `MemPoolDB::fee_rate_histogram()`).
+    /// Nothing the transaction writes is persisted, and its nonces and fee are not checked.
+    pub fn estimate_tx_fee(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        tx: &StacksTransaction,
+        fee_rate_per_byte: u64,
+    ) -> Result<u64, Error> {
+        let cost = match tx.payload {
+            TransactionPayload::ContractCall(_) | TransactionPayload::SmartContract(_) => {
+                let (_, result, cost) = self.simulate_transaction(sortdb, block_id, tx);
+                match result {
+                    // the transaction would still be mined (and pay for its cost) if it aborts
+                    Ok(_) | Err(InterpreterError::Runtime(..)) => cost,
+                    Err(e) => return Err(Error::ClarityError(clarity_error::Interpreter(e))),
+                }
+            }
+            TransactionPayload::TokenTransfer(..)
+            | TransactionPayload::PoisonMicroblock(..)
+            | TransactionPayload::Coinbase(_) => ExecutionCost::zero(),
+        };
+
+        let fee_bytes = (tx.tx_len() as u128) + self.cost_as_block_bytes(&cost);
+        let fee_rate_per_byte = cmp::max(fee_rate_per_byte, MINIMUM_TX_FEE_RATE_PER_BYTE);
+        let fee = fee_bytes.saturating_mul(fee_rate_per_byte as u128);
+        Ok(cmp::max(
+            cmp::min(fee, u64::max_value() as u128) as u64,
+            MINIMUM_TX_FEE,
+        ))
+    }
+
+    /// How many bytes of a block take up the same share of it as the largest share of the
+    /// block's execution budget that `cost` uses?  An unlimited budget would make every cost
+    /// free, so in that case `cost` is measured against `FEE_ESTIMATE_BLOCK_LIMIT` instead.
+    fn cost_as_block_bytes(&self, cost: &ExecutionCost) -> u128 {
+        let limit = if self.block_limit == ExecutionCost::max_value() {
+            &FEE_ESTIMATE_BLOCK_LIMIT
+        } else {
+            &self.block_limit
+        };
+        let dimensions = [
+            (cost.runtime, limit.runtime),
+            (cost.read_count, limit.read_count),
+            (cost.read_length, limit.read_length),
+            (cost.write_count, limit.write_count),
+            (cost.write_length, limit.write_length),
+        ];
+        dimensions
+            .iter()
+            .filter(|(_, limit)| *limit > 0)
+            .map(|(used, limit)| (*used as u128) * (MAX_BLOCK_LEN as u128) / (*limit as u128))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Run a contract-call or smart-contract transaction's Clarity code on top of the given chain
+    /// tip, the same way simulate_contract_call() runs a call.  Returns the transaction's origin
+    /// account, what the code returned (the assets it moved, or the error it hit), and the
+    /// execution cost it incurred along the way.  Nothing the transaction writes is persisted.
+    fn simulate_transaction(
+        &mut self,
+        sortdb: &SortitionDB,
+        block_id: &StacksBlockId,
+        tx: &StacksTransaction,
+    ) -> (
+        StacksAccount,
+        Result<AssetMap, InterpreterError>,
+        ExecutionCost,
+    ) {
+        let origin_account =
+            self.with_read_only_clarity_tx(&sortdb.index_conn(), block_id, |clarity_tx| {
+                StacksChainState::get_account(clarity_tx, &tx.origin_address().into())
+            });
+
+        let (result, cost) = self.simulate_in_environment(sortdb, block_id, |vm_env| {
+            match tx.payload {
+                TransactionPayload::ContractCall(ref contract_call) => {
+                    let args: Vec<SymbolicExpression> = contract_call
+                        .function_args
+                        .iter()
+                        .map(|arg| SymbolicExpression::atom_value(arg.clone()))
+                        .collect();
+                    vm_env
+                        .execute_transaction(
+                            Value::Principal(origin_account.principal.clone()),
+                            contract_call.to_clarity_contract_id(),
+                            &contract_call.function_name,
+                            &args,
+                        )
+                        .map(|(_, asset_map, _)| asset_map)
+                }
+                TransactionPayload::SmartContract(ref smart_contract) => {
+                    let issuer_principal = match origin_account.principal {
+                        PrincipalData::Standard(ref p) => p.clone(),
+                        _ => {
+                            unreachable!("BUG: transaction issued by something other than a standard principal");
+                        }
+                    };
+                    let contract_id = QualifiedContractIdentifier::new(
+                        issuer_principal,
+                        smart_contract.name.clone(),
+                    );
+                    vm_env
+                        .initialize_contract(contract_id, &smart_contract.code_body.to_string())
+                        .map(|(_, asset_map, _)| asset_map)
+                }
+                _ => unreachable!("BUG: no simulation for this transaction payload"),
+            }
+        });
+
+        (origin_account, result, cost)
+    }
 }
 
 #[cfg(test)]
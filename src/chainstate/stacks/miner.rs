@@ -742,8 +742,9 @@ impl StacksBlockBuilder {
     ) -> Result<ClarityTx<'a>, Error> {
         // find matured miner rewards, so we can grant them within the Clarity DB tx.
         let matured_miner_rewards_opt = {
+            let schedule = chainstate.miner_reward_schedule;
             let mut tx = chainstate.headers_tx_begin()?;
-            StacksChainState::find_mature_miner_rewards(&mut tx, &self.chain_tip, None)?
+            StacksChainState::find_mature_miner_rewards(&mut tx, &self.chain_tip, None, &schedule)?
         };
 
         self.miner_payouts = matured_miner_rewards_opt;
@@ -943,6 +944,36 @@ impl StacksBlockBuilder {
         pubkey_hash: Hash160,
         coinbase_tx: &StacksTransaction,
         execution_budget: ExecutionCost,
+    ) -> Result<(StacksBlock, ExecutionCost, u64), Error> {
+        StacksBlockBuilder::build_anchored_block_with_txs(
+            chainstate_handle,
+            burn_dbconn,
+            mempool,
+            parent_stacks_header,
+            total_burn,
+            proof,
+            pubkey_hash,
+            coinbase_tx,
+            &[],
+            execution_budget,
+        )
+    }
+
+    /// Given access to the mempool, mine an anchored block with no more than the given execution cost.
+    /// The `must_include` transactions are mined in order immediately after the coinbase, ahead of
+    /// anything from the mempool.  If any of them cannot be mined at the chain tip, block
+    /// assembly is aborted.
+    pub fn build_anchored_block_with_txs(
+        chainstate_handle: &StacksChainState, // not directly used; used as a handle to open other chainstates
+        burn_dbconn: &dyn BurnStateDB,
+        mempool: &MemPoolDB,
+        parent_stacks_header: &StacksHeaderInfo, // Stacks header we're building off of
+        total_burn: u64, // the burn so far on the burnchain (i.e. from the last burnchain block)
+        proof: VRFProof, // proof over the burnchain's last seed
+        pubkey_hash: Hash160,
+        coinbase_tx: &StacksTransaction,
+        must_include: &[StacksTransaction],
+        execution_budget: ExecutionCost,
     ) -> Result<(StacksBlock, ExecutionCost, u64), Error> {
         if let TransactionPayload::Coinbase(..) = coinbase_tx.payload {
         } else {
@@ -979,6 +1010,31 @@ impl StacksBlockBuilder {
         let mut mined_origin_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction origins to the nonces we used
         let mut mined_sponsor_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction sponsors to the nonces we used
 
+        for tx in must_include.iter() {
+            if let Err(e) = builder.try_mine_tx(&mut epoch_tx, tx) {
+                warn!("Failed to apply must-include tx {}: {:?}", &tx.txid(), &e);
+                epoch_tx.rollback_block();
+                return Err(Error::InvalidStacksTransaction(
+                    format!(
+                        "Must-include transaction {} is invalid at {}/{}: {}",
+                        &tx.txid(),
+                        &tip_consensus_hash,
+                        &tip_block_hash,
+                        e
+                    ),
+                    false,
+                ));
+            }
+
+            considered.insert(tx.txid());
+            mined_origin_nonces.insert(tx.origin_address(), tx.get_origin_nonce());
+            if let (Some(sponsor_addr), Some(sponsor_nonce)) =
+                (tx.sponsor_address(), tx.get_sponsor_nonce())
+            {
+                mined_sponsor_nonces.insert(sponsor_addr, sponsor_nonce);
+            }
+        }
+
         let result = mempool.iterate_candidates(
             &tip_consensus_hash,
             &tip_block_hash,
@@ -7022,6 +7078,311 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_build_anchored_blocks_must_include() {
+        let privk = StacksPrivateKey::from_hex(
+            "42faca653724860da7a41bfcef7e6ba78db55146f6900de8cb2a9f760ffac70c01",
+        )
+        .unwrap();
+        let addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk)],
+        )
+        .unwrap();
+
+        let pinned_privk = StacksPrivateKey::new();
+        let pinned_addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&pinned_privk)],
+        )
+        .unwrap();
+
+        let mut peer_config =
+            TestPeerConfig::new("test_build_anchored_blocks_must_include", 2016, 2017);
+        peer_config.initial_balances = vec![
+            (addr.to_account_principal(), 1000000000),
+            (pinned_addr.to_account_principal(), 1000000000),
+        ];
+
+        let mut peer = TestPeer::new(peer_config);
+
+        let chainstate_path = peer.chainstate_path.clone();
+
+        let num_blocks = 5;
+        let recipient_addr_str = "ST1RFD5Q2QPK3E0F08HG9XDX7SSC7CNRS0QR0SGEV";
+        let recipient = StacksAddress::from_string(recipient_addr_str).unwrap();
+        let mut sender_nonce = 0;
+        let mut pinned_nonce = 0;
+
+        for tenure_id in 0..num_blocks {
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let mut pinned_txid = None;
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = match parent_opt {
+                        None => StacksChainState::get_genesis_header_info(chainstate.headers_db())
+                            .unwrap(),
+                        Some(block) => {
+                            let ic = sortdb.index_conn();
+                            let snapshot =
+                                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                    &ic,
+                                    &tip.sortition_id,
+                                    &block.block_hash(),
+                                )
+                                .unwrap()
+                                .unwrap(); // succeeds because we don't fork
+                            StacksChainState::get_anchored_block_header_info(
+                                chainstate.headers_db(),
+                                &snapshot.consensus_hash,
+                                &snapshot.winning_stacks_block_hash,
+                            )
+                            .unwrap()
+                            .unwrap()
+                        }
+                    };
+
+                    let parent_header_hash = parent_tip.anchored_header.block_hash();
+                    let parent_consensus_hash = parent_tip.consensus_hash.clone();
+
+                    let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let mut must_include = vec![];
+                    if tenure_id > 0 {
+                        // a higher-fee mempool transaction that would otherwise be mined first
+                        let stx_transfer = make_user_stacks_transfer(
+                            &privk,
+                            sender_nonce,
+                            1000,
+                            &recipient.to_account_principal(),
+                            1,
+                        );
+                        sender_nonce += 1;
+
+                        mempool
+                            .submit(&parent_consensus_hash, &parent_header_hash, stx_transfer)
+                            .unwrap();
+
+                        // a pinned transaction with a stale nonce aborts assembly
+                        let bad_pinned_tx = make_user_stacks_transfer(
+                            &pinned_privk,
+                            pinned_nonce + 1000,
+                            1,
+                            &recipient.to_account_principal(),
+                            2,
+                        );
+                        match StacksBlockBuilder::build_anchored_block_with_txs(
+                            chainstate,
+                            &sortdb.index_conn(),
+                            &mempool,
+                            &parent_tip,
+                            tip.total_burn,
+                            vrf_proof.clone(),
+                            Hash160([tenure_id as u8; 20]),
+                            &coinbase_tx,
+                            &[bad_pinned_tx],
+                            ExecutionCost::max_value(),
+                        ) {
+                            Err(chainstate::stacks::Error::InvalidStacksTransaction(..)) => {}
+                            res => panic!("Expected invalid must-include tx, got {:?}", res),
+                        }
+
+                        let pinned_tx = make_user_stacks_transfer(
+                            &pinned_privk,
+                            pinned_nonce,
+                            1,
+                            &recipient.to_account_principal(),
+                            2,
+                        );
+                        pinned_nonce += 1;
+                        pinned_txid = Some(pinned_tx.txid());
+                        must_include.push(pinned_tx);
+                    }
+
+                    let anchored_block = StacksBlockBuilder::build_anchored_block_with_txs(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &mempool,
+                        &parent_tip,
+                        tip.total_burn,
+                        vrf_proof,
+                        Hash160([tenure_id as u8; 20]),
+                        &coinbase_tx,
+                        &must_include,
+                        ExecutionCost::max_value(),
+                    )
+                    .unwrap();
+                    (anchored_block.0, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            if tenure_id > 0 {
+                // pinned transaction comes right after the coinbase, ahead of the mempool
+                assert_eq!(stacks_block.txs.len(), 3);
+                if let TransactionPayload::Coinbase(..) = stacks_block.txs[0].payload {
+                } else {
+                    assert!(false);
+                }
+                assert_eq!(Some(stacks_block.txs[1].txid()), pinned_txid);
+                if let TransactionPayload::TokenTransfer(_, ref amount, _) =
+                    stacks_block.txs[2].payload
+                {
+                    assert_eq!(*amount, 1);
+                } else {
+                    assert!(false);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_anchored_blocks_mempool_assembly_order() {
+        let privks: Vec<_> = (0..3).map(|_| StacksPrivateKey::new()).collect();
+        let balances: Vec<_> = privks
+            .iter()
+            .map(|privk| {
+                let addr = StacksAddress::from_public_keys(
+                    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                    &AddressHashMode::SerializeP2PKH,
+                    1,
+                    &vec![StacksPublicKey::from_private(privk)],
+                )
+                .unwrap();
+                (addr.to_account_principal(), 1000000000)
+            })
+            .collect();
+
+        let mut peer_config = TestPeerConfig::new(
+            "test_build_anchored_blocks_mempool_assembly_order",
+            2018,
+            2019,
+        );
+        peer_config.initial_balances = balances;
+
+        let mut peer = TestPeer::new(peer_config);
+
+        let chainstate_path = peer.chainstate_path.clone();
+
+        let num_blocks = 3;
+        let recipient_addr_str = "ST1RFD5Q2QPK3E0F08HG9XDX7SSC7CNRS0QR0SGEV";
+        let recipient = StacksAddress::from_string(recipient_addr_str).unwrap();
+        let mut sender_nonce = 0;
+
+        for tenure_id in 0..num_blocks {
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let mut expected_txids = vec![];
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = match parent_opt {
+                        None => StacksChainState::get_genesis_header_info(chainstate.headers_db())
+                            .unwrap(),
+                        Some(block) => {
+                            let ic = sortdb.index_conn();
+                            let snapshot =
+                                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                    &ic,
+                                    &tip.sortition_id,
+                                    &block.block_hash(),
+                                )
+                                .unwrap()
+                                .unwrap(); // succeeds because we don't fork
+                            StacksChainState::get_anchored_block_header_info(
+                                chainstate.headers_db(),
+                                &snapshot.consensus_hash,
+                                &snapshot.winning_stacks_block_hash,
+                            )
+                            .unwrap()
+                            .unwrap()
+                        }
+                    };
+
+                    let parent_header_hash = parent_tip.anchored_header.block_hash();
+                    let parent_consensus_hash = parent_tip.consensus_hash.clone();
+
+                    let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    if tenure_id > 0 {
+                        // two transactions per sender, with fees unrelated to arrival order
+                        for i in 0..2 {
+                            for (j, privk) in privks.iter().enumerate() {
+                                let stx_transfer = make_user_stacks_transfer(
+                                    privk,
+                                    sender_nonce + i,
+                                    (1 + j as u64) * (2 - i) * 100,
+                                    &recipient.to_account_principal(),
+                                    1,
+                                );
+                                mempool
+                                    .submit(
+                                        &parent_consensus_hash,
+                                        &parent_header_hash,
+                                        stx_transfer,
+                                    )
+                                    .unwrap();
+                            }
+                        }
+                        sender_nonce += 2;
+
+                        expected_txids = mempool
+                            .iter_for_assembly(chainstate, &parent_tip.index_block_hash())
+                            .unwrap()
+                            .map(|txinfo| txinfo.tx.txid())
+                            .collect();
+                        assert_eq!(expected_txids.len(), 2 * privks.len());
+                    }
+
+                    let anchored_block = StacksBlockBuilder::build_anchored_block(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &mempool,
+                        &parent_tip,
+                        tip.total_burn,
+                        vrf_proof,
+                        Hash160([tenure_id as u8; 20]),
+                        &coinbase_tx,
+                        ExecutionCost::max_value(),
+                    )
+                    .unwrap();
+                    (anchored_block.0, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            // the builder mined the mempool transactions in the order the iterator yielded them
+            let mined_txids: Vec<_> = stacks_block.txs[1..].iter().map(|tx| tx.txid()).collect();
+            assert_eq!(mined_txids, expected_txids);
+        }
+    }
+
     // TODO: invalid block with duplicate microblock public key hash (okay between forks, but not
     // within the same fork)
     // TODO: (BLOCKED) build off of different points in the same microblock stream
@@ -164,6 +164,9 @@ pub enum Error {
     PoxAlreadyLocked,
     PoxInsufficientBalance,
     PoxNoRewardCycle,
+    ReadOnlyQueryError(usize, clarity_error),
+    InvalidMinerRewardSchedule(String),
+    RewardSetTooLarge(u128, u128),
 }
 
 impl From<marf_error> for Error {
@@ -207,6 +210,13 @@ impl fmt::Display for Error {
             Error::PoxAlreadyLocked => write!(f, "Account has already locked STX for PoX"),
             Error::PoxInsufficientBalance => write!(f, "Not enough STX to lock"),
             Error::PoxNoRewardCycle => write!(f, "No such reward cycle"),
+            Error::ReadOnlyQueryError(i, ref e) => write!(f, "Read-only query {} failed: {}", i, e),
+            Error::InvalidMinerRewardSchedule(ref s) => fmt::Display::fmt(s, f),
+            Error::RewardSetTooLarge(num, max) => write!(
+                f,
+                "Reward set has {} entries, but at most {} are allowed",
+                num, max
+            ),
         }
     }
 }
@@ -237,6 +247,9 @@ impl error::Error for Error {
             Error::PoxAlreadyLocked => None,
             Error::PoxInsufficientBalance => None,
             Error::PoxNoRewardCycle => None,
+            Error::ReadOnlyQueryError(_, ref e) => Some(e),
+            Error::InvalidMinerRewardSchedule(ref _s) => None,
+            Error::RewardSetTooLarge(..) => None,
         }
     }
 }
@@ -267,6 +280,9 @@ impl Error {
             Error::PoxAlreadyLocked => "PoxAlreadyLocked",
             Error::PoxInsufficientBalance => "PoxInsufficientBalance",
             Error::PoxNoRewardCycle => "PoxNoRewardCycle",
+            Error::ReadOnlyQueryError(_, ref _e) => "ReadOnlyQueryError",
+            Error::InvalidMinerRewardSchedule(ref _s) => "InvalidMinerRewardSchedule",
+            Error::RewardSetTooLarge(..) => "RewardSetTooLarge",
         }
     }
 
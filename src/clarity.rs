@@ -123,6 +123,7 @@ fn run_analysis(
         analysis_db,
         save_contract,
         LimitedCostTracker::new_max_limit(),
+        analysis::AnalysisPasses::all(),
     )
     .map_err(|(e, _)| e)
 }
@@ -26,6 +26,8 @@ use rusqlite::Transaction;
 use rusqlite::NO_PARAMS;
 
 use std::cmp;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::ops::Deref;
 use std::ops::DerefMut;
 
@@ -36,13 +38,14 @@ use net::StacksMessageCodec;
 
 use chainstate::burn::BlockHeaderHash;
 use chainstate::stacks::{
-    db::blocks::MemPoolRejection, db::StacksChainState, index::Error as MarfError,
-    Error as ChainstateError, StacksAddress, StacksBlockHeader, StacksTransaction,
+    db::StacksChainState, index::Error as MarfError, Error as ChainstateError, StacksAddress,
+    StacksBlockHeader, StacksBlockId, StacksTransaction,
 };
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
+use util::db::query_count;
 use util::db::query_row;
 use util::db::query_rows;
 use util::db::tx_begin_immediate;
@@ -58,10 +61,23 @@ use core::FIRST_STACKS_BLOCK_HASH;
 
 use rusqlite::Error as SqliteError;
 
+use monitoring;
+
+pub use chainstate::stacks::db::blocks::MemPoolRejection;
+
 // maximum number of confirmations a transaction can have before it's garbage-collected
 pub const MEMPOOL_MAX_TRANSACTION_AGE: u64 = 256;
 pub const MAXIMUM_MEMPOOL_TX_CHAINING: u64 = 5;
 
+/// What happened when a transaction was added to the mempool
+#[derive(Debug, PartialEq, Clone)]
+pub enum MemPoolAddResult {
+    /// The transaction was added, and did not displace any other transaction
+    Added,
+    /// The transaction replaced the pending transaction with this txid
+    Replaced(Txid),
+}
+
 pub struct MemPoolAdmitter {
     // mempool admission should have its own chain state view.
     //   the mempool admitter interacts with the chain state
@@ -129,6 +145,45 @@ pub struct MemPoolTxMetadata {
     pub accept_time: u64,
 }
 
+/// The columns of a pending transaction that are needed to decide whether to evict it
+#[derive(Clone)]
+struct EvictionCandidate {
+    txid: Txid,
+    origin_address: StacksAddress,
+    origin_nonce: u64,
+    sponsor_address: StacksAddress,
+    sponsor_nonce: u64,
+    fee_rate: u64,
+    accept_time: u64,
+    len: u64,
+}
+
+impl EvictionCandidate {
+    /// The account nonces this transaction uses: its origin's, and its sponsor's if it has one
+    fn nonces(&self) -> Vec<(&StacksAddress, u64)> {
+        let mut nonces = vec![(&self.origin_address, self.origin_nonce)];
+        if self.sponsor_address != self.origin_address || self.sponsor_nonce != self.origin_nonce {
+            nonces.push((&self.sponsor_address, self.sponsor_nonce));
+        }
+        nonces
+    }
+}
+
+impl FromRow<EvictionCandidate> for EvictionCandidate {
+    fn from_row<'a>(row: &'a Row) -> Result<EvictionCandidate, db_error> {
+        Ok(EvictionCandidate {
+            txid: Txid::from_column(row, "txid")?,
+            origin_address: StacksAddress::from_column(row, "origin_address")?,
+            origin_nonce: u64::from_column(row, "origin_nonce")?,
+            sponsor_address: StacksAddress::from_column(row, "sponsor_address")?,
+            sponsor_nonce: u64::from_column(row, "sponsor_nonce")?,
+            fee_rate: u64::from_column(row, "fee_rate")?,
+            accept_time: u64::from_column(row, "accept_time")?,
+            len: u64::from_column(row, "length")?,
+        })
+    }
+}
+
 impl FromRow<MemPoolTxMetadata> for MemPoolTxMetadata {
     fn from_row<'a>(row: &'a Row) -> Result<MemPoolTxMetadata, db_error> {
         let txid = Txid::from_column(row, "txid")?;
@@ -208,17 +263,37 @@ const MEMPOOL_SQL: &'static [&'static str] = &[
     CREATE INDEX by_chaintip ON mempool(consensus_hash,block_header_hash);
     CREATE INDEX by_estimated_fee ON mempool(estimated_fee);
     "#,
+    r#"
+    CREATE INDEX by_fee_rate ON mempool(fee_rate, accept_time);
+    "#,
 ];
 
+/// Indexes added to `MEMPOOL_SQL` after the mempool schema was first deployed, which are created
+/// in existing mempools that predate them.
+const MEMPOOL_INDEX_MIGRATIONS: &'static [(&'static str, &'static str)] = &[(
+    "by_fee_rate",
+    "CREATE INDEX by_fee_rate ON mempool(fee_rate, accept_time);",
+)];
+
 pub struct MemPoolDB {
     db: DBConn,
     path: String,
     admitter: MemPoolAdmitter,
+    replace_fee_bump_pct: u64,
+    max_txs: Option<u64>,
+    max_bytes: Option<u64>,
 }
 
 pub struct MemPoolTx<'a> {
     tx: DBTx<'a>,
     admitter: &'a mut MemPoolAdmitter,
+    // minimum fee increase, as a percentage of the pending transaction's fee, needed to replace a
+    // pending transaction with the same origin or sponsor nonce in the same fork.
+    replace_fee_bump_pct: u64,
+    // maximum number of transactions and total transaction bytes the mempool may hold.  Once
+    // either is reached, lower-fee-rate transactions are evicted to make room.
+    max_txs: Option<u64>,
+    max_bytes: Option<u64>,
 }
 
 impl<'a> Deref for MemPoolTx<'a> {
@@ -235,8 +310,29 @@ impl<'a> DerefMut for MemPoolTx<'a> {
 }
 
 impl<'a> MemPoolTx<'a> {
-    pub fn new(tx: DBTx<'a>, admitter: &'a mut MemPoolAdmitter) -> MemPoolTx<'a> {
-        MemPoolTx { tx, admitter }
+    pub fn new(
+        tx: DBTx<'a>,
+        admitter: &'a mut MemPoolAdmitter,
+        replace_fee_bump_pct: u64,
+        max_txs: Option<u64>,
+        max_bytes: Option<u64>,
+    ) -> MemPoolTx<'a> {
+        MemPoolTx {
+            tx,
+            admitter,
+            replace_fee_bump_pct,
+            max_txs,
+            max_bytes,
+        }
+    }
+
+    /// Does a transaction with fee `new_fee` pay enough to replace a pending transaction with fee
+    /// `old_fee`?  The new fee must be strictly higher, and at least `replace_fee_bump_pct`
+    /// percent higher.
+    fn is_sufficient_fee_bump(&self, old_fee: u64, new_fee: u64) -> bool {
+        new_fee > old_fee
+            && (new_fee as u128) * 100
+                >= (old_fee as u128) * (100 + self.replace_fee_bump_pct as u128)
     }
 
     pub fn commit(self) -> Result<(), db_error> {
@@ -328,6 +424,24 @@ impl MemPoolDB {
         Ok(())
     }
 
+    /// Bring an existing mempool db's schema up to date with `MEMPOOL_SQL`.
+    fn migrate_mempool_db(conn: &mut DBConn) -> Result<(), db_error> {
+        let tx = tx_begin_immediate(conn)?;
+
+        for (index_name, cmd) in MEMPOOL_INDEX_MIGRATIONS {
+            let sql =
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = ?1".to_string();
+            let args: &[&dyn ToSql] = &[index_name];
+            if query_count(&tx, &sql, args)? == 0 {
+                debug!("Add index {} to the mempool", index_name);
+                tx.execute(cmd, NO_PARAMS).map_err(db_error::SqliteError)?;
+            }
+        }
+
+        tx.commit().map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
     /// Open the mempool db within the chainstate directory.
     /// The chainstate must be instantiated already.
     pub fn open(
@@ -381,12 +495,17 @@ impl MemPoolDB {
         if create_flag {
             // instantiate!
             MemPoolDB::instantiate_mempool_db(&mut conn)?;
+        } else {
+            MemPoolDB::migrate_mempool_db(&mut conn)?;
         }
 
         Ok(MemPoolDB {
             db: conn,
             path: db_path.to_string(),
             admitter: admitter,
+            replace_fee_bump_pct: 0,
+            max_txs: None,
+            max_bytes: None,
         })
     }
 
@@ -593,13 +712,98 @@ impl MemPoolDB {
         }
     }
 
+    /// Get the pending transactions that the block builder would consider, in the order it would
+    ///  consider them, when assembling a block on top of the given Stacks tip.  Transactions are
+    ///  visited exactly as in `iterate_candidates()`, and a transaction is skipped if it was
+    ///  already yielded or if its origin (or sponsor) nonce is not greater than that of an
+    ///  earlier-yielded transaction from the same account.  The mempool is not modified.
+    ///
+    ///  This order matches the builder's only as long as every yielded transaction can be mined.
+    ///  The builder records an account's nonce only after `try_mine_tx()` succeeds, whereas this
+    ///  method records it for every transaction it yields, since it does not execute them.  So if
+    ///  the builder fails to mine a transaction, it may go on to consider another transaction with
+    ///  the same origin (or sponsor) nonce that this method skipped.
+    pub fn iter_for_assembly(
+        &self,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+    ) -> Result<impl Iterator<Item = MemPoolTxInfo>, ChainstateError> {
+        let tip_header = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            chainstate.headers_db(),
+            tip,
+        )?
+        .ok_or(ChainstateError::NoSuchBlockError)?;
+
+        let mut considered = HashSet::new();
+        let mut origin_nonces: HashMap<StacksAddress, u64> = HashMap::new();
+        let mut sponsor_nonces: HashMap<StacksAddress, u64> = HashMap::new();
+        let mut ordered = vec![];
+
+        self.iterate_candidates(
+            &tip_header.consensus_hash,
+            &tip_header.anchored_header.block_hash(),
+            tip_header.block_height,
+            chainstate,
+            |available_txs| -> Result<(), ChainstateError> {
+                for txinfo in available_txs.into_iter() {
+                    if considered.contains(&txinfo.tx.txid()) {
+                        continue;
+                    }
+                    if let Some(nonce) = origin_nonces.get(&txinfo.tx.origin_address()) {
+                        if *nonce >= txinfo.tx.get_origin_nonce() {
+                            continue;
+                        }
+                    }
+                    if let (Some(sponsor_addr), Some(sponsor_nonce)) =
+                        (txinfo.tx.sponsor_address(), txinfo.tx.get_sponsor_nonce())
+                    {
+                        if let Some(nonce) = sponsor_nonces.get(&sponsor_addr) {
+                            if *nonce >= sponsor_nonce {
+                                continue;
+                            }
+                        }
+                        sponsor_nonces.insert(sponsor_addr, sponsor_nonce);
+                    }
+
+                    considered.insert(txinfo.tx.txid());
+                    origin_nonces.insert(txinfo.tx.origin_address(), txinfo.tx.get_origin_nonce());
+                    ordered.push(txinfo);
+                }
+                Ok(())
+            },
+        )?;
+
+        Ok(ordered.into_iter())
+    }
+
     pub fn conn(&self) -> &DBConn {
         &self.db
     }
 
     pub fn tx_begin<'a>(&'a mut self) -> Result<MemPoolTx<'a>, db_error> {
         let tx = tx_begin_immediate(&mut self.db)?;
-        Ok(MemPoolTx::new(tx, &mut self.admitter))
+        Ok(MemPoolTx::new(
+            tx,
+            &mut self.admitter,
+            self.replace_fee_bump_pct,
+            self.max_txs,
+            self.max_bytes,
+        ))
+    }
+
+    /// Set the minimum fee increase, as a percentage of the pending transaction's fee, that a
+    /// transaction must pay to replace a pending transaction with the same origin or sponsor
+    /// nonce.  Defaults to 0, in which case any strictly higher fee is sufficient.
+    pub fn set_replace_fee_bump_pct(&mut self, pct: u64) {
+        self.replace_fee_bump_pct = pct;
+    }
+
+    /// Cap the number of transactions and the total transaction bytes held in the mempool.  Once
+    /// either limit is reached, a new transaction is only admitted if it pays a higher fee rate
+    /// than the transactions it evicts.  `None` means no limit, which is the default.
+    pub fn set_size_limits(&mut self, max_txs: Option<u64>, max_bytes: Option<u64>) {
+        self.max_txs = max_txs;
+        self.max_bytes = max_bytes;
     }
 
     fn db_has_tx(conn: &DBConn, txid: &Txid) -> Result<bool, db_error> {
@@ -773,7 +977,8 @@ impl MemPoolDB {
     }
 
     /// Add a transaction to the mempool.  If it already exists, then replace it if the given fee
-    /// is higher than the one that's already there.
+    /// is sufficiently higher than the one that's already there (or if the existing one is in a
+    /// different fork), and report the txid of the transaction that was replaced.
     /// Carry out the mempool admission test before adding.
     /// Don't call directly; use submit()
     fn try_add_tx<'a>(
@@ -789,7 +994,7 @@ impl MemPoolDB {
         origin_nonce: u64,
         sponsor_address: &StacksAddress,
         sponsor_nonce: u64,
-    ) -> Result<(), MemPoolRejection> {
+    ) -> Result<MemPoolAddResult, MemPoolRejection> {
         let length = tx_bytes.len() as u64;
 
         // do we already have txs with either the same origin nonce or sponsor nonce ?
@@ -806,8 +1011,8 @@ impl MemPoolDB {
         };
 
         // if so, is this a replace-by-fee? or a replace-in-chain-tip?
-        let add_tx = if let Some(prior_tx) = prior_tx {
-            if estimated_fee > prior_tx.estimated_fee {
+        let add_tx = if let Some(ref prior_tx) = prior_tx {
+            if tx.is_sufficient_fee_bump(prior_tx.estimated_fee, estimated_fee) {
                 // is this a replace-by-fee ?
                 true
             } else if !tx.is_block_in_fork(
@@ -819,8 +1024,8 @@ impl MemPoolDB {
                 // is this a replace-across-fork ?
                 true
             } else {
-                // there's a >= fee tx in this fork, cannot add
-                info!("TX conflicts with sponsor/origin nonce in same fork with >= fee: new_txid={}, old_txid={}, origin_addr={}, origin_nonce={}, sponsor_addr={}, sponsor_nonce={}, new_fee={}, old_fee={}",
+                // there's a tx in this fork that this one does not sufficiently out-bid, cannot add
+                info!("TX conflicts with sponsor/origin nonce in same fork without a sufficient fee bump: new_txid={}, old_txid={}, origin_addr={}, origin_nonce={}, sponsor_addr={}, sponsor_nonce={}, new_fee={}, old_fee={}",
                       txid, prior_tx.txid, origin_address, origin_nonce, sponsor_address, sponsor_nonce, estimated_fee, prior_tx.estimated_fee);
                 false
             }
@@ -833,6 +1038,29 @@ impl MemPoolDB {
             return Err(MemPoolRejection::ConflictingNonceInMempool);
         }
 
+        // if the mempool is full, make room by evicting lower-fee-rate txs
+        let evicted = MemPoolDB::find_evictions(
+            tx,
+            prior_tx.as_ref(),
+            origin_address,
+            origin_nonce,
+            sponsor_address,
+            sponsor_nonce,
+            fee_rate,
+            length,
+        )?;
+        for evicted_tx in evicted.iter() {
+            debug!(
+                "Evict tx {} (fee rate {}) to make room for {} (fee rate {})",
+                &evicted_tx.txid, evicted_tx.fee_rate, &txid, fee_rate
+            );
+            tx.execute(
+                "DELETE FROM mempool WHERE txid = ?1",
+                &[&evicted_tx.txid as &dyn ToSql],
+            )
+            .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+        }
+
         let sql = "INSERT OR REPLACE INTO mempool (
             txid,
             origin_address,
@@ -867,7 +1095,169 @@ impl MemPoolDB {
 
         tx.execute(sql, args)
             .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
-        Ok(())
+
+        // the new tx's origin and sponsor nonces are unique, so the prior tx was removed
+        match prior_tx {
+            Some(prior_tx) => {
+                debug!("Replaced tx {} with {}", &prior_tx.txid, &txid);
+                Ok(MemPoolAddResult::Replaced(prior_tx.txid))
+            }
+            None => Ok(MemPoolAddResult::Added),
+        }
+    }
+
+    /// Find the transactions to evict so that a new transaction of `length` bytes fits within the
+    /// mempool's `max_txs` and `max_bytes` limits.  Only transactions with a strictly lower fee
+    /// rate than `fee_rate` are evicted, lowest fee rate (then oldest) first.  If evicting all of
+    /// them would still not make enough room, the new transaction is rejected.
+    /// A transaction is only evicted once every higher-nonce transaction of its origin and of its
+    /// sponsor has been, so that an eviction never strands either account's later transactions
+    /// behind a nonce gap (and the new transaction is never stranded behind the evictions of its
+    /// own origin's or sponsor's transactions).
+    /// `replaced_tx` is the pending transaction the new one replaces, if any; it is removed
+    /// anyway, so it does not count against the limits.
+    fn find_evictions<'a>(
+        tx: &mut MemPoolTx<'a>,
+        replaced_tx: Option<&MemPoolTxMetadata>,
+        origin_address: &StacksAddress,
+        origin_nonce: u64,
+        sponsor_address: &StacksAddress,
+        sponsor_nonce: u64,
+        fee_rate: u64,
+        length: u64,
+    ) -> Result<Vec<EvictionCandidate>, MemPoolRejection> {
+        if tx.max_txs.is_none() && tx.max_bytes.is_none() {
+            return Ok(vec![]);
+        }
+
+        let (mut num_txs, mut num_bytes) = tx.query_row_and_then(
+            "SELECT COUNT(*) AS num_txs, IFNULL(SUM(length), 0) AS num_bytes FROM mempool",
+            NO_PARAMS,
+            |row| -> Result<(u64, u64), db_error> {
+                Ok((
+                    u64::from_column(row, "num_txs")?,
+                    u64::from_column(row, "num_bytes")?,
+                ))
+            },
+        )?;
+
+        if let Some(replaced_tx) = replaced_tx {
+            num_txs = num_txs.saturating_sub(1);
+            num_bytes = num_bytes.saturating_sub(replaced_tx.len);
+        }
+
+        let max_txs = tx.max_txs.unwrap_or(u64::max_value());
+        let max_bytes = tx.max_bytes.unwrap_or(u64::max_value());
+        let is_full = |num_txs: u64, num_bytes: u64| {
+            num_txs.saturating_add(1) > max_txs || num_bytes.saturating_add(length) > max_bytes
+        };
+
+        if !is_full(num_txs, num_bytes) {
+            return Ok(vec![]);
+        }
+
+        // every pending tx that uses the nonces of an account that one of the cheaper txs uses
+        let sql = "SELECT txid, origin_address, origin_nonce, sponsor_address, sponsor_nonce,
+                fee_rate, accept_time, length
+            FROM mempool
+            WHERE origin_address IN (SELECT origin_address FROM mempool WHERE fee_rate < ?1
+                    UNION SELECT sponsor_address FROM mempool WHERE fee_rate < ?1)
+                OR sponsor_address IN (SELECT origin_address FROM mempool WHERE fee_rate < ?1
+                    UNION SELECT sponsor_address FROM mempool WHERE fee_rate < ?1)";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(fee_rate)?];
+        let candidates: Vec<EvictionCandidate> = query_rows::<EvictionCandidate, _>(tx, sql, args)?
+            .into_iter()
+            .filter(|candidate| match replaced_tx {
+                Some(replaced_tx) => replaced_tx.txid != candidate.txid,
+                None => true,
+            })
+            .collect();
+
+        // each account's pending txs, as (nonce, candidate index), in nonce order
+        let mut account_txs: HashMap<StacksAddress, Vec<(u64, usize)>> = HashMap::new();
+        for (i, candidate) in candidates.iter().enumerate() {
+            for (address, nonce) in candidate.nonces() {
+                account_txs
+                    .entry(address.clone())
+                    .or_insert_with(Vec::new)
+                    .push((nonce, i));
+            }
+        }
+        for txs in account_txs.values_mut() {
+            txs.sort();
+        }
+
+        // a tx can be evicted once it holds the highest pending nonce of each account it uses
+        let is_evictable = |i: usize, account_txs: &HashMap<StacksAddress, Vec<(u64, usize)>>| {
+            let candidate = &candidates[i];
+            candidate.fee_rate < fee_rate
+                && candidate.nonces().iter().all(|(address, nonce)| {
+                    !(*address == origin_address && *nonce < origin_nonce)
+                        && !(*address == sponsor_address && *nonce < sponsor_nonce)
+                        && account_txs
+                            .get(*address)
+                            .and_then(|txs| txs.last())
+                            .map(|(_, last)| *last == i)
+                            .unwrap_or(false)
+                })
+        };
+
+        // evict the cheapest of the evictable transactions, one at a time
+        let mut next_evictions = BinaryHeap::new();
+        let mut queued = vec![false; candidates.len()];
+        for txs in account_txs.values() {
+            if let Some((_, i)) = txs.last() {
+                if !queued[*i] && is_evictable(*i, &account_txs) {
+                    queued[*i] = true;
+                    let candidate = &candidates[*i];
+                    next_evictions.push(Reverse((candidate.fee_rate, candidate.accept_time, *i)));
+                }
+            }
+        }
+
+        let mut evicted = vec![];
+        while is_full(num_txs, num_bytes) {
+            let i = match next_evictions.pop() {
+                Some(Reverse((_, _, i))) => i,
+                None => break,
+            };
+            let candidate = &candidates[i];
+            num_txs = num_txs.saturating_sub(1);
+            num_bytes = num_bytes.saturating_sub(candidate.len);
+            evicted.push(candidate.clone());
+
+            // the next-highest nonces of this tx's accounts may be evictable now
+            for (address, _) in candidate.nonces() {
+                let next = {
+                    let txs = account_txs
+                        .get_mut(address)
+                        .expect("BUG: no pending txs for evicted tx's account");
+                    txs.pop();
+                    txs.last().map(|(_, next)| *next)
+                };
+                if let Some(next) = next {
+                    if !queued[next] && is_evictable(next, &account_txs) {
+                        queued[next] = true;
+                        let next_candidate = &candidates[next];
+                        next_evictions.push(Reverse((
+                            next_candidate.fee_rate,
+                            next_candidate.accept_time,
+                            next,
+                        )));
+                    }
+                }
+            }
+        }
+
+        if is_full(num_txs, num_bytes) {
+            info!(
+                "Mempool is full and tx does not pay enough to evict other txs: fee_rate={}, length={}, max_txs={:?}, max_bytes={:?}",
+                fee_rate, length, tx.max_txs, tx.max_bytes
+            );
+            return Err(MemPoolRejection::MempoolFull);
+        }
+
+        Ok(evicted)
     }
 
     /// Garbage-collect the mempool.  Remove transactions that have a given number of
@@ -908,6 +1298,14 @@ impl MemPoolDB {
         .collect()
     }
 
+    /// Count an admission outcome in the mempool metrics
+    fn record_admission<T>(result: &Result<T, MemPoolRejection>) {
+        match result {
+            Ok(_) => monitoring::increment_mempool_accepted_counter(),
+            Err(e) => monitoring::increment_mempool_rejected_counter(e.reason_code()),
+        }
+    }
+
     /// Submit a transaction to the mempool at a particular chain tip.
     pub fn tx_submit(
         mempool_tx: &mut MemPoolTx,
@@ -915,7 +1313,25 @@ impl MemPoolDB {
         block_hash: &BlockHeaderHash,
         tx: StacksTransaction,
         do_admission_checks: bool,
-    ) -> Result<(), MemPoolRejection> {
+    ) -> Result<MemPoolAddResult, MemPoolRejection> {
+        let result = MemPoolDB::tx_admit(
+            mempool_tx,
+            consensus_hash,
+            block_hash,
+            tx,
+            do_admission_checks,
+        );
+        MemPoolDB::record_admission(&result);
+        result
+    }
+
+    fn tx_admit(
+        mempool_tx: &mut MemPoolTx,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        tx: StacksTransaction,
+        do_admission_checks: bool,
+    ) -> Result<MemPoolAddResult, MemPoolRejection> {
         test_debug!(
             "Mempool submit {} at {}/{}",
             tx.txid(),
@@ -990,9 +1406,7 @@ impl MemPoolDB {
             origin_nonce,
             &sponsor_address,
             sponsor_nonce,
-        )?;
-
-        Ok(())
+        )
     }
 
     /// One-shot submit
@@ -1001,11 +1415,11 @@ impl MemPoolDB {
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
         tx: StacksTransaction,
-    ) -> Result<(), MemPoolRejection> {
+    ) -> Result<MemPoolAddResult, MemPoolRejection> {
         let mut mempool_tx = self.tx_begin().map_err(MemPoolRejection::DBError)?;
-        MemPoolDB::tx_submit(&mut mempool_tx, consensus_hash, block_hash, tx, true)?;
+        let result = MemPoolDB::tx_submit(&mut mempool_tx, consensus_hash, block_hash, tx, true)?;
         mempool_tx.commit().map_err(MemPoolRejection::DBError)?;
-        Ok(())
+        Ok(result)
     }
 
     /// Directly submit to the mempool, and don't do any admissions checks.
@@ -1014,14 +1428,17 @@ impl MemPoolDB {
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
         tx_bytes: Vec<u8>,
-    ) -> Result<(), MemPoolRejection> {
-        let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..])
-            .map_err(MemPoolRejection::DeserializationFailure)?;
+    ) -> Result<MemPoolAddResult, MemPoolRejection> {
+        let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).map_err(|e| {
+            let rejection = MemPoolRejection::DeserializationFailure(e);
+            monitoring::increment_mempool_rejected_counter(rejection.reason_code());
+            rejection
+        })?;
 
         let mut mempool_tx = self.tx_begin().map_err(MemPoolRejection::DBError)?;
-        MemPoolDB::tx_submit(&mut mempool_tx, consensus_hash, block_hash, tx, false)?;
+        let result = MemPoolDB::tx_submit(&mut mempool_tx, consensus_hash, block_hash, tx, false)?;
         mempool_tx.commit().map_err(MemPoolRejection::DBError)?;
-        Ok(())
+        Ok(result)
     }
 
     /// Do we have a transaction?
@@ -1039,6 +1456,68 @@ impl MemPoolDB {
             }
         }
     }
+
+    /// Get the lowest height at which a transaction can still be mined.  Transactions below this
+    /// height would be garbage-collected once the chain tip advanced to the highest height we know
+    /// of, so they are not counted as pending.
+    fn get_min_admissible_height(conn: &DBConn) -> Result<u64, db_error> {
+        let sql = "SELECT height FROM mempool ORDER BY height DESC LIMIT 1";
+        let tip_height: Option<u64> = query_row(conn, sql, NO_PARAMS)?;
+        Ok(tip_height
+            .unwrap_or(0)
+            .saturating_sub(MEMPOOL_MAX_TRANSACTION_AGE))
+    }
+
+    fn get_admissible_fee_rates(conn: &DBConn) -> Result<Vec<u64>, db_error> {
+        let min_height = MemPoolDB::get_min_admissible_height(conn)?;
+        let sql = "SELECT fee_rate FROM mempool WHERE height >= ?1";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(min_height)?];
+        query_rows::<u64, _>(conn, sql, args)
+    }
+
+    fn count_admissible_txs(conn: &DBConn) -> Result<u64, db_error> {
+        let min_height = MemPoolDB::get_min_admissible_height(conn)?;
+        let sql = "SELECT COUNT(*) FROM mempool WHERE height >= ?1".to_string();
+        let args: &[&dyn ToSql] = &[&u64_to_sql(min_height)?];
+        let count = query_count(conn, &sql, args)?;
+        Ok(count as u64)
+    }
+
+    /// How many pending transactions are in the mempool?
+    pub fn len(&self) -> usize {
+        match MemPoolDB::count_admissible_txs(self.conn()) {
+            Ok(count) => count as usize,
+            Err(e) => {
+                warn!("Failed to query mempool size: {:?}", &e);
+                0
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count the pending transactions in each fee-rate bucket.  `buckets` holds the ascending lower
+    /// bound of each bucket; bucket i covers fee rates in [buckets[i], buckets[i+1]), and the last
+    /// bucket has no upper bound.  Transactions with a fee rate below buckets[0] are not counted.
+    pub fn fee_rate_histogram(&self, buckets: &[u64]) -> Vec<usize> {
+        let mut histogram = vec![0; buckets.len()];
+        let fee_rates = match MemPoolDB::get_admissible_fee_rates(self.conn()) {
+            Ok(fee_rates) => fee_rates,
+            Err(e) => {
+                warn!("Failed to query mempool fee rates: {:?}", &e);
+                return histogram;
+            }
+        };
+
+        for fee_rate in fee_rates.into_iter() {
+            if let Some(idx) = buckets.iter().rposition(|lower| *lower <= fee_rate) {
+                histogram[idx] += 1;
+            }
+        }
+        histogram
+    }
 }
 
 #[cfg(test)]
@@ -1068,12 +1547,14 @@ mod tests {
         C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
     };
 
-    use super::MemPoolDB;
+    use super::{MemPoolAddResult, MemPoolDB, MemPoolTx, MEMPOOL_MAX_TRANSACTION_AGE};
     use util::db::{DBConn, FromRow};
 
     use chainstate::burn::ConsensusHash;
     use chainstate::stacks::db::test::chainstate_path;
     use chainstate::stacks::db::test::instantiate_chainstate;
+    use chainstate::stacks::db::test::instantiate_chainstate_with_balances;
+    use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
     use chainstate::stacks::test::codec_all_transactions;
 
     const FOO_CONTRACT: &'static str = "(define-public (foo) (ok 1))
@@ -1248,6 +1729,104 @@ mod tests {
         assert!(!MemPoolDB::db_has_tx(&mempool_tx, &txid).unwrap());
     }
 
+    #[test]
+    fn mempool_replace_by_fee() {
+        let _chainstate = instantiate_chainstate(false, 0x80000000, "mempool_replace_by_fee");
+        let chainstate_path = chainstate_path("mempool_replace_by_fee");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        // replacements must pay at least 10% more
+        mempool.set_replace_fee_bump_pct(10);
+
+        let mut txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+        let mut tx = txs.pop().unwrap();
+
+        let consensus_hash = ConsensusHash([0x1; 20]);
+        let block_hash = BlockHeaderHash([0x2; 32]);
+        let origin_address = StacksAddress {
+            version: 22,
+            bytes: Hash160::from_data(&[0; 32]),
+        };
+        let sponsor_address = StacksAddress {
+            version: 22,
+            bytes: Hash160::from_data(&[1; 32]),
+        };
+        let height = 100;
+
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        let try_add = |mempool_tx: &mut MemPoolTx, tx: &StacksTransaction| {
+            let tx_bytes = tx.serialize_to_vec();
+            let estimated_fee = tx.get_fee_rate() * (tx_bytes.len() as u64);
+            MemPoolDB::try_add_tx(
+                mempool_tx,
+                &consensus_hash,
+                &block_hash,
+                tx.txid(),
+                tx_bytes,
+                estimated_fee,
+                tx.get_fee_rate(),
+                height,
+                &origin_address,
+                0,
+                &sponsor_address,
+                0,
+            )
+        };
+
+        // initial insert
+        tx.set_fee_rate(100);
+        let first_txid = tx.txid();
+        assert_eq!(
+            try_add(&mut mempool_tx, &tx).unwrap(),
+            MemPoolAddResult::Added
+        );
+
+        // same-fee resubmission is rejected
+        match try_add(&mut mempool_tx, &tx).unwrap_err() {
+            MemPoolRejection::ConflictingNonceInMempool => {}
+            e => panic!("Unexpected rejection: {:?}", &e),
+        }
+
+        // a higher fee that isn't a big enough bump is rejected
+        tx.set_fee_rate(105);
+        let low_bump_txid = tx.txid();
+        match try_add(&mut mempool_tx, &tx).unwrap_err() {
+            MemPoolRejection::ConflictingNonceInMempool => {}
+            e => panic!("Unexpected rejection: {:?}", &e),
+        }
+
+        // a lower fee is rejected
+        tx.set_fee_rate(99);
+        let lower_fee_txid = tx.txid();
+        match try_add(&mut mempool_tx, &tx).unwrap_err() {
+            MemPoolRejection::ConflictingNonceInMempool => {}
+            e => panic!("Unexpected rejection: {:?}", &e),
+        }
+
+        assert!(MemPoolDB::db_has_tx(&mempool_tx, &first_txid).unwrap());
+        assert!(!MemPoolDB::db_has_tx(&mempool_tx, &low_bump_txid).unwrap());
+        assert!(!MemPoolDB::db_has_tx(&mempool_tx, &lower_fee_txid).unwrap());
+
+        // a sufficient bump replaces the pending tx
+        tx.set_fee_rate(110);
+        let bumped_txid = tx.txid();
+        assert_eq!(
+            try_add(&mut mempool_tx, &tx).unwrap(),
+            MemPoolAddResult::Replaced(first_txid.clone())
+        );
+
+        assert!(!MemPoolDB::db_has_tx(&mempool_tx, &first_txid).unwrap());
+        assert!(MemPoolDB::db_has_tx(&mempool_tx, &bumped_txid).unwrap());
+
+        mempool_tx.commit().unwrap();
+        assert_eq!(MemPoolDB::get_all_txs(mempool.conn()).unwrap().len(), 1);
+    }
+
     #[test]
     fn mempool_db_load_store_replace_tx() {
         let _chainstate =
@@ -1496,4 +2075,506 @@ mod tests {
         .unwrap();
         assert_eq!(txs.len(), 0);
     }
+
+    #[test]
+    fn mempool_fee_rate_histogram() {
+        let _chainstate = instantiate_chainstate(false, 0x80000000, "mempool_fee_rate_histogram");
+        let chainstate_path = chainstate_path("mempool_fee_rate_histogram");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        assert_eq!(mempool.len(), 0);
+        assert_eq!(mempool.fee_rate_histogram(&[0, 10, 100]), vec![0, 0, 0]);
+
+        let mut txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+
+        let tip_height = MEMPOOL_MAX_TRANSACTION_AGE + 100;
+
+        // (fee rate, height).  The last transaction is too old to be mined off of the tip.
+        let fee_rates_and_heights = vec![
+            (1, tip_height),
+            (5, tip_height),
+            (10, tip_height),
+            (50, tip_height - 1),
+            (99, tip_height - MEMPOOL_MAX_TRANSACTION_AGE),
+            (100, tip_height),
+            (1000, tip_height),
+            (5000, tip_height - MEMPOOL_MAX_TRANSACTION_AGE - 1),
+        ];
+
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        for (i, (fee_rate, height)) in fee_rates_and_heights.into_iter().enumerate() {
+            let mut tx = txs.pop().unwrap();
+            let origin_address = StacksAddress {
+                version: 22,
+                bytes: Hash160::from_data(&(2 * i).to_be_bytes()),
+            };
+            let sponsor_address = StacksAddress {
+                version: 22,
+                bytes: Hash160::from_data(&(2 * i + 1).to_be_bytes()),
+            };
+
+            tx.set_fee_rate(fee_rate);
+
+            let txid = tx.txid();
+            let tx_bytes = tx.serialize_to_vec();
+            let len = tx_bytes.len() as u64;
+            let estimated_fee = tx.get_fee_rate() * len;
+
+            MemPoolDB::try_add_tx(
+                &mut mempool_tx,
+                &ConsensusHash([0x1; 20]),
+                &BlockHeaderHash([0x2; 32]),
+                txid,
+                tx_bytes,
+                estimated_fee,
+                tx.get_fee_rate(),
+                height,
+                &origin_address,
+                0,
+                &sponsor_address,
+                0,
+            )
+            .unwrap();
+        }
+        mempool_tx.commit().unwrap();
+
+        assert_eq!(mempool.len(), 7);
+        assert!(!mempool.is_empty());
+
+        assert_eq!(mempool.fee_rate_histogram(&[0, 10, 100]), vec![2, 3, 2]);
+        assert_eq!(mempool.fee_rate_histogram(&[5, 50, 1000]), vec![2, 3, 1]);
+        assert_eq!(mempool.fee_rate_histogram(&[0]), vec![7]);
+        assert_eq!(mempool.fee_rate_histogram(&[2000]), vec![0]);
+        assert_eq!(mempool.fee_rate_histogram(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn mempool_size_limits() {
+        let _chainstate = instantiate_chainstate(false, 0x80000000, "mempool_size_limits");
+        let chainstate_path = chainstate_path("mempool_size_limits");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+        let template_tx = txs[0].clone();
+
+        // add a copy of the template tx with the given fee rate, from its own origin address
+        let mut next_addr = 0u64;
+        let mut add_tx = |mempool: &mut MemPoolDB, fee_rate: u64| {
+            let mut tx = template_tx.clone();
+            tx.set_fee_rate(fee_rate);
+            let origin_address = StacksAddress {
+                version: 22,
+                bytes: Hash160::from_data(&next_addr.to_be_bytes()),
+            };
+            next_addr += 1;
+
+            let txid = tx.txid();
+            let tx_bytes = tx.serialize_to_vec();
+            let len = tx_bytes.len() as u64;
+
+            let mut mempool_tx = mempool.tx_begin().unwrap();
+            let result = MemPoolDB::try_add_tx(
+                &mut mempool_tx,
+                &ConsensusHash([0x1; 20]),
+                &BlockHeaderHash([0x2; 32]),
+                txid,
+                tx_bytes,
+                fee_rate * len,
+                fee_rate,
+                1,
+                &origin_address,
+                0,
+                &origin_address,
+                0,
+            );
+            mempool_tx.commit().unwrap();
+            (result, len)
+        };
+
+        let pending_fee_rates = |mempool: &MemPoolDB| {
+            let mut fee_rates: Vec<u64> = MemPoolDB::get_all_txs(mempool.conn())
+                .unwrap()
+                .into_iter()
+                .map(|tx_info| tx_info.metadata.fee_rate)
+                .collect();
+            fee_rates.sort();
+            fee_rates
+        };
+
+        mempool.set_size_limits(Some(3), None);
+        let mut tx_len = 0;
+        for fee_rate in [10, 20, 30].iter() {
+            let (result, len) = add_tx(&mut mempool, *fee_rate);
+            assert_eq!(result.unwrap(), MemPoolAddResult::Added);
+            tx_len = len;
+        }
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 20, 30]);
+
+        // full, and this tx pays less than everything in the mempool
+        match add_tx(&mut mempool, 5).0.unwrap_err() {
+            e @ MemPoolRejection::MempoolFull => assert_eq!(e.reason_code(), "MempoolFull"),
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 20, 30]);
+
+        // full, but this tx out-bids the cheapest tx
+        let (result, _) = add_tx(&mut mempool, 50);
+        assert_eq!(result.unwrap(), MemPoolAddResult::Added);
+        assert_eq!(pending_fee_rates(&mempool), vec![20, 30, 50]);
+
+        // over the byte limit, so two txs must go to make room for this one
+        mempool.set_size_limits(None, Some(2 * tx_len));
+        let (result, _) = add_tx(&mut mempool, 40);
+        assert_eq!(result.unwrap(), MemPoolAddResult::Added);
+        assert_eq!(pending_fee_rates(&mempool), vec![40, 50]);
+
+        // a tx that can only make room by evicting higher-fee-rate txs is rejected
+        match add_tx(&mut mempool, 35).0.unwrap_err() {
+            MemPoolRejection::MempoolFull => {}
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+        assert_eq!(pending_fee_rates(&mempool), vec![40, 50]);
+
+        // no limits
+        mempool.set_size_limits(None, None);
+        let (result, _) = add_tx(&mut mempool, 1);
+        assert_eq!(result.unwrap(), MemPoolAddResult::Added);
+        assert_eq!(pending_fee_rates(&mempool), vec![1, 40, 50]);
+    }
+
+    #[test]
+    fn mempool_evicts_highest_nonce_first() {
+        let _chainstate = instantiate_chainstate(false, 0x80000000, "mempool_evict_nonce_order");
+        let chainstate_path = chainstate_path("mempool_evict_nonce_order");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+        let template_tx = txs[0].clone();
+
+        let add_tx = |mempool: &mut MemPoolDB, origin: u64, nonce: u64, fee_rate: u64| {
+            let mut tx = template_tx.clone();
+            tx.set_fee_rate(fee_rate);
+            tx.set_origin_nonce(nonce);
+            let origin_address = StacksAddress {
+                version: 22,
+                bytes: Hash160::from_data(&origin.to_be_bytes()),
+            };
+
+            let txid = tx.txid();
+            let tx_bytes = tx.serialize_to_vec();
+            let len = tx_bytes.len() as u64;
+
+            let mut mempool_tx = mempool.tx_begin().unwrap();
+            let result = MemPoolDB::try_add_tx(
+                &mut mempool_tx,
+                &ConsensusHash([0x1; 20]),
+                &BlockHeaderHash([0x2; 32]),
+                txid,
+                tx_bytes,
+                fee_rate * len,
+                fee_rate,
+                1,
+                &origin_address,
+                nonce,
+                &origin_address,
+                nonce,
+            );
+            mempool_tx.commit().unwrap();
+            result
+        };
+
+        // every tx in this test has its own fee rate
+        let pending_fee_rates = |mempool: &MemPoolDB| {
+            let mut fee_rates: Vec<u64> = MemPoolDB::get_all_txs(mempool.conn())
+                .unwrap()
+                .into_iter()
+                .map(|tx_info| tx_info.metadata.fee_rate)
+                .collect();
+            fee_rates.sort();
+            fee_rates
+        };
+
+        mempool.set_size_limits(Some(3), None);
+
+        // origin 0's cheapest tx comes before a more expensive one
+        add_tx(&mut mempool, 0, 0, 10).unwrap();
+        add_tx(&mut mempool, 0, 1, 30).unwrap();
+        add_tx(&mut mempool, 1, 0, 20).unwrap();
+
+        // evicting origin 0's nonce 0 would strand its nonce 1, which this tx can't out-bid
+        add_tx(&mut mempool, 2, 0, 25).unwrap();
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 25, 30]);
+
+        // two txs must go to make room for this one, and origin 0's nonce 1 goes before its
+        // cheaper nonce 0
+        mempool.set_size_limits(Some(2), None);
+        add_tx(&mut mempool, 3, 0, 40).unwrap();
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 40]);
+
+        // a tx may not evict its own origin's lower-nonce txs
+        match add_tx(&mut mempool, 0, 1, 35).unwrap_err() {
+            MemPoolRejection::MempoolFull => {}
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 40]);
+    }
+
+    #[test]
+    fn mempool_evicts_highest_sponsor_nonce_first() {
+        let _chainstate =
+            instantiate_chainstate(false, 0x80000000, "mempool_evict_sponsor_nonce_order");
+        let chainstate_path = chainstate_path("mempool_evict_sponsor_nonce_order");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+        let template_tx = txs[0].clone();
+
+        let make_addr = |id: u64| StacksAddress {
+            version: 22,
+            bytes: Hash160::from_data(&id.to_be_bytes()),
+        };
+
+        // add a tx from its own origin at nonce 0, sponsored by `sponsor` at `sponsor_nonce` if
+        // given
+        let add_tx =
+            |mempool: &mut MemPoolDB, origin: u64, sponsor: Option<(u64, u64)>, fee_rate: u64| {
+                let mut tx = template_tx.clone();
+                tx.set_fee_rate(fee_rate);
+                let origin_address = make_addr(origin);
+                let (sponsor_address, sponsor_nonce) = match sponsor {
+                    Some((sponsor, sponsor_nonce)) => (make_addr(sponsor), sponsor_nonce),
+                    None => (origin_address.clone(), 0),
+                };
+
+                let txid = tx.txid();
+                let tx_bytes = tx.serialize_to_vec();
+                let len = tx_bytes.len() as u64;
+
+                let mut mempool_tx = mempool.tx_begin().unwrap();
+                let result = MemPoolDB::try_add_tx(
+                    &mut mempool_tx,
+                    &ConsensusHash([0x1; 20]),
+                    &BlockHeaderHash([0x2; 32]),
+                    txid,
+                    tx_bytes,
+                    fee_rate * len,
+                    fee_rate,
+                    1,
+                    &origin_address,
+                    0,
+                    &sponsor_address,
+                    sponsor_nonce,
+                );
+                mempool_tx.commit().unwrap();
+                result
+            };
+
+        // every tx in this test has its own fee rate
+        let pending_fee_rates = |mempool: &MemPoolDB| {
+            let mut fee_rates: Vec<u64> = MemPoolDB::get_all_txs(mempool.conn())
+                .unwrap()
+                .into_iter()
+                .map(|tx_info| tx_info.metadata.fee_rate)
+                .collect();
+            fee_rates.sort();
+            fee_rates
+        };
+
+        mempool.set_size_limits(Some(3), None);
+
+        // sponsor 100's cheapest tx comes before a more expensive one, and the two txs have
+        // different origins
+        add_tx(&mut mempool, 0, Some((100, 0)), 10).unwrap();
+        add_tx(&mut mempool, 1, Some((100, 1)), 30).unwrap();
+        add_tx(&mut mempool, 2, None, 20).unwrap();
+
+        // evicting sponsor 100's nonce 0 would strand its nonce 1, which this tx can't out-bid
+        add_tx(&mut mempool, 3, None, 25).unwrap();
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 25, 30]);
+
+        // two txs must go to make room for this one, and sponsor 100's nonce 1 goes before its
+        // cheaper nonce 0
+        mempool.set_size_limits(Some(2), None);
+        add_tx(&mut mempool, 4, None, 40).unwrap();
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 40]);
+
+        // a tx may not evict its own sponsor's lower-nonce txs
+        match add_tx(&mut mempool, 5, Some((100, 1)), 35).unwrap_err() {
+            MemPoolRejection::MempoolFull => {}
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+        assert_eq!(pending_fee_rates(&mempool), vec![10, 40]);
+    }
+
+    fn make_stx_transfer(
+        sk: &StacksPrivateKey,
+        nonce: u64,
+        fee: u64,
+        amount: u64,
+    ) -> StacksTransaction {
+        let recipient = StacksAddress {
+            version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            bytes: Hash160([0x11; 20]),
+        };
+        let mut tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(sk).unwrap(),
+            TransactionPayload::TokenTransfer(recipient.into(), amount, TokenTransferMemo([0; 34])),
+        );
+        tx.chain_id = 0x80000000;
+        tx.set_origin_nonce(nonce);
+        tx.set_fee_rate(fee);
+
+        let mut signer = StacksTransactionSigner::new(&tx);
+        signer.sign_origin(sk).unwrap();
+        signer.get_tx().unwrap()
+    }
+
+    #[test]
+    fn mempool_submit_rejection_reasons() {
+        let sk = StacksPrivateKey::from_hex(SK_1).unwrap();
+        let addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&sk)],
+        )
+        .unwrap();
+
+        let _chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_submit_rejection_reasons",
+            vec![(addr.clone(), 1_000_000)],
+        );
+        let chainstate_path = chainstate_path("mempool_submit_rejection_reasons");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let ch = FIRST_BURNCHAIN_CONSENSUS_HASH;
+        let bhh = FIRST_STACKS_BLOCK_HASH;
+
+        match mempool.submit_raw(&ch, &bhh, vec![0xff, 0xff]).unwrap_err() {
+            e @ MemPoolRejection::DeserializationFailure(_) => {
+                assert_eq!(e.reason_code(), "Deserialization")
+            }
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+
+        let tx = make_stx_transfer(&sk, 0, 0, 100);
+        match mempool.submit(&ch, &bhh, tx).unwrap_err() {
+            e @ MemPoolRejection::FeeTooLow(0, _) => assert_eq!(e.reason_code(), "FeeTooLow"),
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+
+        let tx = make_stx_transfer(&sk, 1, 1000, 100);
+        match mempool.submit(&ch, &bhh, tx).unwrap_err() {
+            e @ MemPoolRejection::BadNonces(_) => assert_eq!(e.reason_code(), "BadNonce"),
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+
+        let tx = make_stx_transfer(&sk, 0, 1000, 10_000_000);
+        match mempool.submit(&ch, &bhh, tx).unwrap_err() {
+            e @ MemPoolRejection::NotEnoughFunds(..) => {
+                assert_eq!(e.reason_code(), "NotEnoughFunds")
+            }
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+
+        let mut tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&sk).unwrap(),
+            TransactionPayload::new_contract_call(addr.clone(), "no-such-contract", "foo", vec![])
+                .unwrap(),
+        );
+        tx.chain_id = 0x80000000;
+        tx.set_fee_rate(1000);
+        let mut signer = StacksTransactionSigner::new(&tx);
+        signer.sign_origin(&sk).unwrap();
+        let tx = signer.get_tx().unwrap();
+        match mempool.submit(&ch, &bhh, tx).unwrap_err() {
+            e @ MemPoolRejection::NoSuchContract => assert_eq!(e.reason_code(), "NoSuchContract"),
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+
+        // a valid transaction is admitted...
+        let tx = make_stx_transfer(&sk, 0, 1000, 100);
+        mempool.submit(&ch, &bhh, tx).unwrap();
+
+        // ...but can't be replaced by one with the same nonce and a lower fee
+        let tx = make_stx_transfer(&sk, 0, 999, 200);
+        match mempool.submit(&ch, &bhh, tx).unwrap_err() {
+            e @ MemPoolRejection::ConflictingNonceInMempool => {
+                assert_eq!(e.reason_code(), "ConflictingNonceInMempool")
+            }
+            e => panic!("Unexpected rejection {:?}", e),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "monitoring_prom")]
+    fn mempool_admission_metrics() {
+        use monitoring::{get_mempool_accepted_count, get_mempool_rejected_count};
+
+        let sk = StacksPrivateKey::from_hex(SK_2).unwrap();
+        let addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&sk)],
+        )
+        .unwrap();
+
+        let _chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_admission_metrics",
+            vec![(addr.clone(), 1_000_000)],
+        );
+        let chainstate_path = chainstate_path("mempool_admission_metrics");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let ch = FIRST_BURNCHAIN_CONSENSUS_HASH;
+        let bhh = FIRST_STACKS_BLOCK_HASH;
+
+        // the counters are process-wide, and other tests may bump them concurrently
+        let accepted = get_mempool_accepted_count();
+        let deserialization = get_mempool_rejected_count("Deserialization");
+        let fee_too_low = get_mempool_rejected_count("FeeTooLow");
+        let bad_nonce = get_mempool_rejected_count("BadNonce");
+
+        mempool.submit_raw(&ch, &bhh, vec![0xff, 0xff]).unwrap_err();
+        assert!(get_mempool_rejected_count("Deserialization") >= deserialization + 1);
+
+        let tx = make_stx_transfer(&sk, 0, 0, 100);
+        mempool.submit(&ch, &bhh, tx).unwrap_err();
+        assert!(get_mempool_rejected_count("FeeTooLow") >= fee_too_low + 1);
+
+        let tx = make_stx_transfer(&sk, 1, 1000, 100);
+        mempool.submit(&ch, &bhh, tx).unwrap_err();
+        assert!(get_mempool_rejected_count("BadNonce") >= bad_nonce + 1);
+
+        let tx = make_stx_transfer(&sk, 0, 1000, 100);
+        mempool.submit(&ch, &bhh, tx).unwrap();
+        assert!(get_mempool_accepted_count() >= accepted + 1);
+    }
 }
@@ -101,6 +101,32 @@ pub fn increment_errors_emitted_counter() {
     prometheus::ERRORS_EMITTED_COUNTER.inc();
 }
 
+pub fn increment_mempool_accepted_counter() {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MEMPOOL_ACCEPTED_COUNTER.inc();
+}
+
+/// `reason` is the rejection's `MemPoolRejection::reason_code()`
+#[allow(unused_variables)]
+pub fn increment_mempool_rejected_counter(reason: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MEMPOOL_REJECTED_COUNTER
+        .with_label_values(&[reason])
+        .inc();
+}
+
+#[cfg(feature = "monitoring_prom")]
+pub fn get_mempool_accepted_count() -> u64 {
+    prometheus::MEMPOOL_ACCEPTED_COUNTER.get()
+}
+
+#[cfg(feature = "monitoring_prom")]
+pub fn get_mempool_rejected_count(reason: &str) -> u64 {
+    prometheus::MEMPOOL_REJECTED_COUNTER
+        .with_label_values(&[reason])
+        .get()
+}
+
 #[allow(unused_variables)]
 pub fn update_active_miners_count_gauge(value: i64) {
     #[cfg(feature = "monitoring_prom")]
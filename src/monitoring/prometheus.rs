@@ -1,4 +1,4 @@
-use prometheus::{IntCounter, IntGauge};
+use prometheus::{IntCounter, IntCounterVec, IntGauge};
 
 lazy_static! {
     pub static ref RPC_CALL_COUNTER: IntCounter = register_int_counter!(opts!(
@@ -121,6 +121,18 @@ lazy_static! {
         labels! {"handler" => "all",}
     )).unwrap();
 
+    pub static ref MEMPOOL_ACCEPTED_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_mempool_accepted_total",
+        "Total number of transactions admitted to the mempool.",
+        labels! {"handler" => "all",}
+    )).unwrap();
+
+    pub static ref MEMPOOL_REJECTED_COUNTER: IntCounterVec = register_int_counter_vec!(opts!(
+        "stacks_mempool_rejected_total",
+        "Total number of transactions rejected by the mempool, by reason.",
+        labels! {"handler" => "all",}
+    ), &["reason"]).unwrap();
+
     pub static ref ACTIVE_MINERS_COUNT_GAUGE: IntGauge = register_int_gauge!(opts!(
         "stacks_node_active_miners_total",
         "Total number of active miners.",
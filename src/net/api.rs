@@ -0,0 +1,227 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! The JSON bodies of the RPC interface's responses.  Every field name is `snake_case`, as
+//! documented in docs/rpc-endpoints.md, and each /v2 response's schema is pinned by a test below
+//! so that new fields and endpoints can't drift from it.
+
+use chainstate::burn::BlockHeaderHash;
+use chainstate::burn::ConsensusHash;
+use chainstate::stacks::StacksBlockId;
+
+use serde::{Deserialize, Serialize};
+
+/// The data we return on GET /v2/info
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPeerInfoData {
+    pub peer_version: u32,
+    pub pox_consensus: ConsensusHash,
+    pub burn_block_height: u64,
+    pub stable_pox_consensus: ConsensusHash,
+    pub stable_burn_block_height: u64,
+    pub server_version: String,
+    pub network_id: u32,
+    pub parent_network_id: u32,
+    pub stacks_tip_height: u64,
+    pub stacks_tip: BlockHeaderHash,
+    pub stacks_tip_consensus_hash: String,
+    pub unanchored_tip: StacksBlockId,
+    pub exit_at_block_height: Option<u64>,
+}
+
+/// The data we return on GET /v2/pox
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPoxInfoData {
+    pub contract_id: String,
+    pub first_burnchain_block_height: u128,
+    pub min_amount_ustx: u128,
+    pub prepare_cycle_length: u128,
+    pub rejection_fraction: u128,
+    pub reward_cycle_id: u128,
+    pub reward_cycle_length: u128,
+    pub rejection_votes_left_required: u128,
+    pub total_liquid_supply_ustx: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapEntryResponse {
+    pub data: String,
+    #[serde(rename = "proof")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marf_proof: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractSrcResponse {
+    pub source: String,
+    pub publish_height: u32,
+    #[serde(rename = "proof")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marf_proof: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallReadOnlyResponse {
+    pub okay: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountEntryResponse {
+    pub balance: String,
+    pub locked: String,
+    pub unlock_height: u64,
+    pub nonce: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub balance_proof: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub nonce_proof: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde_json;
+    use std::fmt::Debug;
+
+    /// Check that `value` serializes to exactly `expected_json` (modulo whitespace), and that
+    /// `expected_json` deserializes back to `value`.
+    fn check_json_schema<T: Serialize + DeserializeOwned + PartialEq + Debug>(
+        value: &T,
+        expected_json: &str,
+    ) {
+        assert_eq!(
+            serde_json::to_value(value).unwrap(),
+            serde_json::from_str::<serde_json::Value>(expected_json).unwrap()
+        );
+        assert_eq!(&serde_json::from_str::<T>(expected_json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_rpc_peer_info_json_schema() {
+        let peer_info = RPCPeerInfoData {
+            peer_version: 0x18000000,
+            pox_consensus: ConsensusHash([0x11; 20]),
+            burn_block_height: 123,
+            stable_pox_consensus: ConsensusHash([0x11; 20]),
+            stable_burn_block_height: 116,
+            server_version: "blockstack-core 0.0.1".to_string(),
+            network_id: 0x80000000,
+            parent_network_id: 0x03,
+            stacks_tip_height: 45,
+            stacks_tip: BlockHeaderHash([0x22; 32]),
+            stacks_tip_consensus_hash: "1111111111111111111111111111111111111111".to_string(),
+            unanchored_tip: StacksBlockId([0x33; 32]),
+            exit_at_block_height: None,
+        };
+
+        let expected_json = r#"{
+            "peer_version": 402653184,
+            "pox_consensus": "1111111111111111111111111111111111111111",
+            "burn_block_height": 123,
+            "stable_pox_consensus": "1111111111111111111111111111111111111111",
+            "stable_burn_block_height": 116,
+            "server_version": "blockstack-core 0.0.1",
+            "network_id": 2147483648,
+            "parent_network_id": 3,
+            "stacks_tip_height": 45,
+            "stacks_tip": "2222222222222222222222222222222222222222222222222222222222222222",
+            "stacks_tip_consensus_hash": "1111111111111111111111111111111111111111",
+            "unanchored_tip": "3333333333333333333333333333333333333333333333333333333333333333",
+            "exit_at_block_height": null
+        }"#;
+
+        check_json_schema(&peer_info, expected_json);
+    }
+
+    #[test]
+    fn test_rpc_pox_info_json_schema() {
+        let pox_info = RPCPoxInfoData {
+            contract_id: "ST000000000000000000002AMW42H.pox".to_string(),
+            first_burnchain_block_height: 0,
+            min_amount_ustx: 20000000000,
+            prepare_cycle_length: 30,
+            rejection_fraction: 25,
+            reward_cycle_id: 3,
+            reward_cycle_length: 150,
+            rejection_votes_left_required: 12345,
+            total_liquid_supply_ustx: 1000000000000,
+        };
+
+        let expected_json = r#"{
+            "contract_id": "ST000000000000000000002AMW42H.pox",
+            "first_burnchain_block_height": 0,
+            "min_amount_ustx": 20000000000,
+            "prepare_cycle_length": 30,
+            "rejection_fraction": 25,
+            "reward_cycle_id": 3,
+            "reward_cycle_length": 150,
+            "rejection_votes_left_required": 12345,
+            "total_liquid_supply_ustx": 1000000000000
+        }"#;
+
+        check_json_schema(&pox_info, expected_json);
+    }
+
+    #[test]
+    fn test_rpc_account_entry_json_schema() {
+        let mut account = AccountEntryResponse {
+            balance: "0x0000000000000000000000000000000a".to_string(),
+            locked: "0x00000000000000000000000000000005".to_string(),
+            unlock_height: 100,
+            nonce: 1,
+            balance_proof: Some("0x01fa".to_string()),
+            nonce_proof: Some("0x01ab".to_string()),
+        };
+
+        let expected_json = r#"{
+            "balance": "0x0000000000000000000000000000000a",
+            "locked": "0x00000000000000000000000000000005",
+            "unlock_height": 100,
+            "nonce": 1,
+            "balance_proof": "0x01fa",
+            "nonce_proof": "0x01ab"
+        }"#;
+
+        check_json_schema(&account, expected_json);
+
+        // proofs are omitted entirely when not requested
+        account.balance_proof = None;
+        account.nonce_proof = None;
+
+        let expected_json_no_proofs = r#"{
+            "balance": "0x0000000000000000000000000000000a",
+            "locked": "0x00000000000000000000000000000005",
+            "unlock_height": 100,
+            "nonce": 1
+        }"#;
+
+        check_json_schema(&account, expected_json_no_proofs);
+    }
+}
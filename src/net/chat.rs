@@ -32,6 +32,7 @@ use net::*;
 
 use net::connection::ConnectionOptions;
 use net::connection::ConnectionP2P;
+use net::connection::NetworkClock;
 use net::connection::ReplyHandleP2P;
 use net::GetBlocksInv;
 use net::GetPoxInv;
@@ -71,7 +72,6 @@ use std::convert::TryFrom;
 use std::io::Read;
 use std::io::Write;
 
-use util::get_epoch_time_secs;
 use util::hash::to_hex;
 use util::log;
 
@@ -125,7 +125,7 @@ impl RelayStats {
         if other.last_seen > self.last_seen {
             self.num_messages += other.num_messages;
             self.num_bytes += other.num_bytes;
-            self.last_seen = get_epoch_time_secs();
+            self.last_seen = other.last_seen;
         }
     }
 }
@@ -150,10 +150,12 @@ pub struct NeighborStats {
     pub microblocks_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub transaction_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub relayed_messages: HashMap<NeighborAddress, RelayStats>,
+    /// clock used to timestamp health points, pushes and relayers
+    pub clock: NetworkClock,
 }
 
 impl NeighborStats {
-    pub fn new(outbound: bool) -> NeighborStats {
+    pub fn new(outbound: bool, clock: NetworkClock) -> NeighborStats {
         NeighborStats {
             outbound: outbound,
             first_contact_time: 0,
@@ -173,13 +175,14 @@ impl NeighborStats {
             microblocks_push_rx_counts: VecDeque::new(),
             transaction_push_rx_counts: VecDeque::new(),
             relayed_messages: HashMap::new(),
+            clock: clock,
         }
     }
 
     pub fn add_healthpoint(&mut self, success: bool) -> () {
         let hp = NeighborHealthPoint {
             success: success,
-            time: get_epoch_time_secs(),
+            time: self.clock.now_secs(),
         };
         self.healthpoints.push_back(hp);
         while self.healthpoints.len() > NUM_HEALTH_POINTS {
@@ -188,24 +191,26 @@ impl NeighborStats {
     }
 
     pub fn add_block_push(&mut self, message_size: u64) -> () {
-        self.block_push_rx_counts
-            .push_back((get_epoch_time_secs(), message_size));
+        let now = self.clock.now_secs();
+        self.block_push_rx_counts.push_back((now, message_size));
         while self.block_push_rx_counts.len() > NUM_BLOCK_POINTS {
             self.block_push_rx_counts.pop_front();
         }
     }
 
     pub fn add_microblocks_push(&mut self, message_size: u64) -> () {
+        let now = self.clock.now_secs();
         self.microblocks_push_rx_counts
-            .push_back((get_epoch_time_secs(), message_size));
+            .push_back((now, message_size));
         while self.microblocks_push_rx_counts.len() > NUM_BLOCK_POINTS {
             self.microblocks_push_rx_counts.pop_front();
         }
     }
 
     pub fn add_transaction_push(&mut self, message_size: u64) -> () {
+        let now = self.clock.now_secs();
         self.transaction_push_rx_counts
-            .push_back((get_epoch_time_secs(), message_size));
+            .push_back((now, message_size));
         while self.transaction_push_rx_counts.len() > NUM_BLOCK_POINTS {
             self.transaction_push_rx_counts.pop_front();
         }
@@ -215,12 +220,12 @@ impl NeighborStats {
         if let Some(stats) = self.relayed_messages.get_mut(&addr) {
             stats.num_messages += 1;
             stats.num_bytes += num_bytes;
-            stats.last_seen = get_epoch_time_secs();
+            stats.last_seen = self.clock.now_secs();
         } else {
             let info = RelayStats {
                 num_messages: 1,
                 num_bytes: num_bytes,
-                last_seen: get_epoch_time_secs(),
+                last_seen: self.clock.now_secs(),
             };
             self.relayed_messages.insert(addr, info);
         }
@@ -241,7 +246,7 @@ impl NeighborStats {
 
         let mut successful = 0;
         let mut total = 0;
-        let now = get_epoch_time_secs();
+        let now = self.clock.now_secs();
         for hp in self.healthpoints.iter() {
             // penalize stale data points -- only look at recent data
             if hp.success && now < hp.time + HEALTH_POINT_LIFETIME {
@@ -252,14 +257,13 @@ impl NeighborStats {
         (successful as f64) / (total as f64)
     }
 
-    fn get_bandwidth(rx_counts: &VecDeque<(u64, u64)>, lifetime: u64) -> f64 {
+    fn get_bandwidth(rx_counts: &VecDeque<(u64, u64)>, lifetime: u64, now: u64) -> f64 {
         if rx_counts.len() < 2 {
             return 0.0;
         }
 
         let elapsed_time_start = rx_counts.front().unwrap().0;
         let elapsed_time_end = rx_counts.back().unwrap().0;
-        let now = get_epoch_time_secs();
 
         let mut total_bytes = 0;
         for (time, size) in rx_counts.iter() {
@@ -277,17 +281,29 @@ impl NeighborStats {
 
     /// Get a peer's total block-push bandwidth usage.
     pub fn get_block_push_bandwidth(&self) -> f64 {
-        NeighborStats::get_bandwidth(&self.block_push_rx_counts, BLOCK_POINT_LIFETIME)
+        NeighborStats::get_bandwidth(
+            &self.block_push_rx_counts,
+            BLOCK_POINT_LIFETIME,
+            self.clock.now_secs(),
+        )
     }
 
     /// Get a peer's total microblock-push bandwidth usage.
     pub fn get_microblocks_push_bandwidth(&self) -> f64 {
-        NeighborStats::get_bandwidth(&self.microblocks_push_rx_counts, BLOCK_POINT_LIFETIME)
+        NeighborStats::get_bandwidth(
+            &self.microblocks_push_rx_counts,
+            BLOCK_POINT_LIFETIME,
+            self.clock.now_secs(),
+        )
     }
 
     /// Get a peer's total transaction-push bandwidth usage
     pub fn get_transaction_push_bandwidth(&self) -> f64 {
-        NeighborStats::get_bandwidth(&self.transaction_push_rx_counts, BLOCK_POINT_LIFETIME)
+        NeighborStats::get_bandwidth(
+            &self.transaction_push_rx_counts,
+            BLOCK_POINT_LIFETIME,
+            self.clock.now_secs(),
+        )
     }
 
     /// Determine how many of a particular message this peer has received
@@ -381,12 +397,13 @@ impl NeighborKey {
 }
 
 impl Neighbor {
-    /// Update fields in this neighbor from a given handshake.
+    /// Update fields in this neighbor from a given handshake, received at `now`.
     /// Also, re-calculate the peer's ASN and organization ID
     pub fn handshake_update(
         &mut self,
         conn: &DBConn,
         handshake_data: &HandshakeData,
+        now: u64,
     ) -> Result<(), net_error> {
         let pubk = handshake_data.node_public_key.to_public_key()?;
         let asn_opt =
@@ -399,7 +416,7 @@ impl Neighbor {
 
         self.public_key = pubk;
         self.expire_block = handshake_data.expire_block_height;
-        self.last_contact_time = get_epoch_time_secs();
+        self.last_contact_time = now;
 
         if asn != 0 {
             self.asn = asn;
@@ -414,6 +431,7 @@ impl Neighbor {
         peer_version: u32,
         network_id: u32,
         handshake_data: &HandshakeData,
+        now: u64,
     ) -> Result<Neighbor, net_error> {
         let addr = NeighborKey::from_handshake(peer_version, network_id, handshake_data);
         let pubk = handshake_data.node_public_key.to_public_key()?;
@@ -448,7 +466,7 @@ impl Neighbor {
             };
         }
 
-        neighbor.handshake_update(conn, &handshake_data)?;
+        neighbor.handshake_update(conn, &handshake_data, now)?;
         Ok(neighbor)
     }
 
@@ -494,7 +512,7 @@ impl ConversationP2P {
         conn_id: usize,
     ) -> ConversationP2P {
         ConversationP2P {
-            instantiated: get_epoch_time_secs(),
+            instantiated: conn_opts.clock.now_secs(),
             network_id: network_id,
             version: version,
             connection: ConnectionP2P::new(StacksP2P::new(), conn_opts, None),
@@ -519,7 +537,7 @@ impl ConversationP2P {
             burnchain_stable_tip_height: 0,
             burnchain_stable_tip_burn_header_hash: BurnchainHeaderHash([0u8; 32]),
 
-            stats: NeighborStats::new(outbound),
+            stats: NeighborStats::new(outbound, conn_opts.clock.clone()),
             reply_handles: VecDeque::new(),
         }
     }
@@ -1086,8 +1104,9 @@ impl ConversationP2P {
                 message.preamble.peer_version,
                 message.preamble.network_id,
                 &handshake_data,
+                self.connection.options.clock.now_secs(),
             )?;
-            neighbor.save_update(&mut tx)?;
+            neighbor.save_update(&mut tx, self.connection.options.clock.now_secs())?;
             tx.commit()
                 .map_err(|e| net_error::DBError(db_error::SqliteError(e)))?;
 
@@ -1109,7 +1128,7 @@ impl ConversationP2P {
         );
 
         // update stats
-        self.stats.last_contact_time = get_epoch_time_secs();
+        self.stats.last_contact_time = self.connection.options.clock.now_secs();
         self.peer_heartbeat = self.heartbeat; // use our own heartbeat to determine how often we expect this peer to ping us, since that's what we've told the peer
 
         // always pass back handshakes, even though we "handled" them (since other processes --
@@ -1137,7 +1156,7 @@ impl ConversationP2P {
                 handshake_accept.heartbeat_interval
             };
 
-        self.stats.last_handshake_time = get_epoch_time_secs();
+        self.stats.last_handshake_time = self.connection.options.clock.now_secs();
 
         debug!(
             "HandshakeAccept from {:?}: set public key to {:?} expiring at {:?} heartbeat {}s",
@@ -1195,6 +1214,7 @@ impl ConversationP2P {
             MAX_NEIGHBORS_DATA_LEN,
             chain_view.burn_block_height,
             false,
+            self.connection.options.clock.now_secs(),
         )
         .map_err(net_error::DBError)?;
 
@@ -1788,7 +1808,7 @@ impl ConversationP2P {
                 Ok(num_recved) => {
                     total_recved += num_recved;
                     if num_recved > 0 {
-                        self.stats.last_recv_time = get_epoch_time_secs();
+                        self.stats.last_recv_time = self.connection.options.clock.now_secs();
                         self.stats.bytes_rx += num_recved as u64;
                     } else {
                         break;
@@ -1816,7 +1836,7 @@ impl ConversationP2P {
                 Ok(num_sent) => {
                     total_sent += num_sent;
                     if num_sent > 0 {
-                        self.stats.last_send_time = get_epoch_time_secs();
+                        self.stats.last_send_time = self.connection.options.clock.now_secs();
                         self.stats.bytes_tx += num_sent as u64;
                     } else {
                         break;
@@ -2156,7 +2176,7 @@ impl ConversationP2P {
                 }
             }
 
-            let now = get_epoch_time_secs();
+            let now = self.connection.options.clock.now_secs();
             let _msgtype = msg.payload.get_message_name().to_owned();
             let _seq = msg.request_id();
 
@@ -2176,7 +2196,7 @@ impl ConversationP2P {
 
                 self.stats.msgs_rx += 1;
                 self.stats.last_recv_time = now;
-                self.stats.last_contact_time = get_epoch_time_secs();
+                self.stats.last_contact_time = now;
                 self.stats.add_healthpoint(true);
 
                 // update chain view from preamble
@@ -2302,6 +2322,7 @@ mod test {
     use std::io::Read;
     use std::io::Write;
 
+    use util::get_epoch_time_secs;
     use util::test::*;
 
     use net::test::*;
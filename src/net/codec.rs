@@ -324,10 +324,10 @@ impl StacksMessageCodec for Preamble {
 
         if payload_len >= MAX_MESSAGE_LEN {
             test_debug!("Payload len is too big: {}", payload_len);
-            return Err(net_error::DeserializeError(format!(
-                "Payload len is too big: {}",
-                payload_len
-            )));
+            return Err(net_error::OversizedMessage(
+                payload_len as u64,
+                (MAX_MESSAGE_LEN - 1) as u64,
+            ));
         }
 
         if burn_block_height <= burn_stable_block_height {
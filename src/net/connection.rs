@@ -19,13 +19,18 @@
 
 use std::collections::VecDeque;
 use std::convert::TryFrom;
+use std::fmt;
 use std::io;
 use std::io::{Read, Write};
 use std::net;
 use std::ops::Deref;
 use std::ops::DerefMut;
+use std::sync::Arc;
 use std::time::Duration;
 
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use std::sync::mpsc::sync_channel;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::RecvError;
@@ -61,6 +66,7 @@ use vm::{costs::ExecutionCost, types::BOUND_VALUE_SERIALIZATION_HEX};
 
 use chainstate::burn::ConsensusHash;
 
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
 use util::log;
@@ -316,14 +322,110 @@ struct ConnectionOutbox<P: ProtocolFamily> {
     inflight: VecDeque<ReceiverNotify<P>>,
 }
 
+/// Source of the current time for the network's deadlines, bans, and timestamps
+pub trait Clock: Send + Sync {
+    /// Seconds since the epoch
+    fn now_secs(&self) -> u64;
+
+    /// Milliseconds since the epoch.  Clocks that only keep seconds report whole seconds.
+    fn now_ms(&self) -> u128 {
+        (self.now_secs() as u128) * 1000
+    }
+}
+
+/// The system's wall clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> u64 {
+        get_epoch_time_secs()
+    }
+
+    fn now_ms(&self) -> u128 {
+        get_epoch_time_ms()
+    }
+}
+
+/// A clock that only moves when told to, for testing time-dependent network logic.
+/// Clones share the same time.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<AtomicU64>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now: u64) -> MockClock {
+        MockClock {
+            now: Arc::new(AtomicU64::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.now.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now_secs(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// Shared handle to the clock a peer network reads the time from.
+/// Two handles are equal if they refer to the same clock.
+#[derive(Clone)]
+pub struct NetworkClock(Arc<dyn Clock>);
+
+impl NetworkClock {
+    pub fn new<C: Clock + 'static>(clock: C) -> NetworkClock {
+        NetworkClock(Arc::new(clock))
+    }
+
+    pub fn now_secs(&self) -> u64 {
+        self.0.now_secs()
+    }
+
+    pub fn now_ms(&self) -> u128 {
+        self.0.now_ms()
+    }
+}
+
+impl std::default::Default for NetworkClock {
+    fn default() -> NetworkClock {
+        NetworkClock::new(SystemClock)
+    }
+}
+
+impl fmt::Debug for NetworkClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NetworkClock({})", self.now_secs())
+    }
+}
+
+impl PartialEq for NetworkClock {
+    fn eq(&self, other: &NetworkClock) -> bool {
+        &*self.0 as *const dyn Clock as *const u8 == &*other.0 as *const dyn Clock as *const u8
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ConnectionOptions {
     pub inbox_maxlen: usize,
     pub outbox_maxlen: usize,
     pub connect_timeout: u64,
+    pub connect_backoff: u64,
+    pub max_connect_backoff: u64,
     pub handshake_timeout: u64,
     pub timeout: u64,
     pub idle_timeout: u64,
+    pub peer_idle_timeout: u64,
     pub heartbeat: u32,
     pub private_key_lifetime: u64,
     pub num_neighbors: u64,
@@ -339,6 +441,7 @@ pub struct ConnectionOptions {
     pub num_initial_walks: u64,
     pub walk_retry_count: u64,
     pub walk_interval: u64,
+    pub walk_min_neighbors: u64,
     pub walk_inbound_ratio: u64,
     pub inv_sync_interval: u64,
     pub download_interval: u64,
@@ -350,11 +453,17 @@ pub struct ConnectionOptions {
     pub max_block_push_bandwidth: u64,
     pub max_microblocks_push_bandwidth: u64,
     pub max_transaction_push_bandwidth: u64,
+    pub max_p2p_messages_per_sec: u64,
+    pub max_p2p_messages_burst: u64,
+    pub max_relay_handles_per_peer: usize,
     pub max_sockets: usize,
     pub public_ip_address: Option<(PeerAddress, u16)>,
     pub public_ip_request_timeout: u64,
     pub public_ip_timeout: u64,
     pub public_ip_max_retries: u64,
+    pub public_ip_resolver_url: Option<String>,
+    pub rng_seed: Option<u64>,
+    pub clock: NetworkClock,
 
     // fault injection
     pub disable_neighbor_walk: bool,
@@ -375,9 +484,12 @@ impl std::default::Default for ConnectionOptions {
             inbox_maxlen: 5,
             outbox_maxlen: 5,
             connect_timeout: 10, // how long a socket can be in a connecting state
+            connect_backoff: 10, // how long to wait before reconnecting to a peer after its second consecutive failed connection (doubles on each failure)
+            max_connect_backoff: 3600, // most time we'll wait before reconnecting to a failing peer
             handshake_timeout: 30, // how long before a peer must send a handshake, after connecting
             timeout: 30,         // how long to wait for a reply to a request
             idle_timeout: 15, // how long a non-request HTTP connection can be idle before it's closed
+            peer_idle_timeout: 0, // how long a p2p conversation can go without receiving a message before it's closed (0 means never)
             heartbeat: 3600,  // send a heartbeat once an hour by default
             private_key_lifetime: 4302, // key expires after ~1 month
             num_neighbors: 32, // how many outbound connections we can have, full-stop
@@ -393,6 +505,7 @@ impl std::default::Default for ConnectionOptions {
             num_initial_walks: NUM_INITIAL_WALKS,
             walk_retry_count: WALK_RETRY_COUNT,
             walk_interval: NEIGHBOR_WALK_INTERVAL, // how often to do a neighbor walk.
+            walk_min_neighbors: 0, // walk again right away if we have fewer peers than this
             walk_inbound_ratio: 2, // walk inbound neighbors twice as often as outbound by default
             inv_sync_interval: INV_SYNC_INTERVAL, // how often to synchronize block inventories
             download_interval: BLOCK_DOWNLOAD_INTERVAL, // how often to scan for blocks to download
@@ -410,11 +523,17 @@ impl std::default::Default for ConnectionOptions {
             max_block_push_bandwidth: 0, // infinite upload bandwidth allowed
             max_microblocks_push_bandwidth: 0, // infinite upload bandwidth allowed
             max_transaction_push_bandwidth: 0, // infinite upload bandwidth allowed
-            max_sockets: 800,            // maximum number of client sockets we'll ever register
-            public_ip_address: None,     // resolve it at runtime by default
+            max_p2p_messages_per_sec: 0, // unlimited inbound p2p messages per peer
+            max_p2p_messages_burst: 0, // how many messages a peer can send at once, if rate-limited (0 means one second's worth)
+            max_relay_handles_per_peer: 1024, // maximum number of unsent relayed messages queued up for a peer
+            max_sockets: 800, // maximum number of client sockets we'll ever register
+            public_ip_address: None, // resolve it at runtime by default
             public_ip_request_timeout: 60, // how often we can attempt to look up our public IP address
             public_ip_timeout: 3600,       // re-learn the public IP ever hour, if it's not given
             public_ip_max_retries: 3, // maximum number of retries before self-throttling for $public_ip_timeout
+            public_ip_resolver_url: None, // ask our initial neighbors for our public IP by default
+            rng_seed: None,           // sample neighbors nondeterministically by default
+            clock: NetworkClock::default(), // read the system clock by default
 
             // no faults on by default
             disable_neighbor_walk: false,
@@ -499,10 +618,10 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
                 if let Some(payload_len) = protocol.payload_len(&preamble) {
                     if (payload_len as u32) >= MAX_MESSAGE_LEN {
                         // message would be too big
-                        return Err(net_error::DeserializeError(format!(
-                            "Preamble payload length {} is too big",
-                            payload_len
-                        )));
+                        return Err(net_error::OversizedMessage(
+                            payload_len as u64,
+                            (MAX_MESSAGE_LEN - 1) as u64,
+                        ));
                     }
                 }
 
@@ -2199,4 +2318,43 @@ mod test {
 
         pinger.join().unwrap();
     }
+
+    #[test]
+    fn connection_reject_oversized_message() {
+        let conn_opts = ConnectionOptions::default();
+        let mut conn = ConnectionP2P::new(StacksP2P::new(), &conn_opts, None);
+
+        // a message that claims to be bigger than any message can be
+        let preamble = Preamble::new(
+            0x12345678,
+            0x9abcdef0,
+            12345,
+            &BurnchainHeaderHash([0x11; 32]),
+            12339,
+            &BurnchainHeaderHash([0x22; 32]),
+            MAX_MESSAGE_LEN,
+        );
+
+        let relayers: Vec<RelayData> = vec![];
+        let mut frame = vec![];
+        preamble.consensus_serialize(&mut frame).unwrap();
+        relayers.consensus_serialize(&mut frame).unwrap();
+        frame.push(StacksMessageID::Transaction as u8);
+        frame.extend_from_slice(&[0u8; 4096]);
+
+        let res = conn.recv_data(&mut &frame[..]);
+        match res {
+            Err(net_error::OversizedMessage(len, max_len)) => {
+                assert_eq!(len, MAX_MESSAGE_LEN as u64);
+                assert_eq!(max_len, (MAX_MESSAGE_LEN - 1) as u64);
+            }
+            _ => {
+                panic!("Accepted oversized message: {:?}", &res);
+            }
+        }
+
+        // nothing beyond the preamble was bufferred
+        assert!(conn.inbox.buf.len() <= PREAMBLE_ENCODED_SIZE as usize);
+        assert_eq!(conn.inbox_len(), 0);
+    }
 }
@@ -33,7 +33,6 @@ use util::db::DBConn;
 use util::db::Error as db_error;
 use util::db::{query_count, query_row, query_rows, u64_to_sql, FromColumn, FromRow};
 
-use util;
 use util::hash::{bin_bytes, hex_bytes, to_bin, to_hex, Hash160, Sha256Sum, Sha512Trunc256Sum};
 use util::log;
 use util::macros::is_big_endian;
@@ -790,16 +789,17 @@ impl PeerDB {
         query_row::<Neighbor, _>(conn, &qry, &args)
     }
 
-    /// Is a peer denied?
+    /// Is a peer denied as of `now` (in seconds since the epoch)?
     pub fn is_peer_denied(
         conn: &DBConn,
         network_id: u32,
         peer_addr: &PeerAddress,
         peer_port: u16,
+        now: u64,
     ) -> Result<bool, db_error> {
         match PeerDB::get_peer(conn, network_id, peer_addr, peer_port)? {
             Some(neighbor) => {
-                if neighbor.is_denied() {
+                if neighbor.is_denied_at(now) {
                     return Ok(true);
                 }
                 if PeerDB::is_address_denied(conn, &neighbor.addr.addrbytes)? {
@@ -1203,19 +1203,18 @@ impl PeerDB {
         Ok(())
     }
 
-    /// Get random neighbors, optionally always including allowed neighbors
+    /// Get random neighbors, optionally always including allowed neighbors.
+    /// Allow and deny expirations are evaluated as of `now_secs` (in seconds since the epoch).
     pub fn get_random_neighbors(
         conn: &DBConn,
         network_id: u32,
         count: u32,
         block_height: u64,
         always_include_allowed: bool,
+        now_secs: u64,
     ) -> Result<Vec<Neighbor>, db_error> {
         let mut ret = vec![];
 
-        // UTC time
-        let now_secs = util::get_epoch_time_secs();
-
         if always_include_allowed {
             // always include allowed neighbors, freshness be damned
             let allow_qry = "SELECT * FROM frontier WHERE network_id = ?1 AND denied < ?2 AND (allowed < 0 OR ?3 < allowed)".to_string();
@@ -1265,8 +1264,9 @@ impl PeerDB {
         network_id: u32,
         count: u32,
         block_height: u64,
+        now_secs: u64,
     ) -> Result<Vec<Neighbor>, db_error> {
-        PeerDB::get_random_neighbors(conn, network_id, count, block_height, true)
+        PeerDB::get_random_neighbors(conn, network_id, count, block_height, true, now_secs)
     }
 
     /// Get a randomized set of peers for walking the peer graph.
@@ -1276,8 +1276,9 @@ impl PeerDB {
         network_id: u32,
         count: u32,
         block_height: u64,
+        now_secs: u64,
     ) -> Result<Vec<Neighbor>, db_error> {
-        PeerDB::get_random_neighbors(conn, network_id, count, block_height, false)
+        PeerDB::get_random_neighbors(conn, network_id, count, block_height, false, now_secs)
     }
 
     /// Add an IPv4 <--> ASN mapping
@@ -1346,6 +1347,39 @@ impl PeerDB {
         let rows = query_rows::<Neighbor, _>(conn, &qry, NO_PARAMS)?;
         Ok(rows)
     }
+
+    /// Get the whole frontier, so it can be persisted and imported again on restart to seed the
+    /// neighbor walk.
+    pub fn export_frontier(&self) -> Result<Vec<Neighbor>, db_error> {
+        PeerDB::get_all_peers(self.conn())
+    }
+
+    /// Seed the frontier with previously-exported neighbors.  They are marked as unverified
+    /// (i.e. never contacted) until we complete a handshake with them again.  Neighbors that
+    /// are already in the frontier, or whose slots are all taken, are skipped.
+    /// Returns how many neighbors were inserted.
+    pub fn import_frontier(&mut self, neighbors: Vec<Neighbor>) -> Result<usize, db_error> {
+        let mut tx = self.tx_begin()?;
+        let mut num_imported = 0;
+        for mut neighbor in neighbors.into_iter() {
+            let present = PeerDB::get_peer(
+                &tx,
+                neighbor.addr.network_id,
+                &neighbor.addr.addrbytes,
+                neighbor.addr.port,
+            )?;
+            if present.is_some() {
+                continue;
+            }
+
+            neighbor.last_contact_time = 0;
+            if PeerDB::try_insert_peer(&mut tx, &neighbor)? {
+                num_imported += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(num_imported)
+    }
 }
 
 #[cfg(test)]
@@ -1354,6 +1388,7 @@ mod test {
     use net::Neighbor;
     use net::NeighborKey;
     use net::PeerAddress;
+    use util::get_epoch_time_secs;
 
     #[test]
     fn test_local_peer() {
@@ -1527,10 +1562,83 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_export_import_frontier() {
+        let mut neighbors = vec![];
+        for i in 0..3 {
+            neighbors.push(Neighbor {
+                addr: NeighborKey {
+                    peer_version: 0x12345678,
+                    network_id: 0x9abcdef0,
+                    addrbytes: PeerAddress([i as u8; 16]),
+                    port: i,
+                },
+                public_key: Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new()),
+                expire_block: (i + 23456) as u64,
+                last_contact_time: (1552509642 + (i as u64)) as u64,
+                allowed: -1,
+                denied: -1,
+                asn: (34567 + i) as u32,
+                org: (45678 + i) as u32,
+                in_degree: 1,
+                out_degree: 1,
+            });
+        }
+
+        let mut db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+        {
+            let mut tx = db.tx_begin().unwrap();
+            for neighbor in neighbors.iter() {
+                assert!(PeerDB::try_insert_peer(&mut tx, neighbor).unwrap());
+            }
+            tx.commit().unwrap();
+        }
+
+        let exported = db.export_frontier().unwrap();
+        assert_eq!(exported, neighbors);
+        assert!(exported.iter().all(|n| n.is_verified()));
+
+        let mut fresh_db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+        assert_eq!(PeerDB::get_frontier_size(fresh_db.conn()).unwrap(), 0);
+
+        assert_eq!(fresh_db.import_frontier(exported.clone()).unwrap(), 3);
+
+        let imported = fresh_db.export_frontier().unwrap();
+        assert_eq!(imported.len(), 3);
+        for (neighbor, imported_neighbor) in neighbors.iter().zip(imported.iter()) {
+            assert_eq!(imported_neighbor.addr, neighbor.addr);
+            assert_eq!(imported_neighbor.public_key, neighbor.public_key);
+            assert_eq!(imported_neighbor.expire_block, neighbor.expire_block);
+
+            // not trusted until we handshake with it again
+            assert!(!imported_neighbor.is_verified());
+        }
+
+        // importing again doesn't duplicate anything
+        assert_eq!(fresh_db.import_frontier(exported).unwrap(), 0);
+        assert_eq!(PeerDB::get_frontier_size(fresh_db.conn()).unwrap(), 3);
+    }
+
     #[test]
     fn test_initial_neighbors() {
         let mut initial_neighbors = vec![];
-        let now_secs = util::get_epoch_time_secs();
+        let now_secs = get_epoch_time_secs();
         for i in 0..10 {
             initial_neighbors.push(Neighbor {
                 addr: NeighborKey {
@@ -1597,17 +1705,19 @@ mod test {
         )
         .unwrap();
 
-        let n5 = PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 5, 23455).unwrap();
+        let n5 = PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 5, 23455, now_secs).unwrap();
         assert!(are_present(&n5, &initial_neighbors));
 
-        let n10 = PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 10, 23455).unwrap();
+        let n10 =
+            PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 10, 23455, now_secs).unwrap();
         assert!(are_present(&n10, &initial_neighbors));
 
-        let n20 = PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 20, 23455).unwrap();
+        let n20 =
+            PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 20, 23455, now_secs).unwrap();
         assert!(are_present(&initial_neighbors, &n20));
 
         let n15_fresh =
-            PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 15, 23456 + 14).unwrap();
+            PeerDB::get_initial_neighbors(db.conn(), 0x9abcdef0, 15, 23456 + 14, now_secs).unwrap();
         assert!(are_present(
             &n15_fresh[10..15].to_vec(),
             &initial_neighbors[10..20].to_vec()
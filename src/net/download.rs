@@ -40,6 +40,7 @@ use net::rpc::*;
 use net::*;
 
 use net::connection::ConnectionOptions;
+use net::connection::NetworkClock;
 use net::connection::ReplyHandleHttp;
 use net::GetBlocksInv;
 use net::StacksMessage;
@@ -85,7 +86,6 @@ use std::io::Write;
 use std::convert::TryFrom;
 
 use util::get_epoch_time_ms;
-use util::get_epoch_time_secs;
 use util::hash::to_hex;
 use util::log;
 
@@ -139,6 +139,7 @@ impl BlockRequestKey {
         index_block_hash: StacksBlockId,
         child_block_header: Option<StacksBlockHeader>,
         sortition_height: u64,
+        download_start: u64,
     ) -> BlockRequestKey {
         BlockRequestKey {
             neighbor: neighbor,
@@ -148,7 +149,7 @@ impl BlockRequestKey {
             index_block_hash: index_block_hash,
             child_block_header: child_block_header,
             sortition_height: sortition_height,
-            download_start: get_epoch_time_secs(),
+            download_start: download_start,
         }
     }
 }
@@ -221,6 +222,9 @@ pub struct BlockDownloader {
     /// when did we last request a given block hash
     requested_blocks: HashMap<StacksBlockId, u64>,
     requested_microblocks: HashMap<StacksBlockId, u64>,
+
+    /// clock used for URL bans and re-request intervals
+    clock: NetworkClock,
 }
 
 impl BlockDownloader {
@@ -228,6 +232,7 @@ impl BlockDownloader {
         dns_timeout: u128,
         download_interval: u64,
         max_inflight_requests: u64,
+        clock: NetworkClock,
     ) -> BlockDownloader {
         BlockDownloader {
             state: BlockDownloaderState::DNSLookupBegin,
@@ -266,6 +271,7 @@ impl BlockDownloader {
             download_interval: download_interval,
             requested_blocks: HashMap::new(),
             requested_microblocks: HashMap::new(),
+            clock: clock,
         }
     }
 
@@ -429,7 +435,7 @@ impl BlockDownloader {
                         // don't try this again for a while
                         self.blocked_urls.insert(
                             block_key.data_url,
-                            get_epoch_time_secs() + BLOCK_DOWNLOAD_BAN_URL,
+                            self.clock.now_secs() + BLOCK_DOWNLOAD_BAN_URL,
                         );
                     }
                 }
@@ -533,7 +539,7 @@ impl BlockDownloader {
                         // don't try this again for a while
                         self.blocked_urls.insert(
                             block_key.data_url,
-                            get_epoch_time_secs() + BLOCK_DOWNLOAD_BAN_URL,
+                            self.clock.now_secs() + BLOCK_DOWNLOAD_BAN_URL,
                         );
                     }
                 }
@@ -866,7 +872,7 @@ impl BlockDownloader {
 
             // was recently requested?  could still be bufferred up for storage
             if let Some(fetched_ts) = self.requested_blocks.get(index_hash) {
-                if get_epoch_time_secs() < fetched_ts + BLOCK_REREQUEST_INTERVAL {
+                if self.clock.now_secs() < fetched_ts + BLOCK_REREQUEST_INTERVAL {
                     return true;
                 }
             }
@@ -881,7 +887,7 @@ impl BlockDownloader {
 
             // was recently requested?  could still be bufferred up for storage
             if let Some(fetched_ts) = self.requested_microblocks.get(index_hash) {
-                if get_epoch_time_secs() < fetched_ts + BLOCK_REREQUEST_INTERVAL {
+                if self.clock.now_secs() < fetched_ts + BLOCK_REREQUEST_INTERVAL {
                     return true;
                 }
             }
@@ -1236,7 +1242,7 @@ impl PeerNetwork {
                 }
 
                 let prev_blocked = if let Some(deadline) = downloader.blocked_urls.get(&data_url) {
-                    if get_epoch_time_secs() < *deadline {
+                    if self.connection_opts.clock.now_secs() < *deadline {
                         debug!(
                             "{:?}: Will not request {} {}/{} from {:?} (of {:?}) until after {}",
                             &self.local_peer,
@@ -1285,6 +1291,7 @@ impl PeerNetwork {
                     target_index_block_hash.clone(),
                     child_block_header.clone(),
                     (i as u64) + start_sortition_height,
+                    self.connection_opts.clock.now_secs(),
                 );
                 requests.push_back(request);
             }
@@ -1437,7 +1444,7 @@ impl PeerNetwork {
                         &network.local_peer, &max_height, &max_mblock_height
                     );
 
-                    let now = get_epoch_time_secs();
+                    let now = network.connection_opts.clock.now_secs();
 
                     // queue up block requests in order by sortition height
                     while height <= max_height
@@ -1880,7 +1887,7 @@ impl PeerNetwork {
         let mut done = false;
         let mut old_pox_id = None;
 
-        let now = get_epoch_time_secs();
+        let now = self.connection_opts.clock.now_secs();
 
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
             // extract blocks and microblocks downloaded
@@ -2043,7 +2050,7 @@ impl PeerNetwork {
                     // Either we have everything already, or none of our peers have anything we don't have, or we can't reach any of our peers.
                     // Regardless, we can throttle back now.
                     debug!("Did a full pass over the burn chain sortitions and found no new data");
-                    downloader.finished_scan_at = get_epoch_time_secs();
+                    downloader.finished_scan_at = network.connection_opts.clock.now_secs();
                 }
 
                 // propagate PoX ID as it was when we started
@@ -2098,6 +2105,7 @@ impl PeerNetwork {
             self.connection_opts.dns_timeout,
             self.connection_opts.download_interval,
             self.connection_opts.max_inflight_blocks,
+            self.connection_opts.clock.clone(),
         ));
     }
 
@@ -2139,7 +2147,7 @@ impl PeerNetwork {
                 {
                     if downloader.last_inv_update_at == last_inv_update_at
                         && downloader.finished_scan_at + downloader.download_interval
-                            >= get_epoch_time_secs()
+                            >= self.connection_opts.clock.now_secs()
                     {
                         // throttle ourselves
                         debug!(
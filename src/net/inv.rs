@@ -44,7 +44,6 @@ use net::p2p::PeerNetwork;
 use util::db::DBConn;
 use util::db::Error as db_error;
 use util::get_epoch_time_ms;
-use util::get_epoch_time_secs;
 use util::secp256k1::Secp256k1PrivateKey;
 use util::secp256k1::Secp256k1PublicKey;
 
@@ -94,7 +93,7 @@ pub struct PeerBlocksInv {
     pub num_sortitions: u64,
     /// Number of reward cycles we know this peer knows about
     pub num_reward_cycles: u64,
-    /// Time of last update, in seconds
+    /// Time of last update from the network, in seconds (0 if never updated)
     pub last_updated_at: u64,
     /// Burn block height of first sortition
     pub first_block_height: u64,
@@ -128,7 +127,7 @@ impl PeerBlocksInv {
             num_sortitions: num_sortitions,
             num_reward_cycles: num_reward_cycles,
             pox_inv: pox_inv,
-            last_updated_at: get_epoch_time_secs(),
+            last_updated_at: 0,
             first_block_height: first_block_height,
         }
     }
@@ -254,8 +253,6 @@ impl PeerBlocksInv {
             insert_index += 1;
         }
 
-        self.last_updated_at = get_epoch_time_secs();
-
         assert!(insert_index / 8 <= self.block_inv.len() as u64);
         assert!(self.num_sortitions / 8 <= self.block_inv.len() as u64);
 
@@ -384,8 +381,6 @@ impl PeerBlocksInv {
             self.truncate_block_inventories(burnchain, *flipped_reward_cycle);
         }
 
-        self.last_updated_at = get_epoch_time_secs();
-
         assert!(insert_index / 8 <= self.pox_inv.len() as u64);
         assert!(self.num_reward_cycles / 8 <= self.pox_inv.len() as u64);
 
@@ -920,6 +915,8 @@ pub struct InvState {
     hint_do_full_rescan: bool,
     /// last time a full scan was completed
     last_rescanned_at: u64,
+    /// Neighbor whose state machine should be driven before all others on the next pass
+    pub priority_peer: Option<NeighborKey>,
 }
 
 impl InvState {
@@ -943,6 +940,7 @@ impl InvState {
             hint_learned_data: false,
             hint_do_full_rescan: true,
             last_rescanned_at: 0,
+            priority_peer: None,
         }
     }
 
@@ -1081,6 +1079,44 @@ impl InvState {
         self.block_stats.remove(&nk);
     }
 
+    /// Sync with this neighbor before any other on the next pass, restarting its scan if it
+    /// already finished.  Forces the next pass to happen even if we'd otherwise be throttled.
+    pub fn prioritize_peer(&mut self, nk: NeighborKey) -> () {
+        match self.block_stats.get_mut(&nk) {
+            Some(stats) => {
+                if stats.done {
+                    stats.reset_pox_scan(0);
+                    stats.status = NodeStatus::Online;
+                    stats.done = false;
+                    stats.learned_data = false;
+                }
+            }
+            None => {
+                self.add_peer(nk.clone());
+            }
+        }
+        self.sync_peers.insert(nk.clone());
+        self.priority_peer = Some(nk);
+        self.hint_do_full_rescan = true;
+    }
+
+    /// Order in which to drive the neighbors' state machines: the prioritized neighbor (if any)
+    /// first, then everyone else.
+    pub fn get_sync_order(&self) -> Vec<NeighborKey> {
+        let mut order = vec![];
+        if let Some(ref nk) = self.priority_peer {
+            if self.block_stats.contains_key(nk) {
+                order.push(nk.clone());
+            }
+        }
+        for nk in self.block_stats.keys() {
+            if Some(nk) != self.priority_peer.as_ref() {
+                order.push(nk.clone());
+            }
+        }
+        order
+    }
+
     /// Set a block or confirmed microblock stream as available, given the burn header hash and consensus hash.
     /// Used when processing a BlocksAvailable or MicroblocksAvailable message.
     /// Drops if the message refers to a block height
@@ -1688,6 +1724,7 @@ impl PeerNetwork {
             pox_inv.pox_bitvec.clone(),
             true,
         );
+        stats.inv.last_updated_at = self.connection_opts.clock.now_secs();
 
         if let Some(lowest_learned_reward_cycle) = lowest_learned_reward_cycle.as_ref() {
             debug!(
@@ -1833,6 +1870,7 @@ impl PeerNetwork {
             blocks_inv.microblocks_bitvec,
             true,
         );
+        stats.inv.last_updated_at = self.connection_opts.clock.now_secs();
 
         debug!("{:?}: {:?} has {} new blocks and {} new microblocks (total {} blocks, {} microblocks, {} sortitions): {:?}", 
                &self.local_peer, &nk, new_blocks, new_microblocks, stats.inv.num_blocks(), stats.inv.num_microblock_streams(), stats.inv.num_sortitions, &stats.inv);
@@ -1849,7 +1887,7 @@ impl PeerNetwork {
             stats.reset_block_scan(stats.block_reward_cycle);
         } else {
             // we're done scanning!  proceed to rescan
-            stats.last_rescan_timestamp = get_epoch_time_secs();
+            stats.last_rescan_timestamp = self.connection_opts.clock.now_secs();
             stats.done = true;
         }
 
@@ -1959,7 +1997,8 @@ impl PeerNetwork {
 
             if !inv_state.hint_do_full_rescan
                 && !inv_state.hint_learned_data
-                && inv_state.last_rescanned_at + inv_state.sync_interval >= get_epoch_time_secs()
+                && inv_state.last_rescanned_at + inv_state.sync_interval
+                    >= network.connection_opts.clock.now_secs()
             {
                 // we didn't learn anything on the last sync, and it hasn't been enough time
                 // since the last sync for us to do it again
@@ -1971,7 +2010,24 @@ impl PeerNetwork {
                 return Ok((true, vec![], vec![]));
             }
 
-            for (nk, stats) in inv_state.block_stats.iter_mut() {
+            for nk in inv_state.get_sync_order().iter() {
+                if inv_state.priority_peer.as_ref() == Some(nk) {
+                    if !network.is_peer_target(nk) && network.is_peer_pending(nk) {
+                        // still connecting or handshaking; try again next pass
+                        debug!(
+                            "{:?}: waiting for prioritized peer {:?} to connect",
+                            &network.local_peer, nk
+                        );
+                        all_done = false;
+                        continue;
+                    }
+                    inv_state.priority_peer = None;
+                }
+
+                let stats = match inv_state.block_stats.get_mut(nk) {
+                    Some(stats) => stats,
+                    None => continue,
+                };
                 if !stats.done {
                     let done =
                         match network.inv_sync_run(sortdb, nk, stats, inv_state.request_timeout) {
@@ -2007,7 +2063,7 @@ impl PeerNetwork {
                         );
                         inv_state.hint_learned_data =
                             inv_state.hint_learned_data || stats.learned_data;
-                        inv_state.last_change_at = get_epoch_time_secs();
+                        inv_state.last_change_at = network.connection_opts.clock.now_secs();
                     }
                 }
             }
@@ -2019,7 +2075,7 @@ impl PeerNetwork {
 
                 if !inv_state.hint_learned_data && inv_state.block_stats.len() > 0 {
                     // did a full scan without learning anything new
-                    inv_state.last_rescanned_at = get_epoch_time_secs();
+                    inv_state.last_rescanned_at = network.connection_opts.clock.now_secs();
                     inv_state.hint_do_full_rescan = false;
 
                     debug!(
@@ -17,6 +17,7 @@
  along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
 */
 
+pub mod api;
 pub mod asn;
 pub mod chat;
 pub mod codec;
@@ -30,6 +31,7 @@ pub mod neighbors;
 pub mod p2p;
 pub mod poll;
 pub mod prune;
+pub mod publicip;
 pub mod relay;
 pub mod rpc;
 pub mod server;
@@ -128,6 +130,9 @@ pub enum Error {
     UnderflowError(String),
     /// Overflow -- message too big
     OverflowError(String),
+    /// Message preamble claims a payload longer than the largest message we accept (claimed
+    /// length, maximum length)
+    OversizedMessage(u64, u64),
     /// Wrong protocol family
     WrongProtocolFamily,
     /// Array is too big
@@ -249,6 +254,11 @@ impl fmt::Display for Error {
             Error::WriteError(ref io) => fmt::Display::fmt(io, f),
             Error::UnderflowError(ref s) => fmt::Display::fmt(s, f),
             Error::OverflowError(ref s) => fmt::Display::fmt(s, f),
+            Error::OversizedMessage(ref len, ref max_len) => write!(
+                f,
+                "Message payload length {} exceeds maximum of {}",
+                len, max_len
+            ),
             Error::WrongProtocolFamily => write!(f, "Improper use of protocol family"),
             Error::ArrayTooLong => write!(f, "Array too long"),
             Error::RecvTimeout => write!(f, "Packet receive timeout"),
@@ -307,6 +317,7 @@ impl error::Error for Error {
             Error::WriteError(ref io) => Some(io),
             Error::UnderflowError(ref _s) => None,
             Error::OverflowError(ref _s) => None,
+            Error::OversizedMessage(ref _len, ref _max_len) => None,
             Error::WrongProtocolFamily => None,
             Error::ArrayTooLong => None,
             Error::RecvTimeout => None,
@@ -979,38 +990,6 @@ impl PeerHost {
     }
 }
 
-/// The data we return on GET /v2/info
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct RPCPeerInfoData {
-    pub peer_version: u32,
-    pub pox_consensus: ConsensusHash,
-    pub burn_block_height: u64,
-    pub stable_pox_consensus: ConsensusHash,
-    pub stable_burn_block_height: u64,
-    pub server_version: String,
-    pub network_id: u32,
-    pub parent_network_id: u32,
-    pub stacks_tip_height: u64,
-    pub stacks_tip: BlockHeaderHash,
-    pub stacks_tip_consensus_hash: String,
-    pub unanchored_tip: StacksBlockId,
-    pub exit_at_block_height: Option<u64>,
-}
-
-/// The data we return on GET /v2/pox
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct RPCPoxInfoData {
-    pub contract_id: String,
-    pub first_burnchain_block_height: u128,
-    pub min_amount_ustx: u128,
-    pub prepare_cycle_length: u128,
-    pub rejection_fraction: u128,
-    pub reward_cycle_id: u128,
-    pub reward_cycle_length: u128,
-    pub rejection_votes_left_required: u128,
-    pub total_liquid_supply_ustx: u128,
-}
-
 #[derive(Debug, Clone, PartialEq, Copy, Hash)]
 #[repr(u8)]
 pub enum HttpVersion {
@@ -1025,50 +1004,6 @@ pub struct HttpRequestMetadata {
     pub keep_alive: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct MapEntryResponse {
-    pub data: String,
-    #[serde(rename = "proof")]
-    #[serde(default)]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub marf_proof: Option<String>,
-}
-
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct ContractSrcResponse {
-    pub source: String,
-    pub publish_height: u32,
-    #[serde(rename = "proof")]
-    #[serde(default)]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub marf_proof: Option<String>,
-}
-
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct CallReadOnlyResponse {
-    pub okay: bool,
-    #[serde(default)]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub result: Option<String>,
-    #[serde(default)]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub cause: Option<String>,
-}
-
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct AccountEntryResponse {
-    pub balance: String,
-    pub locked: String,
-    pub unlock_height: u64,
-    pub nonce: u64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(default)]
-    pub balance_proof: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(default)]
-    pub nonce_proof: Option<String>,
-}
-
 /// Request ID to use or expect from non-Stacks HTTP clients.
 /// In particular, if a HTTP response does not contain the x-request-id header, then it's assumed
 /// to be this value.  This is needed to support fetching immutables like block and microblock data
@@ -1408,6 +1343,11 @@ pub struct StacksP2P {}
 
 pub use self::http::StacksHttp;
 
+pub use self::api::{
+    AccountEntryResponse, CallReadOnlyResponse, ContractSrcResponse, MapEntryResponse,
+    RPCPeerInfoData, RPCPoxInfoData,
+};
+
 // an array in our protocol can't exceed this many items
 pub const ARRAY_MAX_LEN: u32 = u32::max_value();
 
@@ -1574,11 +1514,26 @@ pub struct Neighbor {
 
 impl Neighbor {
     pub fn is_allowed(&self) -> bool {
-        self.allowed < 0 || (self.allowed as u64) > get_epoch_time_secs()
+        self.is_allowed_at(get_epoch_time_secs())
+    }
+
+    /// Is this neighbor allowed as of `now` (in seconds since the epoch)?
+    pub fn is_allowed_at(&self, now: u64) -> bool {
+        self.allowed < 0 || (self.allowed as u64) > now
     }
 
     pub fn is_denied(&self) -> bool {
-        self.denied < 0 || (self.denied as u64) > get_epoch_time_secs()
+        self.is_denied_at(get_epoch_time_secs())
+    }
+
+    /// Is this neighbor denied as of `now` (in seconds since the epoch)?
+    pub fn is_denied_at(&self, now: u64) -> bool {
+        self.denied < 0 || (self.denied as u64) > now
+    }
+
+    /// Have we ever completed a handshake with this neighbor?
+    pub fn is_verified(&self) -> bool {
+        self.last_contact_time > 0
     }
 }
 
@@ -2002,11 +1957,18 @@ pub mod test {
         pub initial_balances: Vec<(PrincipalData, u64)>,
         pub spending_account: TestMiner,
         pub setup_code: String,
+        /// If set, the peer's miner keys, VRF proofs and microblock keys are all derived from
+        /// this seed, so the same sequence of tenures produces the same blocks on every run.
+        pub rng_seed: Option<u64>,
+        /// When miners' coinbases become liquid, for every chainstate this peer opens
+        pub miner_reward_schedule: MinerRewardSchedule,
     }
 
     impl TestPeerConfig {
         pub fn default() -> TestPeerConfig {
-            let conn_opts = ConnectionOptions::default();
+            let mut conn_opts = ConnectionOptions::default();
+            // test peers start up at different times, so keep retrying them
+            conn_opts.connect_backoff = 0;
             let start_block = 0;
             let mut burnchain = Burnchain::default_unittest(
                 start_block,
@@ -2045,6 +2007,8 @@ pub mod test {
                 initial_balances: vec![],
                 spending_account: spending_account,
                 setup_code: "".into(),
+                rng_seed: None,
+                miner_reward_schedule: MinerRewardSchedule::default(),
             }
         }
 
@@ -2132,6 +2096,7 @@ pub mod test {
         pub mempool: Option<MemPoolDB>,
         pub chainstate_path: String,
         pub coord: ChainsCoordinator<'a, NullEventDispatcher, (), OnChainRewardSetProvider>,
+        microblock_key_seed: Option<[u8; 32]>,
     }
 
     impl<'a> TestPeer<'a> {
@@ -2149,9 +2114,16 @@ pub mod test {
 
             fs::create_dir_all(&test_path).unwrap();
 
-            let mut miner_factory = TestMinerFactory::new();
+            // the miner's VRF keys (and thus its VRF proofs) are derived from its private keys
+            let mut miner_factory = match config.rng_seed {
+                Some(seed) => TestMinerFactory::from_u64(seed),
+                None => TestMinerFactory::new(),
+            };
             let mut miner =
                 miner_factory.next_miner(&config.burnchain, 1, 1, AddressHashMode::SerializeP2PKH);
+            let microblock_key_seed = config
+                .rng_seed
+                .map(|seed| Sha256Sum::from_data(&seed.to_be_bytes()).0);
 
             let mut burnchain = get_burnchain(&test_path);
             burnchain.first_block_height = config.burnchain.first_block_height;
@@ -2196,7 +2168,7 @@ pub mod test {
             .unwrap();
 
             let init_code = config.setup_code.clone();
-            let (chainstate, _) = StacksChainState::open_and_exec(false, config.network_id, &chainstate_path, Some(config.initial_balances.clone()),
+            let (chainstate, _) = StacksChainState::open_testnet_with_miner_reward_schedule(config.network_id, &chainstate_path, Some(config.initial_balances.clone()),
                 |ref mut clarity_tx| {
                     if init_code.len() > 0 {
                         clarity_tx.connection().as_transaction(|clarity| {
@@ -2229,7 +2201,8 @@ pub mod test {
                         });
                     }
                 },
-                ExecutionCost::max_value()).unwrap();
+                ExecutionCost::max_value(),
+                config.miner_reward_schedule).unwrap();
 
             let mut coord =
                 ChainsCoordinator::test_new(&burnchain, &test_path, OnChainRewardSetProvider());
@@ -2315,6 +2288,20 @@ pub mod test {
                 mempool: Some(mempool),
                 chainstate_path: chainstate_path,
                 coord: coord,
+                microblock_key_seed: microblock_key_seed,
+            }
+        }
+
+        /// Make a key for signing microblocks.  These are random, unless the peer was given an
+        /// RNG seed.
+        pub fn next_microblock_privkey(&mut self) -> StacksPrivateKey {
+            match self.microblock_key_seed {
+                Some(ref mut key_seed) => {
+                    let h = Sha256Sum::from_data(&key_seed[..]);
+                    key_seed.copy_from_slice(h.as_bytes());
+                    StacksPrivateKey::from_slice(h.as_bytes()).unwrap()
+                }
+                None => StacksPrivateKey::new(),
             }
         }
 
@@ -2951,6 +2938,81 @@ pub mod test {
             )
         }
 
+        /// Run `num_tenures` tenures in which this peer's miner produces a coinbase-only anchored
+        /// block, and process each one.  Returns the resulting canonical Stacks chain tip.
+        pub fn advance_tenures(&mut self, num_tenures: usize) -> StacksBlockId {
+            for _ in 0..num_tenures {
+                let tip =
+                    SortitionDB::get_canonical_burn_chain_tip(self.sortdb.as_ref().unwrap().conn())
+                        .unwrap();
+                let microblock_privkey = StacksPrivateKey::new();
+                let microblock_pubkeyhash = Hash160::from_data(
+                    &StacksPublicKey::from_private(&microblock_privkey).to_bytes(),
+                );
+
+                let (burn_ops, stacks_block, microblocks) = self.make_tenure(
+                    |ref mut miner,
+                     ref mut sortdb,
+                     ref mut chainstate,
+                     vrf_proof,
+                     ref parent_opt,
+                     _| {
+                        let parent_tip = match parent_opt {
+                            None => {
+                                StacksChainState::get_genesis_header_info(chainstate.headers_db())
+                                    .unwrap()
+                            }
+                            Some(block) => {
+                                let ic = sortdb.index_conn();
+                                let snapshot =
+                                    SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                        &ic,
+                                        &tip.sortition_id,
+                                        &block.block_hash(),
+                                    )
+                                    .unwrap()
+                                    .unwrap(); // succeeds because we don't fork
+                                StacksChainState::get_anchored_block_header_info(
+                                    chainstate.headers_db(),
+                                    &snapshot.consensus_hash,
+                                    &snapshot.winning_stacks_block_hash,
+                                )
+                                .unwrap()
+                                .unwrap()
+                            }
+                        };
+                        let coinbase_tx = make_coinbase(miner, tip.block_height as usize);
+
+                        let block_builder = StacksBlockBuilder::make_block_builder(
+                            &parent_tip,
+                            vrf_proof,
+                            tip.total_burn,
+                            microblock_pubkeyhash,
+                        )
+                        .unwrap();
+                        let (anchored_block, _size, _cost) =
+                            StacksBlockBuilder::make_anchored_block_from_txs(
+                                block_builder,
+                                chainstate,
+                                &sortdb.index_conn(),
+                                vec![coinbase_tx],
+                            )
+                            .unwrap();
+                        (anchored_block, vec![])
+                    },
+                );
+
+                self.next_burnchain_block(burn_ops);
+                self.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+            }
+
+            let (consensus_hash, block_bhh) = SortitionDB::get_canonical_stacks_chain_tip_hash(
+                self.sortdb.as_ref().unwrap().conn(),
+            )
+            .unwrap();
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh)
+        }
+
         // have this peer produce an anchored block and microblock tail using its internal miner.
         pub fn make_default_tenure(
             &mut self,
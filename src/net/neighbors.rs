@@ -30,6 +30,7 @@ use net::codec::*;
 use net::*;
 
 use net::connection::ConnectionOptions;
+use net::connection::NetworkClock;
 use net::connection::ReplyHandleP2P;
 
 use net::db::LocalPeer;
@@ -56,7 +57,6 @@ use burnchains::PublicKey;
 
 use rand::prelude::*;
 use rand::thread_rng;
-use util::get_epoch_time_secs;
 use util::hash::*;
 use util::log;
 
@@ -111,18 +111,18 @@ impl Neighbor {
 
     /// Update this peer in the DB.
     /// If there's no DB entry for this peer, then do nothing.
-    /// Updates last-contact-time to now, since this is only called when we get back a Handshake
-    pub fn save_update<'a>(&mut self, tx: &mut DBTx<'a>) -> Result<(), net_error> {
-        self.last_contact_time = get_epoch_time_secs();
+    /// Updates last-contact-time to `now`, since this is only called when we get back a Handshake
+    pub fn save_update<'a>(&mut self, tx: &mut DBTx<'a>, now: u64) -> Result<(), net_error> {
+        self.last_contact_time = now;
         PeerDB::update_peer(tx, &self).map_err(net_error::DBError)
     }
 
     /// Save to the peer DB, inserting it if it isn't already there.
-    /// Updates last-contact-time to now, since this is only called when we get back a Handshake
+    /// Updates last-contact-time to `now`, since this is only called when we get back a Handshake
     /// Return true if saved.
     /// Return false if not saved -- i.e. the frontier is full and we should try evicting neighbors.
-    pub fn save<'a>(&mut self, tx: &mut DBTx<'a>) -> Result<bool, net_error> {
-        self.last_contact_time = get_epoch_time_secs();
+    pub fn save<'a>(&mut self, tx: &mut DBTx<'a>, now: u64) -> Result<bool, net_error> {
+        self.last_contact_time = now;
         PeerDB::try_insert_peer(tx, &self).map_err(net_error::DBError)
     }
 
@@ -315,6 +315,9 @@ pub struct NeighborWalk {
     walk_min_duration: u64, // minimum steps we have to take before reset
     walk_max_duration: u64, // maximum steps we have to take before reset
     walk_reset_prob: f64,   // probability that we do a reset once the minimum duration is met
+
+    // where the walk reads the time from (the network's clock)
+    clock: NetworkClock,
 }
 
 impl NeighborWalk {
@@ -324,6 +327,7 @@ impl NeighborWalk {
         neighbor: &Neighbor,
         outbound: bool,
         pingbacks: HashMap<NeighborAddress, NeighborPingback>,
+        clock: NetworkClock,
     ) -> NeighborWalk {
         NeighborWalk {
             local_peer: local_peer,
@@ -364,13 +368,15 @@ impl NeighborWalk {
 
             result: NeighborWalkResult::new(),
 
-            walk_start_time: get_epoch_time_secs(),
+            walk_start_time: clock.now_secs(),
             walk_end_time: 0,
 
             walk_step_count: 0,
             walk_min_duration: 20,
             walk_max_duration: 40,
             walk_reset_prob: 0.05,
+
+            clock: clock,
         }
     }
 
@@ -410,7 +416,7 @@ impl NeighborWalk {
 
         let result = self.result.clone();
 
-        self.walk_end_time = get_epoch_time_secs();
+        self.walk_end_time = self.clock.now_secs();
 
         // leave self.frontier and self.result alone until the next walk.
         // (makes it so that at the end of the walk, we can query the result and frontier, which
@@ -537,6 +543,7 @@ impl NeighborWalk {
                                     message.preamble.peer_version,
                                     message.preamble.network_id,
                                     &data.handshake,
+                                    self.clock.now_secs(),
                                 )?;
                                 let res = if neighbor_from_handshake.addr != self.cur_neighbor.addr
                                 {
@@ -549,9 +556,13 @@ impl NeighborWalk {
                                     Err(net_error::PeerNotConnected)
                                 } else {
                                     // this is indeed cur_neighbor
+                                    self.cur_neighbor.handshake_update(
+                                        &mut tx,
+                                        &data.handshake,
+                                        self.clock.now_secs(),
+                                    )?;
                                     self.cur_neighbor
-                                        .handshake_update(&mut tx, &data.handshake)?;
-                                    self.cur_neighbor.save_update(&mut tx)?;
+                                        .save_update(&mut tx, self.clock.now_secs())?;
 
                                     debug!(
                                         "{:?}: Connected with {:?}",
@@ -578,6 +589,7 @@ impl NeighborWalk {
                                     message.preamble.peer_version,
                                     message.preamble.network_id,
                                     &data.handshake,
+                                    self.clock.now_secs(),
                                 )?;
                                 debug!(
                                     "{:?}: Connected with inbound non-frontier neighbor {:?}: {:?}",
@@ -650,13 +662,14 @@ impl NeighborWalk {
 
     /// Find the neighbor addresses that we need to resolve to neighbors,
     /// and find out the neighbor addresses that we already have fresh neighbor data for.
-    /// If we know of a neighbor, and contacted it recently, then consider it resolved _even if_
-    /// the reported NeighborAddress public key hash doesn't match our records.
+    /// If we know of a neighbor, and contacted it recently (as of `now`), then consider it
+    /// resolved _even if_ the reported NeighborAddress public key hash doesn't match our records.
     fn lookup_stale_neighbors(
         dbconn: &DBConn,
         network_id: u32,
         block_height: u64,
         addrs: &Vec<NeighborAddress>,
+        now: u64,
     ) -> Result<(HashMap<NeighborAddress, Neighbor>, Vec<NeighborAddress>), net_error> {
         let mut to_resolve = vec![];
         let mut resolved: HashMap<NeighborAddress, Neighbor> = HashMap::new();
@@ -682,9 +695,7 @@ impl NeighborWalk {
                             // neighboraddress.  Only try to re-connect with it if we haven't done
                             // so recently, so a rogue neighbor can't force us to DDoS another
                             // peer.
-                            if n.last_contact_time + NEIGHBOR_MINIMUM_CONTACT_INTERVAL
-                                < get_epoch_time_secs()
-                            {
+                            if n.last_contact_time + NEIGHBOR_MINIMUM_CONTACT_INTERVAL < now {
                                 to_resolve.push((*naddr).clone());
                             } else {
                                 // recently contacted
@@ -694,9 +705,7 @@ impl NeighborWalk {
                     }
                 }
                 Some(neighbor) => {
-                    if neighbor.last_contact_time + NEIGHBOR_MINIMUM_CONTACT_INTERVAL
-                        < get_epoch_time_secs()
-                    {
+                    if neighbor.last_contact_time + NEIGHBOR_MINIMUM_CONTACT_INTERVAL < now {
                         // stale
                         to_resolve.push((*naddr).clone());
                     } else {
@@ -756,6 +765,7 @@ impl NeighborWalk {
                             message.preamble.network_id,
                             block_height,
                             &data.neighbors,
+                            self.clock.now_secs(),
                         )?;
 
                         for (_naddr, neighbor) in found.drain() {
@@ -861,8 +871,13 @@ impl NeighborWalk {
         network_id: u32,
         handshake: &HandshakeData,
     ) -> Result<(bool, Neighbor), net_error> {
-        let mut neighbor_from_handshake =
-            Neighbor::from_handshake(tx, peer_version, network_id, handshake)?;
+        let mut neighbor_from_handshake = Neighbor::from_handshake(
+            tx,
+            peer_version,
+            network_id,
+            handshake,
+            self.clock.now_secs(),
+        )?;
         let neighbor_opt = Neighbor::from_neighbor_address(tx, network_id, block_height, naddr)?;
         match neighbor_opt {
             Some(neighbor) => {
@@ -870,7 +885,7 @@ impl NeighborWalk {
                     "{:?}: already know about {:?}",
                     &self.local_peer, &neighbor.addr
                 );
-                neighbor_from_handshake.save_update(tx)?;
+                neighbor_from_handshake.save_update(tx, self.clock.now_secs())?;
 
                 // seen this neighbor before
                 Ok((false, neighbor_from_handshake))
@@ -882,7 +897,7 @@ impl NeighborWalk {
                 );
 
                 // didn't know about this neighbor yet. Try to add it.
-                let added = neighbor_from_handshake.save(tx)?;
+                let added = neighbor_from_handshake.save(tx, self.clock.now_secs())?;
                 if !added {
                     // no more room in the db.  See if we can add it by
                     // evicting an existing neighbor once we're done with this
@@ -1219,7 +1234,8 @@ impl NeighborWalk {
                 );
 
                 let mut tx = network.peerdb.tx_begin()?;
-                self.cur_neighbor.save_update(&mut tx)?;
+                self.cur_neighbor
+                    .save_update(&mut tx, self.clock.now_secs())?;
                 tx.commit()?;
             }
 
@@ -1237,17 +1253,20 @@ impl NeighborWalk {
         }
     }
 
-    /// Pick a random neighbor from the frontier, excluding an optional given neighbor
-    fn pick_random_neighbor(
+    /// Pick a random neighbor from the frontier, excluding an optional given neighbor.
+    /// The frontier is visited in sorted order, so a seeded `rng` always picks the same neighbor.
+    fn pick_random_neighbor<R: Rng>(
         frontier: &HashMap<NeighborKey, Neighbor>,
         exclude: Option<&Neighbor>,
+        rng: &mut R,
     ) -> Option<Neighbor> {
-        let mut rnd = thread_rng();
-
-        let sample = rnd.gen_range(0, frontier.len());
+        let sample = rng.gen_range(0, frontier.len());
         let mut count = 0;
 
-        for (nk, n) in frontier.iter() {
+        let mut candidates: Vec<(&NeighborKey, &Neighbor)> = frontier.iter().collect();
+        candidates.sort_by(|(nk1, _), (nk2, _)| nk1.cmp(nk2));
+
+        for (nk, n) in candidates.into_iter() {
             count += match exclude {
                 None => 1,
                 Some(ref e) => {
@@ -1292,15 +1311,13 @@ impl NeighborWalk {
     /// peer's AS's node count to the current peer's AS's node count.
     ///
     /// This method updates self.next_neighbor with a new neighbor to step to, or None to restart.
-    pub fn step(&mut self, peerdb_conn: &DBConn) -> () {
+    pub fn step<R: Rng>(&mut self, peerdb_conn: &DBConn, rng: &mut R) -> () {
         test_debug!(
             "{:?}: execute neighbor step from {:?}",
             &self.local_peer,
             &self.cur_neighbor.addr
         );
 
-        let mut rnd = thread_rng();
-
         // step to a node in cur_neighbor's frontier, per MHRWDA
         let next_neighbor_opt = if self.frontier.len() == 0 {
             // just started the walk, so stay here for now -- we don't yet know the neighbor's
@@ -1312,9 +1329,9 @@ impl NeighborWalk {
                 None
             }
         } else {
-            let next_neighbor = NeighborWalk::pick_random_neighbor(&self.frontier, None)
+            let next_neighbor = NeighborWalk::pick_random_neighbor(&self.frontier, None, rng)
                 .expect("BUG: empty frontier size"); // won't panic since self.frontier.len() > 0
-            let walk_prob: f64 = rnd.gen();
+            let walk_prob: f64 = rng.gen();
             if walk_prob
                 < fmin!(
                     1.0,
@@ -1336,9 +1353,10 @@ impl NeighborWalk {
                                 let alt_next_neighbor = NeighborWalk::pick_random_neighbor(
                                     &self.frontier,
                                     Some(&prev_neighbor),
+                                    rng,
                                 )
                                 .expect("BUG: empty frontier size");
-                                let alt_prob: f64 = rnd.gen();
+                                let alt_prob: f64 = rng.gen();
 
                                 let cur_to_alt = NeighborWalk::degree_ratio(
                                     peerdb_conn,
@@ -1556,8 +1574,9 @@ impl NeighborWalk {
                                 message.preamble.peer_version,
                                 message.preamble.network_id,
                                 &data.handshake,
+                                self.clock.now_secs(),
                             )?;
-                            neighbor_from_handshake.save_update(&mut tx)?;
+                            neighbor_from_handshake.save_update(&mut tx, self.clock.now_secs())?;
                             tx.commit()?;
 
                             // not going to replace
@@ -1674,6 +1693,7 @@ impl PeerNetwork {
             self.local_peer.network_id,
             num_neighbors as u32,
             block_height,
+            self.connection_opts.clock.now_secs(),
         )
         .map_err(net_error::DBError)?;
 
@@ -1806,6 +1826,7 @@ impl PeerNetwork {
             &next_neighbors[0],
             true,
             self.walk_pingbacks.clone(),
+            self.connection_opts.clock.clone(),
         );
 
         debug!(
@@ -1829,8 +1850,10 @@ impl PeerNetwork {
             return Err(net_error::NoSuchNeighbor);
         }
 
-        // pick a random inbound conversation
-        let mut idx = thread_rng().gen::<usize>() % self.peers.len();
+        // pick a random inbound conversation (in sorted order, so seeded walks are repeatable)
+        let mut event_ids: Vec<usize> = self.peers.keys().cloned().collect();
+        event_ids.sort();
+        let mut idx = self.rng.gen::<usize>() % event_ids.len();
 
         test_debug!(
             "{:?}: try inbound neighbors -- sample out of {}. idx = {}",
@@ -1840,14 +1863,14 @@ impl PeerNetwork {
         );
 
         for _ in 0..self.walk_pingbacks.len() + 1 {
-            let event_id = match self.peers.keys().skip(idx).next() {
+            let event_id = match event_ids.get(idx) {
                 Some(eid) => *eid,
                 None => {
                     idx = 0;
                     continue;
                 }
             };
-            idx = (idx + 1) % self.peers.len();
+            idx = (idx + 1) % event_ids.len();
 
             let convo = self
                 .peers
@@ -1876,6 +1899,7 @@ impl PeerNetwork {
                 &empty_neighbor,
                 false,
                 self.walk_pingbacks.clone(),
+                self.connection_opts.clock.clone(),
             );
 
             debug!(
@@ -1897,7 +1921,7 @@ impl PeerNetwork {
             return Err(net_error::NoSuchNeighbor);
         }
 
-        let idx = thread_rng().gen::<usize>() % self.walk_pingbacks.len();
+        let idx = self.rng.gen::<usize>() % self.walk_pingbacks.len();
 
         test_debug!(
             "{:?}: try pingback candidates -- sample out of {}. idx = {}",
@@ -1906,8 +1930,10 @@ impl PeerNetwork {
             idx
         );
 
-        let addr = match self.walk_pingbacks.keys().skip(idx).next() {
-            Some(ref addr) => (*addr).clone(),
+        let mut pingback_addrs: Vec<&NeighborAddress> = self.walk_pingbacks.keys().collect();
+        pingback_addrs.sort();
+        let addr = match pingback_addrs.get(idx) {
+            Some(addr) => (*addr).clone(),
             None => {
                 return Err(net_error::NoSuchNeighbor);
             }
@@ -1917,7 +1943,13 @@ impl PeerNetwork {
         let nk = NeighborKey::from_neighbor_address(pb.peer_version, pb.network_id, &addr);
 
         // don't proceed if denied
-        if PeerDB::is_peer_denied(&self.peerdb.conn(), nk.network_id, &nk.addrbytes, nk.port)? {
+        if PeerDB::is_peer_denied(
+            &self.peerdb.conn(),
+            nk.network_id,
+            &nk.addrbytes,
+            nk.port,
+            self.connection_opts.clock.now_secs(),
+        )? {
             debug!(
                 "{:?}: pingback neighbor {:?} is denied",
                 &self.local_peer, &nk
@@ -1934,6 +1966,7 @@ impl PeerNetwork {
             &empty_neighbor,
             false,
             self.walk_pingbacks.clone(),
+            self.connection_opts.clock.clone(),
         );
 
         debug!(
@@ -2349,7 +2382,7 @@ impl PeerNetwork {
                 Some(_neighbor) => {
                     // finished calculating this neighbor's in/out degree.
                     // walk to the next neighbor.
-                    walk.step(network.peerdb.conn());
+                    walk.step(network.peerdb.conn(), &mut network.rng);
                     Ok(true)
                 }
             }
@@ -2370,7 +2403,9 @@ impl PeerNetwork {
                 }
 
                 // pingback hint is stale? (or we tried to connect and timed out?)
-                if pingback.ts + network.connection_opts.pingback_timeout < get_epoch_time_secs() {
+                if pingback.ts + network.connection_opts.pingback_timeout
+                    < network.connection_opts.clock.now_secs()
+                {
                     continue;
                 }
 
@@ -2567,6 +2602,48 @@ impl PeerNetwork {
         }
     }
 
+    /// When can the next walk begin, now that we've finished one?  If we have fewer than
+    /// walk_min_neighbors peers, then walk again right away.  Otherwise, wait for walk_interval
+    /// seconds.
+    fn next_walk_deadline(&self) -> u64 {
+        if (self.num_peers() as u64) < self.connection_opts.walk_min_neighbors {
+            debug!(
+                "{:?}: Only {} peers (need {}); walk again right away",
+                &self.local_peer,
+                self.num_peers(),
+                self.connection_opts.walk_min_neighbors
+            );
+            self.connection_opts.clock.now_secs()
+        } else {
+            self.connection_opts.walk_interval + self.connection_opts.clock.now_secs()
+        }
+    }
+
+    /// Abort the neighbor walk in progress, if there is one, and disconnect the sockets it is
+    /// still connecting to probe neighbors.  Connections the walk already established stay up.
+    /// The walk throttle is reset, so a new walk can begin right away.
+    pub fn cancel_walk(&mut self) -> () {
+        let walk = match self.walk.take() {
+            Some(walk) => walk,
+            None => {
+                return;
+            }
+        };
+
+        debug!(
+            "{:?}: cancel neighbor walk after {} steps",
+            &self.local_peer, walk.walk_step_count
+        );
+        for event_id in walk.events.iter() {
+            if self.is_connecting(*event_id) {
+                self.deregister_peer(*event_id);
+            }
+        }
+
+        self.walk_deadline = 0;
+        self.walk_retries = 0;
+    }
+
     /// Update the state of our peer graph walk.
     /// If we complete a walk, give back a walk result.
     /// Mask errors by restarting the graph walk.
@@ -2577,7 +2654,7 @@ impl PeerNetwork {
             // time to do a walk yet?
             if (self.walk_count > self.connection_opts.num_initial_walks
                 || self.walk_retries > self.connection_opts.walk_retry_count)
-                && self.walk_deadline > get_epoch_time_secs()
+                && self.walk_deadline > self.connection_opts.clock.now_secs()
             {
                 // we've done enough walks for an initial mixing, or we can't connect to anyone,
                 // so throttle ourselves down until the walk deadline passes.
@@ -2624,8 +2701,8 @@ impl PeerNetwork {
                             &self.local_peer, &e
                         );
                         self.walk_retries += 1;
-                        self.walk_deadline =
-                            self.connection_opts.walk_interval + get_epoch_time_secs();
+                        self.walk_deadline = self.connection_opts.walk_interval
+                            + self.connection_opts.clock.now_secs();
                         return (true, None);
                     }
                 },
@@ -2635,7 +2712,8 @@ impl PeerNetwork {
                         &self.local_peer, &e
                     );
                     self.walk_retries += 1;
-                    self.walk_deadline = self.connection_opts.walk_interval + get_epoch_time_secs();
+                    self.walk_deadline =
+                        self.connection_opts.walk_interval + self.connection_opts.clock.now_secs();
                     return (true, None);
                 }
             }
@@ -2725,15 +2803,15 @@ impl PeerNetwork {
                         // finished a walk completely
                         done = true;
                         self.walk_count += 1;
-                        self.walk_deadline =
-                            self.connection_opts.walk_interval + get_epoch_time_secs();
+                        self.walk_deadline = self.next_walk_deadline();
 
                         if self.walk_count > NUM_INITIAL_WALKS
-                            && self.prune_deadline < get_epoch_time_secs()
+                            && self.prune_deadline < self.connection_opts.clock.now_secs()
                         {
                             // clean up
                             walk_result.do_prune = true;
-                            self.prune_deadline = get_epoch_time_secs() + PRUNE_FREQUENCY;
+                            self.prune_deadline =
+                                self.connection_opts.clock.now_secs() + PRUNE_FREQUENCY;
                         }
                     }
                     None => {}
@@ -2754,8 +2832,7 @@ impl PeerNetwork {
                             && walk.walk_step_count >= walk.walk_min_duration
                         {
                             // consider re-setting the walk state, now that we completed a walk.
-                            let mut rng = thread_rng();
-                            let sample: f64 = rng.gen();
+                            let sample: f64 = self.rng.gen();
                             if walk.walk_step_count >= walk.walk_max_duration
                                 || sample < walk.walk_reset_prob
                             {
@@ -2834,7 +2911,9 @@ mod test {
     use net::chat::*;
     use net::db::*;
     use net::test::*;
+    use util::get_epoch_time_secs;
     use util::hash::*;
+    use util::secp256k1::Secp256k1PrivateKey;
     use util::sleep_ms;
     use util::test::*;
 
@@ -3065,6 +3144,41 @@ mod test {
         })
     }
 
+    #[test]
+    #[ignore]
+    fn test_step_walk_1_neighbor_walk_interval() {
+        with_timeout(600, || {
+            let mut peer_1_config = TestPeerConfig::from_port(31960);
+            let peer_2_config = TestPeerConfig::from_port(31962);
+
+            // peer 1 crawls peer 2, and would like to have more peers than it can find
+            peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+            peer_1_config.connection_opts.walk_interval = 3600;
+            peer_1_config.connection_opts.walk_min_neighbors = 10;
+
+            let mut peer_1 = TestPeer::new(peer_1_config);
+            let mut peer_2 = TestPeer::new(peer_2_config);
+
+            // too few neighbors, so walk again right away
+            while peer_1.network.walk_count < 1 {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+            }
+            assert!(peer_1.network.walk_deadline <= get_epoch_time_secs());
+
+            // enough neighbors, so back off
+            peer_1.network.connection_opts.walk_min_neighbors = 1;
+            let walk_count = peer_1.network.walk_count;
+            let walk_start = get_epoch_time_secs();
+            while peer_1.network.walk_count <= walk_count {
+                let _ = peer_1.step();
+                let _ = peer_2.step();
+            }
+            assert!(peer_1.network.walk_deadline >= walk_start + 3600);
+            assert!(peer_1.network.walk_deadline <= get_epoch_time_secs() + 3600);
+        })
+    }
+
     #[test]
     #[ignore]
     fn test_step_walk_1_neighbor_denied() {
@@ -5315,4 +5429,41 @@ mod test {
         dump_peers(&peers);
         dump_peer_histograms(&peers);
     }
+
+    #[test]
+    fn test_pick_random_neighbor_seeded() {
+        let neighbors: Vec<Neighbor> = (0..20)
+            .map(|i| {
+                let nk = NeighborKey {
+                    peer_version: 0x12345678,
+                    network_id: 0x9abcdef0,
+                    addrbytes: PeerAddress::from_ipv4(10, 0, 0, i + 1),
+                    port: 20000 + (i as u16),
+                };
+                let pubk = Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new());
+                Neighbor::empty(&nk, &pubk, 0)
+            })
+            .collect();
+
+        // each frontier hashes its keys differently, so they iterate in different orders
+        let mut picks = vec![];
+        for _ in 0..2 {
+            let mut frontier = HashMap::new();
+            for n in neighbors.iter() {
+                frontier.insert(n.addr.clone(), n.clone());
+            }
+
+            let mut rng = StdRng::seed_from_u64(12345);
+            let frontier_picks: Vec<NeighborKey> = (0..10)
+                .map(|_| {
+                    NeighborWalk::pick_random_neighbor(&frontier, Some(&neighbors[0]), &mut rng)
+                        .unwrap()
+                        .addr
+                })
+                .collect();
+            picks.push(frontier_picks);
+        }
+
+        assert_eq!(picks[0], picks[1]);
+    }
 }
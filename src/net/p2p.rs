@@ -48,6 +48,8 @@ use net::neighbors::*;
 
 use net::prune::*;
 
+use net::publicip::{HttpPublicIpResolver, PublicIpResolver, PublicIpResolverThread};
+
 use net::server::*;
 
 use net::relay::*;
@@ -68,6 +70,7 @@ use std::sync::mpsc::TrySendError;
 
 use std::net::SocketAddr;
 
+use std::cmp;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -84,7 +87,6 @@ use chainstate::stacks::db::StacksChainState;
 
 use chainstate::stacks::{MAX_BLOCK_LEN, MAX_TRANSACTION_LEN};
 
-use util::get_epoch_time_secs;
 use util::log;
 
 use rand::prelude::*;
@@ -105,6 +107,8 @@ pub enum NetworkRequest {
     AdvertizeMicroblocks(BlocksAvailableMap), // announce to all wanting neighbors that we have these confirmed microblock streams
     Relay(NeighborKey, StacksMessage),
     Broadcast(Vec<RelayData>, StacksMessageType),
+    BroadcastTrusted(Vec<RelayData>, StacksMessageType), // broadcast only to allowed neighbors
+    InvSync(NeighborKey), // sync this neighbor's block inventory before any other
 }
 
 /// Handle for other threads to use to issue p2p network requests.
@@ -181,6 +185,24 @@ impl NetworkHandle {
         let req = NetworkRequest::Broadcast(relay_hints, msg);
         self.send_request(req)
     }
+
+    /// Broadcast a message via the p2p network thread, but only to the connected neighbors that
+    /// are allowed in the peer DB.  Nothing is sent if none of them are.
+    pub fn broadcast_to_trusted(
+        &mut self,
+        relay_hints: Vec<RelayData>,
+        msg: StacksMessageType,
+    ) -> Result<(), net_error> {
+        let req = NetworkRequest::BroadcastTrusted(relay_hints, msg);
+        self.send_request(req)
+    }
+
+    /// Have the p2p thread sync this neighbor's block inventory next, connecting to it first if
+    /// need be.
+    pub fn request_inv_sync(&mut self, neighbor: NeighborKey) -> Result<(), net_error> {
+        let req = NetworkRequest::InvSync(neighbor);
+        self.send_request(req)
+    }
 }
 
 impl NetworkHandleServer {
@@ -206,6 +228,96 @@ pub enum PeerNetworkWorkState {
 
 pub type PeerMap = HashMap<usize, ConversationP2P>;
 
+/// Backoff state for a neighbor we keep failing to connect to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectBackoff {
+    /// how many times in a row we failed to connect
+    pub num_failures: u64,
+    /// when we last failed to connect, in seconds
+    pub last_failure_at: u64,
+}
+
+impl ConnectBackoff {
+    pub fn new() -> ConnectBackoff {
+        ConnectBackoff {
+            num_failures: 0,
+            last_failure_at: 0,
+        }
+    }
+
+    pub fn add_failure(&mut self, now: u64) -> () {
+        self.num_failures += 1;
+        self.last_failure_at = now;
+    }
+
+    /// How long to wait after the last failure before connecting again.
+    /// A single failure is forgiven; after that, the cooldown starts at `backoff` seconds and
+    /// doubles with each consecutive failure, up to `max_backoff` seconds.
+    pub fn cooldown(&self, backoff: u64, max_backoff: u64) -> u64 {
+        if self.num_failures < 2 {
+            return 0;
+        }
+        let shift = cmp::min(self.num_failures - 2, 63) as u32;
+        cmp::min(backoff.saturating_mul(1u64 << shift), max_backoff)
+    }
+
+    /// Are we still waiting to reconnect?
+    pub fn is_cooling_down(&self, backoff: u64, max_backoff: u64, now: u64) -> bool {
+        self.last_failure_at + self.cooldown(backoff, max_backoff) > now
+    }
+}
+
+/// Token bucket that limits how many inbound messages we will process from a single peer.
+/// The bucket holds up to `burst` tokens, and refills at `messages_per_sec` tokens per second.
+/// A `burst` of 0 means "unset", in which case the bucket holds one second's worth of messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageRateLimiter {
+    messages_per_sec: u64,
+    burst: u64,
+    tokens: f64,
+    last_refill_ms: u128,
+    /// number of messages dropped since the peer last stayed within its rate
+    pub num_dropped: u64,
+}
+
+impl MessageRateLimiter {
+    pub fn new(messages_per_sec: u64, burst: u64, now_ms: u128) -> MessageRateLimiter {
+        let burst = if burst == 0 { messages_per_sec } else { burst };
+        MessageRateLimiter {
+            messages_per_sec: messages_per_sec,
+            burst: burst,
+            tokens: burst as f64,
+            last_refill_ms: now_ms,
+            num_dropped: 0,
+        }
+    }
+
+    /// Refill the bucket, and spend tokens on up to `num_messages` messages.
+    /// Returns how many of the messages may be processed.
+    pub fn admit(&mut self, num_messages: usize, now_ms: u128) -> usize {
+        let elapsed_ms = now_ms.saturating_sub(self.last_refill_ms);
+        self.last_refill_ms = cmp::max(self.last_refill_ms, now_ms);
+        self.tokens = (self.tokens + (elapsed_ms as f64) * (self.messages_per_sec as f64) / 1000.0)
+            .min(self.burst as f64);
+
+        let num_admitted = cmp::min(num_messages as u64, self.tokens.floor() as u64);
+        self.tokens -= num_admitted as f64;
+
+        if num_admitted < (num_messages as u64) {
+            self.num_dropped += (num_messages as u64) - num_admitted;
+        } else {
+            self.num_dropped = 0;
+        }
+        num_admitted as usize
+    }
+
+    /// Has this peer sent us more than a whole burst's worth of messages over its rate, without
+    /// ever slowing down?  If so, it should be banned.
+    pub fn is_violating(&self) -> bool {
+        self.num_dropped > self.burst
+    }
+}
+
 pub struct PeerNetwork {
     pub local_peer: LocalPeer,
     pub peer_version: u32,
@@ -218,7 +330,10 @@ pub struct PeerNetwork {
     pub sockets: HashMap<usize, mio_net::TcpStream>,
     pub events: HashMap<NeighborKey, usize>,
     pub connecting: HashMap<usize, (mio_net::TcpStream, bool, u64)>, // (socket, outbound?, connection sent timestamp)
+    pub connecting_neighbors: HashMap<usize, NeighborKey>, // who we're trying to connect to on each connecting event
+    pub connect_backoffs: HashMap<NeighborKey, ConnectBackoff>,
     pub bans: HashSet<usize>,
+    pub rate_limiters: HashMap<usize, MessageRateLimiter>,
 
     // ongoing messages the network is sending via the p2p interface (not bound to a specific
     // conversation).
@@ -239,6 +354,9 @@ pub struct PeerNetwork {
     // connection options
     pub connection_opts: ConnectionOptions,
 
+    // source of randomness for neighbor sampling (seeded if connection_opts.rng_seed is set)
+    pub rng: StdRng,
+
     // work state -- we can be walking, fetching block inventories, fetching blocks, pruning, etc.
     pub work_state: PeerNetworkWorkState,
 
@@ -288,6 +406,7 @@ pub struct PeerNetwork {
     public_ip_learned_at: u64,
     public_ip_reply_handle: Option<ReplyHandleP2P>,
     public_ip_retries: u64,
+    public_ip_resolver: Option<PublicIpResolverThread>,
 }
 
 impl PeerNetwork {
@@ -309,6 +428,16 @@ impl PeerNetwork {
         let pub_ip = connection_opts.public_ip_address.clone();
         let pub_ip_learned = pub_ip.is_none();
         local_peer.public_ip_address = pub_ip.clone();
+        let rng = match connection_opts.rng_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let pub_ip_resolver = connection_opts.public_ip_resolver_url.as_ref().map(|url| {
+            PublicIpResolverThread::spawn(Box::new(HttpPublicIpResolver::new(
+                url,
+                connection_opts.timeout,
+            )))
+        });
         PeerNetwork {
             local_peer: local_peer,
             peer_version: peer_version,
@@ -320,7 +449,10 @@ impl PeerNetwork {
             sockets: HashMap::new(),
             events: HashMap::new(),
             connecting: HashMap::new(),
+            connecting_neighbors: HashMap::new(),
+            connect_backoffs: HashMap::new(),
             bans: HashSet::new(),
+            rate_limiters: HashMap::new(),
 
             relay_handles: HashMap::new(),
             relayer_stats: RelayerStats::new(),
@@ -332,6 +464,7 @@ impl PeerNetwork {
 
             burnchain: burnchain,
             connection_opts: connection_opts,
+            rng: rng,
 
             work_state: PeerNetworkWorkState::GetPublicIP,
 
@@ -371,6 +504,7 @@ impl PeerNetwork {
             public_ip_confirmed: false,
             public_ip_reply_handle: None,
             public_ip_retries: 0,
+            public_ip_resolver: pub_ip_resolver,
         }
     }
 
@@ -516,14 +650,25 @@ impl PeerNetwork {
         Ok(rh)
     }
 
+    /// Queue up a relay handle to be flushed to a peer.
+    /// If the peer already has too many relay handles pending, then the new one is dropped (along
+    /// with the message it was carrying).
     fn add_relay_handle(&mut self, event_id: usize, relay_handle: ReplyHandleP2P) -> () {
-        if let Some(handle_list) = self.relay_handles.get_mut(&event_id) {
-            handle_list.push_back(relay_handle);
-        } else {
-            let mut handle_list = VecDeque::new();
-            handle_list.push_back(relay_handle);
-            self.relay_handles.insert(event_id, handle_list);
+        let max_handles = self.connection_opts.max_relay_handles_per_peer;
+        let handle_list = self
+            .relay_handles
+            .entry(event_id)
+            .or_insert_with(VecDeque::new);
+
+        // never evict queued handles -- the one at the front may be partway onto the wire
+        if max_handles > 0 && handle_list.len() >= max_handles {
+            warn!(
+                "{:?}: Event {} already has {} pending relay handles; dropping the new one",
+                &self.local_peer, event_id, max_handles
+            );
+            return;
         }
+        handle_list.push_back(relay_handle);
     }
 
     /// Relay a signed message to a peer.
@@ -671,6 +816,28 @@ impl PeerNetwork {
     ) -> Result<usize, net_error> {
         debug!("{:?}: connect to {:?}", &self.local_peer, neighbor);
 
+        // don't keep hammering peers we can't reach
+        if let Some(backoff) = self.connect_backoffs.get(neighbor) {
+            if backoff.is_cooling_down(
+                self.connection_opts.connect_backoff,
+                self.connection_opts.max_connect_backoff,
+                self.connection_opts.clock.now_secs(),
+            ) {
+                debug!(
+                    "{:?}: will not connect to {:?} until {} ({} consecutive failures)",
+                    &self.local_peer,
+                    neighbor,
+                    backoff.last_failure_at
+                        + backoff.cooldown(
+                            self.connection_opts.connect_backoff,
+                            self.connection_opts.max_connect_backoff
+                        ),
+                    backoff.num_failures
+                );
+                return Err(net_error::NoSuchNeighbor);
+            }
+        }
+
         if check_denied {
             // don't talk to our bind address
             if self.is_bound(neighbor) {
@@ -687,6 +854,7 @@ impl PeerNetwork {
                 neighbor.network_id,
                 &neighbor.addrbytes,
                 neighbor.port,
+                self.connection_opts.clock.now_secs(),
             )? {
                 debug!(
                     "{:?}: Neighbor {:?} is denied; will not connect",
@@ -711,13 +879,27 @@ impl PeerNetwork {
                 return Err(net_error::NotConnected);
             }
             Some(ref mut network) => {
-                let sock = NetworkState::connect(&neighbor.addrbytes.to_socketaddr(neighbor.port))?;
+                let sock =
+                    match NetworkState::connect(&neighbor.addrbytes.to_socketaddr(neighbor.port)) {
+                        Ok(sock) => sock,
+                        Err(e) => {
+                            self.connect_backoffs
+                                .entry(neighbor.clone())
+                                .or_insert_with(ConnectBackoff::new)
+                                .add_failure(self.connection_opts.clock.now_secs());
+                            return Err(e);
+                        }
+                    };
                 let hint_event_id = network.next_event_id()?;
                 let registered_event_id =
                     network.register(self.p2p_network_handle, hint_event_id, &sock)?;
 
-                self.connecting
-                    .insert(registered_event_id, (sock, true, get_epoch_time_secs()));
+                self.connecting.insert(
+                    registered_event_id,
+                    (sock, true, self.connection_opts.clock.now_secs()),
+                );
+                self.connecting_neighbors
+                    .insert(registered_event_id, neighbor.clone());
                 registered_event_id
             }
         };
@@ -731,7 +913,7 @@ impl PeerNetwork {
     /// The outbound will be sampled according to their AS distribution
     /// The inbound will be sampled according to how rarely they send duplicate messages
     fn sample_broadcast_peers<R: RelayPayload>(
-        &self,
+        &mut self,
         relay_hints: &Vec<RelayData>,
         payload: &R,
     ) -> Result<Vec<NeighborKey>, net_error> {
@@ -790,10 +972,16 @@ impl PeerNetwork {
             &outbound_dist
         );
 
-        let mut outbound_sample =
-            RelayerStats::sample_neighbors(outbound_dist, MAX_BROADCAST_OUTBOUND_RECEIVERS);
-        let mut inbound_sample =
-            RelayerStats::sample_neighbors(inbound_dist, MAX_BROADCAST_INBOUND_RECEIVERS);
+        let mut outbound_sample = RelayerStats::sample_neighbors(
+            outbound_dist,
+            MAX_BROADCAST_OUTBOUND_RECEIVERS,
+            &mut self.rng,
+        );
+        let mut inbound_sample = RelayerStats::sample_neighbors(
+            inbound_dist,
+            MAX_BROADCAST_INBOUND_RECEIVERS,
+            &mut self.rng,
+        );
 
         debug!(
             "Inbound recipients (out of {}): {:?}",
@@ -810,6 +998,87 @@ impl PeerNetwork {
         Ok(outbound_sample)
     }
 
+    /// Pick the neighbors to broadcast a message to.  Note that only some messages can be
+    /// broadcasted.
+    fn sample_broadcast_recipients(
+        &mut self,
+        relay_hints: &Vec<RelayData>,
+        msg: &StacksMessageType,
+    ) -> Result<Vec<NeighborKey>, net_error> {
+        match *msg {
+            StacksMessageType::Blocks(ref data) => {
+                // send to each neighbor that needs one
+                let mut all_neighbors = HashSet::new();
+                for (_, block) in data.blocks.iter() {
+                    let mut neighbors = self.sample_broadcast_peers(relay_hints, block)?;
+                    for nk in neighbors.drain(..) {
+                        all_neighbors.insert(nk);
+                    }
+                }
+                Ok(all_neighbors.into_iter().collect())
+            }
+            StacksMessageType::Microblocks(ref data) => {
+                // send to each neighbor that needs at least one
+                let mut all_neighbors = HashSet::new();
+                for mblock in data.microblocks.iter() {
+                    let mut neighbors = self.sample_broadcast_peers(relay_hints, mblock)?;
+                    for nk in neighbors.drain(..) {
+                        all_neighbors.insert(nk);
+                    }
+                }
+                Ok(all_neighbors.into_iter().collect())
+            }
+            StacksMessageType::Transaction(ref data) => {
+                self.sample_broadcast_peers(relay_hints, data)
+            }
+            _ => {
+                // not suitable for broadcast
+                Err(net_error::InvalidMessage)
+            }
+        }
+    }
+
+    /// Pick every connected neighbor that is allowed in the peer DB to receive a broadcast
+    /// message, except for the ones that already relayed it to us.  Unlike
+    /// sample_broadcast_recipients(), this does not sample -- trusted peers always get it.
+    fn get_trusted_broadcast_recipients(
+        &self,
+        relay_hints: &Vec<RelayData>,
+        msg: &StacksMessageType,
+    ) -> Result<Vec<NeighborKey>, net_error> {
+        match *msg {
+            StacksMessageType::Blocks(_)
+            | StacksMessageType::Microblocks(_)
+            | StacksMessageType::Transaction(_) => {}
+            _ => {
+                // not suitable for broadcast
+                return Err(net_error::InvalidMessage);
+            }
+        }
+
+        let now = self.connection_opts.clock.now_secs();
+        let mut trusted_keys = vec![];
+        for (_, convo) in self.peers.iter() {
+            if let Some(pubkey) = convo.ref_public_key() {
+                let pubkey_hash = Hash160::from_data(&pubkey.to_bytes());
+                if relay_hints
+                    .iter()
+                    .any(|rhint| rhint.peer.public_key_hash == pubkey_hash)
+                {
+                    // already saw this message
+                    continue;
+                }
+            }
+
+            let nk = convo.to_neighbor_key();
+            match PeerDB::get_peer(self.peerdb.conn(), nk.network_id, &nk.addrbytes, nk.port)? {
+                Some(ref neighbor) if neighbor.is_allowed_at(now) => trusted_keys.push(nk),
+                _ => {}
+            }
+        }
+        Ok(trusted_keys)
+    }
+
     /// Dispatch a single request from another thread.
     fn dispatch_request(&mut self, request: NetworkRequest) -> Result<(), net_error> {
         match request {
@@ -842,42 +1111,36 @@ impl PeerNetwork {
                 .relay_signed_message(&neighbor_key, msg)
                 .and_then(|_| Ok(())),
             NetworkRequest::Broadcast(relay_hints, msg) => {
-                // pick some neighbors. Note that only some messages can be broadcasted.
-                let neighbor_keys = match msg {
-                    StacksMessageType::Blocks(ref data) => {
-                        // send to each neighbor that needs one
-                        let mut all_neighbors = HashSet::new();
-                        for (_, block) in data.blocks.iter() {
-                            let mut neighbors = self.sample_broadcast_peers(&relay_hints, block)?;
-                            for nk in neighbors.drain(..) {
-                                all_neighbors.insert(nk);
-                            }
-                        }
-                        Ok(all_neighbors.into_iter().collect())
-                    }
-                    StacksMessageType::Microblocks(ref data) => {
-                        // send to each neighbor that needs at least one
-                        let mut all_neighbors = HashSet::new();
-                        for mblock in data.microblocks.iter() {
-                            let mut neighbors =
-                                self.sample_broadcast_peers(&relay_hints, mblock)?;
-                            for nk in neighbors.drain(..) {
-                                all_neighbors.insert(nk);
-                            }
-                        }
-                        Ok(all_neighbors.into_iter().collect())
-                    }
-                    StacksMessageType::Transaction(ref data) => {
-                        self.sample_broadcast_peers(&relay_hints, data)
-                    }
-                    _ => {
-                        // not suitable for broadcast
-                        return Err(net_error::InvalidMessage);
-                    }
-                }?;
+                let neighbor_keys = self.sample_broadcast_recipients(&relay_hints, &msg)?;
                 self.broadcast_message(neighbor_keys, relay_hints, msg);
                 Ok(())
             }
+            NetworkRequest::BroadcastTrusted(relay_hints, msg) => {
+                let trusted_keys = self.get_trusted_broadcast_recipients(&relay_hints, &msg)?;
+                if trusted_keys.is_empty() {
+                    info!(
+                        "{:?}: No trusted peers connected; will not broadcast '{}'",
+                        &self.local_peer,
+                        msg.get_message_description()
+                    );
+                    return Ok(());
+                }
+                self.broadcast_message(trusted_keys, relay_hints, msg);
+                Ok(())
+            }
+            NetworkRequest::InvSync(neighbor_key) => {
+                if !self.is_registered(&neighbor_key) {
+                    debug!(
+                        "{:?}: not connected to {:?}; connecting before inv sync",
+                        &self.local_peer, &neighbor_key
+                    );
+                    self.connect_peer(&neighbor_key)?;
+                }
+                PeerNetwork::with_inv_state(self, |_network, inv_state| {
+                    inv_state.prioritize_peer(neighbor_key);
+                    Ok(())
+                })
+            }
         }
     }
 
@@ -951,7 +1214,7 @@ impl PeerNetwork {
             let (neighbor_key, neighbor_info_opt) = match self.peers.get(&event_id) {
                 Some(convo) => match Neighbor::from_conversation(&tx, convo)? {
                     Some(neighbor) => {
-                        if neighbor.is_allowed() {
+                        if neighbor.is_allowed_at(self.connection_opts.clock.now_secs()) {
                             debug!(
                                 "Misbehaving neighbor {:?} is allowed; will not punish",
                                 &neighbor.addr
@@ -975,7 +1238,7 @@ impl PeerNetwork {
 
             disconnect.push(event_id);
 
-            let now = get_epoch_time_secs();
+            let now = self.connection_opts.clock.now_secs();
             let penalty = if let Some(neighbor_info) = neighbor_info_opt {
                 if neighbor_info.denied < 0
                     || (neighbor_info.denied as u64) < now + DENY_MIN_BAN_DURATION
@@ -1104,6 +1367,7 @@ impl PeerNetwork {
             neighbor_key.network_id,
             &neighbor_key.addrbytes,
             neighbor_key.port,
+            self.connection_opts.clock.now_secs(),
         )? {
             info!(
                 "{:?}: Peer {:?} is denied; dropping",
@@ -1246,6 +1510,21 @@ impl PeerNetwork {
         self.events.contains_key(neighbor_key)
     }
 
+    /// Are we still connecting to, or handshaking with, a remote host?
+    pub fn is_peer_pending(&self, neighbor_key: &NeighborKey) -> bool {
+        if self
+            .connecting_neighbors
+            .values()
+            .any(|nk| nk == neighbor_key)
+        {
+            return true;
+        }
+        match self.get_convo(neighbor_key) {
+            Some(convo) => !convo.is_authenticated(),
+            None => false,
+        }
+    }
+
     /// Get the event ID associated with a neighbor key
     pub fn get_event_id(&self, neighbor_key: &NeighborKey) -> Option<usize> {
         let event_id_opt = match self.events.get(neighbor_key) {
@@ -1320,9 +1599,35 @@ impl PeerNetwork {
         }
 
         self.relay_handles.remove(&event_id);
+        self.rate_limiters.remove(&event_id);
+        self.connecting_neighbors.remove(&event_id);
         self.peers.remove(&event_id);
     }
 
+    /// Disconnect from every peer, including ones we're still connecting to, and clear out all
+    /// per-peer state.  The network stays bound, so new peers can still connect afterwards.
+    pub fn disconnect_all(&mut self) -> () {
+        let mut event_ids: HashSet<usize> = HashSet::new();
+        event_ids.extend(self.peers.keys());
+        event_ids.extend(self.sockets.keys());
+        event_ids.extend(self.connecting.keys());
+        event_ids.extend(self.events.values());
+        event_ids.extend(self.relay_handles.keys());
+
+        debug!(
+            "{:?}: Disconnect all {} events",
+            &self.local_peer,
+            event_ids.len()
+        );
+        for event_id in event_ids.into_iter() {
+            self.deregister_peer(event_id);
+        }
+
+        // sockets only get deregistered above if we have a poller
+        self.sockets.clear();
+        self.connecting.clear();
+    }
+
     /// Deregister by neighbor key
     pub fn deregister_neighbor(&mut self, neighbor_key: &NeighborKey) -> () {
         debug!("Disconnect from {:?}", neighbor_key);
@@ -1432,7 +1737,35 @@ impl PeerNetwork {
         Ok(registered)
     }
 
+    /// Drop inbound messages in a conversation's inbox that exceed the peer's message rate.
+    /// Returns the number of messages dropped.
+    fn rate_limit_p2p_conversation(
+        local_peer: &LocalPeer,
+        event_id: usize,
+        convo: &mut ConversationP2P,
+        rate_limiter: &mut MessageRateLimiter,
+        now_ms: u128,
+    ) -> usize {
+        let num_inbound = convo.connection.inbox_len();
+        let num_admitted = rate_limiter.admit(num_inbound, now_ms);
+        if num_admitted < num_inbound {
+            debug!(
+                "{:?}: Drop {} of {} inbound messages from event {}: rate limit exceeded",
+                local_peer,
+                num_inbound - num_admitted,
+                num_inbound,
+                event_id
+            );
+            for _ in num_admitted..num_inbound {
+                convo.connection.next_inbox_message();
+            }
+        }
+        num_inbound - num_admitted
+    }
+
     /// Process network traffic on a p2p conversation.
+    /// If a rate limiter is given, then messages received in excess of the peer's rate as of
+    /// `now_ms` are dropped.
     /// Returns list of unhandled messages, and whether or not the convo is still alive.
     fn process_p2p_conversation(
         local_peer: &LocalPeer,
@@ -1445,6 +1778,8 @@ impl PeerNetwork {
         event_id: usize,
         client_sock: &mut mio_net::TcpStream,
         convo: &mut ConversationP2P,
+        rate_limiter: Option<&mut MessageRateLimiter>,
+        now_ms: u128,
     ) -> Result<(Vec<StacksMessage>, bool), net_error> {
         // get incoming bytes and update the state of this conversation.
         let mut convo_dead = false;
@@ -1459,6 +1794,14 @@ impl PeerNetwork {
                             local_peer, event_id, &client_sock
                         );
                     }
+                    net_error::OversizedMessage(..) => {
+                        // don't bother with anything else this peer sent
+                        info!(
+                            "{:?}: Drop oversized message on event {} (socket {:?}): {:?}",
+                            local_peer, event_id, &client_sock, &e
+                        );
+                        return Err(e);
+                    }
                     _ => {
                         debug!(
                             "{:?}: Failed to receive data on event {} (socket {:?}): {:?}",
@@ -1471,6 +1814,16 @@ impl PeerNetwork {
             Ok(_) => {}
         }
 
+        if let Some(rate_limiter) = rate_limiter {
+            PeerNetwork::rate_limit_p2p_conversation(
+                local_peer,
+                event_id,
+                convo,
+                rate_limiter,
+                now_ms,
+            );
+        }
+
         // react to inbound messages -- do we need to send something out, or fulfill requests
         // to other threads?  Try to chat even if the recv() failed, since we'll want to at
         // least drain the conversation inbox.
@@ -1514,6 +1867,16 @@ impl PeerNetwork {
         Ok((unhandled, !convo_dead))
     }
 
+    /// Record that we failed to connect to whomever we were trying to reach on this event
+    fn add_connect_failure(&mut self, event_id: usize) -> () {
+        if let Some(nk) = self.connecting_neighbors.remove(&event_id) {
+            self.connect_backoffs
+                .entry(nk)
+                .or_insert_with(ConnectBackoff::new)
+                .add_failure(self.connection_opts.clock.now_secs());
+        }
+    }
+
     /// Process any newly-connecting sockets
     fn process_connecting_sockets(&mut self, poll_state: &mut NetworkPollState) -> () {
         for event_id in poll_state.ready.iter() {
@@ -1525,11 +1888,15 @@ impl PeerNetwork {
                         "{:?}: Failed to register connecting socket on event {} ({}): {:?}",
                         &self.local_peer, event_id, sock_str, &_e
                     );
+                    self.add_connect_failure(*event_id);
                 } else {
                     debug!(
                         "{:?}: Registered peer on event {}: {:?} (outbound={})",
                         &self.local_peer, event_id, sock_str, outbound
                     );
+                    if let Some(nk) = self.connecting_neighbors.remove(event_id) {
+                        self.connect_backoffs.remove(&nk);
+                    }
                 }
             }
         }
@@ -1567,6 +1934,17 @@ impl PeerNetwork {
                 Some(ref mut convo) => {
                     // activity on a p2p socket
                     debug!("{:?}: process p2p data from {:?}", &self.local_peer, convo);
+                    let now_ms = self.connection_opts.clock.now_ms();
+                    let rate_limiter = if self.connection_opts.max_p2p_messages_per_sec > 0 {
+                        let messages_per_sec = self.connection_opts.max_p2p_messages_per_sec;
+                        let burst = self.connection_opts.max_p2p_messages_burst;
+                        Some(self.rate_limiters.entry(*event_id).or_insert_with(|| {
+                            MessageRateLimiter::new(messages_per_sec, burst, now_ms)
+                        }))
+                    } else {
+                        None
+                    };
+
                     let mut convo_unhandled = match PeerNetwork::process_p2p_conversation(
                         &self.local_peer,
                         &mut self.peerdb,
@@ -1578,6 +1956,8 @@ impl PeerNetwork {
                         *event_id,
                         client_sock,
                         convo,
+                        rate_limiter,
+                        now_ms,
                     ) {
                         Ok((convo_unhandled, alive)) => {
                             if !alive {
@@ -1586,8 +1966,9 @@ impl PeerNetwork {
                             }
                             convo_unhandled
                         }
-                        Err(_e) => {
-                            test_debug!("Connection to {:?} failed: {:?}", &convo, &_e);
+                        Err(e) => {
+                            test_debug!("Connection to {:?} failed: {:?}", &convo, &e);
+                            self.ban_if_oversized(*event_id, &e);
                             to_remove.push(*event_id);
                             continue;
                         }
@@ -1608,11 +1989,48 @@ impl PeerNetwork {
                     to_remove.push(*event_id);
                 }
             }
+
+            // ban peers that keep flooding us
+            self.ban_if_rate_limited(*event_id);
         }
 
         (to_remove, unhandled)
     }
 
+    /// Ban a peer if it has kept sending us messages in excess of its rate limit.
+    /// Returns true if the peer was banned.
+    fn ban_if_rate_limited(&mut self, event_id: usize) -> bool {
+        let num_dropped = match self.rate_limiters.get(&event_id) {
+            Some(rate_limiter) if rate_limiter.is_violating() => rate_limiter.num_dropped,
+            _ => {
+                return false;
+            }
+        };
+
+        info!(
+            "{:?}: Ban event {}: dropped {} messages over its rate limit",
+            &self.local_peer, event_id, num_dropped
+        );
+        self.bans.insert(event_id);
+        true
+    }
+
+    /// Ban a peer if it sent us a message bigger than the largest one we would accept.
+    /// Returns true if the peer was banned.
+    fn ban_if_oversized(&mut self, event_id: usize, error: &net_error) -> bool {
+        match *error {
+            net_error::OversizedMessage(ref len, ref max_len) => {
+                info!(
+                    "{:?}: Ban event {}: sent a {}-byte message (max is {})",
+                    &self.local_peer, event_id, len, max_len
+                );
+                self.bans.insert(event_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Get stats for a neighbor
     pub fn get_neighbor_stats(&self, nk: &NeighborKey) -> Option<NeighborStats> {
         match self.events.get(&nk) {
@@ -1624,6 +2042,28 @@ impl PeerNetwork {
         }
     }
 
+    /// Summarize what we've learned from each neighbor's block inventory, as a list of
+    /// (neighbor, number of anchored blocks, number of confirmed microblock streams), sorted by
+    /// neighbor.  Empty if we haven't begun synchronizing inventories.
+    pub fn get_neighbor_inventory_summary(&self) -> Vec<(NeighborKey, u64, u64)> {
+        let mut summary = match self.inv_state {
+            Some(ref inv_state) => inv_state
+                .block_stats
+                .iter()
+                .map(|(nk, stats)| {
+                    (
+                        nk.clone(),
+                        stats.inv.num_blocks(),
+                        stats.inv.num_microblock_streams(),
+                    )
+                })
+                .collect(),
+            None => vec![],
+        };
+        summary.sort();
+        summary
+    }
+
     /// Update peer connections as a result of a peer graph walk.
     /// -- Drop broken connections.
     /// -- Update our frontier.
@@ -1648,7 +2088,7 @@ impl PeerNetwork {
     /// Queue up pings to everyone we haven't spoken to in a while to let them know that we're still
     /// alive.
     pub fn queue_ping_heartbeats(&mut self) -> () {
-        let now = get_epoch_time_secs();
+        let now = self.connection_opts.clock.now_secs();
         let mut relay_handles = HashMap::new();
         for (_, convo) in self.peers.iter_mut() {
             if convo.is_outbound()
@@ -1690,7 +2130,7 @@ impl PeerNetwork {
 
     /// Remove unresponsive peers
     fn disconnect_unresponsive(&mut self) -> usize {
-        let now = get_epoch_time_secs();
+        let now = self.connection_opts.clock.now_secs();
         let mut to_remove = vec![];
         for (event_id, (socket, _, ts)) in self.connecting.iter() {
             if ts + self.connection_opts.connect_timeout < now {
@@ -1700,6 +2140,23 @@ impl PeerNetwork {
         }
 
         for (event_id, convo) in self.peers.iter() {
+            if self.connection_opts.peer_idle_timeout > 0 {
+                let last_recv_time = cmp::max(convo.stats.last_recv_time, convo.instantiated);
+                if last_recv_time + self.connection_opts.peer_idle_timeout < now {
+                    // this peer has gone silent
+                    debug!(
+                        "{:?}: Disconnect idle peer {:?}: {} + {} < {}",
+                        &self.local_peer,
+                        &convo,
+                        last_recv_time,
+                        self.connection_opts.peer_idle_timeout,
+                        now
+                    );
+                    to_remove.push(*event_id);
+                    continue;
+                }
+            }
+
             if convo.is_authenticated() {
                 // have handshaked with this remote peer
                 if convo.stats.last_contact_time
@@ -1737,11 +2194,25 @@ impl PeerNetwork {
 
         let ret = to_remove.len();
         for event_id in to_remove.into_iter() {
+            if self.connecting.contains_key(&event_id) {
+                self.add_connect_failure(event_id);
+            }
             self.deregister_peer(event_id);
         }
         ret
     }
 
+    /// Forget connection failures for peers we have not failed to reach in a long time, so
+    /// connect_backoffs does not grow with every neighbor we have ever tried.  An entry is stale
+    /// once twice the maximum backoff has passed since its last failure -- by then its cooldown
+    /// has long expired and we have evidently stopped trying the peer.
+    fn prune_connect_backoffs(&mut self) -> () {
+        let now = self.connection_opts.clock.now_secs();
+        let expiry = self.connection_opts.max_connect_backoff.saturating_mul(2);
+        self.connect_backoffs
+            .retain(|_, backoff| backoff.last_failure_at.saturating_add(expiry) >= now);
+    }
+
     /// Prune inbound and outbound connections if we can
     fn prune_connections(&mut self) -> () {
         if cfg!(test) && self.connection_opts.disable_network_prune {
@@ -1750,7 +2221,7 @@ impl PeerNetwork {
 
         test_debug!("Prune connections");
         let mut safe: HashSet<usize> = HashSet::new();
-        let now = get_epoch_time_secs();
+        let now = self.connection_opts.clock.now_secs();
 
         // don't prune allowed peers
         for (nk, event_id) in self.events.iter() {
@@ -2006,17 +2477,98 @@ impl PeerNetwork {
         return Ok(true);
     }
 
+    /// Set the public IP address we learned.
+    /// If it changed, then disconnect from everyone so we can re-handshake with the new address.
+    fn adopt_public_ip(&mut self, addrbytes: PeerAddress) -> () {
+        self.public_ip_confirmed = true;
+        self.public_ip_learned_at = self.connection_opts.clock.now_secs();
+        self.public_ip_retries = 0;
+
+        // if our IP address changed, then disconnect witih everyone
+        let old_ip = self.local_peer.public_ip_address.clone();
+        self.local_peer.public_ip_address = Some((addrbytes, self.bind_nk.port));
+
+        if old_ip != self.local_peer.public_ip_address {
+            let mut all_event_ids = vec![];
+            for (eid, _) in self.peers.iter() {
+                all_event_ids.push(*eid);
+            }
+
+            info!("IP address changed from {:?} to {:?}; closing all connections and re-establishing them", &old_ip, &self.local_peer.public_ip_address);
+            for eid in all_event_ids.into_iter() {
+                self.deregister_peer(eid);
+            }
+        }
+    }
+
+    /// Use a different resolver to learn our public IP address, instead of the one (if any) given
+    /// by the connection options.
+    pub fn set_public_ip_resolver(&mut self, resolver: Box<dyn PublicIpResolver>) -> () {
+        self.public_ip_resolver = Some(PublicIpResolverThread::spawn(resolver));
+    }
+
+    /// Try to learn our public IP address from our public IP resolver, if we have one.
+    /// The lookup runs on the resolver's own thread; this only starts it or checks on it.
+    /// Returns Some(true) if we learned it, Some(false) if the lookup is still in progress, and
+    /// None if we have no resolver or it failed.
+    fn try_resolve_public_ip(&mut self) -> Option<bool> {
+        let resolver = self.public_ip_resolver.as_mut()?;
+        if !resolver.is_inflight() {
+            if let Err(e) = resolver.begin_lookup() {
+                info!(
+                    "{:?}: Failed to start resolving my IP address ({:?}); will ask my neighbors",
+                    &self.local_peer, &e
+                );
+                return None;
+            }
+            self.public_ip_requested_at = self.connection_opts.clock.now_secs();
+            return Some(false);
+        }
+
+        let resolved = match resolver.poll_lookup() {
+            Some(resolved) => resolved,
+            None => {
+                // still looking it up
+                return Some(false);
+            }
+        };
+        match resolved {
+            Ok(addrbytes) => {
+                info!(
+                    "{:?}: resolved my IP address to {:?}",
+                    &self.local_peer, &addrbytes
+                );
+                self.adopt_public_ip(addrbytes);
+                Some(true)
+            }
+            Err(e) => {
+                info!(
+                    "{:?}: Failed to resolve my IP address ({:?}); will ask my neighbors",
+                    &self.local_peer, &e
+                );
+                None
+            }
+        }
+    }
+
     /// Learn this peer's public IP address.
     /// If it was given to us directly, then we can just skip this step.
-    /// Once learned, we'll confirm it by trying to self-connect.
+    /// If we have a public IP resolver, then we'll try it first, and fall back to asking one of
+    /// our initial neighbors if it fails.
     fn do_learn_public_ip(&mut self) -> Result<bool, net_error> {
+        if self.public_ip_reply_handle.is_none() {
+            if let Some(resolved) = self.try_resolve_public_ip() {
+                return Ok(resolved);
+            }
+        }
+
         if self.public_ip_reply_handle.is_none() {
             if !self.begin_learn_public_ip()? {
                 return Ok(false);
             }
 
             // began request
-            self.public_ip_requested_at = get_epoch_time_secs();
+            self.public_ip_requested_at = self.connection_opts.clock.now_secs();
             self.public_ip_retries += 1;
         }
 
@@ -2044,26 +2596,7 @@ impl PeerNetwork {
                             "{:?}: learned that my IP address is {:?}",
                             &self.local_peer, &data.addrbytes
                         );
-                        self.public_ip_confirmed = true;
-                        self.public_ip_learned_at = get_epoch_time_secs();
-                        self.public_ip_retries = 0;
-
-                        // if our IP address changed, then disconnect witih everyone
-                        let old_ip = self.local_peer.public_ip_address.clone();
-                        self.local_peer.public_ip_address =
-                            Some((data.addrbytes, self.bind_nk.port));
-
-                        if old_ip != self.local_peer.public_ip_address {
-                            let mut all_event_ids = vec![];
-                            for (eid, _) in self.peers.iter() {
-                                all_event_ids.push(*eid);
-                            }
-
-                            info!("IP address changed from {:?} to {:?}; closing all connections and re-establishing them", &old_ip, &self.local_peer.public_ip_address);
-                            for eid in all_event_ids.into_iter() {
-                                self.deregister_peer(eid);
-                            }
-                        }
+                        self.adopt_public_ip(data.addrbytes);
                         return Ok(true);
                     }
                     other_payload => {
@@ -2106,7 +2639,7 @@ impl PeerNetwork {
         }
         if self.local_peer.public_ip_address.is_some()
             && self.public_ip_learned_at + self.connection_opts.public_ip_timeout
-                >= get_epoch_time_secs()
+                >= self.connection_opts.clock.now_secs()
         {
             // still fresh
             test_debug!("{:?}: learned IP address is still fresh", &self.local_peer);
@@ -2119,7 +2652,9 @@ impl PeerNetwork {
         };
 
         if self.public_ip_retries > self.connection_opts.public_ip_max_retries {
-            if self.public_ip_requested_at + throttle_timeout >= get_epoch_time_secs() {
+            if self.public_ip_requested_at + throttle_timeout
+                >= self.connection_opts.clock.now_secs()
+            {
                 // throttle
                 debug!(
                     "{:?}: throttle public IP request (max retries {} exceeded) until {}",
@@ -2157,7 +2692,7 @@ impl PeerNetwork {
         }
         if self.local_peer.public_ip_address.is_some()
             && self.public_ip_requested_at + self.connection_opts.public_ip_request_timeout
-                >= get_epoch_time_secs()
+                >= self.connection_opts.clock.now_secs()
         {
             // throttle
             debug!(
@@ -2851,7 +3386,9 @@ impl PeerNetwork {
         // clear timed-out pingbacks
         let mut to_remove = vec![];
         for (naddr, pingback) in self.walk_pingbacks.iter() {
-            if pingback.ts + self.connection_opts.pingback_timeout < get_epoch_time_secs() {
+            if pingback.ts + self.connection_opts.pingback_timeout
+                < self.connection_opts.clock.now_secs()
+            {
                 to_remove.push((*naddr).clone());
             }
         }
@@ -2904,16 +3441,19 @@ impl PeerNetwork {
                         NeighborPingback {
                             peer_version: nk.peer_version,
                             network_id: nk.network_id,
-                            ts: get_epoch_time_secs(),
+                            ts: self.connection_opts.clock.now_secs(),
                             pubkey: pubkey,
                         },
                     );
 
                     if self.walk_pingbacks.len() > MAX_NEIGHBORS_DATA_LEN as usize {
                         // drop one at random
-                        let idx = thread_rng().gen::<usize>() % self.walk_pingbacks.len();
-                        let drop_addr = match self.walk_pingbacks.keys().skip(idx).next() {
-                            Some(ref addr) => (*addr).clone(),
+                        let idx = self.rng.gen::<usize>() % self.walk_pingbacks.len();
+                        let mut pingback_addrs: Vec<&NeighborAddress> =
+                            self.walk_pingbacks.keys().collect();
+                        pingback_addrs.sort();
+                        let drop_addr = match pingback_addrs.get(idx) {
+                            Some(addr) => (*addr).clone(),
                             None => {
                                 continue;
                             }
@@ -3067,6 +3607,7 @@ impl PeerNetwork {
 
         // clear out peers that we haven't heard from in our heartbeat interval
         self.disconnect_unresponsive();
+        self.prune_connect_backoffs();
 
         // queue up pings to neighbors we haven't spoken to in a while
         self.queue_ping_heartbeats();
@@ -3180,9 +3721,14 @@ mod test {
     use net::codec::*;
     use net::db::*;
     use net::*;
+    use std::io;
+    use std::io::Read;
     use std::thread;
     use std::time;
+    use util::get_epoch_time_secs;
     use util::log;
+    use util::pipe::Pipe;
+    use util::secp256k1::Secp256k1PrivateKey;
     use util::sleep_ms;
     use util::test::*;
 
@@ -3228,6 +3774,13 @@ mod test {
         conn_opts.inbox_maxlen = 5;
         conn_opts.outbox_maxlen = 5;
 
+        make_test_p2p_network_with_opts(initial_neighbors, conn_opts)
+    }
+
+    fn make_test_p2p_network_with_opts(
+        initial_neighbors: &Vec<Neighbor>,
+        conn_opts: ConnectionOptions,
+    ) -> PeerNetwork {
         let first_burn_hash = BurnchainHeaderHash::from_hex(
             "0000000000000000000000000000000000000000000000000000000000000000",
         )
@@ -3339,6 +3892,45 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_cancel_walk() {
+        // fake neighbor endpoint that never accepts, so the walk never gets past connecting
+        let neighbor = make_test_neighbor(2670);
+        let mut p2p = make_test_p2p_network(&vec![neighbor.clone()]);
+
+        use std::net::TcpListener;
+        let _listener = TcpListener::bind("127.0.0.1:2670").unwrap();
+
+        p2p.bind(
+            &"127.0.0.1:2671".parse().unwrap(),
+            &"127.0.0.1:2672".parse().unwrap(),
+        )
+        .unwrap();
+
+        // no-op without a walk
+        p2p.cancel_walk();
+        assert!(p2p.walk.is_none());
+
+        p2p.walk_peer_graph();
+        assert!(p2p.walk.is_some());
+        assert_eq!(p2p.connecting.len(), 1);
+        let event_id = *p2p.connecting.keys().next().unwrap();
+        assert!(p2p.is_connecting(event_id));
+
+        p2p.walk_deadline = get_epoch_time_secs() + 3600;
+        p2p.walk_retries = 3;
+
+        p2p.cancel_walk();
+        assert!(p2p.walk.is_none());
+        assert_eq!(p2p.walk_deadline, 0);
+        assert_eq!(p2p.walk_retries, 0);
+
+        // no leaks -- only server events remain
+        assert!(p2p.connecting.is_empty());
+        assert!(p2p.connecting_neighbors.is_empty());
+        assert_eq!(p2p.network.as_ref().unwrap().num_events(), 2);
+    }
+
     // tests relay_signed_message()
     #[test]
     #[ignore]
@@ -3529,4 +4121,812 @@ mod test {
             test_debug!("fake endpoint thread joined");
         })
     }
+
+    #[test]
+    fn test_rate_limit_p2p_conversation() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        p2p.connection_opts.inbox_maxlen = 100;
+        p2p.connection_opts.max_p2p_messages_per_sec = 10;
+        p2p.connection_opts.max_p2p_messages_burst = 5;
+
+        let socketaddr: SocketAddr = "127.0.0.1:2500".parse().unwrap();
+        let mut sender = ConversationP2P::new(
+            p2p.local_peer.network_id,
+            p2p.peer_version,
+            &p2p.burnchain,
+            &socketaddr,
+            &p2p.connection_opts,
+            true,
+            0,
+        );
+        let receiver = ConversationP2P::new(
+            p2p.local_peer.network_id,
+            p2p.peer_version,
+            &p2p.burnchain,
+            &socketaddr,
+            &p2p.connection_opts,
+            false,
+            1,
+        );
+        p2p.peers.insert(1, receiver);
+
+        let sender_privkey = Secp256k1PrivateKey::new();
+        let mut now_ms = 1_000_000;
+        let mut total_dropped = 0;
+
+        for round in 0..2 {
+            // peer floods us with 10 pings at once
+            let mut bytes = vec![];
+            for _ in 0..10 {
+                let ping = sender
+                    .sign_message(
+                        &p2p.chain_view,
+                        &sender_privkey,
+                        StacksMessageType::Ping(PingData::new()),
+                    )
+                    .unwrap();
+                ping.consensus_serialize(&mut bytes).unwrap();
+            }
+
+            let convo = p2p.peers.get_mut(&1).unwrap();
+            convo.recv(&mut &bytes[..]).unwrap();
+            assert_eq!(convo.connection.inbox_len(), 10);
+
+            let rate_limiter = p2p
+                .rate_limiters
+                .entry(1)
+                .or_insert(MessageRateLimiter::new(10, 5, now_ms));
+            let num_dropped = PeerNetwork::rate_limit_p2p_conversation(
+                &p2p.local_peer,
+                1,
+                convo,
+                rate_limiter,
+                now_ms,
+            );
+            total_dropped += num_dropped;
+
+            if round == 0 {
+                // whole burst admitted, the rest dropped
+                assert_eq!(num_dropped, 5);
+                assert_eq!(convo.connection.inbox_len(), 5);
+            } else {
+                // one token refilled in 100ms
+                assert_eq!(num_dropped, 9);
+                assert_eq!(convo.connection.inbox_len(), 1);
+            }
+
+            while convo.connection.next_inbox_message().is_some() {}
+
+            // a single burst over the limit doesn't get the peer banned
+            let banned = p2p.ban_if_rate_limited(1);
+            assert_eq!(banned, round > 0);
+            now_ms += 100;
+        }
+
+        assert_eq!(total_dropped, 14);
+        assert!(p2p.bans.contains(&1));
+
+        // limiter state goes away with the peer
+        p2p.deregister_peer(1);
+        assert!(p2p.rate_limiters.is_empty());
+        assert!(p2p.peers.is_empty());
+    }
+
+    #[test]
+    fn test_ban_oversized_message() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+
+        let socketaddr: SocketAddr = "127.0.0.1:2510".parse().unwrap();
+        let receiver = ConversationP2P::new(
+            p2p.local_peer.network_id,
+            p2p.peer_version,
+            &p2p.burnchain,
+            &socketaddr,
+            &p2p.connection_opts,
+            false,
+            1,
+        );
+        p2p.peers.insert(1, receiver);
+
+        // peer claims to be sending us a message bigger than any message can be
+        let preamble = Preamble::new(
+            p2p.peer_version,
+            p2p.local_peer.network_id,
+            p2p.chain_view.burn_block_height,
+            &p2p.chain_view.burn_block_hash,
+            p2p.chain_view.burn_stable_block_height,
+            &p2p.chain_view.burn_stable_block_hash,
+            MAX_MESSAGE_LEN,
+        );
+
+        let mut bytes = vec![];
+        preamble.consensus_serialize(&mut bytes).unwrap();
+        let relayers: Vec<RelayData> = vec![];
+        relayers.consensus_serialize(&mut bytes).unwrap();
+        bytes.push(StacksMessageID::Transaction as u8);
+        bytes.extend_from_slice(&[0u8; 4096]);
+
+        let err = {
+            let convo = p2p.peers.get_mut(&1).unwrap();
+            let err = convo.recv(&mut &bytes[..]).unwrap_err();
+            assert_eq!(convo.connection.inbox_len(), 0);
+            err
+        };
+
+        // other errors don't get the peer banned
+        assert!(!p2p.ban_if_oversized(1, &net_error::ConnectionBroken));
+        assert!(p2p.bans.is_empty());
+
+        assert!(p2p.ban_if_oversized(1, &err));
+        assert!(p2p.bans.contains(&1));
+    }
+
+    #[test]
+    fn test_message_rate_limiter_refill() {
+        let mut rate_limiter = MessageRateLimiter::new(10, 5, 0);
+
+        assert_eq!(rate_limiter.admit(3, 0), 3);
+        assert_eq!(rate_limiter.admit(3, 0), 2);
+        assert_eq!(rate_limiter.num_dropped, 1);
+
+        // refills at 10 messages/sec, but never beyond the burst size
+        assert_eq!(rate_limiter.admit(3, 200), 2);
+        assert_eq!(rate_limiter.num_dropped, 2);
+        assert_eq!(rate_limiter.admit(10, 10_000), 5);
+
+        // staying within the rate resets the violation count
+        assert_eq!(rate_limiter.admit(1, 10_100), 1);
+        assert_eq!(rate_limiter.num_dropped, 0);
+        assert!(!rate_limiter.is_violating());
+    }
+
+    #[test]
+    fn test_message_rate_limiter_default_burst() {
+        // only the rate is set, so the burst size is left at its default
+        let mut connection_opts = ConnectionOptions::default();
+        connection_opts.max_p2p_messages_per_sec = 10;
+
+        let mut rate_limiter = MessageRateLimiter::new(
+            connection_opts.max_p2p_messages_per_sec,
+            connection_opts.max_p2p_messages_burst,
+            0,
+        );
+
+        // a second's worth of messages at once is fine
+        assert_eq!(rate_limiter.admit(10, 0), 10);
+        assert_eq!(rate_limiter.num_dropped, 0);
+
+        // more than that is dropped, but doesn't get the peer banned right away
+        assert_eq!(rate_limiter.admit(5, 100), 1);
+        assert_eq!(rate_limiter.num_dropped, 4);
+        assert!(!rate_limiter.is_violating());
+    }
+
+    #[test]
+    fn test_disconnect_all() {
+        with_timeout(100, || {
+            let mut p2p = make_test_p2p_network(&vec![]);
+
+            // no-op without a network
+            p2p.disconnect_all();
+            assert_eq!(p2p.num_peers(), 0);
+
+            p2p.bind(
+                &"127.0.0.1:2610".parse().unwrap(),
+                &"127.0.0.1:2611".parse().unwrap(),
+            )
+            .unwrap();
+
+            use std::net::TcpListener;
+            let mut listeners = vec![];
+            let mut event_ids = vec![];
+            for port in 2600..2604 {
+                listeners.push(TcpListener::bind(("127.0.0.1", port)).unwrap());
+                let neighbor = make_test_neighbor(port);
+                event_ids.push(p2p.connect_peer(&neighbor.addr).unwrap());
+            }
+            assert_eq!(p2p.connecting.len(), 4);
+
+            // let the connections complete
+            thread::sleep(time::Duration::from_millis(1000));
+
+            // register all but the last as peers, and leave the last one connecting
+            for event_id in event_ids[0..3].iter() {
+                let (socket, outbound, _) = p2p.connecting.remove(event_id).unwrap();
+                p2p.register_peer(*event_id, socket, outbound).unwrap();
+            }
+
+            assert_eq!(p2p.num_peers(), 3);
+            assert_eq!(p2p.peers.len(), 3);
+            assert_eq!(p2p.events.len(), 3);
+            assert_eq!(p2p.connecting.len(), 1);
+
+            p2p.disconnect_all();
+
+            assert_eq!(p2p.num_peers(), 0);
+            assert!(p2p.peers.is_empty());
+            assert!(p2p.sockets.is_empty());
+            assert!(p2p.events.is_empty());
+            assert!(p2p.connecting.is_empty());
+            assert!(p2p.relay_handles.is_empty());
+
+            // still bound
+            assert!(p2p.network.is_some());
+        })
+    }
+
+    struct FixedPublicIpResolver(Result<PeerAddress, ()>);
+
+    impl PublicIpResolver for FixedPublicIpResolver {
+        fn resolve_public_ip(&mut self) -> Result<PeerAddress, net_error> {
+            self.0.clone().map_err(|_| net_error::ConnectionError)
+        }
+    }
+
+    /// Resolves to 5.6.7.8, but only once it is told to
+    struct SlowPublicIpResolver(Receiver<()>);
+
+    impl PublicIpResolver for SlowPublicIpResolver {
+        fn resolve_public_ip(&mut self) -> Result<PeerAddress, net_error> {
+            self.0.recv().map_err(|_| net_error::ConnectionError)?;
+            Ok(PeerAddress::from_ipv4(5, 6, 7, 8))
+        }
+    }
+
+    /// Poll until the public IP lookup finishes
+    fn wait_for_public_ip(p2p: &mut PeerNetwork) {
+        while !p2p.do_get_public_ip().unwrap() {
+            sleep_ms(10);
+        }
+    }
+
+    #[test]
+    fn test_public_ip_resolver() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        p2p.bind(
+            &"127.0.0.1:2630".parse().unwrap(),
+            &"127.0.0.1:2631".parse().unwrap(),
+        )
+        .unwrap();
+
+        // a failing resolver falls back to asking our neighbors, of which we have none
+        p2p.set_public_ip_resolver(Box::new(FixedPublicIpResolver(Err(()))));
+        wait_for_public_ip(&mut p2p);
+        assert!(p2p.local_peer.public_ip_address.is_none());
+        assert!(!p2p.public_ip_confirmed);
+
+        // a working resolver gives us our IP without needing any neighbors
+        p2p.set_public_ip_resolver(Box::new(FixedPublicIpResolver(Ok(PeerAddress::from_ipv4(
+            1, 2, 3, 4,
+        )))));
+        wait_for_public_ip(&mut p2p);
+        assert_eq!(
+            p2p.local_peer.public_ip_address,
+            Some((PeerAddress::from_ipv4(1, 2, 3, 4), 2630))
+        );
+        assert!(p2p.public_ip_confirmed);
+
+        // still fresh, so no need to ask again
+        assert!(!p2p.need_public_ip());
+    }
+
+    #[test]
+    fn test_public_ip_resolver_does_not_block() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        p2p.bind(
+            &"127.0.0.1:2632".parse().unwrap(),
+            &"127.0.0.1:2633".parse().unwrap(),
+        )
+        .unwrap();
+
+        let (release_tx, release_rx) = sync_channel(1);
+        p2p.set_public_ip_resolver(Box::new(SlowPublicIpResolver(release_rx)));
+
+        // the lookup is stuck, but the p2p thread keeps going
+        for _ in 0..3 {
+            assert!(!p2p.do_get_public_ip().unwrap());
+        }
+        assert!(p2p.local_peer.public_ip_address.is_none());
+
+        release_tx.send(()).unwrap();
+        wait_for_public_ip(&mut p2p);
+        assert_eq!(
+            p2p.local_peer.public_ip_address,
+            Some((PeerAddress::from_ipv4(5, 6, 7, 8), 2632))
+        );
+    }
+
+    #[test]
+    fn test_sample_broadcast_peers_seeded() {
+        let mut conn_opts = ConnectionOptions::default();
+        conn_opts.rng_seed = Some(12345);
+
+        let mut samples = vec![];
+        for _ in 0..2 {
+            let mut p2p = make_test_p2p_network_with_opts(&vec![], conn_opts.clone());
+            for i in 0..40 {
+                let socketaddr: SocketAddr =
+                    format!("10.0.0.{}:{}", i + 1, 2700 + i).parse().unwrap();
+                let convo = ConversationP2P::new(
+                    p2p.local_peer.network_id,
+                    p2p.peer_version,
+                    &p2p.burnchain,
+                    &socketaddr,
+                    &p2p.connection_opts,
+                    i % 2 == 0,
+                    i,
+                );
+                p2p.peers.insert(i, convo);
+            }
+
+            let mut network_samples = vec![];
+            for _ in 0..5 {
+                let sample = p2p
+                    .sample_broadcast_peers(&vec![], &BlocksAvailableData::new())
+                    .unwrap();
+                assert_eq!(
+                    sample.len(),
+                    MAX_BROADCAST_OUTBOUND_RECEIVERS + MAX_BROADCAST_INBOUND_RECEIVERS
+                );
+                network_samples.push(sample);
+            }
+            samples.push(network_samples);
+        }
+
+        // same seed and same peers yield the same samples
+        assert_eq!(samples[0], samples[1]);
+    }
+
+    #[test]
+    fn test_get_neighbor_inventory_summary() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        assert_eq!(p2p.get_neighbor_inventory_summary(), vec![]);
+
+        let nk_1 = make_test_neighbor(2640).addr;
+        let nk_2 = make_test_neighbor(2641).addr;
+
+        let mut inv_state = InvState::new(0, 60, 60, HashSet::new());
+        inv_state.add_peer(nk_1.clone());
+        inv_state.add_peer(nk_2.clone());
+        {
+            let stats_1 = inv_state.get_stats_mut(&nk_1).unwrap();
+            stats_1.inv.set_block_bit(0);
+            stats_1.inv.set_block_bit(3);
+            stats_1.inv.set_block_bit(9);
+            stats_1.inv.set_microblocks_bit(3);
+
+            let stats_2 = inv_state.get_stats_mut(&nk_2).unwrap();
+            stats_2.inv.set_microblocks_bit(1);
+            stats_2.inv.set_microblocks_bit(2);
+        }
+        p2p.inv_state = Some(inv_state);
+
+        let summary = p2p.get_neighbor_inventory_summary();
+        assert_eq!(summary, vec![(nk_1.clone(), 3, 1), (nk_2.clone(), 0, 2)]);
+
+        // reading the summary doesn't change anything
+        assert_eq!(p2p.get_neighbor_inventory_summary(), summary);
+        let inv_state = p2p.inv_state.as_ref().unwrap();
+        assert_eq!(inv_state.get_inv_sortitions(&nk_1), 10);
+        assert_eq!(inv_state.get_inv_sortitions(&nk_2), 3);
+    }
+
+    #[test]
+    fn test_request_inv_sync() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        let mut handle = p2p.new_handle(1);
+
+        let nk_1 = make_test_neighbor(2660).addr;
+        let nk_2 = make_test_neighbor(2661).addr;
+
+        // both neighbors have already been scanned this pass
+        let mut inv_state = InvState::new(0, 60, 60, HashSet::new());
+        inv_state.add_peer(nk_1.clone());
+        inv_state.add_peer(nk_2.clone());
+        inv_state.get_stats_mut(&nk_1).unwrap().done = true;
+        inv_state.get_stats_mut(&nk_2).unwrap().done = true;
+        p2p.inv_state = Some(inv_state);
+
+        // we're connected to nk_2
+        let socketaddr: SocketAddr = "127.0.0.1:2661".parse().unwrap();
+        let convo = ConversationP2P::new(
+            p2p.local_peer.network_id,
+            p2p.peer_version,
+            &p2p.burnchain,
+            &socketaddr,
+            &p2p.connection_opts,
+            true,
+            1,
+        );
+        p2p.peers.insert(1, convo);
+        p2p.events.insert(nk_2.clone(), 1);
+
+        handle.request_inv_sync(nk_2.clone()).unwrap();
+        p2p.dispatch_requests();
+
+        // no need to connect to a neighbor we're already talking to
+        assert!(p2p.connecting.is_empty());
+
+        let inv_state = p2p.inv_state.as_ref().unwrap();
+        assert_eq!(inv_state.priority_peer, Some(nk_2.clone()));
+        assert_eq!(inv_state.get_sync_order()[0], nk_2);
+
+        // nk_2's scan restarts; nk_1's is left alone
+        let stats_2 = inv_state.get_stats(&nk_2).unwrap();
+        assert!(!stats_2.done);
+        assert_eq!(stats_2.state, InvWorkState::GetPoxInvBegin);
+        assert!(inv_state.get_stats(&nk_1).unwrap().done);
+    }
+
+    #[test]
+    fn test_disconnect_idle_peer() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        p2p.connection_opts.handshake_timeout = 5;
+        p2p.connection_opts.peer_idle_timeout = 10;
+
+        // an authenticated peer that has gone silent
+        let nk = make_test_neighbor(2680).addr;
+        let socketaddr: SocketAddr = "127.0.0.1:2680".parse().unwrap();
+        let mut convo = ConversationP2P::new(
+            p2p.local_peer.network_id,
+            p2p.peer_version,
+            &p2p.burnchain,
+            &socketaddr,
+            &p2p.connection_opts,
+            true,
+            1,
+        );
+        convo.set_public_key(Some(Secp256k1PublicKey::from_private(
+            &Secp256k1PrivateKey::new(),
+        )));
+        let now = get_epoch_time_secs();
+        convo.stats.last_contact_time = now;
+        convo.stats.last_recv_time = now;
+        p2p.peers.insert(1, convo);
+        p2p.events.insert(nk.clone(), 1);
+
+        // not idle yet
+        assert_eq!(p2p.disconnect_unresponsive(), 0);
+        assert!(p2p.get_convo(&nk).is_some());
+
+        // advance past the idle timeout, but not past the heartbeat
+        let convo = p2p.peers.get_mut(&1).unwrap();
+        convo.instantiated -= 11;
+        convo.stats.last_contact_time -= 11;
+        convo.stats.last_recv_time -= 11;
+
+        assert_eq!(p2p.disconnect_unresponsive(), 1);
+        assert!(p2p.get_convo(&nk).is_none());
+        assert!(p2p.peers.is_empty());
+    }
+
+    #[test]
+    fn test_connect_backoff() {
+        let backoff = ConnectBackoff {
+            num_failures: 1,
+            last_failure_at: 1000,
+        };
+        assert_eq!(backoff.cooldown(10, 100), 0);
+        assert!(!backoff.is_cooling_down(10, 100, 1000));
+
+        let backoff = ConnectBackoff {
+            num_failures: 3,
+            last_failure_at: 1000,
+        };
+        assert_eq!(backoff.cooldown(10, 100), 20);
+        assert!(backoff.is_cooling_down(10, 100, 1019));
+        assert!(!backoff.is_cooling_down(10, 100, 1020));
+
+        let backoff = ConnectBackoff {
+            num_failures: 100,
+            last_failure_at: 1000,
+        };
+        assert_eq!(backoff.cooldown(10, 100), 100);
+    }
+
+    #[test]
+    fn test_connect_peer_backoff() {
+        with_timeout(100, || {
+            let mut p2p = make_test_p2p_network(&vec![]);
+            p2p.connection_opts.connect_backoff = 60;
+            p2p.bind(
+                &"127.0.0.1:2650".parse().unwrap(),
+                &"127.0.0.1:2651".parse().unwrap(),
+            )
+            .unwrap();
+
+            // accepts connections, but never handshakes
+            use std::net::TcpListener;
+            let _listener = TcpListener::bind("127.0.0.1:2652").unwrap();
+            let neighbor = make_test_neighbor(2652);
+
+            // two connection attempts time out
+            for i in 0..2 {
+                let event_id = p2p.connect_peer(&neighbor.addr).unwrap();
+                p2p.connecting.get_mut(&event_id).unwrap().2 = 0;
+                assert_eq!(p2p.disconnect_unresponsive(), 1);
+                assert_eq!(
+                    p2p.connect_backoffs
+                        .get(&neighbor.addr)
+                        .unwrap()
+                        .num_failures,
+                    i + 1
+                );
+            }
+            assert!(p2p.connecting.is_empty());
+            assert!(p2p.connecting_neighbors.is_empty());
+
+            // third attempt is skipped while we cool down
+            match p2p.connect_peer(&neighbor.addr) {
+                Err(net_error::NoSuchNeighbor) => {}
+                x => panic!("Expected NoSuchNeighbor, got {:?}", &x),
+            }
+            assert!(p2p.connecting.is_empty());
+
+            // once the cooldown passes, we try again
+            p2p.connect_backoffs
+                .get_mut(&neighbor.addr)
+                .unwrap()
+                .last_failure_at -= 60;
+            let event_id = p2p.connect_peer(&neighbor.addr).unwrap();
+            assert!(p2p.connecting.contains_key(&event_id));
+        })
+    }
+
+    #[test]
+    fn test_prune_connect_backoffs() {
+        use net::connection::{MockClock, NetworkClock};
+
+        let mut p2p = make_test_p2p_network(&vec![]);
+        p2p.connection_opts.max_connect_backoff = 100;
+        let now = 1000;
+        p2p.connection_opts.clock = NetworkClock::new(MockClock::new(now));
+
+        let recent = make_test_neighbor(2660);
+        let stale = make_test_neighbor(2661);
+        p2p.connect_backoffs.insert(
+            recent.addr.clone(),
+            ConnectBackoff {
+                num_failures: 5,
+                last_failure_at: now - 200,
+            },
+        );
+        p2p.connect_backoffs.insert(
+            stale.addr.clone(),
+            ConnectBackoff {
+                num_failures: 5,
+                last_failure_at: now - 201,
+            },
+        );
+
+        p2p.prune_connect_backoffs();
+        assert_eq!(p2p.connect_backoffs.len(), 1);
+        assert!(p2p.connect_backoffs.contains_key(&recent.addr));
+    }
+
+    #[test]
+    fn test_add_relay_handle_limit() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        p2p.connection_opts.max_relay_handles_per_peer = 3;
+
+        let mut pipe_reads = vec![];
+        for _ in 0..10 {
+            let (mut pipe_read, pipe_write) = Pipe::new();
+            pipe_read.set_nonblocking(true);
+            pipe_reads.push(pipe_read);
+
+            p2p.add_relay_handle(1, ReplyHandleP2P::new_relay(pipe_write, 1));
+            assert!(p2p.relay_handles.get(&1).unwrap().len() <= 3);
+        }
+        assert_eq!(p2p.relay_handles.get(&1).unwrap().len(), 3);
+
+        // the newest handles were dropped, so their pipes are closed, while the queued ones
+        // (including the one at the front) are left alone
+        let mut buf = [0u8; 1];
+        for (i, pipe_read) in pipe_reads.iter_mut().enumerate() {
+            let err = pipe_read.read(&mut buf).unwrap_err();
+            if i < 3 {
+                assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+            } else {
+                assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+            }
+        }
+    }
+
+    #[test]
+    fn test_broadcast_to_trusted() {
+        use chainstate::stacks::*;
+        use vm::types::{PrincipalData, StandardPrincipalData};
+
+        let mut p2p = make_test_p2p_network(&vec![]);
+        let mut handle = p2p.new_handle(1);
+
+        let mut neighbor_keys = vec![];
+        for i in 0..3 {
+            let socketaddr: SocketAddr = format!("127.0.0.1:{}", 2690 + i).parse().unwrap();
+            let convo = ConversationP2P::new(
+                p2p.local_peer.network_id,
+                p2p.peer_version,
+                &p2p.burnchain,
+                &socketaddr,
+                &p2p.connection_opts,
+                true,
+                i,
+            );
+            let nk = convo.to_neighbor_key();
+            p2p.peers.insert(i, convo);
+            p2p.events.insert(nk.clone(), i);
+            neighbor_keys.push(nk);
+        }
+
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&StacksPrivateKey::new()).unwrap(),
+            TransactionPayload::TokenTransfer(
+                PrincipalData::Standard(StandardPrincipalData(0x1a, [0x11; 20])),
+                123,
+                TokenTransferMemo([0u8; 34]),
+            ),
+        );
+
+        // no trusted peers, so nothing is sent
+        handle
+            .broadcast_to_trusted(vec![], StacksMessageType::Transaction(tx.clone()))
+            .unwrap();
+        p2p.dispatch_requests();
+        assert!(p2p.relay_handles.is_empty());
+
+        // trust the second peer
+        {
+            let mut tx = p2p.peerdb.tx_begin().unwrap();
+            PeerDB::set_allow_peer(
+                &mut tx,
+                neighbor_keys[1].network_id,
+                &neighbor_keys[1].addrbytes,
+                neighbor_keys[1].port,
+                -1,
+            )
+            .unwrap();
+            tx.commit().unwrap();
+        }
+
+        handle
+            .broadcast_to_trusted(vec![], StacksMessageType::Transaction(tx.clone()))
+            .unwrap();
+        p2p.dispatch_requests();
+        assert_eq!(
+            p2p.relay_handles.keys().cloned().collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        // an ordinary broadcast still reaches everyone
+        handle
+            .broadcast_message(vec![], StacksMessageType::Transaction(tx))
+            .unwrap();
+        p2p.dispatch_requests();
+        let mut recipients: Vec<_> = p2p.relay_handles.keys().cloned().collect();
+        recipients.sort();
+        assert_eq!(recipients, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_broadcast_to_trusted_reaches_all_trusted_peers() {
+        use chainstate::stacks::*;
+        use vm::types::{PrincipalData, StandardPrincipalData};
+
+        let mut p2p = make_test_p2p_network(&vec![]);
+        let mut handle = p2p.new_handle(1);
+
+        // more trusted outbound peers than an ordinary broadcast would sample
+        let num_peers = MAX_BROADCAST_OUTBOUND_RECEIVERS + 2;
+        {
+            let mut tx = p2p.peerdb.tx_begin().unwrap();
+            for i in 0..num_peers {
+                let socketaddr: SocketAddr = format!("127.0.0.1:{}", 2700 + i).parse().unwrap();
+                let convo = ConversationP2P::new(
+                    p2p.local_peer.network_id,
+                    p2p.peer_version,
+                    &p2p.burnchain,
+                    &socketaddr,
+                    &p2p.connection_opts,
+                    true,
+                    i,
+                );
+                let nk = convo.to_neighbor_key();
+                PeerDB::set_allow_peer(&mut tx, nk.network_id, &nk.addrbytes, nk.port, -1).unwrap();
+                p2p.peers.insert(i, convo);
+                p2p.events.insert(nk, i);
+            }
+            tx.commit().unwrap();
+        }
+
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&StacksPrivateKey::new()).unwrap(),
+            TransactionPayload::TokenTransfer(
+                PrincipalData::Standard(StandardPrincipalData(0x1a, [0x11; 20])),
+                123,
+                TokenTransferMemo([0u8; 34]),
+            ),
+        );
+
+        handle
+            .broadcast_to_trusted(vec![], StacksMessageType::Transaction(tx))
+            .unwrap();
+        p2p.dispatch_requests();
+        let mut recipients: Vec<_> = p2p.relay_handles.keys().cloned().collect();
+        recipients.sort();
+        assert_eq!(recipients, (0..num_peers).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_ban_expires_with_mock_clock() {
+        use net::connection::{MockClock, NetworkClock};
+
+        with_timeout(100, || {
+            let clock = MockClock::new(get_epoch_time_secs());
+            let mut conn_opts = ConnectionOptions::default();
+            conn_opts.clock = NetworkClock::new(clock.clone());
+
+            let mut p2p = make_test_p2p_network_with_opts(&vec![], conn_opts);
+            p2p.bind(
+                &"127.0.0.1:2695".parse().unwrap(),
+                &"127.0.0.1:2696".parse().unwrap(),
+            )
+            .unwrap();
+
+            use std::net::TcpListener;
+            let _listener = TcpListener::bind("127.0.0.1:2697").unwrap();
+
+            let socketaddr: SocketAddr = "127.0.0.1:2697".parse().unwrap();
+            let convo = ConversationP2P::new(
+                p2p.local_peer.network_id,
+                p2p.peer_version,
+                &p2p.burnchain,
+                &socketaddr,
+                &p2p.connection_opts,
+                true,
+                1,
+            );
+            let nk = convo.to_neighbor_key();
+            p2p.peers.insert(1, convo);
+            p2p.events.insert(nk.clone(), 1);
+
+            // ban the peer, and drop it like the network would
+            p2p.bans.insert(1);
+            assert_eq!(p2p.process_bans().unwrap(), vec![1]);
+            p2p.deregister_peer(1);
+
+            // denied for the whole ban duration, no matter how long the test takes
+            clock.advance(DENY_BAN_DURATION - 1);
+            match p2p.connect_peer(&nk) {
+                Err(net_error::Denied) => {}
+                x => panic!("Expected Denied, got {:?}", &x),
+            }
+
+            // eligible again once the ban expires
+            clock.advance(1);
+            let event_id = p2p.connect_peer(&nk).unwrap();
+            assert!(p2p.connecting.contains_key(&event_id));
+        })
+    }
+
+    #[test]
+    fn test_rate_limit_refills_with_mock_clock() {
+        use net::connection::{MockClock, NetworkClock};
+
+        let clock = MockClock::new(1000);
+        let network_clock = NetworkClock::new(clock.clone());
+        assert_eq!(network_clock.now_ms(), 1_000_000);
+
+        let mut rate_limiter = MessageRateLimiter::new(10, 5, network_clock.now_ms());
+        assert_eq!(rate_limiter.admit(10, network_clock.now_ms()), 5);
+
+        // no refill until the clock moves, however long the test takes
+        assert_eq!(rate_limiter.admit(10, network_clock.now_ms()), 0);
+
+        clock.advance(1);
+        assert_eq!(network_clock.now_ms(), 1_001_000);
+        assert_eq!(rate_limiter.admit(10, network_clock.now_ms()), 5);
+    }
 }
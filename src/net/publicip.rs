@@ -0,0 +1,249 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use net::Error as net_error;
+use net::PeerAddress;
+
+use url;
+
+/// Something other than our peers that can tell us our public IP address.
+/// If the `PeerNetwork` has no resolver, or if its resolver fails, then it falls back to asking
+/// one of its initial neighbors for a NAT punch reply.
+pub trait PublicIpResolver: Send {
+    /// Look up our public IP address.
+    fn resolve_public_ip(&mut self) -> Result<PeerAddress, net_error>;
+}
+
+/// Resolve our public IP address by querying a plain-HTTP "what is my IP" endpoint, which is
+/// expected to reply with the caller's IP address as the (whitespace-padded) response body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpPublicIpResolver {
+    url: String,
+    timeout: Duration,
+}
+
+impl HttpPublicIpResolver {
+    pub fn new(url: &str, timeout_secs: u64) -> HttpPublicIpResolver {
+        HttpPublicIpResolver {
+            url: url.to_string(),
+            timeout: Duration::from_secs(timeout_secs),
+        }
+    }
+
+    /// Send a GET to the endpoint, and return the raw reply
+    fn query(&self) -> Result<String, net_error> {
+        let url = url::Url::parse(&self.url).map_err(|e| {
+            net_error::RecvError(format!("Invalid public IP URL {}: {:?}", &self.url, &e))
+        })?;
+        if url.scheme() != "http" {
+            return Err(net_error::RecvError(format!(
+                "Unsupported public IP URL scheme: {}",
+                url.scheme()
+            )));
+        }
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| net_error::RecvError(format!("No host in {}", &self.url)))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let addr = (host, port)
+            .to_socket_addrs()
+            .map_err(|_e| net_error::ConnectionError)?
+            .next()
+            .ok_or(net_error::ConnectionError)?;
+
+        let mut sock = TcpStream::connect_timeout(&addr, self.timeout)
+            .map_err(|_e| net_error::ConnectionError)?;
+        sock.set_read_timeout(Some(self.timeout))
+            .map_err(|_e| net_error::SocketError)?;
+        sock.set_write_timeout(Some(self.timeout))
+            .map_err(|_e| net_error::SocketError)?;
+
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            url.path(),
+            host
+        );
+        sock.write_all(request.as_bytes())
+            .map_err(net_error::WriteError)?;
+
+        let mut reply = String::new();
+        sock.read_to_string(&mut reply)
+            .map_err(net_error::ReadError)?;
+        Ok(reply)
+    }
+
+    /// Extract the IP address from an HTTP reply
+    pub fn parse_reply(reply: &str) -> Result<PeerAddress, net_error> {
+        let mut parts = reply.splitn(2, "\r\n\r\n");
+        let headers = parts.next().unwrap_or("");
+        let body = parts
+            .next()
+            .ok_or_else(|| net_error::DeserializeError("No HTTP body".to_string()))?;
+
+        let status_ok = headers
+            .lines()
+            .next()
+            .and_then(|status_line| status_line.split_whitespace().nth(1))
+            .map(|status| status == "200")
+            .unwrap_or(false);
+        if !status_ok {
+            return Err(net_error::DeserializeError(format!(
+                "Unexpected HTTP status: {:?}",
+                headers.lines().next()
+            )));
+        }
+
+        let ip_addr = body.trim().parse::<IpAddr>().map_err(|_e| {
+            net_error::DeserializeError(format!("Not an IP address: {:?}", body.trim()))
+        })?;
+        Ok(PeerAddress::from_ip(&ip_addr))
+    }
+}
+
+impl PublicIpResolver for HttpPublicIpResolver {
+    fn resolve_public_ip(&mut self) -> Result<PeerAddress, net_error> {
+        let reply = self.query()?;
+        HttpPublicIpResolver::parse_reply(&reply)
+    }
+}
+
+/// Runs a `PublicIpResolver` on its own thread, so that a slow lookup doesn't stall the p2p
+/// event loop.  The p2p thread starts a lookup with `begin_lookup()`, and polls for its result
+/// with `poll_lookup()`.  The resolver thread exits once this handle is dropped.
+pub struct PublicIpResolverThread {
+    requests: SyncSender<()>,
+    replies: Receiver<Result<PeerAddress, net_error>>,
+    inflight: bool,
+}
+
+impl PublicIpResolverThread {
+    pub fn spawn(mut resolver: Box<dyn PublicIpResolver>) -> PublicIpResolverThread {
+        let (request_tx, request_rx) = sync_channel::<()>(1);
+        let (reply_tx, reply_rx) = sync_channel(1);
+
+        thread::spawn(move || {
+            while let Ok(()) = request_rx.recv() {
+                let resolved = resolver.resolve_public_ip();
+                if reply_tx.send(resolved).is_err() {
+                    break;
+                }
+            }
+            debug!("Public IP resolver thread exiting");
+        });
+
+        PublicIpResolverThread {
+            requests: request_tx,
+            replies: reply_rx,
+            inflight: false,
+        }
+    }
+
+    /// Is a lookup in progress?
+    pub fn is_inflight(&self) -> bool {
+        self.inflight
+    }
+
+    /// Start a lookup, unless one is already in progress
+    pub fn begin_lookup(&mut self) -> Result<(), net_error> {
+        if self.inflight {
+            return Ok(());
+        }
+        self.requests
+            .try_send(())
+            .map_err(|_e| net_error::InvalidHandle)?;
+        self.inflight = true;
+        Ok(())
+    }
+
+    /// Get the result of the lookup in progress, if it has finished.
+    pub fn poll_lookup(&mut self) -> Option<Result<PeerAddress, net_error>> {
+        if !self.inflight {
+            return None;
+        }
+        match self.replies.try_recv() {
+            Ok(resolved) => {
+                self.inflight = false;
+                Some(resolved)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.inflight = false;
+                Some(Err(net_error::InvalidHandle))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_parse_public_ip_reply() {
+        assert_eq!(
+            HttpPublicIpResolver::parse_reply(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n1.2.3.4\n"
+            )
+            .unwrap(),
+            PeerAddress::from_ipv4(1, 2, 3, 4)
+        );
+        assert!(HttpPublicIpResolver::parse_reply(
+            "HTTP/1.1 500 Internal Server Error\r\n\r\n1.2.3.4"
+        )
+        .is_err());
+        assert!(HttpPublicIpResolver::parse_reply("HTTP/1.1 200 OK\r\n\r\nnot-an-ip").is_err());
+        assert!(HttpPublicIpResolver::parse_reply("HTTP/1.1 200 OK\r\n").is_err());
+    }
+
+    #[test]
+    fn test_http_public_ip_resolver() {
+        let listener = TcpListener::bind("127.0.0.1:2620").unwrap();
+        let server = thread::spawn(move || {
+            let (mut sock, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = sock.read(&mut buf).unwrap();
+            sock.write_all(b"HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\n5.6.7.8\n")
+                .unwrap();
+        });
+
+        let mut resolver = HttpPublicIpResolver::new("http://127.0.0.1:2620/ip", 5);
+        assert_eq!(
+            resolver.resolve_public_ip().unwrap(),
+            PeerAddress::from_ipv4(5, 6, 7, 8)
+        );
+        server.join().unwrap();
+
+        // nothing listening
+        let mut resolver = HttpPublicIpResolver::new("http://127.0.0.1:2621/ip", 1);
+        assert!(resolver.resolve_public_ip().is_err());
+
+        // unsupported
+        let mut resolver = HttpPublicIpResolver::new("https://127.0.0.1:2620/ip", 1);
+        assert!(resolver.resolve_public_ip().is_err());
+    }
+}
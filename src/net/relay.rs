@@ -70,6 +70,8 @@ pub const RELAY_DUPLICATE_INFERENCE_WARMUP: usize = 128;
 pub struct Relayer {
     /// Connection to the p2p thread
     p2p: NetworkHandle,
+    /// Whether or not to advertize and forward microblocks
+    relay_microblocks: bool,
 }
 
 #[derive(Debug)]
@@ -385,15 +387,18 @@ impl RelayerStats {
     /// Sample a set of neighbors according to our relay data.
     /// Sampling is done *without* replacement, so the resulting neighbors list will have length
     /// min(count, rankings.len())
-    pub fn sample_neighbors(
+    /// Neighbors are visited in sorted order, so the sample only depends on the rankings and the
+    /// state of `rng`.
+    pub fn sample_neighbors<R: Rng>(
         rankings: HashMap<NeighborKey, usize>,
         count: usize,
+        rng: &mut R,
     ) -> Vec<NeighborKey> {
         let mut ret = vec![];
-        let mut rng = thread_rng();
 
         let mut norm = rankings.values().fold(0, |t, s| t + s);
         let mut rankings_vec: Vec<(NeighborKey, usize)> = rankings.into_iter().collect();
+        rankings_vec.sort();
 
         if norm <= 1 {
             // there is one or zero options
@@ -439,7 +444,10 @@ impl RelayerStats {
 
 impl Relayer {
     pub fn new(handle: NetworkHandle) -> Relayer {
-        Relayer { p2p: handle }
+        Relayer {
+            p2p: handle,
+            relay_microblocks: true,
+        }
     }
 
     pub fn from_p2p(network: &mut PeerNetwork) -> Relayer {
@@ -447,6 +455,11 @@ impl Relayer {
         Relayer::new(handle)
     }
 
+    /// Point the relayer at a different p2p network, e.g. one that replaced a crashed p2p thread.
+    pub fn set_network_handle(&mut self, handle: NetworkHandle) {
+        self.p2p = handle;
+    }
+
     /// Given blocks pushed to us, verify that they correspond to expected block data.
     pub fn validate_blocks_push(
         conn: &SortitionDBConn,
@@ -1047,7 +1060,12 @@ impl Relayer {
         }
 
         if let Err(e) = mempool.submit(consensus_hash, block_hash, tx) {
-            info!("Reject transaction {}: {:?}", txid, &e);
+            info!(
+                "Reject transaction {} ({}): {:?}",
+                txid,
+                e.reason_code(),
+                &e
+            );
             return false;
         }
 
@@ -1124,12 +1142,22 @@ impl Relayer {
             .broadcast_message(vec![], StacksMessageType::Blocks(blocks_data))
     }
 
+    /// Stop (or resume) advertizing and forwarding microblocks to our neighbors.  Microblocks we
+    /// receive are still stored.
+    pub fn set_relay_microblocks(&mut self, relay_microblocks: bool) -> () {
+        self.relay_microblocks = relay_microblocks;
+    }
+
     pub fn broadcast_microblock(
         &mut self,
         block_consensus_hash: &ConsensusHash,
         block_header_hash: &BlockHeaderHash,
         microblock: StacksMicroblock,
     ) -> Result<(), net_error> {
+        if !self.relay_microblocks {
+            debug!("Microblock relaying is disabled; not broadcasting microblock");
+            return Ok(());
+        }
         self.p2p.broadcast_message(
             vec![],
             StacksMessageType::Microblocks(MicroblocksData {
@@ -1187,7 +1215,7 @@ impl Relayer {
                 // have the p2p thread tell our neighbors about newly-discovered confirmed microblock streams
                 let mblocks_available =
                     Relayer::load_blocks_available_data(sortdb, new_confirmed_microblocks)?;
-                if mblocks_available.len() > 0 {
+                if mblocks_available.len() > 0 && self.relay_microblocks {
                     debug!(
                         "{:?}: Confirmed microblock streams available: {}",
                         &_local_peer,
@@ -1199,7 +1227,7 @@ impl Relayer {
                 }
 
                 // have the p2p thread forward all new unconfirmed microblocks
-                if new_microblocks.len() > 0 {
+                if new_microblocks.len() > 0 && self.relay_microblocks {
                     debug!(
                         "{:?}: Unconfirmed microblocks: {}",
                         &_local_peer,
@@ -1659,10 +1687,7 @@ mod test {
         assert_eq!(relayer_stats.relay_stats.len(), 1);
         assert_eq!(relayer_stats.relay_stats.get(&na).unwrap().num_messages, 3);
         assert_eq!(relayer_stats.relay_stats.get(&na).unwrap().num_bytes, 3);
-        assert!(
-            relayer_stats.relay_stats.get(&na).unwrap().last_seen < now
-                && relayer_stats.relay_stats.get(&na).unwrap().last_seen >= get_epoch_time_secs()
-        );
+        assert_eq!(relayer_stats.relay_stats.get(&na).unwrap().last_seen, now);
         assert_eq!(relayer_stats.relay_updates.len(), 1);
 
         let relay_stats_3 = RelayStats {
@@ -1678,10 +1703,7 @@ mod test {
         assert_eq!(relayer_stats.relay_stats.len(), 1);
         assert_eq!(relayer_stats.relay_stats.get(&na).unwrap().num_messages, 3);
         assert_eq!(relayer_stats.relay_stats.get(&na).unwrap().num_bytes, 3);
-        assert!(
-            relayer_stats.relay_stats.get(&na).unwrap().last_seen < now
-                && relayer_stats.relay_stats.get(&na).unwrap().last_seen >= get_epoch_time_secs()
-        );
+        assert_eq!(relayer_stats.relay_stats.get(&na).unwrap().last_seen, now);
         assert_eq!(relayer_stats.relay_updates.len(), 1);
 
         for i in 0..(MAX_RELAYER_STATS + 1) {
@@ -295,12 +295,14 @@ impl RPCPoxInfoData {
 }
 
 impl RPCNeighborsInfo {
-    /// Load neighbor address information from the peer network
+    /// Load neighbor address information from the peer network, as of `now` (in seconds since
+    /// the epoch)
     pub fn from_p2p(
         network_id: u32,
         peers: &PeerMap,
         chain_view: &BurnchainView,
         peerdb: &PeerDB,
+        now: u64,
     ) -> Result<RPCNeighborsInfo, net_error> {
         let neighbor_sample = PeerDB::get_random_neighbors(
             peerdb.conn(),
@@ -308,6 +310,7 @@ impl RPCNeighborsInfo {
             MAX_NEIGHBORS_DATA_LEN,
             chain_view.burn_block_height,
             false,
+            now,
         )
         .map_err(net_error::DBError)?;
 
@@ -553,9 +556,10 @@ impl ConversationHttp {
         chain_view: &BurnchainView,
         peers: &PeerMap,
         peerdb: &PeerDB,
+        now: u64,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let neighbor_data = RPCNeighborsInfo::from_p2p(network_id, peers, chain_view, peerdb)?;
+        let neighbor_data = RPCNeighborsInfo::from_p2p(network_id, peers, chain_view, peerdb, now)?;
         let response = HttpResponseType::Neighbors(response_metadata, neighbor_data);
         response.send(http, fd)
     }
@@ -1251,6 +1255,7 @@ impl ConversationHttp {
                     chain_view,
                     peers,
                     peerdb,
+                    self.connection.options.clock.now_secs(),
                 )?;
                 None
             }
@@ -18,7 +18,10 @@
 */
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
 
 // Message Priorities/Levels
 // Apache Conventions defined here: https://commons.apache.org/proper/commons-logging/guide.html#Message_PrioritiesLevels
@@ -72,10 +75,75 @@ pub fn get_loglevel() -> u8 {
     }
 }
 
+lazy_static! {
+    // process-wide log level overrides, by module name
+    static ref MODULE_LOGLEVELS: RwLock<HashMap<String, u8>> = RwLock::new(HashMap::new());
+}
+
+// whether MODULE_LOGLEVELS has any entries, so that logging without overrides (the usual case)
+// doesn't need to take its lock.  Only written while holding the write lock.
+static HAVE_MODULE_LOGLEVELS: AtomicBool = AtomicBool::new(false);
+
+/// Parse a log level name ("trace", "debug", "info", "warn", "error" or "fatal").
+pub fn parse_loglevel(name: &str) -> Result<u8, String> {
+    match name.to_lowercase().as_str() {
+        "trace" => Ok(LOG_TRACE),
+        "debug" => Ok(LOG_DEBUG),
+        "info" => Ok(LOG_INFO),
+        "warn" => Ok(LOG_WARN),
+        "error" => Ok(LOG_ERROR),
+        "fatal" => Ok(LOG_FATAL),
+        _ => Err(format!("Invalid log level '{}'", name)),
+    }
+}
+
+/// Override the log level of every module with the given name, in all threads.
+/// A module's name is any one segment of its path, e.g. `coordinator` for
+/// `chainstate::coordinator::comm`.
+pub fn set_module_loglevel(module: &str, ll: u8) -> Result<(), String> {
+    match ll {
+        LOG_TRACE..=LOG_FATAL => {
+            let mut module_loglevels = MODULE_LOGLEVELS
+                .write()
+                .expect("FATAL: module log levels lock poisoned");
+            module_loglevels.insert(module.to_string(), ll);
+            HAVE_MODULE_LOGLEVELS.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        _ => Err("Invalid log level".to_string()),
+    }
+}
+
+/// Remove all module log level overrides.
+pub fn clear_module_loglevels() {
+    let mut module_loglevels = MODULE_LOGLEVELS
+        .write()
+        .expect("FATAL: module log levels lock poisoned");
+    module_loglevels.clear();
+    HAVE_MODULE_LOGLEVELS.store(false, Ordering::SeqCst);
+}
+
+/// Get the log level in effect for the given module path (as given by `module_path!()`).
+/// The override for the innermost matching path segment wins; modules without an override
+/// use the global level.
+pub fn get_module_loglevel(module_path: &str) -> u8 {
+    if HAVE_MODULE_LOGLEVELS.load(Ordering::SeqCst) {
+        let module_loglevels = MODULE_LOGLEVELS
+            .read()
+            .expect("FATAL: module log levels lock poisoned");
+        for segment in module_path.rsplit("::") {
+            if let Some(ll) = module_loglevels.get(segment) {
+                return *ll;
+            }
+        }
+    }
+    get_loglevel()
+}
+
 #[macro_export]
 macro_rules! trace {
     ($($arg:tt)*) => ({
-        if ::util::log::get_loglevel() <= ::util::log::LOG_TRACE {
+        if ::util::log::get_module_loglevel(module_path!()) <= ::util::log::LOG_TRACE {
             use std::time::SystemTime;
             use std::thread;
             let (ts_sec, ts_msec) = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
@@ -90,7 +158,7 @@ macro_rules! trace {
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => ({
-        if ::util::log::get_loglevel() <= ::util::log::LOG_DEBUG {
+        if ::util::log::get_module_loglevel(module_path!()) <= ::util::log::LOG_DEBUG {
             use std::time::SystemTime;
             use std::thread;
             let (ts_sec, ts_msec) = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
@@ -105,7 +173,7 @@ macro_rules! debug {
 #[macro_export]
 macro_rules! info {
     ($($arg:tt)*) => ({
-        if ::util::log::get_loglevel() <= ::util::log::LOG_INFO {
+        if ::util::log::get_module_loglevel(module_path!()) <= ::util::log::LOG_INFO {
             use std::time::SystemTime;
             use std::thread;
             let (ts_sec, ts_msec) = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
@@ -120,7 +188,7 @@ macro_rules! info {
 #[macro_export]
 macro_rules! warn {
     ($($arg:tt)*) => ({
-        if ::util::log::get_loglevel() <= ::util::log::LOG_WARN {
+        if ::util::log::get_module_loglevel(module_path!()) <= ::util::log::LOG_WARN {
             use std::time::SystemTime;
             use std::thread;
             use crate::monitoring::increment_warning_emitted_counter;
@@ -138,7 +206,7 @@ macro_rules! warn {
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => ({
-        if ::util::log::get_loglevel() <= ::util::log::LOG_ERROR {
+        if ::util::log::get_module_loglevel(module_path!()) <= ::util::log::LOG_ERROR {
             use std::time::SystemTime;
             use std::thread;
             use crate::monitoring::increment_errors_emitted_counter;
@@ -156,7 +224,7 @@ macro_rules! error {
 #[macro_export]
 macro_rules! fatal {
     ($($arg:tt)*) => ({
-        if ::util::log::get_loglevel() <= ::util::log::LOG_FATAL {
+        if ::util::log::get_module_loglevel(module_path!()) <= ::util::log::LOG_FATAL {
             use std::time::SystemTime;
             use std::thread;
             let (ts_sec, ts_msec) = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
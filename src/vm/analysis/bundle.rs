@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use vm::analysis::errors::{CheckErrors, CheckResult};
+use vm::analysis::{type_check, AnalysisDatabase, ContractAnalysis};
+use vm::functions::NativeFunctions;
+use vm::representations::SymbolicExpressionType::{
+    Atom, AtomValue, Field, List, LiteralValue, TraitReference,
+};
+use vm::representations::{SymbolicExpression, TraitDefinition};
+use vm::types::{PrincipalData, QualifiedContractIdentifier, Value};
+
+#[derive(Clone, Copy, PartialEq)]
+enum VisitState {
+    Unvisited,
+    Visiting,
+    Visited,
+}
+
+/// Type-check a bundle of contracts that may depend on one another, in any order.
+/// A contract depends on another if it calls it with `contract-call?`, or refers to one of its
+/// traits.  Contracts are analyzed (and inserted into `analysis_db`) after the bundled contracts
+/// they depend on, and their analyses are returned in that order.  Dependencies outside the
+/// bundle must already be in `analysis_db`.
+pub fn type_check_bundle(
+    contracts: Vec<(QualifiedContractIdentifier, Vec<SymbolicExpression>)>,
+    analysis_db: &mut AnalysisDatabase,
+) -> CheckResult<Vec<ContractAnalysis>> {
+    let mut indexes = HashMap::new();
+    for (i, (contract_identifier, _)) in contracts.iter().enumerate() {
+        indexes.insert(contract_identifier.clone(), i);
+    }
+
+    // edges to other contracts in this bundle
+    let dependencies: Vec<Vec<usize>> = contracts
+        .iter()
+        .enumerate()
+        .map(|(i, (_, expressions))| {
+            let mut deps = vec![];
+            find_contract_dependencies(
+                expressions,
+                &mut |contract_identifier: &QualifiedContractIdentifier| {
+                    if let Some(dep) = indexes.get(contract_identifier) {
+                        if *dep != i && !deps.contains(dep) {
+                            deps.push(*dep);
+                        }
+                    }
+                },
+            );
+            deps
+        })
+        .collect();
+
+    let mut states = vec![VisitState::Unvisited; contracts.len()];
+    let mut path = vec![];
+    let mut order = vec![];
+    for i in 0..contracts.len() {
+        sort_dependencies(i, &dependencies, &mut states, &mut path, &mut order).map_err(
+            |cycle| {
+                CheckErrors::CircularReference(
+                    cycle
+                        .into_iter()
+                        .map(|i| contracts[i].0.to_string())
+                        .collect(),
+                )
+            },
+        )?;
+    }
+
+    let mut contracts: Vec<_> = contracts.into_iter().map(Some).collect();
+    let mut analyses = vec![];
+    for i in order {
+        let (contract_identifier, mut expressions) = contracts[i]
+            .take()
+            .expect("BUG: contract visited twice in bundle");
+        let analysis = type_check(&contract_identifier, &mut expressions, analysis_db, true)?;
+        analyses.push(analysis);
+    }
+    Ok(analyses)
+}
+
+/// Depth-first post-order walk over the bundle's dependency graph.
+/// On a cycle, returns the contracts in it.
+fn sort_dependencies(
+    node: usize,
+    dependencies: &Vec<Vec<usize>>,
+    states: &mut Vec<VisitState>,
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), Vec<usize>> {
+    match states[node] {
+        VisitState::Visited => return Ok(()),
+        VisitState::Visiting => {
+            let start = path
+                .iter()
+                .position(|n| *n == node)
+                .expect("BUG: visiting node not on path");
+            return Err(path[start..].to_vec());
+        }
+        VisitState::Unvisited => {}
+    }
+
+    states[node] = VisitState::Visiting;
+    path.push(node);
+    for dep in dependencies[node].iter() {
+        sort_dependencies(*dep, dependencies, states, path, order)?;
+    }
+    path.pop();
+    states[node] = VisitState::Visited;
+    order.push(node);
+    Ok(())
+}
+
+/// Call `found` on each contract that these expressions call into or take a trait from.
+fn find_contract_dependencies<F>(expressions: &[SymbolicExpression], found: &mut F)
+where
+    F: FnMut(&QualifiedContractIdentifier),
+{
+    for expression in expressions.iter() {
+        match expression.expr {
+            Field(ref trait_identifier) => found(&trait_identifier.contract_identifier),
+            TraitReference(_, TraitDefinition::Imported(ref trait_identifier)) => {
+                found(&trait_identifier.contract_identifier)
+            }
+            List(ref children) => {
+                let is_contract_call = match children.first() {
+                    Some(SymbolicExpression {
+                        expr: Atom(ref name),
+                        ..
+                    }) => match NativeFunctions::lookup_by_name(name) {
+                        Some(NativeFunctions::ContractCall) => true,
+                        _ => false,
+                    },
+                    _ => false,
+                };
+                if is_contract_call && children.len() > 1 {
+                    match children[1].expr {
+                        LiteralValue(Value::Principal(PrincipalData::Contract(ref callee)))
+                        | AtomValue(Value::Principal(PrincipalData::Contract(ref callee))) => {
+                            found(callee)
+                        }
+                        _ => {}
+                    }
+                }
+                find_contract_dependencies(children, found);
+            }
+            _ => {}
+        }
+    }
+}
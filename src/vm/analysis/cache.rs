@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use util::hash::Sha512Trunc256Sum;
+use vm::analysis::ContractAnalysis;
+use vm::representations::SymbolicExpression;
+#[cfg(feature = "developer-mode")]
+use vm::representations::{Span, SymbolicExpressionType};
+use vm::types::QualifiedContractIdentifier;
+
+/// Cache of contract analyses, so that tools which analyze the same contract over and over
+/// (e.g. a language server) don't have to re-run every analysis pass each time.
+///
+/// Entries are keyed by contract identifier and checked against a hash of the contract's parsed
+/// expressions, so a change to the contract's code invalidates its entry.  Note that the cache
+/// does not track the contracts that a contract depends on -- callers that redefine a dependency
+/// should `clear()` the cache.
+pub struct AnalysisCache {
+    entries: HashMap<QualifiedContractIdentifier, (Sha512Trunc256Sum, ContractAnalysis)>,
+}
+
+impl Default for AnalysisCache {
+    fn default() -> AnalysisCache {
+        AnalysisCache::new()
+    }
+}
+
+impl AnalysisCache {
+    pub fn new() -> AnalysisCache {
+        AnalysisCache {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Hash of the canonical (parsed) form of a contract, so whitespace and comments don't matter.
+    pub fn hash_expressions(expressions: &[SymbolicExpression]) -> Sha512Trunc256Sum {
+        #[allow(unused_mut)]
+        let mut canonical = expressions.to_vec();
+        #[cfg(feature = "developer-mode")]
+        AnalysisCache::strip_spans(&mut canonical);
+
+        let serialized = serde_json::to_vec(&canonical)
+            .expect("FATAL: failed to serialize contract expressions");
+        Sha512Trunc256Sum::from_data(&serialized)
+    }
+
+    /// Source locations depend on formatting, so leave them out of the hash
+    #[cfg(feature = "developer-mode")]
+    fn strip_spans(expressions: &mut [SymbolicExpression]) {
+        for expression in expressions.iter_mut() {
+            expression.span = Span::zero();
+            if let SymbolicExpressionType::List(ref mut children) = expression.expr {
+                AnalysisCache::strip_spans(children);
+            }
+        }
+    }
+
+    /// Get a copy of the cached analysis for this contract, if the contract's code hasn't changed.
+    /// The returned analysis has no cost tracker.
+    pub fn get(
+        &self,
+        contract_identifier: &QualifiedContractIdentifier,
+        expressions_hash: &Sha512Trunc256Sum,
+    ) -> Option<ContractAnalysis> {
+        match self.entries.get(contract_identifier) {
+            Some((cached_hash, analysis)) if cached_hash == expressions_hash => {
+                Some(analysis.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Store a contract's analysis, replacing whatever was cached for it before.
+    pub fn insert(&mut self, expressions_hash: Sha512Trunc256Sum, analysis: &ContractAnalysis) {
+        let mut cached = analysis.clone();
+        cached.cost_track = None;
+        self.entries.insert(
+            analysis.contract_identifier.clone(),
+            (expressions_hash, cached),
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
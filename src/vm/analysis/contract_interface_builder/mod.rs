@@ -22,6 +22,7 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
         type_map: _,
         cost_track: _,
         contract_interface: _,
+        warnings: _,
     } = contract_analysis;
 
     contract_interface
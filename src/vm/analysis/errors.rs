@@ -1,5 +1,6 @@
 use std::error;
 use std::fmt;
+use vm::ast::errors::{ParseError, ParseErrors};
 use vm::costs::{CostErrors, ExecutionCost};
 use vm::diagnostic::{DiagnosableError, Diagnostic};
 use vm::representations::SymbolicExpression;
@@ -143,6 +144,7 @@ pub enum CheckErrors {
     ImportTraitBadSignature,
     TraitReferenceNotAllowed,
     BadTraitImplementation(String, String),
+    TraitMethodNotReadOnly(String, String),
     DefineTraitBadSignature,
     UnexpectedTraitOrFieldReference,
     TraitBasedContractCallInReadOnly,
@@ -156,6 +158,9 @@ pub enum CheckErrors {
 
     WriteAttemptedInReadOnly,
     AtBlockClosureMustBeReadOnly,
+
+    // the checked snippet isn't valid Clarity
+    ParseError(ParseErrors),
 }
 
 #[derive(Debug, PartialEq)]
@@ -226,6 +231,12 @@ impl From<CostErrors> for CheckErrors {
     }
 }
 
+impl From<ParseError> for CheckError {
+    fn from(err: ParseError) -> Self {
+        CheckError::new(CheckErrors::ParseError(err.err))
+    }
+}
+
 impl error::Error for CheckError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         None
@@ -364,6 +375,7 @@ impl DiagnosableError for CheckErrors {
             CheckErrors::TraitBasedContractCallInReadOnly => format!("use of trait based contract calls are not allowed in read-only context"),
             CheckErrors::WriteAttemptedInReadOnly => format!("expecting read-only statements, detected a writing operation"),
             CheckErrors::AtBlockClosureMustBeReadOnly => format!("(at-block ...) closures expect read-only statements, but detected a writing operation"),
+            CheckErrors::ParseError(e) => format!("failed to parse: {}", e.message()),
             CheckErrors::BadTokenName => format!("expecting an token name as an argument"),
             CheckErrors::DefineFTBadSignature => format!("(define-token ...) expects a token name as an argument"),
             CheckErrors::DefineNFTBadSignature => format!("(define-asset ...) expects an asset name and an asset identifier type signature as arguments"),
@@ -373,6 +385,7 @@ impl DiagnosableError for CheckErrors {
             CheckErrors::TraitMethodUnknown(trait_name, func_name) => format!("method '{}' unspecified in trait <{}>", func_name, trait_name),
             CheckErrors::ImportTraitBadSignature => format!("(use-trait ...) expects a trait name and a trait identifier"),
             CheckErrors::BadTraitImplementation(trait_name, func_name) => format!("invalid signature for method '{}' regarding trait's specification <{}>", func_name, trait_name),
+            CheckErrors::TraitMethodNotReadOnly(trait_name, func_name) => format!("method '{}' must be read-only to implement trait <{}>", func_name, trait_name),
             CheckErrors::ExpectedTraitIdentifier => format!("expecting expression of type trait identifier"),
             CheckErrors::UnexpectedTraitOrFieldReference => format!("unexpected use of trait reference or field"),
             CheckErrors::DefineTraitBadSignature => format!("invalid trait definition"),
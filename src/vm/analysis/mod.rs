@@ -1,24 +1,30 @@
 pub mod analysis_db;
+pub mod bundle;
+pub mod cache;
 pub mod contract_interface_builder;
 pub mod errors;
 pub mod read_only_checker;
 pub mod trait_checker;
 pub mod type_checker;
 pub mod types;
+pub mod unreachable_checker;
 
-pub use self::types::{AnalysisPass, ContractAnalysis};
+pub use self::types::{AnalysisPass, AnalysisPasses, AnalysisWarning, ContractAnalysis};
 use vm::costs::LimitedCostTracker;
 use vm::database::STORE_CONTRACT_SRC_INTERFACE;
 use vm::representations::SymbolicExpression;
 use vm::types::{QualifiedContractIdentifier, TypeSignature};
 
 pub use self::analysis_db::AnalysisDatabase;
+pub use self::bundle::type_check_bundle;
+pub use self::cache::AnalysisCache;
 pub use self::errors::{CheckError, CheckErrors, CheckResult};
 
 use self::contract_interface_builder::build_contract_interface;
 use self::read_only_checker::ReadOnlyChecker;
 use self::trait_checker::TraitChecker;
 use self::type_checker::TypeChecker;
+use self::unreachable_checker::UnreachableChecker;
 
 pub fn mem_type_check(snippet: &str) -> CheckResult<(Option<TypeSignature>, ContractAnalysis)> {
     use vm::ast::parse;
@@ -45,6 +51,20 @@ pub fn mem_type_check(snippet: &str) -> CheckResult<(Option<TypeSignature>, Cont
     })
 }
 
+/// Is the given Clarity snippet read-only?
+/// Only the read-only pass is run over the snippet -- it isn't type-checked.
+/// Returns a `ParseError` if the snippet isn't valid Clarity, and the read-only pass's error if
+/// that fails (e.g. on an unknown function).
+pub fn is_snippet_read_only(snippet: &str) -> CheckResult<bool> {
+    use vm::ast::build_ast;
+    use vm::database::MemoryBackingStore;
+    let contract_identifier = QualifiedContractIdentifier::transient();
+    let expressions = build_ast(&contract_identifier, snippet, &mut ())?.expressions;
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    analysis_db.execute(|db| ReadOnlyChecker::run_snippet(db, &expressions))
+}
+
 // Legacy function
 // The analysis is not just checking type.
 pub fn type_check(
@@ -59,26 +79,91 @@ pub fn type_check(
         analysis_db,
         insert_contract,
         LimitedCostTracker::new_max_limit(),
+        AnalysisPasses::all(),
     )
     .map_err(|(e, _cost_tracker)| e)
 }
 
+/// Analyze a contract.  The type checker always runs; `passes` selects which of the other
+/// passes run as well.  Use `AnalysisPasses::all()` unless the contract was already validated --
+/// see `AnalysisPasses`.
 pub fn run_analysis(
     contract_identifier: &QualifiedContractIdentifier,
     expressions: &mut [SymbolicExpression],
     analysis_db: &mut AnalysisDatabase,
     save_contract: bool,
     cost_tracker: LimitedCostTracker,
+    passes: AnalysisPasses,
 ) -> Result<ContractAnalysis, (CheckError, LimitedCostTracker)> {
+    run_analysis_with_cache(
+        contract_identifier,
+        expressions,
+        analysis_db,
+        save_contract,
+        cost_tracker,
+        None,
+        passes,
+        false,
+    )
+}
+
+/// Same as `run_analysis`, but if a cache is given, then an identical contract that was already
+/// analyzed successfully is not re-analyzed -- its cached analysis is returned instead.
+/// Only analyses that ran all of the `passes` are added to the cache.
+/// If `warn_unreachable` is set, the `UnreachableChecker` pass also runs, and its findings are
+/// collected in the analysis's `warnings`.
+pub fn run_analysis_with_cache(
+    contract_identifier: &QualifiedContractIdentifier,
+    expressions: &mut [SymbolicExpression],
+    analysis_db: &mut AnalysisDatabase,
+    save_contract: bool,
+    cost_tracker: LimitedCostTracker,
+    mut cache: Option<&mut AnalysisCache>,
+    passes: AnalysisPasses,
+    warn_unreachable: bool,
+) -> Result<ContractAnalysis, (CheckError, LimitedCostTracker)> {
+    let expressions_hash = match cache {
+        Some(ref cache) => {
+            let expressions_hash = AnalysisCache::hash_expressions(expressions);
+            if let Some(mut contract_analysis) = cache.get(contract_identifier, &expressions_hash) {
+                contract_analysis.replace_contract_cost_tracker(cost_tracker);
+                let result = analysis_db.execute(|db| {
+                    // the cached analysis may have been made with different warnings enabled
+                    contract_analysis.warnings.clear();
+                    if warn_unreachable {
+                        UnreachableChecker::run_pass(&mut contract_analysis, db)?;
+                    }
+                    if save_contract {
+                        db.insert_contract(&contract_identifier, &contract_analysis)?;
+                    }
+                    Ok(())
+                });
+                return match result {
+                    Ok(_) => Ok(contract_analysis),
+                    Err(e) => Err((e, contract_analysis.take_contract_cost_tracker())),
+                };
+            }
+            Some(expressions_hash)
+        }
+        None => None,
+    };
+
     let mut contract_analysis = ContractAnalysis::new(
         contract_identifier.clone(),
         expressions.to_vec(),
         cost_tracker,
     );
     let result = analysis_db.execute(|db| {
-        ReadOnlyChecker::run_pass(&mut contract_analysis, db)?;
+        if passes.contains(AnalysisPasses::READ_ONLY) {
+            ReadOnlyChecker::run_pass(&mut contract_analysis, db)?;
+        }
         TypeChecker::run_pass(&mut contract_analysis, db)?;
-        TraitChecker::run_pass(&mut contract_analysis, db)?;
+        if passes.contains(AnalysisPasses::TRAITS) {
+            TraitChecker::run_pass(&mut contract_analysis, db)?;
+        }
+        if warn_unreachable {
+            UnreachableChecker::run_pass(&mut contract_analysis, db)?;
+        }
         if STORE_CONTRACT_SRC_INTERFACE {
             let interface = build_contract_interface(&contract_analysis);
             contract_analysis.contract_interface = Some(interface);
@@ -89,7 +174,15 @@ pub fn run_analysis(
         Ok(())
     });
     match result {
-        Ok(_) => Ok(contract_analysis),
+        Ok(_) => {
+            // an analysis that skipped passes mustn't be handed out to callers that didn't
+            if let (Some(cache), Some(expressions_hash)) = (cache.as_mut(), expressions_hash) {
+                if passes == AnalysisPasses::all() {
+                    cache.insert(expressions_hash, &contract_analysis);
+                }
+            }
+            Ok(contract_analysis)
+        }
         Err(e) => Err((e, contract_analysis.take_contract_cost_tracker())),
     }
 }
@@ -58,6 +58,32 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
         Ok(())
     }
 
+    /// Checks the top-level expressions of a snippet (rather than a whole contract),
+    ///   returning whether or not all of its non-definition expressions are read only.
+    /// Definitions in the snippet are checked for validity, but don't affect the result.
+    pub fn run_snippet(
+        analysis_db: &'a mut AnalysisDatabase<'b>,
+        expressions: &[SymbolicExpression],
+    ) -> CheckResult<bool> {
+        let mut checker = ReadOnlyChecker::new(analysis_db);
+        let mut read_only = true;
+        for exp in expressions.iter() {
+            let mut result = if DefineFunctionsParsed::try_parse(exp)?.is_some() {
+                checker.check_reads_only_valid(exp).map(|_| true)
+            } else {
+                checker.check_read_only(exp)
+            };
+            if let Err(ref mut error) = result {
+                if !error.has_expression() {
+                    error.set_expression(&exp);
+                }
+            }
+            read_only = result? && read_only;
+        }
+
+        Ok(read_only)
+    }
+
     fn check_define_function(
         &mut self,
         signature: &[SymbolicExpression],
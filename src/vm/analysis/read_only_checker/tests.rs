@@ -1,4 +1,7 @@
-use vm::analysis::{mem_type_check, type_check, AnalysisDatabase, CheckError, CheckErrors};
+use vm::analysis::{
+    is_snippet_read_only, mem_type_check, type_check, AnalysisDatabase, CheckError, CheckErrors,
+};
+use vm::ast::errors::ParseErrors;
 use vm::ast::parse;
 use vm::database::MemoryBackingStore;
 use vm::types::QualifiedContractIdentifier;
@@ -178,3 +181,45 @@ fn test_contract_call_read_only_violations() {
     db.execute(|db| type_check(&contract_ok_caller_id, &mut ok_caller, db, false))
         .unwrap();
 }
+
+#[test]
+fn test_is_snippet_read_only() {
+    assert_eq!(is_snippet_read_only("(+ 1 (* 2 3))").unwrap(), true);
+    assert_eq!(
+        is_snippet_read_only(
+            "(define-data-var foo int 1)
+             (var-get foo)"
+        )
+        .unwrap(),
+        true
+    );
+    assert_eq!(
+        is_snippet_read_only(
+            "(define-data-var foo int 1)
+             (var-set foo 2)"
+        )
+        .unwrap(),
+        false
+    );
+    // one writing expression makes the whole snippet writing
+    assert_eq!(
+        is_snippet_read_only(
+            "(define-data-var foo int 1)
+             (var-get foo)
+             (begin (var-set foo 2) (var-get foo))"
+        )
+        .unwrap(),
+        false
+    );
+
+    assert_eq!(
+        is_snippet_read_only("(+ 1 2").unwrap_err().err,
+        CheckErrors::ParseError(ParseErrors::ClosingParenthesisExpected)
+    );
+    assert_eq!(
+        is_snippet_read_only("(no-such-function 1)")
+            .unwrap_err()
+            .err,
+        CheckErrors::UnknownFunction("no-such-function".to_string())
+    );
+}
@@ -289,3 +289,205 @@ fn test_write_attempt_in_readonly() {
     assert!(format!("{}", err.diagnostic)
         .contains("expecting read-only statements, detected a writing operation"));
 }
+
+#[test]
+fn test_analysis_cache() {
+    use vm::analysis::{run_analysis_with_cache, AnalysisCache, AnalysisPasses};
+    use vm::costs::{ExecutionCost, LimitedCostTracker};
+    use vm::database::MemoryBackingStore;
+    use vm::types::QualifiedContractIdentifier;
+
+    let contract_id = QualifiedContractIdentifier::local("cached").unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    let mut cache = AnalysisCache::default();
+    assert!(cache.is_empty());
+
+    // how much did this one call charge? without a cache, the passes always run
+    let mut analyze = |src: &str, cache: Option<&mut AnalysisCache>| {
+        let mut ast = parse(&contract_id, src).unwrap();
+        let mut analysis = run_analysis_with_cache(
+            &contract_id,
+            &mut ast,
+            &mut analysis_db,
+            false,
+            LimitedCostTracker::new_max_limit(),
+            cache,
+            AnalysisPasses::all(),
+            false,
+        )
+        .map_err(|(e, _)| e)
+        .unwrap();
+        let charged = analysis.take_contract_cost_tracker().get_total();
+        (analysis, charged)
+    };
+
+    let src = "(define-public (foo (x int)) (ok (+ x 1)))";
+    let changed_src = "(define-public (foo (x int)) (ok (- x 1)))";
+    let (_, src_cost) = analyze(src, None);
+    let (_, changed_src_cost) = analyze(changed_src, None);
+    assert!(src_cost != ExecutionCost::zero());
+    assert!(changed_src_cost != ExecutionCost::zero());
+
+    // a miss runs the passes, and charges exactly what an uncached analysis does
+    let (first, first_charged) = analyze(src, Some(&mut cache));
+    assert_eq!(first_charged, src_cost);
+    assert_eq!(cache.len(), 1);
+    assert!(!cache.is_empty());
+
+    // same code, modulo whitespace -- the passes don't run again
+    let (second, second_charged) = analyze(
+        "(define-public (foo (x int))\n  (ok (+ x 1)))",
+        Some(&mut cache),
+    );
+    assert_eq!(second_charged, ExecutionCost::zero());
+    assert_eq!(first.public_function_types, second.public_function_types);
+
+    // code changed, so the cached entry is stale
+    let (third, third_charged) = analyze(changed_src, Some(&mut cache));
+    assert_eq!(third_charged, changed_src_cost);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(first.public_function_types, third.public_function_types);
+
+    // and the new code is what's cached now
+    let (_, fourth_charged) = analyze(changed_src, Some(&mut cache));
+    assert_eq!(fourth_charged, ExecutionCost::zero());
+}
+
+#[test]
+fn test_skip_analysis_passes() {
+    use vm::analysis::{run_analysis, AnalysisPasses};
+    use vm::costs::LimitedCostTracker;
+    use vm::database::MemoryBackingStore;
+    use vm::types::QualifiedContractIdentifier;
+
+    let contract_id = QualifiedContractIdentifier::transient();
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+
+    let mut analyze = |src: &str, passes: AnalysisPasses| {
+        let mut ast = parse(&contract_id, src).unwrap();
+        run_analysis(
+            &contract_id,
+            &mut ast,
+            &mut analysis_db,
+            false,
+            LimitedCostTracker::new_max_limit(),
+            passes,
+        )
+        .map(|mut analysis| {
+            analysis.take_contract_cost_tracker();
+            analysis
+        })
+        .map_err(|(e, _)| e)
+    };
+
+    // the type checker's result doesn't depend on the other passes
+    let src = "(define-data-var x int 0)
+        (define-read-only (get-x) (var-get x))
+        (define-public (set-x (v int)) (ok (var-set x v)))";
+    let full = analyze(src, AnalysisPasses::all()).unwrap();
+    let reduced = analyze(src, AnalysisPasses::empty()).unwrap();
+    assert_eq!(full, reduced);
+
+    // a write in a read-only function is only caught by the read-only pass
+    let src = "(define-data-var x int 0)
+        (define-read-only (set-x) (var-set x 1))";
+    let err = analyze(src, AnalysisPasses::all()).unwrap_err();
+    assert_eq!(err.err, CheckErrors::WriteAttemptedInReadOnly);
+    let err = analyze(src, AnalysisPasses::TRAITS).unwrap_err();
+    assert_eq!(err.err, CheckErrors::WriteAttemptedInReadOnly);
+
+    // with it skipped, the function gets the same type it would as a private function
+    let reduced = analyze(src, AnalysisPasses::empty()).unwrap();
+    let private = analyze(
+        "(define-data-var x int 0)
+        (define-private (set-x) (var-set x 1))",
+        AnalysisPasses::all(),
+    )
+    .unwrap();
+    assert!(reduced.read_only_function_types.get("set-x").is_some());
+    assert_eq!(
+        reduced.read_only_function_types.get("set-x"),
+        private.private_function_types.get("set-x")
+    );
+}
+
+#[test]
+fn test_type_check_bundle() {
+    use vm::analysis::type_check_bundle;
+    use vm::database::MemoryBackingStore;
+    use vm::types::QualifiedContractIdentifier;
+
+    let contract_a = "(use-trait token-trait .contract-b.token-trait)
+         (define-public (transfer (token <token-trait>))
+           (contract-call? token get-balance))
+         (define-public (call-c)
+           (contract-call? .contract-c get-one))";
+    let contract_b = "(define-trait token-trait ((get-balance () (response uint uint))))";
+    let contract_c = "(define-public (get-one) (ok u1))";
+
+    let make_bundle = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| {
+                let src = match *name {
+                    "contract-a" => contract_a,
+                    "contract-b" => contract_b,
+                    "contract-c" => contract_c,
+                    _ => contract_b,
+                };
+                let contract_id = QualifiedContractIdentifier::local(name).unwrap();
+                let ast = parse(&contract_id, src).unwrap();
+                (contract_id, ast)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for names in [
+        vec!["contract-a", "contract-b", "contract-c"],
+        vec!["contract-c", "contract-b", "contract-a"],
+        vec!["contract-b", "contract-a", "contract-c"],
+    ]
+    .iter()
+    {
+        let mut marf = MemoryBackingStore::new();
+        let mut analysis_db = marf.as_analysis_db();
+        let analyses = type_check_bundle(make_bundle(names), &mut analysis_db).unwrap();
+        assert_eq!(analyses.len(), 3);
+
+        // contract-a is analyzed after both of its dependencies
+        let position = |name: &str| {
+            analyses
+                .iter()
+                .position(|a| a.contract_identifier.name.as_str() == name)
+                .unwrap()
+        };
+        assert!(position("contract-a") > position("contract-b"));
+        assert!(position("contract-a") > position("contract-c"));
+    }
+
+    // analyzed one at a time in the wrong order, contract-a fails
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    let (contract_id, mut ast) = make_bundle(&["contract-a"]).pop().unwrap();
+    assert!(type_check(&contract_id, &mut ast, &mut analysis_db, true).is_err());
+
+    // cycles are rejected
+    let cycle_d = "(define-public (foo) (contract-call? .cycle-e bar))";
+    let cycle_e = "(define-public (bar) (contract-call? .cycle-d foo))";
+    let bundle = vec![("cycle-d", cycle_d), ("cycle-e", cycle_e)]
+        .into_iter()
+        .map(|(name, src)| {
+            let contract_id = QualifiedContractIdentifier::local(name).unwrap();
+            let ast = parse(&contract_id, src).unwrap();
+            (contract_id, ast)
+        })
+        .collect();
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    match type_check_bundle(bundle, &mut analysis_db).unwrap_err().err {
+        CheckErrors::CircularReference(names) => assert_eq!(names.len(), 2),
+        e => panic!("Unexpected error: {:?}", e),
+    }
+}
@@ -90,6 +90,61 @@ fn test_incomplete_impl_trait_2() {
     }
 }
 
+#[test]
+#[cfg(feature = "trait_read_only")]
+fn test_impl_read_only_trait_method() {
+    let contract_defining_trait = "(define-trait trait-1 (
+            (get-1 (uint) (response uint uint) read-only)
+            (set-1 (uint) (response bool uint))))";
+    let impl_contract = "(impl-trait .defun.trait-1)
+        (define-read-only (get-1 (x uint)) (ok u1))
+        (define-read-only (set-1 (x uint)) (ok true))";
+    let def_contract_id = QualifiedContractIdentifier::local("defun").unwrap();
+    let impl_contract_id = QualifiedContractIdentifier::local("implem").unwrap();
+    let mut c1 = parse(&def_contract_id, contract_defining_trait).unwrap();
+    let mut c3 = parse(&impl_contract_id, impl_contract).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut db = marf.as_analysis_db();
+    db.execute(|db| {
+        type_check(&def_contract_id, &mut c1, db, true)?;
+        type_check(&impl_contract_id, &mut c3, db, true)
+    })
+    .unwrap();
+
+    let trait_definition = db
+        .execute(|db| db.get_defined_trait(&def_contract_id, "trait-1"))
+        .unwrap()
+        .unwrap();
+    assert!(trait_definition["get-1"].read_only);
+    assert!(!trait_definition["set-1"].read_only);
+}
+
+#[test]
+#[cfg(feature = "trait_read_only")]
+fn test_impl_read_only_trait_method_with_public() {
+    let contract_defining_trait = "(define-trait trait-1 (
+            (get-1 (uint) (response uint uint) read-only)))";
+    let impl_contract = "(impl-trait .defun.trait-1)
+        (define-data-var x uint u0)
+        (define-public (get-1 (y uint)) (begin (var-set x y) (ok y)))";
+    let def_contract_id = QualifiedContractIdentifier::local("defun").unwrap();
+    let impl_contract_id = QualifiedContractIdentifier::local("implem").unwrap();
+    let mut c1 = parse(&def_contract_id, contract_defining_trait).unwrap();
+    let mut c3 = parse(&impl_contract_id, impl_contract).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut db = marf.as_analysis_db();
+    let err = db
+        .execute(|db| {
+            type_check(&def_contract_id, &mut c1, db, true).unwrap();
+            type_check(&impl_contract_id, &mut c3, db, true)
+        })
+        .unwrap_err();
+    match err.err {
+        CheckErrors::TraitMethodNotReadOnly(_, _) => {}
+        _ => panic!("{:?}", err),
+    }
+}
+
 #[test]
 fn test_impl_trait_arg_admission_1() {
     let contract_defining_trait = "(define-trait trait-1 (
@@ -10,7 +10,7 @@ use vm::contexts::OwnedEnvironment;
 use vm::representations::SymbolicExpression;
 use vm::types::{
     FixedFunction, FunctionType, PrincipalData, QualifiedContractIdentifier, TypeSignature, Value,
-    BUFF_32, BUFF_64,
+    BUFF_32, BUFF_64, TRAIT_READ_ONLY_METHODS,
 };
 
 use vm::database::MemoryBackingStore;
@@ -122,6 +122,53 @@ fn test_define_trait() {
     }
 }
 
+#[test]
+fn test_define_trait_read_only_annotation() {
+    let read_only = "(define-trait trait-1 ((get-1 () (response uint uint) read-only)))";
+    let bad_annotation = "(define-trait trait-1 ((get-1 () (response uint uint) public)))";
+
+    if TRAIT_READ_ONLY_METHODS {
+        mem_type_check(read_only).unwrap();
+        assert_eq!(
+            CheckErrors::DefineTraitBadSignature,
+            type_check_helper(bad_annotation).unwrap_err().err
+        );
+    } else {
+        // not activated: a method signature with a fourth element is rejected as before
+        for bad_test in [read_only, bad_annotation].iter() {
+            assert_eq!(
+                CheckErrors::InvalidTypeDescription,
+                type_check_helper(bad_test).unwrap_err().err
+            );
+        }
+    }
+}
+
+#[test]
+fn test_define_trait_analysis_json() {
+    use vm::database::ClaritySerializable;
+
+    // a trait without read-only methods is stored exactly as it was before the annotation existed
+    let contract = "(define-trait trait-1 ((get-1 (uint) (response uint uint))))";
+    let (_, analysis) = mem_type_check(contract).unwrap();
+    let expected = r#"{"trait-1":{"get-1":{"args":["UIntType"],"returns":{"ResponseType":["UIntType","UIntType"]}}}}"#;
+    assert_eq!(
+        serde_json::to_string(&analysis.defined_traits).unwrap(),
+        expected
+    );
+    assert!(!analysis.serialize().contains("\"read_only\""));
+
+    if TRAIT_READ_ONLY_METHODS {
+        let contract = "(define-trait trait-1 ((get-1 (uint) (response uint uint) read-only)))";
+        let (_, analysis) = mem_type_check(contract).unwrap();
+        let expected = r#"{"trait-1":{"get-1":{"args":["UIntType"],"returns":{"ResponseType":["UIntType","UIntType"]},"read_only":true}}}"#;
+        assert_eq!(
+            serde_json::to_string(&analysis.defined_traits).unwrap(),
+            expected
+        );
+    }
+}
+
 #[test]
 fn test_use_trait() {
     let bad = [
@@ -40,6 +40,66 @@ pub struct ContractAnalysis {
     pub type_map: Option<TypeMap>,
     #[serde(skip)]
     pub cost_track: Option<LimitedCostTracker>,
+    /// Warnings from opt-in analysis passes.  These don't make the contract invalid.
+    #[serde(skip)]
+    pub warnings: Vec<AnalysisWarning>,
+}
+
+/// Something suspicious, but not invalid, that an analysis pass found in a contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisWarning {
+    pub message: String,
+    pub expression: SymbolicExpression,
+}
+
+impl AnalysisWarning {
+    pub fn new(message: &str, expression: &SymbolicExpression) -> AnalysisWarning {
+        AnalysisWarning {
+            message: message.to_string(),
+            expression: expression.clone(),
+        }
+    }
+}
+
+/// Which of the optional analysis passes `run_analysis` runs.  The type checker isn't listed --
+/// it always runs, since it produces the contract's types and interface.
+///
+/// Skipping passes is only safe for code that was already fully analyzed elsewhere (e.g. boot
+/// code at startup); a contract that skips them may violate the rules those passes enforce.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalysisPasses(u8);
+
+impl AnalysisPasses {
+    /// ReadOnlyChecker: read-only functions don't write
+    pub const READ_ONLY: AnalysisPasses = AnalysisPasses(0x01);
+    /// TraitChecker: declared trait implementations are complete
+    pub const TRAITS: AnalysisPasses = AnalysisPasses(0x02);
+
+    pub fn all() -> AnalysisPasses {
+        AnalysisPasses::READ_ONLY | AnalysisPasses::TRAITS
+    }
+
+    /// Only the type checker
+    pub fn empty() -> AnalysisPasses {
+        AnalysisPasses(0)
+    }
+
+    pub fn contains(&self, other: AnalysisPasses) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for AnalysisPasses {
+    fn default() -> AnalysisPasses {
+        AnalysisPasses::all()
+    }
+}
+
+impl ::std::ops::BitOr for AnalysisPasses {
+    type Output = AnalysisPasses;
+    fn bitor(self, other: AnalysisPasses) -> AnalysisPasses {
+        AnalysisPasses(self.0 | other.0)
+    }
 }
 
 impl ContractAnalysis {
@@ -64,6 +124,7 @@ impl ContractAnalysis {
             fungible_tokens: BTreeSet::new(),
             non_fungible_tokens: BTreeMap::new(),
             cost_track: Some(cost_track),
+            warnings: vec![],
         }
     }
 
@@ -175,6 +236,13 @@ impl ContractAnalysis {
                 self.get_public_function_type(func_name),
                 self.get_read_only_function_type(func_name),
             ) {
+                (Some(FunctionType::Fixed(_)), None) if expected_sig.read_only => {
+                    return Err(CheckErrors::TraitMethodNotReadOnly(
+                        trait_name,
+                        func_name.to_string(),
+                    )
+                    .into())
+                }
                 (Some(FunctionType::Fixed(func)), None)
                 | (None, Some(FunctionType::Fixed(func))) => {
                     let args_sig = func.args.iter().map(|a| a.signature.clone()).collect();
@@ -0,0 +1,131 @@
+use vm::analysis::errors::CheckResult;
+use vm::analysis::types::{AnalysisPass, AnalysisWarning, ContractAnalysis};
+use vm::analysis::AnalysisDatabase;
+use vm::functions::NativeFunctions;
+use vm::representations::SymbolicExpression;
+use vm::variables::NativeVariables;
+
+#[cfg(test)]
+mod tests;
+
+/// Opt-in pass that warns about expressions in a `begin` or `let` body that can never be
+/// evaluated, because an earlier expression in the body always returns early -- e.g. an
+/// `(asserts! false ...)` or a `(try! (err ...))`.  It never fails the analysis.
+pub struct UnreachableChecker {
+    warnings: Vec<AnalysisWarning>,
+}
+
+impl AnalysisPass for UnreachableChecker {
+    fn run_pass(
+        contract_analysis: &mut ContractAnalysis,
+        _analysis_db: &mut AnalysisDatabase,
+    ) -> CheckResult<()> {
+        let mut command = UnreachableChecker::new();
+        command.run(contract_analysis);
+        Ok(())
+    }
+}
+
+impl UnreachableChecker {
+    fn new() -> Self {
+        Self { warnings: vec![] }
+    }
+
+    pub fn run(&mut self, contract_analysis: &mut ContractAnalysis) {
+        for exp in contract_analysis.expressions.iter() {
+            self.check_expression(exp);
+        }
+        contract_analysis.warnings.append(&mut self.warnings);
+    }
+
+    fn check_expression(&mut self, expr: &SymbolicExpression) {
+        match native_call(expr) {
+            Some((NativeFunctions::Begin, body)) => {
+                self.check_sequence(&body.iter().collect::<Vec<_>>())
+            }
+            Some((NativeFunctions::Let, args)) => self.check_sequence(&let_sequence(args)),
+            _ => {
+                if let Some(children) = expr.match_list() {
+                    for child in children.iter() {
+                        self.check_expression(child);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check expressions that are evaluated one after the other
+    fn check_sequence(&mut self, exprs: &[&SymbolicExpression]) {
+        let mut returned = false;
+        for expr in exprs.iter() {
+            if returned {
+                self.warnings.push(AnalysisWarning::new(
+                    "unreachable code: an earlier expression always returns early",
+                    expr,
+                ));
+                continue;
+            }
+            self.check_expression(expr);
+            returned = always_returns(expr);
+        }
+    }
+}
+
+/// If `expr` is a call to a native function, get the function and its arguments
+fn native_call(expr: &SymbolicExpression) -> Option<(NativeFunctions, &[SymbolicExpression])> {
+    let (function_name, args) = expr.match_list()?.split_first()?;
+    let function = NativeFunctions::lookup_by_name(function_name.match_atom()?)?;
+    Some((function, args))
+}
+
+/// The expressions a `let` evaluates in order: its binding values, and then its body
+fn let_sequence(args: &[SymbolicExpression]) -> Vec<&SymbolicExpression> {
+    let (bindings, body) = match args.split_first() {
+        Some(split) => split,
+        None => return vec![],
+    };
+    let mut sequence = vec![];
+    if let Some(bindings) = bindings.match_list() {
+        for binding in bindings.iter() {
+            if let Some(&[_, ref value]) = binding.match_list() {
+                sequence.push(value);
+            }
+        }
+    }
+    sequence.extend(body.iter());
+    sequence
+}
+
+/// If `expr` names a native variable (e.g. `none`), get the variable
+fn native_variable(expr: &SymbolicExpression) -> Option<NativeVariables> {
+    NativeVariables::lookup_by_name(expr.match_atom()?)
+}
+
+fn is_err_or_none(expr: &SymbolicExpression) -> bool {
+    match (native_call(expr), native_variable(expr)) {
+        (Some((NativeFunctions::ConsError, _)), _) => true,
+        (_, Some(NativeVariables::NativeNone)) => true,
+        _ => false,
+    }
+}
+
+/// Does evaluating `expr` unconditionally return early?  Conditional returns (e.g. inside an
+/// `if` or `match`) don't count.
+fn always_returns(expr: &SymbolicExpression) -> bool {
+    match native_call(expr) {
+        Some((NativeFunctions::Asserts, args)) => match args.first().and_then(native_variable) {
+            Some(NativeVariables::NativeFalse) => true,
+            _ => false,
+        },
+        Some((NativeFunctions::TryRet, args)) | Some((NativeFunctions::UnwrapRet, args)) => {
+            args.first().map(is_err_or_none).unwrap_or(false)
+        }
+        Some((NativeFunctions::UnwrapErrRet, args)) => match args.first().and_then(native_call) {
+            Some((NativeFunctions::ConsOkay, _)) => true,
+            _ => false,
+        },
+        Some((NativeFunctions::Begin, body)) => body.iter().any(always_returns),
+        Some((NativeFunctions::Let, args)) => let_sequence(args).into_iter().any(always_returns),
+        _ => false,
+    }
+}
@@ -0,0 +1,106 @@
+use vm::analysis::{run_analysis, run_analysis_with_cache, AnalysisPasses};
+use vm::ast::parse;
+use vm::costs::LimitedCostTracker;
+use vm::database::MemoryBackingStore;
+use vm::types::QualifiedContractIdentifier;
+
+/// Analyze a contract with the unreachable code pass enabled, and get the offending expressions
+fn unreachable_exprs(contract: &str) -> Vec<String> {
+    let contract_id = QualifiedContractIdentifier::transient();
+    let mut ast = parse(&contract_id, contract).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut db = marf.as_analysis_db();
+    let analysis = run_analysis_with_cache(
+        &contract_id,
+        &mut ast,
+        &mut db,
+        false,
+        LimitedCostTracker::new_max_limit(),
+        None,
+        AnalysisPasses::all(),
+        true,
+    )
+    .map_err(|(e, _)| e)
+    .unwrap();
+    analysis
+        .warnings
+        .iter()
+        .map(|warning| {
+            assert!(warning.message.contains("unreachable code"));
+            format!("{}", warning.expression)
+        })
+        .collect()
+}
+
+#[test]
+fn test_unconditional_asserts() {
+    let contract = "(define-public (foo (x uint))
+          (begin
+            (asserts! false (err u1))
+            (print x)
+            (ok x)))";
+    assert_eq!(
+        unreachable_exprs(contract),
+        vec!["( print x )".to_string(), "( ok x )".to_string()]
+    );
+
+    // nothing to warn about when the pass isn't enabled
+    let contract_id = QualifiedContractIdentifier::transient();
+    let mut ast = parse(&contract_id, contract).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut db = marf.as_analysis_db();
+    let analysis = run_analysis(
+        &contract_id,
+        &mut ast,
+        &mut db,
+        false,
+        LimitedCostTracker::new_max_limit(),
+        AnalysisPasses::all(),
+    )
+    .map_err(|(e, _)| e)
+    .unwrap();
+    assert!(analysis.warnings.is_empty());
+}
+
+#[test]
+fn test_conditional_return_is_reachable() {
+    let contract = "(define-public (foo (x uint))
+          (begin
+            (asserts! (> x u1) (err u1))
+            (if (> x u2) (asserts! false (err u2)) true)
+            (unwrap! (some x) (err u3))
+            (ok x)))";
+    assert!(unreachable_exprs(contract).is_empty());
+}
+
+#[test]
+fn test_nested_begin_and_let() {
+    // the inner `begin` always returns, so the `let` and everything after it does too
+    let contract = "(define-public (foo (x uint))
+          (begin
+            (let ((y (+ x u1)))
+              (begin (asserts! false (err u1)) (print y)))
+            (ok x)))";
+    assert_eq!(
+        unreachable_exprs(contract),
+        vec!["( print y )".to_string(), "( ok x )".to_string()]
+    );
+
+    // a binding that always returns cuts off the rest of the bindings and the body
+    let contract = "(define-public (foo (x uint))
+          (let ((a (asserts! false (err u1)))
+                (b u2))
+            (ok b)))";
+    assert_eq!(
+        unreachable_exprs(contract),
+        vec!["u2".to_string(), "( ok b )".to_string()]
+    );
+
+    // a nested `begin` that returns conditionally doesn't cut off its parent
+    let contract = "(define-public (foo (x uint))
+          (begin
+            (let ((y (+ x u1)))
+              (begin (asserts! (> y u1) (err u1)) (print y)))
+            (ok x)))";
+    assert!(unreachable_exprs(contract).is_empty());
+}
@@ -1,5 +1,6 @@
 use std::error;
 use std::fmt;
+use vm::ast::stack_depth_checker::MAX_AST_STACK_DEPTH;
 use vm::costs::{CostErrors, ExecutionCost};
 use vm::diagnostic::{DiagnosableError, Diagnostic};
 use vm::representations::PreSymbolicExpression;
@@ -15,6 +16,7 @@ pub enum ParseErrors {
     MemoryBalanceExceeded(u64, u64),
     TooManyExpressions,
     ExpressionStackDepthTooDeep,
+    MaxStackDepthTooLarge(usize),
     FailedCapturingInput,
     SeparatorExpected(String),
     SeparatorExpectedAfterColon(String),
@@ -212,6 +214,10 @@ impl DiagnosableError for ParseErrors {
                 "AST has too deep of an expression nesting. The maximum stack depth is {}",
                 MAX_CALL_STACK_DEPTH
             ),
+            ParseErrors::MaxStackDepthTooLarge(max_depth) => format!(
+                "configured maximum stack depth {} exceeds the limit of {}",
+                max_depth, MAX_AST_STACK_DEPTH
+            ),
             ParseErrors::InvalidCharactersDetected => format!("invalid characters detected"),
             ParseErrors::InvalidEscaping => format!("invalid escaping detected in string"),
         }
@@ -16,7 +16,7 @@ use vm::types::QualifiedContractIdentifier;
 use self::definition_sorter::DefinitionSorter;
 use self::errors::ParseResult;
 use self::expression_identifier::ExpressionIdentifier;
-use self::stack_depth_checker::StackDepthChecker;
+use self::stack_depth_checker::{StackDepthChecker, DEFAULT_AST_STACK_DEPTH};
 use self::sugar_expander::SugarExpander;
 use self::traits_resolver::TraitsResolver;
 use self::types::BuildASTPass;
@@ -36,6 +36,18 @@ pub fn build_ast<T: CostTracker>(
     source_code: &str,
     cost_track: &mut T,
 ) -> ParseResult<ContractAST> {
+    build_ast_with_passes(contract_identifier, source_code, cost_track, None)
+}
+
+/// Build the AST, overriding the maximum expression nesting depth with `max_stack_depth`.
+///  If it is `None`, the default depth is used.
+pub fn build_ast_with_passes<T: CostTracker>(
+    contract_identifier: &QualifiedContractIdentifier,
+    source_code: &str,
+    cost_track: &mut T,
+    max_stack_depth: Option<usize>,
+) -> ParseResult<ContractAST> {
+    let max_stack_depth = max_stack_depth.unwrap_or(DEFAULT_AST_STACK_DEPTH);
     runtime_cost!(
         cost_functions::AST_PARSE,
         cost_track,
@@ -43,7 +55,7 @@ pub fn build_ast<T: CostTracker>(
     )?;
     let pre_expressions = parser::parse(source_code)?;
     let mut contract_ast = ContractAST::new(contract_identifier.clone(), pre_expressions);
-    StackDepthChecker::run_pass(&mut contract_ast)?;
+    StackDepthChecker::run_pass_with_max_depth(&mut contract_ast, max_stack_depth)?;
     ExpressionIdentifier::run_pre_expression_pass(&mut contract_ast)?;
     DefinitionSorter::run_pass(&mut contract_ast, cost_track)?;
     TraitsResolver::run_pass(&mut contract_ast)?;
@@ -56,7 +68,9 @@ pub fn build_ast<T: CostTracker>(
 mod tests {
     use super::*;
     use std::collections::HashMap;
-    use vm::costs::LimitedCostTracker;
+    use vm::ast::errors::ParseErrors;
+    use vm::ast::stack_depth_checker::MAX_AST_STACK_DEPTH;
+    use vm::costs::{ExecutionCost, LimitedCostTracker, ProfilingCostTracker};
     use vm::representations::depth_traverse;
 
     fn dependency_edge_counting_runtime(iters: usize) -> u64 {
@@ -90,6 +104,80 @@ mod tests {
         assert!(ratio_8_16 > ratio_4_8);
     }
 
+    #[test]
+    fn test_profiling_cost_tracker() {
+        let contract = "(define-private (a) 1) (define-private (b) (a)) (define-public (c) (ok (b)))";
+
+        let mut cost_track = ProfilingCostTracker::new();
+        build_ast(
+            &QualifiedContractIdentifier::transient(),
+            contract,
+            &mut cost_track,
+        )
+        .unwrap();
+
+        let report = cost_track.report();
+        let parse_cost = report.get("AST_PARSE").unwrap();
+        assert!(parse_cost.runtime > 0);
+        assert!(report.contains_key("AST_CYCLE_DETECTION"));
+
+        // the breakdown accounts for the whole total
+        let mut sum = ExecutionCost::zero();
+        for cost in report.values() {
+            sum.add(cost).unwrap();
+        }
+        assert_eq!(sum, cost_track.get_total());
+    }
+
+    fn nested_lists(depth: usize) -> String {
+        format!("{}1{}", "(list ".repeat(depth), ")".repeat(depth))
+    }
+
+    #[test]
+    fn test_build_ast_with_max_stack_depth() {
+        let contract_identifier = QualifiedContractIdentifier::transient();
+        let max_stack_depth = DEFAULT_AST_STACK_DEPTH + 10;
+
+        // too deep for the default...
+        let err = build_ast(
+            &contract_identifier,
+            &nested_lists(max_stack_depth - 1),
+            &mut (),
+        )
+        .unwrap_err();
+        assert_eq!(err.err, ParseErrors::ExpressionStackDepthTooDeep);
+
+        // ...but just under the configured maximum
+        build_ast_with_passes(
+            &contract_identifier,
+            &nested_lists(max_stack_depth - 1),
+            &mut (),
+            Some(max_stack_depth),
+        )
+        .unwrap();
+
+        let err = build_ast_with_passes(
+            &contract_identifier,
+            &nested_lists(max_stack_depth),
+            &mut (),
+            Some(max_stack_depth),
+        )
+        .unwrap_err();
+        assert_eq!(err.err, ParseErrors::ExpressionStackDepthTooDeep);
+
+        let err = build_ast_with_passes(
+            &contract_identifier,
+            &nested_lists(1),
+            &mut (),
+            Some(MAX_AST_STACK_DEPTH + 1),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.err,
+            ParseErrors::MaxStackDepthTooLarge(MAX_AST_STACK_DEPTH + 1)
+        );
+    }
+
     #[test]
     fn test_expression_identification_tuples() {
         let progn = "{ a: (+ 1 2 3),
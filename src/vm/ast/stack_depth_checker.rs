@@ -10,13 +10,21 @@ use vm::MAX_CALL_STACK_DEPTH;
 //    AST depth, without impacting the stack depth).
 pub const AST_CALL_STACK_DEPTH_BUFFER: u64 = 5;
 
-fn check(args: &[PreSymbolicExpression], depth: u64) -> ParseResult<()> {
-    if depth >= (AST_CALL_STACK_DEPTH_BUFFER + MAX_CALL_STACK_DEPTH as u64) {
+// the AST depth enforced when no other maximum is configured
+pub const DEFAULT_AST_STACK_DEPTH: usize =
+    AST_CALL_STACK_DEPTH_BUFFER as usize + MAX_CALL_STACK_DEPTH;
+
+// a configured maximum AST depth may not exceed this, since the
+//    AST passes recurse on the nesting depth.
+pub const MAX_AST_STACK_DEPTH: usize = 256;
+
+fn check(args: &[PreSymbolicExpression], depth: usize, max_depth: usize) -> ParseResult<()> {
+    if depth >= max_depth {
         return Err(ParseErrors::ExpressionStackDepthTooDeep.into());
     }
     for expression in args.iter() {
         match expression.pre_expr {
-            List(ref exprs) => check(exprs, depth + 1),
+            List(ref exprs) => check(exprs, depth + 1, max_depth),
             _ => {
                 // Other symbolic expressions don't have depth
                 //  impacts.
@@ -29,8 +37,20 @@ fn check(args: &[PreSymbolicExpression], depth: u64) -> ParseResult<()> {
 
 pub struct StackDepthChecker;
 
+impl StackDepthChecker {
+    pub fn run_pass_with_max_depth(
+        contract_ast: &mut ContractAST,
+        max_depth: usize,
+    ) -> ParseResult<()> {
+        if max_depth > MAX_AST_STACK_DEPTH {
+            return Err(ParseErrors::MaxStackDepthTooLarge(max_depth).into());
+        }
+        check(&contract_ast.pre_expressions, 0, max_depth)
+    }
+}
+
 impl BuildASTPass for StackDepthChecker {
     fn run_pass(contract_ast: &mut ContractAST) -> ParseResult<()> {
-        check(&contract_ast.pre_expressions, 0)
+        StackDepthChecker::run_pass_with_max_depth(contract_ast, DEFAULT_AST_STACK_DEPTH)
     }
 }
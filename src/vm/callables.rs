@@ -181,6 +181,14 @@ impl DefinedFunction {
                     self.name.to_string(),
                 ))?;
 
+        if expected_sig.read_only && !self.is_read_only() {
+            return Err(CheckErrors::TraitMethodNotReadOnly(
+                trait_name.clone(),
+                self.name.to_string(),
+            )
+            .into());
+        }
+
         let args = self.arg_types.iter().map(|a| a.clone()).collect();
         if !expected_sig.check_args_trait_compliance(args) {
             return Err(CheckErrors::BadTraitImplementation(
@@ -6,7 +6,7 @@ use vm::ast::{errors::ParseError, errors::ParseErrors, ContractAST};
 use vm::contexts::{AssetMap, Environment, OwnedEnvironment};
 use vm::costs::{CostTracker, ExecutionCost, LimitedCostTracker};
 use vm::database::{
-    BurnStateDB, ClarityDatabase, HeadersDB, MarfedKV, RollbackWrapper,
+    BurnStateDB, ClarityBackingStore, ClarityDatabase, HeadersDB, MarfedKV, RollbackWrapper,
     RollbackWrapperPersistedLog, SqliteConnection,
 };
 use vm::errors::Error as InterpreterError;
@@ -212,6 +212,21 @@ impl ClarityInstance {
         f(datastore.get_marf())
     }
 
+    pub fn with_side_store<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut SqliteConnection) -> R,
+    {
+        let datastore = self
+            .datastore
+            .as_mut()
+            // this is a panicking failure, because there should be _no instance_ in which a ClarityBlockConnection
+            //   doesn't restore it's parent's datastore
+            .expect(
+                "FAIL: use of begin_block while prior block neither committed nor rolled back.",
+            );
+        f(datastore.get_side_store())
+    }
+
     pub fn begin_block<'a>(
         &'a mut self,
         current: &StacksBlockId,
@@ -642,6 +657,7 @@ impl<'a> ClarityTransactionConnection<'a> {
                     db,
                     false,
                     cost_track,
+                    analysis::AnalysisPasses::all(),
                 );
 
                 match result {
@@ -1,6 +1,7 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryInto;
 use std::fmt;
+use std::io::Write;
 
 use vm::ast;
 use vm::ast::ContractAST;
@@ -9,7 +10,7 @@ use vm::contracts::Contract;
 use vm::costs::{cost_functions, CostErrors, CostTracker, ExecutionCost, LimitedCostTracker};
 use vm::database::ClarityDatabase;
 use vm::errors::{CheckErrors, InterpreterError, InterpreterResult as Result, RuntimeErrorType};
-use vm::functions::handle_contract_call_special_cases;
+use vm::functions::{handle_contract_call_special_cases, list_cons_to_writer, NativeFunctions};
 use vm::representations::{ClarityName, ContractName, SymbolicExpression};
 use vm::stx_transfer_consolidated;
 use vm::types::signatures::FunctionSignature;
@@ -673,6 +674,43 @@ impl<'a, 'b> Environment<'a, 'b> {
         contract_identifier: &QualifiedContractIdentifier,
         program: &str,
     ) -> Result<Value> {
+        self.with_read_only_program(contract_identifier, program, |expr, env, context| {
+            eval(expr, env, context)
+        })
+    }
+
+    /// Evaluate a read-only program like `eval_read_only()`, but write the consensus serialization
+    /// of its result to `w`.  If the program is a `(list ...)` expression, each of its items is
+    /// written out as soon as it has been evaluated, so the list itself is never built.  Any other
+    /// program is evaluated in full before it is written.
+    pub fn eval_read_only_to_writer<W: Write>(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        program: &str,
+        w: &mut W,
+    ) -> Result<()> {
+        self.with_read_only_program(contract_identifier, program, |expr, env, context| {
+            if let Some(list_args) = match_list_cons(expr) {
+                return list_cons_to_writer(list_args, env, context, w);
+            }
+            let value = eval(expr, env, context)?;
+            value.serialize_write_owned(w).map_err(|e| {
+                InterpreterError::InterpreterError(format!("Failed to write value: {}", e)).into()
+            })
+        })
+    }
+
+    /// Parse `program`, and run `to_do` on its first expression in the context of the given
+    /// contract.  Any changes it makes are rolled back.
+    fn with_read_only_program<F, R>(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        program: &str,
+        to_do: F,
+    ) -> Result<R>
+    where
+        F: FnOnce(&SymbolicExpression, &mut Environment, &LocalContext) -> Result<R>,
+    {
         let parsed = ast::build_ast(contract_identifier, program, self)?.expressions;
 
         if parsed.len() < 1 {
@@ -698,7 +736,7 @@ impl<'a, 'b> Environment<'a, 'b> {
                 self.caller.clone(),
             );
             let local_context = LocalContext::new();
-            eval(&parsed[0], &mut nested_env, &local_context)
+            to_do(&parsed[0], &mut nested_env, &local_context)
         };
 
         self.global_context.roll_back();
@@ -1080,6 +1118,15 @@ impl<'a, 'b> Environment<'a, 'b> {
     }
 }
 
+/// If `expr` is a `(list ...)` expression, get the expressions for its items.
+fn match_list_cons(expr: &SymbolicExpression) -> Option<&[SymbolicExpression]> {
+    let (function_name, args) = expr.match_list()?.split_first()?;
+    match NativeFunctions::lookup_by_name(function_name.match_atom()?) {
+        Some(NativeFunctions::ListCons) => Some(args),
+        _ => None,
+    }
+}
+
 impl<'a> GlobalContext<'a> {
     // Instantiate a new Global Context
     pub fn new(database: ClarityDatabase, cost_track: LimitedCostTracker) -> GlobalContext {
@@ -2,6 +2,7 @@ pub mod constants;
 pub mod cost_functions;
 
 use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::{cmp, fmt};
 use vm::types::TypeSignature;
@@ -20,7 +21,7 @@ macro_rules! runtime_cost {
             .map_err(|_| CostErrors::CostOverflow)
             .and_then(|input| ($cost_spec).compute_cost(input));
         match input {
-            Ok(cost) => CostTracker::add_cost($env, cost),
+            Ok(cost) => CostTracker::add_cost_by_function($env, stringify!($cost_spec), cost),
             Err(e) => Err(e),
         }
     }};
@@ -43,7 +44,7 @@ pub fn analysis_typecheck_cost<T: CostTracker>(
     let t2_size = t2.type_size().map_err(|_| CostErrors::CostOverflow)?;
     let cost =
         cost_functions::ANALYSIS_TYPE_CHECK.compute_cost(cmp::max(t1_size, t2_size) as u64)?;
-    track.add_cost(cost)
+    track.add_cost_by_function("ANALYSIS_TYPE_CHECK", cost)
 }
 
 pub struct TypeCheckCost {}
@@ -63,6 +64,12 @@ pub trait CostTracker {
     fn add_memory(&mut self, memory: u64) -> Result<()>;
     fn drop_memory(&mut self, memory: u64);
     fn reset_memory(&mut self);
+
+    /// Add a cost computed by the given cost function (named by its path in `cost_functions`).
+    /// Only trackers that attribute costs care about the name.
+    fn add_cost_by_function(&mut self, _cost_function: &str, cost: ExecutionCost) -> Result<()> {
+        self.add_cost(cost)
+    }
 }
 
 // Don't track!
@@ -179,6 +186,69 @@ impl CostTracker for &mut LimitedCostTracker {
     }
 }
 
+/// An unlimited cost tracker that also breaks down the accumulated cost by the cost function
+/// that computed it, for profiling contracts.  Costs not computed by a named cost function only
+/// count towards the total.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfilingCostTracker {
+    total: ExecutionCost,
+    by_function: HashMap<String, ExecutionCost>,
+    memory: u64,
+}
+
+impl ProfilingCostTracker {
+    pub fn new() -> ProfilingCostTracker {
+        ProfilingCostTracker {
+            total: ExecutionCost::zero(),
+            by_function: HashMap::new(),
+            memory: 0,
+        }
+    }
+    pub fn get_total(&self) -> ExecutionCost {
+        self.total.clone()
+    }
+    /// The cost accumulated by each cost function, keyed by its name (e.g. "AST_PARSE")
+    pub fn report(&self) -> HashMap<String, ExecutionCost> {
+        self.by_function.clone()
+    }
+}
+
+impl CostTracker for ProfilingCostTracker {
+    fn add_cost(&mut self, cost: ExecutionCost) -> std::result::Result<(), CostErrors> {
+        self.total.add(&cost)
+    }
+    fn add_memory(&mut self, memory: u64) -> std::result::Result<(), CostErrors> {
+        self.memory = self.memory.cost_overflow_add(memory)?;
+        Ok(())
+    }
+    fn drop_memory(&mut self, memory: u64) {
+        self.memory = self
+            .memory
+            .checked_sub(memory)
+            .expect("Underflowed dropped memory");
+    }
+    fn reset_memory(&mut self) {
+        self.memory = 0;
+    }
+    fn add_cost_by_function(
+        &mut self,
+        cost_function: &str,
+        cost: ExecutionCost,
+    ) -> std::result::Result<(), CostErrors> {
+        // stringify!() may put spaces around the path separators
+        let name = cost_function
+            .rsplit("::")
+            .next()
+            .unwrap_or(cost_function)
+            .trim();
+        self.by_function
+            .entry(name.to_string())
+            .or_insert_with(ExecutionCost::zero)
+            .add(&cost)?;
+        self.add_cost(cost)
+    }
+}
+
 impl TypeCheckCost {
     pub fn compute_cost(&self, t: &TypeSignature) -> Result<ExecutionCost> {
         cost_functions::INNER_TYPE_CHECK_COST.compute_cost(t.size() as u64)
@@ -135,6 +135,63 @@ impl SqliteConnection {
         }
     }
 
+    /// Find the contracts whose identifiers begin with `contract_prefix` and which have a metadata
+    /// entry for `key` in any block.  Metadata is kept for every fork, so the caller needs to check
+    /// which of these contracts exist in the fork it cares about.
+    pub fn find_contracts_with_metadata(
+        &mut self,
+        contract_prefix: &str,
+        key: &str,
+    ) -> Vec<String> {
+        let key_prefix = format!("clr-meta::{}", contract_prefix);
+        let key_suffix = format!("::{}", key);
+
+        // every key that starts with key_prefix sorts in [key_prefix, key_prefix_end), so the
+        // (key, blockhash) index can find them without scanning the table.
+        let mut key_prefix_end = key_prefix.clone();
+        let last_char = key_prefix_end
+            .pop()
+            .expect("BUG: metadata key prefix is empty");
+        key_prefix_end.push(
+            std::char::from_u32(last_char as u32 + 1)
+                .expect("BUG: metadata key prefix ends in an unincrementable character"),
+        );
+
+        let params: [&dyn ToSql; 4] = [
+            &key_prefix,
+            &key_prefix_end,
+            &(key_suffix.len() as i64),
+            &key_suffix,
+        ];
+
+        let mut stmt = match self.conn.prepare(
+            "SELECT DISTINCT key FROM metadata_table WHERE key >= ? AND key < ? AND substr(key, -?) = ? ORDER BY key",
+        ) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                error!("Failed to prepare query for {}: {:?}", &key_prefix, &e);
+                panic!(SQL_FAIL_MESSAGE);
+            }
+        };
+
+        let keys: Vec<String> = match stmt
+            .query_map(&params, |row| row.get(0))
+            .and_then(|rows| rows.collect::<std::result::Result<Vec<String>, SqliteError>>())
+        {
+            Ok(keys) => keys,
+            Err(e) => {
+                error!("Failed to query ({}): {:?}", &key_prefix, &e);
+                panic!(SQL_FAIL_MESSAGE);
+            }
+        };
+
+        keys.into_iter()
+            .map(|meta_key| {
+                meta_key["clr-meta::".len()..meta_key.len() - key_suffix.len()].to_string()
+            })
+            .collect()
+    }
+
     pub fn has_entry(&mut self, key: &str) -> bool {
         sqlite_has_entry(&self.conn, key)
     }
@@ -27,6 +27,7 @@ use vm::{eval, Environment, LocalContext};
 use address::AddressHashMode;
 use chainstate::stacks::{StacksAddress, C32_ADDRESS_VERSION_TESTNET_SINGLESIG};
 pub use vm::functions::assets::{get_stx_balance_snapshot, stx_transfer_consolidated};
+pub use vm::functions::sequences::list_cons_to_writer;
 pub use vm::functions::special::handle_contract_call_special_cases;
 
 define_named_enum!(NativeFunctions {
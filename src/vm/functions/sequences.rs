@@ -1,8 +1,10 @@
 use std::cmp;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
+use std::io::Write;
 use vm::costs::{cost_functions, CostOverflowingMath};
 use vm::errors::{
-    check_argument_count, CheckErrors, InterpreterResult as Result, RuntimeErrorType,
+    check_argument_count, CheckErrors, InterpreterError, InterpreterResult as Result,
+    RuntimeErrorType,
 };
 use vm::representations::{SymbolicExpression, SymbolicExpressionType};
 use vm::types::{
@@ -29,6 +31,44 @@ pub fn list_cons(
     Value::list_from(args)
 }
 
+/// Like `list_cons`, but instead of building the list, write its consensus serialization to `w`,
+/// writing out each item as soon as it has been evaluated.  The items are checked and charged for
+/// just as `list_cons` would, but if one of them fails, the ones before it have already been written.
+pub fn list_cons_to_writer<W: Write>(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+    w: &mut W,
+) -> Result<()> {
+    let len = u32::try_from(args.len()).map_err(|_| CheckErrors::ValueTooLarge)?;
+    Value::serialize_write_list_prefix(w, len).map_err(list_write_error)?;
+
+    let mut arg_size = 0;
+    let mut entry_type: Option<TypeSignature> = None;
+    for (i, arg) in args.iter().enumerate() {
+        let item = eval(arg, env, context)?;
+        arg_size = arg_size.cost_overflow_add(item.size().into())?;
+
+        let item_type = TypeSignature::type_of(&item);
+        let next_entry_type = match entry_type {
+            Some(ref entry_type) => TypeSignature::least_supertype(entry_type, &item_type)?,
+            None => item_type,
+        };
+        // fails if the list so far is already too big to be a value
+        ListTypeData::new_list(next_entry_type.clone(), (i + 1) as u32)?;
+        entry_type = Some(next_entry_type);
+
+        item.serialize_write_owned(w).map_err(list_write_error)?;
+    }
+
+    runtime_cost!(cost_functions::LIST_CONS, env, arg_size)?;
+    Ok(())
+}
+
+fn list_write_error(e: std::io::Error) -> InterpreterError {
+    InterpreterError::InterpreterError(format!("Failed to write list: {}", e))
+}
+
 pub fn special_filter(
     args: &[SymbolicExpression],
     env: &mut Environment,
@@ -9,6 +9,7 @@ use std::{cmp, fmt};
 use regex::Regex;
 
 use address::c32;
+use address::AddressHashMode;
 use util::hash;
 use vm::errors::{
     CheckErrors, IncomparableError, InterpreterError, InterpreterResult as Result, RuntimeErrorType,
@@ -19,7 +20,7 @@ pub use vm::types::signatures::{
     parse_name_type_pairs, AssetIdentifier, BufferLength, FixedFunction, FunctionArg,
     FunctionSignature, FunctionType, ListTypeData, SequenceSubtype, StringSubtype,
     StringUTF8Length, TupleTypeSignature, TypeSignature, BUFF_1, BUFF_20, BUFF_32, BUFF_33,
-    BUFF_64, BUFF_65,
+    BUFF_64, BUFF_65, TRAIT_READ_ONLY_METHODS,
 };
 
 pub const MAX_VALUE_SIZE: u32 = 1024 * 1024; // 1MB
@@ -636,6 +637,26 @@ impl Value {
         Value::Sequence(SequenceData::Buffer(BuffData { data: vec![byte] }))
     }
 
+    /// Make the `{ version: (buff 1), hashbytes: (buff 20) }` tuple representation of a PoX
+    /// address, as expected by the PoX contract.
+    pub fn pox_addr(hash_mode: AddressHashMode, hash: hash::Hash160) -> Value {
+        Value::Tuple(
+            TupleData::from_data(vec![
+                (
+                    ClarityName::try_from("version".to_owned()).unwrap(),
+                    Value::buff_from_byte(hash_mode as u8),
+                ),
+                (
+                    ClarityName::try_from("hashbytes".to_owned()).unwrap(),
+                    Value::Sequence(SequenceData::Buffer(BuffData {
+                        data: hash.as_bytes().to_vec(),
+                    })),
+                ),
+            ])
+            .expect("FATAL: failed to construct pox-addr tuple"),
+        )
+    }
+
     pub fn string_ascii_from_bytes(bytes: Vec<u8>) -> Result<Value> {
         // check the string size
         BufferLength::try_from(bytes.len())?;
@@ -808,6 +829,118 @@ impl Value {
             panic!("FATAL: not a response");
         }
     }
+
+    // Non-panicking counterparts to the expect_* accessors above.  Use these when decoding
+    // values that did not come from trusted code, since they return None on a type mismatch.
+
+    pub fn try_u128(self) -> Option<u128> {
+        match self {
+            Value::UInt(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    pub fn try_i128(self) -> Option<i128> {
+        match self {
+            Value::Int(inner) => Some(inner),
+            _ => None,
+        }
+    }
+
+    pub fn try_buff(self, sz: usize) -> Option<Vec<u8>> {
+        match self {
+            Value::Sequence(SequenceData::Buffer(buffdata)) if buffdata.data.len() == sz => {
+                Some(buffdata.data)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn try_bool(self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn try_tuple(self) -> Option<TupleData> {
+        match self {
+            Value::Tuple(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn try_optional(self) -> Option<Option<Value>> {
+        match self {
+            Value::Optional(opt) => Some(opt.data.map(|boxed_value| *boxed_value)),
+            _ => None,
+        }
+    }
+
+    pub fn try_principal(self) -> Option<PrincipalData> {
+        match self {
+            Value::Principal(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn try_result(self) -> Option<std::result::Result<Value, Value>> {
+        match self {
+            Value::Response(res_data) => {
+                if res_data.committed {
+                    Some(Ok(*res_data.data))
+                } else {
+                    Some(Err(*res_data.data))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Render this value as Clarity-like source text, with each tuple field and list item on its
+    /// own line, indented by nesting depth.  Meant for logs and debugging output.
+    /// This is not the `Display` format: lists are written as `(list ...)` rather than `(...)`,
+    /// and the output spans multiple lines.  Leaf values (including those inside optionals and
+    /// responses) are rendered with `Display`.
+    pub fn pretty_print(&self) -> String {
+        let mut out = String::new();
+        self.pretty_print_indent(0, &mut out);
+        out
+    }
+
+    fn pretty_print_indent(&self, indent: usize, out: &mut String) {
+        let pad = " ".repeat(indent + 2);
+        match self {
+            Value::Tuple(tuple_data) => {
+                out.push_str("(tuple");
+                for (name, value) in tuple_data.data_map.iter() {
+                    out.push_str(&format!("\n{}({} ", pad, &**name));
+                    value.pretty_print_indent(indent + 2, out);
+                    out.push_str(")");
+                }
+                out.push_str(")");
+            }
+            Value::Sequence(SequenceData::List(list_data)) => {
+                out.push_str("(list");
+                for value in list_data.data.iter() {
+                    out.push_str(&format!("\n{}", pad));
+                    value.pretty_print_indent(indent + 2, out);
+                }
+                out.push_str(")");
+            }
+            Value::Optional(OptionalData { data: Some(value) }) => {
+                out.push_str("(some ");
+                value.pretty_print_indent(indent, out);
+                out.push_str(")");
+            }
+            Value::Response(res_data) => {
+                out.push_str(if res_data.committed { "(ok " } else { "(err " });
+                res_data.data.pretty_print_indent(indent, out);
+                out.push_str(")");
+            }
+            _ => out.push_str(&format!("{}", self)),
+        }
+    }
 }
 
 impl BuffData {
@@ -1104,6 +1237,121 @@ impl fmt::Display for TupleData {
 #[cfg(test)]
 mod test {
     use super::*;
+    #[test]
+    fn test_pretty_print() {
+        let pox_addr = Value::Tuple(
+            TupleData::from_data(vec![
+                (
+                    ClarityName::try_from("version".to_string()).unwrap(),
+                    Value::buff_from_byte(0x01),
+                ),
+                (
+                    ClarityName::try_from("hashbytes".to_string()).unwrap(),
+                    Value::buff_from(vec![0x11, 0x22]).unwrap(),
+                ),
+            ])
+            .unwrap(),
+        );
+        let value = Value::Tuple(
+            TupleData::from_data(vec![
+                (
+                    ClarityName::try_from("amount".to_string()).unwrap(),
+                    Value::UInt(10),
+                ),
+                (
+                    ClarityName::try_from("pox-addr".to_string()).unwrap(),
+                    pox_addr,
+                ),
+                (
+                    ClarityName::try_from("reward".to_string()).unwrap(),
+                    Value::some(Value::UInt(5)).unwrap(),
+                ),
+                (
+                    ClarityName::try_from("unlocks".to_string()).unwrap(),
+                    Value::list_from(vec![Value::Int(1), Value::Int(2)]).unwrap(),
+                ),
+                (
+                    ClarityName::try_from("missing".to_string()).unwrap(),
+                    Value::none(),
+                ),
+            ])
+            .unwrap(),
+        );
+
+        let expected = "(tuple
+  (amount u10)
+  (missing none)
+  (pox-addr (tuple
+    (hashbytes 0x1122)
+    (version 0x01)))
+  (reward (some u5))
+  (unlocks (list
+    1
+    2)))";
+        assert_eq!(value.pretty_print(), expected);
+
+        // scalars render the same as Display
+        assert_eq!(Value::UInt(5).pretty_print(), format!("{}", Value::UInt(5)));
+        assert_eq!(Value::none().pretty_print(), "none");
+        assert_eq!(Value::list_from(vec![]).unwrap().pretty_print(), "(list)");
+        assert_eq!(Value::okay(Value::Int(1)).unwrap().pretty_print(), "(ok 1)");
+        assert_eq!(
+            Value::error(Value::Int(2)).unwrap().pretty_print(),
+            "(err 2)"
+        );
+    }
+
+    #[test]
+    fn test_try_accessors() {
+        assert_eq!(Value::UInt(1).try_u128(), Some(1));
+        assert_eq!(Value::Int(1).try_u128(), None);
+
+        assert_eq!(Value::Int(-1).try_i128(), Some(-1));
+        assert_eq!(Value::UInt(1).try_i128(), None);
+
+        assert_eq!(
+            Value::buff_from(vec![1, 2]).unwrap().try_buff(2),
+            Some(vec![1, 2])
+        );
+        assert_eq!(Value::buff_from(vec![1, 2]).unwrap().try_buff(3), None);
+        assert_eq!(Value::Int(1).try_buff(1), None);
+
+        assert_eq!(Value::Bool(true).try_bool(), Some(true));
+        assert_eq!(Value::Int(1).try_bool(), None);
+
+        let tuple = TupleData::from_data(vec![(
+            ClarityName::try_from("a".to_string()).unwrap(),
+            Value::Int(1),
+        )])
+        .unwrap();
+        assert_eq!(Value::Tuple(tuple.clone()).try_tuple(), Some(tuple));
+        assert_eq!(Value::none().try_tuple(), None);
+
+        assert_eq!(
+            Value::some(Value::Int(1)).unwrap().try_optional(),
+            Some(Some(Value::Int(1)))
+        );
+        assert_eq!(Value::none().try_optional(), Some(None));
+        assert_eq!(Value::Int(1).try_optional(), None);
+
+        let principal = PrincipalData::Standard(StandardPrincipalData::transient());
+        assert_eq!(
+            Value::Principal(principal.clone()).try_principal(),
+            Some(principal)
+        );
+        assert_eq!(Value::Int(1).try_principal(), None);
+
+        assert_eq!(
+            Value::okay(Value::Int(1)).unwrap().try_result(),
+            Some(Ok(Value::Int(1)))
+        );
+        assert_eq!(
+            Value::error(Value::Int(2)).unwrap().try_result(),
+            Some(Err(Value::Int(2)))
+        );
+        assert_eq!(Value::Int(1).try_result(), None);
+    }
+
     #[test]
     fn test_constructors() {
         assert_eq!(
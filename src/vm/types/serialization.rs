@@ -592,6 +592,38 @@ impl Value {
         Ok(())
     }
 
+    /// How many bytes `serialize_write()` would write for this value, without buffering them.
+    /// Useful for budgeting how much work decoding a value (or many of them) will take.
+    pub fn serialized_size(&self) -> u64 {
+        let mut counter = ByteCounter(0);
+        self.serialize_write(&mut counter)
+            .expect("IOError counting serialized bytes.");
+        counter.0
+    }
+
+    /// Serialize this value like `serialize_write()`, but consume it in the process.
+    /// If this is a list, each item is dropped as soon as it has been written, so a large
+    /// list's memory is released while it is being written out instead of afterwards.
+    pub fn serialize_write_owned<W: Write>(self, w: &mut W) -> std::io::Result<()> {
+        match self {
+            Value::Sequence(SequenceData::List(data)) => {
+                Value::serialize_write_list_prefix(w, data.len())?;
+                for item in data.data.into_iter() {
+                    item.serialize_write_owned(w)?;
+                }
+                Ok(())
+            }
+            other => other.serialize_write(w),
+        }
+    }
+
+    /// Write what `serialize_write()` writes for a list of `len` items before the items themselves.
+    /// Writing this followed by `len` serialized items is the serialization of the list.
+    pub fn serialize_write_list_prefix<W: Write>(w: &mut W, len: u32) -> std::io::Result<()> {
+        w.write_all(&[TypePrefix::List as u8])?;
+        w.write_all(&len.to_be_bytes())
+    }
+
     /// This function attempts to deserialize a hex string into a Clarity Value.
     ///   The `expected_type` parameter determines whether or not the deserializer should expect (and enforce)
     ///   a particular type. `ClarityDB` uses this to ensure that lists, tuples, etc. loaded from the database
@@ -634,6 +666,20 @@ impl Value {
     }
 }
 
+/// Writer that discards its input, keeping only a count of the bytes written to it
+struct ByteCounter(u64);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 impl ClaritySerializable for Value {
     fn serialize(&self) -> String {
         let mut byte_serialization = Vec::new();
@@ -1008,6 +1054,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_serialize_write_owned() {
+        let large_list = Value::list_from(
+            (0..10000)
+                .map(|i| Value::list_from(vec![Value::Int(i), Value::UInt(i as u128)]).unwrap())
+                .collect(),
+        )
+        .unwrap();
+
+        for v in [
+            large_list,
+            Value::list_from(vec![]).unwrap(),
+            Value::some(Value::list_from(vec![Value::Int(1)]).unwrap()).unwrap(),
+            Value::UInt(123),
+        ]
+        .iter()
+        {
+            let mut expected = vec![];
+            v.serialize_write(&mut expected).unwrap();
+
+            let mut streamed = vec![];
+            v.clone().serialize_write_owned(&mut streamed).unwrap();
+
+            assert_eq!(streamed, expected);
+            assert_eq!(&Value::try_deserialize_bytes_untyped(&streamed).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn test_serialized_size() {
+        let issuer =
+            PrincipalData::parse_standard_principal("SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G")
+                .unwrap();
+        let values = vec![
+            Value::Int(-1),
+            Value::UInt(1),
+            Value::Bool(true),
+            Value::none(),
+            Value::some(Value::UInt(2)).unwrap(),
+            Value::okay(Value::Int(3)).unwrap(),
+            Value::buff_from(vec![0xab; 40]).unwrap(),
+            Value::string_ascii_from_bytes(b"hello world".to_vec()).unwrap(),
+            Value::from(issuer.clone()),
+            Value::from(PrincipalData::Contract(QualifiedContractIdentifier::new(
+                issuer,
+                "foo".into(),
+            ))),
+            Value::list_from(vec![Value::Int(1), Value::Int(2), Value::Int(3)]).unwrap(),
+            Value::from(
+                TupleData::from_data(vec![
+                    ("a".into(), Value::UInt(1)),
+                    ("bb".into(), Value::buff_from(vec![1, 2, 3]).unwrap()),
+                ])
+                .unwrap(),
+            ),
+        ];
+
+        for v in values.iter() {
+            let mut bytes = vec![];
+            v.serialize_write(&mut bytes).unwrap();
+            assert_eq!(v.serialized_size(), bytes.len() as u64);
+        }
+    }
+
     #[test]
     fn try_deser_large_list() {
         let buff = vec![
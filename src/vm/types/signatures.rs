@@ -116,6 +116,12 @@ pub const BUFF_33: TypeSignature = SequenceType(SequenceSubtype::BufferType(Buff
 pub const BUFF_20: TypeSignature = SequenceType(SequenceSubtype::BufferType(BufferLength(20)));
 pub const BUFF_1: TypeSignature = SequenceType(SequenceSubtype::BufferType(BufferLength(1)));
 
+/// Whether `define-trait` accepts the `read-only` method annotation.  Accepting it changes which
+///  contracts pass analysis, so every node on a network must agree on it: it stays off unless
+///  the `trait_read_only` feature is enabled, and nodes built without it reject such traits
+///  exactly as before.
+pub const TRAIT_READ_ONLY_METHODS: bool = cfg!(feature = "trait_read_only");
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ListTypeData {
     max_len: u32,
@@ -126,6 +132,14 @@ pub struct ListTypeData {
 pub struct FunctionSignature {
     pub args: Vec<TypeSignature>,
     pub returns: TypeSignature,
+    /// Set for trait methods declared `read-only`, which only read-only functions may implement.
+    ///  Left out of the serialized signature when unset, so that stored analyses are unchanged.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub read_only: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -165,7 +179,11 @@ impl From<FixedFunction> for FunctionSignature {
     fn from(data: FixedFunction) -> FunctionSignature {
         let FixedFunction { args, returns } = data;
         let args = args.into_iter().map(|x| x.signature).collect();
-        FunctionSignature { args, returns }
+        FunctionSignature {
+            args,
+            returns,
+            read_only: false,
+        }
     }
 }
 
@@ -978,7 +996,9 @@ impl TypeSignature {
             let args = function_type
                 .match_list()
                 .ok_or(CheckErrors::DefineTraitBadSignature)?;
-            if args.len() != 3 {
+            // (name (args) returns), or (name (args) returns read-only) if trait methods may be
+            //  annotated read-only
+            if args.len() != 3 && !(TRAIT_READ_ONLY_METHODS && args.len() == 4) {
                 return Err(CheckErrors::InvalidTypeDescription);
             }
 
@@ -1006,11 +1026,21 @@ impl TypeSignature {
                 _ => Err(CheckErrors::DefineTraitBadSignature),
             }?;
 
+            // Extract function's read-only annotation, if any
+            let read_only = match args.get(3) {
+                Some(annotation) => match annotation.match_atom() {
+                    Some(atom) if atom.as_str() == "read-only" => true,
+                    _ => return Err(CheckErrors::DefineTraitBadSignature),
+                },
+                None => false,
+            };
+
             trait_signature.insert(
                 fn_name.clone(),
                 FunctionSignature {
                     args: fn_args,
                     returns: fn_return,
+                    read_only,
                 },
             );
         }
@@ -20,6 +20,7 @@ use stacks::burnchains::bitcoin::indexer::{
 };
 use stacks::burnchains::bitcoin::spv::SpvClient;
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
+use stacks::burnchains::burnchain::IndexerBlockFetcher;
 use stacks::burnchains::db::BurnchainDB;
 use stacks::burnchains::indexer::BurnchainIndexer;
 use stacks::burnchains::Burnchain;
@@ -27,6 +28,7 @@ use stacks::burnchains::BurnchainStateTransitionOps;
 use stacks::burnchains::Error as burnchain_error;
 use stacks::burnchains::PoxConstants;
 use stacks::burnchains::PublicKey;
+use stacks::burnchains::Txid;
 use stacks::chainstate::burn::db::sortdb::SortitionDB;
 use stacks::chainstate::burn::operations::{
     leader_block_commit::OUTPUTS_PER_COMMIT, BlockstackOperationType, LeaderBlockCommitOp,
@@ -53,10 +55,20 @@ pub struct BitcoinRegtestController {
     burnchain_db: Option<BurnchainDB>,
     chain_tip: Option<BurnchainTip>,
     use_coordinator: Option<CoordinatorChannels>,
+    utxo_count: usize,
 }
 
 const DUST_UTXO_LIMIT: u64 = 5500;
 
+/// Once the miner's address holds more than this many UTXOs, the next transaction also spends the
+/// smallest of them, so that they get merged into its change output.
+pub const UTXO_CONSOLIDATION_THRESHOLD: usize = 10;
+/// Most UTXOs that a single transaction will spend
+pub const UTXO_MAX_INPUTS: usize = 50;
+/// Fee paid for each UTXO a transaction spends, on top of `burnchain.burnchain_op_tx_fee`.  A
+/// signed P2PKH input adds about 148 bytes to the transaction.
+pub const UTXO_INPUT_FEE: u64 = 3000;
+
 impl BitcoinRegtestController {
     pub fn new(config: Config, coordinator_channel: Option<CoordinatorChannels>) -> Self {
         std::fs::create_dir_all(&config.node.get_burnchain_path())
@@ -98,6 +110,7 @@ impl BitcoinRegtestController {
             db: None,
             burnchain_db: None,
             chain_tip: None,
+            utxo_count: 0,
         }
     }
 
@@ -127,6 +140,7 @@ impl BitcoinRegtestController {
             db: None,
             burnchain_db: None,
             chain_tip: None,
+            utxo_count: 0,
         }
     }
 
@@ -152,6 +166,17 @@ impl BitcoinRegtestController {
         burnchain
     }
 
+    /// Make a fetcher that downloads single burnchain blocks from the bitcoin peer, so that the
+    /// chains coordinator can fill in gaps in the burnchain DB.
+    pub fn make_block_fetcher(&self) -> IndexerBlockFetcher<BitcoinIndexer> {
+        let (_, network_type) = self.config.burnchain.get_bitcoin_network();
+        let burnchain_indexer = BitcoinIndexer {
+            config: self.indexer_config.clone(),
+            runtime: BitcoinIndexerRuntime::new(network_type),
+        };
+        IndexerBlockFetcher::new(&self.get_burnchain(), burnchain_indexer)
+    }
+
     fn setup_indexer_runtime(&mut self) -> (Burnchain, BitcoinIndexer) {
         let (_, network_type) = self.config.burnchain.get_bitcoin_network();
         let indexer_runtime = BitcoinIndexerRuntime::new(network_type);
@@ -312,11 +337,13 @@ impl BitcoinRegtestController {
         Ok((burnchain_tip, burnchain_height))
     }
 
+    /// Fetch the UTXOs held by `public_key`'s address.
+    /// Returns InsufficientFunds if they don't add up to `amount_required`.
     pub fn get_utxos(
         &self,
         public_key: &Secp256k1PublicKey,
         amount_required: u64,
-    ) -> Option<Vec<UTXO>> {
+    ) -> Result<Vec<UTXO>, BurnchainControllerError> {
         // Configure UTXO filter
         let pkh = Hash160::from_data(&public_key.to_bytes())
             .to_bytes()
@@ -328,12 +355,8 @@ impl BitcoinRegtestController {
         let filter_addresses = vec![address.to_b58()];
 
         let mut utxos = loop {
-            let result = BitcoinRPCRequest::list_unspent(
-                &self.config,
-                filter_addresses.clone(),
-                false,
-                amount_required,
-            );
+            let result =
+                BitcoinRPCRequest::list_unspent(&self.config, filter_addresses.clone(), false, 0);
 
             // Perform request
             match result {
@@ -358,7 +381,7 @@ impl BitcoinRegtestController {
                     &self.config,
                     filter_addresses.clone(),
                     false,
-                    amount_required,
+                    0,
                 );
 
                 utxos = match result {
@@ -371,7 +394,10 @@ impl BitcoinRegtestController {
                 };
 
                 if utxos.len() == 0 {
-                    return None;
+                    return Err(BurnchainControllerError::InsufficientFunds {
+                        needed: amount_required,
+                        available: 0,
+                    });
                 } else {
                     break utxos;
                 }
@@ -382,26 +408,74 @@ impl BitcoinRegtestController {
 
         let total_unspent: u64 = utxos.iter().map(|o| o.amount).sum();
         if total_unspent < amount_required {
-            debug!(
-                "Total unspent {} < {} for {:?}",
-                total_unspent,
-                amount_required,
-                &public_key.to_hex()
-            );
-            return None;
+            return Err(BurnchainControllerError::InsufficientFunds {
+                needed: amount_required,
+                available: total_unspent,
+            });
         }
 
-        Some(utxos)
+        Ok(utxos)
+    }
+
+    /// Number of UTXOs held by the miner's address, as of the last transaction this controller
+    /// built.
+    pub fn get_utxo_count(&self) -> usize {
+        self.utxo_count
+    }
+
+    /// Choose which of the miner's UTXOs to spend in order to pay `amount_required`, plus
+    /// `UTXO_INPUT_FEE` for each UTXO spent.
+    /// The largest UTXOs are used first, so most transactions spend a single UTXO and leave a
+    /// single change output.  If there are more than `UTXO_CONSOLIDATION_THRESHOLD` UTXOs, then
+    /// the smallest ones that are worth more than their input fee are spent as well (up to
+    /// `UTXO_MAX_INPUTS` in total), so that the number of UTXOs stays bounded.
+    /// Returns InsufficientFunds if at most `UTXO_MAX_INPUTS` of the UTXOs can't cover the amount
+    /// and fees.
+    pub fn select_utxos(
+        mut utxos: Vec<UTXO>,
+        amount_required: u64,
+    ) -> Result<Vec<UTXO>, BurnchainControllerError> {
+        let consolidate = utxos.len() > UTXO_CONSOLIDATION_THRESHOLD;
+        utxos.sort_by(|u1, u2| u2.amount.cmp(&u1.amount));
+
+        let mut total = 0;
+        let mut num_needed = 0;
+        for utxo in utxos.iter() {
+            if total >= amount_required + UTXO_INPUT_FEE * (num_needed as u64) {
+                break;
+            }
+            if num_needed >= UTXO_MAX_INPUTS {
+                break;
+            }
+            total += utxo.amount;
+            num_needed += 1;
+        }
+        if total < amount_required + UTXO_INPUT_FEE * (num_needed as u64) {
+            return Err(BurnchainControllerError::InsufficientFunds {
+                needed: amount_required + UTXO_INPUT_FEE * (num_needed as u64),
+                available: total,
+            });
+        }
+
+        let mut remaining = utxos.split_off(num_needed);
+        if consolidate {
+            remaining.retain(|utxo| utxo.amount > UTXO_INPUT_FEE);
+            remaining.reverse();
+            remaining.truncate(UTXO_MAX_INPUTS.saturating_sub(utxos.len()));
+            utxos.append(&mut remaining);
+        }
+        Ok(utxos)
     }
 
     fn build_leader_key_register_tx(
         &mut self,
         payload: LeaderKeyRegisterOp,
         signer: &mut BurnchainOpSigner,
-    ) -> Option<Transaction> {
+        chained_utxo: Option<UTXO>,
+    ) -> Option<(Transaction, Option<UTXO>)> {
         let public_key = signer.get_public_key();
 
-        let (mut tx, utxos) = self.prepare_tx(&public_key, DUST_UTXO_LIMIT)?;
+        let (mut tx, utxos) = self.prepare_tx(&public_key, DUST_UTXO_LIMIT, chained_utxo)?;
 
         // Serialize the payload
         let op_bytes = {
@@ -429,7 +503,7 @@ impl BitcoinRegtestController {
 
         tx.output.push(identifier_output);
 
-        self.finalize_tx(&mut tx, DUST_UTXO_LIMIT, utxos, signer)?;
+        let change_utxo = self.finalize_tx(&mut tx, DUST_UTXO_LIMIT, utxos, signer)?;
 
         increment_btc_ops_sent_counter();
 
@@ -438,17 +512,18 @@ impl BitcoinRegtestController {
             public_key.to_hex()
         );
 
-        Some(tx)
+        Some((tx, change_utxo))
     }
 
     fn build_leader_block_commit_tx(
         &mut self,
         payload: LeaderBlockCommitOp,
         signer: &mut BurnchainOpSigner,
-    ) -> Option<Transaction> {
+        chained_utxo: Option<UTXO>,
+    ) -> Option<(Transaction, Option<UTXO>)> {
         let public_key = signer.get_public_key();
 
-        let (mut tx, utxos) = self.prepare_tx(&public_key, payload.burn_fee)?;
+        let (mut tx, utxos) = self.prepare_tx(&public_key, payload.burn_fee, chained_utxo)?;
 
         // Serialize the payload
         let op_bytes = {
@@ -499,7 +574,7 @@ impl BitcoinRegtestController {
             tx.output.push(burn_output);
         }
 
-        self.finalize_tx(&mut tx, payload.burn_fee, utxos, signer)?;
+        let change_utxo = self.finalize_tx(&mut tx, payload.burn_fee, utxos, signer)?;
 
         increment_btc_ops_sent_counter();
 
@@ -508,24 +583,55 @@ impl BitcoinRegtestController {
             public_key.to_hex()
         );
 
-        Some(tx)
+        Some((tx, change_utxo))
     }
 
+    /// If `chained_utxo` is given, the transaction spends only that UTXO (i.e. the change of a
+    /// transaction that hasn't been mined yet).
     fn prepare_tx(
-        &self,
+        &mut self,
         public_key: &Secp256k1PublicKey,
         ops_fee: u64,
+        chained_utxo: Option<UTXO>,
     ) -> Option<(Transaction, Vec<UTXO>)> {
         let tx_fee = self.config.burnchain.burnchain_op_tx_fee;
         let amount_required = tx_fee + ops_fee;
 
-        // Fetch some UTXOs
-        let utxos = match self.get_utxos(&public_key, amount_required) {
-            Some(utxos) => utxos,
-            None => {
-                debug!("No UTXOs for {}", &public_key.to_hex());
+        let utxos = if let Some(utxo) = chained_utxo {
+            if utxo.amount < amount_required + UTXO_INPUT_FEE {
+                let e = BurnchainControllerError::InsufficientFunds {
+                    needed: amount_required + UTXO_INPUT_FEE,
+                    available: utxo.amount,
+                };
+                debug!("Chained UTXO: {} for {:?}", e, &public_key.to_hex());
                 return None;
             }
+            vec![utxo]
+        } else {
+            // Fetch some UTXOs
+            let all_utxos = match self.get_utxos(&public_key, amount_required) {
+                Ok(utxos) => utxos,
+                Err(e) => {
+                    debug!("{} for {}", e, &public_key.to_hex());
+                    return None;
+                }
+            };
+            self.utxo_count = all_utxos.len();
+
+            let utxos = match BitcoinRegtestController::select_utxos(all_utxos, amount_required) {
+                Ok(utxos) => utxos,
+                Err(e) => {
+                    debug!("Selecting UTXOs: {} for {}", e, &public_key.to_hex());
+                    return None;
+                }
+            };
+            debug!(
+                "Spending {} of {} UTXOs for {}",
+                utxos.len(),
+                self.utxo_count,
+                &public_key.to_hex()
+            );
+            utxos
         };
 
         let mut inputs = vec![];
@@ -557,14 +663,17 @@ impl BitcoinRegtestController {
         Some((transaction, utxos))
     }
 
+    /// Add the change output and sign the transaction.  Returns the change output, if there is
+    /// one.  The caller is responsible for disposing of the signer.
     fn finalize_tx(
-        &self,
+        &mut self,
         tx: &mut Transaction,
         total_spent: u64,
         utxos: Vec<UTXO>,
         signer: &mut BurnchainOpSigner,
-    ) -> Option<()> {
-        let tx_fee = self.config.burnchain.burnchain_op_tx_fee;
+    ) -> Option<Option<UTXO>> {
+        let tx_fee =
+            self.config.burnchain.burnchain_op_tx_fee + UTXO_INPUT_FEE * (utxos.len() as u64);
 
         // Append the change output
         let total_unspent: u64 = utxos.iter().map(|o| o.amount).sum();
@@ -579,12 +688,16 @@ impl BitcoinRegtestController {
             return None;
         }
         let value = total_unspent - total_spent - tx_fee;
-        if value >= DUST_UTXO_LIMIT {
+        self.utxo_count = self.utxo_count.saturating_sub(utxos.len());
+        let change_vout = if value >= DUST_UTXO_LIMIT {
             let change_output = BitcoinAddress::to_p2pkh_tx_out(&change_address_hash, value);
             tx.output.push(change_output);
+            self.utxo_count += 1;
+            Some(tx.output.len() - 1)
         } else {
             debug!("Not enough change to clear dust limit. Not adding change address.");
-        }
+            None
+        };
 
         // Sign the UTXOs
         for (i, utxo) in utxos.iter().enumerate() {
@@ -608,33 +721,94 @@ impl BitcoinRegtestController {
                 .push_slice(&public_key.to_bytes())
                 .into_script();
         }
-        signer.dispose();
 
-        Some(())
+        // the txid covers the signatures, so this has to come last
+        let change_utxo = change_vout.map(|vout| UTXO {
+            txid: tx.txid(),
+            vout: vout as u32,
+            script_pub_key: tx.output[vout].script_pubkey.clone(),
+            amount: value,
+        });
+        Some(change_utxo)
     }
 
     fn build_user_burn_support_tx(
         &mut self,
         _payload: UserBurnSupportOp,
         _signer: &mut BurnchainOpSigner,
-    ) -> Option<Transaction> {
+        _chained_utxo: Option<UTXO>,
+    ) -> Option<(Transaction, Option<UTXO>)> {
         unimplemented!()
     }
 
+    fn build_operation_tx(
+        &mut self,
+        operation: BlockstackOperationType,
+        op_signer: &mut BurnchainOpSigner,
+        chained_utxo: Option<UTXO>,
+    ) -> Option<(Transaction, Option<UTXO>)> {
+        match operation {
+            BlockstackOperationType::LeaderBlockCommit(payload) => {
+                self.build_leader_block_commit_tx(payload, op_signer, chained_utxo)
+            }
+            BlockstackOperationType::LeaderKeyRegister(payload) => {
+                self.build_leader_key_register_tx(payload, op_signer, chained_utxo)
+            }
+            BlockstackOperationType::UserBurnSupport(payload) => {
+                self.build_user_burn_support_tx(payload, op_signer, chained_utxo)
+            }
+        }
+    }
+
+    /// Build one transaction per operation, each spending the change of the one before it.
+    /// Fails with the index of the first operation whose transaction can't be built.
+    fn build_chained_operation_txs(
+        &mut self,
+        operations: Vec<BlockstackOperationType>,
+        op_signer: &mut BurnchainOpSigner,
+    ) -> Result<Vec<Transaction>, BurnchainControllerError> {
+        let num_operations = operations.len();
+        let mut transactions = vec![];
+        let mut chained_utxo = None;
+        for (i, operation) in operations.into_iter().enumerate() {
+            let (tx, change_utxo) = self
+                .build_operation_tx(operation, op_signer, chained_utxo.take())
+                .ok_or(BurnchainControllerError::BatchSubmissionFailed(i))?;
+            if change_utxo.is_none() && i + 1 < num_operations {
+                warn!(
+                    "Not enough change in transaction {} of batch to fund the rest of the batch",
+                    i
+                );
+                return Err(BurnchainControllerError::BatchSubmissionFailed(i + 1));
+            }
+            chained_utxo = change_utxo;
+            transactions.push(tx);
+        }
+        Ok(transactions)
+    }
+
     fn send_transaction(&self, transaction: SerializedTx) -> bool {
         let result = BitcoinRPCRequest::send_raw_transaction(&self.config, transaction.to_hex());
         match result {
             Ok(_) => true,
             Err(e) => {
-                error!(
-                    "Bitcoin RPC failure: transaction submission failed - {:?}",
-                    e
-                );
+                error!("Bitcoin RPC failure: transaction submission failed - {}", e);
                 false
             }
         }
     }
 
+    /// Send a signed transaction to bitcoind, and report why it was rejected if it was.
+    pub fn send_raw_transaction(
+        &self,
+        transaction: Transaction,
+    ) -> Result<(), BurnchainControllerError> {
+        BitcoinRPCRequest::send_raw_transaction(
+            &self.config,
+            SerializedTx::new(transaction).to_hex(),
+        )
+    }
+
     /// wait until the ChainsCoordinator has processed sortitions up to the
     ///   canonical chain tip, or has processed up to height_to_wait
     pub fn wait_for_sortitions(&self, height_to_wait: Option<u64>) -> BurnchainTip {
@@ -788,26 +962,42 @@ impl BurnchainController for BitcoinRegtestController {
         operation: BlockstackOperationType,
         op_signer: &mut BurnchainOpSigner,
     ) -> bool {
-        let transaction = match operation {
-            BlockstackOperationType::LeaderBlockCommit(payload) => {
-                self.build_leader_block_commit_tx(payload, op_signer)
-            }
-            BlockstackOperationType::LeaderKeyRegister(payload) => {
-                self.build_leader_key_register_tx(payload, op_signer)
-            }
-            BlockstackOperationType::UserBurnSupport(payload) => {
-                self.build_user_burn_support_tx(payload, op_signer)
-            }
-        };
+        let built = self.build_operation_tx(operation, op_signer, None);
+        op_signer.dispose();
 
-        let transaction = match transaction {
-            Some(tx) => SerializedTx::new(tx),
+        let transaction = match built {
+            Some((tx, _)) => SerializedTx::new(tx),
             _ => return false,
         };
 
         self.send_transaction(transaction)
     }
 
+    /// A Bitcoin transaction can only carry one operation, so each transaction in the batch
+    /// spends the change of the one before it.  That way, none of them can be mined without the
+    /// ones before it.  Nothing is sent unless every transaction in the batch could be built.
+    fn submit_operations(
+        &mut self,
+        operations: Vec<BlockstackOperationType>,
+        op_signer: &mut BurnchainOpSigner,
+    ) -> Result<Vec<Txid>, BurnchainControllerError> {
+        // the signer is done with once the batch is built, whether or not that worked
+        let built = self.build_chained_operation_txs(operations, op_signer);
+        op_signer.dispose();
+        let transactions = built?;
+
+        let mut txids = vec![];
+        for (i, tx) in transactions.into_iter().enumerate() {
+            let txid = Txid::from_vec_be(&tx.txid().as_bytes().to_vec())
+                .expect("BUG: Bitcoin txid is not 32 bytes");
+            if !self.send_transaction(SerializedTx::new(tx)) {
+                return Err(BurnchainControllerError::BatchSubmissionFailed(i));
+            }
+            txids.push(txid);
+        }
+        Ok(txids)
+    }
+
     #[cfg(test)]
     fn bootstrap_chain(&mut self, num_blocks: u64) {
         if let Some(local_mining_pubkey) = &self.config.burnchain.local_mining_public_key {
@@ -872,11 +1062,12 @@ pub struct ParsedUTXO {
     safe: bool,
 }
 
+#[derive(Clone)]
 pub struct UTXO {
-    txid: Sha256dHash,
-    vout: u32,
-    script_pub_key: Script,
-    amount: u64,
+    pub txid: Sha256dHash,
+    pub vout: u32,
+    pub script_pub_key: Script,
+    pub amount: u64,
 }
 
 impl ParsedUTXO {
@@ -956,14 +1147,7 @@ struct BitcoinRPCRequest {
     pub jsonrpc: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-enum RPCError {
-    Network(String),
-    Parsing(String),
-    Bitcoind(String),
-}
-
-type RPCResult<T> = Result<T, RPCError>;
+type RPCResult<T> = Result<T, BurnchainControllerError>;
 
 impl BitcoinRPCRequest {
     fn build_rpc_request(config: &Config) -> Request {
@@ -1008,7 +1192,7 @@ impl BitcoinRPCRequest {
         include_unsafe: bool,
         minimum_sum_amount: u64,
     ) -> RPCResult<Vec<UTXO>> {
-        let min_conf = 0;
+        let min_conf = config.burnchain.utxo_min_conf;
         let max_conf = 9999999;
         let minimum_amount = ParsedUTXO::sat_to_serialized_btc(minimum_sum_amount);
 
@@ -1087,14 +1271,7 @@ impl BitcoinRPCRequest {
             jsonrpc: "2.0".to_string(),
         };
 
-        let json_resp = BitcoinRPCRequest::send(&config, payload)?;
-
-        if let Some(e) = json_resp.get("error") {
-            if !e.is_null() {
-                error!("Error submitting transaction: {}", json_resp);
-                return Err(RPCError::Bitcoind(json_resp.to_string()));
-            }
-        }
+        BitcoinRPCRequest::send(&config, payload)?;
         Ok(())
     }
 
@@ -1127,7 +1304,10 @@ impl BitcoinRPCRequest {
         let body = match serde_json::to_vec(&json!(payload)) {
             Ok(body) => body,
             Err(err) => {
-                return Err(RPCError::Network(format!("RPC Error: {}", err)));
+                return Err(BurnchainControllerError::SerializationError(format!(
+                    "Bitcoin RPC: {}",
+                    err
+                )));
             }
         };
         request
@@ -1139,7 +1319,7 @@ impl BitcoinRPCRequest {
             let stream = match TcpStream::connect(config.burnchain.get_rpc_socket_addr()).await {
                 Ok(stream) => stream,
                 Err(err) => {
-                    return Err(RPCError::Network(format!(
+                    return Err(BurnchainControllerError::RPCError(format!(
                         "Bitcoin RPC: connection failed - {:?}",
                         err
                     )))
@@ -1149,7 +1329,7 @@ impl BitcoinRPCRequest {
             match client::connect(stream, request).await {
                 Ok(response) => Ok(response),
                 Err(err) => {
-                    return Err(RPCError::Network(format!(
+                    return Err(BurnchainControllerError::RPCError(format!(
                         "Bitcoin RPC: invoking procedure failed - {:?}",
                         err
                     )))
@@ -1157,13 +1337,7 @@ impl BitcoinRPCRequest {
             }
         })?;
 
-        if !response.status().is_success() {
-            return Err(RPCError::Network(format!(
-                "Bitcoin RPC: status({}) != success, {:?}",
-                response.status(),
-                response
-            )));
-        }
+        let status = response.status();
 
         let (res, buffer) = async_std::task::block_on(async move {
             let mut buffer = Vec::new();
@@ -1173,14 +1347,53 @@ impl BitcoinRPCRequest {
         });
 
         if res.is_err() {
-            return Err(RPCError::Network(format!(
+            return Err(BurnchainControllerError::RPCError(format!(
                 "Bitcoin RPC: unable to read body - {:?}",
                 res
             )));
         }
 
-        let payload = serde_json::from_slice::<serde_json::Value>(&buffer[..])
-            .map_err(|e| RPCError::Parsing(format!("Bitcoin RPC: {}", e)))?;
+        // bitcoind answers failed calls with a non-success status and a JSON-RPC error object
+        let payload = match serde_json::from_slice::<serde_json::Value>(&buffer[..]) {
+            Ok(payload) => payload,
+            Err(e) => {
+                if !status.is_success() {
+                    return Err(BurnchainControllerError::RPCError(format!(
+                        "Bitcoin RPC: status({}) != success",
+                        status
+                    )));
+                }
+                return Err(BurnchainControllerError::SerializationError(format!(
+                    "Bitcoin RPC: {}",
+                    e
+                )));
+            }
+        };
+
+        if let Some(message) = BitcoinRPCRequest::get_error_message(&payload) {
+            return Err(BurnchainControllerError::RPCError(message));
+        }
+
+        if !status.is_success() {
+            return Err(BurnchainControllerError::RPCError(format!(
+                "Bitcoin RPC: status({}) != success, {}",
+                status, payload
+            )));
+        }
+
         Ok(payload)
     }
+
+    /// Get the error message from a JSON-RPC response, if the call failed.
+    fn get_error_message(response: &serde_json::Value) -> Option<String> {
+        match response.get("error") {
+            Some(error) if !error.is_null() => {
+                match error.get("message").and_then(|message| message.as_str()) {
+                    Some(message) => Some(message.to_string()),
+                    None => Some(error.to_string()),
+                }
+            }
+            _ => None,
+        }
+    }
 }
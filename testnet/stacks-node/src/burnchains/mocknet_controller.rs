@@ -1,5 +1,5 @@
 use std::collections::VecDeque;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use super::super::operations::BurnchainOpSigner;
 use super::super::Config;
@@ -63,6 +63,11 @@ impl MocknetController {
         ));
         block.header()
     }
+
+    /// Mocknet transactions don't exist, so their txids are made up from where they land
+    fn build_txid(block_height: u64, vtxindex: u32) -> Txid {
+        Txid(Sha256Sum::from_data(format!("{}::{}", block_height, vtxindex).as_bytes()).0)
+    }
 }
 
 impl BurnchainController for MocknetController {
@@ -126,6 +131,22 @@ impl BurnchainController for MocknetController {
         true
     }
 
+    /// All queued operations go into the next block anyway, so a batch is just queued.
+    fn submit_operations(
+        &mut self,
+        operations: Vec<BlockstackOperationType>,
+        _op_signer: &mut BurnchainOpSigner,
+    ) -> Result<Vec<Txid>, BurnchainControllerError> {
+        let next_block_height = self.get_chain_tip().block_snapshot.block_height + 1;
+        let mut txids = vec![];
+        for operation in operations.into_iter() {
+            let vtxindex = self.queued_operations.len() as u32 + 1;
+            txids.push(Self::build_txid(next_block_height, vtxindex));
+            self.queued_operations.push_back(operation);
+        }
+        Ok(txids)
+    }
+
     fn sync(
         &mut self,
         _ignored_target_height_opt: Option<u64>,
@@ -138,12 +159,7 @@ impl BurnchainController for MocknetController {
         let mut ops = vec![];
 
         while let Some(payload) = self.queued_operations.pop_front() {
-            let txid = Txid(
-                Sha256Sum::from_data(
-                    format!("{}::{}", next_block_header.block_height, vtxindex).as_bytes(),
-                )
-                .0,
-            );
+            let txid = Self::build_txid(next_block_header.block_height, vtxindex);
             let op = match payload {
                 BlockstackOperationType::LeaderKeyRegister(payload) => {
                     BlockstackOperationType::LeaderKeyRegister(LeaderKeyRegisterOp {
@@ -239,6 +255,27 @@ impl BurnchainController for MocknetController {
         Ok((new_state, block_height))
     }
 
+    /// Every sync mines a block, so this mines exactly the blocks missing up to `target`.
+    fn sync_to_height(
+        &mut self,
+        target: u64,
+        timeout: Duration,
+    ) -> Result<BurnchainTip, BurnchainControllerError> {
+        let deadline = Instant::now() + timeout;
+        let mut tip = self.get_chain_tip();
+        while tip.block_snapshot.block_height < target {
+            if Instant::now() >= deadline {
+                return Err(BurnchainControllerError::SyncTimeout {
+                    target,
+                    reached: tip.block_snapshot.block_height,
+                });
+            }
+            let (new_tip, _) = self.sync(Some(target))?;
+            tip = new_tip;
+        }
+        Ok(tip)
+    }
+
     #[cfg(test)]
     fn bootstrap_chain(&mut self, _num_blocks: u64) {}
 }
@@ -7,18 +7,39 @@ pub use self::mocknet_controller::MocknetController;
 use super::operations::BurnchainOpSigner;
 
 use std::fmt;
-use std::time::Instant;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use stacks::burnchains;
 use stacks::burnchains::BurnchainStateTransitionOps;
+use stacks::burnchains::Txid;
 use stacks::chainstate::burn::db::sortdb::SortitionDB;
-use stacks::chainstate::burn::operations::BlockstackOperationType;
+use stacks::chainstate::burn::operations::{
+    BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp, UserBurnSupportOp,
+};
 use stacks::chainstate::burn::BlockSnapshot;
+use stacks::chainstate::burn::ConsensusHash;
 
 #[derive(Debug)]
 pub enum Error {
     CoordinatorClosed,
     IndexerError(burnchains::Error),
+    /// The operation at this index in a batch could not be submitted
+    BatchSubmissionFailed(usize),
+    /// A bitcoind JSON-RPC call failed, or bitcoind answered it with an error
+    RPCError(String),
+    /// A bitcoind JSON-RPC request or response could not be encoded or decoded
+    SerializationError(String),
+    /// The miner's UTXOs don't cover the amount a transaction needs to spend
+    InsufficientFunds {
+        needed: u64,
+        available: u64,
+    },
+    /// The burnchain tip did not reach the target height in time
+    SyncTimeout {
+        target: u64,
+        reached: u64,
+    },
 }
 
 impl fmt::Display for Error {
@@ -26,6 +47,23 @@ impl fmt::Display for Error {
         match self {
             Error::CoordinatorClosed => write!(f, "ChainsCoordinator closed"),
             Error::IndexerError(ref e) => write!(f, "Indexer error: {:?}", e),
+            Error::BatchSubmissionFailed(i) => {
+                write!(f, "Failed to submit operation {} of batch", i)
+            }
+            Error::RPCError(ref msg) => write!(f, "Bitcoin RPC error: {}", msg),
+            Error::SerializationError(ref msg) => {
+                write!(f, "Bitcoin RPC serialization error: {}", msg)
+            }
+            Error::InsufficientFunds { needed, available } => write!(
+                f,
+                "Insufficient funds: needed {} sats, but only {} are available",
+                needed, available
+            ),
+            Error::SyncTimeout { target, reached } => write!(
+                f,
+                "Timed out syncing to burnchain height {}: only reached {}",
+                target, reached
+            ),
         }
     }
 }
@@ -38,11 +76,58 @@ pub trait BurnchainController {
         operation: BlockstackOperationType,
         op_signer: &mut BurnchainOpSigner,
     ) -> bool;
+    /// Submit several operations so that they are mined in the same burnchain block.
+    /// Returns the txid of each operation's burnchain transaction, in the order given.
+    /// The default implementation submits them one by one, and reports the txids the operations
+    /// were built with.  Either way, `op_signer` is disposed of once this returns.
+    fn submit_operations(
+        &mut self,
+        operations: Vec<BlockstackOperationType>,
+        op_signer: &mut BurnchainOpSigner,
+    ) -> Result<Vec<Txid>, Error> {
+        let mut txids = vec![];
+        for (i, operation) in operations.into_iter().enumerate() {
+            let txid = operation.txid();
+            if !self.submit_operation(operation, op_signer) {
+                op_signer.dispose();
+                return Err(Error::BatchSubmissionFailed(i));
+            }
+            txids.push(txid);
+        }
+        op_signer.dispose();
+        Ok(txids)
+    }
     fn sync(&mut self, target_block_height_opt: Option<u64>) -> Result<(BurnchainTip, u64), Error>;
     fn sortdb_ref(&self) -> &SortitionDB;
     fn sortdb_mut(&mut self) -> &mut SortitionDB;
     fn get_chain_tip(&mut self) -> BurnchainTip;
 
+    /// Sync repeatedly until the burnchain tip is at least at height `target`, and return that
+    /// tip.  Fails with Error::SyncTimeout if it isn't reached within `timeout`.
+    fn sync_to_height(&mut self, target: u64, timeout: Duration) -> Result<BurnchainTip, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let (tip, block_height) = self.sync(Some(target))?;
+            if block_height >= target {
+                return Ok(tip);
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::SyncTimeout {
+                    target,
+                    reached: block_height,
+                });
+            }
+            thread::sleep(Duration::from_millis(1000));
+        }
+    }
+
+    /// Burnchain block height of the sortition with this consensus hash, if it's known
+    fn burn_height_for_consensus_hash(&self, consensus_hash: &ConsensusHash) -> Option<u64> {
+        SortitionDB::get_block_snapshot_consensus(self.sortdb_ref().conn(), consensus_hash)
+            .expect("FATAL: failed to query sortition DB")
+            .map(|snapshot| snapshot.block_height)
+    }
+
     #[cfg(test)]
     fn bootstrap_chain(&mut self, blocks_count: u64);
 }
@@ -57,15 +142,46 @@ pub struct BurnchainTip {
 impl BurnchainTip {
     pub fn get_winning_tx_index(&self) -> Option<u32> {
         let winning_tx_id = self.block_snapshot.winning_block_txid;
-        let mut winning_tx_vtindex = None;
+        self.block_commits()
+            .into_iter()
+            .filter(|op| op.txid == winning_tx_id)
+            .last()
+            .map(|op| op.vtxindex)
+    }
 
-        for op in self.state_transition.accepted_ops.iter() {
-            if let BlockstackOperationType::LeaderBlockCommit(op) = op {
-                if op.txid == winning_tx_id {
-                    winning_tx_vtindex = Some(op.vtxindex)
-                }
-            }
-        }
-        winning_tx_vtindex
+    /// Leader key registrations accepted in this burnchain block, in block order
+    pub fn leader_key_registers(&self) -> Vec<&LeaderKeyRegisterOp> {
+        self.state_transition
+            .accepted_ops
+            .iter()
+            .filter_map(|op| match op {
+                BlockstackOperationType::LeaderKeyRegister(op) => Some(op),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Leader block commits accepted in this burnchain block, in block order
+    pub fn block_commits(&self) -> Vec<&LeaderBlockCommitOp> {
+        self.state_transition
+            .accepted_ops
+            .iter()
+            .filter_map(|op| match op {
+                BlockstackOperationType::LeaderBlockCommit(op) => Some(op),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// User burn supports accepted in this burnchain block, in block order
+    pub fn user_burns(&self) -> Vec<&UserBurnSupportOp> {
+        self.state_transition
+            .accepted_ops
+            .iter()
+            .filter_map(|op| match op {
+                BlockstackOperationType::UserBurnSupport(op) => Some(op),
+                _ => None,
+            })
+            .collect()
     }
 }
@@ -1,33 +1,50 @@
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::env;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use rand::RngCore;
 
 use stacks::burnchains::bitcoin::indexer::FIRST_BLOCK_MAINNET;
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
 use stacks::burnchains::{MagicBytes, BLOCKSTACK_MAGIC_MAINNET};
+use stacks::chainstate::coordinator::DEFAULT_MAX_REORG_DEPTH;
+use stacks::chainstate::stacks::StacksTransaction;
 use stacks::net::connection::ConnectionOptions;
-use stacks::net::{Neighbor, NeighborKey, PeerAddress};
+use stacks::net::{Neighbor, NeighborKey, PeerAddress, StacksMessageCodec};
 use stacks::util::hash::{hex_bytes, to_hex};
+use stacks::util::log;
 use stacks::util::secp256k1::Secp256k1PublicKey;
 use stacks::vm::costs::ExecutionCost;
 use stacks::vm::types::{AssetIdentifier, PrincipalData, QualifiedContractIdentifier};
 
 use super::neon_node::TESTNET_PEER_VERSION;
-use super::node::TESTNET_CHAIN_ID;
+use super::node::{REGTEST_CHAIN_ID, TESTNET_CHAIN_ID};
 
 const MINIMUM_DUST_FEE: u64 = 5500;
 
+/// Number of `ConfigFile::regtest()` presets created so far by this process
+static REGTEST_PRESETS: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Clone, Deserialize, Default)]
 pub struct ConfigFile {
     pub burnchain: Option<BurnchainConfigFile>,
     pub node: Option<NodeConfigFile>,
+    /// Legacy name for `ustx_balance`
     pub mstx_balance: Option<Vec<InitialBalanceFile>>,
+    /// Genesis balances for a custom chain, one `[[ustx_balance]]` table (with an `address` and
+    /// an `amount` in microSTX) per account
+    pub ustx_balance: Option<Vec<InitialBalanceFile>>,
     pub events_observer: Option<Vec<EventObserverConfigFile>>,
     pub connection_options: Option<ConnectionOptionsFile>,
     pub block_limit: Option<BlockLimitFile>,
+    pub miner: Option<MinerConfigFile>,
+    pub mempool: Option<MempoolConfigFile>,
+    pub coordinator: Option<CoordinatorConfigFile>,
+    pub logging: Option<HashMap<String, String>>,
 }
 
 impl ConfigFile {
@@ -54,7 +71,7 @@ impl ConfigFile {
 
         let node = NodeConfigFile {
             bootstrap_node: Some("048dd4f26101715853533dee005f0915375854fd5be73405f679c1917a5d4d16aaaf3c4c0d7a9c132a36b8c5fe1287f07dad8c910174d789eb24bdfb5ae26f5f27@neon.blockstack.org:20444".to_string()),
-            miner: Some(false),
+            miner: Some(true),
             ..NodeConfigFile::default()
         };
 
@@ -97,7 +114,7 @@ impl ConfigFile {
 
         let node = NodeConfigFile {
             bootstrap_node: Some("048dd4f26101715853533dee005f0915375854fd5be73405f679c1917a5d4d16aaaf3c4c0d7a9c132a36b8c5fe1287f07dad8c910174d789eb24bdfb5ae26f5f27@argon.blockstack.org:20444".to_string()),
-            miner: Some(false),
+            miner: Some(true),
             ..NodeConfigFile::default()
         };
 
@@ -140,7 +157,7 @@ impl ConfigFile {
 
         let node = NodeConfigFile {
             bootstrap_node: Some("048dd4f26101715853533dee005f0915375854fd5be73405f679c1917a5d4d16aaaf3c4c0d7a9c132a36b8c5fe1287f07dad8c910174d789eb24bdfb5ae26f5f27@krypton.blockstack.org:20444".to_string()),
-            miner: Some(false),
+            miner: Some(true),
             ..NodeConfigFile::default()
         };
 
@@ -182,7 +199,7 @@ impl ConfigFile {
 
         let node = NodeConfigFile {
             bootstrap_node: Some("048dd4f26101715853533dee005f0915375854fd5be73405f679c1917a5d4d16aaaf3c4c0d7a9c132a36b8c5fe1287f07dad8c910174d789eb24bdfb5ae26f5f27@xenon.blockstack.org:20444".to_string()),
-            miner: Some(false),
+            miner: Some(true),
             ..NodeConfigFile::default()
         };
 
@@ -237,7 +254,51 @@ impl ConfigFile {
         };
 
         let node = NodeConfigFile {
-            miner: Some(false),
+            miner: Some(true),
+            ..NodeConfigFile::default()
+        };
+
+        ConfigFile {
+            burnchain: Some(burnchain),
+            node: Some(node),
+            ..ConfigFile::default()
+        }
+    }
+
+    /// Settings for an isolated local chain, relying on a local bitcoind server in regtest
+    /// mode (configured as for `helium()`).  Each preset has its own working directory under
+    /// the OS temp dir, and its own RPC and P2P ports, so that several regtest nodes can run
+    /// side by side without colliding.
+    pub fn regtest() -> ConfigFile {
+        let preset_id = REGTEST_PRESETS.fetch_add(1, Ordering::SeqCst);
+
+        let mut rng = rand::thread_rng();
+        let mut buf = [0u8; 8];
+        rng.fill_bytes(&mut buf);
+        let working_dir =
+            env::temp_dir().join(format!("stacks-regtest-{}-{}", to_hex(&buf), preset_id));
+
+        let rpc_port = *REGTEST_BASE_PORT + 2 * ((preset_id % 1000) as u16);
+        let p2p_port = rpc_port + 1;
+
+        let burnchain = BurnchainConfigFile {
+            mode: Some("helium".to_string()),
+            commit_anchor_block_within: Some(10_000),
+            rpc_port: Some(18443),
+            peer_port: Some(18444),
+            peer_host: Some("127.0.0.1".to_string()),
+            username: Some("helium".to_string()),
+            password: Some("helium".to_string()),
+            local_mining_public_key: Some("04ee0b1602eb18fef7986887a7e8769a30c9df981d33c8380d255edef003abdcd243a0eb74afdf6740e6c423e62aec631519a24cf5b1d62bf8a3e06ddc695dcb77".to_string()),
+            ..BurnchainConfigFile::default()
+        };
+
+        let node = NodeConfigFile {
+            working_dir: Some(working_dir.to_str().unwrap().to_string()),
+            rpc_bind: Some(format!("127.0.0.1:{}", rpc_port)),
+            p2p_bind: Some(format!("127.0.0.1:{}", p2p_port)),
+            miner: Some(true),
+            chain_id: Some(REGTEST_CHAIN_ID),
             ..NodeConfigFile::default()
         };
 
@@ -256,7 +317,7 @@ impl ConfigFile {
         };
 
         let node = NodeConfigFile {
-            miner: Some(false),
+            miner: Some(true),
             ..NodeConfigFile::default()
         };
 
@@ -276,9 +337,16 @@ pub struct Config {
     pub events_observers: Vec<EventObserverConfig>,
     pub connection_options: ConnectionOptions,
     pub block_limit: ExecutionCost,
+    pub miner: MinerConfig,
+    pub mempool: MempoolConfig,
+    pub coordinator: CoordinatorConfig,
+    pub logging: LoggingConfig,
 }
 
 lazy_static! {
+    // randomized per process, so that regtest nodes in different processes are unlikely to collide
+    static ref REGTEST_BASE_PORT: u16 =
+        30000 + 2 * (rand::thread_rng().next_u32() % 10000) as u16;
     static ref HELIUM_DEFAULT_CONNECTION_OPTIONS: ConnectionOptions = ConnectionOptions {
         inbox_maxlen: 100,
         outbox_maxlen: 100,
@@ -354,7 +422,19 @@ impl Config {
                     wait_time_for_microblocks: node
                         .wait_time_for_microblocks
                         .unwrap_or(default_node_config.wait_time_for_microblocks),
+                    mempool_replace_fee_bump_pct: node
+                        .mempool_replace_fee_bump_pct
+                        .unwrap_or(default_node_config.mempool_replace_fee_bump_pct),
                     prometheus_bind: node.prometheus_bind,
+                    receipts_log_path: node.receipts_log_path,
+                    event_index_path: node.event_index_path,
+                    event_index_retention: node
+                        .event_index_retention
+                        .unwrap_or(default_node_config.event_index_retention),
+                    p2p_max_restarts: node
+                        .p2p_max_restarts
+                        .unwrap_or(default_node_config.p2p_max_restarts),
+                    chain_id: node.chain_id.unwrap_or(default_node_config.chain_id),
                 };
                 node_config.set_bootstrap_node(node.bootstrap_node);
                 node_config
@@ -362,6 +442,14 @@ impl Config {
             None => default_node_config,
         };
 
+        if let Err(e) = node.get_bind_addrs() {
+            panic!("{}", e)
+        }
+
+        if node.event_index_retention == 0 {
+            panic!("Setting node.event_index_retention must be at least 1 block")
+        }
+
         let default_burnchain_config = BurnchainConfig::default();
         let burnchain = match config_file.burnchain {
             Some(burnchain) => {
@@ -411,6 +499,9 @@ impl Config {
                     burnchain_op_tx_fee: burnchain
                         .burnchain_op_tx_fee
                         .unwrap_or(default_burnchain_config.burnchain_op_tx_fee),
+                    utxo_min_conf: burnchain
+                        .utxo_min_conf
+                        .unwrap_or(default_burnchain_config.utxo_min_conf),
                     process_exit_at_block_height: burnchain.process_exit_at_block_height,
                 }
             }
@@ -430,22 +521,32 @@ impl Config {
             panic!("Config is missing the setting `burnchain.local_mining_public_key` (mandatory for helium)")
         }
 
-        let initial_balances: Vec<InitialBalance> = match config_file.mstx_balance {
-            Some(balances) => balances
-                .iter()
-                .map(|balance| {
-                    let address: PrincipalData =
-                        PrincipalData::parse_standard_principal(&balance.address)
-                            .unwrap()
-                            .into();
-                    InitialBalance {
-                        address,
-                        amount: balance.amount,
-                    }
-                })
-                .collect(),
-            None => vec![],
-        };
+        let initial_balances: Vec<InitialBalance> = config_file
+            .mstx_balance
+            .unwrap_or(vec![])
+            .into_iter()
+            .chain(config_file.ustx_balance.unwrap_or(vec![]).into_iter())
+            .map(|balance| {
+                let address: PrincipalData =
+                    match PrincipalData::parse_standard_principal(&balance.address) {
+                        Ok(address) => address.into(),
+                        Err(e) => panic!(
+                            "Setting ustx_balance.address is invalid: {} ({:?})",
+                            &balance.address, e
+                        ),
+                    };
+                if balance.amount == 0 {
+                    panic!(
+                        "Setting ustx_balance.amount is invalid: must be nonzero for {}",
+                        &balance.address
+                    )
+                }
+                InitialBalance {
+                    address,
+                    amount: balance.amount,
+                }
+            })
+            .collect();
 
         let mut events_observers = match config_file.events_observer {
             Some(raw_observers) => {
@@ -462,6 +563,8 @@ impl Config {
                     observers.push(EventObserverConfig {
                         endpoint,
                         events_keys,
+                        max_send_attempts: observer.max_send_attempts,
+                        since_height: observer.since_height,
                     });
                 }
                 observers
@@ -474,6 +577,8 @@ impl Config {
             Ok(val) => events_observers.push(EventObserverConfig {
                 endpoint: val,
                 events_keys: vec![EventKeyType::AnyEvent],
+                max_send_attempts: None,
+                since_height: None,
             }),
             _ => (),
         };
@@ -506,6 +611,36 @@ impl Config {
                 opts.read_only_call_limit_runtime.map(|x| {
                     read_only_call_limit.runtime = x;
                 });
+                for (name, timeout) in [
+                    ("connect_timeout_secs", opts.connect_timeout_secs),
+                    ("handshake_timeout_secs", opts.handshake_timeout_secs),
+                    ("peer_idle_timeout_secs", opts.peer_idle_timeout_secs),
+                ]
+                .iter()
+                {
+                    if *timeout == Some(0) {
+                        panic!(
+                            "Setting connection_options.{} is invalid: must be nonzero",
+                            name
+                        );
+                    }
+                }
+                let handshake_timeout = opts.handshake_timeout_secs.unwrap_or_else(|| {
+                    HELIUM_DEFAULT_CONNECTION_OPTIONS.handshake_timeout.clone()
+                });
+                let peer_idle_timeout = opts.peer_idle_timeout_secs.unwrap_or_else(|| {
+                    HELIUM_DEFAULT_CONNECTION_OPTIONS.peer_idle_timeout.clone()
+                });
+                let heartbeat = opts
+                    .heartbeat
+                    .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.heartbeat.clone());
+                if peer_idle_timeout > 0 && peer_idle_timeout <= handshake_timeout {
+                    panic!("Setting connection_options.peer_idle_timeout_secs is invalid: must be greater than handshake_timeout_secs ({})", handshake_timeout);
+                }
+                // a peer that only sends us heartbeats is still alive
+                if peer_idle_timeout > 0 && peer_idle_timeout <= heartbeat as u64 {
+                    panic!("Setting connection_options.peer_idle_timeout_secs is invalid: must be greater than heartbeat ({})", heartbeat);
+                }
                 ConnectionOptions {
                     read_only_call_limit,
                     inbox_maxlen: opts
@@ -520,9 +655,12 @@ impl Config {
                     idle_timeout: opts
                         .idle_timeout
                         .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.idle_timeout.clone()),
-                    heartbeat: opts
-                        .heartbeat
-                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.heartbeat.clone()),
+                    connect_timeout: opts.connect_timeout_secs.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.connect_timeout.clone()
+                    }),
+                    handshake_timeout,
+                    peer_idle_timeout,
+                    heartbeat,
                     private_key_lifetime: opts.private_key_lifetime.unwrap_or_else(|| {
                         HELIUM_DEFAULT_CONNECTION_OPTIONS
                             .private_key_lifetime
@@ -574,6 +712,9 @@ impl Config {
                     walk_interval: opts
                         .walk_interval
                         .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.walk_interval.clone()),
+                    walk_min_neighbors: opts.walk_min_neighbors.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.walk_min_neighbors.clone()
+                    }),
                     dns_timeout: opts
                         .dns_timeout
                         .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.dns_timeout.clone()),
@@ -596,6 +737,7 @@ impl Config {
                         HELIUM_DEFAULT_CONNECTION_OPTIONS.inv_sync_interval.clone()
                     }),
                     public_ip_address: ip_addr,
+                    public_ip_resolver_url: opts.public_ip_resolver_url,
                     ..ConnectionOptions::default()
                 }
             }
@@ -621,6 +763,91 @@ impl Config {
             None => HELIUM_BLOCK_LIMIT.clone(),
         };
 
+        let default_miner_config = MinerConfig::default();
+        let miner = match config_file.miner {
+            Some(miner) => MinerConfig {
+                wait_for_sync: miner
+                    .wait_for_sync
+                    .unwrap_or(default_miner_config.wait_for_sync),
+                max_sync_lag: miner
+                    .max_sync_lag
+                    .unwrap_or(default_miner_config.max_sync_lag),
+                disable_microblocks: miner
+                    .disable_microblocks
+                    .unwrap_or(default_miner_config.disable_microblocks),
+                burn_fee_escalation: match miner.burn_fee_escalation {
+                    Some(schedule) => {
+                        if schedule.is_empty() {
+                            panic!("Setting miner.burn_fee_escalation is invalid: the schedule is empty");
+                        }
+                        Some(schedule)
+                    }
+                    None => default_miner_config.burn_fee_escalation,
+                },
+                must_include: match miner.must_include {
+                    Some(txs) => txs
+                        .iter()
+                        .map(|tx_hex| {
+                            let tx_bytes = hex_bytes(tx_hex).unwrap_or_else(|_| {
+                                panic!(
+                                    "Setting miner.must_include is invalid: {} is not a hex string",
+                                    tx_hex
+                                )
+                            });
+                            StacksTransaction::consensus_deserialize(&mut &tx_bytes[..])
+                                .unwrap_or_else(|e| {
+                                    panic!(
+                                        "Setting miner.must_include is invalid: {} is not a transaction: {:?}",
+                                        tx_hex, e
+                                    )
+                                })
+                        })
+                        .collect(),
+                    None => default_miner_config.must_include,
+                },
+            },
+            None => default_miner_config,
+        };
+
+        let mempool = match config_file.mempool {
+            Some(mempool) => MempoolConfig {
+                max_txs: mempool.max_txs,
+                max_bytes: mempool.max_bytes,
+            },
+            None => MempoolConfig::default(),
+        };
+
+        let default_coordinator_config = CoordinatorConfig::default();
+        let coordinator = match config_file.coordinator {
+            Some(coordinator) => CoordinatorConfig {
+                max_reorg_depth: coordinator
+                    .max_reorg_depth
+                    .unwrap_or(default_coordinator_config.max_reorg_depth),
+                block_validation_threads: coordinator
+                    .block_validation_threads
+                    .unwrap_or(default_coordinator_config.block_validation_threads),
+            },
+            None => default_coordinator_config,
+        };
+
+        if coordinator.block_validation_threads == 0 {
+            panic!("Setting coordinator.block_validation_threads is invalid: must be at least 1")
+        }
+
+        let logging = match config_file.logging {
+            Some(module_levels) => {
+                let mut logging = LoggingConfig::default();
+                for (module, level) in module_levels.into_iter() {
+                    let ll = log::parse_loglevel(&level).unwrap_or_else(|e| {
+                        panic!("Setting logging.{} is invalid: {}", module, e)
+                    });
+                    logging.module_levels.insert(module, ll);
+                }
+                logging
+            }
+            None => LoggingConfig::default(),
+        };
+
         Config {
             node,
             burnchain,
@@ -628,6 +855,10 @@ impl Config {
             events_observers,
             connection_options,
             block_limit,
+            miner,
+            mempool,
+            coordinator,
+            logging,
         }
     }
 
@@ -696,6 +927,10 @@ impl std::default::Default for Config {
             events_observers: vec![],
             connection_options,
             block_limit,
+            miner: MinerConfig::default(),
+            mempool: MempoolConfig::default(),
+            coordinator: CoordinatorConfig::default(),
+            logging: LoggingConfig::default(),
         }
     }
 }
@@ -718,6 +953,9 @@ pub struct BurnchainConfig {
     pub magic_bytes: MagicBytes,
     pub local_mining_public_key: Option<String>,
     pub burnchain_op_tx_fee: u64,
+    /// Confirmations a UTXO needs before the miner will spend it.  Unconfirmed change is only
+    /// spent when operations are chained within a single `submit_operations` call.
+    pub utxo_min_conf: u32,
     pub process_exit_at_block_height: Option<u64>,
 }
 
@@ -740,6 +978,7 @@ impl BurnchainConfig {
             magic_bytes: BLOCKSTACK_MAGIC_MAINNET.clone(),
             local_mining_public_key: None,
             burnchain_op_tx_fee: MINIMUM_DUST_FEE,
+            utxo_min_conf: 1,
             process_exit_at_block_height: None,
         }
     }
@@ -790,6 +1029,7 @@ pub struct BurnchainConfigFile {
     pub magic_bytes: Option<String>,
     pub local_mining_public_key: Option<String>,
     pub burnchain_op_tx_fee: Option<u64>,
+    pub utxo_min_conf: Option<u32>,
     pub process_exit_at_block_height: Option<u64>,
 }
 
@@ -804,10 +1044,25 @@ pub struct NodeConfig {
     pub p2p_address: String,
     pub local_peer_seed: Vec<u8>,
     pub bootstrap_node: Option<Neighbor>,
+    /// Whether to mine.  A follower processes burnchain and Stacks blocks, but never registers
+    /// VRF keys or submits block commits.
     pub miner: bool,
     pub mine_microblocks: bool,
     pub wait_time_for_microblocks: u64,
+    /// Minimum fee increase, as a percentage, needed to replace a pending mempool transaction
+    pub mempool_replace_fee_bump_pct: u64,
     pub prometheus_bind: Option<String>,
+    pub receipts_log_path: Option<String>,
+    /// Directory in which to index announced blocks and their events, so that event observers
+    /// can be sent the blocks they missed.  No index is kept if None.
+    pub event_index_path: Option<String>,
+    /// How many of the most recent block heights the event index keeps
+    pub event_index_retention: u64,
+    /// How many times the p2p network thread is restarted after a panic before the node gives
+    /// up and aborts.
+    pub p2p_max_restarts: u64,
+    /// Chain id of the Stacks chain, which every transaction is signed for
+    pub chain_id: u32,
 }
 
 impl NodeConfig {
@@ -837,10 +1092,16 @@ impl NodeConfig {
             p2p_address: format!("127.0.0.1:{}", rpc_port),
             bootstrap_node: None,
             local_peer_seed: local_peer_seed.to_vec(),
-            miner: false,
+            miner: true,
             mine_microblocks: false,
             wait_time_for_microblocks: 15000,
+            mempool_replace_fee_bump_pct: 0,
             prometheus_bind: None,
+            receipts_log_path: None,
+            event_index_path: None,
+            event_index_retention: 1000,
+            p2p_max_restarts: 3,
+            chain_id: TESTNET_CHAIN_ID,
         }
     }
 
@@ -848,6 +1109,57 @@ impl NodeConfig {
         format!("{}/burnchain", self.working_dir)
     }
 
+    /// Parse `p2p_bind` and `rpc_bind` into the (p2p, rpc) addresses to bind the servers to.
+    /// Errors if either is not a socket address, or has a privileged port (below 1024) while
+    /// the process is not running as root.
+    pub fn get_bind_addrs(&self) -> Result<(SocketAddr, SocketAddr), String> {
+        self.parse_bind_addrs(NodeConfig::can_bind_privileged_ports())
+    }
+
+    /// Like `get_bind_addrs()`, but only allow privileged ports if `allow_privileged_ports` is set.
+    pub fn parse_bind_addrs(
+        &self,
+        allow_privileged_ports: bool,
+    ) -> Result<(SocketAddr, SocketAddr), String> {
+        let p2p_bind =
+            NodeConfig::parse_bind_addr("node.p2p_bind", &self.p2p_bind, allow_privileged_ports)?;
+        let rpc_bind =
+            NodeConfig::parse_bind_addr("node.rpc_bind", &self.rpc_bind, allow_privileged_ports)?;
+        Ok((p2p_bind, rpc_bind))
+    }
+
+    fn parse_bind_addr(
+        setting: &str,
+        bind: &str,
+        allow_privileged_ports: bool,
+    ) -> Result<SocketAddr, String> {
+        let addr: SocketAddr = bind.parse().map_err(|e| {
+            format!(
+                "Setting {} = \"{}\" is not a socket address: {}",
+                setting, bind, e
+            )
+        })?;
+
+        // port 0 means the OS picks one
+        if addr.port() != 0 && addr.port() < 1024 && !allow_privileged_ports {
+            return Err(format!(
+                "Setting {} = \"{}\" has a privileged port, which only root can bind",
+                setting, bind
+            ));
+        }
+        Ok(addr)
+    }
+
+    #[cfg(unix)]
+    fn can_bind_privileged_ports() -> bool {
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    #[cfg(not(unix))]
+    fn can_bind_privileged_ports() -> bool {
+        true
+    }
+
     pub fn get_default_spv_headers_path(&self) -> String {
         format!("{}/spv-headers.dat", self.get_burnchain_path())
     }
@@ -884,12 +1196,92 @@ impl NodeConfig {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct MinerConfig {
+    /// Skip tenures while the sortitions processed lag the burnchain tip
+    pub wait_for_sync: bool,
+    /// How many blocks the sortitions may lag the burnchain tip before mining pauses
+    pub max_sync_lag: u64,
+    /// Only mine anchored blocks, and don't produce or relay microblocks
+    pub disable_microblocks: bool,
+    /// Burn amounts for block commits after 0, 1, 2, ... consecutive missed sortitions, instead of
+    ///  the burnchain's `burn_fee_cap`.  The last entry applies once the schedule runs out, and a
+    ///  won sortition starts over from the first.
+    pub burn_fee_escalation: Option<Vec<u64>>,
+    /// Transactions to mine right after the coinbase of every block this node assembles, ahead
+    ///  of the mempool.  A tenure is aborted if any of them can't be mined at its chain tip, so
+    ///  they should be taken out once they are mined.
+    pub must_include: Vec<StacksTransaction>,
+}
+
+impl MinerConfig {
+    fn default() -> MinerConfig {
+        MinerConfig {
+            wait_for_sync: false,
+            max_sync_lag: 1,
+            disable_microblocks: false,
+            burn_fee_escalation: None,
+            must_include: vec![],
+        }
+    }
+}
+
+/// Mempool size limits.  Once either limit is reached, lower-fee-rate transactions are evicted to
+/// make room for higher-fee-rate ones.
+#[derive(Clone, Debug, Default)]
+pub struct MempoolConfig {
+    /// Maximum number of transactions to hold, or no limit if `None`
+    pub max_txs: Option<u64>,
+    /// Maximum total size in bytes of the transactions to hold, or no limit if `None`
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CoordinatorConfig {
+    /// How many sortitions a newly-discovered PoX anchor block may roll back.  If an anchor block
+    ///  would trigger a deeper reorg, the coordinator refuses it and halts.
+    pub max_reorg_depth: u64,
+    /// How many threads the coordinator uses to check staging blocks' transaction signatures
+    ///  ahead of processing them.  Blocks are still processed one at a time.
+    pub block_validation_threads: usize,
+}
+
+impl CoordinatorConfig {
+    fn default() -> CoordinatorConfig {
+        CoordinatorConfig {
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            block_validation_threads: 1,
+        }
+    }
+}
+
+/// Per-module log level overrides, from the `[logging]` section (e.g. `coordinator = "debug"`).
+/// Modules without an override log at the global level.
+#[derive(Clone, Debug, Default)]
+pub struct LoggingConfig {
+    pub module_levels: HashMap<String, u8>,
+}
+
+impl LoggingConfig {
+    /// Install the module log level overrides for the whole process
+    pub fn install(&self) {
+        for (module, ll) in self.module_levels.iter() {
+            log::set_module_loglevel(module, *ll)
+                .expect("FATAL: module log level was not validated");
+        }
+    }
+}
+
 #[derive(Clone, Default, Deserialize)]
 pub struct ConnectionOptionsFile {
     pub inbox_maxlen: Option<usize>,
     pub outbox_maxlen: Option<usize>,
     pub timeout: Option<u64>,
     pub idle_timeout: Option<u64>,
+    // p2p timeouts; `idle_timeout` is for HTTP connections
+    pub connect_timeout_secs: Option<u64>,
+    pub handshake_timeout_secs: Option<u64>,
+    pub peer_idle_timeout_secs: Option<u64>,
     pub heartbeat: Option<u32>,
     pub private_key_lifetime: Option<u64>,
     pub num_neighbors: Option<u64>,
@@ -902,6 +1294,7 @@ pub struct ConnectionOptionsFile {
     pub soft_max_neighbors_per_org: Option<u64>,
     pub soft_max_clients_per_host: Option<u64>,
     pub walk_interval: Option<u64>,
+    pub walk_min_neighbors: Option<u64>,
     pub dns_timeout: Option<u128>,
     pub max_inflight_blocks: Option<u64>,
     pub read_only_call_limit_write_length: Option<u64>,
@@ -913,6 +1306,7 @@ pub struct ConnectionOptionsFile {
     pub download_interval: Option<u64>,
     pub inv_sync_interval: Option<u64>,
     pub public_ip_address: Option<String>,
+    pub public_ip_resolver_url: Option<String>,
 }
 
 #[derive(Clone, Default, Deserialize)]
@@ -938,22 +1332,56 @@ pub struct NodeConfigFile {
     pub miner: Option<bool>,
     pub mine_microblocks: Option<bool>,
     pub wait_time_for_microblocks: Option<u64>,
+    pub mempool_replace_fee_bump_pct: Option<u64>,
     pub prometheus_bind: Option<String>,
+    pub receipts_log_path: Option<String>,
+    pub event_index_path: Option<String>,
+    pub event_index_retention: Option<u64>,
+    pub p2p_max_restarts: Option<u64>,
+    pub chain_id: Option<u32>,
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct MinerConfigFile {
+    pub wait_for_sync: Option<bool>,
+    pub max_sync_lag: Option<u64>,
+    pub disable_microblocks: Option<bool>,
+    pub burn_fee_escalation: Option<Vec<u64>>,
+    /// hex-encoded transactions
+    pub must_include: Option<Vec<String>>,
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct MempoolConfigFile {
+    pub max_txs: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct CoordinatorConfigFile {
+    pub max_reorg_depth: Option<u64>,
+    pub block_validation_threads: Option<usize>,
 }
 
 #[derive(Clone, Deserialize, Default)]
 pub struct EventObserverConfigFile {
     pub endpoint: String,
     pub events_keys: Vec<String>,
+    pub max_send_attempts: Option<u64>,
+    pub since_height: Option<u64>,
 }
 
 #[derive(Clone, Default)]
 pub struct EventObserverConfig {
     pub endpoint: String,
     pub events_keys: Vec<EventKeyType>,
+    /// How many times to try sending a payload before giving up on it.  Retries forever if None.
+    pub max_send_attempts: Option<u64>,
+    /// If set, the observer is first sent every block in the event index from this height on.
+    pub since_height: Option<u64>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum EventKeyType {
     SmartContractEvent((QualifiedContractIdentifier, String)),
     AssetEvent(AssetIdentifier),
@@ -4,6 +4,10 @@ use stacks::chainstate::stacks::events::StacksTransactionReceipt;
 use stacks::chainstate::stacks::StacksBlock;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::thread::sleep;
 use std::time::Duration;
 
@@ -13,7 +17,6 @@ use http_types::{Method, Request, Url};
 
 use serde_json::json;
 
-use stacks::burnchains::Txid;
 use stacks::chainstate::stacks::events::{
     FTEventType, NFTEventType, STXEventType, StacksTransactionEvent,
 };
@@ -24,12 +27,23 @@ use stacks::util::hash::bytes_to_hex;
 use stacks::vm::analysis::contract_interface_builder::build_contract_interface;
 use stacks::vm::types::{AssetIdentifier, QualifiedContractIdentifier, Value};
 
-use super::config::{EventKeyType, EventObserverConfig};
+use super::config::{Config, EventKeyType, EventObserverConfig};
 use super::node::ChainTip;
 
 #[derive(Debug, Clone)]
 struct EventObserver {
     endpoint: String,
+    max_send_attempts: Option<u64>,
+    delivery: Arc<Mutex<DeliveryState>>,
+}
+
+/// Sequence numbers of the payloads sent to an observer.  Each payload gets the next sequence
+/// number, starting from 1, which is sent along in the `X-Event-Sequence` header.
+#[derive(Debug, Default)]
+struct DeliveryState {
+    last_seq: u64,
+    /// Highest sequence number such that it and every sequence number before it were acknowledged
+    last_acked_seq: u64,
 }
 
 const STATUS_RESP_TRUE: &str = "success";
@@ -39,13 +53,56 @@ const STATUS_RESP_POST_CONDITION: &str = "abort_by_post_condition";
 pub const PATH_MEMPOOL_TX_SUBMIT: &str = "new_mempool_tx";
 pub const PATH_BLOCK_PROCESSED: &str = "new_block";
 
+pub const HEADER_EVENT_SEQUENCE: &str = "X-Event-Sequence";
+
 impl EventObserver {
+    fn new(conf: &EventObserverConfig) -> EventObserver {
+        EventObserver {
+            endpoint: conf.endpoint.clone(),
+            max_send_attempts: conf.max_send_attempts,
+            delivery: Arc::new(Mutex::new(DeliveryState::default())),
+        }
+    }
+
+    fn last_acked_seq(&self) -> u64 {
+        self.delivery
+            .lock()
+            .expect("FATAL: event observer delivery state lock poisoned")
+            .last_acked_seq
+    }
+
     fn send_payload(&self, payload: &serde_json::Value, path: &str) {
+        // hold the delivery state while sending, so payloads are delivered in sequence order
+        let mut delivery = self
+            .delivery
+            .lock()
+            .expect("FATAL: event observer delivery state lock poisoned");
+        delivery.last_seq += 1;
+        let seq = delivery.last_seq;
+
+        if self.try_send_payload(payload, path, seq) {
+            if delivery.last_acked_seq + 1 == seq {
+                delivery.last_acked_seq = seq;
+            } else {
+                warn!(
+                    "Event dispatcher: {} acknowledged payload {}, but never acknowledged payloads {} through {}",
+                    self.endpoint,
+                    seq,
+                    delivery.last_acked_seq + 1,
+                    seq - 1
+                );
+            }
+        }
+    }
+
+    /// POST the payload, retrying until it succeeds or max_send_attempts is reached.
+    /// Returns true if the observer acknowledged it.
+    fn try_send_payload(&self, payload: &serde_json::Value, path: &str, seq: u64) -> bool {
         let body = match serde_json::to_vec(&payload) {
             Ok(body) => body,
             Err(err) => {
                 error!("Event dispatcher: serialization failed  - {:?}", err);
-                return;
+                return false;
             }
         };
 
@@ -62,12 +119,15 @@ impl EventObserver {
         };
 
         let backoff = Duration::from_millis((1.0 * 1_000.0) as u64);
+        let mut attempts = 0;
 
         loop {
             let body = body.clone();
             let mut req = Request::new(Method::Post, url.clone());
             req.append_header("Content-Type", "application/json")
                 .expect("Unable to set header");
+            req.append_header(HEADER_EVENT_SEQUENCE, format!("{}", seq))
+                .expect("Unable to set header");
             req.set_body(body);
 
             let response = async_std::task::block_on(async {
@@ -90,7 +150,7 @@ impl EventObserver {
 
             if let Some(response) = response {
                 if response.status().is_success() {
-                    break;
+                    return true;
                 } else {
                     error!(
                         "Event dispatcher: POST {} failed with error {:?}",
@@ -98,6 +158,17 @@ impl EventObserver {
                     );
                 }
             }
+
+            attempts += 1;
+            if let Some(max_send_attempts) = self.max_send_attempts {
+                if attempts >= max_send_attempts {
+                    error!(
+                        "Event dispatcher: giving up on payload {} to {} after {} attempts",
+                        seq, self.endpoint, attempts
+                    );
+                    return false;
+                }
+            }
             sleep(backoff);
         }
     }
@@ -167,19 +238,12 @@ impl EventObserver {
         self.send_payload(payload, PATH_MEMPOOL_TX_SUBMIT);
     }
 
-    fn send(
-        &self,
-        filtered_events: Vec<&(bool, Txid, &StacksTransactionEvent)>,
+    /// Build the new block payload, minus the events, which are filled in per observer
+    fn make_new_block_payload(
         chain_tip: &ChainTip,
         parent_index_hash: &StacksBlockId,
         boot_receipts: Option<&Vec<StacksTransactionReceipt>>,
-    ) {
-        // Serialize events to JSON
-        let serialized_events: Vec<serde_json::Value> = filtered_events
-            .iter()
-            .map(|(committed, txid, event)| event.json_serialize(txid, *committed))
-            .collect();
-
+    ) -> serde_json::Value {
         let mut tx_index: u32 = 0;
         let mut serialized_txs = vec![];
 
@@ -197,8 +261,7 @@ impl EventObserver {
             }
         }
 
-        // Wrap events
-        let payload = json!({
+        json!({
             "block_hash": format!("0x{}", chain_tip.block.block_hash()),
             "block_height": chain_tip.metadata.block_height,
             "burn_block_time": chain_tip.metadata.burn_header_timestamp,
@@ -206,24 +269,330 @@ impl EventObserver {
             "parent_block_hash": format!("0x{}", chain_tip.block.header.parent_block),
             "parent_index_block_hash": format!("0x{}", parent_index_hash),
             "parent_microblock": format!("0x{}", chain_tip.block.header.parent_microblock),
-            "events": serialized_events,
+            "events": [],
             "transactions": serialized_txs,
-        });
+        })
+    }
 
-        // Send payload
+    fn send_new_block(&self, block_payload: &serde_json::Value, events: Vec<serde_json::Value>) {
+        let mut payload = block_payload.clone();
+        payload["events"] = serde_json::Value::Array(events);
         self.send_payload(&payload, PATH_BLOCK_PROCESSED);
     }
 }
 
+/// The key under which an event is announced, if observers can subscribe to it specifically.
+fn event_key(event: &StacksTransactionEvent) -> Option<EventKeyType> {
+    match event {
+        StacksTransactionEvent::SmartContractEvent(event_data) => {
+            Some(EventKeyType::SmartContractEvent(event_data.key.clone()))
+        }
+        StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(_))
+        | StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_))
+        | StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(_))
+        | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_)) => {
+            Some(EventKeyType::STXEvent)
+        }
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(event_data)) => Some(
+            EventKeyType::AssetEvent(event_data.asset_identifier.clone()),
+        ),
+        StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(event_data)) => Some(
+            EventKeyType::AssetEvent(event_data.asset_identifier.clone()),
+        ),
+        StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(event_data)) => Some(
+            EventKeyType::AssetEvent(event_data.asset_identifier.clone()),
+        ),
+        StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(event_data)) => Some(
+            EventKeyType::AssetEvent(event_data.asset_identifier.clone()),
+        ),
+    }
+}
+
+/// Check that an event observer endpoint is a bare `host:port`, which is what try_send_payload()
+/// connects to and builds its request URLs from.
+fn validate_observer_endpoint(endpoint: &str) -> Result<(), String> {
+    if endpoint.contains(|c| c == '/' || c == '?' || c == '#' || c == '@') {
+        return Err(format!(
+            "Event observer endpoint '{}' must be a bare host:port, not a URL",
+            endpoint
+        ));
+    }
+    let has_port = match endpoint.rsplitn(2, ':').next() {
+        Some(port) => port.parse::<u16>().is_ok(),
+        None => false,
+    };
+    if !has_port || Url::parse(&format!("http://{}", endpoint)).is_err() {
+        return Err(format!(
+            "Event observer endpoint '{}' is not a valid host:port",
+            endpoint
+        ));
+    }
+    Ok(())
+}
+
+/// Whether an observer subscribed to `events_keys` wants an event announced under `event_key`.
+fn is_subscribed(events_keys: &[EventKeyType], event_key: Option<&EventKeyType>) -> bool {
+    events_keys.iter().any(|key| match key {
+        EventKeyType::AnyEvent => true,
+        key => Some(key) == event_key,
+    })
+}
+
+/// On-disk index of announced blocks and their events, keyed by block height, so that observers
+/// registered late can be sent the blocks they missed.  Each height has its own file in the index
+/// directory, holding one JSON line per block announced at that height.  Only the `retention`
+/// most recent heights are kept.
+#[derive(Debug, Clone)]
+pub struct EventIndex {
+    path: String,
+    retention: u64,
+}
+
+impl EventIndex {
+    pub fn new(path: &str, retention: u64) -> EventIndex {
+        EventIndex {
+            path: path.to_string(),
+            retention,
+        }
+    }
+
+    fn height_path(&self, height: u64) -> PathBuf {
+        Path::new(&self.path).join(format!("{}.json", height))
+    }
+
+    /// Get the indexed block heights, in ascending order
+    fn heights(&self) -> Vec<u64> {
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            // nothing indexed yet
+            Err(_) => return vec![],
+        };
+
+        let mut heights: Vec<u64> = entries
+            .filter_map(|entry| {
+                let file_name = entry.ok()?.file_name().into_string().ok()?;
+                if !file_name.ends_with(".json") {
+                    return None;
+                }
+                file_name[0..file_name.len() - ".json".len()].parse().ok()
+            })
+            .collect();
+        heights.sort();
+        heights
+    }
+
+    fn make_record(
+        block_payload: &serde_json::Value,
+        events: &[serde_json::Value],
+        event_keys: &[Option<EventKeyType>],
+    ) -> serde_json::Value {
+        let indexed_events: Vec<_> = events
+            .iter()
+            .zip(event_keys.iter())
+            .map(|(event, key)| json!({ "key": key, "event": event }))
+            .collect();
+
+        json!({
+            "block": block_payload,
+            "events": indexed_events,
+        })
+    }
+
+    /// Append a block's record at its height, and prune the heights that fell out of retention
+    fn append(&self, height: u64, record: &serde_json::Value) {
+        let mut line = record.to_string().into_bytes();
+        line.push(b'\n');
+
+        let result = fs::create_dir_all(&self.path)
+            .and_then(|_| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(self.height_path(height))
+            })
+            .and_then(|mut fd| fd.write_all(&line));
+
+        if let Err(e) = result {
+            warn!(
+                "Event dispatcher: failed to index block at height {} in {}: {:?}",
+                height, &self.path, &e
+            );
+        }
+
+        self.prune(height);
+    }
+
+    /// Remove every height that is at least `retention` blocks below `tip_height`
+    fn prune(&self, tip_height: u64) {
+        for height in self.heights() {
+            if height + self.retention > tip_height {
+                break;
+            }
+            if let Err(e) = fs::remove_file(self.height_path(height)) {
+                warn!(
+                    "Event dispatcher: failed to prune indexed height {} in {}: {:?}",
+                    height, &self.path, &e
+                );
+            }
+        }
+    }
+
+    /// Load the records of every block indexed at or above `since_height`, in height order and
+    /// then in announcement order.
+    fn load_since(&self, since_height: u64) -> Vec<serde_json::Value> {
+        let mut records = vec![];
+        for height in self.heights() {
+            if height < since_height {
+                continue;
+            }
+
+            let fd = match fs::File::open(self.height_path(height)) {
+                Ok(fd) => fd,
+                Err(e) => {
+                    warn!(
+                        "Event dispatcher: failed to open indexed height {} in {}: {:?}",
+                        height, &self.path, &e
+                    );
+                    continue;
+                }
+            };
+
+            for line in BufReader::new(fd).lines() {
+                match line
+                    .map_err(|e| format!("{:?}", &e))
+                    .and_then(|line| serde_json::from_str(&line).map_err(|e| format!("{:?}", &e)))
+                {
+                    Ok(record) => records.push(record),
+                    Err(e) => {
+                        warn!(
+                            "Event dispatcher: failed to read indexed height {} in {}: {}",
+                            height, &self.path, &e
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+        records
+    }
+}
+
+/// Identifies a registered observer.  Ids are not reused once an observer is removed.  This is
+/// the observer's index in the registry, so it is as wide as one and cannot wrap around.
+pub type ObserverId = usize;
+
+/// The event keys an observer subscribes to
+pub type EventFilter = Vec<EventKeyType>;
+
+/// The registered observers and which of them subscribed to what.  Removed observers leave an
+/// empty slot behind, so that the ids of the others stay valid.
+#[derive(Default)]
+struct ObserverRegistry {
+    observers: Vec<Option<EventObserver>>,
+    contract_events_observers_lookup:
+        HashMap<(QualifiedContractIdentifier, String), HashSet<ObserverId>>,
+    assets_observers_lookup: HashMap<AssetIdentifier, HashSet<ObserverId>>,
+    mempool_observers_lookup: HashSet<ObserverId>,
+    stx_observers_lookup: HashSet<ObserverId>,
+    any_event_observers_lookup: HashSet<ObserverId>,
+}
+
+impl ObserverRegistry {
+    fn update_dispatch_matrix_if_observer_subscribed(
+        &self,
+        asset_identifier: &AssetIdentifier,
+        event_index: usize,
+        dispatch_matrix: &mut Vec<HashSet<usize>>,
+    ) {
+        if let Some(observer_indexes) = self.assets_observers_lookup.get(asset_identifier) {
+            for o_i in observer_indexes {
+                dispatch_matrix[*o_i].insert(event_index);
+            }
+        }
+    }
+
+    fn insert(
+        &mut self,
+        event_observer: EventObserver,
+        events_keys: &[EventKeyType],
+    ) -> ObserverId {
+        let observer_index = self.observers.len();
+
+        for event_key_type in events_keys.iter() {
+            match event_key_type {
+                EventKeyType::SmartContractEvent(event_key) => {
+                    match self
+                        .contract_events_observers_lookup
+                        .entry(event_key.clone())
+                    {
+                        Entry::Occupied(observer_indexes) => {
+                            observer_indexes.into_mut().insert(observer_index);
+                        }
+                        Entry::Vacant(v) => {
+                            let mut observer_indexes = HashSet::new();
+                            observer_indexes.insert(observer_index);
+                            v.insert(observer_indexes);
+                        }
+                    };
+                }
+                EventKeyType::MemPoolTransactions => {
+                    self.mempool_observers_lookup.insert(observer_index);
+                }
+                EventKeyType::STXEvent => {
+                    self.stx_observers_lookup.insert(observer_index);
+                }
+                EventKeyType::AssetEvent(event_key) => {
+                    match self.assets_observers_lookup.entry(event_key.clone()) {
+                        Entry::Occupied(observer_indexes) => {
+                            observer_indexes.into_mut().insert(observer_index);
+                        }
+                        Entry::Vacant(v) => {
+                            let mut observer_indexes = HashSet::new();
+                            observer_indexes.insert(observer_index);
+                            v.insert(observer_indexes);
+                        }
+                    };
+                }
+                EventKeyType::AnyEvent => {
+                    self.any_event_observers_lookup.insert(observer_index);
+                }
+            }
+        }
+
+        self.observers.push(Some(event_observer));
+        observer_index
+    }
+
+    /// Empty the observer's slot and drop it from every lookup.  Returns the removed observer,
+    /// if it was registered.
+    fn remove(&mut self, id: ObserverId) -> Option<EventObserver> {
+        let event_observer = self.observers.get_mut(id)?.take()?;
+
+        for observer_indexes in self.contract_events_observers_lookup.values_mut() {
+            observer_indexes.remove(&id);
+        }
+        self.contract_events_observers_lookup
+            .retain(|_, observer_indexes| !observer_indexes.is_empty());
+        for observer_indexes in self.assets_observers_lookup.values_mut() {
+            observer_indexes.remove(&id);
+        }
+        self.assets_observers_lookup
+            .retain(|_, observer_indexes| !observer_indexes.is_empty());
+        self.mempool_observers_lookup.remove(&id);
+        self.stx_observers_lookup.remove(&id);
+        self.any_event_observers_lookup.remove(&id);
+
+        Some(event_observer)
+    }
+}
+
+/// Dispatches block and mempool events to the registered observers.  Clones share the same
+/// observers, so an observer added or removed through one clone is seen by all of them.
 #[derive(Clone)]
 pub struct EventDispatcher {
-    registered_observers: Vec<EventObserver>,
-    contract_events_observers_lookup: HashMap<(QualifiedContractIdentifier, String), HashSet<u16>>,
-    assets_observers_lookup: HashMap<AssetIdentifier, HashSet<u16>>,
-    mempool_observers_lookup: HashSet<u16>,
-    stx_observers_lookup: HashSet<u16>,
-    any_event_observers_lookup: HashSet<u16>,
+    registry: Arc<RwLock<ObserverRegistry>>,
     boot_receipts: Vec<StacksTransactionReceipt>,
+    event_index: Option<EventIndex>,
 }
 
 impl BlockEventDispatcher for EventDispatcher {
@@ -250,23 +619,46 @@ impl BlockEventDispatcher for EventDispatcher {
 impl EventDispatcher {
     pub fn new() -> EventDispatcher {
         EventDispatcher {
-            registered_observers: vec![],
-            contract_events_observers_lookup: HashMap::new(),
-            assets_observers_lookup: HashMap::new(),
-            stx_observers_lookup: HashSet::new(),
-            any_event_observers_lookup: HashSet::new(),
-            mempool_observers_lookup: HashSet::new(),
+            registry: Arc::new(RwLock::new(ObserverRegistry::default())),
             boot_receipts: vec![],
+            event_index: None,
+        }
+    }
+
+    /// Make a dispatcher with the node's event index, if any, and its configured observers
+    pub fn from_config(config: &Config) -> EventDispatcher {
+        let mut event_dispatcher = EventDispatcher::new();
+        if let Some(ref path) = config.node.event_index_path {
+            event_dispatcher
+                .set_event_index(EventIndex::new(path, config.node.event_index_retention));
         }
+        for observer in config.events_observers.iter() {
+            event_dispatcher.register_observer(observer);
+        }
+        event_dispatcher
+    }
+
+    /// Index every block announced from now on.  Observers registered afterwards with a
+    /// `since_height` are sent the indexed blocks from that height on.
+    pub fn set_event_index(&mut self, event_index: EventIndex) {
+        self.event_index = Some(event_index);
+    }
+
+    fn read_registry(&self) -> RwLockReadGuard<ObserverRegistry> {
+        self.registry
+            .read()
+            .expect("FATAL: event observer registry lock poisoned")
+    }
+
+    fn write_registry(&self) -> RwLockWriteGuard<ObserverRegistry> {
+        self.registry
+            .write()
+            .expect("FATAL: event observer registry lock poisoned")
     }
 
     pub fn process_chain_tip(&self, chain_tip: &ChainTip, parent_index_hash: &StacksBlockId) {
-        let mut dispatch_matrix: Vec<HashSet<usize>> = self
-            .registered_observers
-            .iter()
-            .map(|_| HashSet::new())
-            .collect();
-        let mut events: Vec<(bool, Txid, &StacksTransactionEvent)> = vec![];
+        let mut events: Vec<serde_json::Value> = vec![];
+        let mut event_keys: Vec<Option<EventKeyType>> = vec![];
         let mut i: usize = 0;
 
         let boot_receipts = if chain_tip.metadata.block_height == 1 {
@@ -275,99 +667,109 @@ impl EventDispatcher {
             None
         };
 
-        for receipt in chain_tip.receipts.iter() {
-            let tx_hash = receipt.transaction.txid();
-            for event in receipt.events.iter() {
-                match event {
-                    StacksTransactionEvent::SmartContractEvent(event_data) => {
-                        if let Some(observer_indexes) =
-                            self.contract_events_observers_lookup.get(&event_data.key)
-                        {
-                            for o_i in observer_indexes {
-                                dispatch_matrix[*o_i as usize].insert(i);
+        // only hold the registry while matching events, so that observers can be added or
+        // removed while the block is being sent
+        let dispatches: Vec<(EventObserver, HashSet<usize>)> = {
+            let registry = self.read_registry();
+            let mut dispatch_matrix: Vec<HashSet<usize>> =
+                registry.observers.iter().map(|_| HashSet::new()).collect();
+
+            for receipt in chain_tip.receipts.iter() {
+                let tx_hash = receipt.transaction.txid();
+                for event in receipt.events.iter() {
+                    let key = event_key(event);
+                    match key {
+                        Some(EventKeyType::SmartContractEvent(ref contract_event_key)) => {
+                            if let Some(observer_indexes) = registry
+                                .contract_events_observers_lookup
+                                .get(contract_event_key)
+                            {
+                                for o_i in observer_indexes {
+                                    dispatch_matrix[*o_i].insert(i);
+                                }
                             }
                         }
-                    }
-                    StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(_))
-                    | StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_))
-                    | StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(_))
-                    | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_)) => {
-                        for o_i in &self.stx_observers_lookup {
-                            dispatch_matrix[*o_i as usize].insert(i);
+                        Some(EventKeyType::STXEvent) => {
+                            for o_i in &registry.stx_observers_lookup {
+                                dispatch_matrix[*o_i].insert(i);
+                            }
                         }
+                        Some(EventKeyType::AssetEvent(ref asset_identifier)) => {
+                            registry.update_dispatch_matrix_if_observer_subscribed(
+                                asset_identifier,
+                                i,
+                                &mut dispatch_matrix,
+                            );
+                        }
+                        _ => {}
                     }
-                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(
-                        event_data,
-                    )) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
+                    events.push(event.json_serialize(&tx_hash, !receipt.post_condition_aborted));
+                    event_keys.push(key);
+                    for o_i in &registry.any_event_observers_lookup {
+                        dispatch_matrix[*o_i].insert(i);
                     }
+                    i += 1;
                 }
-                events.push((!receipt.post_condition_aborted, tx_hash, event));
-                for o_i in &self.any_event_observers_lookup {
-                    dispatch_matrix[*o_i as usize].insert(i);
-                }
-                i += 1;
             }
+
+            registry
+                .observers
+                .iter()
+                .zip(dispatch_matrix.into_iter())
+                .filter_map(|(observer, filtered_events_ids)| {
+                    observer
+                        .as_ref()
+                        .map(|observer| (observer.clone(), filtered_events_ids))
+                })
+                .collect()
+        };
+
+        let block_payload =
+            EventObserver::make_new_block_payload(chain_tip, parent_index_hash, boot_receipts);
+
+        if let Some(ref event_index) = self.event_index {
+            event_index.append(
+                chain_tip.metadata.block_height,
+                &EventIndex::make_record(&block_payload, &events, &event_keys),
+            );
         }
 
-        for (observer_id, filtered_events_ids) in dispatch_matrix.iter().enumerate() {
+        for (observer, filtered_events_ids) in dispatches.iter() {
             let filtered_events: Vec<_> = filtered_events_ids
                 .iter()
-                .map(|event_id| &events[*event_id])
+                .map(|event_id| events[*event_id].clone())
                 .collect();
 
-            self.registered_observers[observer_id].send(
-                filtered_events,
-                chain_tip,
-                parent_index_hash,
-                boot_receipts,
-            );
+            observer.send_new_block(&block_payload, filtered_events);
         }
     }
 
     pub fn process_new_mempool_txs(&self, txs: Vec<StacksTransaction>) {
         // lazily assemble payload only if we have observers
-        let interested_observers: Vec<_> = self
-            .registered_observers
-            .iter()
-            .enumerate()
-            .filter(|(obs_id, _observer)| {
-                self.mempool_observers_lookup.contains(&(*obs_id as u16))
-                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
-            })
-            .collect();
+        let interested_observers: Vec<_> = {
+            let registry = self.read_registry();
+            registry
+                .observers
+                .iter()
+                .enumerate()
+                .filter_map(|(obs_id, observer)| {
+                    if registry.mempool_observers_lookup.contains(&obs_id)
+                        || registry.any_event_observers_lookup.contains(&obs_id)
+                    {
+                        observer.clone()
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        };
         if interested_observers.len() < 1 {
             return;
         }
 
         let payload = EventObserver::make_new_mempool_txs_payload(txs);
 
-        for (_, observer) in interested_observers.iter() {
+        for observer in interested_observers.iter() {
             observer.send_new_mempool_txs(&payload);
         }
     }
@@ -376,69 +778,98 @@ impl EventDispatcher {
         self.boot_receipts = receipts;
     }
 
-    fn update_dispatch_matrix_if_observer_subscribed(
+    /// Get the highest sequence number such that the observer acknowledged that payload and
+    /// every payload before it.  0 if it has acknowledged none, or if it was removed.
+    pub fn last_acked_seq(&self, observer_id: ObserverId) -> u64 {
+        self.read_registry().observers[observer_id]
+            .as_ref()
+            .map(|observer| observer.last_acked_seq())
+            .unwrap_or(0)
+    }
+
+    /// Send an observer every indexed block from `since_height` on, with the events it subscribed to
+    fn replay_indexed_blocks(
         &self,
-        asset_identifier: &AssetIdentifier,
-        event_index: usize,
-        dispatch_matrix: &mut Vec<HashSet<usize>>,
+        event_observer: &EventObserver,
+        events_keys: &[EventKeyType],
+        since_height: u64,
     ) {
-        if let Some(observer_indexes) = self.assets_observers_lookup.get(asset_identifier) {
-            for o_i in observer_indexes {
-                dispatch_matrix[*o_i as usize].insert(event_index);
+        let event_index = match self.event_index {
+            Some(ref event_index) => event_index,
+            None => {
+                warn!(
+                    "Event dispatcher: cannot send blocks since height {} to {}, because no event index is kept",
+                    since_height, &event_observer.endpoint
+                );
+                return;
             }
+        };
+
+        let records = event_index.load_since(since_height);
+        info!(
+            "Event dispatcher: sending {} indexed blocks since height {} to {}",
+            records.len(),
+            since_height,
+            &event_observer.endpoint
+        );
+
+        for record in records.iter() {
+            let filtered_events: Vec<_> = match record["events"].as_array() {
+                Some(events) => events
+                    .iter()
+                    .filter(|indexed_event| {
+                        let key: Option<EventKeyType> =
+                            serde_json::from_value(indexed_event["key"].clone()).unwrap_or(None);
+                        is_subscribed(events_keys, key.as_ref())
+                    })
+                    .map(|indexed_event| indexed_event["event"].clone())
+                    .collect(),
+                None => vec![],
+            };
+
+            event_observer.send_new_block(&record["block"], filtered_events);
         }
     }
 
-    pub fn register_observer(&mut self, conf: &EventObserverConfig) {
-        // let event_observer = EventObserver::new(&conf.address, conf.port);
+    pub fn register_observer(&mut self, conf: &EventObserverConfig) -> ObserverId {
         info!("Registering event observer at: {}", conf.endpoint);
-        let event_observer = EventObserver {
-            endpoint: conf.endpoint.clone(),
-        };
+        let event_observer = EventObserver::new(conf);
 
-        let observer_index = self.registered_observers.len() as u16;
+        if let Some(since_height) = conf.since_height {
+            self.replay_indexed_blocks(&event_observer, &conf.events_keys, since_height);
+        }
 
-        for event_key_type in conf.events_keys.iter() {
-            match event_key_type {
-                EventKeyType::SmartContractEvent(event_key) => {
-                    match self
-                        .contract_events_observers_lookup
-                        .entry(event_key.clone())
-                    {
-                        Entry::Occupied(observer_indexes) => {
-                            observer_indexes.into_mut().insert(observer_index);
-                        }
-                        Entry::Vacant(v) => {
-                            let mut observer_indexes = HashSet::new();
-                            observer_indexes.insert(observer_index);
-                            v.insert(observer_indexes);
-                        }
-                    };
-                }
-                EventKeyType::MemPoolTransactions => {
-                    self.mempool_observers_lookup.insert(observer_index);
-                }
-                EventKeyType::STXEvent => {
-                    self.stx_observers_lookup.insert(observer_index);
-                }
-                EventKeyType::AssetEvent(event_key) => {
-                    match self.assets_observers_lookup.entry(event_key.clone()) {
-                        Entry::Occupied(observer_indexes) => {
-                            observer_indexes.into_mut().insert(observer_index);
-                        }
-                        Entry::Vacant(v) => {
-                            let mut observer_indexes = HashSet::new();
-                            observer_indexes.insert(observer_index);
-                            v.insert(observer_indexes);
-                        }
-                    };
-                }
-                EventKeyType::AnyEvent => {
-                    self.any_event_observers_lookup.insert(observer_index);
-                }
+        self.write_registry()
+            .insert(event_observer, &conf.events_keys)
+    }
+
+    /// Register an observer at runtime.  It is sent every event dispatched from now on that
+    /// passes `filter`, or every event if there is no filter.  Every clone of this dispatcher
+    /// sees the new observer.
+    /// `endpoint` must be a bare `host:port` (no scheme or path); anything else is rejected here,
+    /// rather than failing once events are dispatched to it.
+    pub fn add_observer(
+        &mut self,
+        endpoint: String,
+        filter: Option<EventFilter>,
+    ) -> Result<ObserverId, String> {
+        validate_observer_endpoint(&endpoint)?;
+        Ok(self.register_observer(&EventObserverConfig {
+            endpoint,
+            events_keys: filter.unwrap_or_else(|| vec![EventKeyType::AnyEvent]),
+            max_send_attempts: None,
+            since_height: None,
+        }))
+    }
+
+    /// Stop dispatching events to an observer.  A payload already being sent to it is still
+    /// delivered.  Does nothing if the observer was already removed.
+    pub fn remove_observer(&mut self, id: ObserverId) {
+        match self.write_registry().remove(id) {
+            Some(event_observer) => {
+                info!("Removed event observer at: {}", event_observer.endpoint)
             }
+            None => warn!("Event dispatcher: no event observer with id {}", id),
         }
-
-        self.registered_observers.push(event_observer);
     }
 }
@@ -10,6 +10,9 @@ use stacks::chainstate::stacks::{
 use stacks::util::hash::Sha256Sum;
 use stacks::util::vrf::{VRFPrivateKey, VRFProof, VRFPublicKey, VRF};
 
+/// Default number of burnchain blocks for which a key derived by `rotate_vrf_key()` is used
+pub const DEFAULT_VRF_KEY_ROTATION_PERIOD: u64 = 144;
+
 #[derive(Clone)]
 pub struct Keychain {
     secret_keys: Vec<StacksPrivateKey>,
@@ -20,6 +23,7 @@ pub struct Keychain {
     vrf_secret_keys: Vec<VRFPrivateKey>,
     vrf_map: HashMap<VRFPublicKey, VRFPrivateKey>,
     rotations: u64,
+    vrf_rotation_period: u64,
 }
 
 impl Keychain {
@@ -48,6 +52,7 @@ impl Keychain {
             rotations: 0,
             vrf_secret_keys: vec![],
             vrf_map: HashMap::new(),
+            vrf_rotation_period: DEFAULT_VRF_KEY_ROTATION_PERIOD,
         }
     }
 
@@ -75,26 +80,58 @@ impl Keychain {
             .rotations
             .checked_add(1)
             .expect("Exhausted VRF keypairs"); // this would require quite the hash power...
-        let mut seed = {
+        let seed = {
             let mut secret_state = self.hashed_secret_state.to_bytes().to_vec();
             secret_state.extend_from_slice(&self.rotations.to_be_bytes());
             secret_state.extend_from_slice(&block_height.to_be_bytes());
             Sha256Sum::from_data(&secret_state)
         };
 
+        let sk = Keychain::make_vrf_private_key(seed);
+        let pk = VRFPublicKey::from_private(&sk);
+
+        self.vrf_secret_keys.push(sk.clone());
+        self.vrf_map.insert(pk.clone(), sk);
+        pk
+    }
+
+    /// Set the number of burnchain blocks for which a key derived by `rotate_vrf_key()` is used.
+    pub fn set_vrf_rotation_period(&mut self, period: u64) {
+        assert!(period > 0, "VRF key rotation period must be positive");
+        self.vrf_rotation_period = period;
+    }
+
+    /// Derive the VRF key for the rotation window that contains `burn_block_height`.  The key
+    /// depends only on the secret state and the window, so a restarted node re-derives the same
+    /// key it registered for this window instead of having to register a new one.
+    pub fn rotate_vrf_key(&mut self, burn_block_height: u64) -> VRFPrivateKey {
+        let window = burn_block_height / self.vrf_rotation_period;
+        let seed = {
+            let mut secret_state = self.hashed_secret_state.to_bytes().to_vec();
+            secret_state.extend_from_slice(b"vrf-key-rotation");
+            secret_state.extend_from_slice(&window.to_be_bytes());
+            Sha256Sum::from_data(&secret_state)
+        };
+
+        let sk = Keychain::make_vrf_private_key(seed);
+        let pk = VRFPublicKey::from_private(&sk);
+
+        if !self.vrf_map.contains_key(&pk) {
+            self.vrf_secret_keys.push(sk.clone());
+            self.vrf_map.insert(pk, sk.clone());
+        }
+        sk
+    }
+
+    fn make_vrf_private_key(mut seed: Sha256Sum) -> VRFPrivateKey {
         // Not every 256-bit number is a valid Ed25519 secret key.
         // As such, we continuously generate seeds through re-hashing until one works.
-        let sk = loop {
+        loop {
             match VRFPrivateKey::from_bytes(seed.as_bytes()) {
                 Some(sk) => break sk,
                 None => seed = Sha256Sum::from_data(seed.as_bytes()),
             }
-        };
-        let pk = VRFPublicKey::from_private(&sk);
-
-        self.vrf_secret_keys.push(sk.clone());
-        self.vrf_map.insert(pk.clone(), sk);
-        pk
+        }
     }
 
     pub fn rotate_microblock_keypair(&mut self) -> StacksPrivateKey {
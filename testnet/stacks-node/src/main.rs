@@ -43,13 +43,30 @@ use std::env;
 use std::convert::TryInto;
 use std::panic;
 use std::process;
+use std::thread;
 
 use backtrace::Backtrace;
 
-fn main() {
-    panic::set_hook(Box::new(|_| {
-        eprintln!("Process abort due to thread panic");
+/// Install a panic hook that prints a backtrace, dumps core, and exits the process
+///  when any thread panics.  Only the `stacks-node` binary should call this; code
+///  that embeds the node or the coordinator in another process must not, since
+///  the hook kills the host process on a panic that it could otherwise recover from.
+///  Panics in supervised threads (see `run_loop::supervise_thread()`) are only logged,
+///  so that their supervisor can restart them.
+pub fn install_crash_handler() {
+    panic::set_hook(Box::new(|panic_info| {
         let bt = Backtrace::new();
+        if let Some(name) = thread::current().name() {
+            if name.starts_with(run_loop::SUPERVISED_THREAD_PREFIX) {
+                error!(
+                    "Supervised thread {} panicked: {}\n{:?}",
+                    name, panic_info, &bt
+                );
+                return;
+            }
+        }
+
+        eprintln!("Process abort due to thread panic");
         eprintln!("{:?}", &bt);
 
         // force a core dump
@@ -68,6 +85,10 @@ fn main() {
         // just in case
         process::exit(1);
     }));
+}
+
+fn main() {
+    install_crash_handler();
 
     let mut args = Arguments::from_env();
     let subcommand = args.subcommand().unwrap().unwrap_or_default();
@@ -81,6 +102,10 @@ fn main() {
             args.finish().unwrap();
             ConfigFile::helium()
         }
+        "regtest" => {
+            args.finish().unwrap();
+            ConfigFile::regtest()
+        }
         "neon" => {
             args.finish().unwrap();
             ConfigFile::neon()
@@ -120,6 +145,7 @@ fn main() {
     };
 
     let conf = Config::from_config_file(config_file);
+    conf.logging.install();
     debug!("node configuration {:?}", &conf.node);
     debug!("burnchain configuration {:?}", &conf.burnchain);
     debug!("connection configuration {:?}", &conf.connection_options);
@@ -169,6 +195,9 @@ helium\t\tStart a node based on a local setup relying on a local instance of bit
 \t\t  rpcuser=helium
 \t\t  rpcpassword=helium
 
+regtest\t\tStart a node on an isolated local chain, relying on a local instance of bitcoind (configured as for helium).
+\t\tThe node gets its own chain ID, a temporary working directory, and its own RPC and P2P ports.
+
 argon\t\tStart a node that will join and stream blocks from the public argon testnet, powered by Blockstack (Proof of Burn).
 
 krypton\t\tStart a node that will join and stream blocks from the public krypton testnet, powered by Blockstack via (Proof of Transfer).
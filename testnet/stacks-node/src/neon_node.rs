@@ -1,11 +1,14 @@
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain};
-use crate::config::HELIUM_BLOCK_LIMIT;
-use crate::run_loop::RegisteredKey;
+use crate::config::{MinerConfig, HELIUM_BLOCK_LIMIT};
+use crate::run_loop::{supervise_thread, RegisteredKey, SUPERVISED_THREAD_PREFIX};
 
+use std::cmp;
 use std::collections::VecDeque;
 use std::convert::{TryFrom, TryInto};
 use std::default::Default;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::{thread, thread::JoinHandle};
 
 use stacks::burnchains::{Burnchain, BurnchainHeaderHash, PublicKey, Txid};
@@ -30,7 +33,7 @@ use stacks::core::mempool::MemPoolDB;
 use stacks::net::{
     db::{LocalPeer, PeerDB},
     dns::DNSResolver,
-    p2p::PeerNetwork,
+    p2p::{NetworkHandle, PeerNetwork},
     relay::Relayer,
     rpc::RPCHandlerArgs,
     Error as NetError, NetworkResult, PeerAddress, StacksMessageCodec,
@@ -53,7 +56,6 @@ use stacks::vm::database::BurnStateDB;
 
 use stacks::monitoring::{increment_stx_blocks_mined_counter, update_active_miners_count_gauge};
 
-pub const TESTNET_CHAIN_ID: u32 = 0x80000000;
 pub const TESTNET_PEER_VERSION: u32 = 0xfacade01;
 pub const RELAYER_MAX_BUFFER: usize = 100;
 
@@ -70,6 +72,8 @@ enum RelayerDirective {
     ProcessTenure(ConsensusHash, BurnchainHeaderHash, BlockHeaderHash),
     RunTenure(RegisteredKey, BlockSnapshot),
     RegisterKey(BlockSnapshot),
+    /// The p2p thread was restarted; talk to its new network handle from now on
+    SetNetworkHandle(NetworkHandle),
 }
 
 pub struct InitializedNeonNode {
@@ -79,6 +83,7 @@ pub struct InitializedNeonNode {
     active_keys: Vec<RegisteredKey>,
     sleep_before_tenure: u64,
     is_miner: bool,
+    burnchain_tip_height: Arc<AtomicU64>,
 }
 
 pub struct NeonGenesisNode {
@@ -101,6 +106,102 @@ fn bump_processed_counter(blocks_processed: &BlocksProcessedCounter) {
 #[cfg(not(test))]
 fn bump_processed_counter(_blocks_processed: &BlocksProcessedCounter) {}
 
+/// Keeps the miner from spending burnchain tokens on tenures while the node's sortitions lag
+///  the burnchain tip (i.e. while it would be mining on a stale chain).
+pub struct MiningSyncGate {
+    wait_for_sync: bool,
+    max_sync_lag: u64,
+    paused: bool,
+}
+
+impl MiningSyncGate {
+    pub fn new(miner_config: &MinerConfig) -> MiningSyncGate {
+        MiningSyncGate {
+            wait_for_sync: miner_config.wait_for_sync,
+            max_sync_lag: miner_config.max_sync_lag,
+            paused: false,
+        }
+    }
+
+    /// Can the miner attempt a tenure, given the height of the last processed sortition and the
+    ///  height of the burnchain tip?
+    pub fn check(&mut self, burn_block_height: u64, burnchain_tip_height: u64) -> bool {
+        if !self.wait_for_sync {
+            return true;
+        }
+
+        let lag = burnchain_tip_height.saturating_sub(burn_block_height);
+        if lag > self.max_sync_lag {
+            if !self.paused {
+                info!(
+                    "Pausing mining: sortitions at height {} lag the burnchain tip at height {}",
+                    burn_block_height, burnchain_tip_height
+                );
+                self.paused = true;
+            }
+        } else if self.paused {
+            info!(
+                "Resuming mining: sortitions caught up to height {}",
+                burn_block_height
+            );
+            self.paused = false;
+        }
+        !self.paused
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+/// Picks the burn amount for the miner's block commits.  Without an escalation schedule, every
+///  commit burns the burnchain's `burn_fee_cap`.  With one, each consecutive missed sortition
+///  moves one entry further down the schedule (stopping at the last), and a win resets it.
+///  Schedule entries above `burn_fee_cap` burn `burn_fee_cap`.
+pub struct BurnFeeEscalation {
+    burn_fee_cap: u64,
+    schedule: Option<Vec<u64>>,
+    missed_sortitions: usize,
+}
+
+impl BurnFeeEscalation {
+    pub fn new(burn_fee_cap: u64, miner_config: &MinerConfig) -> BurnFeeEscalation {
+        BurnFeeEscalation {
+            burn_fee_cap,
+            schedule: miner_config.burn_fee_escalation.clone(),
+            missed_sortitions: 0,
+        }
+    }
+
+    /// How much to burn on the next block commit
+    pub fn burn_fee(&self) -> u64 {
+        match self.schedule {
+            Some(ref schedule) if schedule.len() > 0 => cmp::min(
+                schedule[cmp::min(self.missed_sortitions, schedule.len() - 1)],
+                self.burn_fee_cap,
+            ),
+            _ => self.burn_fee_cap,
+        }
+    }
+
+    /// Our block commit won the sortition
+    pub fn record_won(&mut self) {
+        self.missed_sortitions = 0;
+    }
+
+    /// Our block commit lost the sortition
+    pub fn record_missed(&mut self) {
+        self.missed_sortitions = self.missed_sortitions.saturating_add(1);
+        if self.schedule.is_some() {
+            info!(
+                "Missed {} consecutive sortitions, next block commit burns {}",
+                self.missed_sortitions,
+                self.burn_fee()
+            );
+        }
+    }
+}
+
 /// Process artifacts from the tenure.
 /// At this point, we're modifying the chainstate, and merging the artifacts from the previous tenure.
 fn inner_process_tenure(
@@ -143,7 +244,11 @@ fn inner_process_tenure(
     Ok(true)
 }
 
-fn inner_generate_coinbase_tx(keychain: &mut Keychain, nonce: u64) -> StacksTransaction {
+fn inner_generate_coinbase_tx(
+    keychain: &mut Keychain,
+    nonce: u64,
+    chain_id: u32,
+) -> StacksTransaction {
     let mut tx_auth = keychain.get_transaction_auth().unwrap();
     tx_auth.set_origin_nonce(nonce);
 
@@ -152,7 +257,7 @@ fn inner_generate_coinbase_tx(keychain: &mut Keychain, nonce: u64) -> StacksTran
         tx_auth,
         TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
     );
-    tx.chain_id = TESTNET_CHAIN_ID;
+    tx.chain_id = chain_id;
     tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
     let mut tx_signer = StacksTransactionSigner::new(&tx);
     keychain.sign_as_origin(&mut tx_signer);
@@ -246,103 +351,108 @@ fn spawn_peer(
     let block_limit = config.block_limit;
     let exit_at_block_height = config.burnchain.process_exit_at_block_height;
 
-    this.bind(p2p_sock, rpc_sock).unwrap();
+    this.bind(p2p_sock, rpc_sock)?;
     let (mut dns_resolver, mut dns_client) = DNSResolver::new(10);
     let sortdb = SortitionDB::open(&burn_db_path, false).map_err(NetError::DBError)?;
 
     let (mut chainstate, _) = StacksChainState::open_with_block_limit(
         false,
-        TESTNET_CHAIN_ID,
+        config.node.chain_id,
         &stacks_chainstate_path,
         block_limit,
     )
     .map_err(|e| NetError::ChainstateError(e.to_string()))?;
 
-    let mut mem_pool = MemPoolDB::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path)
+    let mut mem_pool = MemPoolDB::open(false, config.node.chain_id, &stacks_chainstate_path)
         .map_err(NetError::DBError)?;
+    mem_pool.set_replace_fee_bump_pct(config.node.mempool_replace_fee_bump_pct);
+    mem_pool.set_size_limits(config.mempool.max_txs, config.mempool.max_bytes);
 
     // buffer up blocks to store without stalling the p2p thread
     let mut results_with_data = VecDeque::new();
 
-    let server_thread = thread::spawn(move || {
-        let handler_args = RPCHandlerArgs {
-            exit_at_block_height: exit_at_block_height.as_ref(),
-            ..RPCHandlerArgs::default()
-        };
+    let server_thread = thread::Builder::new()
+        .name(format!("{}p2p", SUPERVISED_THREAD_PREFIX))
+        .spawn(move || {
+            let handler_args = RPCHandlerArgs {
+                exit_at_block_height: exit_at_block_height.as_ref(),
+                ..RPCHandlerArgs::default()
+            };
 
-        let mut disconnected = false;
-        while !disconnected {
-            let download_backpressure = results_with_data.len() > 0;
-            let poll_ms = if !download_backpressure && this.has_more_downloads() {
-                // keep getting those blocks -- drive the downloader state-machine
-                debug!(
-                    "P2P: backpressure: {}, more downloads: {}",
-                    download_backpressure,
-                    this.has_more_downloads()
+            let mut disconnected = false;
+            while !disconnected {
+                let download_backpressure = results_with_data.len() > 0;
+                let poll_ms = if !download_backpressure && this.has_more_downloads() {
+                    // keep getting those blocks -- drive the downloader state-machine
+                    debug!(
+                        "P2P: backpressure: {}, more downloads: {}",
+                        download_backpressure,
+                        this.has_more_downloads()
+                    );
+                    100
+                } else {
+                    poll_timeout
+                };
+
+                // update p2p's read-only view of the unconfirmed state
+                let (canonical_consensus_tip, canonical_block_tip) =
+                    SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())
+                        .expect("Failed to read canonical stacks chain tip");
+                let canonical_tip = StacksBlockHeader::make_index_block_hash(
+                    &canonical_consensus_tip,
+                    &canonical_block_tip,
                 );
-                100
-            } else {
-                poll_timeout
-            };
+                chainstate
+                    .refresh_unconfirmed_state_readonly(canonical_tip)
+                    .expect("Failed to open unconfirmed Clarity state");
+
+                let network_result = match this.run(
+                    &sortdb,
+                    &mut chainstate,
+                    &mut mem_pool,
+                    Some(&mut dns_client),
+                    download_backpressure,
+                    poll_ms,
+                    &handler_args,
+                ) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        error!("P2P: Failed to process network dispatch: {:?}", &e);
+                        panic!();
+                    }
+                };
 
-            // update p2p's read-only view of the unconfirmed state
-            let (canonical_consensus_tip, canonical_block_tip) =
-                SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())
-                    .expect("Failed to read canonical stacks chain tip");
-            let canonical_tip = StacksBlockHeader::make_index_block_hash(
-                &canonical_consensus_tip,
-                &canonical_block_tip,
-            );
-            chainstate
-                .refresh_unconfirmed_state_readonly(canonical_tip)
-                .expect("Failed to open unconfirmed Clarity state");
-
-            let network_result = match this.run(
-                &sortdb,
-                &mut chainstate,
-                &mut mem_pool,
-                Some(&mut dns_client),
-                download_backpressure,
-                poll_ms,
-                &handler_args,
-            ) {
-                Ok(res) => res,
-                Err(e) => {
-                    error!("P2P: Failed to process network dispatch: {:?}", &e);
-                    panic!();
+                if network_result.has_data_to_store() {
+                    results_with_data.push_back(RelayerDirective::HandleNetResult(network_result));
                 }
-            };
 
-            if network_result.has_data_to_store() {
-                results_with_data.push_back(RelayerDirective::HandleNetResult(network_result));
-            }
-
-            while let Some(next_result) = results_with_data.pop_front() {
-                // have blocks, microblocks, and/or transactions (don't care about anything else),
-                if let Err(e) = relay_channel.try_send(next_result) {
-                    debug!(
-                        "P2P: {:?}: download backpressure detected",
-                        &this.local_peer
-                    );
-                    match e {
-                        TrySendError::Full(directive) => {
-                            // don't lose this data -- just try it again
-                            results_with_data.push_front(directive);
-                            break;
-                        }
-                        TrySendError::Disconnected(_) => {
-                            info!("P2P: Relayer hang up with p2p channel");
-                            disconnected = true;
-                            break;
+                while let Some(next_result) = results_with_data.pop_front() {
+                    // have blocks, microblocks, and/or transactions (don't care about anything else),
+                    if let Err(e) = relay_channel.try_send(next_result) {
+                        debug!(
+                            "P2P: {:?}: download backpressure detected",
+                            &this.local_peer
+                        );
+                        match e {
+                            TrySendError::Full(directive) => {
+                                // don't lose this data -- just try it again
+                                results_with_data.push_front(directive);
+                                break;
+                            }
+                            TrySendError::Disconnected(_) => {
+                                info!("P2P: Relayer hang up with p2p channel");
+                                disconnected = true;
+                                break;
+                            }
                         }
+                    } else {
+                        debug!("P2P: Dispatched result to Relayer!");
                     }
-                } else {
-                    debug!("P2P: Dispatched result to Relayer!");
                 }
             }
-        }
-        debug!("P2P thread exit!");
-    });
+            debug!("P2P thread exit!");
+        })
+        .expect("Failed to spawn p2p thread");
 
     let _jh = thread::spawn(move || {
         dns_resolver.thread_main();
@@ -363,6 +473,7 @@ fn spawn_miner_relayer(
     blocks_processed: BlocksProcessedCounter,
     burnchain: Burnchain,
     coord_comms: CoordinatorChannels,
+    burnchain_tip_height: Arc<AtomicU64>,
 ) -> Result<(), NetError> {
     // Note: the relayer is *the* block processor, it is responsible for writes to the chainstate --
     //   no other codepaths should be writing once this is spawned.
@@ -374,20 +485,22 @@ fn spawn_miner_relayer(
 
     let (mut chainstate, _) = StacksChainState::open_with_block_limit(
         false,
-        TESTNET_CHAIN_ID,
+        config.node.chain_id,
         &stacks_chainstate_path,
         config.block_limit.clone(),
     )
     .map_err(|e| NetError::ChainstateError(e.to_string()))?;
 
-    let mut mem_pool = MemPoolDB::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path)
+    let mut mem_pool = MemPoolDB::open(false, config.node.chain_id, &stacks_chainstate_path)
         .map_err(NetError::DBError)?;
 
     let mut last_mined_block: Option<AssembledAnchorBlock> = None;
-    let burn_fee_cap = config.burnchain.burn_fee_cap;
-    let mine_microblocks = config.node.mine_microblocks;
+    let mut burn_fee_escalation =
+        BurnFeeEscalation::new(config.burnchain.burn_fee_cap, &config.miner);
+    let mine_microblocks = config.node.mine_microblocks && !config.miner.disable_microblocks;
 
     let mut bitcoin_controller = BitcoinRegtestController::new_dummy(config.clone());
+    let mut sync_gate = MiningSyncGate::new(&config.miner);
 
     let _relayer_handle = thread::spawn(move || {
         while let Ok(mut directive) = relay_channel.recv() {
@@ -430,6 +543,7 @@ fn spawn_miner_relayer(
                             );
 
                             increment_stx_blocks_mined_counter();
+                            burn_fee_escalation.record_won();
 
                             match inner_process_tenure(
                                 &mined_block,
@@ -549,6 +663,7 @@ fn spawn_miner_relayer(
                                 }
                             }
                         } else {
+                            burn_fee_escalation.record_missed();
                             warn!("Did not win sortition, my blocks [burn_hash= {}, block_hash= {}], their blocks [parent_consenus_hash= {}, burn_hash= {}, block_hash ={}]",
                                   mined_burn_hash, mined_block.block_hash(), parent_consensus_hash, burn_hash, block_header_hash);
                         }
@@ -556,6 +671,10 @@ fn spawn_miner_relayer(
                 }
                 RelayerDirective::RunTenure(registered_key, last_burn_block) => {
                     debug!("Relayer: Run tenure");
+                    if !relayer_synced(&sortdb, &burnchain_tip_height, &mut sync_gate) {
+                        bump_processed_counter(&blocks_processed);
+                        continue;
+                    }
                     last_mined_block = InitializedNeonNode::relayer_run_tenure(
                         &config,
                         registered_key,
@@ -565,13 +684,17 @@ fn spawn_miner_relayer(
                         last_burn_block,
                         &mut keychain,
                         &mut mem_pool,
-                        burn_fee_cap,
+                        burn_fee_escalation.burn_fee(),
                         &mut bitcoin_controller,
                     );
                     bump_processed_counter(&blocks_processed);
                 }
                 RelayerDirective::RegisterKey(ref last_burn_block) => {
                     debug!("Relayer: Register key");
+                    if !relayer_synced(&sortdb, &burnchain_tip_height, &mut sync_gate) {
+                        bump_processed_counter(&blocks_processed);
+                        continue;
+                    }
                     rotate_vrf_and_register(
                         &mut keychain,
                         last_burn_block,
@@ -579,6 +702,10 @@ fn spawn_miner_relayer(
                     );
                     bump_processed_counter(&blocks_processed);
                 }
+                RelayerDirective::SetNetworkHandle(handle) => {
+                    debug!("Relayer: Set network handle");
+                    relayer.set_network_handle(handle);
+                }
             }
         }
         debug!("Relayer exit!");
@@ -587,6 +714,98 @@ fn spawn_miner_relayer(
     Ok(())
 }
 
+/// Check the relayer's view of the sortitions against the burnchain tip
+fn relayer_synced(
+    sortdb: &SortitionDB,
+    burnchain_tip_height: &Arc<AtomicU64>,
+    sync_gate: &mut MiningSyncGate,
+) -> bool {
+    let burn_block_height = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+        .expect("FATAL: failed to query sortition DB")
+        .block_height;
+    sync_gate.check(
+        burn_block_height,
+        burnchain_tip_height.load(Ordering::SeqCst),
+    )
+}
+
+/// Open the peer DB and instantiate a p2p network object on it, at the sortition DB's current
+/// chain view.  Called once at startup, and again each time the p2p thread is restarted.
+fn make_peer_network(config: &Config, burnchain: &Burnchain) -> PeerNetwork {
+    // we can call _open_ here rather than _connect_, since connect is first called in
+    //   make_genesis_block
+    let sortdb = SortitionDB::open(&config.get_burn_db_file_path(), false)
+        .expect("Error while instantiating sortition db");
+
+    let view = {
+        let ic = sortdb.index_conn();
+        let sortition_tip =
+            SortitionDB::get_canonical_burn_chain_tip(&ic).expect("Failed to get sortition tip");
+        ic.get_burnchain_view(burnchain, &sortition_tip).unwrap()
+    };
+
+    // create a new peerdb
+    let data_url = UrlString::try_from(format!("{}", &config.node.data_url)).unwrap();
+    let mut initial_neighbors = vec![];
+    if let Some(ref bootstrap_node) = &config.node.bootstrap_node {
+        initial_neighbors.push(bootstrap_node.clone());
+    }
+
+    println!("BOOTSTRAP WITH {:?}", initial_neighbors);
+
+    let (p2p_sock, _) = config
+        .node
+        .get_bind_addrs()
+        .expect("Failed to parse bind addresses");
+    let p2p_addr: SocketAddr = config.node.p2p_address.parse().expect(&format!(
+        "Failed to parse socket: {}",
+        &config.node.p2p_address
+    ));
+    let node_privkey = {
+        let mut re_hashed_seed = config.node.local_peer_seed.clone();
+        let my_private_key = loop {
+            match Secp256k1PrivateKey::from_slice(&re_hashed_seed[..]) {
+                Ok(sk) => break sk,
+                Err(_) => {
+                    re_hashed_seed = Sha256Sum::from_data(&re_hashed_seed[..])
+                        .as_bytes()
+                        .to_vec()
+                }
+            }
+        };
+        my_private_key
+    };
+
+    let peerdb = PeerDB::connect(
+        &config.get_peer_db_path(),
+        true,
+        config.node.chain_id,
+        burnchain.network_id,
+        Some(node_privkey),
+        config.connection_options.private_key_lifetime.clone(),
+        PeerAddress::from_socketaddr(&p2p_addr),
+        p2p_sock.port(),
+        data_url.clone(),
+        &vec![],
+        Some(&initial_neighbors),
+    )
+    .unwrap();
+
+    let local_peer = match PeerDB::get_local_peer(peerdb.conn()) {
+        Ok(local_peer) => local_peer,
+        _ => panic!("Unable to retrieve local peer"),
+    };
+
+    PeerNetwork::new(
+        peerdb,
+        local_peer,
+        TESTNET_PEER_VERSION,
+        burnchain.clone(),
+        view,
+        config.connection_options.clone(),
+    )
+}
+
 impl InitializedNeonNode {
     fn new(
         config: Config,
@@ -597,11 +816,6 @@ impl InitializedNeonNode {
         blocks_processed: BlocksProcessedCounter,
         coord_comms: CoordinatorChannels,
     ) -> InitializedNeonNode {
-        // we can call _open_ here rather than _connect_, since connect is first called in
-        //   make_genesis_block
-        let sortdb = SortitionDB::open(&config.get_burn_db_file_path(), false)
-            .expect("Error while instantiating sortition db");
-
         let burnchain = Burnchain::new(
             &config.get_burn_db_path(),
             &config.burnchain.chain,
@@ -609,86 +823,27 @@ impl InitializedNeonNode {
         )
         .expect("Error while instantiating burnchain");
 
-        let view = {
-            let ic = sortdb.index_conn();
-            let sortition_tip = SortitionDB::get_canonical_burn_chain_tip(&ic)
-                .expect("Failed to get sortition tip");
-            ic.get_burnchain_view(&burnchain, &sortition_tip).unwrap()
-        };
-
-        // create a new peerdb
-        let data_url = UrlString::try_from(format!("{}", &config.node.data_url)).unwrap();
-        let mut initial_neighbors = vec![];
-        if let Some(ref bootstrap_node) = &config.node.bootstrap_node {
-            initial_neighbors.push(bootstrap_node.clone());
-        }
-
-        println!("BOOTSTRAP WITH {:?}", initial_neighbors);
-
-        let p2p_sock: SocketAddr = config.node.p2p_bind.parse().expect(&format!(
-            "Failed to parse socket: {}",
-            &config.node.p2p_bind
-        ));
-        let rpc_sock = config.node.rpc_bind.parse().expect(&format!(
-            "Failed to parse socket: {}",
-            &config.node.rpc_bind
-        ));
-        let p2p_addr: SocketAddr = config.node.p2p_address.parse().expect(&format!(
-            "Failed to parse socket: {}",
-            &config.node.p2p_address
-        ));
-        let node_privkey = {
-            let mut re_hashed_seed = config.node.local_peer_seed.clone();
-            let my_private_key = loop {
-                match Secp256k1PrivateKey::from_slice(&re_hashed_seed[..]) {
-                    Ok(sk) => break sk,
-                    Err(_) => {
-                        re_hashed_seed = Sha256Sum::from_data(&re_hashed_seed[..])
-                            .as_bytes()
-                            .to_vec()
-                    }
-                }
-            };
-            my_private_key
-        };
-
-        let peerdb = PeerDB::connect(
-            &config.get_peer_db_path(),
-            true,
-            TESTNET_CHAIN_ID,
-            burnchain.network_id,
-            Some(node_privkey),
-            config.connection_options.private_key_lifetime.clone(),
-            PeerAddress::from_socketaddr(&p2p_addr),
-            p2p_sock.port(),
-            data_url.clone(),
-            &vec![],
-            Some(&initial_neighbors),
-        )
-        .unwrap();
-
-        let local_peer = match PeerDB::get_local_peer(peerdb.conn()) {
-            Ok(local_peer) => local_peer,
-            _ => panic!("Unable to retrieve local peer"),
-        };
+        let (p2p_sock, rpc_sock) = config
+            .node
+            .get_bind_addrs()
+            .expect("Failed to parse bind addresses");
 
         // now we're ready to instantiate a p2p network object, the relayer, and the event dispatcher
-        let mut p2p_net = PeerNetwork::new(
-            peerdb,
-            local_peer.clone(),
-            TESTNET_PEER_VERSION,
-            burnchain.clone(),
-            view,
-            config.connection_options.clone(),
-        );
+        let mut p2p_net = make_peer_network(&config, &burnchain);
+        let local_peer = p2p_net.local_peer.clone();
 
         // setup the relayer channel
         let (relay_send, relay_recv) = sync_channel(RELAYER_MAX_BUFFER);
 
         let burnchain_signer = keychain.get_burnchain_signer();
-        let relayer = Relayer::from_p2p(&mut p2p_net);
+        let mut relayer = Relayer::from_p2p(&mut p2p_net);
+        if config.miner.disable_microblocks {
+            info!("Microblocks are disabled; mining and relaying anchored blocks only");
+            relayer.set_relay_microblocks(false);
+        }
 
         let sleep_before_tenure = config.node.wait_time_for_microblocks;
+        let burnchain_tip_height = Arc::new(AtomicU64::new(0));
 
         spawn_miner_relayer(
             relayer,
@@ -700,20 +855,40 @@ impl InitializedNeonNode {
             relay_recv,
             event_dispatcher,
             blocks_processed.clone(),
-            burnchain,
+            burnchain.clone(),
             coord_comms,
+            burnchain_tip_height.clone(),
         )
         .expect("Failed to initialize mine/relay thread");
 
-        spawn_peer(
-            p2p_net,
-            &p2p_sock,
-            &rpc_sock,
-            config.clone(),
-            5000,
-            relay_send.clone(),
-        )
-        .expect("Failed to initialize mine/relay thread");
+        // if the p2p thread panics, rebuild the p2p network from the peer DB and restart it.
+        //   The relayer gets a handle to the new network; the coordinator and chainstate are
+        //   untouched.
+        let p2p_config = config.clone();
+        let p2p_relay_send = relay_send.clone();
+        let mut first_p2p_net = Some(p2p_net);
+        supervise_thread("p2p", config.node.p2p_max_restarts, move || {
+            let p2p_net = match first_p2p_net.take() {
+                Some(p2p_net) => p2p_net,
+                None => {
+                    let mut p2p_net = make_peer_network(&p2p_config, &burnchain);
+                    let handle = p2p_net.new_handle(1024);
+                    p2p_relay_send
+                        .send(RelayerDirective::SetNetworkHandle(handle))
+                        .map_err(|e| NetError::SendError(e.to_string()))?;
+                    p2p_net
+                }
+            };
+            spawn_peer(
+                p2p_net,
+                &p2p_sock,
+                &rpc_sock,
+                p2p_config.clone(),
+                5000,
+                p2p_relay_send.clone(),
+            )
+        })
+        .expect("Failed to initialize p2p thread");
 
         info!("Bound HTTP server on: {}", &config.node.rpc_bind);
         info!("Bound P2P server on: {}", &config.node.p2p_bind);
@@ -731,9 +906,15 @@ impl InitializedNeonNode {
             is_miner,
             sleep_before_tenure,
             active_keys,
+            burnchain_tip_height,
         }
     }
 
+    /// Tell the relayer how tall the burnchain is, so it can tell when it's behind.
+    pub fn set_burnchain_tip_height(&self, height: u64) {
+        self.burnchain_tip_height.store(height, Ordering::SeqCst);
+    }
+
     /// Tell the relayer to fire off a tenure and a block commit op.
     pub fn relayer_issue_tenure(&mut self) -> bool {
         if !self.is_miner {
@@ -831,7 +1012,7 @@ impl InitializedNeonNode {
         burn_block: BlockSnapshot,
         keychain: &mut Keychain,
         mem_pool: &mut MemPoolDB,
-        burn_fee_cap: u64,
+        burn_fee: u64,
         bitcoin_controller: &mut BitcoinRegtestController,
     ) -> Option<AssembledAnchorBlock> {
         // Generates a proof out of the sortition hash provided in the params.
@@ -963,10 +1144,11 @@ impl InitializedNeonNode {
             )
         };
 
-        let coinbase_tx = inner_generate_coinbase_tx(keychain, coinbase_nonce);
+        let coinbase_tx =
+            inner_generate_coinbase_tx(keychain, coinbase_nonce, config.node.chain_id);
 
         let (anchored_block, consumed_execution, bytes_so_far) =
-            match StacksBlockBuilder::build_anchored_block(
+            match StacksBlockBuilder::build_anchored_block_with_txs(
                 chain_state,
                 &burn_db.index_conn(),
                 mem_pool,
@@ -975,6 +1157,7 @@ impl InitializedNeonNode {
                 vrf_proof.clone(),
                 mblock_pubkey_hash,
                 &coinbase_tx,
+                &config.miner.must_include,
                 HELIUM_BLOCK_LIMIT.clone(),
             ) {
                 Ok(block) => block,
@@ -1013,7 +1196,7 @@ impl InitializedNeonNode {
         let op = inner_generate_block_commit_op(
             keychain.get_burnchain_signer(),
             anchored_block.block_hash(),
-            burn_fee_cap,
+            burn_fee,
             &registered_key,
             parent_block_burn_height
                 .try_into()
@@ -1131,7 +1314,7 @@ impl NeonGenesisNode {
         // do the initial open!
         let (_chain_state, receipts) = match StacksChainState::open_and_exec(
             false,
-            TESTNET_CHAIN_ID,
+            config.node.chain_id,
             &config.get_chainstate_path(),
             Some(initial_balances),
             boot_block_exec,
@@ -1,5 +1,5 @@
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain, Tenure};
-use crate::run_loop::RegisteredKey;
+use crate::run_loop::{NodeHealth, NodeHealthHandle, RegisteredKey};
 
 use std::convert::TryFrom;
 use std::default::Default;
@@ -32,6 +32,7 @@ use stacks::util::vrf::VRFPublicKey;
 use stacks::chainstate::stacks::index::TrieHash;
 
 pub const TESTNET_CHAIN_ID: u32 = 0x80000000;
+pub const REGTEST_CHAIN_ID: u32 = 0x80000100;
 pub const TESTNET_PEER_VERSION: u32 = 0xfacade01;
 
 #[derive(Debug, Clone)]
@@ -66,6 +67,7 @@ pub struct Node {
     last_sortitioned_block: Option<BurnchainTip>,
     event_dispatcher: EventDispatcher,
     nonce: u64,
+    health: NodeHealthHandle,
 }
 
 fn spawn_peer(
@@ -75,8 +77,10 @@ fn spawn_peer(
     burn_db_path: String,
     stacks_chainstate_path: String,
     event_dispatcher: EventDispatcher,
+    chain_id: u32,
     exit_at_block_height: Option<u64>,
     poll_timeout: u64,
+    health: NodeHealthHandle,
 ) -> Result<JoinHandle<()>, NetError> {
     this.bind(p2p_sock, rpc_sock).unwrap();
     let server_thread = thread::spawn(move || {
@@ -95,7 +99,7 @@ fn spawn_peer(
                 }
             };
             let (mut chainstate, _) =
-                match StacksChainState::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path) {
+                match StacksChainState::open(false, chain_id, &stacks_chainstate_path) {
                     Ok(x) => x,
                     Err(e) => {
                         warn!("Error while connecting chainstate db in peer loop: {}", e);
@@ -104,15 +108,14 @@ fn spawn_peer(
                     }
                 };
 
-            let mut mem_pool =
-                match MemPoolDB::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path) {
-                    Ok(x) => x,
-                    Err(e) => {
-                        warn!("Error while connecting to mempool db in peer loop: {}", e);
-                        thread::sleep(time::Duration::from_secs(1));
-                        continue;
-                    }
-                };
+            let mut mem_pool = match MemPoolDB::open(false, chain_id, &stacks_chainstate_path) {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("Error while connecting to mempool db in peer loop: {}", e);
+                    thread::sleep(time::Duration::from_secs(1));
+                    continue;
+                }
+            };
 
             let net_result = this
                 .run(
@@ -128,6 +131,9 @@ fn spawn_peer(
             if net_result.has_transactions() {
                 event_dispatcher.process_new_mempool_txs(net_result.transactions())
             }
+
+            health.set_chain_view_height(this.chain_view.burn_block_height);
+            health.set_num_peers(this.num_peers() as u64);
         }
     });
     Ok(server_thread)
@@ -149,7 +155,7 @@ impl Node {
 
         let chain_state_result = StacksChainState::open_and_exec(
             false,
-            TESTNET_CHAIN_ID,
+            config.node.chain_id,
             &config.get_chainstate_path(),
             Some(initial_balances),
             boot_block_exec,
@@ -164,14 +170,12 @@ impl Node {
                 err
             ),
         };
-        let mut event_dispatcher = EventDispatcher::new();
-
-        for observer in &config.events_observers {
-            event_dispatcher.register_observer(observer);
-        }
+        let mut event_dispatcher = EventDispatcher::from_config(&config);
 
         event_dispatcher.process_boot_receipts(receipts);
 
+        let health = NodeHealthHandle::new(config.miner.max_sync_lag);
+
         Self {
             active_registered_key: None,
             bootstraping_chain: false,
@@ -182,6 +186,7 @@ impl Node {
             config,
             burnchain_tip: None,
             nonce: 0,
+            health,
             event_dispatcher,
         }
     }
@@ -194,21 +199,19 @@ impl Node {
 
         let keychain = Keychain::default(config.node.seed.clone());
 
-        let mut event_dispatcher = EventDispatcher::new();
-
-        for observer in &config.events_observers {
-            event_dispatcher.register_observer(observer);
-        }
+        let event_dispatcher = EventDispatcher::from_config(&config);
 
         let chainstate_path = config.get_chainstate_path();
         let sortdb_path = config.get_burn_db_file_path();
 
         let (chain_state, _) =
-            match StacksChainState::open(false, TESTNET_CHAIN_ID, &chainstate_path) {
+            match StacksChainState::open(false, config.node.chain_id, &chainstate_path) {
                 Ok(x) => x,
                 Err(_e) => panic!(),
             };
 
+        let health = NodeHealthHandle::new(config.miner.max_sync_lag);
+
         let mut node = Node {
             active_registered_key: None,
             bootstraping_chain: false,
@@ -219,6 +222,7 @@ impl Node {
             config,
             burnchain_tip: None,
             nonce: 0,
+            health,
             event_dispatcher,
         };
 
@@ -275,14 +279,11 @@ impl Node {
 
         println!("BOOTSTRAP WITH {:?}", initial_neighbors);
 
-        let rpc_sock: SocketAddr = self.config.node.rpc_bind.parse().expect(&format!(
-            "Failed to parse socket: {}",
-            &self.config.node.rpc_bind
-        ));
-        let p2p_sock: SocketAddr = self.config.node.p2p_bind.parse().expect(&format!(
-            "Failed to parse socket: {}",
-            &self.config.node.p2p_bind
-        ));
+        let (p2p_sock, rpc_sock) = self
+            .config
+            .node
+            .get_bind_addrs()
+            .expect("Failed to parse bind addresses");
         let p2p_addr: SocketAddr = self.config.node.p2p_address.parse().expect(&format!(
             "Failed to parse socket: {}",
             &self.config.node.p2p_address
@@ -305,7 +306,7 @@ impl Node {
         let peerdb = PeerDB::connect(
             &self.config.get_peer_db_path(),
             true,
-            TESTNET_CHAIN_ID,
+            self.config.node.chain_id,
             burnchain.network_id,
             Some(node_privkey),
             self.config.connection_options.private_key_lifetime.clone(),
@@ -340,8 +341,10 @@ impl Node {
             self.config.get_burn_db_file_path(),
             self.config.get_chainstate_path(),
             event_dispatcher,
+            self.config.node.chain_id,
             exit_at_block_height,
             1000,
+            self.health.clone(),
         )
         .unwrap();
 
@@ -412,6 +415,11 @@ impl Node {
         // Keep a pointer of the burnchain's chain tip.
         self.burnchain_tip = Some(burnchain_tip.clone());
 
+        self.health
+            .set_burnchain_tip_height(burnchain_tip.block_snapshot.block_height);
+        self.health
+            .set_miner_active(self.active_registered_key.is_some());
+
         (self.last_sortitioned_block.clone(), won_sortition)
     }
 
@@ -469,8 +477,12 @@ impl Node {
             },
         };
 
-        let mem_pool = MemPoolDB::open(false, TESTNET_CHAIN_ID, &self.chain_state.root_path)
-            .expect("FATAL: failed to open mempool");
+        let mem_pool = MemPoolDB::open(
+            false,
+            self.config.node.chain_id,
+            &self.chain_state.root_path,
+        )
+        .expect("FATAL: failed to open mempool");
 
         // Construct the coinbase transaction - 1st txn that should be handled and included in
         // the upcoming tenure.
@@ -533,7 +545,7 @@ impl Node {
         microblocks: Vec<StacksMicroblock>,
         db: &mut SortitionDB,
     ) -> ChainTip {
-        let parent_consensus_hash = {
+        {
             // look up parent consensus hash
             let ic = db.index_conn();
             let parent_consensus_hash = StacksChainState::get_parent_consensus_hash(
@@ -580,10 +592,27 @@ impl Node {
                     );
                 }
             }
+        }
 
-            parent_consensus_hash
-        };
+        // todo(ludo): yikes but good enough in the context of helium:
+        // we only expect 1 block.
+        let chain_tip = self
+            .process_staged_blocks(db)
+            .into_iter()
+            .next()
+            .expect("BUG: processed the tenure, but did not reach a new chain tip");
+
+        // Unset the `bootstraping_chain` flag.
+        if self.bootstraping_chain {
+            self.bootstraping_chain = false;
+        }
 
+        chain_tip
+    }
+
+    /// Process every staged Stacks block that is ready, and announce each one to the event
+    /// observers.  Returns the chain tips reached, in the order they were processed.
+    pub fn process_staged_blocks(&mut self, db: &mut SortitionDB) -> Vec<ChainTip> {
         let mut processed_blocks = vec![];
         loop {
             let mut process_blocks_at_tip = {
@@ -602,45 +631,73 @@ impl Node {
             }
         }
 
-        // todo(ludo): yikes but good enough in the context of helium:
-        // we only expect 1 block.
-        let processed_block = processed_blocks[0].clone().0.unwrap();
-
-        // Handle events
-        let receipts = processed_block.tx_receipts;
-        let metadata = processed_block.header;
-        let block: StacksBlock = {
-            let block_path = StacksChainState::get_block_path(
-                &self.chain_state.blocks_path,
+        let mut chain_tips = vec![];
+        for (processed_block, _) in processed_blocks.into_iter() {
+            let processed_block = match processed_block {
+                Some(processed_block) => processed_block,
+                None => continue,
+            };
+
+            // Handle events
+            let receipts = processed_block.tx_receipts;
+            let metadata = processed_block.header;
+            let block: StacksBlock = {
+                let block_path = StacksChainState::get_block_path(
+                    &self.chain_state.blocks_path,
+                    &metadata.consensus_hash,
+                    &metadata.anchored_header.block_hash(),
+                )
+                .unwrap();
+                StacksChainState::consensus_load(&block_path).unwrap()
+            };
+
+            let parent_consensus_hash = StacksChainState::get_parent_consensus_hash(
+                &db.index_conn(),
+                &block.header.parent_block,
                 &metadata.consensus_hash,
-                &metadata.anchored_header.block_hash(),
             )
-            .unwrap();
-            StacksChainState::consensus_load(&block_path).unwrap()
-        };
+            .expect(&format!(
+                "BUG: could not query chainstate to find parent consensus hash of {}/{}",
+                &metadata.consensus_hash,
+                &block.block_hash()
+            ))
+            .expect(&format!(
+                "BUG: no such parent of block {}/{}",
+                &metadata.consensus_hash,
+                &block.block_hash()
+            ));
 
-        let parent_index_hash = StacksBlockHeader::make_index_block_hash(
-            &parent_consensus_hash,
-            &block.header.parent_block,
-        );
+            let parent_index_hash = StacksBlockHeader::make_index_block_hash(
+                &parent_consensus_hash,
+                &block.header.parent_block,
+            );
 
-        let chain_tip = ChainTip {
-            metadata,
-            block,
-            receipts,
-        };
+            let chain_tip = ChainTip {
+                metadata,
+                block,
+                receipts,
+            };
 
-        self.event_dispatcher
-            .process_chain_tip(&chain_tip, &parent_index_hash);
+            self.event_dispatcher
+                .process_chain_tip(&chain_tip, &parent_index_hash);
 
-        self.chain_tip = Some(chain_tip.clone());
+            self.chain_tip = Some(chain_tip.clone());
+            self.health
+                .set_stacks_tip_height(chain_tip.metadata.block_height);
 
-        // Unset the `bootstraping_chain` flag.
-        if self.bootstraping_chain {
-            self.bootstraping_chain = false;
+            chain_tips.push(chain_tip);
         }
 
-        chain_tip
+        chain_tips
+    }
+
+    /// Handle for reading the node's sync state from other threads
+    pub fn health_handle(&self) -> NodeHealthHandle {
+        self.health.clone()
+    }
+
+    pub fn get_health(&self) -> NodeHealth {
+        self.health.get()
     }
 
     /// Returns the Stacks address of the node
@@ -720,7 +777,7 @@ impl Node {
             tx_auth,
             TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
         );
-        tx.chain_id = TESTNET_CHAIN_ID;
+        tx.chain_id = self.config.node.chain_id;
         tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
         let mut tx_signer = StacksTransactionSigner::new(&tx);
         self.keychain.sign_as_origin(&mut tx_signer);
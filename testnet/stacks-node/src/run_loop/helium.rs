@@ -1,10 +1,13 @@
 use crate::burnchains::Error as BurnchainControllerError;
+use crate::tenure::{TenureArtifacts, MAX_TENURE_RESTARTS};
 use crate::{
-    BitcoinRegtestController, BurnchainController, ChainTip, Config, MocknetController, Node,
+    BitcoinRegtestController, BurnchainController, BurnchainTip, ChainTip, Config,
+    MocknetController, Node, Tenure,
 };
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
 use stacks::chainstate::stacks::db::ClarityTx;
 
-use super::RunLoopCallbacks;
+use super::{NodeHealth, NodeHealthHandle, RunLoopCallbacks};
 
 /// RunLoop is coordinating a simulated burnchain and some simulated nodes
 /// taking turns in producing blocks.
@@ -34,6 +37,16 @@ impl RunLoop {
         }
     }
 
+    /// Summarize the node's sync state
+    pub fn get_health(&self) -> NodeHealth {
+        self.node.get_health()
+    }
+
+    /// Handle for reading the node's sync state while the run loop is running
+    pub fn health_handle(&self) -> NodeHealthHandle {
+        self.node.health_handle()
+    }
+
     /// Starts the testnet runloop.
     ///
     /// This function will block by looping infinitely.
@@ -55,6 +68,10 @@ impl RunLoop {
         // Update each node with the genesis block.
         self.node.process_burnchain_state(&initial_state);
 
+        if !self.config.node.miner {
+            return self.follow(burnchain, expected_num_rounds);
+        }
+
         // make first non-genesis block, with initial VRF keys
         self.node.setup(&mut burnchain);
 
@@ -71,25 +88,22 @@ impl RunLoop {
 
         // Bootstrap the chain: node will start a new tenure,
         // using the sortition hash from block #1 for generating a VRF.
-        let leader = &mut self.node;
-        let mut first_tenure = match leader.initiate_genesis_tenure(&burnchain_tip) {
+        let first_tenure = match self.node.initiate_genesis_tenure(&burnchain_tip) {
             Some(res) => res,
             None => panic!("Error while initiating genesis tenure"),
         };
 
-        self.callbacks.invoke_new_tenure(
-            round_index,
-            &burnchain_tip,
-            &chain_tip,
-            &mut first_tenure,
-        );
-
         // TODO (hack) instantiate db
         let _ = burnchain.sortdb_mut();
 
         // Run the tenure, keep the artifacts
-        let artifacts_from_1st_tenure = match first_tenure.run(&burnchain.sortdb_ref().index_conn())
-        {
+        let artifacts_from_1st_tenure = match self.run_tenure(
+            first_tenure,
+            &mut burnchain,
+            round_index,
+            &burnchain_tip,
+            &chain_tip,
+        ) {
             Some(res) => res,
             None => panic!("Error while running 1st tenure"),
         };
@@ -98,7 +112,7 @@ impl RunLoop {
         // while having the option of running multiple tenures concurrently and try different strategies.
         // As a result, once the tenure ran and we have the artifacts (anchored_blocks, microblocks),
         // we have the 1st node (leading) updating its chainstate with the artifacts from its own tenure.
-        leader.commit_artifacts(
+        self.node.commit_artifacts(
             &artifacts_from_1st_tenure.anchored_block,
             &artifacts_from_1st_tenure.parent_block,
             &mut burnchain,
@@ -150,15 +164,13 @@ impl RunLoop {
 
             // Run the last initialized tenure
             let artifacts_from_tenure = match leader_tenure {
-                Some(mut tenure) => {
-                    self.callbacks.invoke_new_tenure(
-                        round_index,
-                        &burnchain_tip,
-                        &chain_tip,
-                        &mut tenure,
-                    );
-                    tenure.run(&burnchain.sortdb_ref().index_conn())
-                }
+                Some(tenure) => self.run_tenure(
+                    tenure,
+                    &mut burnchain,
+                    round_index,
+                    &burnchain_tip,
+                    &chain_tip,
+                ),
                 None => None,
             };
 
@@ -191,8 +203,14 @@ impl RunLoop {
                 };
 
             match artifacts_from_tenure {
-                // Pass if we're missing the artifacts from the current tenure.
-                None => continue,
+                // Pass if we're missing the artifacts from the current tenure, e.g. because it
+                // was abandoned.  If we won this sortition, try again with a new tenure.
+                None => {
+                    if won_sortition {
+                        leader_tenure = self.node.initiate_new_tenure();
+                    }
+                    continue;
+                }
                 Some(ref artifacts) => {
                     // Have the node process its tenure.
                     // We should have some additional checks here, and ensure that the previous artifacts are legit.
@@ -221,4 +239,97 @@ impl RunLoop {
             round_index += 1;
         }
     }
+
+    /// Run a tenure, and check that the burnchain tip it was assembled against is still the
+    /// canonical tip before its block gets committed to.  If the tip changed, the tenure is
+    /// abandoned, and a new one is assembled against the new tip, up to `MAX_TENURE_RESTARTS`
+    /// times.
+    fn run_tenure(
+        &mut self,
+        mut tenure: Tenure,
+        burnchain: &mut Box<dyn BurnchainController>,
+        round_index: u64,
+        burnchain_tip: &BurnchainTip,
+        chain_tip: &ChainTip,
+    ) -> Option<TenureArtifacts> {
+        let mut restarts = 0;
+        loop {
+            self.callbacks
+                .invoke_new_tenure(round_index, burnchain_tip, chain_tip, &mut tenure);
+
+            let artifacts = tenure.run(&burnchain.sortdb_ref().index_conn())?;
+            let canonical_snapshot =
+                SortitionDB::get_canonical_burn_chain_tip(burnchain.sortdb_ref().conn())
+                    .expect("FATAL: failed to query the canonical burnchain tip");
+            if artifacts.is_parent_tip_current(&canonical_snapshot) {
+                return Some(artifacts);
+            }
+
+            if restarts >= MAX_TENURE_RESTARTS {
+                info!(
+                    "Abandoning tenure: burnchain tip changed from {} to {} during assembly, and the tenure was already restarted {} times",
+                    &artifacts.parent_block.block_snapshot.consensus_hash,
+                    &canonical_snapshot.consensus_hash,
+                    restarts
+                );
+                return None;
+            }
+
+            info!(
+                "Restarting tenure: burnchain tip changed from {} to {} during assembly",
+                &artifacts.parent_block.block_snapshot.consensus_hash,
+                &canonical_snapshot.consensus_hash
+            );
+            restarts += 1;
+
+            let canonical_tip = burnchain.get_chain_tip();
+            self.node.process_burnchain_state(&canonical_tip);
+            tenure = self.node.initiate_new_tenure()?;
+        }
+    }
+
+    /// Track the burnchain without mining: no VRF keys get registered, and no tenures get run.
+    /// Stacks blocks that reach the staging area are still processed, once per round.
+    fn follow(
+        &mut self,
+        mut burnchain: Box<dyn BurnchainController>,
+        expected_num_rounds: u64,
+    ) -> Result<(), BurnchainControllerError> {
+        info!("Follower node: starting up");
+
+        let mut chain_tip = ChainTip::genesis(self.config.get_initial_liquid_ustx());
+
+        self.node.spawn_peer_server();
+
+        let mut round_index: u64 = 0;
+        loop {
+            if expected_num_rounds == round_index {
+                return Ok(());
+            }
+
+            let (burnchain_tip, _) = burnchain.sync(None)?;
+
+            self.callbacks
+                .invoke_new_burn_chain_state(round_index, &burnchain_tip, &chain_tip);
+
+            self.node.process_burnchain_state(&burnchain_tip);
+
+            for new_chain_tip in self
+                .node
+                .process_staged_blocks(burnchain.sortdb_mut())
+                .into_iter()
+            {
+                chain_tip = new_chain_tip;
+                self.callbacks.invoke_new_stacks_chain_state(
+                    round_index,
+                    &burnchain_tip,
+                    &chain_tip,
+                    &mut self.node.chain_state,
+                    &burnchain.sortdb_ref().index_conn(),
+                );
+            }
+
+            round_index += 1;
+        }
+    }
 }
@@ -11,6 +11,12 @@ use stacks::util::vrf::VRFPublicKey;
 
 use stacks::vm::database::BurnStateDB;
 
+use std::any::Any;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
 macro_rules! info_blue {
     ($($arg:tt)*) => ({
         eprintln!("\x1b[0;96m{}\x1b[0m", format!($($arg)*));
@@ -147,3 +153,154 @@ pub struct RegisteredKey {
     pub op_vtxindex: u32,
     pub vrf_public_key: VRFPublicKey,
 }
+
+/// Summary of the node's sync state, e.g. for a readiness probe
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeHealth {
+    /// Burnchain block height in the p2p network's chain view
+    pub burn_block_height: u64,
+    /// Height of the Stacks chain tip
+    pub stacks_tip_height: u64,
+    /// Whether the node has a registered VRF key, and can thus mine
+    pub miner_active: bool,
+    /// Number of p2p peers
+    pub num_peers: u64,
+    /// Whether the chain view is within `miner.max_sync_lag` blocks of the burnchain
+    /// controller's tip
+    pub is_synced: bool,
+}
+
+/// Handle to the node's sync state.  The run loop and the p2p thread update it as they go, and
+/// it can be read from any thread without blocking on either of them.
+#[derive(Clone)]
+pub struct NodeHealthHandle {
+    chain_view_height: Arc<AtomicU64>,
+    burnchain_tip_height: Arc<AtomicU64>,
+    stacks_tip_height: Arc<AtomicU64>,
+    miner_active: Arc<AtomicBool>,
+    num_peers: Arc<AtomicU64>,
+    max_sync_lag: u64,
+}
+
+impl NodeHealthHandle {
+    pub fn new(max_sync_lag: u64) -> NodeHealthHandle {
+        NodeHealthHandle {
+            chain_view_height: Arc::new(AtomicU64::new(0)),
+            burnchain_tip_height: Arc::new(AtomicU64::new(0)),
+            stacks_tip_height: Arc::new(AtomicU64::new(0)),
+            miner_active: Arc::new(AtomicBool::new(false)),
+            num_peers: Arc::new(AtomicU64::new(0)),
+            max_sync_lag,
+        }
+    }
+
+    pub fn set_chain_view_height(&self, height: u64) {
+        self.chain_view_height.store(height, Ordering::SeqCst);
+    }
+
+    pub fn set_burnchain_tip_height(&self, height: u64) {
+        self.burnchain_tip_height.store(height, Ordering::SeqCst);
+    }
+
+    pub fn set_stacks_tip_height(&self, height: u64) {
+        self.stacks_tip_height.store(height, Ordering::SeqCst);
+    }
+
+    pub fn set_miner_active(&self, active: bool) {
+        self.miner_active.store(active, Ordering::SeqCst);
+    }
+
+    pub fn set_num_peers(&self, num_peers: u64) {
+        self.num_peers.store(num_peers, Ordering::SeqCst);
+    }
+
+    pub fn get(&self) -> NodeHealth {
+        let burn_block_height = self.chain_view_height.load(Ordering::SeqCst);
+        let burnchain_tip_height = self.burnchain_tip_height.load(Ordering::SeqCst);
+
+        // not synced until we've heard from the burnchain controller at all
+        let is_synced = burnchain_tip_height > 0
+            && burn_block_height + self.max_sync_lag >= burnchain_tip_height;
+
+        NodeHealth {
+            burn_block_height,
+            stacks_tip_height: self.stacks_tip_height.load(Ordering::SeqCst),
+            miner_active: self.miner_active.load(Ordering::SeqCst),
+            num_peers: self.num_peers.load(Ordering::SeqCst),
+            is_synced,
+        }
+    }
+}
+
+/// Threads whose names start with this prefix are run under `supervise_thread()`.  The node's
+/// crash handler logs their panics and lets them unwind, instead of aborting the process.
+pub const SUPERVISED_THREAD_PREFIX: &str = "supervised-";
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+/// Spawn a worker thread with `spawn_worker`, and spawn a supervisor thread that restarts the
+/// worker each time it panics, up to `max_restarts` times.  If the worker panics once more after
+/// that, the supervisor panics too.  The worker should be spawned on a thread whose name starts
+/// with `SUPERVISED_THREAD_PREFIX`, so that its panics reach the supervisor.
+///
+/// Returns the supervisor's join handle, which yields the number of restarts once the worker
+/// exits cleanly.  Errors from the first call to `spawn_worker` are returned to the caller.
+pub fn supervise_thread<F, E>(
+    name: &str,
+    max_restarts: u64,
+    mut spawn_worker: F,
+) -> Result<JoinHandle<u64>, E>
+where
+    F: FnMut() -> Result<JoinHandle<()>, E> + Send + 'static,
+    E: fmt::Debug,
+{
+    let mut worker = spawn_worker()?;
+    let name = name.to_string();
+    let supervisor = thread::Builder::new()
+        .name(format!("{}-supervisor", &name))
+        .spawn(move || {
+            let mut restarts = 0;
+            loop {
+                let payload = match worker.join() {
+                    Ok(()) => {
+                        debug!("Supervised thread {} exited", &name);
+                        return restarts;
+                    }
+                    Err(payload) => payload,
+                };
+
+                let msg = panic_message(&payload);
+                if restarts >= max_restarts {
+                    error!(
+                        "Supervised thread {} panicked ({}); giving up after {} restarts",
+                        &name, &msg, restarts
+                    );
+                    panic!("Supervised thread {} panicked too many times", &name);
+                }
+
+                restarts += 1;
+                warn!(
+                    "Supervised thread {} panicked ({}); restarting ({} of {})",
+                    &name, &msg, restarts, max_restarts
+                );
+                worker = match spawn_worker() {
+                    Ok(jh) => jh,
+                    Err(e) => {
+                        error!("Failed to restart supervised thread {}: {:?}", &name, &e);
+                        panic!("Failed to restart supervised thread {}", &name);
+                    }
+                };
+            }
+        })
+        .expect("Failed to spawn supervisor thread");
+
+    Ok(supervisor)
+}
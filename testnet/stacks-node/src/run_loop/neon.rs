@@ -1,7 +1,9 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 
 use crate::{
-    neon_node, BitcoinRegtestController, BurnchainController, Config, EventDispatcher, Keychain,
+    BitcoinRegtestController, BurnchainController, Config, EventDispatcher, Keychain,
     NeonGenesisNode,
 };
 use stacks::burnchains::bitcoin::address::BitcoinAddress;
@@ -9,7 +11,11 @@ use stacks::burnchains::bitcoin::address::BitcoinAddressType;
 use stacks::burnchains::{Address, Burnchain};
 use stacks::chainstate::burn::db::sortdb::SortitionDB;
 use stacks::chainstate::coordinator::comm::{CoordinatorChannels, CoordinatorReceivers};
-use stacks::chainstate::coordinator::{ChainsCoordinator, CoordinatorCommunication};
+pub use stacks::chainstate::coordinator::dispatcher::OnNewBlockCallback;
+use stacks::chainstate::coordinator::dispatcher::OnNewBlockDispatcher;
+use stacks::chainstate::coordinator::{
+    ChainsCoordinator, CoordinatorCommunication, LogFileDispatcher,
+};
 
 use super::RunLoopCallbacks;
 
@@ -22,6 +28,7 @@ use crate::syncctl::PoxSyncWatchdog;
 pub struct RunLoop {
     config: Config,
     pub callbacks: RunLoopCallbacks,
+    on_new_block: Option<OnNewBlockCallback>,
     blocks_processed: std::sync::Arc<std::sync::atomic::AtomicU64>,
     coordinator_channels: Option<(CoordinatorReceivers, CoordinatorChannels)>,
 }
@@ -30,6 +37,7 @@ pub struct RunLoop {
 pub struct RunLoop {
     config: Config,
     pub callbacks: RunLoopCallbacks,
+    on_new_block: Option<OnNewBlockCallback>,
     coordinator_channels: Option<(CoordinatorReceivers, CoordinatorChannels)>,
 }
 
@@ -42,6 +50,7 @@ impl RunLoop {
             config,
             coordinator_channels: Some(channels),
             callbacks: RunLoopCallbacks::new(),
+            on_new_block: None,
         }
     }
 
@@ -52,10 +61,19 @@ impl RunLoop {
             config,
             coordinator_channels: Some(channels),
             callbacks: RunLoopCallbacks::new(),
+            on_new_block: None,
             blocks_processed: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
         }
     }
 
+    /// Set a callback to invoke after the chains coordinator processes each Stacks block.
+    /// It runs on the coordinator thread, which cannot process any other block until it
+    /// returns, so it should be quick: hand off any slow work to another thread.
+    /// Must be set before the run loop is started.
+    pub fn set_on_new_block(&mut self, callback: OnNewBlockCallback) {
+        self.on_new_block = Some(callback);
+    }
+
     pub fn get_coordinator_channel(&self) -> Option<CoordinatorChannels> {
         self.coordinator_channels.as_ref().map(|x| x.1.clone())
     }
@@ -128,8 +146,10 @@ impl RunLoop {
         };
 
         let mainnet = false;
-        let chainid = neon_node::TESTNET_CHAIN_ID;
+        let chainid = self.config.node.chain_id;
         let block_limit = self.config.block_limit.clone();
+        let max_reorg_depth = self.config.coordinator.max_reorg_depth;
+        let block_validation_threads = self.config.coordinator.block_validation_threads;
         let initial_balances = self
             .config
             .initial_balances
@@ -139,12 +159,19 @@ impl RunLoop {
         let burnchain_poll_time = 30; // TODO: this is testnet-specific
 
         // setup dispatcher
-        let mut event_dispatcher = EventDispatcher::new();
-        for observer in self.config.events_observers.iter() {
-            event_dispatcher.register_observer(observer);
-        }
+        let event_dispatcher = EventDispatcher::from_config(&self.config);
 
-        let mut coordinator_dispatcher = event_dispatcher.clone();
+        let receipts_log_dispatcher = self
+            .config
+            .node
+            .receipts_log_path
+            .as_ref()
+            .map(|path| LogFileDispatcher::new(path));
+        let on_new_block_dispatcher = self.on_new_block.take().map(OnNewBlockDispatcher::new);
+        let mut coordinator_dispatcher = (
+            event_dispatcher.clone(),
+            (receipts_log_dispatcher, on_new_block_dispatcher),
+        );
         let burnchain_config = match Burnchain::new(
             &self.config.get_burn_db_path(),
             &self.config.burnchain.chain,
@@ -158,19 +185,30 @@ impl RunLoop {
         };
         let chainstate_path = self.config.get_chainstate_path();
         let coordinator_burnchain_config = burnchain_config.clone();
+        let burnchain_block_fetcher = burnchain.make_block_fetcher();
 
+        // set if the chains coordinator halts (instead of being told to stop), so the run loop
+        //   stops the node too
+        let coordinator_halted = Arc::new(AtomicBool::new(false));
+        let coordinator_halted_flag = coordinator_halted.clone();
         thread::spawn(move || {
-            ChainsCoordinator::run(
+            if let Err(e) = ChainsCoordinator::run(
                 &chainstate_path,
                 coordinator_burnchain_config,
                 mainnet,
                 chainid,
                 Some(initial_balances),
                 block_limit,
+                max_reorg_depth,
+                block_validation_threads,
+                Some(Box::new(burnchain_block_fetcher)),
                 &mut coordinator_dispatcher,
                 coordinator_receivers,
                 |_| {},
-            );
+            ) {
+                error!("Chains coordinator halted: {:?}", e);
+                coordinator_halted_flag.store(true, Ordering::SeqCst);
+            }
         });
 
         let mut burnchain_tip = burnchain.wait_for_sortitions(None);
@@ -222,6 +260,11 @@ impl RunLoop {
         target_burnchain_block_height = pox_constants.reward_cycle_length as u64;
 
         loop {
+            if coordinator_halted.load(Ordering::SeqCst) {
+                error!("Chains coordinator halted, exiting.");
+                return;
+            }
+
             // wait until it's okay to process the next sortitions
             let ibd =
                 pox_watchdog.pox_sync_wait(&burnchain_config, &burnchain_tip, burnchain_height);
@@ -238,6 +281,7 @@ impl RunLoop {
             target_burnchain_block_height += pox_constants.reward_cycle_length as u64;
             burnchain_tip = next_burnchain_tip;
             burnchain_height = next_burnchain_height;
+            node.set_burnchain_tip_height(burnchain_height);
 
             let sortition_tip = &burnchain_tip.block_snapshot.sortition_id;
             let next_height = burnchain_tip.block_snapshot.block_height;
@@ -1,11 +1,11 @@
-use super::node::{ChainTip, TESTNET_CHAIN_ID};
+use super::node::ChainTip;
 use super::{BurnchainTip, Config};
 
 use std::thread;
 use std::time::{Duration, Instant};
 
 use stacks::burnchains::PublicKey;
-use stacks::chainstate::burn::VRFSeed;
+use stacks::chainstate::burn::{BlockSnapshot, VRFSeed};
 use stacks::chainstate::stacks::db::StacksChainState;
 use stacks::chainstate::stacks::{
     StacksBlock, StacksBlockBuilder, StacksMicroblock, StacksPrivateKey, StacksPublicKey,
@@ -17,6 +17,10 @@ use stacks::util::vrf::VRFProof;
 
 use stacks::vm::database::BurnStateDB;
 
+/// How many times a tenure gets restarted because the burnchain tip changed while its block was
+/// assembled, before it is abandoned
+pub const MAX_TENURE_RESTARTS: u64 = 3;
+
 pub struct TenureArtifacts {
     pub anchored_block: StacksBlock,
     pub microblocks: Vec<StacksMicroblock>,
@@ -24,6 +28,15 @@ pub struct TenureArtifacts {
     pub burn_fee: u64,
 }
 
+impl TenureArtifacts {
+    /// Whether the burnchain tip captured when the block was assembled is still the canonical
+    /// tip, i.e. whether it is safe to commit to the block.  `canonical_tip` should be read from
+    /// the sortition DB right before committing, not taken from a cached `BurnchainTip`.
+    pub fn is_parent_tip_current(&self, canonical_tip: &BlockSnapshot) -> bool {
+        self.parent_block.block_snapshot.consensus_hash == canonical_tip.consensus_hash
+    }
+}
+
 pub struct Tenure {
     coinbase_tx: StacksTransaction,
     config: Config,
@@ -31,6 +44,9 @@ pub struct Tenure {
     pub parent_block: ChainTip,
     pub mem_pool: MemPoolDB,
     pub vrf_seed: VRFSeed,
+    /// Transactions mined immediately after the coinbase, ahead of the mempool.  Starts out as
+    /// the configured `miner.must_include` transactions.
+    pub must_include: Vec<StacksTransaction>,
     burn_fee_cap: u64,
     vrf_proof: VRFProof,
     microblock_pubkeyhash: Hash160,
@@ -53,6 +69,7 @@ impl<'a> Tenure {
         let microblock_pubkeyhash = Hash160::from_data(&microblock_pubkey.to_bytes());
 
         let parent_block_total_burn = burnchain_tip.block_snapshot.total_burn;
+        let must_include = config.miner.must_include.clone();
 
         Self {
             coinbase_tx,
@@ -61,6 +78,7 @@ impl<'a> Tenure {
             mem_pool,
             parent_block,
             vrf_seed: VRFSeed::from_proof(&vrf_proof),
+            must_include,
             vrf_proof,
             burn_fee_cap,
             microblock_pubkeyhash,
@@ -80,13 +98,13 @@ impl<'a> Tenure {
 
         let (mut chain_state, _) = StacksChainState::open_with_block_limit(
             false,
-            TESTNET_CHAIN_ID,
+            self.config.node.chain_id,
             &self.config.get_chainstate_path(),
             self.config.block_limit.clone(),
         )
         .unwrap();
 
-        let (anchored_block, _, _) = StacksBlockBuilder::build_anchored_block(
+        let (anchored_block, _, _) = match StacksBlockBuilder::build_anchored_block_with_txs(
             &mut chain_state,
             burn_dbconn,
             &mut self.mem_pool,
@@ -95,9 +113,15 @@ impl<'a> Tenure {
             self.vrf_proof.clone(),
             self.microblock_pubkeyhash.clone(),
             &self.coinbase_tx,
+            &self.must_include,
             self.config.block_limit.clone(),
-        )
-        .unwrap();
+        ) {
+            Ok(block) => block,
+            Err(e) => {
+                error!("Failure mining anchored block, aborting tenure: {:?}", e);
+                return None;
+            }
+        };
 
         info!("Finish tenure: {}", anchored_block.block_hash());
 
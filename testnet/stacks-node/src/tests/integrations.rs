@@ -1,12 +1,13 @@
 use std::collections::HashMap;
 
 use stacks::burnchains::Address;
-use stacks::chainstate::burn::VRFSeed;
+use stacks::chainstate::burn::{ConsensusHash, VRFSeed};
 use stacks::chainstate::stacks::{
     db::blocks::MemPoolRejection, db::StacksChainState, StacksAddress, StacksBlockHeader,
-    StacksPrivateKey, StacksTransaction,
+    StacksPrivateKey, StacksTransaction, TransactionPayload,
 };
 use stacks::core::mempool::MAXIMUM_MEMPOOL_TX_CHAINING;
+use stacks::core::EMPTY_MICROBLOCK_PARENT_HASH;
 use stacks::net::StacksMessageCodec;
 use stacks::net::{AccountEntryResponse, CallReadOnlyRequestBody, ContractSrcResponse};
 use stacks::util::hash::hex_bytes;
@@ -898,6 +899,165 @@ fn contract_stx_transfer() {
     run_loop.start(num_rounds).unwrap();
 }
 
+#[test]
+fn mine_anchored_blocks_without_microblocks() {
+    let mut conf = super::new_test_conf();
+    conf.node.mine_microblocks = true;
+    conf.miner.disable_microblocks = true;
+
+    let num_rounds = 3;
+
+    let mut run_loop = RunLoop::new(conf);
+    run_loop.callbacks.on_new_stacks_chain_state(
+        |round, _burnchain_tip, chain_tip, _chain_state, _burn_dbconn| {
+            // anchored blocks are still mined...
+            assert_eq!(chain_tip.metadata.block_height, round + 1);
+
+            // ...but none of them confirm a microblock stream
+            assert_eq!(
+                chain_tip.block.header.parent_microblock,
+                EMPTY_MICROBLOCK_PARENT_HASH
+            );
+            assert_eq!(chain_tip.block.header.parent_microblock_sequence, 0);
+        },
+    );
+
+    run_loop.start(num_rounds).unwrap();
+}
+
+fn make_pinned_tx() -> StacksTransaction {
+    let contract_sk = StacksPrivateKey::from_hex(SK_1).unwrap();
+    let publish_tx =
+        make_contract_publish(&contract_sk, 0, 0, "pinned", "(define-data-var x int 1)");
+    StacksTransaction::consensus_deserialize(&mut &publish_tx[..]).unwrap()
+}
+
+#[test]
+fn mine_must_include_txs_after_coinbase() {
+    let mut conf = super::new_test_conf();
+    conf.miner.must_include = vec![make_pinned_tx()];
+
+    // only the genesis tenure: the next one would abort, since the pinned transaction's nonce
+    //  is used by then
+    let num_rounds = 1;
+
+    let mut run_loop = RunLoop::new(conf);
+    run_loop.callbacks.on_new_stacks_chain_state(
+        |round, _burnchain_tip, chain_tip, _chain_state, _burn_dbconn| {
+            assert_eq!(round, 0);
+            let txs = &chain_tip.block.txs;
+            assert_eq!(txs.len(), 2);
+            match txs[0].payload {
+                TransactionPayload::Coinbase(..) => {}
+                _ => panic!("Expected a coinbase first, got {:?}", &txs[0]),
+            }
+            assert_eq!(txs[1], make_pinned_tx());
+        },
+    );
+
+    run_loop.start(num_rounds).unwrap();
+}
+
+#[test]
+fn tenure_restarts_on_stale_burnchain_tip() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static TENURES_STARTED: AtomicU64 = AtomicU64::new(0);
+    const STALE_ROUND: u64 = 2;
+
+    let conf = super::new_test_conf();
+
+    let num_rounds = 4;
+
+    let mut run_loop = RunLoop::new(conf);
+    run_loop
+        .callbacks
+        .on_new_tenure(|round, _burnchain_tip, _chain_tip, tenure| {
+            let started = TENURES_STARTED.fetch_add(1, Ordering::SeqCst);
+            if round == STALE_ROUND && started == round {
+                // pretend a new burnchain block arrived while this tenure was being assembled
+                tenure.burnchain_tip.block_snapshot.consensus_hash = ConsensusHash([0xff; 20]);
+            }
+        });
+    run_loop.callbacks.on_new_stacks_chain_state(
+        |round, _burnchain_tip, chain_tip, _chain_state, _burn_dbconn| {
+            // the stale tenure never got committed to, but its restart did
+            assert_eq!(chain_tip.metadata.block_height, round + 1);
+        },
+    );
+
+    run_loop.start(num_rounds).unwrap();
+
+    // one tenure per round, plus the restart
+    assert_eq!(TENURES_STARTED.load(Ordering::SeqCst), num_rounds + 1);
+}
+
+#[test]
+fn follower_never_mines() {
+    let mut conf = super::new_test_conf();
+    conf.node.miner = false;
+
+    let num_rounds = 5;
+
+    let mut run_loop = RunLoop::new(conf);
+    run_loop
+        .callbacks
+        .on_new_burn_chain_state(|_round, burnchain_tip, _chain_tip| {
+            // no key registrations or block commits
+            assert_eq!(burnchain_tip.state_transition.accepted_ops.len(), 0);
+            assert!(!burnchain_tip.block_snapshot.sortition);
+        });
+    run_loop
+        .callbacks
+        .on_new_tenure(|_round, _burnchain_tip, _chain_tip, _tenure| {
+            panic!("Follower should not run a tenure");
+        });
+    run_loop.callbacks.on_new_stacks_chain_state(
+        |_round, _burnchain_tip, _chain_tip, _chain_state, _burn_dbconn| {
+            panic!("Follower should not produce a Stacks block");
+        },
+    );
+
+    run_loop.start(num_rounds).unwrap();
+
+    // still processed every burnchain block
+    let burnchain_tip = run_loop.node.burnchain_tip.clone().unwrap();
+    assert_eq!(burnchain_tip.block_snapshot.block_height, num_rounds);
+    assert!(!run_loop.get_health().miner_active);
+}
+
+#[test]
+fn node_health_becomes_synced() {
+    let conf = super::new_test_conf();
+
+    let num_rounds = 4;
+
+    let mut run_loop = RunLoop::new(conf);
+    let health = run_loop.health_handle();
+
+    // haven't heard from the burnchain yet
+    assert!(!health.get().is_synced);
+
+    run_loop.start(num_rounds).unwrap();
+
+    // the p2p thread refreshes its chain view on its own schedule
+    let mut is_synced = false;
+    for _ in 0..60 {
+        if health.get().is_synced {
+            is_synced = true;
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+    assert!(is_synced);
+
+    let node_health = run_loop.get_health();
+    assert!(node_health.is_synced);
+    assert!(node_health.burn_block_height > 0);
+    assert_eq!(node_health.stacks_tip_height, num_rounds);
+    assert!(node_health.miner_active);
+}
+
 /// Test mining a smart contract twice (in non-sequential blocks)
 ///   this can happen in the testnet leader if they get "behind"
 ///   the burnchain and a previously mined block doesn't get included
@@ -4,32 +4,57 @@ mod mempool;
 mod neon_integrations;
 
 use stacks::address::AddressHashMode;
-use stacks::chainstate::burn::ConsensusHash;
-use stacks::chainstate::stacks::events::{STXEventType, StacksTransactionEvent};
+use stacks::burnchains::{BurnchainHeaderHash, Txid};
+use stacks::chainstate::burn::operations::{
+    BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp, UserBurnSupportOp,
+};
+use stacks::chainstate::burn::{BlockHeaderHash, ConsensusHash, VRFSeed};
+use stacks::chainstate::stacks::events::{
+    STXEventType, SmartContractEventData, StacksTransactionEvent, StacksTransactionReceipt,
+};
 use stacks::chainstate::stacks::{
     db::StacksChainState, miner::StacksMicroblockBuilder, CoinbasePayload, StacksAddress,
-    StacksBlock, StacksMicroblock, StacksMicroblockHeader, StacksPrivateKey, StacksPublicKey,
-    StacksTransaction, StacksTransactionSigner, TokenTransferMemo, TransactionAnchorMode,
-    TransactionAuth, TransactionContractCall, TransactionPayload, TransactionPostConditionMode,
-    TransactionSmartContract, TransactionSpendingCondition, TransactionVersion,
-    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    StacksBlock, StacksBlockHeader, StacksBlockId, StacksMicroblock, StacksMicroblockHeader,
+    StacksPrivateKey, StacksPublicKey, StacksTransaction, StacksTransactionSigner,
+    TokenTransferMemo, TransactionAnchorMode, TransactionAuth, TransactionContractCall,
+    TransactionPayload, TransactionPostConditionMode, TransactionSmartContract,
+    TransactionSpendingCondition, TransactionVersion, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
 };
 use stacks::core::mempool::MemPoolTxInfo;
+use stacks::deps::bitcoin::blockdata::script::Script;
+use stacks::deps::bitcoin::blockdata::transaction::Transaction;
+use stacks::deps::bitcoin::util::hash::Sha256dHash;
 use stacks::net::StacksMessageCodec;
-use stacks::util::hash::hex_bytes;
+use stacks::util::hash::{hex_bytes, to_hex, Hash160};
 use stacks::util::strings::StacksString;
+use stacks::util::vrf::{VRFPrivateKey, VRFPublicKey};
 use stacks::vm::costs::ExecutionCost;
-use stacks::vm::types::PrincipalData;
+use stacks::vm::types::{PrincipalData, QualifiedContractIdentifier};
 use stacks::vm::{ClarityName, ContractName, Value};
 
-use super::burnchains::bitcoin_regtest_controller::ParsedUTXO;
-use super::node::TESTNET_CHAIN_ID;
-use super::Config;
+use super::burnchains::bitcoin_regtest_controller::{
+    BitcoinRegtestController, ParsedUTXO, UTXO, UTXO_CONSOLIDATION_THRESHOLD, UTXO_INPUT_FEE,
+    UTXO_MAX_INPUTS,
+};
+use super::burnchains::{
+    BurnchainController, Error as BurnchainControllerError, MocknetController,
+};
+use super::config::{EventKeyType, EventObserverConfig, MinerConfig, NodeConfigFile};
+use super::event_dispatcher::{EventIndex, HEADER_EVENT_SEQUENCE};
+use super::neon_node::{BurnFeeEscalation, MiningSyncGate};
+use super::node::{ChainTip, REGTEST_CHAIN_ID, TESTNET_CHAIN_ID};
+use super::operations::BurnchainOpSigner;
+use super::{Config, ConfigFile, EventDispatcher, Keychain};
 use crate::helium::RunLoop;
+use crate::run_loop::{supervise_thread, SUPERVISED_THREAD_PREFIX};
 use rand::RngCore;
 use std::convert::TryInto;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-use stacks::vm::database::BurnStateDB;
+use stacks::core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
+use stacks::vm::database::{BurnStateDB, NULL_BURN_STATE_DB};
 
 // $ cat /tmp/out.clar
 pub const STORE_CONTRACT: &str = r#"(define-map store ((key (string-ascii 32))) ((value (string-ascii 32))))
@@ -252,6 +277,20 @@ pub fn make_contract_call(
     serialize_sign_standard_single_sig_tx(payload.into(), sender, nonce, fee_rate)
 }
 
+/// A key registration with a fresh VRF key, for feeding to a mocknet burnchain
+fn make_leader_key_register(consensus_hash: &ConsensusHash) -> BlockstackOperationType {
+    BlockstackOperationType::LeaderKeyRegister(LeaderKeyRegisterOp {
+        public_key: VRFPublicKey::from_private(&VRFPrivateKey::new()),
+        memo: vec![],
+        address: to_addr(&StacksPrivateKey::new()),
+        consensus_hash: consensus_hash.clone(),
+        vtxindex: 0,
+        txid: Txid([0u8; 32]),
+        block_height: 0,
+        burn_header_hash: BurnchainHeaderHash([0u8; 32]),
+    })
+}
+
 fn make_microblock(
     privk: &StacksPrivateKey,
     chainstate: &mut StacksChainState,
@@ -740,3 +779,1075 @@ fn test_btc_to_sat_errors() {
     assert!(ParsedUTXO::serialized_btc_to_sat("7.4e-7").is_none());
     assert!(ParsedUTXO::serialized_btc_to_sat("5.96e-6").is_none());
 }
+
+#[test]
+fn test_select_utxos_bounds_utxo_count() {
+    let make_utxo = |i: u64, amount: u64| UTXO {
+        txid: Sha256dHash::from_data(&i.to_be_bytes()),
+        vout: 0,
+        script_pub_key: Script::new(),
+        amount,
+    };
+
+    // a big UTXO, plus a pile of small change left over from earlier commits
+    let mut wallet = vec![make_utxo(0, 100_000_000)];
+    for i in 1..40 {
+        wallet.push(make_utxo(i, 10_000 + i));
+    }
+
+    let amount_required = 20_000 + 1_000;
+    let mut next_txid = 1000;
+    for _ in 0..200 {
+        let selected =
+            BitcoinRegtestController::select_utxos(wallet.clone(), amount_required).unwrap();
+        assert!(selected.len() <= UTXO_MAX_INPUTS);
+
+        // every input pays its own fee
+        let fee = UTXO_INPUT_FEE * (selected.len() as u64);
+        let total: u64 = selected.iter().map(|u| u.amount).sum();
+        assert!(total >= amount_required + fee);
+
+        wallet.retain(|u| {
+            !selected
+                .iter()
+                .any(|s| s.txid == u.txid && s.vout == u.vout)
+        });
+        wallet.push(make_utxo(next_txid, total - amount_required - fee));
+        next_txid += 1;
+
+        // we also keep receiving small outputs (e.g. PoX payouts)
+        wallet.push(make_utxo(next_txid, 11_000));
+        next_txid += 1;
+
+        assert!(wallet.len() <= UTXO_CONSOLIDATION_THRESHOLD + 2);
+    }
+
+    // not enough funds
+    match BitcoinRegtestController::select_utxos(vec![make_utxo(0, 100)], 1000).err() {
+        Some(BurnchainControllerError::InsufficientFunds { needed, available }) => {
+            assert_eq!(needed, 1000 + UTXO_INPUT_FEE);
+            assert_eq!(available, 100);
+        }
+        x => panic!("Expected InsufficientFunds, got {:?}", &x),
+    }
+
+    // enough funds, but not once the input fee is paid
+    match BitcoinRegtestController::select_utxos(vec![make_utxo(0, 1000)], 1000).err() {
+        Some(BurnchainControllerError::InsufficientFunds { needed, available }) => {
+            assert_eq!(needed, 1000 + UTXO_INPUT_FEE);
+            assert_eq!(available, 1000);
+        }
+        x => panic!("Expected InsufficientFunds, got {:?}", &x),
+    }
+
+    // a second input is needed to pay for the first one's fee
+    let selected = BitcoinRegtestController::select_utxos(
+        vec![
+            make_utxo(0, 1000 + UTXO_INPUT_FEE),
+            make_utxo(1, 2 * UTXO_INPUT_FEE),
+        ],
+        1500,
+    )
+    .unwrap();
+    assert_eq!(selected.len(), 2);
+
+    // dust isn't worth consolidating
+    let mut wallet = vec![make_utxo(0, 100_000_000)];
+    for i in 1..20 {
+        wallet.push(make_utxo(i, UTXO_INPUT_FEE));
+    }
+    let selected = BitcoinRegtestController::select_utxos(wallet, 1000).unwrap();
+    assert_eq!(selected.len(), 1);
+
+    // no more than UTXO_MAX_INPUTS can be spent to make up the amount
+    let wallet: Vec<_> = (0..(UTXO_MAX_INPUTS as u64) + 10)
+        .map(|i| make_utxo(i, UTXO_INPUT_FEE + 100))
+        .collect();
+    match BitcoinRegtestController::select_utxos(wallet, 100 * (UTXO_MAX_INPUTS as u64) + 1).err() {
+        Some(BurnchainControllerError::InsufficientFunds { needed, available }) => {
+            assert_eq!(needed, available + 1);
+        }
+        x => panic!("Expected InsufficientFunds, got {:?}", &x),
+    }
+
+    // the largest UTXO is used first
+    let selected = BitcoinRegtestController::select_utxos(
+        vec![make_utxo(0, 1000), make_utxo(1, 5000), make_utxo(2, 2000)],
+        1500,
+    )
+    .unwrap();
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].amount, 5000);
+}
+
+#[test]
+fn test_mocknet_submit_operations_in_one_block() {
+    let conf = new_test_conf();
+    let mut burnchain_controller = MocknetController::generic(conf);
+    let (genesis_tip, _) = burnchain_controller.start(None).unwrap();
+
+    let ops: Vec<_> = (0..2)
+        .map(|_| make_leader_key_register(&genesis_tip.block_snapshot.consensus_hash))
+        .collect();
+
+    let mut op_signer = BurnchainOpSigner::new(StacksPrivateKey::new(), false);
+    let txids = burnchain_controller
+        .submit_operations(ops, &mut op_signer)
+        .unwrap();
+    assert_eq!(txids.len(), 2);
+
+    let (tip, _) = burnchain_controller.sync(None).unwrap();
+    let accepted_txids: Vec<_> = tip
+        .state_transition
+        .accepted_ops
+        .iter()
+        .map(|op| op.txid())
+        .collect();
+    assert_eq!(accepted_txids, txids);
+}
+
+#[test]
+fn test_mocknet_sync_to_height() {
+    let conf = new_test_conf();
+    let mut burnchain_controller = MocknetController::generic(conf);
+    let (genesis_tip, _) = burnchain_controller.start(None).unwrap();
+    assert!(genesis_tip.block_snapshot.block_height < 5);
+
+    let tip = burnchain_controller
+        .sync_to_height(5, Duration::from_secs(30))
+        .unwrap();
+    assert_eq!(tip.block_snapshot.block_height, 5);
+    assert_eq!(burnchain_controller.get_chain_tip().block_snapshot.block_height, 5);
+
+    // already there, so no more blocks get mined
+    let tip = burnchain_controller
+        .sync_to_height(3, Duration::from_secs(30))
+        .unwrap();
+    assert_eq!(tip.block_snapshot.block_height, 5);
+
+    // no time to mine anything
+    match burnchain_controller.sync_to_height(10, Duration::from_secs(0)) {
+        Err(BurnchainControllerError::SyncTimeout { target, reached }) => {
+            assert_eq!(target, 10);
+            assert_eq!(reached, 5);
+        }
+        Err(e) => panic!("Expected a sync timeout, got {}", e),
+        Ok(_) => panic!("Expected a sync timeout"),
+    }
+}
+
+#[test]
+fn test_burnchain_tip_accepted_ops_by_type() {
+    let conf = new_test_conf();
+    let mut burnchain_controller = MocknetController::generic(conf.clone());
+    let (mut tip, _) = burnchain_controller.start(None).unwrap();
+    let consensus_hash = tip.block_snapshot.consensus_hash.clone();
+
+    let block_commit = LeaderBlockCommitOp {
+        block_header_hash: BlockHeaderHash([1u8; 32]),
+        new_seed: VRFSeed([2u8; 32]),
+        parent_block_ptr: 0,
+        parent_vtxindex: 0,
+        key_block_ptr: 1,
+        key_vtxindex: 0,
+        memo: vec![],
+        burn_fee: 1000,
+        input: Keychain::default(conf.node.seed.clone()).get_burnchain_signer(),
+        commit_outs: vec![],
+        txid: Txid([3u8; 32]),
+        vtxindex: 2,
+        block_height: 0,
+        burn_header_hash: BurnchainHeaderHash([0u8; 32]),
+    };
+    let user_burn = UserBurnSupportOp {
+        address: to_addr(&StacksPrivateKey::new()),
+        consensus_hash: consensus_hash.clone(),
+        public_key: VRFPublicKey::from_private(&VRFPrivateKey::new()),
+        key_block_ptr: 1,
+        key_vtxindex: 0,
+        block_header_hash_160: Hash160([4u8; 20]),
+        burn_fee: 100,
+        txid: Txid([5u8; 32]),
+        vtxindex: 3,
+        block_height: 0,
+        burn_header_hash: BurnchainHeaderHash([0u8; 32]),
+    };
+    let key_registers = vec![
+        make_leader_key_register(&consensus_hash),
+        make_leader_key_register(&consensus_hash),
+    ];
+
+    tip.state_transition.accepted_ops = vec![
+        key_registers[0].clone(),
+        BlockstackOperationType::LeaderBlockCommit(block_commit.clone()),
+        key_registers[1].clone(),
+        BlockstackOperationType::UserBurnSupport(user_burn.clone()),
+    ];
+
+    let expected_key_registers: Vec<_> = key_registers
+        .iter()
+        .map(|op| match op {
+            BlockstackOperationType::LeaderKeyRegister(op) => op,
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(tip.leader_key_registers(), expected_key_registers);
+    assert_eq!(tip.block_commits(), vec![&block_commit]);
+    assert_eq!(tip.user_burns(), vec![&user_burn]);
+
+    tip.state_transition.accepted_ops = vec![];
+    assert!(tip.leader_key_registers().is_empty());
+    assert!(tip.block_commits().is_empty());
+    assert!(tip.user_burns().is_empty());
+}
+
+#[test]
+fn test_burn_height_for_consensus_hash() {
+    let conf = new_test_conf();
+    let mut burnchain_controller = MocknetController::generic(conf);
+    burnchain_controller.start(None).unwrap();
+
+    let (tip, _) = burnchain_controller.sync(None).unwrap();
+    let consensus_hash = tip.block_snapshot.consensus_hash.clone();
+    let height = tip.block_snapshot.block_height;
+    burnchain_controller.sync(None).unwrap();
+
+    assert_eq!(
+        burnchain_controller.burn_height_for_consensus_hash(&consensus_hash),
+        Some(height)
+    );
+    assert_eq!(
+        burnchain_controller.burn_height_for_consensus_hash(&ConsensusHash([0xff; 20])),
+        None
+    );
+}
+
+#[test]
+fn test_miner_pauses_while_behind_burnchain() {
+    let mut conf = new_test_conf();
+    conf.miner.wait_for_sync = true;
+    conf.miner.max_sync_lag = 1;
+    let mut sync_gate = MiningSyncGate::new(&conf.miner);
+
+    let mut burnchain_controller = MocknetController::generic(conf);
+    let (mut tip, _) = burnchain_controller.start(None).unwrap();
+    let mut op_signer = BurnchainOpSigner::new(StacksPrivateKey::new(), false);
+
+    // the burnchain tip runs ahead of the processed sortitions, and then they catch up
+    for lag in [3u64, 2, 0].iter() {
+        let burn_block_height = tip.block_snapshot.block_height;
+        if sync_gate.check(burn_block_height, burn_block_height + lag) {
+            let op = make_leader_key_register(&tip.block_snapshot.consensus_hash);
+            assert!(burnchain_controller.submit_operation(op, &mut op_signer));
+        }
+
+        tip = burnchain_controller.sync(None).unwrap().0;
+        if *lag > 1 {
+            assert!(sync_gate.is_paused());
+            assert!(tip.state_transition.accepted_ops.is_empty());
+        } else {
+            assert!(!sync_gate.is_paused());
+            assert_eq!(tip.state_transition.accepted_ops.len(), 1);
+        }
+    }
+
+    // off by default
+    let mut sync_gate = MiningSyncGate::new(&MinerConfig {
+        wait_for_sync: false,
+        max_sync_lag: 1,
+        disable_microblocks: false,
+        burn_fee_escalation: None,
+        must_include: vec![],
+    });
+    assert!(sync_gate.check(1, 100));
+}
+
+#[test]
+fn test_burn_fee_escalation() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [burnchain]
+        burn_fee_cap = 5000
+
+        [miner]
+        burn_fee_escalation = [1000, 2000, 4000]
+        "#,
+    );
+    let conf = Config::from_config_file(config_file);
+    assert_eq!(conf.miner.burn_fee_escalation, Some(vec![1000, 2000, 4000]));
+
+    let mut escalation = BurnFeeEscalation::new(conf.burnchain.burn_fee_cap, &conf.miner);
+    assert_eq!(escalation.burn_fee(), 1000);
+
+    // two missed sortitions in a row
+    escalation.record_missed();
+    assert_eq!(escalation.burn_fee(), 2000);
+    escalation.record_missed();
+    assert_eq!(escalation.burn_fee(), 4000);
+
+    // the schedule is exhausted
+    escalation.record_missed();
+    assert_eq!(escalation.burn_fee(), 4000);
+
+    // a win starts over
+    escalation.record_won();
+    assert_eq!(escalation.burn_fee(), 1000);
+
+    // without a schedule, always burn the fee cap
+    let conf = Config::from_config_file(ConfigFile::from_str(
+        r#"
+        [burnchain]
+        burn_fee_cap = 5000
+        "#,
+    ));
+    let mut escalation = BurnFeeEscalation::new(conf.burnchain.burn_fee_cap, &conf.miner);
+    escalation.record_missed();
+    escalation.record_missed();
+    assert_eq!(escalation.burn_fee(), 5000);
+
+    // entries above the fee cap burn the fee cap
+    let conf = Config::from_config_file(ConfigFile::from_str(
+        r#"
+        [burnchain]
+        burn_fee_cap = 5000
+
+        [miner]
+        burn_fee_escalation = [1000, 8000]
+        "#,
+    ));
+    let mut escalation = BurnFeeEscalation::new(conf.burnchain.burn_fee_cap, &conf.miner);
+    assert_eq!(escalation.burn_fee(), 1000);
+    escalation.record_missed();
+    assert_eq!(escalation.burn_fee(), 5000);
+    escalation.record_missed();
+    assert_eq!(escalation.burn_fee(), 5000);
+}
+
+#[test]
+fn test_must_include_config() {
+    let sk = StacksPrivateKey::from_hex(
+        "b8d99fd45da58038d630d9855d3ca2466e8e0f89d3894c4724f0efc9ff4b51f001",
+    )
+    .unwrap();
+    let tx_bytes = make_contract_publish(&sk, 0, 0, "pinned", "(define-data-var x int 1)");
+    let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).unwrap();
+
+    let config_file = ConfigFile::from_str(&format!(
+        r#"
+        [miner]
+        must_include = ["{}"]
+        "#,
+        to_hex(&tx_bytes)
+    ));
+    let conf = Config::from_config_file(config_file);
+    assert_eq!(conf.miner.must_include, vec![tx]);
+
+    let conf = Config::from_config_file(ConfigFile::from_str(""));
+    assert!(conf.miner.must_include.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Setting miner.must_include is invalid: 0001 is not a transaction")]
+fn test_invalid_must_include_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [miner]
+        must_include = ["0001"]
+        "#,
+    );
+    Config::from_config_file(config_file);
+}
+
+#[test]
+fn test_regtest_presets_are_isolated() {
+    let conf_1 = Config::from_config_file(ConfigFile::regtest());
+    let conf_2 = Config::from_config_file(ConfigFile::regtest());
+
+    assert_eq!(conf_1.burnchain.mode, "helium");
+    assert_eq!(conf_1.node.chain_id, REGTEST_CHAIN_ID);
+    assert_eq!(conf_2.node.chain_id, REGTEST_CHAIN_ID);
+    assert!(REGTEST_CHAIN_ID != TESTNET_CHAIN_ID);
+
+    let tmp_dir = std::env::temp_dir();
+    assert!(std::path::Path::new(&conf_1.node.working_dir).starts_with(&tmp_dir));
+    assert!(std::path::Path::new(&conf_2.node.working_dir).starts_with(&tmp_dir));
+
+    assert!(conf_1.node.working_dir != conf_2.node.working_dir);
+    assert!(conf_1.node.rpc_bind != conf_2.node.rpc_bind);
+    assert!(conf_1.node.p2p_bind != conf_2.node.p2p_bind);
+    assert!(conf_1.node.rpc_bind != conf_1.node.p2p_bind);
+}
+
+#[test]
+fn test_bind_addrs_config() {
+    let mut config_file = ConfigFile::regtest();
+    config_file.node = Some(NodeConfigFile {
+        rpc_bind: Some("127.0.0.1:30443".to_string()),
+        p2p_bind: Some("[::1]:30444".to_string()),
+        ..NodeConfigFile::default()
+    });
+    let conf = Config::from_config_file(config_file);
+
+    assert_eq!(
+        conf.node.get_bind_addrs().unwrap(),
+        (
+            "[::1]:30444".parse().unwrap(),
+            "127.0.0.1:30443".parse().unwrap()
+        )
+    );
+
+    let mut node_config = conf.node.clone();
+    node_config.p2p_bind = "127.0.0.1".to_string();
+    assert!(node_config
+        .get_bind_addrs()
+        .unwrap_err()
+        .starts_with("Setting node.p2p_bind = \"127.0.0.1\" is not a socket address"));
+}
+
+#[test]
+fn test_privileged_bind_port_config() {
+    let mut node_config = Config::from_config_file(ConfigFile::regtest()).node;
+    node_config.rpc_bind = "0.0.0.0:443".to_string();
+
+    assert_eq!(
+        node_config.parse_bind_addrs(false).unwrap_err(),
+        "Setting node.rpc_bind = \"0.0.0.0:443\" has a privileged port, which only root can bind"
+    );
+    assert_eq!(
+        node_config.parse_bind_addrs(true).unwrap().1,
+        "0.0.0.0:443".parse().unwrap()
+    );
+
+    // port 0 lets the OS pick an unprivileged port
+    node_config.rpc_bind = "0.0.0.0:0".to_string();
+    assert!(node_config.parse_bind_addrs(false).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "Setting node.rpc_bind = \"localhost:rpc\" is not a socket address")]
+fn test_invalid_bind_addr_config() {
+    let mut config_file = ConfigFile::regtest();
+    config_file.node = Some(NodeConfigFile {
+        rpc_bind: Some("localhost:rpc".to_string()),
+        ..NodeConfigFile::default()
+    });
+    Config::from_config_file(config_file);
+}
+
+#[test]
+fn test_module_log_levels_config() {
+    use stacks::util::log;
+
+    let config_file = ConfigFile::from_str(
+        r#"
+        [logging]
+        test_logging_coordinator = "debug"
+        test_logging_net = "warn"
+        "#,
+    );
+    let conf = Config::from_config_file(config_file);
+    assert_eq!(conf.logging.module_levels.len(), 2);
+
+    conf.logging.install();
+
+    let coordinator_level =
+        log::get_module_loglevel("stacks::chainstate::test_logging_coordinator::comm");
+    assert!(coordinator_level <= log::LOG_DEBUG);
+    assert!(coordinator_level > log::LOG_TRACE);
+
+    let net_level = log::get_module_loglevel("stacks::test_logging_net::p2p");
+    assert!(net_level > log::LOG_INFO);
+    assert!(net_level <= log::LOG_WARN);
+
+    // modules without an override use the global level
+    assert_eq!(
+        log::get_module_loglevel("stacks::chainstate::stacks::db"),
+        log::get_loglevel()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Setting logging.net is invalid: Invalid log level 'loud'")]
+fn test_invalid_module_log_level_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [logging]
+        net = "loud"
+        "#,
+    );
+    Config::from_config_file(config_file);
+}
+
+#[test]
+fn test_connection_timeouts_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [connection_options]
+        connect_timeout_secs = 5
+        handshake_timeout_secs = 20
+        peer_idle_timeout_secs = 600
+        heartbeat = 300
+        "#,
+    );
+    let conf = Config::from_config_file(config_file);
+    assert_eq!(conf.connection_options.connect_timeout, 5);
+    assert_eq!(conf.connection_options.handshake_timeout, 20);
+    assert_eq!(conf.connection_options.peer_idle_timeout, 600);
+}
+
+#[test]
+#[should_panic(expected = "Setting connection_options.connect_timeout_secs is invalid: must be nonzero")]
+fn test_zero_connection_timeout_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [connection_options]
+        connect_timeout_secs = 0
+        "#,
+    );
+    Config::from_config_file(config_file);
+}
+
+#[test]
+#[should_panic(
+    expected = "Setting connection_options.peer_idle_timeout_secs is invalid: must be greater than handshake_timeout_secs (30)"
+)]
+fn test_idle_timeout_below_handshake_timeout_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [connection_options]
+        handshake_timeout_secs = 30
+        peer_idle_timeout_secs = 30
+        "#,
+    );
+    Config::from_config_file(config_file);
+}
+
+#[test]
+#[should_panic(
+    expected = "Setting connection_options.peer_idle_timeout_secs is invalid: must be greater than heartbeat (3600)"
+)]
+fn test_idle_timeout_below_heartbeat_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [connection_options]
+        peer_idle_timeout_secs = 600
+        "#,
+    );
+    Config::from_config_file(config_file);
+}
+
+#[test]
+fn test_recoverable_panic_does_not_exit() {
+    // setting up a run loop must not install a process-exiting panic hook --
+    // only the binary's main() does that, via install_crash_handler()
+    let conf = new_test_conf();
+    let _run_loop = RunLoop::new(conf);
+
+    let handle = std::thread::spawn(|| {
+        panic!("recoverable panic");
+    });
+    assert!(handle.join().is_err());
+
+    let result = std::panic::catch_unwind(|| {
+        panic!("recoverable panic");
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_supervised_thread_restarts() {
+    let spawn_stub = |spawns: Arc<AtomicU64>, panics: u64| {
+        move || -> Result<std::thread::JoinHandle<()>, ()> {
+            let spawn_num = spawns.fetch_add(1, Ordering::SeqCst);
+            Ok(std::thread::Builder::new()
+                .name(format!("{}stub", SUPERVISED_THREAD_PREFIX))
+                .spawn(move || {
+                    if spawn_num < panics {
+                        panic!("stub worker panic");
+                    }
+                })
+                .unwrap())
+        }
+    };
+
+    // worker that keeps panicking is restarted p2p_max_restarts times, then the supervisor
+    // gives up
+    let conf = new_test_conf();
+    assert_eq!(conf.node.p2p_max_restarts, 3);
+
+    let spawns = Arc::new(AtomicU64::new(0));
+    let supervisor = supervise_thread(
+        "stub",
+        conf.node.p2p_max_restarts,
+        spawn_stub(spawns.clone(), u64::max_value()),
+    )
+    .unwrap();
+    assert!(supervisor.join().is_err());
+    assert_eq!(spawns.load(Ordering::SeqCst), 4);
+
+    // worker that recovers after a restart is not restarted again once it exits
+    let spawns = Arc::new(AtomicU64::new(0));
+    let supervisor = supervise_thread("stub", 3, spawn_stub(spawns.clone(), 2)).unwrap();
+    assert_eq!(supervisor.join().unwrap(), 2);
+    assert_eq!(spawns.load(Ordering::SeqCst), 3);
+
+    // errors spawning the first worker go to the caller
+    assert!(
+        supervise_thread("stub", 3, || -> Result<std::thread::JoinHandle<()>, ()> {
+            Err(())
+        })
+        .is_err()
+    );
+
+    let config_file = ConfigFile::from_str(
+        r#"
+        [node]
+        p2p_max_restarts = 7
+        "#,
+    );
+    let conf = Config::from_config_file(config_file);
+    assert_eq!(conf.node.p2p_max_restarts, 7);
+}
+
+#[test]
+fn test_ustx_balance_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [[ustx_balance]]
+        address = "ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH"
+        amount = 100000000
+
+        [[ustx_balance]]
+        address = "ST26FVX16539KKXZKJN098Q08HRX3XBAP541MFS0P"
+        amount = 200000000
+        "#,
+    );
+    let conf = Config::from_config_file(config_file);
+    assert_eq!(conf.initial_balances.len(), 2);
+
+    let initial_balances = conf
+        .initial_balances
+        .iter()
+        .map(|e| (e.address.clone(), e.amount))
+        .collect();
+    let (mut chainstate, _) = StacksChainState::open_and_exec(
+        false,
+        TESTNET_CHAIN_ID,
+        &conf.get_chainstate_path(),
+        Some(initial_balances),
+        |_| {},
+        conf.block_limit.clone(),
+    )
+    .unwrap();
+
+    let genesis_block_id = StacksBlockHeader::make_index_block_hash(
+        &FIRST_BURNCHAIN_CONSENSUS_HASH,
+        &FIRST_STACKS_BLOCK_HASH,
+    );
+    for (address, amount) in [
+        ("ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH", 100000000),
+        ("ST26FVX16539KKXZKJN098Q08HRX3XBAP541MFS0P", 200000000),
+    ]
+    .iter()
+    {
+        let principal: PrincipalData = PrincipalData::parse_standard_principal(address)
+            .unwrap()
+            .into();
+        let account =
+            chainstate.with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &genesis_block_id, |conn| {
+                StacksChainState::get_account(conn, &principal)
+            });
+        assert_eq!(account.stx_balance.amount_unlocked, *amount);
+        assert_eq!(account.nonce, 0);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Setting ustx_balance.amount is invalid: must be nonzero")]
+fn test_zero_ustx_balance_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [[ustx_balance]]
+        address = "ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH"
+        amount = 0
+        "#,
+    );
+    Config::from_config_file(config_file);
+}
+
+#[test]
+#[should_panic(expected = "Setting ustx_balance.address is invalid")]
+fn test_bad_ustx_balance_address_config() {
+    let config_file = ConfigFile::from_str(
+        r#"
+        [[ustx_balance]]
+        address = "not-an-address"
+        amount = 100
+        "#,
+    );
+    Config::from_config_file(config_file);
+}
+
+#[test]
+fn test_keychain_rotate_vrf_key_by_window() {
+    let seed = vec![0x01; 32];
+
+    let mut keychain = Keychain::default(seed.clone());
+    keychain.set_vrf_rotation_period(10);
+
+    // same window
+    let key_20 = keychain.rotate_vrf_key(20);
+    let key_29 = keychain.rotate_vrf_key(29);
+    assert_eq!(key_20, key_29);
+
+    // next window
+    let key_30 = keychain.rotate_vrf_key(30);
+    assert!(key_29 != key_30);
+
+    // the derived keys can be used to generate proofs
+    let proof = keychain.generate_proof(&VRFPublicKey::from_private(&key_30), &[0x02; 32]);
+    assert!(proof.is_some());
+
+    // a fresh keychain from the same seed derives the same keys, no matter how many keys
+    // it has already derived
+    let mut restarted_keychain = Keychain::default(seed.clone());
+    restarted_keychain.set_vrf_rotation_period(10);
+    restarted_keychain.rotate_vrf_keypair(30);
+    assert_eq!(restarted_keychain.rotate_vrf_key(35), key_30);
+    assert_eq!(restarted_keychain.rotate_vrf_key(21), key_20);
+
+    // a different seed derives different keys
+    let mut other_keychain = Keychain::default(vec![0x02; 32]);
+    other_keychain.set_vrf_rotation_period(10);
+    assert!(other_keychain.rotate_vrf_key(20) != key_20);
+}
+
+/// Consume a full HTTP request (headers plus Content-Length body) from a test server socket.
+fn read_http_request(sock: &mut std::net::TcpStream) -> Vec<u8> {
+    use std::io::Read;
+
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    let body_len = loop {
+        let nread = sock.read(&mut buf).unwrap();
+        assert!(nread > 0, "client closed the connection early");
+        request.extend_from_slice(&buf[0..nread]);
+
+        let request_str = String::from_utf8_lossy(&request).to_string();
+        if let Some(headers_end) = request_str.find("\r\n\r\n") {
+            let content_length = request_str[0..headers_end]
+                .lines()
+                .find_map(|line| {
+                    let mut parts = line.splitn(2, ':');
+                    match (parts.next(), parts.next()) {
+                        (Some(name), Some(value))
+                            if name.trim().eq_ignore_ascii_case("content-length") =>
+                        {
+                            value.trim().parse::<usize>().ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .unwrap_or(0);
+            break headers_end + 4 + content_length;
+        }
+    };
+    while request.len() < body_len {
+        let nread = sock.read(&mut buf).unwrap();
+        assert!(nread > 0, "client closed the connection early");
+        request.extend_from_slice(&buf[0..nread]);
+    }
+    request
+}
+
+#[test]
+fn test_bitcoind_rpc_error_is_surfaced() {
+    use std::io::Write;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // a fake bitcoind that rejects every call
+    let server = std::thread::spawn(move || {
+        let (mut sock, _) = listener.accept().unwrap();
+        read_http_request(&mut sock);
+
+        let body = r#"{"result":null,"error":{"code":-26,"message":"min relay fee not met"},"id":"stacks"}"#;
+        let response = format!(
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        sock.write_all(response.as_bytes()).unwrap();
+        sock.flush().unwrap();
+    });
+
+    let mut conf = new_test_conf();
+    conf.burnchain.peer_host = "127.0.0.1".to_string();
+    conf.burnchain.rpc_port = port;
+    conf.burnchain.username = None;
+    conf.burnchain.password = None;
+
+    let controller = BitcoinRegtestController::new_dummy(conf);
+    let tx = Transaction {
+        input: vec![],
+        output: vec![],
+        version: 1,
+        lock_time: 0,
+    };
+
+    match controller.send_raw_transaction(tx) {
+        Err(BurnchainControllerError::RPCError(msg)) => {
+            assert_eq!(msg, "min relay fee not met");
+        }
+        Err(e) => panic!("Unexpected error: {:?}", &e),
+        Ok(_) => panic!("Transaction should have been rejected"),
+    }
+
+    server.join().unwrap();
+}
+
+#[test]
+fn test_event_observer_last_acked_seq() {
+    use std::io::Write;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // a fake observer that fails the second payload
+    let server = std::thread::spawn(move || {
+        let mut seqs = vec![];
+        for status in ["200 OK", "500 Internal Server Error", "200 OK"].iter() {
+            let (mut sock, _) = listener.accept().unwrap();
+            let request = String::from_utf8(read_http_request(&mut sock)).unwrap();
+            let seq = request
+                .lines()
+                .find_map(|line| {
+                    let mut parts = line.splitn(2, ':');
+                    match (parts.next(), parts.next()) {
+                        (Some(name), Some(value))
+                            if name.trim().eq_ignore_ascii_case(HEADER_EVENT_SEQUENCE) =>
+                        {
+                            value.trim().parse::<u64>().ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .unwrap();
+            seqs.push(seq);
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            sock.write_all(response.as_bytes()).unwrap();
+            sock.flush().unwrap();
+        }
+        seqs
+    });
+
+    let mut dispatcher = EventDispatcher::new();
+    dispatcher.register_observer(&EventObserverConfig {
+        endpoint: format!("127.0.0.1:{}", port),
+        events_keys: vec![EventKeyType::AnyEvent],
+        max_send_attempts: Some(1),
+        since_height: None,
+    });
+    assert_eq!(dispatcher.last_acked_seq(0), 0);
+
+    dispatcher.process_new_mempool_txs(vec![]);
+    assert_eq!(dispatcher.last_acked_seq(0), 1);
+
+    // the second payload is rejected, and the third is acknowledged, but
+    // the acked sequence only covers contiguously acknowledged payloads
+    dispatcher.process_new_mempool_txs(vec![]);
+    dispatcher.process_new_mempool_txs(vec![]);
+    assert_eq!(dispatcher.last_acked_seq(0), 1);
+
+    assert_eq!(server.join().unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_event_observer_replays_indexed_blocks() {
+    use std::io::Write;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // a fake observer that records the block height and events of each new block payload
+    let server = std::thread::spawn(move || {
+        let mut blocks = vec![];
+        for _ in 0..3 {
+            let (mut sock, _) = listener.accept().unwrap();
+            let request = String::from_utf8(read_http_request(&mut sock)).unwrap();
+            let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+            let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+            blocks.push((
+                payload["block_height"].as_u64().unwrap(),
+                payload["events"].as_array().unwrap().clone(),
+            ));
+
+            sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            sock.flush().unwrap();
+        }
+        blocks
+    });
+
+    let sk = StacksPrivateKey::new();
+    let tx = StacksTransaction::consensus_deserialize(
+        &mut &make_contract_publish(&sk, 0, 0, "foo", "(print 1)")[..],
+    )
+    .unwrap();
+    let contract_id = QualifiedContractIdentifier::new(to_addr(&sk).into(), "foo".into());
+    let other_contract_id = QualifiedContractIdentifier::new(to_addr(&sk).into(), "bar".into());
+
+    let make_chain_tip = |block_height: u64| {
+        let mut chain_tip = ChainTip::genesis(0);
+        chain_tip.metadata.block_height = block_height;
+        chain_tip.receipts.push(StacksTransactionReceipt {
+            transaction: tx.clone(),
+            events: vec![
+                StacksTransactionEvent::SmartContractEvent(SmartContractEventData {
+                    key: (other_contract_id.clone(), "print".to_string()),
+                    value: Value::UInt(block_height as u128),
+                }),
+                StacksTransactionEvent::SmartContractEvent(SmartContractEventData {
+                    key: (contract_id.clone(), "print".to_string()),
+                    value: Value::UInt(block_height as u128),
+                }),
+            ],
+            post_condition_aborted: false,
+            result: Value::okay_true(),
+            stx_burned: 0,
+            contract_analysis: None,
+            execution_cost: ExecutionCost::zero(),
+        });
+        chain_tip
+    };
+
+    let conf = new_test_conf();
+    let mut dispatcher = EventDispatcher::new();
+    dispatcher.set_event_index(EventIndex::new(
+        &format!("{}/event_index", &conf.node.working_dir),
+        2,
+    ));
+
+    // height 2 falls out of retention once height 4 is indexed
+    for block_height in 2..5 {
+        dispatcher.process_chain_tip(&make_chain_tip(block_height), &StacksBlockId([0u8; 32]));
+    }
+
+    dispatcher.register_observer(&EventObserverConfig {
+        endpoint: format!("127.0.0.1:{}", port),
+        events_keys: vec![EventKeyType::SmartContractEvent((
+            contract_id.clone(),
+            "print".to_string(),
+        ))],
+        max_send_attempts: Some(1),
+        since_height: Some(2),
+    });
+    dispatcher.process_chain_tip(&make_chain_tip(5), &StacksBlockId([0u8; 32]));
+
+    let blocks = server.join().unwrap();
+    assert_eq!(
+        blocks.iter().map(|(height, _)| *height).collect::<Vec<_>>(),
+        vec![3, 4, 5]
+    );
+    for (_, events) in blocks.iter() {
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0]["contract_event"]["contract_identifier"],
+            contract_id.to_string()
+        );
+    }
+}
+
+#[test]
+fn test_event_observer_added_and_removed_at_runtime() {
+    use std::io::Write;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // a fake observer that records the block height of the first payload it gets
+    let server = std::thread::spawn(move || {
+        let (mut sock, _) = listener.accept().unwrap();
+        let request = String::from_utf8(read_http_request(&mut sock)).unwrap();
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+
+        sock.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        sock.flush().unwrap();
+        (listener, payload["block_height"].as_u64().unwrap())
+    });
+
+    let make_chain_tip = |block_height: u64| {
+        let mut chain_tip = ChainTip::genesis(0);
+        chain_tip.metadata.block_height = block_height;
+        chain_tip
+    };
+
+    let dispatcher = EventDispatcher::new();
+    dispatcher.process_chain_tip(&make_chain_tip(1), &StacksBlockId([0u8; 32]));
+
+    // observers added through a clone are seen by the original
+    let mut shared_dispatcher = dispatcher.clone();
+    let observer_id = shared_dispatcher
+        .add_observer(format!("127.0.0.1:{}", port), None)
+        .unwrap();
+    dispatcher.process_chain_tip(&make_chain_tip(2), &StacksBlockId([0u8; 32]));
+
+    let (listener, block_height) = server.join().unwrap();
+    assert_eq!(block_height, 2);
+    assert_eq!(dispatcher.last_acked_seq(observer_id), 1);
+
+    shared_dispatcher.remove_observer(observer_id);
+    dispatcher.process_chain_tip(&make_chain_tip(3), &StacksBlockId([0u8; 32]));
+    dispatcher.process_new_mempool_txs(vec![]);
+
+    // nothing else was sent
+    listener.set_nonblocking(true).unwrap();
+    match listener.accept() {
+        Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+        Err(e) => panic!("Unexpected error: {:?}", &e),
+        Ok(_) => panic!("Removed observer was sent a payload"),
+    }
+    assert_eq!(dispatcher.last_acked_seq(observer_id), 0);
+}
+
+#[test]
+fn test_add_observer_rejects_bad_endpoints() {
+    let mut dispatcher = EventDispatcher::new();
+    for endpoint in [
+        "http://127.0.0.1:3700",
+        "127.0.0.1:3700/new_block",
+        "127.0.0.1",
+        "127.0.0.1:",
+        "127.0.0.1:70000",
+        "user@127.0.0.1:3700",
+    ]
+    .iter()
+    {
+        assert!(
+            dispatcher.add_observer(endpoint.to_string(), None).is_err(),
+            "accepted {}",
+            endpoint
+        );
+    }
+
+    // rejected endpoints don't use up observer ids
+    assert_eq!(
+        dispatcher
+            .add_observer("localhost:3700".to_string(), None)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        dispatcher
+            .add_observer("[::1]:3700".to_string(), None)
+            .unwrap(),
+        1
+    );
+}
@@ -8,6 +8,7 @@ use stacks::chainstate::stacks::{
     db::StacksChainState, StacksAddress, StacksBlock, StacksBlockHeader, StacksPrivateKey,
     StacksPublicKey, StacksTransaction,
 };
+use stacks::core::EMPTY_MICROBLOCK_PARENT_HASH;
 use stacks::net::StacksMessageCodec;
 use stacks::util::secp256k1::Secp256k1PublicKey;
 use stacks::vm::costs::ExecutionCost;
@@ -24,7 +25,7 @@ use stacks::net::{AccountEntryResponse, RPCPeerInfoData};
 use stacks::util::hash::bytes_to_hex;
 use stacks::util::hash::Hash160;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::{env, thread};
 
@@ -245,6 +246,79 @@ fn bitcoind_integration_test() {
     channel.stop_chains_coordinator();
 }
 
+#[test]
+#[ignore]
+fn on_new_block_integration_test() {
+    if env::var("BITCOIND_TEST") != Ok("1".into()) {
+        return;
+    }
+
+    let (conf, _miner_account) = neon_integration_test_conf();
+
+    let mut btcd_controller = BitcoinCoreController::new(conf.clone());
+    btcd_controller
+        .start_bitcoind()
+        .map_err(|_e| ())
+        .expect("Failed starting bitcoind");
+
+    let mut btc_regtest_controller = BitcoinRegtestController::new(conf.clone(), None);
+    let http_origin = format!("http://{}", &conf.node.rpc_bind);
+
+    btc_regtest_controller.bootstrap_chain(201);
+
+    eprintln!("Chain bootstrapped...");
+
+    let mut run_loop = neon::RunLoop::new(conf);
+    let blocks_processed = run_loop.get_blocks_processed_arc();
+    let client = reqwest::blocking::Client::new();
+
+    // record the height of each block the callback is invoked with
+    let new_block_heights = Arc::new(Mutex::new(vec![]));
+    let callback_heights = new_block_heights.clone();
+    run_loop.set_on_new_block(Box::new(move |_block, header_info| {
+        callback_heights
+            .lock()
+            .unwrap()
+            .push(header_info.block_height);
+    }));
+
+    let channel = run_loop.get_coordinator_channel().unwrap();
+
+    thread::spawn(move || run_loop.start(0));
+
+    // give the run loop some time to start up!
+    wait_for_runloop(&blocks_processed);
+
+    // first block wakes up the run loop
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+
+    // first block will hold our VRF registration
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+
+    // the next blocks will each have a mined Stacks block
+    for _i in 0..3 {
+        next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+    }
+
+    let path = format!("{}/v2/info", &http_origin);
+    let tip_info = client
+        .get(&path)
+        .send()
+        .unwrap()
+        .json::<RPCPeerInfoData>()
+        .unwrap();
+    assert!(tip_info.stacks_tip_height > 0);
+
+    // the callback fired once per processed block, in order
+    let new_block_heights = new_block_heights.lock().unwrap().clone();
+    assert_eq!(
+        new_block_heights,
+        (1..(tip_info.stacks_tip_height + 1)).collect::<Vec<u64>>()
+    );
+
+    channel.stop_chains_coordinator();
+}
+
 #[test]
 #[ignore]
 fn microblock_integration_test() {
@@ -269,6 +343,8 @@ fn microblock_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        max_send_attempts: None,
+        since_height: None,
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -509,6 +585,136 @@ fn microblock_integration_test() {
     channel.stop_chains_coordinator();
 }
 
+#[test]
+#[ignore]
+fn no_microblocks_integration_test() {
+    if env::var("BITCOIND_TEST") != Ok("1".into()) {
+        return;
+    }
+
+    let spender_sk = StacksPrivateKey::from_hex(SK_1).unwrap();
+    let spender_addr: PrincipalData = to_addr(&spender_sk).into();
+
+    let (mut conf, _miner_account) = neon_integration_test_conf();
+
+    conf.initial_balances.push(InitialBalance {
+        address: spender_addr.clone(),
+        amount: 100300,
+    });
+
+    conf.node.mine_microblocks = true;
+    conf.miner.disable_microblocks = true;
+
+    test_observer::spawn();
+
+    conf.events_observers.push(EventObserverConfig {
+        endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
+        events_keys: vec![EventKeyType::AnyEvent],
+        max_send_attempts: None,
+        since_height: None,
+    });
+
+    let mut btcd_controller = BitcoinCoreController::new(conf.clone());
+    btcd_controller
+        .start_bitcoind()
+        .map_err(|_e| ())
+        .expect("Failed starting bitcoind");
+
+    let mut btc_regtest_controller = BitcoinRegtestController::new(conf.clone(), None);
+    let http_origin = format!("http://{}", &conf.node.rpc_bind);
+
+    btc_regtest_controller.bootstrap_chain(201);
+
+    eprintln!("Chain bootstrapped...");
+
+    let mut run_loop = neon::RunLoop::new(conf.clone());
+    let blocks_processed = run_loop.get_blocks_processed_arc();
+    let client = reqwest::blocking::Client::new();
+
+    let channel = run_loop.get_coordinator_channel().unwrap();
+
+    thread::spawn(move || run_loop.start(0));
+
+    // give the run loop some time to start up!
+    wait_for_runloop(&blocks_processed);
+
+    // first block wakes up the run loop
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+
+    // first block will hold our VRF registration
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+
+    // second block will be the first mined Stacks block
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+
+    // push a transaction that can only be mined in a microblock
+    let recipient = StacksAddress::from_string(ADDR_4).unwrap();
+    let tx = make_stacks_transfer_mblock_only(&spender_sk, 0, 1000, &recipient.into(), 1000);
+
+    let path = format!("{}/v2/transactions", &http_origin);
+    let res: String = client
+        .post(&path)
+        .header("Content-Type", "application/octet-stream")
+        .body(tx.clone())
+        .send()
+        .unwrap()
+        .json()
+        .unwrap();
+
+    assert_eq!(
+        res,
+        StacksTransaction::consensus_deserialize(&mut &tx[..])
+            .unwrap()
+            .txid()
+            .to_string()
+    );
+
+    // mine a few more anchored blocks -- without miner.disable_microblocks, the first of these
+    // would have confirmed a microblock with the above transaction.
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+    next_block_and_wait(&mut btc_regtest_controller, &blocks_processed);
+
+    // anchored blocks are still mined...
+    let path = format!("{}/v2/info", &http_origin);
+    let tip_info = client
+        .get(&path)
+        .send()
+        .unwrap()
+        .json::<RPCPeerInfoData>()
+        .unwrap();
+    assert!(tip_info.stacks_tip_height >= 4);
+
+    // ...but none of them confirm a microblock stream
+    let blocks_observed = test_observer::get_blocks();
+    assert_eq!(blocks_observed.len() as u64, tip_info.stacks_tip_height);
+    for block in blocks_observed.iter() {
+        let parent_microblock = block
+            .get("parent_microblock")
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            parent_microblock,
+            format!("0x{}", &EMPTY_MICROBLOCK_PARENT_HASH)
+        );
+    }
+
+    // so the microblock-only transaction was never mined
+    let path = format!("{}/v2/accounts/{}?proof=0", &http_origin, &spender_addr);
+    let res = client
+        .get(&path)
+        .send()
+        .unwrap()
+        .json::<AccountEntryResponse>()
+        .unwrap();
+    assert_eq!(res.nonce, 0);
+    assert_eq!(u128::from_str_radix(&res.balance[2..], 16).unwrap(), 100300);
+
+    channel.stop_chains_coordinator();
+}
+
 #[test]
 #[ignore]
 fn size_check_integration_test() {